@@ -1,28 +1,76 @@
 use cosmwasm_std::{
-    ensure_eq, to_json_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, Deps, DepsMut, Event,
-    Fraction, MessageInfo, Reply, Response, StdError, SubMsg, SubMsgResponse, Uint128, Validator,
-    WasmMsg,
+    coin, ensure, ensure_eq, to_json_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut,
+    Env, Event, Fraction, IbcChannel, IbcMsg, MessageInfo, Order, Reply, Response, StdError,
+    StdResult, Storage, SubMsg, SubMsgResponse, Timestamp, Uint128, Uint256, Uint64, Validator,
+    WasmMsg, WeightedVoteOption,
 };
-use cw2::set_contract_version;
-use cw_storage_plus::Item;
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bounder, Item, Map};
 use cw_utils::{must_pay, nonpayable, parse_instantiate_response_data};
-use mesh_apis::ibc::ConsumerPacket;
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx};
+use mesh_apis::error::MeshError;
+use mesh_apis::events::{RewardsEvent, StakeEvent, UnstakeEvent};
+use mesh_apis::ibc::{
+    BatchedUnstake, ConsumerPacket, Features, ProviderPacket, StakeChecksum, ValidatorStake,
+    VoteWeight,
+};
+use osmosis_std::types::ibc::applications::transfer::v1::MsgTransferResponse;
+use std::collections::BTreeMap;
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx, ReplyCtx, SudoCtx};
 use sylvia::{contract, schemars};
 
-use mesh_apis::converter_api::{self, ConverterApi, RewardInfo, ValidatorSlashInfo};
+use mesh_apis::converter_api::{
+    self, ConverterApi, ForcedUnbondInfo, RewardInfo, ValidatorSlashInfo, ValidatorUptime,
+};
+use mesh_apis::migration::{Upgrade, OWNERSHIP_VERSION};
+use mesh_apis::ownership_api::{self, Ownership, OwnershipApi};
 use mesh_apis::price_feed_api;
-use mesh_apis::virtual_staking_api;
+use mesh_apis::virtual_staking_api::{self, VirtualStakingInitMsg};
 
+use crate::curve::{self, CurveSegment};
 use crate::error::ContractError;
-use crate::ibc::{make_ibc_packet, valset_update_msg, IBC_CHANNEL};
-use crate::msg::ConfigResponse;
-use crate::state::Config;
+use crate::ibc::{
+    callback_memo, channel_features, open_channels, packet_hash, packet_timeout, provider_contract,
+    rewards_transfer_msg, routed_rewards_memo, valset_update_chunks, valset_update_packet,
+    IbcLifecycleAck, IbcLifecycleTimeout, IBC_CHANNELS, TIMEOUT_POLICY,
+};
+use crate::msg::{
+    ChannelInfo, ChannelStake, ChannelStakesResponse, ChannelsResponse, ConfigResponse,
+    EffectiveWeightResponse, OutboxPacketInfo, OutboxResponse, PendingRewardsResponse,
+    RelayerRewardsResponse, StakeChecksumResponse, StakePerValidatorResponse, StuckRewardsInfo,
+    StuckRewardsResponse, TotalVirtualStakeResponse, UndistributedRewardsResponse,
+    ValidatorVirtualStake,
+};
+use crate::relayer::RelayerIncentives;
+use crate::state::{
+    Config, LegacyConfig, OutboxPacket, OutboxStatus, PendingTransfer, RoutedRewards, StuckRewards,
+};
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const REPLY_ID_INSTANTIATE: u64 = 1;
+const REPLY_ID_TRANSFER: u64 = 2;
+
+pub const DEFAULT_PAGE_LIMIT: u32 = 10;
+pub const MAX_PAGE_LIMIT: u32 = 30;
+
+/// Delay before the first retry of a failed rewards transfer, in seconds.
+/// It doubles with every further failure, see `TIMEOUT_POLICY`.
+pub const RETRY_BASE_DELAY: u64 = 10 * 60;
+/// After that many failed transfers, rewards can only be redirected by governance
+pub const MAX_TRANSFER_FAILURES: u32 = 5;
+
+/// Max number of timed out packets sent again by a single `retry_packets` call
+pub const RETRY_PACKETS_BATCH: usize = 30;
+
+/// Provider channel the test stake methods act on
+#[cfg(any(test, feature = "mt"))]
+pub const TEST_CHANNEL: &str = "channel-0";
+
+/// Aligns pagination limit
+fn clamp_page_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize
+}
 
 #[cfg(not(feature = "fake-custom"))]
 pub mod custom {
@@ -40,12 +88,42 @@ pub mod custom {
 pub struct ConverterContract<'a> {
     pub config: Item<'a, Config>,
     pub virtual_stake: Item<'a, Addr>,
+    /// Virtual staking contract the virtual stake is being moved to, during a migration
+    pub virtual_stake_migration: Item<'a, Addr>,
+    /// Rewards transfer sent in this tx, waiting for its IBC sequence in the reply
+    pub transfer_in_flight: Item<'a, PendingTransfer>,
+    /// Rewards transfers waiting for their ICS-20 ack, indexed by `(channel, sequence)`
+    pub pending_transfers: Map<'a, (&'a str, u64), PendingTransfer>,
+    /// Rewards whose transfer failed, indexed by an increasing id
+    pub stuck_rewards: Map<'a, u64, StuckRewards>,
+    pub stuck_rewards_count: Item<'a, u64>,
+    /// Virtual stake bonded by each provider, in the remote denom, by `(channel, validator)`
+    pub channel_stakes: Map<'a, (&'a str, &'a str), Uint128>,
+    /// Virtual stake requested from the virtual staking contract, in the local denom, by
+    /// validator. Mirrors its bond requests, so they can be reconciled
+    pub virtual_stakes: Map<'a, &'a str, Uint128>,
+    /// Rolling `StakeChecksum` of `channel_stakes`, by channel
+    pub stake_checksums: Map<'a, &'a str, Uint64>,
+    /// Rewards whose distribution failed on the provider, by `(channel, validator)`.
+    /// They are sent again with the next rewards batch
+    pub undistributed_rewards: Map<'a, (&'a str, &'a str), Uint128>,
+    /// Packets sent to the providers, tracked until acked, indexed by an increasing id
+    pub outbox: Map<'a, u64, OutboxPacket>,
+    pub outbox_count: Item<'a, u64>,
+    /// Outbox ids by `(channel, packet hash, id)`, to find packets back from their ack or
+    /// timeout
+    pub outbox_lookup: Map<'a, (&'a str, u64, u64), ()>,
+    /// Votes of the cross-stakers on the governance proposals, by `(proposal id, channel)`
+    pub proposal_tallies: Map<'a, (u64, &'a str), Vec<VoteWeight>>,
+    /// Relayer fees collected from the distributed rewards, and the packets they pay for
+    pub relayer_incentives: RelayerIncentives<'a>,
 }
 
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[sv::error(ContractError)]
 #[sv::messages(converter_api as ConverterApi)]
+#[sv::messages(ownership_api as OwnershipApi)]
 /// Workaround for lack of support in communication `Empty` <-> `Custom` Contracts.
 #[sv::custom(query=custom::ConverterQuery, msg=custom::ConverterMsg)]
 impl ConverterContract<'_> {
@@ -53,9 +131,43 @@ impl ConverterContract<'_> {
         Self {
             config: Item::new("config"),
             virtual_stake: Item::new("virtual_stake"),
+            virtual_stake_migration: Item::new("virtual_stake_migration"),
+            transfer_in_flight: Item::new("transfer_in_flight"),
+            pending_transfers: Map::new("pending_transfers"),
+            stuck_rewards: Map::new("stuck_rewards"),
+            stuck_rewards_count: Item::new("stuck_rewards_count"),
+            channel_stakes: Map::new("channel_stakes"),
+            virtual_stakes: Map::new("virtual_stakes"),
+            stake_checksums: Map::new("stake_checksums"),
+            undistributed_rewards: Map::new("undistributed_rewards"),
+            outbox: Map::new("outbox"),
+            outbox_count: Item::new("outbox_count"),
+            outbox_lookup: Map::new("outbox_lookup"),
+            proposal_tallies: Map::new("proposal_tallies"),
+            relayer_incentives: RelayerIncentives::new(
+                "relayer_pool",
+                "relayer_periods",
+                "relayers",
+            ),
         }
     }
 
+    fn next_stuck_id(&self, store: &mut dyn Storage) -> StdResult<u64> {
+        let id: u64 = self
+            .stuck_rewards_count
+            .may_load(store)?
+            .unwrap_or_default()
+            + 1;
+        self.stuck_rewards_count.save(store, &id)?;
+        Ok(id)
+    }
+
+    fn next_outbox_id(&self, store: &mut dyn Storage) -> StdResult<u64> {
+        let id: u64 = self.outbox_count.may_load(store)?.unwrap_or_default() + 1;
+        self.outbox_count.save(store, &id)?;
+        Ok(id)
+    }
+
     /// We must first instantiate the price feed contract, then the converter contract.
     /// The converter will then instantiate a virtual staking contract to work with it,
     /// as they both need references to each other. The admin of the virtual staking
@@ -63,6 +175,11 @@ impl ConverterContract<'_> {
     ///
     /// Discount is applied to foreign tokens after adjusting foreign/native price,
     /// such that 0.3 discount means foreign assets have 70% of their value
+    ///
+    /// `transfer_channel` is the ICS-20 channel to the provider, used to transfer rewards to
+    /// provider-side recipients
+    ///
+    /// `owner` defaults to the sender, and is also set as the owner of the virtual staking contract
     #[sv::msg(instantiate)]
     pub fn instantiate(
         &self,
@@ -72,6 +189,8 @@ impl ConverterContract<'_> {
         remote_denom: String,
         virtual_staking_code_id: u64,
         admin: Option<String>,
+        transfer_channel: Option<String>,
+        owner: Option<String>,
     ) -> Result<custom::Response, ContractError> {
         nonpayable(&ctx.info)?;
         // validate args
@@ -79,16 +198,30 @@ impl ConverterContract<'_> {
             return Err(ContractError::InvalidDiscount);
         }
         if remote_denom.is_empty() {
-            return Err(ContractError::InvalidDenom(remote_denom));
+            return Err(ContractError::EmptyRemoteDenom);
         }
         let config = Config {
             price_feed: ctx.deps.api.addr_validate(&price_feed)?,
-            price_adjustment: Decimal::one() - discount,
+            curve: vec![CurveSegment {
+                from: Uint128::zero(),
+                adjustment: Decimal::one() - discount,
+            }],
             local_denom: ctx.deps.querier.query_bonded_denom()?,
             remote_denom,
+            transfer_channel,
+            max_external_stake: None,
+            rewards_routing_channel: None,
+            max_valset_batch: None,
+            relayer_fee: None,
         };
         self.config.save(ctx.deps.storage, &config)?;
 
+        let owner = match owner {
+            Some(owner) => ctx.deps.api.addr_validate(&owner)?,
+            None => ctx.info.sender,
+        };
+        ownership_api::initialize_owner(ctx.deps.storage, Some(owner.clone()))?;
+
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
         if let Some(admin) = &admin {
@@ -99,7 +232,9 @@ impl ConverterContract<'_> {
         let init_msg = WasmMsg::Instantiate {
             admin,
             code_id: virtual_staking_code_id,
-            msg: b"{}".into(),
+            msg: to_json_binary(&VirtualStakingInitMsg {
+                owner: Some(owner.into_string()),
+            })?,
             funds: vec![],
             label: format!("Virtual Staking: {}", &config.remote_denom),
         };
@@ -108,6 +243,38 @@ impl ConverterContract<'_> {
         Ok(Response::new().add_submessage(init_msg))
     }
 
+    /// Migrates from an older version of the contract.
+    /// Contracts from before the ownership storage get their wasm admin as owner, and their
+    /// price adjustment turned into a single segment curve.
+    #[sv::msg(migrate)]
+    pub fn migrate(
+        &self,
+        ctx: MigrateCtx<custom::ConverterQuery>,
+    ) -> Result<custom::Response, ContractError> {
+        let stored = get_contract_version(ctx.deps.storage)?;
+        let upgrade = Upgrade::check(
+            &stored.contract,
+            &stored.version,
+            CONTRACT_NAME,
+            CONTRACT_VERSION,
+        )?;
+
+        if upgrade.crosses(OWNERSHIP_VERSION)? {
+            let legacy: Item<LegacyConfig> = Item::new("config");
+            let config: Config = legacy.load(ctx.deps.storage)?.into();
+            self.config.save(ctx.deps.storage, &config)?;
+
+            let admin = ownership_api::wasm_admin(&ctx.deps.querier, &ctx.env.contract.address)?;
+            ownership_api::migrate_owner(ctx.deps.storage, admin)?;
+        }
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        let event = Event::new("migrate")
+            .add_attribute("from_version", stored.version)
+            .add_attribute("to_version", CONTRACT_VERSION);
+        Ok(Response::new().add_event(event))
+    }
+
     #[sv::msg(reply)]
     fn reply(
         &self,
@@ -116,7 +283,8 @@ impl ConverterContract<'_> {
     ) -> Result<custom::Response, ContractError> {
         match reply.id {
             REPLY_ID_INSTANTIATE => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
-            _ => Err(ContractError::InvalidReplyId(reply.id)),
+            REPLY_ID_TRANSFER => self.reply_transfer_callback(ctx.deps, reply.result.unwrap()),
+            _ => Err(MeshError::InvalidReplyId(reply.id).into()),
         }
     }
 
@@ -132,6 +300,494 @@ impl ConverterContract<'_> {
         Ok(Response::new())
     }
 
+    /// Track the rewards transfer by its IBC sequence, until the ibc-hooks callback arrives
+    fn reply_transfer_callback(
+        &self,
+        deps: DepsMut<custom::ConverterQuery>,
+        reply: SubMsgResponse,
+    ) -> Result<custom::Response, ContractError> {
+        let sequence = MsgTransferResponse::try_from(reply.data.unwrap_or_default())?.sequence;
+        let pending = self.transfer_in_flight.load(deps.storage)?;
+        self.transfer_in_flight.remove(deps.storage);
+
+        // The channel is always set if a transfer was sent
+        let channel = self.config.load(deps.storage)?.transfer_channel.unwrap();
+        self.pending_transfers
+            .save(deps.storage, (&channel, sequence), &pending)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "rewards_transfer")
+            .add_attribute("channel", channel)
+            .add_attribute("sequence", sequence.to_string()))
+    }
+
+    /// Sets the max cross-stake accepted on each validator from the providers, in the
+    /// provider-side denom. Providers are informed with the next valset update of each validator.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_max_external_stake(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        max_stake: Option<Uint128>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.max_external_stake = max_stake;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let max_stake = max_stake.map_or_else(|| "none".to_owned(), |max| max.to_string());
+        Ok(Response::new()
+            .add_attribute("action", "set_max_external_stake")
+            .add_attribute("max_stake", max_stake))
+    }
+
+    /// Sets the max number of validator entries per valset update packet, so large updates fit
+    /// in the IBC packet size. `None` goes back to `DEFAULT_VALSET_BATCH`.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_max_valset_batch(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        max_batch: Option<u32>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+        ensure!(max_batch != Some(0), ContractError::InvalidValsetBatch);
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.max_valset_batch = max_batch;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_max_valset_batch")
+            .add_attribute("max_batch", config.max_valset_batch().to_string()))
+    }
+
+    /// Sends the rewards of the provider on `channel_id` with ICS-20 transfers to its contract,
+    /// instead of `Distribute` packets. `None` goes back to the packets.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_rewards_routing(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        channel_id: Option<String>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        if let Some(channel_id) = &channel_id {
+            ensure!(
+                config.transfer_channel.is_some(),
+                ContractError::NoTransferChannel
+            );
+            provider_contract(ctx.deps.storage, channel_id)?;
+        }
+        config.rewards_routing_channel = channel_id.clone();
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_rewards_routing")
+            .add_attribute("channel", channel_id.unwrap_or_else(|| "none".to_owned())))
+    }
+
+    /// Sets the portion of the distributed rewards paid to the relayers delivering the provider
+    /// packets. `None` stops collecting fees; the collected ones can still be claimed.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_relayer_fee(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        fee: Option<Decimal>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+        if let Some(fee) = fee {
+            ensure!(fee < Decimal::one(), ContractError::InvalidRelayerFee);
+        }
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.relayer_fee = fee;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_relayer_fee")
+            .add_attribute(
+                "fee",
+                fee.map_or_else(|| "none".to_owned(), |fee| fee.to_string()),
+            ))
+    }
+
+    /// Sends the sender the relayer fees earned for the provider packets they delivered
+    #[sv::msg(exec)]
+    fn claim_relayer_rewards(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        let config = self.config.load(ctx.deps.storage)?;
+        let amount = self
+            .relayer_incentives
+            .claim(ctx.deps.storage, &ctx.info.sender)?;
+
+        Ok(Response::new()
+            .add_message(BankMsg::Send {
+                to_address: ctx.info.sender.to_string(),
+                amount: vec![coin(amount.u128(), config.local_denom)],
+            })
+            .add_attribute("action", "claim_relayer_rewards")
+            .add_attribute("relayer", ctx.info.sender)
+            .add_attribute("amount", amount.to_string()))
+    }
+
+    /// Replaces the discount curve. Only the owner can call this.
+    ///
+    /// Stakes are bonded and unbonded at the adjustment of the current curve, so the virtual
+    /// stake of the tokens staked before the update is left unchanged until they are unstaked.
+    #[sv::msg(exec)]
+    fn update_curve(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        curve: Vec<CurveSegment>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+        curve::validate(&curve)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.curve = curve;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "update_curve")
+            .add_attribute("segments", config.curve.len().to_string()))
+    }
+
+    /// Retries a failed rewards transfer to the provider, once its backoff delay passed.
+    /// Callable by anyone.
+    #[sv::msg(exec)]
+    fn retry_stuck_rewards(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        id: u64,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let stuck = self
+            .stuck_rewards
+            .may_load(ctx.deps.storage, id)?
+            .ok_or(ContractError::NoStuckRewards(id))?;
+        ensure!(
+            !TIMEOUT_POLICY.retries_exhausted(stuck.failures),
+            ContractError::RetriesExhausted(id)
+        );
+        ensure!(
+            stuck.retry_at <= ctx.env.block.time,
+            ContractError::RetryNotReady(stuck.retry_at)
+        );
+        self.stuck_rewards.remove(ctx.deps.storage, id);
+
+        let msg = self.send_rewards_transfer(
+            ctx.deps.storage,
+            &ctx.env,
+            PendingTransfer {
+                recipient: stuck.recipient,
+                rewards: stuck.rewards,
+                failures: stuck.failures,
+                stuck_id: Some(id),
+                routed: None,
+            },
+        )?;
+
+        Ok(Response::new()
+            .add_submessage(msg)
+            .add_attribute("action", "retry_stuck_rewards")
+            .add_attribute("id", id.to_string()))
+    }
+
+    /// Called by ibc-hooks with the outcome of a rewards transfer.
+    /// Failed or timed out transfers are queued for a retry, with exponential backoff, except
+    /// for the routed rewards, which are credited back to their channel.
+    #[sv::msg(sudo)]
+    fn ibc_lifecycle_complete(
+        &self,
+        ctx: SudoCtx<custom::ConverterQuery>,
+        ibc_ack: Option<IbcLifecycleAck>,
+        ibc_timeout: Option<IbcLifecycleTimeout>,
+    ) -> Result<custom::Response, ContractError> {
+        let (channel, sequence, success) = match (ibc_ack, ibc_timeout) {
+            (Some(ack), _) => (ack.channel, ack.sequence, ack.success),
+            (None, Some(timeout)) => (timeout.channel, timeout.sequence, false),
+            (None, None) => return Ok(Response::new()),
+        };
+
+        // Not a rewards transfer
+        let Some(pending) = self
+            .pending_transfers
+            .may_load(ctx.deps.storage, (&channel, sequence))?
+        else {
+            return Ok(Response::new());
+        };
+        self.pending_transfers
+            .remove(ctx.deps.storage, (&channel, sequence));
+
+        if success {
+            return Ok(Response::new()
+                .add_attribute("action", "rewards_transferred")
+                .add_attribute("recipient", pending.recipient)
+                .add_attribute("amount", pending.rewards.amount.to_string()));
+        }
+
+        // Routed rewards are credited back, to be sent again with the next rewards batch
+        if let Some(routed) = pending.routed {
+            self.credit_rewards(ctx.deps.storage, &routed.channel_id, &routed.rewards)?;
+            return Ok(Response::new()
+                .add_attribute("action", "routed_rewards_failed")
+                .add_attribute("channel", routed.channel_id)
+                .add_attribute("amount", pending.rewards.amount.to_string()));
+        }
+
+        let id = match pending.stuck_id {
+            Some(id) => id,
+            None => self.next_stuck_id(ctx.deps.storage)?,
+        };
+        let failures = pending.failures + 1;
+        let retry_at = TIMEOUT_POLICY.retry_at(ctx.env.block.time, failures);
+        let stuck = StuckRewards {
+            recipient: pending.recipient,
+            rewards: pending.rewards,
+            failures,
+            retry_at,
+        };
+        self.stuck_rewards.save(ctx.deps.storage, id, &stuck)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "rewards_transfer_failed")
+            .add_attribute("id", id.to_string())
+            .add_attribute("failures", failures.to_string())
+            .add_attribute("retry_at", retry_at.seconds().to_string()))
+    }
+
+    /// Sends stuck rewards to a consumer-side `recipient` instead.
+    /// Only possible after `MAX_TRANSFER_FAILURES` failed transfers.
+    #[sv::msg(sudo)]
+    fn redirect_stuck_rewards(
+        &self,
+        ctx: SudoCtx<custom::ConverterQuery>,
+        id: u64,
+        recipient: String,
+    ) -> Result<custom::Response, ContractError> {
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+        let stuck = self
+            .stuck_rewards
+            .may_load(ctx.deps.storage, id)?
+            .ok_or(ContractError::NoStuckRewards(id))?;
+        ensure!(
+            TIMEOUT_POLICY.retries_exhausted(stuck.failures),
+            ContractError::RetriesNotExhausted(id)
+        );
+        self.stuck_rewards.remove(ctx.deps.storage, id);
+
+        let amount = stuck.rewards.amount.to_string();
+        let msg = BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![stuck.rewards],
+        };
+
+        Ok(Response::new()
+            .add_message(msg)
+            .add_attribute("action", "redirect_stuck_rewards")
+            .add_attribute("id", id.to_string())
+            .add_attribute("recipient", recipient)
+            .add_attribute("amount", amount))
+    }
+
+    /// Starts moving the virtual stake to `new_contract`, to upgrade the virtual staking contract
+    /// without a new converter (and IBC handshake). `new_contract` must be instantiated with this
+    /// converter as its converter. Only callable by governance.
+    ///
+    /// All the bonds requested from the current virtual staking contract are unbonded. Until
+    /// `complete_virtual_staking_migration`, stake changes are only tracked by the converter.
+    #[sv::msg(sudo)]
+    fn start_virtual_staking_migration(
+        &self,
+        ctx: SudoCtx<custom::ConverterQuery>,
+        new_contract: String,
+    ) -> Result<custom::Response, ContractError> {
+        ensure!(
+            !self.virtual_stake_migration.exists(ctx.deps.storage),
+            ContractError::VirtualStakingMigrationInProgress
+        );
+        let new_contract = ctx.deps.api.addr_validate(&new_contract)?;
+        let old_contract = self.virtual_stake.load(ctx.deps.storage)?;
+        let denom = self.config.load(ctx.deps.storage)?.local_denom;
+
+        let mut resp = Response::new();
+        for item in self
+            .virtual_stakes
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (validator, amount) = item?;
+            if amount.is_zero() {
+                continue;
+            }
+            let amount = coin(amount.u128(), &denom);
+            let msg = virtual_staking_api::sv::ExecMsg::Unbond { validator, amount };
+            resp = resp.add_message(WasmMsg::Execute {
+                contract_addr: old_contract.to_string(),
+                msg: to_json_binary(&msg)?,
+                funds: vec![],
+            });
+        }
+        self.virtual_stake_migration
+            .save(ctx.deps.storage, &new_contract)?;
+
+        Ok(resp
+            .add_attribute("action", "start_virtual_staking_migration")
+            .add_attribute("old_contract", old_contract)
+            .add_attribute("new_contract", new_contract))
+    }
+
+    /// Completes the migration started by `start_virtual_staking_migration`, once the old
+    /// contract processed the unbonds (i.e. at its next epoch). The new contract becomes the
+    /// virtual staking contract, and all the tracked bonds are requested from it.
+    /// Only callable by governance.
+    #[sv::msg(sudo)]
+    fn complete_virtual_staking_migration(
+        &self,
+        ctx: SudoCtx<custom::ConverterQuery>,
+    ) -> Result<custom::Response, ContractError> {
+        let new_contract = self
+            .virtual_stake_migration
+            .may_load(ctx.deps.storage)?
+            .ok_or(ContractError::NoVirtualStakingMigration)?;
+        self.virtual_stake_migration.remove(ctx.deps.storage);
+        self.virtual_stake.save(ctx.deps.storage, &new_contract)?;
+        let denom = self.config.load(ctx.deps.storage)?.local_denom;
+
+        let mut resp = Response::new();
+        for item in self
+            .virtual_stakes
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (validator, amount) = item?;
+            if amount.is_zero() {
+                continue;
+            }
+            let amount = coin(amount.u128(), &denom);
+            let msg = virtual_staking_api::sv::ExecMsg::Bond { validator, amount };
+            resp = resp.add_message(WasmMsg::Execute {
+                contract_addr: new_contract.to_string(),
+                msg: to_json_binary(&msg)?,
+                funds: vec![],
+            });
+        }
+
+        Ok(resp
+            .add_attribute("action", "complete_virtual_staking_migration")
+            .add_attribute("new_contract", new_contract))
+    }
+
+    /// Sends the timed out outbox packets again, with a fresh timeout. Permissionless, so
+    /// relayer operators can recover the channel traffic after an outage.
+    ///
+    /// At most `RETRY_PACKETS_BATCH` packets are sent, oldest first. Packets of closed channels
+    /// are left in the outbox.
+    #[sv::msg(exec)]
+    fn retry_packets(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut retriable = vec![];
+        for item in self
+            .outbox
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (id, outbox) = item?;
+            if outbox.status == (OutboxStatus::Retriable {})
+                && IBC_CHANNELS.has(ctx.deps.storage, &outbox.channel_id)
+            {
+                retriable.push((id, outbox));
+                if retriable.len() == RETRY_PACKETS_BATCH {
+                    break;
+                }
+            }
+        }
+
+        let mut resp = Response::new()
+            .add_attribute("action", "retry_packets")
+            .add_attribute("retried", retriable.len().to_string());
+        for (id, mut outbox) in retriable {
+            outbox.status = OutboxStatus::InFlight {};
+            outbox.attempts += 1;
+            self.outbox.save(ctx.deps.storage, id, &outbox)?;
+
+            let features = channel_features(ctx.deps.storage, &outbox.channel_id)?;
+            let msg = IbcMsg::SendPacket {
+                channel_id: outbox.channel_id,
+                data: outbox.packet.encode(features)?,
+                timeout: packet_timeout(&ctx.env, &outbox.packet),
+            };
+            resp = resp.add_message(msg).add_event(
+                Event::new("retry_packet")
+                    .add_attribute("id", id.to_string())
+                    .add_attribute("attempts", outbox.attempts.to_string()),
+            );
+        }
+
+        Ok(resp)
+    }
+
+    /// Compares the virtual stake requested on every validator with the bond requests of the
+    /// virtual staking contract, emitting a `mesh-discrepancy` event for each mismatch.
+    /// Permissionless, as it only reports.
+    #[sv::msg(exec)]
+    fn reconcile(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        use sylvia::types::Remote;
+        use virtual_staking_api::sv::Querier;
+        let remote = Remote::<
+            &dyn virtual_staking_api::VirtualStakingApi<
+                Error = StdError,
+                ExecC = custom::ConverterMsg,
+                QueryC = custom::ConverterQuery,
+            >,
+        >::new(self.virtual_stake.load(ctx.deps.storage)?);
+        let querier = remote.querier(&ctx.deps.querier);
+
+        let mut resp = Response::new().add_attribute("action", "reconcile");
+        let mut checked = 0u32;
+        let mut discrepancies = 0u32;
+        for item in self
+            .virtual_stakes
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (validator, expected) = item?;
+            let requested = querier.bond_status(validator.clone())?.requested;
+            checked += 1;
+            if requested != expected {
+                discrepancies += 1;
+                let event = Event::new("mesh-discrepancy")
+                    .add_attribute("validator", validator)
+                    .add_attribute("converter_stake", expected.to_string())
+                    .add_attribute("virtual_staking_stake", requested.to_string());
+                resp = resp.add_event(event);
+            }
+        }
+
+        Ok(resp
+            .add_attribute("validators", checked.to_string())
+            .add_attribute("discrepancies", discrepancies.to_string()))
+    }
+
     /// This is only used for tests.
     /// Ideally we want conditional compilation of these whole methods and the enum variants
     #[sv::msg(exec)]
@@ -144,12 +800,12 @@ impl ConverterContract<'_> {
         #[cfg(any(test, feature = "mt"))]
         {
             // This can only ever be called in tests
-            self.stake(ctx.deps, validator, stake)
+            self.stake(ctx.deps, TEST_CHANNEL, validator, stake)
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, validator, stake);
-            Err(ContractError::Unauthorized)
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -165,12 +821,74 @@ impl ConverterContract<'_> {
         #[cfg(any(test, feature = "mt"))]
         {
             // This can only ever be called in tests
-            self.unstake(ctx.deps, validator, unstake)
+            self.unstake(ctx.deps, TEST_CHANNEL, validator, unstake)
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, validator, unstake);
-            Err(ContractError::Unauthorized)
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Ideally we want conditional compilation of these whole methods and the enum variants
+    #[sv::msg(exec)]
+    fn test_stake_checksum(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        checksum: Uint64,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests
+            self.check_stake_checksum(ctx.deps, TEST_CHANNEL, checksum)
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, checksum);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Ideally we want conditional compilation of these whole methods and the enum variants
+    #[sv::msg(exec)]
+    fn test_vote(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        proposal_id: u64,
+        tally: Vec<VoteWeight>,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests
+            self.vote(ctx.deps, TEST_CHANNEL, proposal_id, tally)
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, proposal_id, tally);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Ideally we want conditional compilation of these whole methods and the enum variants
+    #[sv::msg(exec)]
+    fn test_distribute_error(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        rewards: Vec<RewardInfo>,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests
+            self.credit_rewards(ctx.deps.storage, TEST_CHANNEL, &rewards)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, rewards);
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -186,26 +904,509 @@ impl ConverterContract<'_> {
         #[cfg(any(test, feature = "mt"))]
         {
             // This can only ever be called in tests
-            self.burn(ctx.deps, &validators, burn)
+            self.burn(ctx.deps, TEST_CHANNEL, &validators, burn)
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, validators, burn);
-            Err(ContractError::Unauthorized)
+            Err(MeshError::Unauthorized.into())
         }
     }
 
+    /// This is only used for tests.
+    /// Records the packet in the outbox, as if sent on the test channel
+    #[sv::msg(exec)]
+    fn test_send_packet(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        packet: ConsumerPacket,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests. The IBC message can't be executed
+            self.send_packet(ctx.deps.storage, &ctx.env, TEST_CHANNEL, packet)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Records the valset update in the outbox, chunked as if sent on the test channel
+    #[sv::msg(exec)]
+    fn test_send_valset_update(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        packet: ConsumerPacket,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests. The IBC messages can't be executed
+            self.send_valset_update(ctx.deps.storage, &ctx.env, TEST_CHANNEL, packet)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Sets the features negotiated on the test channel
+    #[sv::msg(exec)]
+    fn test_set_channel_features(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        features: Features,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            crate::ibc::IBC_FEATURES.save(ctx.deps.storage, TEST_CHANNEL, &features)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, features);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Records `channel` as connected, as after the channel handshake
+    #[sv::msg(exec)]
+    fn test_connect_channel(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        channel: IbcChannel,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            IBC_CHANNELS.save(ctx.deps.storage, &channel.endpoint.channel_id, &channel)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, channel);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Tracks the rewards transfer sent with `sequence` on `channel`, as after the transfer reply
+    #[sv::msg(exec)]
+    fn test_track_transfer(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        channel: String,
+        sequence: u64,
+        transfer: PendingTransfer,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            self.pending_transfers
+                .save(ctx.deps.storage, (&channel, sequence), &transfer)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, channel, sequence, transfer);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Collects relayer `fees`, as from a rewards distribution
+    #[sv::msg(exec)]
+    fn test_collect_relayer_fees(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        fees: Uint128,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            self.relayer_incentives.collect(ctx.deps.storage, fees)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, fees);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Ideally we want conditional compilation of these whole methods and the enum variants
+    #[sv::msg(exec)]
+    fn test_packet_ack(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        packet: ConsumerPacket,
+        success: bool,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests
+            let data = packet.encode(channel_features(ctx.deps.storage, TEST_CHANNEL)?)?;
+            self.packet_acked(ctx.deps.storage, TEST_CHANNEL, &data, success)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet, success);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Ideally we want conditional compilation of these whole methods and the enum variants
+    #[sv::msg(exec)]
+    fn test_packet_timeout(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        packet: ConsumerPacket,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests
+            let data = packet.encode(channel_features(ctx.deps.storage, TEST_CHANNEL)?)?;
+            self.packet_timed_out(ctx.deps.storage, TEST_CHANNEL, &data)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// This is only used for tests.
+    /// Applies the provider packet as if received on the test channel. Any error is to be
+    /// relayed back as an error ack
+    #[sv::msg(exec)]
+    fn test_receive_packet(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        packet: ProviderPacket,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            // This can only ever be called in tests, the sender standing for the relayer
+            self.relayer_incentives
+                .record_packet(ctx.deps.storage, &ctx.info.sender)?;
+            let resp = crate::ibc::receive_packet(ctx.deps, ctx.env, TEST_CHANNEL, packet)?;
+            Ok(Response::new()
+                .add_submessages(resp.messages)
+                .add_attributes(resp.attributes)
+                .add_events(resp.events))
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    #[sv::msg(query)]
+    fn config(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+    ) -> Result<ConfigResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let virtual_staking = self.virtual_stake.load(ctx.deps.storage)?.into_string();
+        let migrating_virtual_staking = self
+            .virtual_stake_migration
+            .may_load(ctx.deps.storage)?
+            .map(Addr::into_string);
+        let max_valset_batch = config.max_valset_batch();
+        Ok(ConfigResponse {
+            price_feed: config.price_feed.into_string(),
+            adjustment: config.curve[0].adjustment,
+            curve: config.curve,
+            virtual_staking,
+            migrating_virtual_staking,
+            transfer_channel: config.transfer_channel,
+            max_external_stake: config.max_external_stake,
+            max_valset_batch,
+            rewards_routing_channel: config.rewards_routing_channel,
+            relayer_fee: config.relayer_fee,
+        })
+    }
+
+    /// Relayer fees earned by `relayer`
+    #[sv::msg(query)]
+    fn relayer_rewards(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        relayer: String,
+    ) -> Result<RelayerRewardsResponse, ContractError> {
+        let relayer = ctx.deps.api.addr_validate(&relayer)?;
+        Ok(self
+            .relayer_incentives
+            .rewards(ctx.deps.storage, &relayer)?)
+    }
+
+    /// Virtual stake `amount` (in the remote denom) would be converted into, staked on top of
+    /// `staked` on a validator, at the current price
+    #[sv::msg(query)]
+    fn effective_weight(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        amount: Uint128,
+        staked: Option<Uint128>,
+    ) -> Result<EffectiveWeightResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let adjustment = curve::adjustment(&config.curve, staked.unwrap_or_default(), amount);
+        let virtual_stake = self.normalize_price(
+            ctx.deps,
+            coin(amount.u128(), config.remote_denom),
+            adjustment,
+        )?;
+        Ok(EffectiveWeightResponse {
+            adjustment,
+            virtual_stake,
+        })
+    }
+
+    /// Open channels, one per provider
+    #[sv::msg(query)]
+    fn channels(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+    ) -> Result<ChannelsResponse, ContractError> {
+        let channels = IBC_CHANNELS
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (channel_id, channel) = item?;
+                Ok(ChannelInfo {
+                    features: channel_features(ctx.deps.storage, &channel_id)?,
+                    channel_id,
+                    connection_id: channel.connection_id,
+                })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(ChannelsResponse { channels })
+    }
+
+    /// Virtual stake bonded by the provider on `channel_id`, by validator.
+    /// `start_after` is the last validator included in previous page
+    #[sv::msg(query)]
+    fn channel_stakes(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        channel_id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<ChannelStakesResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+
+        let stakes = self
+            .channel_stakes
+            .prefix(&channel_id)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (validator, stake) = item?;
+                Ok(ChannelStake { validator, stake })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(ChannelStakesResponse { stakes })
+    }
+
+    /// Rewards of `channel_id` whose distribution failed, waiting for the next rewards batch
+    #[sv::msg(query)]
+    fn undistributed_rewards(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        channel_id: String,
+    ) -> Result<UndistributedRewardsResponse, ContractError> {
+        let rewards = self
+            .undistributed_rewards
+            .prefix(&channel_id)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (validator, reward) = item?;
+                Ok(RewardInfo { validator, reward })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(UndistributedRewardsResponse { rewards })
+    }
+
+    /// Checksum of the virtual stake bonded by the provider on `channel_id`, to be compared
+    /// with the one it reports
+    #[sv::msg(query)]
+    fn stake_checksum(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        channel_id: String,
+    ) -> Result<StakeChecksumResponse, ContractError> {
+        let checksum = self
+            .stake_checksums
+            .may_load(ctx.deps.storage, &channel_id)?
+            .unwrap_or_default();
+        Ok(StakeChecksumResponse { checksum })
+    }
+
+    /// Rewards whose transfer to the provider failed, by id.
+    /// `start_after` is the last id included in previous page
+    #[sv::msg(query)]
+    fn stuck_rewards(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<StuckRewardsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.and_then(Bounder::exclusive_bound);
+
+        let rewards = self
+            .stuck_rewards
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (id, stuck) = item?;
+                Ok(StuckRewardsInfo {
+                    id,
+                    recipient: stuck.recipient,
+                    rewards: stuck.rewards,
+                    failures: stuck.failures,
+                    retry_at: stuck.retry_at,
+                })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(StuckRewardsResponse { rewards })
+    }
+
+    /// Packets sent to the providers and not acked yet, by outbox id.
+    /// `start_after` is the last id included in previous page
+    #[sv::msg(query)]
+    fn outbox(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<OutboxResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.and_then(Bounder::exclusive_bound);
+
+        let packets = self
+            .outbox
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (id, outbox) = item?;
+                Ok(OutboxPacketInfo {
+                    id,
+                    channel_id: outbox.channel_id,
+                    packet: outbox.packet,
+                    status: outbox.status,
+                    attempts: outbox.attempts,
+                })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(OutboxResponse { packets })
+    }
+
+    /// Virtual stake bonded by all the providers, and requested from the virtual staking
+    /// contract for it
     #[sv::msg(query)]
-    fn config(
+    fn total_virtual_stake(
         &self,
         ctx: QueryCtx<custom::ConverterQuery>,
-    ) -> Result<ConfigResponse, ContractError> {
+    ) -> Result<TotalVirtualStakeResponse, ContractError> {
         let config = self.config.load(ctx.deps.storage)?;
-        let virtual_staking = self.virtual_stake.load(ctx.deps.storage)?.into_string();
-        Ok(ConfigResponse {
-            price_feed: config.price_feed.into_string(),
-            adjustment: config.price_adjustment,
-            virtual_staking,
+        let stake = self
+            .channel_stakes
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, stake)| stake))
+            .sum::<StdResult<Uint128>>()?;
+        let virtual_stake = self
+            .virtual_stakes
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, stake)| stake))
+            .sum::<StdResult<Uint128>>()?;
+
+        Ok(TotalVirtualStakeResponse {
+            stake: coin(stake.u128(), config.remote_denom),
+            virtual_stake: coin(virtual_stake.u128(), config.local_denom),
+        })
+    }
+
+    /// Virtual stake by validator, over all the providers, mirroring the stakes on the
+    /// provider side. `start_after` is the last validator included in previous page
+    #[sv::msg(query)]
+    fn stake_per_validator(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<StakePerValidatorResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+
+        let channels = IBC_CHANNELS
+            .keys(ctx.deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let stakes = self
+            .virtual_stakes
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (validator, virtual_stake) = item?;
+                let mut stake = Uint128::zero();
+                for channel_id in &channels {
+                    stake += self
+                        .channel_stakes
+                        .may_load(ctx.deps.storage, (channel_id, &validator))?
+                        .unwrap_or_default();
+                }
+                Ok(ValidatorVirtualStake {
+                    validator,
+                    stake,
+                    virtual_stake,
+                })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(StakePerValidatorResponse { stakes })
+    }
+
+    /// Rewards owed to the providers, and not delivered yet
+    #[sv::msg(query)]
+    fn pending_rewards(
+        &self,
+        ctx: QueryCtx<custom::ConverterQuery>,
+    ) -> Result<PendingRewardsResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.local_denom;
+        let undistributed = self
+            .undistributed_rewards
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, reward)| reward))
+            .sum::<StdResult<Uint128>>()?;
+        let in_transfer = self
+            .pending_transfers
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, transfer)| transfer.rewards.amount))
+            .sum::<StdResult<Uint128>>()?;
+        let stuck = self
+            .stuck_rewards
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, stuck)| stuck.rewards.amount))
+            .sum::<StdResult<Uint128>>()?;
+
+        Ok(PendingRewardsResponse {
+            undistributed: coin(undistributed.u128(), &denom),
+            in_transfer: coin(in_transfer.u128(), &denom),
+            stuck: coin(stuck.u128(), denom),
         })
     }
 
@@ -214,23 +1415,57 @@ impl ConverterContract<'_> {
     pub(crate) fn stake(
         &self,
         deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
         validator: String,
         stake: Coin,
     ) -> Result<custom::Response, ContractError> {
-        let amount = self.normalize_price(deps.as_ref(), stake)?;
+        let remote = stake.amount;
+        let staked = self
+            .channel_stakes
+            .may_load(deps.storage, (channel_id, &validator))?
+            .unwrap_or_default();
+        let curve = self.config.load(deps.storage)?.curve;
+        let adjustment = curve::adjustment(&curve, staked, remote);
+        let amount = self.normalize_price(deps.as_ref(), stake, adjustment)?;
+
+        self.save_channel_stake(deps.storage, channel_id, &validator, staked + remote)?;
+        self.virtual_stakes
+            .update::<_, StdError>(deps.storage, &validator, |stake| {
+                Ok(stake.unwrap_or_default() + amount.amount)
+            })?;
 
         let event = Event::new("mesh-bond")
             .add_attribute("validator", &validator)
             .add_attribute("amount", amount.amount.to_string());
+        let stake_event = Event::from(StakeEvent::new(amount.clone()).validator(&validator));
 
         let msg = virtual_staking_api::sv::ExecMsg::Bond { validator, amount };
-        let msg = WasmMsg::Execute {
-            contract_addr: self.virtual_stake.load(deps.storage)?.into(),
-            msg: to_json_binary(&msg)?,
-            funds: vec![],
-        };
+        let msg = self.virtual_staking_msg(deps.storage, to_json_binary(&msg)?)?;
+
+        Ok(Response::new()
+            .add_messages(msg)
+            .add_event(event)
+            .add_event(stake_event))
+    }
 
-        Ok(Response::new().add_message(msg).add_event(event))
+    /// This is called by ibc_packet_receive.
+    /// Bonds on every validator of the batch, in order.
+    pub(crate) fn stake_batch(
+        &self,
+        mut deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
+        stakes: Vec<ValidatorStake>,
+        denom: String,
+    ) -> Result<custom::Response, ContractError> {
+        let mut resp = Response::new();
+        for ValidatorStake { validator, stake } in stakes {
+            let stake = coin(stake.u128(), &denom);
+            let response = self.stake(deps.branch(), channel_id, validator, stake)?;
+            resp = resp
+                .add_submessages(response.messages)
+                .add_events(response.events);
+        }
+        Ok(resp)
     }
 
     /// This is called by ibc_packet_receive.
@@ -238,23 +1473,69 @@ impl ConverterContract<'_> {
     pub(crate) fn unstake(
         &self,
         deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
         validator: String,
         unstake: Coin,
     ) -> Result<custom::Response, ContractError> {
-        let amount = self.normalize_price(deps.as_ref(), unstake)?;
+        let remote = unstake.amount;
+
+        // A provider can only unbond what it bonded itself
+        let stake = self
+            .channel_stakes
+            .may_load(deps.storage, (channel_id, &validator))?
+            .unwrap_or_default();
+        let remaining =
+            stake
+                .checked_sub(remote)
+                .map_err(|_| ContractError::InsufficientChannelStake {
+                    channel_id: channel_id.to_owned(),
+                    validator: validator.clone(),
+                })?;
+        // The tokens unstaked are the last ones staked, on the curve
+        let curve = self.config.load(deps.storage)?.curve;
+        let adjustment = curve::adjustment(&curve, remaining, remote);
+        let amount = self.normalize_price(deps.as_ref(), unstake, adjustment)?;
+        self.save_channel_stake(deps.storage, channel_id, &validator, remaining)?;
+        self.virtual_stakes
+            .update::<_, StdError>(deps.storage, &validator, |stake| {
+                Ok(stake.unwrap_or_default().saturating_sub(amount.amount))
+            })?;
 
         let event = Event::new("mesh-unbond")
             .add_attribute("validator", &validator)
             .add_attribute("amount", amount.amount.to_string());
+        let unstake_event = Event::from(UnstakeEvent::new(amount.clone()).validator(&validator));
 
         let msg = virtual_staking_api::sv::ExecMsg::Unbond { validator, amount };
-        let msg = WasmMsg::Execute {
-            contract_addr: self.virtual_stake.load(deps.storage)?.into(),
-            msg: to_json_binary(&msg)?,
-            funds: vec![],
-        };
+        let msg = self.virtual_staking_msg(deps.storage, to_json_binary(&msg)?)?;
 
-        Ok(Response::new().add_message(msg).add_event(event))
+        Ok(Response::new()
+            .add_messages(msg)
+            .add_event(event)
+            .add_event(unstake_event))
+    }
+
+    /// This is called by ibc_packet_receive.
+    /// Unbonds every unstake of the batch, in order. They are applied all together or not at all
+    pub(crate) fn unstake_batch(
+        &self,
+        mut deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
+        unstakes: Vec<BatchedUnstake>,
+        denom: String,
+    ) -> Result<custom::Response, ContractError> {
+        let mut resp = Response::new();
+        for BatchedUnstake {
+            validator, unstake, ..
+        } in unstakes
+        {
+            let unstake = coin(unstake.u128(), &denom);
+            let response = self.unstake(deps.branch(), channel_id, validator, unstake)?;
+            resp = resp
+                .add_submessages(response.messages)
+                .add_events(response.events);
+        }
+        Ok(resp)
     }
 
     /// This is called by ibc_packet_receive.
@@ -262,10 +1543,39 @@ impl ConverterContract<'_> {
     pub(crate) fn burn(
         &self,
         deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
         validators: &[String],
         burn: Coin,
     ) -> Result<custom::Response, ContractError> {
-        let amount = self.normalize_price(deps.as_ref(), burn)?;
+        let remote = burn.amount;
+        let curve = self.config.load(deps.storage)?.curve;
+        // Weight of the burned tokens, over all the validators
+        let mut weight = Uint256::zero();
+
+        // Burns are split evenly across the validators, leftovers going to the first one
+        if let Some((first, rest)) = validators.split_first() {
+            let share = remote / Uint128::new(validators.len() as u128);
+            let leftover = remote - share * Uint128::new(validators.len() as u128);
+            for (validator, burned) in std::iter::once((first, share + leftover))
+                .chain(rest.iter().map(|validator| (validator, share)))
+            {
+                let stake = self
+                    .channel_stakes
+                    .may_load(deps.storage, (channel_id, validator))?
+                    .unwrap_or_default();
+                let remaining = stake.saturating_sub(burned);
+                weight += curve::weight_atomics(&curve, remaining, burned);
+                self.save_channel_stake(deps.storage, channel_id, validator, remaining)?;
+            }
+        }
+        let adjustment = if weight.is_zero() {
+            curve[0].adjustment
+        } else {
+            let atomics = weight / Uint256::from(remote);
+            Decimal::new(Uint128::try_from(atomics).unwrap_or(Uint128::MAX))
+        };
+        let amount = self.normalize_price(deps.as_ref(), burn, adjustment)?;
+        self.burn_virtual_stakes(deps.storage, validators, amount.amount)?;
 
         let event = Event::new("mesh-burn")
             .add_attribute("validators", validators.join(","))
@@ -275,19 +1585,212 @@ impl ConverterContract<'_> {
             validators: validators.to_vec(),
             amount,
         };
-        let msg = WasmMsg::Execute {
-            contract_addr: self.virtual_stake.load(deps.storage)?.into(),
-            msg: to_json_binary(&msg)?,
-            funds: vec![],
+        let msg = self.virtual_staking_msg(deps.storage, to_json_binary(&msg)?)?;
+
+        Ok(Response::new().add_messages(msg).add_event(event))
+    }
+
+    /// This is called by ibc_packet_receive.
+    /// It is pulled out into a method, so it can also be called by test_stake_checksum for testing
+    pub(crate) fn check_stake_checksum(
+        &self,
+        deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
+        checksum: Uint64,
+    ) -> Result<custom::Response, ContractError> {
+        let local = self
+            .stake_checksums
+            .may_load(deps.storage, channel_id)?
+            .unwrap_or_default();
+
+        let mut resp = Response::new()
+            .add_attribute("action", "check_stake_checksum")
+            .add_attribute("channel_id", channel_id);
+        if local != checksum {
+            // Bookkeeping diverged from the provider, stakes have to be reconciled
+            let event = Event::new("mesh-reconciliation")
+                .add_attribute("channel_id", channel_id)
+                .add_attribute("provider_checksum", checksum.to_string())
+                .add_attribute("consumer_checksum", local.to_string());
+            resp = resp.add_event(event);
+        }
+
+        Ok(resp)
+    }
+
+    /// This is called by ibc_packet_receive.
+    /// It is pulled out into a method, so it can also be called by test_vote for testing
+    ///
+    /// The tally of the provider replaces its previous one on the proposal, and the tallies of
+    /// all the providers are cast together as a single weighted vote of the virtual staking
+    /// contract.
+    pub(crate) fn vote(
+        &self,
+        deps: DepsMut<custom::ConverterQuery>,
+        channel_id: &str,
+        proposal_id: u64,
+        tally: Vec<VoteWeight>,
+    ) -> Result<custom::Response, ContractError> {
+        self.proposal_tallies
+            .save(deps.storage, (proposal_id, channel_id), &tally)?;
+
+        let mut totals: Vec<VoteWeight> = vec![];
+        for item in self.proposal_tallies.prefix(proposal_id).range(
+            deps.storage,
+            None,
+            None,
+            Order::Ascending,
+        ) {
+            let (_, tally) = item?;
+            for VoteWeight { option, weight } in tally {
+                match totals.iter_mut().find(|total| total.option == option) {
+                    Some(total) => total.weight += weight,
+                    None => totals.push(VoteWeight { option, weight }),
+                }
+            }
+        }
+        let total: Uint128 = totals.iter().map(|total| total.weight).sum();
+
+        let mut resp = Response::new()
+            .add_attribute("action", "vote")
+            .add_attribute("channel_id", channel_id)
+            .add_attribute("proposal_id", proposal_id.to_string())
+            .add_attribute("weight", total.to_string());
+        if total.is_zero() {
+            return Ok(resp);
+        }
+
+        // Weights must add up to exactly one, so the rounding leftover goes to the last option
+        let last = totals.len() - 1;
+        let mut remaining = Decimal::one();
+        let mut options = vec![];
+        for (i, VoteWeight { option, weight }) in totals.into_iter().enumerate() {
+            let weight = if i == last {
+                remaining
+            } else {
+                Decimal::from_ratio(weight, total)
+            };
+            remaining -= weight;
+            if !weight.is_zero() {
+                options.push(WeightedVoteOption { option, weight });
+            }
+        }
+        let msg = virtual_staking_api::sv::ExecMsg::Vote {
+            proposal_id,
+            options,
         };
+        resp = resp.add_messages(self.virtual_staking_msg(deps.storage, to_json_binary(&msg)?)?);
+
+        Ok(resp)
+    }
+
+    /// Burns `amount` from `virtual_stakes`, split over `validators` the same way the virtual
+    /// staking contract splits it over its bond requests
+    fn burn_virtual_stakes(
+        &self,
+        storage: &mut dyn Storage,
+        validators: &[String],
+        amount: Uint128,
+    ) -> StdResult<()> {
+        let mut stakes = vec![];
+        for validator in validators {
+            let stake = self
+                .virtual_stakes
+                .may_load(storage, validator)?
+                .unwrap_or_default()
+                .u128();
+            if stake != 0 {
+                stakes.push((validator.clone(), stake));
+            }
+        }
+        if stakes.is_empty() {
+            return Ok(());
+        }
+
+        let (_, burns) = mesh_burn::distribute_burn(&stakes, amount.u128());
+        for (validator, burned) in burns {
+            self.virtual_stakes
+                .update::<_, StdError>(storage, validator, |stake| {
+                    Ok(stake.unwrap_or_default() - Uint128::new(burned))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Saves the provider stake on the validator, updating the channel stake checksum
+    fn save_channel_stake(
+        &self,
+        storage: &mut dyn Storage,
+        channel_id: &str,
+        validator: &str,
+        stake: Uint128,
+    ) -> StdResult<()> {
+        let old = self
+            .channel_stakes
+            .may_load(storage, (channel_id, validator))?
+            .unwrap_or_default();
+        let checksum: StakeChecksum = self
+            .stake_checksums
+            .may_load(storage, channel_id)?
+            .unwrap_or_default()
+            .into();
+        let checksum = checksum.update(validator, old, stake);
+        self.stake_checksums
+            .save(storage, channel_id, &checksum.into())?;
+
+        if stake.is_zero() {
+            self.channel_stakes.remove(storage, (channel_id, validator));
+            Ok(())
+        } else {
+            self.channel_stakes
+                .save(storage, (channel_id, validator), &stake)
+        }
+    }
 
-        Ok(Response::new().add_message(msg).add_event(event))
+    /// Splits `amount` related to `validator` across the open channels, pro rata to the stake
+    /// each provider has on the validator. Leftovers go to the first channel with a share.
+    /// If no provider has stake on the validator, all of it goes to the first channel.
+    fn split_by_channel(
+        &self,
+        storage: &dyn Storage,
+        validator: &str,
+        amount: Uint128,
+    ) -> Result<Vec<(String, Uint128)>, ContractError> {
+        let mut stakes = open_channels(storage)?
+            .into_iter()
+            .map(|channel| {
+                let channel_id = channel.endpoint.channel_id;
+                let stake = self
+                    .channel_stakes
+                    .may_load(storage, (&channel_id, validator))?
+                    .unwrap_or_default();
+                Ok((channel_id, stake))
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let total: Uint128 = stakes.iter().map(|(_, stake)| *stake).sum();
+        if total.is_zero() {
+            return Ok(vec![(stakes.swap_remove(0).0, amount)]);
+        }
+
+        let mut shares: Vec<_> = stakes
+            .into_iter()
+            .filter(|(_, stake)| !stake.is_zero())
+            .map(|(channel_id, stake)| (channel_id, amount.multiply_ratio(stake, total)))
+            .collect();
+        let allocated: Uint128 = shares.iter().map(|(_, share)| *share).sum();
+        shares[0].1 += amount - allocated;
+        shares.retain(|(_, share)| !share.is_zero());
+        Ok(shares)
     }
 
+    /// Converts a remote `amount` into its local virtual stake, `adjustment` being the discount
+    /// curve adjustment of the amount
     fn normalize_price(
         &self,
         deps: Deps<custom::ConverterQuery>,
         amount: Coin,
+        adjustment: Decimal,
     ) -> Result<Coin, ContractError> {
         let config = self.config.load(deps.storage)?;
         ensure_eq!(
@@ -312,7 +1815,7 @@ impl ConverterContract<'_> {
             >,
         >::new(config.price_feed);
         let price = remote.querier(&deps.querier).price()?.native_per_foreign;
-        let converted = (amount.amount * price) * config.price_adjustment;
+        let converted = (amount.amount * price) * adjustment;
 
         Ok(Coin {
             denom: config.local_denom,
@@ -320,10 +1823,13 @@ impl ConverterContract<'_> {
         })
     }
 
+    /// Converts a local `amount` back into the remote denom, `adjustment` being the discount
+    /// curve adjustment of the amount
     fn invert_price(
         &self,
         deps: Deps<custom::ConverterQuery>,
         amount: Coin,
+        adjustment: Decimal,
     ) -> Result<Coin, ContractError> {
         let config = self.config.load(deps.storage)?;
         ensure_eq!(
@@ -349,10 +1855,7 @@ impl ConverterContract<'_> {
         >::new(config.price_feed);
         let price = remote.querier(&deps.querier).price()?.native_per_foreign;
         let converted = (amount.amount * price.inv().ok_or(ContractError::InvalidPrice {})?)
-            * config
-                .price_adjustment
-                .inv()
-                .ok_or(ContractError::InvalidDiscount {})?;
+            * adjustment.inv().ok_or(ContractError::InvalidDiscount {})?;
 
         Ok(Coin {
             denom: config.remote_denom,
@@ -360,15 +1863,42 @@ impl ConverterContract<'_> {
         })
     }
 
+    /// Average adjustment of the virtual stake on `validator`, over all the providers.
+    /// The base adjustment if there's no stake.
+    fn validator_adjustment(
+        &self,
+        storage: &dyn Storage,
+        validator: &str,
+    ) -> Result<Decimal, ContractError> {
+        let curve = self.config.load(storage)?.curve;
+        let mut total = Uint128::zero();
+        let mut weight = Uint256::zero();
+        for channel_id in IBC_CHANNELS.keys(storage, None, None, Order::Ascending) {
+            let stake = self
+                .channel_stakes
+                .may_load(storage, (&channel_id?, validator))?
+                .unwrap_or_default();
+            total += stake;
+            weight += curve::weight_atomics(&curve, Uint128::zero(), stake);
+        }
+        if total.is_zero() {
+            return Ok(curve[0].adjustment);
+        }
+        let atomics = weight / Uint256::from(total);
+        Ok(Decimal::new(
+            Uint128::try_from(atomics).unwrap_or(Uint128::MAX),
+        ))
+    }
+
+    /// Consumer-side recipients are paid directly. Any other recipient is paid on the provider,
+    /// through the ICS-20 transfer channel, if configured.
     pub(crate) fn transfer_rewards(
         &self,
-        deps: Deps<custom::ConverterQuery>,
+        deps: DepsMut<custom::ConverterQuery>,
+        env: &Env,
         recipient: String,
         rewards: Coin,
-    ) -> Result<CosmosMsg<custom::ConverterMsg>, ContractError> {
-        // ensure the address is proper
-        let recipient = deps.api.addr_validate(&recipient)?;
-
+    ) -> Result<SubMsg<custom::ConverterMsg>, ContractError> {
         // ensure this is the reward denom (same as staking denom)
         let config = self.config.load(deps.storage)?;
         ensure_eq!(
@@ -380,12 +1910,257 @@ impl ConverterContract<'_> {
             }
         );
 
+        let recipient = match (deps.api.addr_validate(&recipient), config.transfer_channel) {
+            (Ok(recipient), _) => recipient,
+            (Err(_), Some(_)) => {
+                return self.send_rewards_transfer(
+                    deps.storage,
+                    env,
+                    PendingTransfer {
+                        recipient,
+                        rewards,
+                        failures: 0,
+                        stuck_id: None,
+                        routed: None,
+                    },
+                )
+            }
+            (Err(err), None) => return Err(err.into()),
+        };
+
         // send the coins
         let msg = BankMsg::Send {
             to_address: recipient.into(),
             amount: vec![rewards],
         };
-        Ok(msg.into())
+        Ok(SubMsg::new(msg))
+    }
+
+    /// Sends the rewards over the ICS-20 transfer channel, tracking them until acked
+    fn send_rewards_transfer(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        pending: PendingTransfer,
+    ) -> Result<SubMsg<custom::ConverterMsg>, ContractError> {
+        let channel = self
+            .config
+            .load(storage)?
+            .transfer_channel
+            .ok_or(ContractError::NoTransferChannel)?;
+        let memo = match &pending.routed {
+            Some(routed) => routed_rewards_memo(env, &pending.recipient, &routed.rewards)?,
+            None => callback_memo(env),
+        };
+        let msg = rewards_transfer_msg(env, &channel, &pending.recipient, &pending.rewards, memo);
+        self.transfer_in_flight.save(storage, &pending)?;
+        Ok(SubMsg::reply_on_success(msg, REPLY_ID_TRANSFER))
+    }
+
+    /// Sends the `rewards` of the provider on `channel_id` to its contract, with an ICS-20
+    /// transfer instead of a distribution packet
+    fn send_routed_rewards(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        channel_id: &str,
+        rewards: Vec<RewardInfo>,
+        denom: &str,
+    ) -> Result<SubMsg<custom::ConverterMsg>, ContractError> {
+        let total: Uint128 = rewards.iter().map(|reward_info| reward_info.reward).sum();
+        let pending = PendingTransfer {
+            recipient: provider_contract(storage, channel_id)?,
+            rewards: coin(total.u128(), denom),
+            failures: 0,
+            stuck_id: None,
+            routed: Some(RoutedRewards {
+                channel_id: channel_id.to_owned(),
+                rewards,
+            }),
+        };
+        self.send_rewards_transfer(storage, env, pending)
+    }
+
+    /// Sends `packet` to the provider on `channel_id`, tracking it in the outbox until acked
+    pub(crate) fn send_packet(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        channel_id: &str,
+        packet: ConsumerPacket,
+    ) -> Result<IbcMsg, ContractError> {
+        let data = packet.encode(channel_features(storage, channel_id)?)?;
+        let id = self.next_outbox_id(storage)?;
+        self.outbox_lookup
+            .save(storage, (channel_id, packet_hash(&data), id), &())?;
+
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel_id.to_owned(),
+            data,
+            timeout: packet_timeout(env, &packet),
+        };
+        let outbox = OutboxPacket {
+            channel_id: channel_id.to_owned(),
+            packet,
+            status: OutboxStatus::InFlight {},
+            attempts: 1,
+        };
+        self.outbox.save(storage, id, &outbox)?;
+        Ok(msg)
+    }
+
+    /// Sends a valset update to the provider on `channel_id`, in chunks of at most
+    /// `max_valset_batch` validator entries. Chunks carry sequence hints if the channel
+    /// supports them. Uptimes are dropped on channels not supporting them
+    pub(crate) fn send_valset_update(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        channel_id: &str,
+        mut packet: ConsumerPacket,
+    ) -> Result<Vec<IbcMsg>, ContractError> {
+        let max_batch = self.config.load(storage)?.max_valset_batch();
+        let features = channel_features(storage, channel_id)?;
+        let hints = features.contains(Features::VALSET_CHUNKS);
+        if let ConsumerPacket::ValsetUpdate {
+            additions,
+            removals,
+            updated,
+            jailed,
+            unjailed,
+            tombstoned,
+            slashed,
+            uptimes,
+            ..
+        } = &mut packet
+        {
+            if !uptimes.is_empty() && !features.contains(Features::VALIDATOR_HEALTH) {
+                uptimes.clear();
+                // Nothing left for this provider
+                if additions.is_empty()
+                    && removals.is_empty()
+                    && updated.is_empty()
+                    && jailed.is_empty()
+                    && unjailed.is_empty()
+                    && tombstoned.is_empty()
+                    && slashed.is_empty()
+                {
+                    return Ok(vec![]);
+                }
+            }
+        }
+        valset_update_chunks(packet, max_batch, hints)
+            .into_iter()
+            .map(|chunk| self.send_packet(storage, env, channel_id, chunk))
+            .collect()
+    }
+
+    /// Finds the in-flight outbox packet sent on `channel_id` with `data`, if any.
+    /// The oldest one is returned if the same packet is in flight more than once
+    fn in_flight_packet(
+        &self,
+        storage: &dyn Storage,
+        channel_id: &str,
+        data: &Binary,
+    ) -> Result<Option<(u64, OutboxPacket)>, ContractError> {
+        let ids = self
+            .outbox_lookup
+            .prefix((channel_id, packet_hash(data)))
+            .keys(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for id in ids {
+            let outbox = self.outbox.load(storage, id)?;
+            if outbox.status == (OutboxStatus::InFlight {})
+                && ConsumerPacket::decode(data)? == outbox.packet
+            {
+                return Ok(Some((id, outbox)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Removes an acked packet from the outbox, returning its id. Acks of packets not in flight
+    /// are ignored, so the outcome of a packet is only ever applied once.
+    /// Rewards whose distribution failed are credited back.
+    pub(crate) fn packet_acked(
+        &self,
+        storage: &mut dyn Storage,
+        channel_id: &str,
+        data: &Binary,
+        success: bool,
+    ) -> Result<Option<u64>, ContractError> {
+        let Some((id, outbox)) = self.in_flight_packet(storage, channel_id, data)? else {
+            return Ok(None);
+        };
+        self.outbox.remove(storage, id);
+        self.outbox_lookup
+            .remove(storage, (channel_id, packet_hash(data), id));
+
+        if !success {
+            let rewards = match outbox.packet {
+                ConsumerPacket::Distribute { validator, rewards } => vec![RewardInfo {
+                    validator,
+                    reward: rewards.amount,
+                }],
+                ConsumerPacket::DistributeBatch { rewards, .. } => rewards,
+                _ => vec![],
+            };
+            self.credit_rewards(storage, channel_id, &rewards)?;
+        }
+        Ok(Some(id))
+    }
+
+    /// Marks a timed out packet as retriable, returning its id. Timeouts of packets not in
+    /// flight are ignored
+    pub(crate) fn packet_timed_out(
+        &self,
+        storage: &mut dyn Storage,
+        channel_id: &str,
+        data: &Binary,
+    ) -> Result<Option<u64>, ContractError> {
+        let Some((id, mut outbox)) = self.in_flight_packet(storage, channel_id, data)? else {
+            return Ok(None);
+        };
+        outbox.status = OutboxStatus::Retriable {};
+        self.outbox.save(storage, id, &outbox)?;
+        Ok(Some(id))
+    }
+
+    /// Credits back rewards whose distribution failed on `channel_id`, so they are sent again
+    /// with the next rewards batch. The rewards tokens never left this contract.
+    pub(crate) fn credit_rewards(
+        &self,
+        storage: &mut dyn Storage,
+        channel_id: &str,
+        rewards: &[RewardInfo],
+    ) -> StdResult<()> {
+        for reward_info in rewards {
+            self.undistributed_rewards.update(
+                storage,
+                (channel_id, &reward_info.validator),
+                |old| -> StdResult<_> { Ok(old.unwrap_or_default() + reward_info.reward) },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Executes `msg` on the virtual staking contract. Nothing is sent during a virtual staking
+    /// migration, as the stakes tracked in `virtual_stakes` are replayed into the new contract
+    /// once it completes.
+    fn virtual_staking_msg(
+        &self,
+        storage: &dyn Storage,
+        msg: Binary,
+    ) -> StdResult<Option<WasmMsg>> {
+        if self.virtual_stake_migration.exists(storage) {
+            return Ok(None);
+        }
+        let msg = WasmMsg::Execute {
+            contract_addr: self.virtual_stake.load(storage)?.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(Some(msg))
     }
 
     fn ensure_authorized(
@@ -394,7 +2169,7 @@ impl ConverterContract<'_> {
         info: &MessageInfo,
     ) -> Result<(), ContractError> {
         let virtual_stake = self.virtual_stake.load(deps.storage)?;
-        ensure_eq!(info.sender, virtual_stake, ContractError::Unauthorized {});
+        ensure_eq!(info.sender, virtual_stake, MeshError::Unauthorized);
 
         Ok(())
     }
@@ -417,14 +2192,51 @@ impl ConverterApi for ConverterContract<'_> {
         let config = self.config.load(ctx.deps.storage)?;
         let denom = config.local_denom;
         must_pay(&ctx.info, &denom)?;
-        let rewards = ctx.info.funds.remove(0);
+        let mut rewards = ctx.info.funds.remove(0);
 
         let event = Event::new("distribute_reward")
             .add_attribute("validator", &validator)
             .add_attribute("amount", rewards.amount.to_string());
+        let rewards_event = Event::from(RewardsEvent::new(rewards.clone()).validator(&validator));
+
+        // The relayers' portion stays here, to be claimed
+        if let Some(fee) = config.relayer_fee {
+            let relayer_fee = rewards.amount * fee;
+            self.relayer_incentives
+                .collect(ctx.deps.storage, relayer_fee)?;
+            rewards.amount -= relayer_fee;
+        }
 
-        let msg = make_ibc_packet(&mut ctx, ConsumerPacket::Distribute { validator, rewards })?;
-        Ok(Response::new().add_message(msg).add_event(event))
+        // Each provider gets its share of the validator rewards
+        let mut msgs = vec![];
+        for (channel_id, amount) in
+            self.split_by_channel(ctx.deps.storage, &validator, rewards.amount)?
+        {
+            if config.rewards_routing_channel.as_ref() == Some(&channel_id) {
+                let rewards = vec![RewardInfo {
+                    validator: validator.clone(),
+                    reward: amount,
+                }];
+                msgs.push(self.send_routed_rewards(
+                    ctx.deps.storage,
+                    &ctx.env,
+                    &channel_id,
+                    rewards,
+                    &denom,
+                )?);
+            } else {
+                let packet = ConsumerPacket::Distribute {
+                    validator: validator.clone(),
+                    rewards: coin(amount.u128(), &denom),
+                };
+                let msg = self.send_packet(ctx.deps.storage, &ctx.env, &channel_id, packet)?;
+                msgs.push(SubMsg::new(msg));
+            }
+        }
+        Ok(Response::new()
+            .add_submessages(msgs)
+            .add_event(event)
+            .add_event(rewards_event))
     }
 
     /// This is a batch form of distribute_reward, including the payment for multiple validators.
@@ -434,8 +2246,8 @@ impl ConverterApi for ConverterContract<'_> {
     /// in the native staking denom.
     fn distribute_rewards(
         &self,
-        mut ctx: ExecCtx<custom::ConverterQuery>,
-        payments: Vec<RewardInfo>,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        mut payments: Vec<RewardInfo>,
     ) -> Result<custom::Response, Self::Error> {
         self.ensure_authorized(&ctx.deps, &ctx.info)?;
 
@@ -452,19 +2264,104 @@ impl ConverterApi for ConverterContract<'_> {
             });
         }
 
-        Ok(Response::new()
+        let resp = Response::new()
             .add_events(payments.iter().map(|reward_info| {
                 Event::new("distribute_reward")
                     .add_attribute("validator", &reward_info.validator)
                     .add_attribute("amount", reward_info.reward)
             }))
-            .add_message(make_ibc_packet(
-                &mut ctx,
-                ConsumerPacket::DistributeBatch {
-                    rewards: payments,
-                    denom,
-                },
-            )?))
+            .add_events(payments.iter().map(|reward_info| {
+                Event::from(
+                    RewardsEvent::new(coin(reward_info.reward.u128(), &denom))
+                        .validator(&reward_info.validator),
+                )
+            }));
+
+        // The relayers' portion stays here, to be claimed
+        if let Some(fee) = config.relayer_fee {
+            let mut relayer_fees = Uint128::zero();
+            for reward_info in payments.iter_mut() {
+                let relayer_fee = reward_info.reward * fee;
+                reward_info.reward -= relayer_fee;
+                relayer_fees += relayer_fee;
+            }
+            self.relayer_incentives
+                .collect(ctx.deps.storage, relayer_fees)?;
+        }
+
+        // Each provider gets its share of the validator rewards
+        let mut rewards_by_channel: BTreeMap<String, Vec<RewardInfo>> = BTreeMap::new();
+        for reward_info in payments {
+            let shares = self.split_by_channel(
+                ctx.deps.storage,
+                &reward_info.validator,
+                reward_info.reward,
+            )?;
+            for (channel_id, reward) in shares {
+                rewards_by_channel
+                    .entry(channel_id)
+                    .or_default()
+                    .push(RewardInfo {
+                        validator: reward_info.validator.clone(),
+                        reward,
+                    });
+            }
+        }
+
+        // Rewards which failed to be distributed before are sent again, to the channels still open
+        let undistributed = self
+            .undistributed_rewards
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for ((channel_id, validator), reward) in undistributed {
+            if !IBC_CHANNELS.has(ctx.deps.storage, &channel_id) {
+                continue;
+            }
+            self.undistributed_rewards
+                .remove(ctx.deps.storage, (&channel_id, &validator));
+            let rewards = rewards_by_channel.entry(channel_id).or_default();
+            match rewards.iter_mut().find(|r| r.validator == validator) {
+                Some(reward_info) => reward_info.reward += reward,
+                None => rewards.push(RewardInfo { validator, reward }),
+            }
+        }
+
+        let mut msgs = vec![];
+        for (channel_id, rewards) in rewards_by_channel {
+            if config.rewards_routing_channel.as_ref() == Some(&channel_id) {
+                msgs.push(self.send_routed_rewards(
+                    ctx.deps.storage,
+                    &ctx.env,
+                    &channel_id,
+                    rewards,
+                    &denom,
+                )?);
+                continue;
+            }
+            let packets = if channel_features(ctx.deps.storage, &channel_id)?
+                .contains(Features::BATCH_REWARDS)
+            {
+                vec![ConsumerPacket::DistributeBatch {
+                    rewards,
+                    denom: denom.clone(),
+                }]
+            } else {
+                // Counterparties not supporting batches get one packet per validator
+                rewards
+                    .into_iter()
+                    .map(|reward_info| ConsumerPacket::Distribute {
+                        validator: reward_info.validator,
+                        rewards: coin(reward_info.reward.u128(), &denom),
+                    })
+                    .collect()
+            };
+            for packet in packets {
+                let msg = self.send_packet(ctx.deps.storage, &ctx.env, &channel_id, packet)?;
+                msgs.push(SubMsg::new(msg));
+            }
+        }
+
+        Ok(resp.add_submessages(msgs))
     }
 
     /// Valset updates.
@@ -482,11 +2379,12 @@ impl ConverterApi for ConverterContract<'_> {
         unjailed: Vec<String>,
         tombstoned: Vec<String>,
         mut slashed: Vec<ValidatorSlashInfo>,
+        uptimes: Vec<ValidatorUptime>,
     ) -> Result<custom::Response, Self::Error> {
         self.ensure_authorized(&ctx.deps, &ctx.info)?;
 
-        // Send over IBC to the Consumer
-        let channel = IBC_CHANNEL.load(ctx.deps.storage)?;
+        // Send over IBC to every provider
+        let channels = open_channels(ctx.deps.storage)?;
 
         let mut event = Event::new("valset_update");
         let mut is_empty = true;
@@ -560,12 +2458,21 @@ impl ConverterApi for ConverterContract<'_> {
                     .collect::<Vec<String>>()
                     .join(","),
             );
+            // The virtual staking contract deducts the slashed amounts from its bond requests
+            for v in &slashed {
+                if let Some(stake) = self.virtual_stakes.may_load(ctx.deps.storage, &v.address)? {
+                    let stake = stake.saturating_sub(v.slash_amount.amount);
+                    self.virtual_stakes
+                        .save(ctx.deps.storage, &v.address, &stake)?;
+                }
+            }
             // Convert slash amounts to Provider's coin
             slashed
                 .iter_mut()
                 .map(|v| {
+                    let adjustment = self.validator_adjustment(ctx.deps.storage, &v.address)?;
                     v.slash_amount =
-                        self.invert_price(ctx.deps.as_ref(), v.slash_amount.clone())?;
+                        self.invert_price(ctx.deps.as_ref(), v.slash_amount.clone(), adjustment)?;
                     Ok(v)
                 })
                 .collect::<Result<Vec<_>, ContractError>>()?;
@@ -585,22 +2492,180 @@ impl ConverterApi for ConverterContract<'_> {
             );
             is_empty = false;
         }
+        if !uptimes.is_empty() {
+            event = event.add_attribute(
+                "uptimes",
+                uptimes
+                    .iter()
+                    .map(|v| format!("{}:{}", v.address, v.uptime()))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            );
+            is_empty = false;
+        }
         let mut resp = Response::new();
         if !is_empty {
-            let valset_msg = valset_update_msg(
-                &ctx.env,
-                &channel,
-                &additions,
-                &removals,
-                &updated,
-                &jailed,
-                &unjailed,
-                &tombstoned,
-                &slashed,
-            )?;
-            resp = resp.add_message(valset_msg);
+            let max_external_stake = self.config.load(ctx.deps.storage)?.max_external_stake;
+            for channel in &channels {
+                let packet = valset_update_packet(
+                    &ctx.env,
+                    max_external_stake,
+                    &additions,
+                    &removals,
+                    &updated,
+                    &jailed,
+                    &unjailed,
+                    &tombstoned,
+                    &slashed,
+                    &uptimes,
+                );
+                let msgs = self.send_valset_update(
+                    ctx.deps.storage,
+                    &ctx.env,
+                    &channel.endpoint.channel_id,
+                    packet,
+                )?;
+                resp = resp.add_messages(msgs);
+            }
         }
         resp = resp.add_event(event);
         Ok(resp)
     }
+
+    /// Forced unbondings due to a max cap reduction.
+    ///
+    /// Amounts are converted to the Provider's coin and reported to the external staking
+    /// contract on the Provider via IBC.
+    fn max_cap_update(
+        &self,
+        ctx: ExecCtx<custom::ConverterQuery>,
+        max_cap: Coin,
+        mut unbonds: Vec<ForcedUnbondInfo>,
+    ) -> Result<custom::Response, Self::Error> {
+        self.ensure_authorized(&ctx.deps, &ctx.info)?;
+
+        let event = Event::new("max_cap_update")
+            .add_attribute("max_cap", max_cap.to_string())
+            .add_attribute(
+                "validators",
+                unbonds
+                    .iter()
+                    .map(|u| u.validator.clone())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            )
+            .add_attribute(
+                "amounts",
+                unbonds
+                    .iter()
+                    .map(|u| u.amount.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            );
+
+        // Convert amounts to Provider's coin. The max cap is converted at the base adjustment,
+        // not being specific to a validator
+        let base_adjustment = self.config.load(ctx.deps.storage)?.curve[0].adjustment;
+        let max_cap = self.invert_price(ctx.deps.as_ref(), max_cap, base_adjustment)?;
+        for unbond in unbonds.iter_mut() {
+            let adjustment = self.validator_adjustment(ctx.deps.storage, &unbond.validator)?;
+            unbond.amount =
+                self.invert_price(ctx.deps.as_ref(), unbond.amount.clone(), adjustment)?;
+        }
+
+        // Informational only, not sent to counterparties that don't know about it
+        let mut channels = vec![];
+        for channel_id in IBC_CHANNELS.keys(ctx.deps.storage, None, None, Order::Ascending) {
+            let channel_id = channel_id?;
+            if channel_features(ctx.deps.storage, &channel_id)?.contains(Features::MAX_CAP_UPDATE) {
+                channels.push(channel_id);
+            }
+        }
+        if channels.is_empty() {
+            return Ok(Response::new().add_event(event));
+        }
+
+        // Each provider gets its share of the unbonds, and the new max cap
+        let mut unbonds_by_channel: BTreeMap<String, Vec<ForcedUnbondInfo>> = BTreeMap::new();
+        for unbond in unbonds {
+            let shares =
+                self.split_by_channel(ctx.deps.storage, &unbond.validator, unbond.amount.amount)?;
+            for (channel_id, amount) in shares {
+                unbonds_by_channel
+                    .entry(channel_id)
+                    .or_default()
+                    .push(ForcedUnbondInfo {
+                        validator: unbond.validator.clone(),
+                        amount: coin(amount.u128(), &unbond.amount.denom),
+                    });
+            }
+        }
+        let msgs = channels
+            .into_iter()
+            .map(|channel_id| {
+                let packet = ConsumerPacket::MaxCapUpdate {
+                    max_cap: max_cap.clone(),
+                    unbonds: unbonds_by_channel.remove(&channel_id).unwrap_or_default(),
+                };
+                self.send_packet(ctx.deps.storage, &ctx.env, &channel_id, packet)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Response::new().add_messages(msgs).add_event(event))
+    }
+}
+
+impl OwnershipApi for ConverterContract<'_> {
+    type Error = ContractError;
+    type ExecC = custom::ConverterMsg;
+    type QueryC = custom::ConverterQuery;
+
+    fn propose_owner(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        new_owner: String,
+        expiry: Option<Timestamp>,
+    ) -> Result<custom::Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+        let ownership = ownership_api::propose_owner(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            new_owner,
+            expiry,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "propose_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn accept_owner(&self, ctx: ExecCtx<Self::QueryC>) -> Result<custom::Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership =
+            ownership_api::accept_owner(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "accept_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn renounce_owner(&self, ctx: ExecCtx<Self::QueryC>) -> Result<custom::Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership = ownership_api::renounce_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "renounce_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn ownership(&self, ctx: QueryCtx<Self::QueryC>) -> Result<Ownership, Self::Error> {
+        Ok(ownership_api::get_ownership(ctx.deps.storage)?)
+    }
 }
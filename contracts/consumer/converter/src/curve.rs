@@ -0,0 +1,81 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal, Uint128, Uint256};
+
+use crate::error::ContractError;
+
+/// Segment of the discount curve, converting cross-stake amounts into voting weight.
+///
+/// Every (remote) token staked on a validator between `from` and the start of the next segment
+/// weighs `adjustment`. The weight of a stake is thus a piecewise-linear function of the amount
+/// staked, and later tokens can be discounted more than the first ones.
+#[cw_serde]
+pub struct CurveSegment {
+    /// Stake, in the remote denom, from which the segment applies
+    pub from: Uint128,
+    /// Weight of every token staked in the segment, i.e. `1.0 - discount`
+    pub adjustment: Decimal,
+}
+
+/// Fails unless the curve starts at zero, its segments are ordered, and their adjustments are
+/// positive, at most one, and non-increasing
+pub fn validate(curve: &[CurveSegment]) -> Result<(), ContractError> {
+    let Some(first) = curve.first() else {
+        return Err(ContractError::InvalidCurve("no segments".to_owned()));
+    };
+    if !first.from.is_zero() {
+        return Err(ContractError::InvalidCurve(
+            "first segment must start at zero".to_owned(),
+        ));
+    }
+    if first.adjustment.is_zero() || first.adjustment > Decimal::one() {
+        return Err(ContractError::InvalidDiscount);
+    }
+    for pair in curve.windows(2) {
+        let (prev, next) = (&pair[0], &pair[1]);
+        if next.from <= prev.from {
+            return Err(ContractError::InvalidCurve(
+                "segments must be ordered by amount".to_owned(),
+            ));
+        }
+        if next.adjustment.is_zero() {
+            return Err(ContractError::InvalidDiscount);
+        }
+        if next.adjustment > prev.adjustment {
+            return Err(ContractError::InvalidCurve(
+                "discount can't decrease with the amount".to_owned(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Weight of `amount` staked on top of `staked`, in `Decimal` atomics
+pub fn weight_atomics(curve: &[CurveSegment], staked: Uint128, amount: Uint128) -> Uint256 {
+    let start = staked;
+    let end = staked.saturating_add(amount);
+    curve
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            let segment_end = curve.get(i + 1).map_or(Uint128::MAX, |next| next.from);
+            let len = end.min(segment_end).saturating_sub(start.max(segment.from));
+            Uint256::from(segment.adjustment.atomics()) * Uint256::from(len)
+        })
+        .sum()
+}
+
+/// Average adjustment of `amount` staked on top of `staked`.
+/// Exactly the segment adjustment if the whole amount falls in a single segment.
+pub fn adjustment(curve: &[CurveSegment], staked: Uint128, amount: Uint128) -> Decimal {
+    if amount.is_zero() {
+        // Marginal adjustment at `staked`
+        return curve
+            .iter()
+            .rev()
+            .find(|segment| segment.from <= staked)
+            .map_or(Decimal::one(), |segment| segment.adjustment);
+    }
+    let atomics = weight_atomics(curve, staked, amount) / Uint256::from(amount);
+    // At most the first segment adjustment, so it fits
+    Decimal::new(Uint128::try_from(atomics).unwrap_or(Uint128::MAX))
+}
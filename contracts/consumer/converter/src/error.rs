@@ -1,6 +1,8 @@
-use cosmwasm_std::{StdError, Uint128};
+use cosmwasm_std::{StdError, Timestamp, Uint128};
 use cw_utils::{ParseReplyError, PaymentError};
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::VersionError;
+use mesh_apis::ownership_api::OwnershipError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -17,11 +19,14 @@ pub enum ContractError {
     #[error("{0}")]
     ParseReply(#[from] ParseReplyError),
 
-    #[error("Unauthorized")]
-    Unauthorized,
+    #[error("{0}")]
+    Ownership(OwnershipError),
+
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
-    #[error("Contract already has an open IBC channel")]
-    IbcChannelAlreadyOpen,
+    #[error("Contract already has an open IBC channel to this provider: {0}")]
+    IbcChannelAlreadyOpen(String),
 
     #[error("You must start the channel handshake on this side, it doesn't support OpenTry")]
     IbcOpenTryDisallowed,
@@ -29,18 +34,70 @@ pub enum ContractError {
     #[error("Sent wrong denom over IBC: {sent}, expected {expected}")]
     WrongDenom { sent: String, expected: String },
 
-    #[error("Invalid reply id: {0}")]
-    InvalidReplyId(u64),
-
     #[error("Invalid price, must be greater than 0.0")]
     InvalidPrice,
 
     #[error("Invalid discount, must be greater or equal than 0.0 and less than 1.0")]
     InvalidDiscount,
 
-    #[error("Invalid denom: {0}")]
-    InvalidDenom(String),
+    #[error("Invalid discount curve: {0}")]
+    InvalidCurve(String),
+
+    #[error("Remote denom can't be empty")]
+    EmptyRemoteDenom,
+
+    #[error("No ICS-20 transfer channel configured")]
+    NoTransferChannel,
+
+    #[error("Valset update batches must hold at least one validator")]
+    InvalidValsetBatch,
+
+    #[error("Unknown IBC channel: {0}")]
+    UnknownChannel(String),
+
+    #[error("Counterparty port {0} is not a contract port")]
+    NotContractPort(String),
+
+    #[error("No stuck rewards with id {0}")]
+    NoStuckRewards(u64),
+
+    #[error("Rewards transfer can't be retried before {0}")]
+    RetryNotReady(Timestamp),
+
+    #[error("Rewards transfer {0} failed too many times, it can only be redirected by governance")]
+    RetriesExhausted(u64),
+
+    #[error("Rewards transfer {0} can still be retried")]
+    RetriesNotExhausted(u64),
+
+    #[error("Not enough stake on {validator} through channel {channel_id}")]
+    InsufficientChannelStake {
+        channel_id: String,
+        validator: String,
+    },
+
+    #[error("A virtual staking migration is already in progress")]
+    VirtualStakingMigrationInProgress,
+
+    #[error("No virtual staking migration in progress")]
+    NoVirtualStakingMigration,
+
+    #[error("Relayer fee must be less than one")]
+    InvalidRelayerFee,
+
+    #[error("No relayer rewards to claim")]
+    NoRelayerRewards,
 
     #[error("Sum of rewards ({sum}) doesn't match funds sent ({sent})")]
     DistributeRewardsInvalidAmount { sum: Uint128, sent: Uint128 },
 }
+
+impl From<OwnershipError> for ContractError {
+    fn from(err: OwnershipError) -> Self {
+        match err {
+            // Unwrapped, so non owners get the same error as any other unauthorized caller
+            OwnershipError::Mesh(err) => ContractError::Mesh(err),
+            err => ContractError::Ownership(err),
+        }
+    }
+}
@@ -1,51 +1,149 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 
+use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{
-    from_json, to_json_binary, DepsMut, Env, Event, Ibc3ChannelOpenResponse, IbcBasicResponse,
-    IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
-    IbcChannelOpenResponse, IbcMsg, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
-    IbcReceiveResponse, IbcTimeout, Validator,
+    from_json, to_json_string, Coin, CosmosMsg, DepsMut, Env, Event, Ibc3ChannelOpenResponse,
+    IbcBasicResponse, IbcChannel, IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg,
+    IbcChannelOpenResponse, IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg,
+    IbcReceiveResponse, IbcTimeout, Order, StdResult, Storage, Uint128, Validator,
 };
-use cw_storage_plus::Item;
+use cw_storage_plus::Map;
+use osmosis_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use osmosis_std::types::ibc::applications::transfer::v1::MsgTransfer;
 
-use mesh_apis::converter_api::ValidatorSlashInfo;
+use mesh_apis::converter_api::{RewardInfo, ValidatorSlashInfo, ValidatorUptime};
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::{
-    ack_success, validate_channel_order, AckWrapper, AddValidator, ConsumerPacket, ProtocolVersion,
-    ProviderPacket, StakeAck, TransferRewardsAck, UnstakeAck, PROTOCOL_NAME,
+    ack_success, validate_channel_order, AckWrapper, AddValidator, ConsumerPacket, Features,
+    ProtocolVersion, ProviderPacket, RewardsTransferHook, RewardsTransferMemo, RewardsTransferMsg,
+    StakeAck, StakeChecksumAck, TimeoutPolicy, TransferRewardsAck, UnstakeAck, ValsetChunk,
+    VoteAck, PROTOCOL_NAME,
 };
-use sylvia::types::ExecCtx;
 
 use crate::{
-    contract::{custom, ConverterContract},
+    contract::{custom, ConverterContract, MAX_TRANSFER_FAILURES, RETRY_BASE_DELAY},
     error::ContractError,
 };
 
 /// This is the maximum version of the Mesh Security protocol that we support
-const SUPPORTED_IBC_PROTOCOL_VERSION: &str = "0.11.0";
+const SUPPORTED_IBC_PROTOCOL_VERSION: &str = "0.12.0";
 /// This is the minimum version that we are compatible with
 const MIN_IBC_PROTOCOL_VERSION: &str = "0.11.0";
+/// Optional protocol features we support
+pub const SUPPORTED_FEATURES: Features = Features::BATCH_REWARDS
+    .union(Features::BATCH_STAKE)
+    .union(Features::MAX_CAP_UPDATE)
+    .union(Features::STAKE_CHECKSUM)
+    .union(Features::VERSIONED_PACKETS)
+    .union(Features::REMOTE_GOVERNANCE)
+    .union(Features::VALSET_CHUNKS)
+    .union(Features::VALIDATOR_HEALTH)
+    .union(Features::BATCH_UNSTAKE);
 
 // IBC specific state
-pub const IBC_CHANNEL: Item<IbcChannel> = Item::new("ibc_channel");
-
-// Let those validator syncs take a day...
-const DEFAULT_VALIDATOR_TIMEOUT: u64 = 24 * 60 * 60;
-// But reward messages should go faster or timeout
-const DEFAULT_REWARD_TIMEOUT: u64 = 60 * 60;
-
-pub fn packet_timeout_validator(env: &Env) -> IbcTimeout {
-    // No idea about their block time, but 24 hours ahead of our view of the clock
-    // should be decently in the future.
-    let timeout = env.block.time.plus_seconds(DEFAULT_VALIDATOR_TIMEOUT);
-    IbcTimeout::with_timestamp(timeout)
+/// Open channels, one per provider chain, by (local) channel id
+pub const IBC_CHANNELS: Map<&str, IbcChannel> = Map::new("ibc_channels");
+/// Features negotiated on each channel, by (local) channel id
+pub const IBC_FEATURES: Map<&str, Features> = Map::new("ibc_features");
+
+/// Let those validator syncs take a day, but the rewards packets and transfers should go faster
+/// or time out. No idea about the provider block time, but an hour ahead of our view of the clock
+/// should be decently in the future.
+/// Failed rewards transfers are retried after `RETRY_BASE_DELAY`, doubling with every further
+/// failure, until `MAX_TRANSFER_FAILURES`.
+pub const TIMEOUT_POLICY: TimeoutPolicy = TimeoutPolicy::new(60 * 60)
+    .with_overrides(&[("valset_update", 24 * 60 * 60)])
+    .with_backoff(RETRY_BASE_DELAY, 24 * 60 * 60)
+    .with_max_failures(MAX_TRANSFER_FAILURES);
+
+/// Packet type of the ICS-20 rewards transfers, for their timeout
+const TRANSFER_PACKET_TYPE: &str = "transfer";
+
+/// Ack of an ICS-20 transfer, as reported by the ibc-hooks callback
+#[cw_serde]
+pub struct IbcLifecycleAck {
+    pub channel: String,
+    pub sequence: u64,
+    pub ack: String,
+    pub success: bool,
 }
 
-pub fn packet_timeout_rewards(env: &Env) -> IbcTimeout {
-    // No idea about their block time, but 1 hour ahead of our view of the clock
-    // should be decently in the future.
-    let timeout = env.block.time.plus_seconds(DEFAULT_REWARD_TIMEOUT);
-    IbcTimeout::with_timestamp(timeout)
+/// Timeout of an ICS-20 transfer, as reported by the ibc-hooks callback
+#[cw_serde]
+pub struct IbcLifecycleTimeout {
+    pub channel: String,
+    pub sequence: u64,
+}
+
+/// ICS-20 transfer of rewards to the provider chain.
+/// The memo should request an ibc-hooks callback to this contract, so failed transfers can be
+/// retried.
+pub(crate) fn rewards_transfer_msg<T>(
+    env: &Env,
+    channel: &str,
+    recipient: &str,
+    rewards: &Coin,
+    memo: String,
+) -> CosmosMsg<T> {
+    let timeout = TIMEOUT_POLICY.timeout_at(env.block.time, TRANSFER_PACKET_TYPE);
+    let msg = MsgTransfer {
+        source_port: "transfer".to_string(),
+        source_channel: channel.to_string(),
+        token: Some(ProtoCoin {
+            denom: rewards.denom.clone(),
+            amount: rewards.amount.to_string(),
+        }),
+        sender: env.contract.address.to_string(),
+        receiver: recipient.to_string(),
+        timeout_height: None,
+        timeout_timestamp: timeout.nanos(),
+        memo,
+    };
+    CosmosMsg::Stargate {
+        type_url: MsgTransfer::TYPE_URL.to_string(),
+        value: msg.into(),
+    }
+}
+
+/// Memo of the transfers to provider-side recipients, only requesting the ibc-hooks callback
+pub(crate) fn callback_memo(env: &Env) -> String {
+    format!(r#"{{"ibc_callback":"{}"}}"#, env.contract.address)
+}
+
+/// Memo of the routed rewards transfers. ibc-hooks executes `ReceiveRewardsTransfer` on the
+/// provider `contract` with the transferred tokens, and calls back this contract
+pub(crate) fn routed_rewards_memo(
+    env: &Env,
+    contract: &str,
+    rewards: &[RewardInfo],
+) -> StdResult<String> {
+    let memo = RewardsTransferMemo {
+        wasm: RewardsTransferHook {
+            contract: contract.to_owned(),
+            msg: RewardsTransferMsg::ReceiveRewardsTransfer {
+                rewards: rewards.to_vec(),
+            },
+        },
+        ibc_callback: env.contract.address.to_string(),
+    };
+    to_json_string(&memo)
+}
+
+/// Address of the provider contract on the other end of `channel_id`, from its `wasm.<address>`
+/// port
+pub(crate) fn provider_contract(
+    storage: &dyn Storage,
+    channel_id: &str,
+) -> Result<String, ContractError> {
+    let channel = IBC_CHANNELS
+        .may_load(storage, channel_id)?
+        .ok_or_else(|| ContractError::UnknownChannel(channel_id.to_owned()))?;
+    let port = channel.counterparty_endpoint.port_id;
+    match port.strip_prefix("wasm.") {
+        Some(contract) => Ok(contract.to_owned()),
+        None => Err(ContractError::NotContractPort(port)),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -55,15 +153,13 @@ pub fn ibc_channel_open(
     _env: Env,
     msg: IbcChannelOpenMsg,
 ) -> Result<IbcChannelOpenResponse, ContractError> {
-    // ensure we have no channel yet
-    if IBC_CHANNEL.may_load(deps.storage)?.is_some() {
-        return Err(ContractError::IbcChannelAlreadyOpen);
-    }
     // ensure we are called with OpenInit
     let channel = match msg {
         IbcChannelOpenMsg::OpenInit { channel } => channel,
         IbcChannelOpenMsg::OpenTry { .. } => return Err(ContractError::IbcOpenTryDisallowed),
     };
+    // ensure we have no channel to this provider yet
+    ensure_new_connection(deps.storage, &channel)?;
 
     // verify the ordering is correct
     validate_channel_order(&channel.order)?;
@@ -71,14 +167,13 @@ pub fn ibc_channel_open(
     // Check the version. If provided, ensure it is compatible.
     // If not provided, use our most recent version.
     let version = if channel.version.is_empty() {
-        ProtocolVersion {
-            protocol: PROTOCOL_NAME.to_string(),
-            version: SUPPORTED_IBC_PROTOCOL_VERSION.to_string(),
-        }
+        ProtocolVersion::new(PROTOCOL_NAME, SUPPORTED_IBC_PROTOCOL_VERSION)
+            .with_features(SUPPORTED_FEATURES)
     } else {
         let v: ProtocolVersion = from_json(channel.version.as_bytes())?;
         // if we can build a response to this, then it is compatible. And we use the highest version there
         v.build_response(SUPPORTED_IBC_PROTOCOL_VERSION, MIN_IBC_PROTOCOL_VERSION)?
+            .with_features(v.features.intersection(SUPPORTED_FEATURES))
     };
 
     let response = Ibc3ChannelOpenResponse {
@@ -94,10 +189,6 @@ pub fn ibc_channel_connect(
     env: Env,
     msg: IbcChannelConnectMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    // ensure we have no channel yet
-    if IBC_CHANNEL.may_load(deps.storage)?.is_some() {
-        return Err(ContractError::IbcChannelAlreadyOpen);
-    }
     // ensure we are called with OpenAck
     let (channel, counterparty_version) = match msg {
         IbcChannelConnectMsg::OpenAck {
@@ -108,26 +199,76 @@ pub fn ibc_channel_connect(
             return Err(ContractError::IbcOpenTryDisallowed)
         }
     };
+    // ensure we have no channel to this provider yet
+    ensure_new_connection(deps.storage, &channel)?;
 
     // Ensure the counterparty responded with a version we support.
     // Note: here, we error if it is higher than what we proposed originally
     let v: ProtocolVersion = from_json(counterparty_version.as_bytes())?;
     v.verify_compatibility(SUPPORTED_IBC_PROTOCOL_VERSION, MIN_IBC_PROTOCOL_VERSION)?;
 
-    // store the channel
-    IBC_CHANNEL.save(deps.storage, &channel)?;
+    // store the channel, and the features enabled on both sides
+    let channel_id = &channel.endpoint.channel_id;
+    IBC_CHANNELS.save(deps.storage, channel_id, &channel)?;
+    IBC_FEATURES.save(
+        deps.storage,
+        channel_id,
+        &v.features.intersection(SUPPORTED_FEATURES),
+    )?;
 
     // Send a validator sync packet to arrive with the newly established channel
     let validators = deps.querier.query_all_validators()?;
-    let msg = valset_update_msg(&env, &channel, &validators, &[], &[], &[], &[], &[], &[])?;
+    let contract = ConverterContract::new();
+    let max_external_stake = contract.config.load(deps.storage)?.max_external_stake;
+    let packet = valset_update_packet(
+        &env,
+        max_external_stake,
+        &validators,
+        &[],
+        &[],
+        &[],
+        &[],
+        &[],
+        &[],
+        &[],
+    );
+    let msgs = contract.send_valset_update(deps.storage, &env, channel_id, packet)?;
+
+    Ok(IbcBasicResponse::new()
+        .add_messages(msgs)
+        .add_attribute("action", "ibc_connect")
+        .add_attribute("channel_id", channel_id))
+}
 
-    Ok(IbcBasicResponse::new().add_message(msg))
+/// Only one channel per provider, i.e. per IBC connection, can be open
+fn ensure_new_connection(storage: &dyn Storage, channel: &IbcChannel) -> Result<(), ContractError> {
+    for open in IBC_CHANNELS.range(storage, None, None, Order::Ascending) {
+        let (_, open) = open?;
+        if open.connection_id == channel.connection_id {
+            return Err(ContractError::IbcChannelAlreadyOpen(
+                open.endpoint.channel_id,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// All the open channels, in channel id order. Errors if there are none.
+pub(crate) fn open_channels(storage: &dyn Storage) -> Result<Vec<IbcChannel>, ContractError> {
+    let channels = IBC_CHANNELS
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, channel)| channel))
+        .collect::<StdResult<Vec<_>>>()?;
+    if channels.is_empty() {
+        return Err(MeshError::ChannelNotOpen.into());
+    }
+    Ok(channels)
 }
 
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn valset_update_msg(
+pub(crate) fn valset_update_packet(
     env: &Env,
-    channel: &IbcChannel,
+    max_external_stake: Option<Uint128>,
     additions: &[Validator],
     removals: &[String],
     updated: &[Validator],
@@ -135,13 +276,19 @@ pub(crate) fn valset_update_msg(
     unjailed: &[String],
     tombstoned: &[String],
     slashed: &[ValidatorSlashInfo],
-) -> Result<IbcMsg, ContractError> {
+    uptimes: &[ValidatorUptime],
+) -> ConsumerPacket {
     let additions = additions
         .iter()
         .map(|v| AddValidator {
             valoper: v.address.clone(),
             // TODO: not yet available in CosmWasm APIs. See https://github.com/CosmWasm/cosmwasm/issues/1828
             pub_key: "TODO".to_string(),
+            self_stake: None,
+            max_external_stake,
+            commission: Some(v.commission),
+            // Not available in CosmWasm APIs either
+            moniker: None,
         })
         .collect();
     let updated = updated
@@ -150,9 +297,13 @@ pub(crate) fn valset_update_msg(
             valoper: v.address.clone(),
             // TODO: not yet available in CosmWasm APIs. See https://github.com/CosmWasm/cosmwasm/issues/1828
             pub_key: "TODO".to_string(),
+            self_stake: None,
+            max_external_stake,
+            commission: Some(v.commission),
+            moniker: None,
         })
         .collect();
-    let packet = ConsumerPacket::ValsetUpdate {
+    ConsumerPacket::ValsetUpdate {
         height: env.block.height,
         time: env.block.time.seconds(),
         additions,
@@ -162,13 +313,124 @@ pub(crate) fn valset_update_msg(
         unjailed: unjailed.to_vec(),
         tombstoned: tombstoned.to_vec(),
         slashed: slashed.to_vec(),
+        uptimes: uptimes.to_vec(),
+        chunk: None,
+    }
+}
+
+/// Validator entries of a valset update chunk
+#[derive(Default)]
+struct ValsetEntries {
+    additions: Vec<AddValidator>,
+    removals: Vec<String>,
+    updated: Vec<AddValidator>,
+    jailed: Vec<String>,
+    unjailed: Vec<String>,
+    tombstoned: Vec<String>,
+    slashed: Vec<ValidatorSlashInfo>,
+    uptimes: Vec<ValidatorUptime>,
+}
+
+impl ValsetEntries {
+    fn len(&self) -> usize {
+        self.additions.len()
+            + self.removals.len()
+            + self.updated.len()
+            + self.jailed.len()
+            + self.unjailed.len()
+            + self.tombstoned.len()
+            + self.slashed.len()
+            + self.uptimes.len()
+    }
+}
+
+/// Appends `items` to the chunks, starting a new chunk whenever the last one is full
+fn push_chunked<T>(
+    chunks: &mut Vec<ValsetEntries>,
+    max_batch: usize,
+    items: Vec<T>,
+    field: fn(&mut ValsetEntries) -> &mut Vec<T>,
+) {
+    for item in items {
+        match chunks.last_mut() {
+            Some(chunk) if chunk.len() < max_batch => field(chunk).push(item),
+            _ => {
+                let mut chunk = ValsetEntries::default();
+                field(&mut chunk).push(item);
+                chunks.push(chunk);
+            }
+        }
+    }
+}
+
+/// Splits a valset update into packets of at most `max_batch` validator entries, so large
+/// validator sets don't overflow the IBC packet size. Other packets are returned as they are.
+///
+/// The entries are split in the order the provider applies them. Removals of jailed validators
+/// are dropped, as the provider only ignores them when both are in the same packet.
+/// Chunk hints are only set if `hints`, and the update is actually split.
+pub(crate) fn valset_update_chunks(
+    packet: ConsumerPacket,
+    max_batch: u32,
+    hints: bool,
+) -> Vec<ConsumerPacket> {
+    let ConsumerPacket::ValsetUpdate {
+        height,
+        time,
+        additions,
+        removals,
+        updated,
+        jailed,
+        unjailed,
+        tombstoned,
+        slashed,
+        uptimes,
+        chunk: _,
+    } = packet
+    else {
+        return vec![packet];
     };
-    let msg = IbcMsg::SendPacket {
-        channel_id: channel.endpoint.channel_id.clone(),
-        data: to_json_binary(&packet)?,
-        timeout: packet_timeout_validator(env),
-    };
-    Ok(msg)
+
+    let removals = removals
+        .into_iter()
+        .filter(|valoper| !jailed.contains(valoper))
+        .collect();
+    let max_batch = max_batch.max(1) as usize;
+    let mut chunks = vec![];
+    push_chunked(&mut chunks, max_batch, slashed, |c| &mut c.slashed);
+    push_chunked(&mut chunks, max_batch, tombstoned, |c| &mut c.tombstoned);
+    push_chunked(&mut chunks, max_batch, additions, |c| &mut c.additions);
+    push_chunked(&mut chunks, max_batch, jailed, |c| &mut c.jailed);
+    push_chunked(&mut chunks, max_batch, removals, |c| &mut c.removals);
+    push_chunked(&mut chunks, max_batch, updated, |c| &mut c.updated);
+    push_chunked(&mut chunks, max_batch, unjailed, |c| &mut c.unjailed);
+    push_chunked(&mut chunks, max_batch, uptimes, |c| &mut c.uptimes);
+    // An empty update is still sent, e.g. the initial sync of a consumer without validators
+    if chunks.is_empty() {
+        chunks.push(ValsetEntries::default());
+    }
+
+    let total = chunks.len() as u32;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, entries)| ConsumerPacket::ValsetUpdate {
+            height,
+            time,
+            additions: entries.additions,
+            removals: entries.removals,
+            updated: entries.updated,
+            jailed: entries.jailed,
+            unjailed: entries.unjailed,
+            tombstoned: entries.tombstoned,
+            slashed: entries.slashed,
+            uptimes: entries.uptimes,
+            chunk: (hints && total > 1).then_some(ValsetChunk {
+                index: index as u32,
+                total,
+            }),
+        })
+        .collect()
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -188,10 +450,26 @@ pub fn ibc_channel_close(
 /// of execution. We just return ok if we dispatched, error if we failed to dispatch
 pub fn ibc_packet_receive(
     deps: DepsMut<custom::ConverterQuery>,
-    _env: Env,
+    env: Env,
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse<custom::ConverterMsg>, ContractError> {
-    let packet: ProviderPacket = from_json(msg.packet.data)?;
+    // Acks go back on the channel the packet came from, so only its provider stake is affected
+    let channel_id = msg.packet.dest.channel_id;
+    let packet = ProviderPacket::decode(&msg.packet.data)?;
+    ConverterContract::new()
+        .relayer_incentives
+        .record_packet(deps.storage, &msg.relayer)?;
+    receive_packet(deps, env, &channel_id, packet)
+}
+
+/// Applies the provider packet received on `channel_id`, returning the response with its
+/// success ack
+pub(crate) fn receive_packet(
+    deps: DepsMut<custom::ConverterQuery>,
+    env: Env,
+    channel_id: &str,
+    packet: ProviderPacket,
+) -> Result<IbcReceiveResponse<custom::ConverterMsg>, ContractError> {
     let contract = ConverterContract::new();
     let res = match packet {
         ProviderPacket::Stake {
@@ -199,7 +477,20 @@ pub fn ibc_packet_receive(
             stake,
             tx_id: _,
         } => {
-            let response = contract.stake(deps, validator, stake)?;
+            let response = contract.stake(deps, channel_id, validator, stake)?;
+            let ack = ack_success(&StakeAck {})?;
+            IbcReceiveResponse::new()
+                .set_ack(ack)
+                .add_submessages(response.messages)
+                .add_events(response.events)
+                .add_attributes(response.attributes)
+        }
+        ProviderPacket::StakeBatch {
+            stakes,
+            denom,
+            tx_id: _,
+        } => {
+            let response = contract.stake_batch(deps, channel_id, stakes, denom)?;
             let ack = ack_success(&StakeAck {})?;
             IbcReceiveResponse::new()
                 .set_ack(ack)
@@ -212,7 +503,16 @@ pub fn ibc_packet_receive(
             unstake,
             tx_id: _,
         } => {
-            let response = contract.unstake(deps, validator, unstake)?;
+            let response = contract.unstake(deps, channel_id, validator, unstake)?;
+            let ack = ack_success(&UnstakeAck {})?;
+            IbcReceiveResponse::new()
+                .set_ack(ack)
+                .add_submessages(response.messages)
+                .add_events(response.events)
+                .add_attributes(response.attributes)
+        }
+        ProviderPacket::UnstakeBatch { unstakes, denom } => {
+            let response = contract.unstake_batch(deps, channel_id, unstakes, denom)?;
             let ack = ack_success(&UnstakeAck {})?;
             IbcReceiveResponse::new()
                 .set_ack(ack)
@@ -221,7 +521,7 @@ pub fn ibc_packet_receive(
                 .add_attributes(response.attributes)
         }
         ProviderPacket::Burn { validators, burn } => {
-            let response = contract.burn(deps, &validators, burn)?;
+            let response = contract.burn(deps, channel_id, &validators, burn)?;
             let ack = ack_success(&UnstakeAck {})?;
             IbcReceiveResponse::new()
                 .set_ack(ack)
@@ -232,9 +532,25 @@ pub fn ibc_packet_receive(
         ProviderPacket::TransferRewards {
             rewards, recipient, ..
         } => {
-            let msg = contract.transfer_rewards(deps.as_ref(), recipient, rewards)?;
+            let msg = contract.transfer_rewards(deps, &env, recipient, rewards)?;
             let ack = ack_success(&TransferRewardsAck {})?;
-            IbcReceiveResponse::new().set_ack(ack).add_message(msg)
+            IbcReceiveResponse::new().set_ack(ack).add_submessage(msg)
+        }
+        ProviderPacket::StakeChecksum { checksum } => {
+            let response = contract.check_stake_checksum(deps, channel_id, checksum)?;
+            let ack = ack_success(&StakeChecksumAck {})?;
+            IbcReceiveResponse::new()
+                .set_ack(ack)
+                .add_events(response.events)
+                .add_attributes(response.attributes)
+        }
+        ProviderPacket::Vote { proposal_id, tally } => {
+            let response = contract.vote(deps, channel_id, proposal_id, tally)?;
+            let ack = ack_success(&VoteAck {})?;
+            IbcReceiveResponse::new()
+                .set_ack(ack)
+                .add_submessages(response.messages)
+                .add_attributes(response.attributes)
         }
     };
     Ok(res)
@@ -242,54 +558,77 @@ pub fn ibc_packet_receive(
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// We get ACKs on sync state without much to do.
-/// If it succeeded, take no action. If it errored, we can't do anything else and let it go.
-/// We just log the error cases so they can be detected.
+/// The packet is done with, and removed from the outbox. If it errored, we can't do anything
+/// else and let it go. We just log the error cases so they can be detected.
+/// Rewards whose distribution failed are credited back, to be sent again with the next batch.
 pub fn ibc_packet_ack(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     let ack: AckWrapper = from_json(&msg.acknowledgement.data)?;
-    let mut res = IbcBasicResponse::new();
-    match ack {
-        AckWrapper::Result(_) => {}
-        AckWrapper::Error(e) => {
-            // The wasmd framework will label this with the contract_addr, which helps us find the port and issue.
-            // Provide info to find the actual packet.
-            let event = Event::new("mesh_ibc_error")
-                .add_attribute("error", e)
-                .add_attribute("channel", msg.original_packet.src.channel_id)
-                .add_attribute("sequence", msg.original_packet.sequence.to_string());
-            res = res.add_event(event);
-        }
+    let channel_id = &msg.original_packet.src.channel_id;
+    let success = matches!(ack, AckWrapper::Result(_));
+    let id = ConverterContract::new().packet_acked(
+        deps.storage,
+        channel_id,
+        &msg.original_packet.data,
+        success,
+    )?;
+
+    let mut res = IbcBasicResponse::new()
+        .add_attribute("action", "packet_ack")
+        .add_attribute("outbox_id", outbox_id_attribute(id));
+    if let AckWrapper::Error(e) = ack {
+        // The wasmd framework will label this with the contract_addr, which helps us find the port and issue.
+        // Provide info to find the actual packet.
+        let event = Event::new("mesh_ibc_error")
+            .add_attribute("error", e)
+            .add_attribute("channel", channel_id)
+            .add_attribute("sequence", msg.original_packet.sequence.to_string());
+        res = res.add_event(event);
     }
     Ok(res)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-/// The most we can do here is retry the packet, hoping it will eventually arrive.
+/// The packet is marked as retriable in the outbox, to be sent again with `retry_packet`.
 pub fn ibc_packet_timeout(
-    _deps: DepsMut,
-    env: Env,
+    deps: DepsMut,
+    _env: Env,
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    // Play it again, Sam.
-    let msg = IbcMsg::SendPacket {
-        channel_id: msg.packet.src.channel_id,
-        data: msg.packet.data,
-        timeout: packet_timeout_validator(&env),
-    };
-    Ok(IbcBasicResponse::new().add_message(msg))
+    let id = ConverterContract::new().packet_timed_out(
+        deps.storage,
+        &msg.packet.src.channel_id,
+        &msg.packet.data,
+    )?;
+    Ok(IbcBasicResponse::new()
+        .add_attribute("action", "packet_timeout")
+        .add_attribute("outbox_id", outbox_id_attribute(id)))
 }
 
-pub(crate) fn make_ibc_packet(
-    ctx: &mut ExecCtx<custom::ConverterQuery>,
-    packet: ConsumerPacket,
-) -> Result<IbcMsg, ContractError> {
-    let channel = IBC_CHANNEL.load(ctx.deps.storage)?;
-    Ok(IbcMsg::SendPacket {
-        channel_id: channel.endpoint.channel_id,
-        data: to_json_binary(&packet)?,
-        timeout: packet_timeout_rewards(&ctx.env),
+/// Packets unknown to the outbox are reported as `none`
+fn outbox_id_attribute(id: Option<u64>) -> String {
+    id.map_or_else(|| "none".to_owned(), |id| id.to_string())
+}
+
+/// Features negotiated on the channel. None for channels opened before features were added
+pub fn channel_features(storage: &dyn Storage, channel_id: &str) -> StdResult<Features> {
+    Ok(IBC_FEATURES
+        .may_load(storage, channel_id)?
+        .unwrap_or_default())
+}
+
+/// Validator syncs are given more time to arrive than the other packets
+pub(crate) fn packet_timeout(env: &Env, packet: &ConsumerPacket) -> IbcTimeout {
+    TIMEOUT_POLICY.timeout(env.block.time, packet.packet_type())
+}
+
+/// FNV-1a hash of the packet data, used to find outbox packets back from their acks and
+/// timeouts. Collisions only cost an extra lookup, as the packets are compared in full
+pub(crate) fn packet_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
     })
 }
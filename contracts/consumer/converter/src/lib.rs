@@ -1,7 +1,9 @@
 pub mod contract;
+pub mod curve;
 pub mod error;
 pub mod ibc;
 pub mod msg;
 #[cfg(test)]
 mod multitest;
+pub mod relayer;
 pub mod state;
@@ -1,13 +1,158 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Decimal;
+use cosmwasm_std::{Coin, Decimal, Timestamp, Uint128, Uint64};
+use mesh_apis::converter_api::RewardInfo;
+use mesh_apis::ibc::{ConsumerPacket, Features};
+
+use crate::curve::CurveSegment;
+use crate::state::OutboxStatus;
 
 #[cw_serde]
 pub struct ConfigResponse {
+    /// Adjustment of the first tokens staked on a validator, i.e. `1.0 - discount`
     pub adjustment: Decimal,
 
+    /// Discount curve, starting with `adjustment`
+    pub curve: Vec<CurveSegment>,
+
     /// Address of the contract we query for the price feed to normalize the foreign asset into native tokens.
     pub price_feed: String,
 
     /// Address of the virtual staking contract.
     pub virtual_staking: String,
+
+    /// Virtual staking contract the virtual stake is being moved to, during a migration
+    pub migrating_virtual_staking: Option<String>,
+
+    /// ICS-20 channel used to transfer rewards to the provider.
+    pub transfer_channel: Option<String>,
+
+    /// Max cross-stake accepted on each validator from the providers, if any
+    pub max_external_stake: Option<Uint128>,
+
+    /// IBC channel whose rewards are sent with ICS-20 transfers, if any
+    pub rewards_routing_channel: Option<String>,
+
+    /// Max number of validator entries per valset update packet
+    pub max_valset_batch: u32,
+
+    /// Portion of the distributed rewards paid to the relayers, if any
+    pub relayer_fee: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct RelayerRewardsResponse {
+    /// Fees that can be claimed with `claim_relayer_rewards`, in the local denom
+    pub claimable: Uint128,
+    /// Packets delivered since the last rewards distribution, paid with the next one
+    pub pending_packets: u64,
+}
+
+#[cw_serde]
+pub struct StuckRewardsInfo {
+    pub id: u64,
+    pub recipient: String,
+    pub rewards: Coin,
+    pub failures: u32,
+    pub retry_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct StuckRewardsResponse {
+    pub rewards: Vec<StuckRewardsInfo>,
+}
+
+/// Open channel to a provider
+#[cw_serde]
+pub struct ChannelInfo {
+    pub channel_id: String,
+    pub connection_id: String,
+    /// Optional protocol features negotiated on the channel
+    pub features: Features,
+}
+
+#[cw_serde]
+pub struct ChannelsResponse {
+    pub channels: Vec<ChannelInfo>,
+}
+
+/// Virtual stake bonded by a provider on a validator, in the remote denom
+#[cw_serde]
+pub struct ChannelStake {
+    pub validator: String,
+    pub stake: Uint128,
+}
+
+#[cw_serde]
+pub struct ChannelStakesResponse {
+    pub stakes: Vec<ChannelStake>,
+}
+
+#[cw_serde]
+pub struct StakeChecksumResponse {
+    /// `mesh_apis::ibc::StakeChecksum` of the provider stakes on the channel
+    pub checksum: Uint64,
+}
+
+#[cw_serde]
+pub struct UndistributedRewardsResponse {
+    /// Rewards to be sent again to the provider with the next rewards batch, by validator
+    pub rewards: Vec<RewardInfo>,
+}
+
+/// Packet sent to a provider and not acked yet
+#[cw_serde]
+pub struct OutboxPacketInfo {
+    pub id: u64,
+    pub channel_id: String,
+    pub packet: ConsumerPacket,
+    pub status: OutboxStatus,
+    /// Number of times the packet was sent
+    pub attempts: u32,
+}
+
+#[cw_serde]
+pub struct OutboxResponse {
+    pub packets: Vec<OutboxPacketInfo>,
+}
+
+#[cw_serde]
+pub struct TotalVirtualStakeResponse {
+    /// Virtual stake bonded by all the providers, in the remote denom
+    pub stake: Coin,
+    /// Virtual stake requested from the virtual staking contract, in the local denom
+    pub virtual_stake: Coin,
+}
+
+/// Virtual stake on a validator, over all the providers
+#[cw_serde]
+pub struct ValidatorVirtualStake {
+    pub validator: String,
+    /// Bonded by the providers, in the remote denom
+    pub stake: Uint128,
+    /// Requested from the virtual staking contract, in the local denom
+    pub virtual_stake: Uint128,
+}
+
+#[cw_serde]
+pub struct StakePerValidatorResponse {
+    pub stakes: Vec<ValidatorVirtualStake>,
+}
+
+/// Rewards owed to the providers, not delivered yet
+#[cw_serde]
+pub struct PendingRewardsResponse {
+    /// Rewards whose distribution failed on a provider, waiting for the next rewards batch
+    pub undistributed: Coin,
+    /// Rewards transfers waiting for their ICS-20 ack
+    pub in_transfer: Coin,
+    /// Rewards whose transfer failed, waiting for a retry
+    pub stuck: Coin,
+}
+
+#[cw_serde]
+pub struct EffectiveWeightResponse {
+    /// Average adjustment applied to the amount, after the discount curve
+    pub adjustment: Decimal,
+    /// Virtual stake the amount would be converted into
+    pub virtual_stake: Coin,
 }
@@ -1,23 +1,37 @@
-mod virtual_staking_mock;
-
-use cosmwasm_std::{coin, coins, Addr, Decimal, StdError, Uint128, Validator};
+use cosmwasm_std::{
+    coin, coins, Addr, Decimal, IbcChannel, IbcEndpoint, IbcOrder, Uint128, Uint64, Validator,
+    VoteOption, WeightedVoteOption,
+};
 use cw_multi_test::{no_init, AppBuilder};
 use mesh_apis::converter_api::sv::mt::ConverterApiProxy;
-use mesh_apis::converter_api::RewardInfo;
+use mesh_apis::converter_api::{RewardInfo, ValidatorUptime};
+use mesh_apis::error::MeshError;
+use mesh_apis::ibc::{
+    AddValidator, ConsumerPacket, Features, ProviderPacket, StakeChecksum, ValsetChunk, VoteWeight,
+};
+use mesh_apis::ownership_api::sv::mt::OwnershipApiProxy;
 use mesh_simple_price_feed::contract::sv::mt::CodeId as PriceFeedCodeId;
 use mesh_simple_price_feed::contract::SimplePriceFeedContract;
+use mesh_virtual_staking_mock_contract::contract::sv::mt::CodeId as VirtualStakingCodeId;
+use mesh_virtual_staking_mock_contract::contract::sv::mt::VirtualStakingMockProxy;
+use mesh_virtual_staking_mock_contract::contract::VirtualStakingMock;
 use sylvia::multitest::{App, Proxy};
-use virtual_staking_mock::sv::mt::CodeId as VirtualStakingCodeId;
-use virtual_staking_mock::VirtualStakingMock;
 
 use crate::contract::sv::mt::CodeId as ConverterCodeId;
 use crate::contract::sv::mt::ConverterContractProxy;
-use crate::contract::{custom, ConverterContract};
+use crate::contract::{
+    custom, ConverterContract, MAX_TRANSFER_FAILURES, RETRY_BASE_DELAY, TEST_CHANNEL,
+};
+use crate::curve::CurveSegment;
 use crate::error::ContractError;
-use crate::error::ContractError::Unauthorized;
-use crate::multitest::virtual_staking_mock::sv::mt::VirtualStakingMockProxy;
+use crate::ibc::{IbcLifecycleAck, IbcLifecycleTimeout};
+use crate::msg::{
+    ChannelStake, OutboxPacketInfo, RelayerRewardsResponse, StuckRewardsInfo, ValidatorVirtualStake,
+};
+use crate::state::{OutboxStatus, PendingTransfer, RoutedRewards, DEFAULT_VALSET_BATCH};
 
 const JUNO: &str = "ujuno";
+const TRANSFER_CHANNEL: &str = "channel-1";
 
 pub type MtApp = cw_multi_test::BasicApp<custom::ConverterMsg, custom::ConverterQuery>;
 
@@ -63,6 +77,8 @@ fn setup<'a>(app: &'a App<MtApp>, args: SetupArgs<'a>) -> SetupResponse<'a> {
             JUNO.to_owned(),
             virtual_staking_code.code_id(),
             Some(admin.to_owned()),
+            Some(TRANSFER_CHANNEL.to_owned()),
+            None,
         )
         .with_label("Juno Converter")
         .with_admin(admin)
@@ -84,6 +100,23 @@ fn setup<'a>(app: &'a App<MtApp>, args: SetupArgs<'a>) -> SetupResponse<'a> {
     }
 }
 
+/// Channel of the converter to `port_id` on the provider
+fn provider_channel(channel_id: &str, port_id: &str) -> IbcChannel {
+    IbcChannel::new(
+        IbcEndpoint {
+            port_id: "wasm.converter".to_owned(),
+            channel_id: channel_id.to_owned(),
+        },
+        IbcEndpoint {
+            port_id: port_id.to_owned(),
+            channel_id: "channel-0".to_owned(),
+        },
+        IbcOrder::Unordered,
+        "mesh",
+        "connection-0",
+    )
+}
+
 #[test]
 fn instantiation() {
     let app = new_app();
@@ -113,6 +146,10 @@ fn instantiation() {
     assert_eq!(config.adjustment, Decimal::percent(60));
     assert!(!config.virtual_staking.is_empty());
 
+    // the instantiator owns the converter by default
+    let ownership = converter.ownership().unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked(owner)));
+
     // let's check we passed the admin here properly
     let vs_info = app
         .app()
@@ -186,6 +223,42 @@ fn ibc_stake_and_unstake() {
         .call(owner)
         .unwrap();
 
+    // the provider can't unstake more than it staked
+    let err = converter
+        .test_unstake(val1.to_string(), coin(1001, JUNO))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientChannelStake {
+            channel_id: TEST_CHANNEL.to_owned(),
+            validator: val1.to_owned(),
+        }
+    );
+
+    // its stake is tracked in the remote denom
+    assert_eq!(
+        converter
+            .channel_stakes(TEST_CHANNEL.to_owned(), None, None)
+            .unwrap()
+            .stakes,
+        vec![
+            ChannelStake {
+                validator: val1.to_owned(),
+                stake: Uint128::new(1000),
+            },
+            ChannelStake {
+                validator: val2.to_owned(),
+                stake: Uint128::new(2000),
+            },
+        ]
+    );
+    assert!(converter
+        .channel_stakes("channel-7".to_owned(), None, None)
+        .unwrap()
+        .stakes
+        .is_empty());
+
     // and check the stakes (1000 * 0.6 * 0.5 = 300) (2000 * 0.6 * 0.5 = 600)
     assert_eq!(
         virtual_staking
@@ -271,6 +344,16 @@ fn ibc_stake_and_burn() {
         .test_burn(vec![val2.to_string()], coin(2000, JUNO))
         .call(owner)
         .unwrap();
+    assert_eq!(
+        converter
+            .channel_stakes(TEST_CHANNEL.to_owned(), None, None)
+            .unwrap()
+            .stakes[1],
+        ChannelStake {
+            validator: val2.to_owned(),
+            stake: Uint128::new(2000),
+        }
+    );
 
     // and check the stakes (1000 * 0.6 * 0.5 = 300) (2000 * 0.6 * 0.5 = 600)
     assert_eq!(
@@ -296,6 +379,327 @@ fn ibc_stake_and_burn() {
             (val2.to_string(), Uint128::new(600)),
         ]
     );
+
+    // Provider stakes checksum matches the bookkeeping
+    let expected =
+        StakeChecksum::from_stakes([(val1, Uint128::new(1000)), (val2, Uint128::new(2000))]);
+    assert_eq!(
+        converter
+            .stake_checksum(TEST_CHANNEL.to_owned())
+            .unwrap()
+            .checksum,
+        Uint64::from(expected)
+    );
+    let is_reconciliation = |e: &cosmwasm_std::Event| e.ty == "wasm-mesh-reconciliation";
+    let resp = converter
+        .test_stake_checksum(expected.into())
+        .call(owner)
+        .unwrap();
+    assert!(!resp.events.iter().any(is_reconciliation));
+
+    // Diverging provider stakes are reported
+    let diverged = expected.update(val1, Uint128::new(1000), Uint128::new(900));
+    let resp = converter
+        .test_stake_checksum(diverged.into())
+        .call(owner)
+        .unwrap();
+    assert!(resp.events.iter().any(is_reconciliation));
+}
+
+#[test]
+fn reconciliation_with_virtual_staking() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40); // 1 OSMO worth of JUNO should give 0.6 OSMO of stake
+    let native_per_foreign = Decimal::percent(50); // 1 JUNO is worth 0.5 OSMO
+
+    let SetupResponse {
+        price_feed: _,
+        converter,
+        virtual_staking,
+    } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    // The provider channel the test stakes go through
+    converter
+        .test_connect_channel(provider_channel(TEST_CHANNEL, "wasm.osmo1provider"))
+        .call(owner)
+        .unwrap();
+
+    let val1 = "Val Kilmer";
+    let val2 = "Valley Girl";
+    converter
+        .test_stake(val1.to_string(), coin(1000, JUNO))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_stake(val2.to_string(), coin(4000, JUNO))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_burn(vec![val2.to_string()], coin(2000, JUNO))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_unstake(val1.to_string(), coin(400, JUNO))
+        .call(owner)
+        .unwrap();
+
+    // Provider-side stakes, and their virtual stake (* 0.6 * 0.5)
+    let total = converter.total_virtual_stake().unwrap();
+    assert_eq!(total.stake, coin(2600, JUNO));
+    assert_eq!(total.virtual_stake, coin(780, "TOKEN"));
+
+    let stake = |validator: &str, stake: u128, virtual_stake: u128| ValidatorVirtualStake {
+        validator: validator.to_owned(),
+        stake: Uint128::new(stake),
+        virtual_stake: Uint128::new(virtual_stake),
+    };
+    assert_eq!(
+        converter.stake_per_validator(None, None).unwrap().stakes,
+        vec![stake(val1, 600, 180), stake(val2, 2000, 600)]
+    );
+    assert_eq!(
+        converter
+            .stake_per_validator(Some(val1.to_owned()), None)
+            .unwrap()
+            .stakes,
+        vec![stake(val2, 2000, 600)]
+    );
+
+    // Nothing to report while the bookkeeping matches
+    let is_discrepancy = |e: &&cosmwasm_std::Event| e.ty == "wasm-mesh-discrepancy";
+    let resp = converter.reconcile().call(owner).unwrap();
+    assert_eq!(resp.events.iter().filter(is_discrepancy).count(), 0);
+
+    // Drifting bond requests are reported, by anyone
+    virtual_staking
+        .force_stake(val2.to_owned(), Uint128::new(500))
+        .call(owner)
+        .unwrap();
+    let resp = converter.reconcile().call("anyone").unwrap();
+    let discrepancies: Vec<_> = resp.events.iter().filter(is_discrepancy).collect();
+    assert_eq!(discrepancies.len(), 1);
+    let attr = |key: &str| {
+        discrepancies[0]
+            .attributes
+            .iter()
+            .find(|a| a.key == key)
+            .map(|a| a.value.clone())
+    };
+    assert_eq!(attr("validator").as_deref(), Some(val2));
+    assert_eq!(attr("converter_stake").as_deref(), Some("600"));
+    assert_eq!(attr("virtual_staking_stake").as_deref(), Some("500"));
+
+    // Rewards owed to the provider, not delivered yet
+    converter
+        .test_distribute_error(vec![RewardInfo {
+            validator: val1.to_owned(),
+            reward: Uint128::new(25),
+        }])
+        .call(owner)
+        .unwrap();
+    let pending = converter.pending_rewards().unwrap();
+    assert_eq!(pending.undistributed, coin(25, "TOKEN"));
+    assert_eq!(pending.in_transfer, coin(0, "TOKEN"));
+    assert_eq!(pending.stuck, coin(0, "TOKEN"));
+}
+
+#[test]
+fn virtual_staking_migration() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40);
+    let native_per_foreign = Decimal::percent(50);
+
+    let SetupResponse {
+        price_feed: _,
+        converter,
+        virtual_staking,
+    } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+    let new_virtual_staking = VirtualStakingCodeId::store_code(&app)
+        .instantiate(None)
+        .call(converter.contract_addr.as_str())
+        .unwrap();
+
+    let val1 = "Val Kilmer";
+    let val2 = "Valley Girl";
+    converter
+        .test_stake(val1.to_string(), coin(1000, JUNO))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_stake(val2.to_string(), coin(4000, JUNO))
+        .call(owner)
+        .unwrap();
+
+    // The old contract is drained
+    converter
+        .start_virtual_staking_migration(new_virtual_staking.contract_addr.to_string())
+        .unwrap();
+    let err = converter
+        .start_virtual_staking_migration(new_virtual_staking.contract_addr.to_string())
+        .unwrap_err();
+    assert_eq!(err, ContractError::VirtualStakingMigrationInProgress);
+    assert_eq!(
+        virtual_staking.all_stake().unwrap().stakes,
+        vec![
+            (val1.to_string(), Uint128::zero()),
+            (val2.to_string(), Uint128::zero()),
+        ]
+    );
+    let config = converter.config().unwrap();
+    assert_eq!(
+        config.virtual_staking,
+        virtual_staking.contract_addr.to_string()
+    );
+    assert_eq!(
+        config.migrating_virtual_staking,
+        Some(new_virtual_staking.contract_addr.to_string())
+    );
+
+    // Stake changes in the meantime are only tracked
+    converter
+        .test_unstake(val2.to_string(), coin(2000, JUNO))
+        .call(owner)
+        .unwrap();
+    assert!(new_virtual_staking.all_stake().unwrap().stakes.is_empty());
+
+    // And replayed into the new contract with the others
+    converter.complete_virtual_staking_migration().unwrap();
+    let err = converter.complete_virtual_staking_migration().unwrap_err();
+    assert_eq!(err, ContractError::NoVirtualStakingMigration);
+    let config = converter.config().unwrap();
+    assert_eq!(
+        config.virtual_staking,
+        new_virtual_staking.contract_addr.to_string()
+    );
+    assert_eq!(config.migrating_virtual_staking, None);
+    assert_eq!(
+        new_virtual_staking.all_stake().unwrap().stakes,
+        vec![
+            (val1.to_string(), Uint128::new(300)),
+            (val2.to_string(), Uint128::new(600)),
+        ]
+    );
+
+    // Only the new contract is used from now on
+    converter
+        .test_stake(val1.to_string(), coin(1000, JUNO))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        new_virtual_staking
+            .stake(val1.to_string())
+            .unwrap()
+            .stake
+            .u128(),
+        600
+    );
+    assert_eq!(
+        virtual_staking
+            .stake(val1.to_string())
+            .unwrap()
+            .stake
+            .u128(),
+        0
+    );
+    let err = converter
+        .distribute_reward(val1.to_string())
+        .call(virtual_staking.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+}
+
+#[test]
+fn remote_vote() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+
+    let SetupResponse {
+        price_feed: _,
+        converter,
+        virtual_staking,
+    } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount: Decimal::percent(10),
+            native_per_foreign: Decimal::percent(40),
+        },
+    );
+
+    // The provider tally is cast as a weighted vote, weights adding up to one
+    converter
+        .test_vote(
+            1,
+            vec![
+                VoteWeight {
+                    option: VoteOption::Yes,
+                    weight: Uint128::new(100),
+                },
+                VoteWeight {
+                    option: VoteOption::No,
+                    weight: Uint128::new(200),
+                },
+            ],
+        )
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        virtual_staking.last_vote(1).unwrap().options,
+        vec![
+            WeightedVoteOption {
+                option: VoteOption::Yes,
+                weight: Decimal::from_ratio(1u128, 3u128),
+            },
+            WeightedVoteOption {
+                option: VoteOption::No,
+                weight: Decimal::one() - Decimal::from_ratio(1u128, 3u128),
+            },
+        ]
+    );
+
+    // A new tally replaces the previous one
+    converter
+        .test_vote(
+            1,
+            vec![VoteWeight {
+                option: VoteOption::Abstain,
+                weight: Uint128::new(300),
+            }],
+        )
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        virtual_staking.last_vote(1).unwrap().options,
+        vec![WeightedVoteOption {
+            option: VoteOption::Abstain,
+            weight: Decimal::one(),
+        }]
+    );
 }
 
 #[test]
@@ -340,9 +744,21 @@ fn valset_update_works() {
 
     // Check that only the virtual staking contract can call this handler
     let res = converter
-        .valset_update(vec![], vec![], vec![], vec![], vec![], vec![], vec![])
+        .valset_update(
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        )
         .call(owner);
-    assert_eq!(res.unwrap_err(), Unauthorized {});
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Mesh(MeshError::Unauthorized)
+    );
 
     let res = converter
         .valset_update(
@@ -353,6 +769,7 @@ fn valset_update_works() {
             vec![],
             vec![],
             vec![],
+            vec![],
         )
         .call(virtual_staking.contract_addr.as_ref());
 
@@ -361,12 +778,9 @@ fn valset_update_works() {
     // TODO: Change this when IBC support is there in mt.
     assert_eq!(
         res.unwrap_err(),
-        ContractError::Std(StdError::NotFound {
-            kind:
-                "type: cosmwasm_std::ibc::IbcChannel; key: [69, 62, 63, 5F, 63, 68, 61, 6E, 6E, 65, 6C]"
-                    .to_string()
-        })
+        ContractError::Mesh(MeshError::ChannelNotOpen)
     );
+    assert!(converter.channels().unwrap().channels.is_empty());
 }
 
 #[test]
@@ -397,21 +811,30 @@ fn unauthorized() {
         .call("mallory")
         .unwrap_err();
 
-    assert_eq!(err, ContractError::Unauthorized);
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
 
     let err = converter
         .distribute_reward("validator".to_string())
         .call("mallory")
         .unwrap_err();
 
-    assert_eq!(err, ContractError::Unauthorized);
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
 
     let err = converter
-        .valset_update(vec![], vec![], vec![], vec![], vec![], vec![], vec![])
+        .valset_update(
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        )
         .call("mallory")
         .unwrap_err();
 
-    assert_eq!(err, ContractError::Unauthorized);
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
 }
 
 #[test]
@@ -545,3 +968,918 @@ fn distribute_rewards_valid_amount() {
         .call(virtual_staking.contract_addr.as_str())
         .unwrap();
 }
+
+#[test]
+fn failed_distributions_are_credited_back() {
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(10);
+    let native_per_foreign = Decimal::percent(40);
+
+    let app = new_app();
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    let reward = |validator: &str, amount: u128| RewardInfo {
+        validator: validator.to_string(),
+        reward: amount.into(),
+    };
+
+    // Failed distributions accumulate by validator, until sent again with the next batch
+    converter
+        .test_distribute_error(vec![reward("alice", 10), reward("bob", 5)])
+        .call(owner)
+        .unwrap();
+    converter
+        .test_distribute_error(vec![reward("alice", 3)])
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter
+            .undistributed_rewards(TEST_CHANNEL.to_owned())
+            .unwrap()
+            .rewards,
+        vec![reward("alice", 13), reward("bob", 5)]
+    );
+    assert!(converter
+        .undistributed_rewards("channel-1".to_owned())
+        .unwrap()
+        .rewards
+        .is_empty());
+}
+
+#[test]
+fn outbox_applies_packet_outcomes_once() {
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(10);
+    let native_per_foreign = Decimal::percent(40);
+
+    let app = new_app();
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    let distribute = |validator: &str, amount: u128| ConsumerPacket::Distribute {
+        validator: validator.to_string(),
+        rewards: coin(amount, "TOKEN"),
+    };
+    converter
+        .test_send_packet(distribute("alice", 10))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_send_packet(distribute("bob", 5))
+        .call(owner)
+        .unwrap();
+    let info =
+        |id: u64, packet: ConsumerPacket, status: OutboxStatus, attempts: u32| OutboxPacketInfo {
+            id,
+            channel_id: TEST_CHANNEL.to_owned(),
+            packet,
+            status,
+            attempts,
+        };
+    assert_eq!(
+        converter.outbox(None, None).unwrap().packets,
+        vec![
+            info(1, distribute("alice", 10), OutboxStatus::InFlight {}, 1),
+            info(2, distribute("bob", 5), OutboxStatus::InFlight {}, 1),
+        ]
+    );
+
+    // Failed distributions are credited back, and the packet is done with
+    converter
+        .test_packet_ack(distribute("alice", 10), false)
+        .call(owner)
+        .unwrap();
+    // Acking it again has no effect
+    converter
+        .test_packet_ack(distribute("alice", 10), false)
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter
+            .undistributed_rewards(TEST_CHANNEL.to_owned())
+            .unwrap()
+            .rewards,
+        vec![RewardInfo {
+            validator: "alice".to_string(),
+            reward: 10u128.into(),
+        }]
+    );
+
+    // Timed out packets wait to be retried, and can't be acked until then
+    converter
+        .test_packet_timeout(distribute("bob", 5))
+        .call(owner)
+        .unwrap();
+    converter
+        .test_packet_ack(distribute("bob", 5), false)
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter.outbox(None, None).unwrap().packets,
+        vec![info(2, distribute("bob", 5), OutboxStatus::Retriable {}, 1)]
+    );
+    assert_eq!(
+        converter
+            .undistributed_rewards(TEST_CHANNEL.to_owned())
+            .unwrap()
+            .rewards
+            .len(),
+        1
+    );
+
+    // Packets of closed channels are not retried
+    let resp = converter.retry_packets().call(owner).unwrap();
+    assert!(resp
+        .events
+        .iter()
+        .all(|event| event.ty != "wasm-retry_packet"));
+    assert_eq!(
+        converter.outbox(None, None).unwrap().packets[0].status,
+        OutboxStatus::Retriable {}
+    );
+}
+
+#[test]
+fn stuck_rewards_retry_and_redirect() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40);
+    let native_per_foreign = Decimal::percent(50);
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    // Rewards transfers sent to the provider, as tracked after the transfer reply
+    for (sequence, failures) in [(1, 0), (2, MAX_TRANSFER_FAILURES - 1), (3, 0)] {
+        let pending = PendingTransfer {
+            recipient: "osmo1recipient".to_owned(),
+            rewards: coin(100, JUNO),
+            failures,
+            stuck_id: None,
+            routed: None,
+        };
+        converter
+            .test_track_transfer(TRANSFER_CHANNEL.to_owned(), sequence, pending)
+            .call(owner)
+            .unwrap();
+    }
+    app.app_mut().init_modules(|router, _, storage| {
+        router
+            .bank
+            .init_balance(storage, &converter.contract_addr, coins(300, JUNO))
+            .unwrap();
+    });
+
+    let timeout = |sequence| IbcLifecycleTimeout {
+        channel: TRANSFER_CHANNEL.to_owned(),
+        sequence,
+    };
+
+    // Successful transfer is just forgotten
+    converter
+        .ibc_lifecycle_complete(
+            Some(IbcLifecycleAck {
+                channel: TRANSFER_CHANNEL.to_owned(),
+                sequence: 3,
+                ack: "AQ==".to_owned(),
+                success: true,
+            }),
+            None,
+        )
+        .unwrap();
+    // Unknown transfers are ignored
+    converter
+        .ibc_lifecycle_complete(None, Some(timeout(9)))
+        .unwrap();
+    assert_eq!(converter.stuck_rewards(None, None).unwrap().rewards, []);
+
+    // Failed transfer is queued for a retry
+    converter
+        .ibc_lifecycle_complete(None, Some(timeout(1)))
+        .unwrap();
+    let retry_at = app.block_info().time.plus_seconds(RETRY_BASE_DELAY);
+    assert_eq!(
+        converter.stuck_rewards(None, None).unwrap().rewards,
+        [StuckRewardsInfo {
+            id: 1,
+            recipient: "osmo1recipient".to_owned(),
+            rewards: coin(100, JUNO),
+            failures: 1,
+            retry_at,
+        }]
+    );
+
+    let err = converter.retry_stuck_rewards(1).call(owner).unwrap_err();
+    assert_eq!(err, ContractError::RetryNotReady(retry_at));
+
+    let err = converter
+        .redirect_stuck_rewards(1, "community".to_owned())
+        .unwrap_err();
+    assert_eq!(err, ContractError::RetriesNotExhausted(1));
+
+    // Too many failures, governance has to redirect the rewards
+    converter
+        .ibc_lifecycle_complete(None, Some(timeout(2)))
+        .unwrap();
+    let err = converter.retry_stuck_rewards(2).call(owner).unwrap_err();
+    assert_eq!(err, ContractError::RetriesExhausted(2));
+
+    converter
+        .redirect_stuck_rewards(2, "community".to_owned())
+        .unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance("community", JUNO).unwrap(),
+        coin(100, JUNO)
+    );
+
+    let stuck = converter.stuck_rewards(None, None).unwrap().rewards;
+    assert_eq!(stuck.len(), 1);
+    assert_eq!(stuck[0].id, 1);
+}
+
+#[test]
+fn setting_max_external_stake() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40);
+    let native_per_foreign = Decimal::percent(50);
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+    assert_eq!(converter.config().unwrap().max_external_stake, None);
+
+    // Only the owner can set it
+    let err = converter
+        .set_max_external_stake(Some(Uint128::new(1000)))
+        .call(admin)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    converter
+        .set_max_external_stake(Some(Uint128::new(1000)))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter.config().unwrap().max_external_stake,
+        Some(Uint128::new(1000))
+    );
+
+    converter.set_max_external_stake(None).call(owner).unwrap();
+    assert_eq!(converter.config().unwrap().max_external_stake, None);
+}
+
+#[test]
+fn valset_update_chunking() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40);
+    let native_per_foreign = Decimal::percent(50);
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+    assert_eq!(
+        converter.config().unwrap().max_valset_batch,
+        DEFAULT_VALSET_BATCH
+    );
+
+    // Only the owner can set the batch size, and it can't be zero
+    let err = converter
+        .set_max_valset_batch(Some(200))
+        .call(admin)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = converter
+        .set_max_valset_batch(Some(0))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidValsetBatch);
+
+    let valopers: Vec<String> = (0..530).map(|i| format!("valoper{i:03}")).collect();
+    let update = ConsumerPacket::ValsetUpdate {
+        height: 100,
+        time: 1234,
+        additions: valopers[..520]
+            .iter()
+            .map(|v| AddValidator::mock(v))
+            .collect(),
+        removals: valopers[520..].to_vec(),
+        updated: vec![],
+        jailed: vec![],
+        unjailed: vec![],
+        tombstoned: vec![],
+        slashed: vec![],
+        uptimes: vec![],
+        chunk: None,
+    };
+    let chunks = |converter: &Proxy<'_, MtApp, ConverterContract<'_>>, skip: usize| {
+        converter
+            .outbox(None, Some(30))
+            .unwrap()
+            .packets
+            .into_iter()
+            .skip(skip)
+            .map(|info| match info.packet {
+                ConsumerPacket::ValsetUpdate {
+                    additions,
+                    removals,
+                    chunk,
+                    ..
+                } => (additions.len(), removals.len(), chunk),
+                packet => panic!("unexpected packet {packet:?}"),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // 530 validator entries are split in batches of 100, without hints for the test channel
+    converter
+        .test_send_valset_update(update.clone())
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        chunks(&converter, 0),
+        vec![
+            (100, 0, None),
+            (100, 0, None),
+            (100, 0, None),
+            (100, 0, None),
+            (100, 0, None),
+            (20, 10, None),
+        ]
+    );
+
+    // Bigger batches, with hints once the channel supports them
+    converter
+        .set_max_valset_batch(Some(200))
+        .call(owner)
+        .unwrap();
+    assert_eq!(converter.config().unwrap().max_valset_batch, 200);
+    converter
+        .test_set_channel_features(Features::VALSET_CHUNKS)
+        .call(owner)
+        .unwrap();
+    converter
+        .test_send_valset_update(update)
+        .call(owner)
+        .unwrap();
+    let chunk = |index, total| Some(ValsetChunk { index, total });
+    assert_eq!(
+        chunks(&converter, 6),
+        vec![
+            (200, 0, chunk(0, 3)),
+            (200, 0, chunk(1, 3)),
+            (120, 10, chunk(2, 3)),
+        ]
+    );
+
+    // Small updates are sent whole
+    converter
+        .test_send_valset_update(ConsumerPacket::ValsetUpdate {
+            height: 200,
+            time: 2234,
+            additions: vec![],
+            removals: vec!["valoper000".to_owned()],
+            updated: vec![],
+            jailed: vec![],
+            unjailed: vec![],
+            tombstoned: vec![],
+            slashed: vec![],
+            uptimes: vec![],
+            chunk: None,
+        })
+        .call(owner)
+        .unwrap();
+    assert_eq!(chunks(&converter, 9), vec![(0, 1, None)]);
+
+    converter.set_max_valset_batch(None).call(owner).unwrap();
+    assert_eq!(
+        converter.config().unwrap().max_valset_batch,
+        DEFAULT_VALSET_BATCH
+    );
+}
+
+#[test]
+fn validator_uptimes() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount: Decimal::percent(40),
+            native_per_foreign: Decimal::percent(50),
+        },
+    );
+
+    let uptime = ValidatorUptime {
+        address: "valoper000".to_owned(),
+        missed_blocks: 150,
+        window: 1000,
+    };
+    assert_eq!(uptime.uptime(), Decimal::percent(85));
+    let update = |removals: Vec<String>| ConsumerPacket::ValsetUpdate {
+        height: 100,
+        time: 1234,
+        additions: vec![],
+        removals,
+        updated: vec![],
+        jailed: vec![],
+        unjailed: vec![],
+        tombstoned: vec![],
+        slashed: vec![],
+        uptimes: vec![uptime.clone()],
+        chunk: None,
+    };
+    let outbox = |converter: &Proxy<'_, MtApp, ConverterContract<'_>>| {
+        converter
+            .outbox(None, Some(30))
+            .unwrap()
+            .packets
+            .into_iter()
+            .map(|info| info.packet)
+            .collect::<Vec<_>>()
+    };
+
+    // Uptimes are dropped for providers not supporting them, along with the packet if nothing
+    // else is left
+    converter
+        .test_send_valset_update(update(vec![]))
+        .call(owner)
+        .unwrap();
+    assert_eq!(outbox(&converter), []);
+    converter
+        .test_send_valset_update(update(vec!["valoper001".to_owned()]))
+        .call(owner)
+        .unwrap();
+    let packets = outbox(&converter);
+    assert!(matches!(
+        &packets[..],
+        [ConsumerPacket::ValsetUpdate { removals, uptimes, .. }]
+            if removals.len() == 1 && uptimes.is_empty()
+    ));
+
+    // And sent as they are otherwise
+    converter
+        .test_set_channel_features(Features::VALIDATOR_HEALTH)
+        .call(owner)
+        .unwrap();
+    converter
+        .test_send_valset_update(update(vec![]))
+        .call(owner)
+        .unwrap();
+    let packets = outbox(&converter);
+    assert_eq!(packets.len(), 2);
+    assert_eq!(packets[1], update(vec![]));
+}
+
+#[test]
+fn rewards_routing() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40);
+    let native_per_foreign = Decimal::percent(50);
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    let routed_channel = "channel-7";
+    let err = converter
+        .set_rewards_routing(Some(routed_channel.to_owned()))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UnknownChannel(routed_channel.to_owned())
+    );
+
+    // Provider channels, one of them not to a contract
+    for (channel_id, port_id) in [
+        (routed_channel, "wasm.osmo1provider"),
+        ("channel-8", "transfer"),
+    ] {
+        converter
+            .test_connect_channel(provider_channel(channel_id, port_id))
+            .call(owner)
+            .unwrap();
+    }
+
+    let err = converter
+        .set_rewards_routing(Some("channel-8".to_owned()))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotContractPort("transfer".to_owned()));
+
+    // Only the owner can route the rewards
+    let err = converter
+        .set_rewards_routing(Some(routed_channel.to_owned()))
+        .call(admin)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    converter
+        .set_rewards_routing(Some(routed_channel.to_owned()))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter.config().unwrap().rewards_routing_channel,
+        Some(routed_channel.to_owned())
+    );
+
+    // Routed transfer, as tracked after the transfer reply
+    let rewards = vec![
+        RewardInfo {
+            validator: "alice".to_owned(),
+            reward: Uint128::new(60),
+        },
+        RewardInfo {
+            validator: "bob".to_owned(),
+            reward: Uint128::new(40),
+        },
+    ];
+    let pending = PendingTransfer {
+        recipient: "osmo1provider".to_owned(),
+        rewards: coin(100, JUNO),
+        failures: 0,
+        stuck_id: None,
+        routed: Some(RoutedRewards {
+            channel_id: routed_channel.to_owned(),
+            rewards: rewards.clone(),
+        }),
+    };
+    converter
+        .test_track_transfer(TRANSFER_CHANNEL.to_owned(), 1, pending)
+        .call(owner)
+        .unwrap();
+
+    // Failed routed transfers are credited back to the channel, not queued for a retry
+    converter
+        .ibc_lifecycle_complete(
+            None,
+            Some(IbcLifecycleTimeout {
+                channel: TRANSFER_CHANNEL.to_owned(),
+                sequence: 1,
+            }),
+        )
+        .unwrap();
+    assert_eq!(converter.stuck_rewards(None, None).unwrap().rewards, []);
+    assert_eq!(
+        converter
+            .undistributed_rewards(routed_channel.to_owned())
+            .unwrap()
+            .rewards,
+        rewards
+    );
+
+    converter.set_rewards_routing(None).call(owner).unwrap();
+    assert_eq!(converter.config().unwrap().rewards_routing_channel, None);
+}
+
+#[test]
+fn discount_curve() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let admin = "theman";
+    let discount = Decimal::percent(40); // 1 OSMO worth of JUNO should give 0.6 OSMO of stake
+    let native_per_foreign = Decimal::percent(50); // 1 JUNO is worth 0.5 OSMO
+
+    let SetupResponse {
+        converter,
+        virtual_staking,
+        ..
+    } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin,
+            discount,
+            native_per_foreign,
+        },
+    );
+
+    // The discount is flat by default
+    let config = converter.config().unwrap();
+    assert_eq!(
+        config.curve,
+        vec![CurveSegment {
+            from: Uint128::zero(),
+            adjustment: Decimal::percent(60),
+        }]
+    );
+
+    // Stake above 1000 JUNO on a validator only counts for 30%
+    let curve = vec![
+        CurveSegment {
+            from: Uint128::zero(),
+            adjustment: Decimal::percent(60),
+        },
+        CurveSegment {
+            from: Uint128::new(1000),
+            adjustment: Decimal::percent(30),
+        },
+    ];
+
+    // Only the owner can update it, and the discount can't decrease with the amount
+    let err = converter
+        .update_curve(curve.clone())
+        .call(admin)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = converter
+        .update_curve(curve.iter().rev().cloned().collect())
+        .call(owner)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidCurve(_)));
+    converter.update_curve(curve.clone()).call(owner).unwrap();
+    assert_eq!(converter.config().unwrap().curve, curve);
+
+    // (1000 * 0.6 + 1000 * 0.3) / 2000 = 0.45, and 2000 * 0.5 * 0.45 = 450
+    let weight = converter
+        .effective_weight(Uint128::new(2000), None)
+        .unwrap();
+    assert_eq!(weight.adjustment, Decimal::percent(45));
+    assert_eq!(weight.virtual_stake, coin(450, "TOKEN"));
+
+    // Later stakes on the validator get the steeper discount
+    let val1 = "Val Kilmer";
+    converter
+        .test_stake(val1.to_string(), coin(1500, JUNO))
+        .call(owner)
+        .unwrap();
+    // (1000 * 0.6 + 500 * 0.3) * 0.5 = 375
+    assert_eq!(
+        virtual_staking
+            .stake(val1.to_string())
+            .unwrap()
+            .stake
+            .u128(),
+        375
+    );
+    converter
+        .test_stake(val1.to_string(), coin(500, JUNO))
+        .call(owner)
+        .unwrap();
+    // 375 + 500 * 0.3 * 0.5 = 450
+    assert_eq!(
+        virtual_staking
+            .stake(val1.to_string())
+            .unwrap()
+            .stake
+            .u128(),
+        450
+    );
+
+    // Unstaking releases the most discounted stake first
+    converter
+        .test_unstake(val1.to_string(), coin(500, JUNO))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        virtual_staking
+            .stake(val1.to_string())
+            .unwrap()
+            .stake
+            .u128(),
+        375
+    );
+}
+
+#[test]
+fn relayer_incentives() {
+    let app = new_app();
+
+    let owner = "sunny";
+    let relayer1 = "relayer1";
+    let relayer2 = "relayer2";
+
+    let SetupResponse { converter, .. } = setup(
+        &app,
+        SetupArgs {
+            owner,
+            admin: "theman",
+            discount: Decimal::percent(10),
+            native_per_foreign: Decimal::percent(40),
+        },
+    );
+
+    let err = converter
+        .set_relayer_fee(Some(Decimal::percent(1)))
+        .call(relayer1)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = converter
+        .set_relayer_fee(Some(Decimal::one()))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidRelayerFee);
+    converter
+        .set_relayer_fee(Some(Decimal::percent(1)))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter.config().unwrap().relayer_fee,
+        Some(Decimal::percent(1))
+    );
+
+    // Delivered packets are tracked by relayer
+    let stake = ProviderPacket::Stake {
+        validator: "alice".to_owned(),
+        stake: coin(100, JUNO),
+        tx_id: 1,
+    };
+    for relayer in [relayer1, relayer1, relayer1, relayer2] {
+        converter
+            .test_receive_packet(stake.clone())
+            .call(relayer)
+            .unwrap();
+    }
+    assert_eq!(
+        converter.relayer_rewards(relayer1.to_owned()).unwrap(),
+        RelayerRewardsResponse {
+            claimable: Uint128::zero(),
+            pending_packets: 3,
+        }
+    );
+    let err = converter
+        .claim_relayer_rewards()
+        .call(relayer1)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRelayerRewards);
+
+    // The fees collected with the next rewards distribution are split pro rata to the packets
+    // (distributions send IBC packets, unsupported by multitest)
+    app.app_mut()
+        .init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &converter.contract_addr, coins(100, "TOKEN"))
+        })
+        .unwrap();
+    converter
+        .test_collect_relayer_fees(Uint128::new(100))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        converter.relayer_rewards(relayer1.to_owned()).unwrap(),
+        RelayerRewardsResponse {
+            claimable: Uint128::new(75),
+            pending_packets: 0,
+        }
+    );
+    assert_eq!(
+        converter.relayer_rewards(relayer2.to_owned()).unwrap(),
+        RelayerRewardsResponse {
+            claimable: Uint128::new(25),
+            pending_packets: 0,
+        }
+    );
+
+    // Packets delivered after that are paid with the next distribution
+    converter.test_receive_packet(stake).call(relayer1).unwrap();
+    assert_eq!(
+        converter.relayer_rewards(relayer1.to_owned()).unwrap(),
+        RelayerRewardsResponse {
+            claimable: Uint128::new(75),
+            pending_packets: 1,
+        }
+    );
+
+    converter.claim_relayer_rewards().call(relayer1).unwrap();
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(relayer1, "TOKEN")
+            .unwrap()
+            .amount
+            .u128(),
+        75
+    );
+    let err = converter
+        .claim_relayer_rewards()
+        .call(relayer1)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRelayerRewards);
+}
+
+#[test]
+fn migrating_from_baseline() {
+    use cosmwasm_std::testing::{mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{
+        to_json_binary, ContractInfoResponse, ContractResult, OwnedDeps, Storage, SystemResult,
+        WasmQuery,
+    };
+    use mesh_apis::ownership_api;
+    use sylvia::types::MigrateCtx;
+
+    use crate::contract::{CONTRACT_NAME, CONTRACT_VERSION};
+
+    let mut deps = OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: MockQuerier::<custom::ConverterQuery>::new(&[]),
+        custom_query_type: std::marker::PhantomData,
+    };
+    let contract = ConverterContract::new();
+
+    // Config and version of the baseline release, with a single price adjustment and no owner
+    deps.storage.set(
+        b"config",
+        br#"{"price_adjustment":"0.6","price_feed":"price_feed","local_denom":"ujuno","remote_denom":"uosmo"}"#,
+    );
+    cw2::set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.10.0-alpha.1").unwrap();
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::ContractInfo { .. } => {
+            let mut info = ContractInfoResponse::default();
+            info.code_id = 1;
+            info.creator = "creator".to_owned();
+            info.admin = Some("admin".to_owned());
+            SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+        }
+        _ => unimplemented!(),
+    });
+
+    let ctx = MigrateCtx {
+        deps: deps.as_mut(),
+        env: mock_env(),
+    };
+    let resp = contract.migrate(ctx).unwrap();
+    assert_eq!(resp.events[0].ty, "migrate");
+
+    assert_eq!(
+        ownership_api::get_ownership(&deps.storage).unwrap().owner,
+        Some(Addr::unchecked("admin"))
+    );
+    // The price adjustment applies to any stake
+    let config = contract.config.load(&deps.storage).unwrap();
+    assert_eq!(
+        config.curve,
+        vec![CurveSegment {
+            from: Uint128::zero(),
+            adjustment: Decimal::percent(60),
+        }]
+    );
+    assert_eq!(config.remote_denom, "uosmo");
+    assert_eq!(config.transfer_channel, None);
+    assert_eq!(
+        cw2::get_contract_version(&deps.storage).unwrap().version,
+        CONTRACT_VERSION
+    );
+}
@@ -0,0 +1,135 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, StdResult, Storage, Uint128};
+use cw_storage_plus::{Item, Map};
+
+use crate::error::ContractError;
+use crate::msg::RelayerRewardsResponse;
+
+/// Packets delivered and fees collected since the last rewards distribution
+#[cw_serde]
+#[derive(Default)]
+pub struct RelayerPool {
+    pub period: u64,
+    pub packets: u64,
+    /// Fees collected while no packet was delivered are kept for the next period
+    pub fees: Uint128,
+}
+
+/// Fees of a past period, split among its packets
+#[cw_serde]
+pub struct RelayerPeriod {
+    pub packets: u64,
+    pub fees: Uint128,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct RelayerInfo {
+    /// Period of `packets`
+    pub period: u64,
+    /// Packets delivered in `period`, not paid yet
+    pub packets: u64,
+    /// Paid fees, waiting to be claimed
+    pub earned: Uint128,
+}
+
+/// Relayer incentives, paid out of a portion of the distributed rewards.
+///
+/// Fees are collected with every rewards distribution, and split among the relayers pro rata to
+/// the provider packets they delivered since the previous one. Relayers are paid lazily, when
+/// they deliver their next packet, claim or are queried.
+pub struct RelayerIncentives<'a> {
+    pub pool: Item<'a, RelayerPool>,
+    pub periods: Map<'a, u64, RelayerPeriod>,
+    pub relayers: Map<'a, &'a Addr, RelayerInfo>,
+}
+
+impl<'a> RelayerIncentives<'a> {
+    pub const fn new(pool_key: &'a str, periods_key: &'a str, relayers_key: &'a str) -> Self {
+        Self {
+            pool: Item::new(pool_key),
+            periods: Map::new(periods_key),
+            relayers: Map::new(relayers_key),
+        }
+    }
+
+    /// Credits a packet delivered by `relayer`
+    pub fn record_packet(&self, storage: &mut dyn Storage, relayer: &Addr) -> StdResult<()> {
+        let mut pool = self.pool.may_load(storage)?.unwrap_or_default();
+        let mut info = self.settled(storage, &pool, relayer)?;
+        info.packets += 1;
+        pool.packets += 1;
+        self.relayers.save(storage, relayer, &info)?;
+        self.pool.save(storage, &pool)
+    }
+
+    /// Collects `fees`, and closes the period if any packet was delivered in it
+    pub fn collect(&self, storage: &mut dyn Storage, fees: Uint128) -> StdResult<()> {
+        let mut pool = self.pool.may_load(storage)?.unwrap_or_default();
+        pool.fees += fees;
+        if pool.packets > 0 && !pool.fees.is_zero() {
+            let period = RelayerPeriod {
+                packets: pool.packets,
+                fees: pool.fees,
+            };
+            self.periods.save(storage, pool.period, &period)?;
+            pool = RelayerPool {
+                period: pool.period + 1,
+                ..RelayerPool::default()
+            };
+        }
+        self.pool.save(storage, &pool)
+    }
+
+    /// Takes all the fees earned by `relayer`
+    pub fn claim(
+        &self,
+        storage: &mut dyn Storage,
+        relayer: &Addr,
+    ) -> Result<Uint128, ContractError> {
+        let pool = self.pool.may_load(storage)?.unwrap_or_default();
+        let mut info = self.settled(storage, &pool, relayer)?;
+        let earned = info.earned;
+        if earned.is_zero() {
+            return Err(ContractError::NoRelayerRewards);
+        }
+        info.earned = Uint128::zero();
+        self.relayers.save(storage, relayer, &info)?;
+        Ok(earned)
+    }
+
+    pub fn rewards(
+        &self,
+        storage: &dyn Storage,
+        relayer: &Addr,
+    ) -> StdResult<RelayerRewardsResponse> {
+        let pool = self.pool.may_load(storage)?.unwrap_or_default();
+        let info = self.settled(storage, &pool, relayer)?;
+        Ok(RelayerRewardsResponse {
+            claimable: info.earned,
+            pending_packets: info.packets,
+        })
+    }
+
+    /// Relayer info, with the packets of a closed period paid
+    fn settled(
+        &self,
+        storage: &dyn Storage,
+        pool: &RelayerPool,
+        relayer: &Addr,
+    ) -> StdResult<RelayerInfo> {
+        let mut info = self
+            .relayers
+            .may_load(storage, relayer)?
+            .unwrap_or_default();
+        if info.period < pool.period {
+            if info.packets > 0 {
+                let period = self.periods.load(storage, info.period)?;
+                info.earned += period.fees.multiply_ratio(info.packets, period.packets);
+            }
+            info.packets = 0;
+            info.period = pool.period;
+        }
+        Ok(info)
+    }
+}
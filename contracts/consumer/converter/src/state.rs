@@ -1,14 +1,18 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal};
+use cosmwasm_std::{Addr, Coin, Decimal, Timestamp, Uint128};
+use mesh_apis::converter_api::RewardInfo;
+use mesh_apis::ibc::ConsumerPacket;
+
+use crate::curve::CurveSegment;
 
 #[cw_serde]
 pub struct Config {
-    /// Adjustment to apply on top of the price feed.
+    /// Adjustments to apply on top of the price feed, depending on the stake on the validator.
     /// Adjustment of 1.0 means take normalized price.
     /// Adjustment of 0.0 means the foreign asset has no value.
     /// Adjustment of 0.4 means the foreign asset has 40% of value after conversion.
     /// Note this is (1.0 - discount)
-    pub price_adjustment: Decimal,
+    pub curve: Vec<CurveSegment>,
 
     /// Address of the contract we query for the price feed to normalize the foreign asset into native tokens.
     pub price_feed: Addr,
@@ -19,4 +23,118 @@ pub struct Config {
     /// Token being "virtually sent" over IBC.
     /// use remote via, eg "uosmo", not "ibc/4EF183..."
     pub remote_denom: String,
+
+    /// ICS-20 channel to the provider chain, used to transfer rewards to provider-side recipients
+    pub transfer_channel: Option<String>,
+
+    /// Max cross-stake accepted on each validator from the providers, in the provider-side denom.
+    /// Sent along with the validators in the valset updates
+    #[serde(default)]
+    pub max_external_stake: Option<Uint128>,
+
+    /// IBC channel whose rewards are sent over the ICS-20 transfer channel, instead of
+    /// `Distribute` packets. The transfer memo tells the provider contract the validators they
+    /// are for
+    #[serde(default)]
+    pub rewards_routing_channel: Option<String>,
+
+    /// Max number of validator entries per valset update packet, `DEFAULT_VALSET_BATCH` if not
+    /// set. Larger updates are split over several packets
+    #[serde(default)]
+    pub max_valset_batch: Option<u32>,
+
+    /// Portion of the distributed rewards paid to the relayers delivering the provider packets,
+    /// see `RelayerIncentives`
+    #[serde(default)]
+    pub relayer_fee: Option<Decimal>,
+}
+
+/// `Config` of the baseline release, with a single price adjustment instead of the curve
+#[cw_serde]
+pub struct LegacyConfig {
+    pub price_adjustment: Decimal,
+    pub price_feed: Addr,
+    pub local_denom: String,
+    pub remote_denom: String,
+}
+
+impl From<LegacyConfig> for Config {
+    fn from(legacy: LegacyConfig) -> Self {
+        Config {
+            curve: vec![CurveSegment {
+                from: Uint128::zero(),
+                adjustment: legacy.price_adjustment,
+            }],
+            price_feed: legacy.price_feed,
+            local_denom: legacy.local_denom,
+            remote_denom: legacy.remote_denom,
+            transfer_channel: None,
+            max_external_stake: None,
+            rewards_routing_channel: None,
+            max_valset_batch: None,
+            relayer_fee: None,
+        }
+    }
+}
+
+/// Max number of validator entries per valset update packet, unless configured otherwise
+pub const DEFAULT_VALSET_BATCH: u32 = 100;
+
+impl Config {
+    pub fn max_valset_batch(&self) -> u32 {
+        self.max_valset_batch.unwrap_or(DEFAULT_VALSET_BATCH)
+    }
+}
+
+/// Rewards transfer to the provider, waiting for its ICS-20 ack or timeout
+#[cw_serde]
+pub struct PendingTransfer {
+    /// Provider-side recipient
+    pub recipient: String,
+    pub rewards: Coin,
+    /// Number of previous failed attempts
+    pub failures: u32,
+    /// Id in the stuck rewards queue, if this is a retry
+    pub stuck_id: Option<u64>,
+    /// Validator rewards of the provider on the IBC channel, if this transfer distributes them
+    #[serde(default)]
+    pub routed: Option<RoutedRewards>,
+}
+
+/// Validator rewards sent to a provider contract with an ICS-20 transfer
+#[cw_serde]
+pub struct RoutedRewards {
+    /// IBC channel of the provider
+    pub channel_id: String,
+    pub rewards: Vec<RewardInfo>,
+}
+
+/// Rewards whose transfer to the provider failed, waiting for a retry
+#[cw_serde]
+pub struct StuckRewards {
+    /// Provider-side recipient
+    pub recipient: String,
+    pub rewards: Coin,
+    /// Number of failed attempts
+    pub failures: u32,
+    /// The transfer can be retried after this time
+    pub retry_at: Timestamp,
+}
+
+/// Packet sent to a provider, tracked in the outbox until acked
+#[cw_serde]
+pub struct OutboxPacket {
+    pub channel_id: String,
+    pub packet: ConsumerPacket,
+    pub status: OutboxStatus,
+    /// Number of times the packet was sent
+    pub attempts: u32,
+}
+
+#[cw_serde]
+pub enum OutboxStatus {
+    /// Sent, waiting for its ack or timeout
+    InFlight {},
+    /// Timed out, waiting to be sent again
+    Retriable {},
 }
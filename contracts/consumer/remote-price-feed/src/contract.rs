@@ -5,6 +5,7 @@ use cw_utils::nonpayable;
 use sylvia::types::{InstantiateCtx, QueryCtx, SudoCtx};
 use sylvia::{contract, schemars};
 
+use mesh_apis::error::MeshError;
 use mesh_apis::price_feed_api::{self, PriceFeedApi, PriceResponse};
 
 use crate::error::ContractError;
@@ -115,7 +116,7 @@ pub fn query_twap(deps: DepsMut, env: &Env) -> Result<Response, ContractError> {
     let channel = contract
         .channel
         .may_load(deps.storage)?
-        .ok_or(ContractError::IbcChannelNotOpen)?;
+        .ok_or(MeshError::ChannelNotOpen)?;
 
     let packet = mesh_apis::ibc::RemotePriceFeedPacket::QueryTwap {
         pool_id,
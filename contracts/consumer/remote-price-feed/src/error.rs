@@ -1,5 +1,6 @@
 use cosmwasm_std::StdError;
 use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::VersionError;
 use thiserror::Error;
 
@@ -19,18 +20,12 @@ pub enum ContractError {
     #[error("{0}")]
     PriceKeeper(#[from] PriceKeeperError),
 
-    #[error("Unauthorized")]
-    Unauthorized,
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
     #[error("Invalid authorized endpoint: {0}")]
     InvalidEndpoint(String),
 
-    #[error("Contract doesn't have an open IBC channel")]
-    IbcChannelNotOpen,
-
-    #[error("Contract already has an open IBC channel")]
-    IbcChannelAlreadyOpen,
-
     #[error("You must start the channel handshake on the other side, it doesn't support OpenInit")]
     IbcOpenInitDisallowed,
 
@@ -8,6 +8,7 @@ use cosmwasm_std::{
     Timestamp,
 };
 use cw_storage_plus::Item;
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::{
     validate_channel_order, PriceFeedProviderAck, ProtocolVersion, RemotePriceFeedPacket,
 };
@@ -41,7 +42,7 @@ pub fn ibc_channel_open(
     // ensure we have no channel yet
     let contract = RemotePriceFeedContract::new();
     if contract.channel.may_load(deps.storage)?.is_some() {
-        return Err(ContractError::IbcChannelAlreadyOpen);
+        return Err(MeshError::ChannelAlreadyOpen.into());
     }
     // ensure we are called with OpenInit
     let (channel, counterparty_version) = match msg {
@@ -61,7 +62,7 @@ pub fn ibc_channel_open(
         || authorized.port_id != channel.counterparty_endpoint.port_id
     {
         // FIXME: do we need a better error here?
-        return Err(ContractError::Unauthorized);
+        return Err(MeshError::Unauthorized.into());
     }
 
     // we handshake with the counterparty version, it must not be empty
@@ -86,7 +87,7 @@ pub fn ibc_channel_connect(
 
     // ensure we have no channel yet
     if contract.channel.may_load(deps.storage)?.is_some() {
-        return Err(ContractError::IbcChannelAlreadyOpen);
+        return Err(MeshError::ChannelAlreadyOpen.into());
     }
     // ensure we are called with OpenConfirm
     let channel = match msg {
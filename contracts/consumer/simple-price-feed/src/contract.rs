@@ -5,6 +5,7 @@ use cw_utils::nonpayable;
 use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, SudoCtx};
 use sylvia::{contract, schemars};
 
+use mesh_apis::error::MeshError;
 use mesh_apis::price_feed_api::{self, PriceFeedApi, PriceResponse};
 
 use crate::error::ContractError;
@@ -81,11 +82,7 @@ impl SimplePriceFeedContract<'_> {
         let mut config = self.config.load(ctx.deps.storage)?;
 
         // Only allow owner to call this
-        ensure_eq!(
-            ctx.info.sender,
-            config.owner,
-            ContractError::Unauthorized {}
-        );
+        ensure_eq!(ctx.info.sender, config.owner, MeshError::Unauthorized);
 
         config.native_per_foreign = native_per_foreign;
         self.config.save(ctx.deps.storage, &config)?;
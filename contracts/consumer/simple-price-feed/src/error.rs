@@ -1,5 +1,6 @@
 use cosmwasm_std::StdError;
 use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,6 +11,6 @@ pub enum ContractError {
     #[error("{0}")]
     Payment(#[from] PaymentError),
 
-    #[error("Unauthorized")]
-    Unauthorized,
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 }
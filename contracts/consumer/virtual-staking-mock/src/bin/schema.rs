@@ -0,0 +1,14 @@
+use cosmwasm_schema::write_api;
+
+use mesh_virtual_staking_mock_contract::contract::sv::{
+    ContractExecMsg, ContractQueryMsg, InstantiateMsg,
+};
+
+#[cfg(not(tarpaulin_include))]
+fn main() {
+    write_api! {
+        instantiate: InstantiateMsg,
+        execute: ContractExecMsg,
+        query: ContractQueryMsg,
+    }
+}
@@ -0,0 +1,470 @@
+use cosmwasm_std::{
+    coin, ensure, ensure_eq, to_json_binary, Coin, Decimal, Env, Event, Order, Response, StdResult,
+    Storage, Uint128, Validator, WasmMsg, WeightedVoteOption,
+};
+use cw2::set_contract_version;
+use cw_storage_plus::{Deque, Item, Map};
+use cw_utils::nonpayable;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, SudoCtx};
+use sylvia::{contract, schemars};
+
+use mesh_apis::converter_api::{self, RewardInfo, ValidatorSlashInfo, ValidatorUptime};
+use mesh_apis::error::MeshError;
+use mesh_apis::virtual_staking_api::{
+    self, ValidatorBondStatus, ValidatorSlash, VirtualStakingApi,
+};
+
+use crate::error::ContractError;
+use crate::msg::{
+    AllStakeResponse, ConfigResponse, ScriptResponse, ScriptedEpoch, StakeResponse, VoteResponse,
+};
+use crate::state::Config;
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg(not(feature = "fake-custom"))]
+pub mod custom {
+    pub type VirtualStakingMsg = cosmwasm_std::Empty;
+    pub type VirtualStakingQuery = cosmwasm_std::Empty;
+    pub type Response = cosmwasm_std::Response<cosmwasm_std::Empty>;
+}
+#[cfg(feature = "fake-custom")]
+pub mod custom {
+    pub type VirtualStakingMsg = mesh_bindings::VirtualStakeCustomMsg;
+    pub type VirtualStakingQuery = mesh_bindings::VirtualStakeCustomQuery;
+    pub type Response = cosmwasm_std::Response<VirtualStakingMsg>;
+}
+
+/// Virtual staking stand-in for tests, simulating the consumer chain without the virtual staking
+/// sdk module.
+/// Bond requests are applied right away, and what happens on the chain on every epoch (rewards,
+/// slashes and valset changes) is scripted in advance. Epochs are run in order by the
+/// `handle_epoch` sudo, or by `advance_epoch`.
+pub struct VirtualStakingMock<'a> {
+    config: Item<'a, Config>,
+    /// Epochs to run, in order
+    script: Deque<'a, ScriptedEpoch>,
+    /// Number of epochs run so far
+    epoch: Item<'a, u64>,
+    stake: Map<'a, &'a str, Uint128>,
+    /// Last vote cast on each proposal
+    votes: Map<'a, u64, Vec<WeightedVoteOption>>,
+}
+
+impl Default for VirtualStakingMock<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(not(feature = "library"), sylvia::entry_points)]
+#[contract]
+#[sv::error(ContractError)]
+#[sv::messages(virtual_staking_api as VirtualStakingApi)]
+#[sv::custom(query=custom::VirtualStakingQuery, msg=custom::VirtualStakingMsg)]
+impl VirtualStakingMock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            config: Item::new("config"),
+            script: Deque::new("script"),
+            epoch: Item::new("epoch"),
+            stake: Map::new("stake"),
+            votes: Map::new("votes"),
+        }
+    }
+
+    /// Instantiated by the converter, like the virtual staking contract
+    #[sv::msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx<custom::VirtualStakingQuery>,
+        // The mock has no owner-only operations
+        _owner: Option<String>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        let denom = ctx.deps.querier.query_bonded_denom()?;
+        let config = Config {
+            denom,
+            converter: ctx.info.sender,
+        };
+        self.config.save(ctx.deps.storage, &config)?;
+        self.epoch.save(ctx.deps.storage, &0)?;
+
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+        Ok(Response::new())
+    }
+
+    /// Appends `epochs` to the script.
+    /// Rewards are paid out of the contract balance, so it must be funded beforehand
+    #[sv::msg(exec)]
+    fn push_epochs(
+        &self,
+        ctx: ExecCtx<custom::VirtualStakingQuery>,
+        epochs: Vec<ScriptedEpoch>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        for epoch in &epochs {
+            ensure!(
+                epoch.slashed.iter().all(|s| s.ratio <= Decimal::one()),
+                ContractError::InvalidSlashRatio
+            );
+            self.script.push_back(ctx.deps.storage, epoch)?;
+        }
+        Ok(Response::new())
+    }
+
+    /// Runs the next scripted epoch, as `handle_epoch` does.
+    /// An empty epoch is run if there's nothing scripted
+    #[sv::msg(exec)]
+    fn advance_epoch(
+        &self,
+        ctx: ExecCtx<custom::VirtualStakingQuery>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.run_epoch(ctx.deps.storage, &ctx.env)
+    }
+
+    #[sv::msg(query)]
+    fn config(
+        &self,
+        ctx: QueryCtx<custom::VirtualStakingQuery>,
+    ) -> Result<ConfigResponse, ContractError> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+        let denom = cfg.denom;
+        let converter = cfg.converter.into_string();
+        Ok(ConfigResponse { denom, converter })
+    }
+
+    /// Overrides the stake on `validator`, to simulate bookkeeping drifting from the converter
+    #[sv::msg(exec)]
+    fn force_stake(
+        &self,
+        ctx: ExecCtx<custom::VirtualStakingQuery>,
+        validator: String,
+        stake: Uint128,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.stake.save(ctx.deps.storage, &validator, &stake)?;
+        Ok(Response::new())
+    }
+
+    #[sv::msg(query)]
+    fn script(
+        &self,
+        ctx: QueryCtx<custom::VirtualStakingQuery>,
+    ) -> Result<ScriptResponse, ContractError> {
+        let epoch = self.epoch.may_load(ctx.deps.storage)?.unwrap_or_default();
+        let pending = self
+            .script
+            .iter(ctx.deps.storage)?
+            .collect::<StdResult<_>>()?;
+        Ok(ScriptResponse { epoch, pending })
+    }
+
+    #[sv::msg(query)]
+    fn stake(
+        &self,
+        ctx: QueryCtx<custom::VirtualStakingQuery>,
+        validator: String,
+    ) -> Result<StakeResponse, ContractError> {
+        let stake = self
+            .stake
+            .may_load(ctx.deps.storage, &validator)?
+            .unwrap_or_default();
+        Ok(StakeResponse { stake })
+    }
+
+    #[sv::msg(query)]
+    fn all_stake(
+        &self,
+        ctx: QueryCtx<custom::VirtualStakingQuery>,
+    ) -> Result<AllStakeResponse, ContractError> {
+        let stakes = self
+            .stake
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        Ok(AllStakeResponse { stakes })
+    }
+
+    #[sv::msg(query)]
+    fn last_vote(
+        &self,
+        ctx: QueryCtx<custom::VirtualStakingQuery>,
+        proposal_id: u64,
+    ) -> Result<VoteResponse, ContractError> {
+        let options = self
+            .votes
+            .may_load(ctx.deps.storage, proposal_id)?
+            .unwrap_or_default();
+        Ok(VoteResponse { options })
+    }
+
+    /// Pops the next scripted epoch, applies its slashes, and reports its valset changes and
+    /// rewards to the converter
+    fn run_epoch(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+    ) -> Result<custom::Response, ContractError> {
+        let cfg = self.config.load(storage)?;
+        let epoch = self.epoch.may_load(storage)?.unwrap_or_default() + 1;
+        self.epoch.save(storage, &epoch)?;
+        let script = self.script.pop_front(storage)?.unwrap_or_default();
+
+        let mut resp = Response::new();
+
+        if script.has_valset_changes() {
+            let mut slashed = vec![];
+            for slash in &script.slashed {
+                let stake = self
+                    .stake
+                    .may_load(storage, &slash.validator)?
+                    .unwrap_or_default();
+                let amount = stake * slash.ratio;
+                self.stake
+                    .save(storage, &slash.validator, &(stake - amount))?;
+                slashed.push(ValidatorSlashInfo {
+                    address: slash.validator.clone(),
+                    infraction_height: env.block.height,
+                    infraction_time: env.block.time.seconds(),
+                    power: u64::try_from(stake.u128()).unwrap_or(u64::MAX),
+                    slash_amount: coin(amount.u128(), &cfg.denom),
+                    slash_ratio: slash.ratio.to_string(),
+                });
+            }
+            let msg = converter_api::sv::ExecMsg::ValsetUpdate {
+                additions: script.additions,
+                removals: script.removals,
+                updated: script.updated,
+                jailed: script.jailed,
+                unjailed: script.unjailed,
+                tombstoned: script.tombstoned,
+                slashed,
+                uptimes: script.uptimes,
+            };
+            resp = resp.add_message(WasmMsg::Execute {
+                contract_addr: cfg.converter.to_string(),
+                msg: to_json_binary(&msg)?,
+                funds: vec![],
+            });
+        }
+
+        let payments: Vec<RewardInfo> = script
+            .rewards
+            .into_iter()
+            .filter(|r| !r.reward.is_zero())
+            .collect();
+        let total: Uint128 = payments.iter().map(|r| r.reward).sum();
+        if !payments.is_empty() {
+            let msg = converter_api::sv::ExecMsg::DistributeRewards { payments };
+            resp = resp.add_message(WasmMsg::Execute {
+                contract_addr: cfg.converter.into_string(),
+                msg: to_json_binary(&msg)?,
+                funds: vec![coin(total.u128(), cfg.denom)],
+            });
+        }
+
+        let evt = Event::new("scripted_epoch")
+            .add_attribute("epoch", epoch.to_string())
+            .add_attribute("rewards", total.to_string())
+            .add_attribute("remaining", self.script.len(storage)?.to_string());
+        Ok(resp.add_event(evt))
+    }
+}
+
+impl VirtualStakingApi for VirtualStakingMock<'_> {
+    type Error = ContractError;
+    type ExecC = custom::VirtualStakingMsg;
+    type QueryC = custom::VirtualStakingQuery;
+
+    /// Bonds right away, as there's no staking module to delegate to
+    fn bond(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+        // only the converter can call this
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized);
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
+
+        self.stake
+            .update::<_, ContractError>(ctx.deps.storage, &validator, |old| {
+                Ok(old.unwrap_or_default() + amount.amount)
+            })?;
+
+        Ok(Response::new())
+    }
+
+    /// Unbonds right away
+    fn unbond(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+        // only the converter can call this
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized);
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
+
+        self.stake
+            .update::<_, ContractError>(ctx.deps.storage, &validator, |old| {
+                old.unwrap_or_default()
+                    .checked_sub(amount.amount)
+                    .map_err(|_| ContractError::InsufficientBond(validator.clone(), amount.amount))
+            })?;
+
+        Ok(Response::new())
+    }
+
+    /// Burns right away, distributed over `validators` like the virtual staking contract does
+    fn burn(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        validators: Vec<String>,
+        amount: Coin,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+        // only the converter can call this
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized);
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
+
+        let mut stakes = vec![];
+        for validator in validators {
+            let stake = self
+                .stake
+                .may_load(ctx.deps.storage, &validator)?
+                .unwrap_or_default()
+                .u128();
+            if stake != 0 {
+                stakes.push((validator, stake));
+            }
+        }
+
+        let (burned, burns) = mesh_burn::distribute_burn(stakes.as_slice(), amount.amount.u128());
+        // Bail if we don't have enough stake
+        if stakes.is_empty() || burned < amount.amount.u128() {
+            return Err(ContractError::InsufficientDelegations(
+                ctx.env.contract.address.to_string(),
+                amount.amount,
+            ));
+        }
+
+        for (validator, burn_amount) in burns {
+            self.stake
+                .update::<_, ContractError>(ctx.deps.storage, validator, |old| {
+                    Ok(old.unwrap_or_default() - Uint128::new(burn_amount))
+                })?;
+        }
+
+        Ok(Response::new())
+    }
+
+    /// Records the vote, instead of sending it to the gov module
+    fn vote(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        proposal_id: u64,
+        options: Vec<WeightedVoteOption>,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+        // only the converter can call this
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized);
+
+        self.votes.save(ctx.deps.storage, proposal_id, &options)?;
+
+        Ok(Response::new())
+    }
+
+    /// The stake is bonded as soon as it's requested
+    fn bond_status(
+        &self,
+        ctx: QueryCtx<Self::QueryC>,
+        validator: String,
+    ) -> Result<ValidatorBondStatus, Self::Error> {
+        let stake = self
+            .stake
+            .may_load(ctx.deps.storage, &validator)?
+            .unwrap_or_default();
+        Ok(ValidatorBondStatus {
+            validator,
+            bonded: stake,
+            requested: stake,
+        })
+    }
+
+    /// Runs the next scripted epoch
+    fn handle_epoch(
+        &self,
+        ctx: SudoCtx<Self::QueryC>,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        self.run_epoch(ctx.deps.storage, &ctx.env)
+    }
+
+    /// The mock has no max cap
+    fn handle_max_cap_changed(
+        &self,
+        _ctx: SudoCtx<Self::QueryC>,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        Ok(Response::new())
+    }
+
+    /// Applies the slashes, and forwards the updates to the converter, out of the script
+    #[allow(clippy::too_many_arguments)]
+    fn handle_valset_update(
+        &self,
+        ctx: SudoCtx<Self::QueryC>,
+        additions: Option<Vec<Validator>>,
+        removals: Option<Vec<String>>,
+        updated: Option<Vec<Validator>>,
+        jailed: Option<Vec<String>>,
+        unjailed: Option<Vec<String>>,
+        tombstoned: Option<Vec<String>>,
+        slashed: Option<Vec<ValidatorSlash>>,
+        uptimes: Option<Vec<ValidatorUptime>>,
+    ) -> Result<Response<Self::ExecC>, Self::Error> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+
+        let slashed = slashed.unwrap_or_default();
+        for slash in &slashed {
+            self.stake
+                .update::<_, ContractError>(ctx.deps.storage, &slash.address, |old| {
+                    Ok(old.unwrap_or_default().saturating_sub(slash.slash_amount))
+                })?;
+        }
+
+        let msg = converter_api::sv::ExecMsg::ValsetUpdate {
+            additions: additions.unwrap_or_default(),
+            removals: removals.unwrap_or_default(),
+            updated: updated.unwrap_or_default(),
+            jailed: jailed.unwrap_or_default(),
+            unjailed: unjailed.unwrap_or_default(),
+            tombstoned: tombstoned.unwrap_or_default(),
+            slashed: slashed
+                .into_iter()
+                .map(|s| ValidatorSlashInfo {
+                    address: s.address,
+                    infraction_height: s.infraction_height,
+                    infraction_time: s.infraction_time,
+                    power: s.power,
+                    slash_amount: coin(s.slash_amount.u128(), &cfg.denom),
+                    slash_ratio: s.slash_ratio,
+                })
+                .collect(),
+            uptimes: uptimes.unwrap_or_default(),
+        };
+        let msg = WasmMsg::Execute {
+            contract_addr: cfg.converter.into_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(Response::new().add_message(msg))
+    }
+}
@@ -0,0 +1,25 @@
+use cosmwasm_std::{StdError, Uint128};
+use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
+
+    #[error("Cannot unbond {1} tokens from validator {0}, not enough staked")]
+    InsufficientBond(String, Uint128),
+
+    #[error("Virtual staking {0} has not enough delegated funds: {1}")]
+    InsufficientDelegations(String, Uint128),
+
+    #[error("Slash ratio must be at most 1")]
+    InvalidSlashRatio,
+}
@@ -0,0 +1,81 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal, Uint128, Validator, WeightedVoteOption};
+use mesh_apis::converter_api::{RewardInfo, ValidatorUptime};
+
+/// What happens on the consumer chain over one epoch. Everything is reported to the converter
+/// when the epoch ends
+#[cw_serde]
+#[derive(Default)]
+pub struct ScriptedEpoch {
+    /// Rewards earned by each validator over the epoch, in the staking denom.
+    /// They are paid out of the contract balance
+    #[serde(default)]
+    pub rewards: Vec<RewardInfo>,
+    #[serde(default)]
+    pub additions: Vec<Validator>,
+    #[serde(default)]
+    pub removals: Vec<String>,
+    #[serde(default)]
+    pub updated: Vec<Validator>,
+    #[serde(default)]
+    pub jailed: Vec<String>,
+    #[serde(default)]
+    pub unjailed: Vec<String>,
+    #[serde(default)]
+    pub tombstoned: Vec<String>,
+    /// Slashes over the stake delegated to each validator
+    #[serde(default)]
+    pub slashed: Vec<ScriptedSlash>,
+    #[serde(default)]
+    pub uptimes: Vec<ValidatorUptime>,
+}
+
+impl ScriptedEpoch {
+    /// Whether there is any valset change to report
+    pub fn has_valset_changes(&self) -> bool {
+        !(self.additions.is_empty()
+            && self.removals.is_empty()
+            && self.updated.is_empty()
+            && self.jailed.is_empty()
+            && self.unjailed.is_empty()
+            && self.tombstoned.is_empty()
+            && self.slashed.is_empty()
+            && self.uptimes.is_empty())
+    }
+}
+
+#[cw_serde]
+pub struct ScriptedSlash {
+    pub validator: String,
+    /// Part of the stake on the validator that is slashed
+    pub ratio: Decimal,
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub denom: String,
+    pub converter: String,
+}
+
+#[cw_serde]
+pub struct ScriptResponse {
+    /// Number of epochs run so far
+    pub epoch: u64,
+    /// Scripted epochs still to be run, in order
+    pub pending: Vec<ScriptedEpoch>,
+}
+
+#[cw_serde]
+pub struct StakeResponse {
+    pub stake: Uint128,
+}
+
+#[cw_serde]
+pub struct AllStakeResponse {
+    pub stakes: Vec<(String, Uint128)>,
+}
+
+#[cw_serde]
+pub struct VoteResponse {
+    pub options: Vec<WeightedVoteOption>,
+}
@@ -0,0 +1,242 @@
+use cosmwasm_std::{
+    coin, coins, to_json_binary, Addr, Binary, Coin, Decimal, Deps, DepsMut, Empty, Env,
+    MessageInfo, Response, StdResult, Uint128, Validator,
+};
+use cw_multi_test::{no_init, Contract, ContractWrapper, Executor};
+use cw_storage_plus::Item;
+use mesh_apis::converter_api::{self, RewardInfo, ValidatorSlashInfo};
+use mesh_apis::virtual_staking_api::sv::mt::VirtualStakingApiProxy;
+
+use crate::contract::custom;
+use crate::contract::sv::mt::{CodeId, VirtualStakingMockProxy};
+use crate::error::ContractError;
+use crate::msg::{ScriptedEpoch, ScriptedSlash};
+
+const DENOM: &str = "TOKEN";
+
+// Custom messages of the virtual staking contract with the `fake-custom` feature
+type MtApp = cw_multi_test::BasicApp<custom::VirtualStakingMsg, custom::VirtualStakingQuery>;
+type App = sylvia::multitest::App<MtApp>;
+
+type Received = Vec<(converter_api::sv::ExecMsg, Vec<Coin>)>;
+
+/// Messages received by the converter stand-in, with the funds sent along
+const RECEIVED: Item<Received> = Item::new("received");
+
+fn converter_instantiate(
+    _deps: DepsMut<custom::VirtualStakingQuery>,
+    _env: Env,
+    _info: MessageInfo,
+    _msg: Empty,
+) -> StdResult<custom::Response> {
+    Ok(Response::new())
+}
+
+fn converter_execute(
+    deps: DepsMut<custom::VirtualStakingQuery>,
+    _env: Env,
+    info: MessageInfo,
+    msg: converter_api::sv::ExecMsg,
+) -> StdResult<custom::Response> {
+    let mut received = RECEIVED.may_load(deps.storage)?.unwrap_or_default();
+    received.push((msg, info.funds));
+    RECEIVED.save(deps.storage, &received)?;
+    Ok(Response::new())
+}
+
+fn converter_query(
+    deps: Deps<custom::VirtualStakingQuery>,
+    _env: Env,
+    _msg: Empty,
+) -> StdResult<Binary> {
+    to_json_binary(&RECEIVED.may_load(deps.storage)?.unwrap_or_default())
+}
+
+/// Converter recording everything the virtual staking reports to it
+fn recording_converter() -> Box<dyn Contract<custom::VirtualStakingMsg, custom::VirtualStakingQuery>>
+{
+    Box::new(ContractWrapper::new(
+        converter_execute,
+        converter_instantiate,
+        converter_query,
+    ))
+}
+
+fn received(app: &App, converter: &Addr) -> Received {
+    app.app()
+        .wrap()
+        .query_wasm_smart(converter, &Empty {})
+        .unwrap()
+}
+
+fn validator(address: &str) -> Validator {
+    Validator {
+        address: address.to_owned(),
+        commission: Decimal::percent(5),
+        max_commission: Decimal::percent(10),
+        max_change_rate: Decimal::percent(1),
+    }
+}
+
+fn reward(validator: &str, amount: u128) -> RewardInfo {
+    RewardInfo {
+        validator: validator.to_owned(),
+        reward: Uint128::new(amount),
+    }
+}
+
+#[test]
+fn scripted_epochs() {
+    let app = App::new(cw_multi_test::custom_app(no_init));
+    let owner = "owner";
+    let val1 = "val1";
+    let val2 = "val2";
+
+    let converter_code = app.app_mut().store_code(recording_converter());
+    let converter = app
+        .app_mut()
+        .instantiate_contract(
+            converter_code,
+            Addr::unchecked(owner),
+            &Empty {},
+            &[],
+            "Converter",
+            None,
+        )
+        .unwrap();
+
+    // Instantiated by the converter, which it reports to
+    let mock = CodeId::store_code(&app)
+        .instantiate(Some(owner.to_owned()))
+        .call(converter.as_str())
+        .unwrap();
+    assert_eq!(mock.config().unwrap().converter, converter.to_string());
+    app.app_mut().init_modules(|router, _, storage| {
+        router
+            .bank
+            .init_balance(storage, &mock.contract_addr, coins(1000, DENOM))
+            .unwrap();
+    });
+
+    // Bonding is immediate
+    mock.bond(val1.to_owned(), coin(1000, DENOM))
+        .call(converter.as_str())
+        .unwrap();
+    mock.bond(val2.to_owned(), coin(500, DENOM))
+        .call(converter.as_str())
+        .unwrap();
+    assert_eq!(mock.stake(val1.to_owned()).unwrap().stake.u128(), 1000);
+
+    let err = mock
+        .push_epochs(vec![ScriptedEpoch {
+            slashed: vec![ScriptedSlash {
+                validator: val1.to_owned(),
+                ratio: Decimal::percent(101),
+            }],
+            ..Default::default()
+        }])
+        .call(owner)
+        .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidSlashRatio));
+
+    let epochs = vec![
+        ScriptedEpoch {
+            rewards: vec![reward(val1, 30), reward(val2, 0)],
+            additions: vec![validator("val3")],
+            ..Default::default()
+        },
+        ScriptedEpoch {
+            rewards: vec![reward(val2, 12)],
+            jailed: vec![val1.to_owned()],
+            slashed: vec![ScriptedSlash {
+                validator: val1.to_owned(),
+                ratio: Decimal::percent(10),
+            }],
+            ..Default::default()
+        },
+    ];
+    mock.push_epochs(epochs.clone()).call(owner).unwrap();
+    let script = mock.script().unwrap();
+    assert_eq!(script.epoch, 0);
+    assert_eq!(script.pending, epochs);
+
+    // First epoch: valset changes are reported before the rewards, paid along with them
+    mock.advance_epoch().call(owner).unwrap();
+    assert_eq!(
+        received(&app, &converter),
+        vec![
+            (
+                converter_api::sv::ExecMsg::ValsetUpdate {
+                    additions: vec![validator("val3")],
+                    removals: vec![],
+                    updated: vec![],
+                    jailed: vec![],
+                    unjailed: vec![],
+                    tombstoned: vec![],
+                    slashed: vec![],
+                    uptimes: vec![],
+                },
+                vec![],
+            ),
+            (
+                converter_api::sv::ExecMsg::DistributeRewards {
+                    payments: vec![reward(val1, 30)],
+                },
+                coins(30, DENOM),
+            ),
+        ]
+    );
+
+    // Second epoch: the slash is applied over the stake on the validator
+    mock.advance_epoch().call(owner).unwrap();
+    let block = app.app().block_info();
+    let messages = received(&app, &converter);
+    assert_eq!(messages.len(), 4);
+    assert_eq!(
+        messages[2],
+        (
+            converter_api::sv::ExecMsg::ValsetUpdate {
+                additions: vec![],
+                removals: vec![],
+                updated: vec![],
+                jailed: vec![val1.to_owned()],
+                unjailed: vec![],
+                tombstoned: vec![],
+                slashed: vec![ValidatorSlashInfo {
+                    address: val1.to_owned(),
+                    infraction_height: block.height,
+                    infraction_time: block.time.seconds(),
+                    power: 1000,
+                    slash_amount: coin(100, DENOM),
+                    slash_ratio: "0.1".to_owned(),
+                }],
+                uptimes: vec![],
+            },
+            vec![],
+        )
+    );
+    assert_eq!(
+        messages[3],
+        (
+            converter_api::sv::ExecMsg::DistributeRewards {
+                payments: vec![reward(val2, 12)],
+            },
+            coins(12, DENOM),
+        )
+    );
+    assert_eq!(mock.stake(val1.to_owned()).unwrap().stake.u128(), 900);
+    assert_eq!(mock.stake(val2.to_owned()).unwrap().stake.u128(), 500);
+
+    // Nothing left to run, so the next epochs are empty
+    mock.advance_epoch().call(owner).unwrap();
+    let script = mock.script().unwrap();
+    assert_eq!(script.epoch, 3);
+    assert!(script.pending.is_empty());
+    assert_eq!(received(&app, &converter).len(), 4);
+    let balance = app
+        .app()
+        .wrap()
+        .query_balance(&mock.contract_addr, DENOM)
+        .unwrap();
+    assert_eq!(balance.amount.u128(), 1000 - 30 - 12);
+}
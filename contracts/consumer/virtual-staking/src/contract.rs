@@ -2,28 +2,46 @@ use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 use cosmwasm_std::{
-    coin, ensure_eq, to_json_binary, Coin, CosmosMsg, CustomQuery, DepsMut, DistributionMsg, Env,
-    Event, Reply, Response, StdResult, Storage, SubMsg, Uint128, Validator, WasmMsg,
+    coin, coins, ensure, ensure_eq, to_json_binary, Coin, CosmosMsg, CustomQuery, Decimal, Deps,
+    DepsMut, DistributionMsg, Env, Event, GovMsg, Order, Reply, Response, StdResult, Storage,
+    SubMsg, Timestamp, Uint128, Validator, WasmMsg, WeightedVoteOption,
 };
-use cw2::set_contract_version;
-use cw_storage_plus::{Item, Map};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bounder, Item, Map};
 use cw_utils::nonpayable;
-use mesh_apis::converter_api::{self, RewardInfo, ValidatorSlashInfo};
+use mesh_apis::converter_api::{
+    self, ForcedUnbondInfo, RewardInfo, ValidatorSlashInfo, ValidatorUptime,
+};
 use mesh_bindings::{
     TokenQuerier, VirtualStakeCustomMsg, VirtualStakeCustomQuery, VirtualStakeMsg,
 };
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx, SudoCtx};
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx, ReplyCtx, SudoCtx};
 use sylvia::{contract, schemars};
 
+use mesh_apis::error::MeshError;
+use mesh_apis::migration::{Upgrade, OWNERSHIP_VERSION};
+use mesh_apis::ownership_api::{self, Ownership, OwnershipApi};
 use mesh_apis::virtual_staking_api::{self, ValidatorSlash, VirtualStakingApi};
 
 use crate::error::ContractError;
-use crate::msg::ConfigResponse;
-use crate::state::Config;
+use crate::msg::{
+    AllBondStatusesResponse, ConfigResponse, StrategyResponse, ValidatorBondStatus,
+    WithheldRewardsResponse,
+};
+use crate::state::{Config, WithholdingPolicy};
+use crate::strategy::{self, DistributionStrategy, StrategyTransition};
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+pub const DEFAULT_PAGE_LIMIT: u32 = 10;
+pub const MAX_PAGE_LIMIT: u32 = 30;
+
+/// Aligns pagination limit
+fn clamp_page_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize
+}
+
 pub struct VirtualStakingContract<'a> {
     pub config: Item<'a, Config>,
     /// Amount of tokens that have been requested to bond to a validator
@@ -44,16 +62,29 @@ pub struct VirtualStakingContract<'a> {
     // `inactive` could be a Map like `bond_requests`, but the only time we use it is to read / write the entire list in bulk (in handle_epoch),
     // never accessing one element. Reading 100 elements in an Item is much cheaper than ranging over a Map with 100 entries.
     pub inactive: Item<'a, Vec<String>>,
+    /// Jailed validators. Their bond can't be increased until they are unjailed.
+    pub jailed: Item<'a, Vec<String>>,
+    /// Tombstoned validators. Their bond is unbonded right away, and can't be increased anymore.
+    pub tombstoned: Item<'a, Vec<String>>,
     /// Amount of tokens that have been burned from a validator.
     /// This is just for accounting / tracking reasons, as token "burning" is being implemented as unbonding,
     /// and there's no real need to discount the burned amount in this contract.
     burned: Map<'a, &'a str, u128>,
+    /// Gradual switch to the current strategy, if in progress
+    pub strategy_transition: Item<'a, StrategyTransition>,
+    /// Epoch in progress, incremented by `handle_epoch`
+    pub epoch: Item<'a, u64>,
+    /// Combined ratio each validator was slashed by, by `(epoch, validator)`
+    pub epoch_slashes: Map<'a, (u64, &'a str), Decimal>,
+    /// Rewards withheld from the slashed validators, by `(epoch, validator)`
+    pub withheld_rewards: Map<'a, (u64, &'a str), Uint128>,
 }
 
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[sv::error(ContractError)]
 #[sv::messages(virtual_staking_api as VirtualStakingApi)]
+#[sv::messages(ownership_api as OwnershipApi)]
 // FIXME: how to handle custom messages for sudo?
 #[sv::custom(query=VirtualStakeCustomQuery, msg=VirtualStakeCustomMsg)]
 // #[sv::override_entry_point(sudo=sudo(SudoMsg))] // Disabled because lack of custom query support
@@ -65,33 +96,83 @@ impl VirtualStakingContract<'_> {
             bonded: Item::new("bonded"),
             slash_requests: Item::new("slashed"),
             inactive: Item::new("inactive"),
+            jailed: Item::new("jailed"),
+            tombstoned: Item::new("tombstoned"),
             burned: Map::new("burned"),
+            strategy_transition: Item::new("strategy_transition"),
+            epoch: Item::new("epoch"),
+            epoch_slashes: Map::new("epoch_slashes"),
+            withheld_rewards: Map::new("withheld_rewards"),
         }
     }
 
-    /// The caller of the instantiation will be the converter contract
+    /// The caller of the instantiation will be the converter contract, passing along its own
+    /// `owner`
     #[sv::msg(instantiate)]
     pub fn instantiate(
         &self,
         ctx: InstantiateCtx<VirtualStakeCustomQuery>,
+        owner: Option<String>,
     ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
         nonpayable(&ctx.info)?;
         let denom = ctx.deps.querier.query_bonded_denom()?;
         let config = Config {
             denom,
             converter: ctx.info.sender,
+            strategy: DistributionStrategy::default(),
+            withholding: WithholdingPolicy::default(),
         };
         self.config.save(ctx.deps.storage, &config)?;
         // initialize these to no one, so no issue when reading for the first time
         self.bonded.save(ctx.deps.storage, &vec![])?;
         self.slash_requests.save(ctx.deps.storage, &vec![])?;
         self.inactive.save(ctx.deps.storage, &vec![])?;
+        self.jailed.save(ctx.deps.storage, &vec![])?;
+        self.tombstoned.save(ctx.deps.storage, &vec![])?;
         VALIDATOR_REWARDS_BATCH.init(ctx.deps.storage)?;
+        let owner = owner
+            .map(|owner| ctx.deps.api.addr_validate(&owner))
+            .transpose()?;
+        ownership_api::initialize_owner(ctx.deps.storage, owner)?;
 
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
         Ok(Response::new())
     }
 
+    /// Migrates from an older version of the contract.
+    /// Contracts from before the ownership storage get their wasm admin as owner, and the
+    /// jailed and tombstoned validators lists they didn't track yet.
+    #[sv::msg(migrate)]
+    pub fn migrate(
+        &self,
+        ctx: MigrateCtx<VirtualStakeCustomQuery>,
+    ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
+        let stored = get_contract_version(ctx.deps.storage)?;
+        let upgrade = Upgrade::check(
+            &stored.contract,
+            &stored.version,
+            CONTRACT_NAME,
+            CONTRACT_VERSION,
+        )?;
+
+        if upgrade.crosses(OWNERSHIP_VERSION)? {
+            let admin = ownership_api::wasm_admin(&ctx.deps.querier, &ctx.env.contract.address)?;
+            ownership_api::migrate_owner(ctx.deps.storage, admin)?;
+            if !self.jailed.exists(ctx.deps.storage) {
+                self.jailed.save(ctx.deps.storage, &vec![])?;
+            }
+            if !self.tombstoned.exists(ctx.deps.storage) {
+                self.tombstoned.save(ctx.deps.storage, &vec![])?;
+            }
+        }
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        let event = Event::new("migrate")
+            .add_attribute("from_version", stored.version)
+            .add_attribute("to_version", CONTRACT_VERSION);
+        Ok(Response::new().add_event(event))
+    }
+
     #[sv::msg(query)]
     fn config(
         &self,
@@ -100,6 +181,136 @@ impl VirtualStakingContract<'_> {
         Ok(self.config.load(ctx.deps.storage)?.into())
     }
 
+    /// Switches the distribution strategy of the bonded tokens. The delegations are moved to the
+    /// new strategy gradually, over `rebalance_epochs` epochs, or all at once at the next epoch if
+    /// zero. Only the owner can call this, and not while a transition is in progress
+    #[sv::msg(exec)]
+    fn set_strategy(
+        &self,
+        ctx: ExecCtx<VirtualStakeCustomQuery>,
+        strategy: DistributionStrategy,
+        rebalance_epochs: u32,
+    ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        ensure!(
+            !self.strategy_transition.exists(ctx.deps.storage),
+            ContractError::StrategyTransitionInProgress
+        );
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        if rebalance_epochs > 0 {
+            let transition = StrategyTransition {
+                from: config.strategy,
+                epochs: rebalance_epochs,
+                elapsed: 0,
+            };
+            self.strategy_transition
+                .save(ctx.deps.storage, &transition)?;
+        }
+        config.strategy = strategy;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_strategy")
+            .add_attribute("rebalance_epochs", rebalance_epochs.to_string());
+        Ok(resp)
+    }
+
+    #[sv::msg(query)]
+    fn strategy(
+        &self,
+        ctx: QueryCtx<VirtualStakeCustomQuery>,
+    ) -> Result<StrategyResponse, ContractError> {
+        let strategy = self.config.load(ctx.deps.storage)?.strategy;
+        let transition = self.strategy_transition.may_load(ctx.deps.storage)?;
+        Ok(StrategyResponse {
+            strategy,
+            transition,
+        })
+    }
+
+    /// Sets what happens to the rewards withheld from the validators slashed during the epoch.
+    /// Only the owner can call this
+    #[sv::msg(exec)]
+    fn set_withholding_policy(
+        &self,
+        ctx: ExecCtx<VirtualStakeCustomQuery>,
+        policy: WithholdingPolicy,
+    ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.withholding = policy;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new().add_attribute("action", "set_withholding_policy"))
+    }
+
+    /// Slash ratio of `validator` during `epoch`, and the rewards of the epoch withheld from it
+    #[sv::msg(query)]
+    fn withheld_rewards(
+        &self,
+        ctx: QueryCtx<VirtualStakeCustomQuery>,
+        epoch: u64,
+        validator: String,
+    ) -> Result<WithheldRewardsResponse, ContractError> {
+        let policy = self.config.load(ctx.deps.storage)?.withholding;
+        let current_epoch = self.epoch.may_load(ctx.deps.storage)?.unwrap_or_default();
+        let key = (epoch, validator.as_str());
+        let slash_ratio = self
+            .epoch_slashes
+            .may_load(ctx.deps.storage, key)?
+            .unwrap_or_default();
+        let withheld = self
+            .withheld_rewards
+            .may_load(ctx.deps.storage, key)?
+            .unwrap_or_default();
+
+        Ok(WithheldRewardsResponse {
+            policy,
+            current_epoch,
+            slash_ratio,
+            withheld,
+        })
+    }
+
+    /// Bond status of all the validators ever requested to be bonded to.
+    /// `start_after` is the last validator included in previous page
+    #[sv::msg(query)]
+    fn all_bond_statuses(
+        &self,
+        ctx: QueryCtx<VirtualStakeCustomQuery>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<AllBondStatusesResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+
+        // Bonded validators are always a subset of the requested ones (see `handle_epoch`)
+        let bonded: HashMap<_, _> = self.bonded.load(ctx.deps.storage)?.into_iter().collect();
+        let bonds = self
+            .bond_requests
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (validator, requested) = item?;
+                let bonded = bonded.get(&validator).copied().unwrap_or_default();
+                Ok(ValidatorBondStatus {
+                    validator,
+                    bonded,
+                    requested,
+                })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(AllBondStatusesResponse { bonds })
+    }
+
     fn adjust_slashings(
         &self,
         deps: DepsMut<VirtualStakeCustomQuery>,
@@ -114,8 +325,9 @@ impl VirtualStakingContract<'_> {
             match slashes.get(validator) {
                 None => continue,
                 Some(s) => {
-                    // Just deduct the slash amount passed by the chain
-                    *prev -= s.slash_amount;
+                    // Just deduct the slash amount passed by the chain.
+                    // Saturating, as tombstoned validators are already unbonded
+                    *prev = prev.saturating_sub(s.slash_amount);
                     // Apply to request as well (to avoid unbonding msg)
                     let mut request = self
                         .bond_requests
@@ -129,6 +341,120 @@ impl VirtualStakingContract<'_> {
         Ok(())
     }
 
+    /// Records the slash of a validator during `epoch`, to withhold its rewards for the epoch.
+    /// Slashes during the same epoch are combined
+    fn record_epoch_slash(
+        &self,
+        storage: &mut dyn Storage,
+        epoch: u64,
+        slash: &ValidatorSlash,
+    ) -> StdResult<()> {
+        // Not failing the valset update on a malformed nominal ratio, nothing is withheld then
+        let Ok(ratio) = slash.slash_ratio.parse::<Decimal>() else {
+            return Ok(());
+        };
+        let ratio = ratio.min(Decimal::one());
+        self.epoch_slashes
+            .update(storage, (epoch, &slash.address), |old| -> StdResult<_> {
+                let kept = (Decimal::one() - old.unwrap_or_default()) * (Decimal::one() - ratio);
+                Ok(Decimal::one() - kept)
+            })?;
+        Ok(())
+    }
+
+    /// Withholds the rewards of the validators slashed during `epoch`, in proportion of their
+    /// slash ratio, and applies the withholding `policy` to them.
+    /// Returns the rewards left to distribute, the withheld amount, and the amount to return to
+    /// the community pool
+    fn withhold_rewards(
+        &self,
+        storage: &mut dyn Storage,
+        policy: &WithholdingPolicy,
+        epoch: u64,
+        mut rewards: Vec<RewardInfo>,
+    ) -> StdResult<(Vec<RewardInfo>, Uint128, Uint128)> {
+        let mut withheld = Uint128::zero();
+        let mut unslashed = Uint128::zero();
+        let mut slashed = HashSet::new();
+        for info in rewards.iter_mut() {
+            let Some(ratio) = self
+                .epoch_slashes
+                .may_load(storage, (epoch, &info.validator))?
+            else {
+                unslashed += info.reward;
+                continue;
+            };
+            slashed.insert(info.validator.clone());
+            let amount = info.reward * ratio;
+            self.withheld_rewards.update(
+                storage,
+                (epoch, &info.validator),
+                |old| -> StdResult<_> { Ok(old.unwrap_or_default() + amount) },
+            )?;
+            info.reward -= amount;
+            withheld += amount;
+        }
+
+        let returned = match policy {
+            WithholdingPolicy::Redistribute {} if !unslashed.is_zero() => {
+                let mut left = withheld;
+                for info in rewards.iter_mut() {
+                    if !slashed.contains(&info.validator) {
+                        let share = withheld.multiply_ratio(info.reward, unslashed);
+                        info.reward += share;
+                        left -= share;
+                    }
+                }
+                // The rounding leftover goes to the first unslashed validator
+                if let Some(info) = rewards
+                    .iter_mut()
+                    .find(|info| !slashed.contains(&info.validator) && !info.reward.is_zero())
+                {
+                    info.reward += left;
+                }
+                Uint128::zero()
+            }
+            _ => withheld,
+        };
+        rewards.retain(|info| !info.reward.is_zero());
+
+        Ok((rewards, withheld, returned))
+    }
+
+    /// Delegations of the `requests` under `strategy`, with at most `max_cap` bonded in total
+    fn target_delegations(
+        &self,
+        deps: Deps<VirtualStakeCustomQuery>,
+        strategy: &DistributionStrategy,
+        requests: &[(String, Uint128)],
+        max_cap: Uint128,
+    ) -> StdResult<Vec<(String, Uint128)>> {
+        match strategy {
+            DistributionStrategy::ProportionalToProviderSelection {} => {
+                Ok(strategy::proportional(requests, max_cap))
+            }
+            DistributionStrategy::ExcludeList { validators } => {
+                Ok(strategy::excluding(requests, validators, max_cap))
+            }
+            DistributionStrategy::EvenAcrossActiveSet {} => {
+                let inactive = self.inactive.load(deps.storage)?;
+                let jailed = self.jailed.may_load(deps.storage)?.unwrap_or_default();
+                let tombstoned = self.tombstoned.may_load(deps.storage)?.unwrap_or_default();
+                let active = deps
+                    .querier
+                    .query_all_validators()?
+                    .into_iter()
+                    .map(|v| v.address)
+                    .filter(|v| {
+                        !inactive.contains(v) && !jailed.contains(v) && !tombstoned.contains(v)
+                    })
+                    .collect();
+                let total = requests.iter().map(|(_, v)| v).sum();
+                Ok(strategy::even(total, max_cap, active))
+            }
+        }
+    }
+
     #[sv::msg(reply)]
     fn reply(
         &self,
@@ -146,7 +472,7 @@ impl VirtualStakingContract<'_> {
                     .add_attribute("target", target);
                 Ok(Response::new().add_event(evt))
             }
-            (id, _) => Err(ContractError::InvalidReplyId(id)),
+            (id, _) => Err(MeshError::InvalidReplyId(id).into()),
         }
     }
 
@@ -201,15 +527,34 @@ impl VirtualStakingContract<'_> {
             let all_rewards = all_rewards(deps.storage)?;
             BATCH.wipe(deps.storage)?;
 
-            let msg = converter_api::sv::ExecMsg::DistributeRewards {
-                payments: all_rewards,
-            };
-            let msg = WasmMsg::Execute {
-                contract_addr: cfg.converter.into_string(),
-                msg: to_json_binary(&msg)?,
-                funds: vec![coin(total.into(), cfg.denom)],
-            };
-            Ok(Response::new().add_message(msg))
+            // Rewards of the validators slashed while earning them are withheld
+            let epoch = REWARDS_EPOCH.may_load(deps.storage)?.unwrap_or_default();
+            let (payments, withheld, returned) =
+                self.withhold_rewards(deps.storage, &cfg.withholding, epoch, all_rewards)?;
+
+            let mut resp = Response::new();
+            if !withheld.is_zero() {
+                let evt = Event::new("withhold_rewards")
+                    .add_attribute("epoch", epoch.to_string())
+                    .add_attribute("withheld", withheld.to_string())
+                    .add_attribute("returned", returned.to_string());
+                resp = resp.add_event(evt);
+            }
+            if !returned.is_zero() {
+                resp = resp.add_message(DistributionMsg::FundCommunityPool {
+                    amount: coins(returned.u128(), &cfg.denom),
+                });
+            }
+            if !payments.is_empty() {
+                let msg = converter_api::sv::ExecMsg::DistributeRewards { payments };
+                let msg = WasmMsg::Execute {
+                    contract_addr: cfg.converter.into_string(),
+                    msg: to_json_binary(&msg)?,
+                    funds: coins((total - returned).u128(), cfg.denom),
+                };
+                resp = resp.add_message(msg);
+            }
+            Ok(resp)
         } else if !new_reward_amount.is_zero() {
             // since we're not sending out the rewards batch yet, we need to persist
             // the update for future calls
@@ -232,6 +577,8 @@ fn pop_target(deps: DepsMut<VirtualStakeCustomQuery>) -> StdResult<(String, bool
     Ok((target, targets.is_empty()))
 }
 
+/// Diffs the current and desired delegations into a single batch of bond / unbond messages.
+/// All unbonds come first, so the bonds never go over the max cap while the batch is processed.
 fn calculate_rebalance(
     current: Vec<(String, Uint128)>,
     desired: Vec<(String, Uint128)>,
@@ -240,19 +587,20 @@ fn calculate_rebalance(
     let mut desired: BTreeMap<_, _> = desired.into_iter().collect();
 
     // this will handle adjustments to all current validators
-    let mut msgs = vec![];
+    let mut unbonds = vec![];
+    let mut bonds = vec![];
     for (validator, prev) in current {
         let next = desired.remove(&validator).unwrap_or_else(Uint128::zero);
         match next.cmp(&prev) {
             Ordering::Less => {
                 let unbond = prev - next;
                 let amount = coin(unbond.u128(), denom);
-                msgs.push(VirtualStakeMsg::Unbond { validator, amount }.into())
+                unbonds.push(VirtualStakeMsg::Unbond { validator, amount }.into())
             }
             Ordering::Greater => {
                 let bond = next - prev;
                 let amount = coin(bond.u128(), denom);
-                msgs.push(VirtualStakeMsg::Bond { validator, amount }.into())
+                bonds.push(VirtualStakeMsg::Bond { validator, amount }.into())
             }
             Ordering::Equal => {}
         }
@@ -261,13 +609,16 @@ fn calculate_rebalance(
     // any new validators in the desired list need to be bonded
     for (validator, bond) in desired {
         let amount = coin(bond.u128(), denom);
-        msgs.push(VirtualStakeMsg::Bond { validator, amount }.into())
+        bonds.push(VirtualStakeMsg::Bond { validator, amount }.into())
     }
 
-    msgs
+    unbonds.extend(bonds);
+    unbonds
 }
 
 const REWARD_TARGETS: Item<Vec<String>> = Item::new("reward_targets");
+/// Epoch the rewards being withdrawn were earned in
+const REWARDS_EPOCH: Item<u64> = Item::new("rewards_epoch");
 const VALIDATOR_REWARDS_BATCH: ValidatorRewardsBatch = ValidatorRewardsBatch::new();
 const REPLY_REWARDS_ID: u64 = 1;
 
@@ -365,12 +716,8 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
     ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
         nonpayable(&ctx.info)?;
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(ctx.info.sender, cfg.converter, ContractError::Unauthorized); // only the converter can call this
-        ensure_eq!(
-            amount.denom,
-            cfg.denom,
-            ContractError::WrongDenom(cfg.denom)
-        );
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized); // only the converter can call this
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
 
         // Update the amount requested
         let mut bonded = self
@@ -395,12 +742,8 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
     ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
         nonpayable(&ctx.info)?;
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(ctx.info.sender, cfg.converter, ContractError::Unauthorized); // only the converter can call this
-        ensure_eq!(
-            amount.denom,
-            cfg.denom,
-            ContractError::WrongDenom(cfg.denom)
-        );
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized); // only the converter can call this
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
 
         // Update the amount requested
         let bonded = self.bond_requests.load(ctx.deps.storage, &validator)?;
@@ -424,12 +767,8 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
     ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
         nonpayable(&ctx.info)?;
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(ctx.info.sender, cfg.converter, ContractError::Unauthorized); // only the converter can call this
-        ensure_eq!(
-            amount.denom,
-            cfg.denom,
-            ContractError::WrongDenom(cfg.denom)
-        );
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized); // only the converter can call this
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
         let mut bonds = vec![];
         for validator in validators {
             let stake = self
@@ -475,6 +814,51 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
         Ok(Response::new())
     }
 
+    /// Casts a weighted vote on a governance proposal with the delegations of this contract.
+    /// Re-voting replaces the previous vote.
+    fn vote(
+        &self,
+        ctx: ExecCtx<VirtualStakeCustomQuery>,
+        proposal_id: u64,
+        options: Vec<WeightedVoteOption>,
+    ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(ctx.info.sender, cfg.converter, MeshError::Unauthorized); // only the converter can call this
+
+        let resp = Response::new()
+            .add_message(GovMsg::VoteWeighted {
+                proposal_id,
+                options,
+            })
+            .add_attribute("action", "vote")
+            .add_attribute("proposal_id", proposal_id.to_string());
+        Ok(resp)
+    }
+
+    /// Amount bonded to `validator` at the last epoch, and amount requested for the next one
+    fn bond_status(
+        &self,
+        ctx: QueryCtx<Self::QueryC>,
+        validator: String,
+    ) -> Result<ValidatorBondStatus, Self::Error> {
+        let bonded = self
+            .bonded
+            .load(ctx.deps.storage)?
+            .into_iter()
+            .find_map(|(v, amount)| (v == validator).then_some(amount))
+            .unwrap_or_default();
+        let requested = self
+            .bond_requests
+            .may_load(ctx.deps.storage, &validator)?
+            .unwrap_or_default();
+        Ok(ValidatorBondStatus {
+            validator,
+            bonded,
+            requested,
+        })
+    }
+
     // FIXME: need to handle custom message types and queries
     /**
      * This is called once per epoch to withdraw all rewards and rebalance the bonded tokens.
@@ -484,12 +868,17 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
      * The basic logic for calculating rebalance is:
      * 1. Get all bond requests
      * 2. Sum the total amount
-     * 3. If the sum <= max_cap then use collected requests as is
-     * 4. If the sum > max_cap,
-     *   a. calculate multiplier Decimal(max_cap / sum)
-     *   b. multiply every element of the collected requests in place.
+     * 3. Spread them across the validators according to the strategy. By default:
+     *    a. If the sum <= max_cap then use collected requests as is
+     *    b. If the sum > max_cap, scale every element of the collected requests by max_cap / sum
+     * 4. If a strategy transition is in progress, move one more step from the previous strategy
+     *    delegations to the new ones
      * 5. Find diff between collected (normalized) requests and last bonding amounts (which go up, which down).
      * 6. Transform diff into unbond and bond requests, sorting so all unbond happen first
+     *
+     * If the max cap is zero, any remaining delegations reported by the SDK are unbonded.
+     * Rewards for all bonded validators are withdrawn and sent to the converter in a single
+     * `DistributeRewards` batch (see `reply_rewards`).
      */
     fn handle_epoch(
         &self,
@@ -497,6 +886,11 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
     ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
         let SudoCtx { mut deps, env, .. } = ctx;
 
+        // The rewards withdrawn now were earned during the epoch ending
+        let ended = self.epoch.may_load(deps.storage)?.unwrap_or_default();
+        self.epoch.save(deps.storage, &(ended + 1))?;
+        REWARDS_EPOCH.save(deps.storage, &ended)?;
+
         // withdraw rewards
         let bonded = self.bonded.load(deps.storage)?;
         let inactive = self.inactive.load(deps.storage)?;
@@ -506,15 +900,18 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
         let bond =
             TokenQuerier::new(&deps.querier).bond_status(env.contract.address.to_string())?;
         let max_cap = bond.cap.amount;
-        // If 0 max cap, then we assume all tokens were force unbonded already, and just return the withdraw rewards
-        // call and set bonded to empty
-        // TODO: verify this behavior with SDK module (otherwise we send unbond message)
+        let config = self.config.load(deps.storage)?;
+        // If 0 max cap, then all tokens are expected to be force unbonded already (see `handle_max_cap_changed`),
+        // so just return the withdraw rewards call and set bonded to empty.
+        // If the SDK still reports delegations though, unbond whatever is left.
         if max_cap.is_zero() {
+            if !bond.delegated.amount.is_zero() {
+                resp = resp.add_messages(calculate_rebalance(bonded, vec![], &config.denom));
+            }
             self.bonded.save(deps.storage, &vec![])?;
             return Ok(resp);
         }
 
-        let config = self.config.load(deps.storage)?;
         // Make current bonded mutable
         let mut current = bonded;
         // Process slashes due to tombstoning (unbonded) or jailing, over bond_requests and current
@@ -532,7 +929,7 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
         }
 
         // calculate what the delegations should be when we are done
-        let mut requests: Vec<(String, Uint128)> = self
+        let requests: Vec<(String, Uint128)> = self
             .bond_requests
             .range(
                 deps.as_ref().storage,
@@ -541,10 +938,40 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
                 cosmwasm_std::Order::Ascending,
             )
             .collect::<Result<_, _>>()?;
-        let total_requested: Uint128 = requests.iter().map(|(_, v)| v).sum();
-        if total_requested > max_cap {
-            for (_, v) in requests.iter_mut() {
-                *v = (*v * max_cap) / total_requested;
+        let target =
+            self.target_delegations(deps.as_ref(), &config.strategy, &requests, max_cap)?;
+        let mut requests = match self.strategy_transition.may_load(deps.storage)? {
+            Some(mut transition) => {
+                let from =
+                    self.target_delegations(deps.as_ref(), &transition.from, &requests, max_cap)?;
+                transition.elapsed += 1;
+                let blended =
+                    strategy::blend(&from, &target, transition.elapsed, transition.epochs);
+                if transition.elapsed >= transition.epochs {
+                    self.strategy_transition.remove(deps.storage);
+                } else {
+                    self.strategy_transition.save(deps.storage, &transition)?;
+                }
+                blended
+            }
+            None => target,
+        };
+
+        // Jailed validators keep (at most) their current bond, and tombstoned ones none
+        let jailed = self.jailed.may_load(deps.storage)?.unwrap_or_default();
+        let tombstoned = self.tombstoned.may_load(deps.storage)?.unwrap_or_default();
+        if !jailed.is_empty() || !tombstoned.is_empty() {
+            let current_by_validator: HashMap<_, _> = current.iter().cloned().collect();
+            for (validator, v) in requests.iter_mut() {
+                if tombstoned.contains(validator) {
+                    *v = Uint128::zero();
+                } else if jailed.contains(validator) {
+                    let prev = current_by_validator
+                        .get(validator.as_str())
+                        .copied()
+                        .unwrap_or_default();
+                    *v = (*v).min(prev);
+                }
             }
         }
 
@@ -558,10 +985,86 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
         Ok(resp)
     }
 
+    /**
+     * This is called by the SDK right after the max cap was changed.
+     *
+     * If the currently bonded amount is over the new max cap, the excess is unbonded right away,
+     * proportionally across all bonded validators, instead of waiting for the next epoch.
+     * Bond requests are left untouched, so the stake is bonded again if the max cap is raised later.
+     * The forced unbondings are reported to the converter, which relays them to the provider.
+     */
+    fn handle_max_cap_changed(
+        &self,
+        ctx: SudoCtx<VirtualStakeCustomQuery>,
+    ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
+        let SudoCtx { deps, env, .. } = ctx;
+
+        let bond =
+            TokenQuerier::new(&deps.querier).bond_status(env.contract.address.to_string())?;
+        let max_cap = bond.cap.amount;
+
+        let current = self.bonded.load(deps.storage)?;
+        let total_bonded: Uint128 = current.iter().map(|(_, v)| v).sum();
+        if total_bonded <= max_cap {
+            return Ok(Response::new());
+        }
+
+        // Scale down all bonds evenly, so their sum fits in the new max cap
+        let desired: Vec<(String, Uint128)> = current
+            .iter()
+            .map(|(validator, v)| (validator.clone(), v.multiply_ratio(max_cap, total_bonded)))
+            .filter(|(_, v)| !v.is_zero())
+            .collect();
+        self.bonded.save(deps.storage, &desired)?;
+
+        let config = self.config.load(deps.storage)?;
+        let desired_by_validator: HashMap<_, _> = desired.iter().cloned().collect();
+        let unbonds: Vec<ForcedUnbondInfo> = current
+            .iter()
+            .filter_map(|(validator, prev)| {
+                let next = desired_by_validator
+                    .get(validator)
+                    .copied()
+                    .unwrap_or_default();
+                let unbond = *prev - next;
+                (!unbond.is_zero()).then(|| ForcedUnbondInfo {
+                    validator: validator.clone(),
+                    amount: coin(unbond.u128(), &config.denom),
+                })
+            })
+            .collect();
+
+        let event = Event::new("max_cap_changed")
+            .add_attribute("max_cap", max_cap.to_string())
+            .add_attribute("bonded", total_bonded.to_string());
+
+        let rebalance = calculate_rebalance(current, desired, &config.denom);
+
+        // Report the forced unbondings to the converter
+        let msg = converter_api::sv::ExecMsg::MaxCapUpdate {
+            max_cap: coin(max_cap.u128(), &config.denom),
+            unbonds,
+        };
+        let msg = WasmMsg::Execute {
+            contract_addr: config.converter.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+
+        Ok(Response::new()
+            .add_messages(rebalance)
+            .add_message(msg)
+            .add_event(event))
+    }
+
     // FIXME: need to handle custom message types and queries
     /**
      * This is called every time there's a change of the active validator set.
      *
+     * Jailed validators are paused: their bond is not increased until they are unjailed.
+     * Tombstoned validators are unbonded right away (minus the slashed amount), and never bonded
+     * to again. Their bond requests are kept, and released as the provider unstakes.
+     * All the updates are forwarded to the converter, which relays them to the provider.
      */
     #[allow(clippy::too_many_arguments)]
     fn handle_valset_update(
@@ -574,6 +1077,7 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
         unjailed: Option<Vec<String>>,
         tombstoned: Option<Vec<String>>,
         slashed: Option<Vec<ValidatorSlash>>,
+        uptimes: Option<Vec<ValidatorUptime>>,
     ) -> Result<Response<VirtualStakeCustomMsg>, ContractError> {
         let SudoCtx { deps, .. } = ctx;
 
@@ -591,6 +1095,61 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
                 old.extend_from_slice(slashed);
                 Ok::<_, ContractError>(old)
             })?;
+            // Their rewards for the epoch in progress are withheld
+            let epoch = self.epoch.may_load(deps.storage)?.unwrap_or_default();
+            for slash in slashed {
+                self.record_epoch_slash(deps.storage, epoch, slash)?;
+            }
+        }
+
+        let cfg = self.config.load(deps.storage)?;
+        let mut resp = Response::new();
+
+        // Pause bonding to jailed validators, until they are unjailed
+        if !jailed.is_empty() || !unjailed.is_empty() {
+            self.jailed.update(deps.storage, |old| {
+                let mut old: Vec<_> = old
+                    .into_iter()
+                    .filter(|v| !unjailed.contains(v))
+                    .chain(jailed.iter().cloned())
+                    .collect();
+                old.sort();
+                old.dedup();
+                Ok::<_, ContractError>(old)
+            })?;
+        }
+
+        // Unbond from tombstoned validators right away
+        if !tombstoned.is_empty() {
+            let slash_requests = self.slash_requests.load(deps.storage)?;
+            let mut bonded = self.bonded.load(deps.storage)?;
+            for (validator, amount) in bonded.iter_mut() {
+                if !tombstoned.contains(validator) {
+                    continue;
+                }
+                // The slashed amount is already gone
+                let slashed: Uint128 = slash_requests
+                    .iter()
+                    .filter(|s| s.address == *validator)
+                    .map(|s| s.slash_amount)
+                    .sum();
+                let unbond = amount.saturating_sub(slashed);
+                if !unbond.is_zero() {
+                    resp = resp.add_message(VirtualStakeMsg::Unbond {
+                        validator: validator.clone(),
+                        amount: coin(unbond.u128(), &cfg.denom),
+                    });
+                }
+                *amount = Uint128::zero();
+            }
+            self.bonded.save(deps.storage, &bonded)?;
+
+            self.tombstoned.update(deps.storage, |mut old| {
+                old.extend_from_slice(tombstoned);
+                old.sort();
+                old.dedup();
+                Ok::<_, ContractError>(old)
+            })?;
         }
 
         // Update inactive list.
@@ -606,7 +1165,6 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
             })?;
         }
         // Send all updates to the converter.
-        let cfg = self.config.load(deps.storage)?;
         let msg = converter_api::sv::ExecMsg::ValsetUpdate {
             additions: additions.to_vec(),
             removals: removals.to_vec(),
@@ -625,15 +1183,77 @@ impl VirtualStakingApi for VirtualStakingContract<'_> {
                     slash_ratio: s.slash_ratio.clone(),
                 })
                 .collect(),
+            uptimes: uptimes.unwrap_or_default(),
         };
         let msg = WasmMsg::Execute {
             contract_addr: cfg.converter.to_string(),
             msg: to_json_binary(&msg)?,
             funds: vec![],
         };
-        let resp = Response::new().add_message(msg);
+        Ok(resp.add_message(msg))
+    }
+}
+
+impl OwnershipApi for VirtualStakingContract<'_> {
+    type Error = ContractError;
+    type ExecC = VirtualStakeCustomMsg;
+    type QueryC = VirtualStakeCustomQuery;
+
+    fn propose_owner(
+        &self,
+        ctx: ExecCtx<VirtualStakeCustomQuery>,
+        new_owner: String,
+        expiry: Option<Timestamp>,
+    ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+        let ownership = ownership_api::propose_owner(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            new_owner,
+            expiry,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "propose_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn accept_owner(
+        &self,
+        ctx: ExecCtx<VirtualStakeCustomQuery>,
+    ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership =
+            ownership_api::accept_owner(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "accept_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn renounce_owner(
+        &self,
+        ctx: ExecCtx<VirtualStakeCustomQuery>,
+    ) -> Result<Response<VirtualStakeCustomMsg>, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership = ownership_api::renounce_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "renounce_owner")
+            .add_attributes(ownership.into_attributes());
         Ok(resp)
     }
+
+    fn ownership(&self, ctx: QueryCtx<VirtualStakeCustomQuery>) -> Result<Ownership, Self::Error> {
+        Ok(ownership_api::get_ownership(ctx.deps.storage)?)
+    }
 }
 
 #[cfg(test)]
@@ -647,12 +1267,14 @@ mod tests {
     use cosmwasm_std::{
         coins, from_json,
         testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage},
-        Decimal,
+        Addr, Decimal, VoteOption,
     };
     use mesh_bindings::{BondStatusResponse, SlashRatioResponse};
 
     use super::*;
 
+    const OWNER: &str = "owner";
+
     type OwnedDeps<C = VirtualStakeCustomQuery> =
         cosmwasm_std::OwnedDeps<MockStorage, MockApi, MockQuerier<C>, C>;
     type DepsMut<'a, C = VirtualStakeCustomQuery> = cosmwasm_std::DepsMut<'a, C>;
@@ -708,42 +1330,393 @@ mod tests {
     /// If there isn't enough cap, bonds get proportionally rebalanced so that their sum
     /// doesn't exceed the cap.
     #[test]
-    fn bond_rebalance() {
+    fn bond_rebalance() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+
+        knobs.bond_status.update_cap(5u128);
+        contract.quick_bond(deps.as_mut(), "val1", 10);
+        contract.quick_bond(deps.as_mut(), "val2", 40);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (1u128, &denom)), ("val2", (4u128, &denom))])
+            .assert_rewards(&[]);
+    }
+
+    #[test]
+    fn set_strategy_owner_only() {
+        let (mut deps, _) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        ownership_api::initialize_owner(&mut deps.storage, Some(Addr::unchecked(OWNER))).unwrap();
+
+        let ctx = ExecCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+            info: mock_info("somebody", &[]),
+        };
+        let err = contract
+            .set_strategy(ctx, DistributionStrategy::EvenAcrossActiveSet {}, 0)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::Mesh(MeshError::Unauthorized)));
+
+        contract.quick_set_strategy(
+            deps.as_mut(),
+            DistributionStrategy::EvenAcrossActiveSet {},
+            3,
+        );
+        let ctx = ExecCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+            info: mock_info(OWNER, &[]),
+        };
+        let err = contract
+            .set_strategy(ctx, DistributionStrategy::default(), 0)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::StrategyTransitionInProgress));
+    }
+
+    #[test]
+    fn even_strategy() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+        set_active_set(&mut deps, &["val1", "val2", "val3"]);
+        ownership_api::initialize_owner(&mut deps.storage, Some(Addr::unchecked(OWNER))).unwrap();
+
+        knobs.bond_status.update_cap(100u128);
+        contract.quick_bond(deps.as_mut(), "val1", 31);
+        contract.quick_set_strategy(
+            deps.as_mut(),
+            DistributionStrategy::EvenAcrossActiveSet {},
+            0,
+        );
+        contract.hit_epoch(deps.as_mut()).assert_bond(&[
+            ("val1", (11u128, &denom)),
+            ("val2", (10u128, &denom)),
+            ("val3", (10u128, &denom)),
+        ]);
+
+        // Jailed validators are left out of the active set
+        contract.jail(deps.as_mut(), "val3", Decimal::zero(), Uint128::zero());
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (5u128, &denom)), ("val2", (5u128, &denom))])
+            .assert_unbond(&[("val3", (10u128, &denom))]);
+    }
+
+    #[test]
+    fn exclude_list_strategy() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+        ownership_api::initialize_owner(&mut deps.storage, Some(Addr::unchecked(OWNER))).unwrap();
+
+        knobs.bond_status.update_cap(100u128);
+        contract.quick_bond(deps.as_mut(), "val1", 10);
+        contract.quick_bond(deps.as_mut(), "val2", 30);
+        contract.quick_bond(deps.as_mut(), "val3", 20);
+        let strategy = DistributionStrategy::ExcludeList {
+            validators: vec!["val2".to_owned()],
+        };
+        contract.quick_set_strategy(deps.as_mut(), strategy, 0);
+        // val2 stake is spread over val1 and val3, proportionally
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (20u128, &denom)), ("val3", (40u128, &denom))]);
+    }
+
+    #[test]
+    fn gradual_strategy_transition() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+        set_active_set(&mut deps, &["val1", "val2"]);
+        ownership_api::initialize_owner(&mut deps.storage, Some(Addr::unchecked(OWNER))).unwrap();
+
+        knobs.bond_status.update_cap(100u128);
+        contract.quick_bond(deps.as_mut(), "val1", 40);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (40u128, &denom))]);
+
+        contract.quick_set_strategy(
+            deps.as_mut(),
+            DistributionStrategy::EvenAcrossActiveSet {},
+            2,
+        );
+
+        // Half way there
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_unbond(&[("val1", (10u128, &denom))])
+            .assert_bond(&[("val2", (10u128, &denom))]);
+        let transition = contract.strategy_transition.load(&deps.storage).unwrap();
+        assert_eq!(transition.elapsed, 1);
+
+        // Done
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_unbond(&[("val1", (10u128, &denom))])
+            .assert_bond(&[("val2", (10u128, &denom))]);
+        assert!(!contract.strategy_transition.exists(&deps.storage));
+
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_unbond(&[])
+            .assert_bond(&[]);
+    }
+
+    #[test]
+    fn bond_statuses() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+
+        knobs.bond_status.update_cap(5u128);
+        contract.quick_bond(deps.as_mut(), "val1", 6);
+        contract.quick_bond(deps.as_mut(), "val2", 4);
+        contract.hit_epoch(deps.as_mut());
+        // Requested, but not bonded yet
+        contract.quick_bond(deps.as_mut(), "val3", 1);
+
+        let status = |validator: &str, bonded: u128, requested: u128| ValidatorBondStatus {
+            validator: validator.to_string(),
+            bonded: Uint128::new(bonded),
+            requested: Uint128::new(requested),
+        };
+        let query_ctx = || QueryCtx {
+            deps: deps.as_ref(),
+            env: mock_env(),
+        };
+
+        let res = contract
+            .bond_status(query_ctx(), "val1".to_string())
+            .unwrap();
+        assert_eq!(res, status("val1", 3, 6));
+        let res = contract
+            .bond_status(query_ctx(), "val4".to_string())
+            .unwrap();
+        assert_eq!(res, status("val4", 0, 0));
+
+        let res = contract
+            .all_bond_statuses(query_ctx(), None, Some(2))
+            .unwrap();
+        assert_eq!(res.bonds, [status("val1", 3, 6), status("val2", 2, 4)]);
+        let res = contract
+            .all_bond_statuses(query_ctx(), Some("val2".to_string()), None)
+            .unwrap();
+        assert_eq!(res.bonds, [status("val3", 0, 1)]);
+    }
+
+    #[test]
+    fn unbond() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+
+        knobs.bond_status.update_cap(10u128);
+        contract.quick_bond(deps.as_mut(), "val1", 5);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (5u128, &denom))])
+            .assert_rewards(&[]);
+
+        contract.quick_unbond(deps.as_mut(), "val1", 5);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_unbond(&[("val1", (5u128, &denom))])
+            .assert_rewards(&["val1"]);
+    }
+
+    #[test]
+    fn zero_cap_unbonds_remaining_delegations() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+
+        knobs.bond_status.update_cap(10u128);
+        contract.quick_bond(deps.as_mut(), "val1", 5);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (5u128, &denom))])
+            .assert_rewards(&[]);
+
+        // The SDK didn't force unbond the delegations
+        knobs.bond_status.update_cap(0u128);
+        knobs.bond_status.update_delegated(5u128);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_unbond(&[("val1", (5u128, &denom))])
+            .assert_rewards(&["val1"]);
+
+        knobs.bond_status.update_delegated(0u128);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_no_bonding()
+            .assert_rewards(&[]);
+    }
+
+    #[test]
+    fn rebalance_unbonds_first() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        let denom = contract.config.load(&deps.storage).unwrap().denom;
+
+        knobs.bond_status.update_cap(20u128);
+        contract.quick_bond(deps.as_mut(), "val1", 5);
+        contract.quick_bond(deps.as_mut(), "val2", 10);
+        contract.hit_epoch(deps.as_mut());
+
+        contract.quick_bond(deps.as_mut(), "val1", 5);
+        contract.quick_unbond(deps.as_mut(), "val2", 5);
+        contract.quick_bond(deps.as_mut(), "val3", 3);
+        let res = contract.hit_epoch(deps.as_mut());
+        res.assert_bond(&[("val1", (5u128, &denom)), ("val3", (3u128, &denom))])
+            .assert_unbond(&[("val2", (5u128, &denom))])
+            .assert_rewards(&["val1", "val2"]);
+        assert!(matches!(
+            res.virtual_stake_msgs.as_slice(),
+            [
+                VirtualStakeMsg::Unbond { .. },
+                VirtualStakeMsg::Bond { .. },
+                VirtualStakeMsg::Bond { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn max_cap_reduced() {
         let (mut deps, knobs) = mock_dependencies();
 
         let contract = VirtualStakingContract::new();
         contract.quick_inst(deps.as_mut());
         let denom = contract.config.load(&deps.storage).unwrap().denom;
 
-        knobs.bond_status.update_cap(5u128);
+        knobs.bond_status.update_cap(20u128);
         contract.quick_bond(deps.as_mut(), "val1", 10);
-        contract.quick_bond(deps.as_mut(), "val2", 40);
+        contract.quick_bond(deps.as_mut(), "val2", 6);
         contract
             .hit_epoch(deps.as_mut())
-            .assert_bond(&[("val1", (1u128, &denom)), ("val2", (4u128, &denom))])
+            .assert_bond(&[("val1", (10u128, &denom)), ("val2", (6u128, &denom))])
             .assert_rewards(&[]);
+
+        // Still under the max cap, nothing to do
+        knobs.bond_status.update_cap(16u128);
+        contract
+            .hit_max_cap_changed(deps.as_mut())
+            .assert_no_bonding()
+            .assert_forced_unbonds(None);
+
+        // Excess is unbonded evenly
+        knobs.bond_status.update_cap(8u128);
+        contract
+            .hit_max_cap_changed(deps.as_mut())
+            .assert_unbond(&[("val1", (5u128, &denom)), ("val2", (3u128, &denom))])
+            .assert_forced_unbonds(Some(&[("val1", 5), ("val2", 3)]));
+
+        // Bond requests are kept, so nothing else changes at the next epoch
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_no_bonding()
+            .assert_rewards(&["val1", "val2"]);
+
+        // Raising the max cap again bonds the requests back
+        knobs.bond_status.update_cap(20u128);
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (5u128, &denom)), ("val2", (3u128, &denom))])
+            .assert_rewards(&["val1", "val2"]);
     }
 
     #[test]
-    fn unbond() {
+    fn max_cap_to_zero() {
         let (mut deps, knobs) = mock_dependencies();
 
         let contract = VirtualStakingContract::new();
         contract.quick_inst(deps.as_mut());
         let denom = contract.config.load(&deps.storage).unwrap().denom;
 
-        knobs.bond_status.update_cap(10u128);
-        contract.quick_bond(deps.as_mut(), "val1", 5);
+        knobs.bond_status.update_cap(20u128);
+        contract.quick_bond(deps.as_mut(), "val1", 10);
+        contract.quick_bond(deps.as_mut(), "val2", 6);
         contract
             .hit_epoch(deps.as_mut())
-            .assert_bond(&[("val1", (5u128, &denom))])
+            .assert_bond(&[("val1", (10u128, &denom)), ("val2", (6u128, &denom))])
             .assert_rewards(&[]);
 
-        contract.quick_unbond(deps.as_mut(), "val1", 5);
+        knobs.bond_status.update_cap(0u128);
+        contract
+            .hit_max_cap_changed(deps.as_mut())
+            .assert_unbond(&[("val1", (10u128, &denom)), ("val2", (6u128, &denom))])
+            .assert_forced_unbonds(Some(&[("val1", 10), ("val2", 6)]));
+        assert_eq!(contract.bonded.load(&deps.storage).unwrap(), vec![]);
+
+        // Nothing bonded, so no rewards to withdraw
         contract
             .hit_epoch(deps.as_mut())
-            .assert_unbond(&[("val1", (5u128, &denom))])
-            .assert_rewards(&["val1"]);
+            .assert_no_bonding()
+            .assert_rewards(&[]);
+    }
+
+    #[test]
+    fn vote() {
+        let (mut deps, _) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+
+        let options = vec![
+            WeightedVoteOption {
+                option: VoteOption::Yes,
+                weight: Decimal::percent(75),
+            },
+            WeightedVoteOption {
+                option: VoteOption::No,
+                weight: Decimal::percent(25),
+            },
+        ];
+        let vote = |deps: DepsMut, sender: &str| {
+            contract.vote(
+                ExecCtx {
+                    deps,
+                    env: mock_env(),
+                    info: mock_info(sender, &[]),
+                },
+                1,
+                options.clone(),
+            )
+        };
+
+        // Only the converter can vote
+        let err = vote(deps.as_mut(), "someone").unwrap_err();
+        assert!(matches!(err, ContractError::Mesh(MeshError::Unauthorized)));
+
+        let resp = vote(deps.as_mut(), "me").unwrap();
+        assert_eq!(
+            resp.messages,
+            vec![SubMsg::new(GovMsg::VoteWeighted {
+                proposal_id: 1,
+                options,
+            })]
+        );
     }
 
     #[test]
@@ -928,13 +1901,20 @@ mod tests {
 
         contract
             .hit_epoch(deps.as_mut())
-            .assert_bond(&[("val1", (20u128, &denom))]) // Tombstoned validators can still bond
+            .assert_bond(&[]) // Jailed validators can't bond
             .assert_unbond(&[]) // No unbond msgs after jailing
             .assert_rewards(&[]); // Rewards are not gathered anymore because of jailing implying removal
 
-        // Check that the non-slashed amounts of val1 have been bonded
+        // Check that only the non-slashed amount of val1 is bonded
         let bonded = contract.bonded.load(deps.as_ref().storage).unwrap();
-        assert_eq!(bonded, [("val1".to_string(), Uint128::new(29)),]);
+        assert_eq!(bonded, [("val1".to_string(), Uint128::new(9)),]);
+
+        // The pending bond goes through once unjailed
+        contract.unjail(deps.as_mut(), "val1");
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_bond(&[("val1", (20u128, &denom))])
+            .assert_unbond(&[]);
     }
 
     #[test]
@@ -1028,22 +2008,24 @@ mod tests {
             .assert_bond(&[("val1", (20u128, &denom)), ("val2", (20u128, &denom))])
             .assert_rewards(&[]);
 
-        // Val1 is being tombstoned
-        contract.tombstone(deps.as_mut(), "val1", Decimal::percent(25), Uint128::new(5));
+        // Val1 is being tombstoned. What's left after slashing for double sign (25%) is unbonded
+        // right away
+        contract
+            .tombstone(deps.as_mut(), "val1", Decimal::percent(25), Uint128::new(5))
+            .assert_unbond(&[("val1", (15u128, &denom))]);
         contract
             .hit_epoch(deps.as_mut())
             .assert_bond(&[]) // No bond msgs after tombstoning
             .assert_unbond(&[]) // No unbond msgs after tombstoning
-            .assert_rewards(&["val1", "val2"]); // Last rewards msgs after tombstoning
+            .assert_rewards(&["val2"]); // Nothing bonded to val1 anymore
 
-        // Check that the bonded amounts of val1 have been slashed for double sign (25%)
         // Val2 is unaffected.
         // TODO: Check that the amounts have been slashed for double sign on-chain (needs mt slashing / tombstoning support)
         let bonded = contract.bonded.load(deps.as_ref().storage).unwrap();
         assert_eq!(
             bonded,
             [
-                ("val1".to_string(), Uint128::new(15)),
+                ("val1".to_string(), Uint128::new(0)),
                 ("val2".to_string(), Uint128::new(20))
             ]
         );
@@ -1070,23 +2052,20 @@ mod tests {
         // Val1 is bonding some more
         contract.quick_bond(deps.as_mut(), "val1", 20);
 
-        // And it's being tombstoned at the same time
-        contract.tombstone(deps.as_mut(), "val1", Decimal::percent(25), Uint128::new(2));
+        // And it's being tombstoned at the same time. The previously bonded amount is unbonded
+        // right away, after slashing for double sign (25%)
+        contract
+            .tombstone(deps.as_mut(), "val1", Decimal::percent(25), Uint128::new(2))
+            .assert_unbond(&[("val1", (8u128, &denom))]);
 
         contract
             .hit_epoch(deps.as_mut())
-            .assert_bond(&[("val1", (20, &denom))]) // FIXME?: Tombstoned validators can still bond
+            .assert_bond(&[]) // Tombstoned validators can't bond
             .assert_unbond(&[])
-            .assert_rewards(&["val1"]); // Rewards are still being gathered
+            .assert_rewards(&[]); // Nothing bonded to val1 anymore
 
-        // Check that the previously bonded amounts of val1 have been slashed for double sign (25%)
         let bonded = contract.bonded.load(deps.as_ref().storage).unwrap();
-        assert_eq!(
-            bonded,
-            [
-                ("val1".to_string(), Uint128::new(8 + 20)), // Due to rounding up
-            ]
-        );
+        assert_eq!(bonded, [("val1".to_string(), Uint128::new(0)),]);
 
         // Subsequent rewards msgs are removed after validator is tombstoned
         contract.hit_epoch(deps.as_mut()).assert_rewards(&[]);
@@ -1111,13 +2090,15 @@ mod tests {
         contract.quick_unbond(deps.as_mut(), "val1", 10);
 
         // And it's being tombstoned at the same time
-        contract.tombstone(deps.as_mut(), "val1", Decimal::percent(25), Uint128::new(2));
+        contract
+            .tombstone(deps.as_mut(), "val1", Decimal::percent(25), Uint128::new(2))
+            .assert_unbond(&[("val1", (8u128, &denom))]); // Unbond adjusted for double sign slashing
 
         contract
             .hit_epoch(deps.as_mut())
-            .assert_bond(&[]) // No bond msgs after jailing
-            .assert_unbond(&[("val1", (8u128, &denom))]) // Unbond adjusted for double sign slashing
-            .assert_rewards(&["val1"]); // Rewards are still being gathered
+            .assert_bond(&[]) // No bond msgs after tombstoning
+            .assert_unbond(&[]) // Already unbonded
+            .assert_rewards(&[]); // Nothing bonded to val1 anymore
 
         // Check that bonded accounting has been adjusted
         let bonded = contract.bonded.load(deps.as_ref().storage).unwrap();
@@ -1204,6 +2185,176 @@ mod tests {
             .assert_eq(&[("val1", 20), ("val2", 10)]);
     }
 
+    #[test]
+    fn slashed_validator_rewards_withheld() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+
+        knobs.bond_status.update_cap(100u128);
+        contract.quick_bond(deps.as_mut(), "val1", 10);
+        contract.quick_bond(deps.as_mut(), "val2", 10);
+        contract.quick_bond(deps.as_mut(), "val3", 20);
+        contract.hit_epoch(deps.as_mut()).assert_rewards(&[]);
+
+        // val1 is slashed by 10% during the epoch, before its rewards are withdrawn
+        contract.slash(deps.as_mut(), "val1", Decimal::percent(10), Uint128::one());
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_rewards(&["val1", "val2", "val3"]);
+
+        // 10% of its rewards are redistributed to the others (3.33 and 6.67, rounding to val2)
+        contract.push_rewards(&mut deps, 100).assert_empty();
+        contract.push_rewards(&mut deps, 100).assert_empty();
+        let res = contract.push_rewards(&mut deps, 200);
+        res.assert_eq(&[("val1", 90), ("val2", 104), ("val3", 206)]);
+        assert_eq!(res.returned(), 0);
+
+        let withheld = contract.quick_withheld_rewards(deps.as_ref(), 1, "val1");
+        assert_eq!(withheld.current_epoch, 2);
+        assert_eq!(withheld.slash_ratio, Decimal::percent(10));
+        assert_eq!(withheld.withheld, Uint128::new(10));
+        let withheld = contract.quick_withheld_rewards(deps.as_ref(), 1, "val2");
+        assert_eq!(withheld.withheld, Uint128::zero());
+
+        // Next epoch, the slashed validator is inactive, and nothing is withheld anymore
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_rewards(&["val2", "val3"]);
+        contract.push_rewards(&mut deps, 100).assert_empty();
+        contract
+            .push_rewards(&mut deps, 200)
+            .assert_eq(&[("val2", 100), ("val3", 200)]);
+    }
+
+    #[test]
+    fn slash_after_epoch_end() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+
+        knobs.bond_status.update_cap(100u128);
+        contract.quick_bond(deps.as_mut(), "val1", 10);
+        contract.quick_bond(deps.as_mut(), "val2", 10);
+        contract.hit_epoch(deps.as_mut()).assert_rewards(&[]);
+
+        // The rewards of the ending epoch are paid in full
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_rewards(&["val1", "val2"]);
+        contract.push_rewards(&mut deps, 100).assert_empty();
+        contract
+            .push_rewards(&mut deps, 100)
+            .assert_eq(&[("val1", 100), ("val2", 100)]);
+
+        // val2 is slashed twice by 10% after the epoch end, which is withheld on the next one
+        contract.slash(deps.as_mut(), "val2", Decimal::percent(10), Uint128::one());
+        contract.slash(deps.as_mut(), "val2", Decimal::percent(10), Uint128::one());
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_rewards(&["val1", "val2"]);
+        contract.push_rewards(&mut deps, 100).assert_empty();
+        contract
+            .push_rewards(&mut deps, 100)
+            .assert_eq(&[("val1", 119), ("val2", 81)]);
+
+        let withheld = contract.quick_withheld_rewards(deps.as_ref(), 1, "val2");
+        assert_eq!(withheld.slash_ratio, Decimal::zero());
+        assert_eq!(withheld.withheld, Uint128::zero());
+        let withheld = contract.quick_withheld_rewards(deps.as_ref(), 2, "val2");
+        assert_eq!(withheld.slash_ratio, Decimal::percent(19));
+        assert_eq!(withheld.withheld, Uint128::new(19));
+    }
+
+    #[test]
+    fn withheld_rewards_returned() {
+        let (mut deps, knobs) = mock_dependencies();
+
+        let contract = VirtualStakingContract::new();
+        contract.quick_inst(deps.as_mut());
+        ownership_api::initialize_owner(&mut deps.storage, Some(Addr::unchecked(OWNER))).unwrap();
+
+        let ctx = ExecCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+            info: mock_info("somebody", &[]),
+        };
+        let err = contract
+            .set_withholding_policy(ctx, WithholdingPolicy::Return {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::Mesh(MeshError::Unauthorized)));
+        let ctx = ExecCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+            info: mock_info(OWNER, &[]),
+        };
+        contract
+            .set_withholding_policy(ctx, WithholdingPolicy::Return {})
+            .unwrap();
+
+        knobs.bond_status.update_cap(100u128);
+        contract.quick_bond(deps.as_mut(), "val1", 10);
+        contract.quick_bond(deps.as_mut(), "val2", 10);
+        contract.hit_epoch(deps.as_mut()).assert_rewards(&[]);
+
+        // Half of val1 rewards go back to the community pool
+        contract.slash(deps.as_mut(), "val1", Decimal::percent(50), Uint128::new(5));
+        contract
+            .hit_epoch(deps.as_mut())
+            .assert_rewards(&["val1", "val2"]);
+        contract.push_rewards(&mut deps, 100).assert_empty();
+        let res = contract.push_rewards(&mut deps, 100);
+        res.assert_eq(&[("val1", 50), ("val2", 100)]);
+        assert_eq!(res.returned(), 50);
+    }
+
+    #[test]
+    fn migrating_from_baseline() {
+        use cosmwasm_std::{ContractInfoResponse, ContractResult, SystemResult, WasmQuery};
+
+        let (mut deps, _) = mock_dependencies();
+        let contract = VirtualStakingContract::new();
+
+        // Config and version of the baseline release, without owner
+        deps.storage
+            .set(b"config", br#"{"denom":"ustake","converter":"converter"}"#);
+        cw2::set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.10.0-alpha.1").unwrap();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { .. } => {
+                let mut info = ContractInfoResponse::default();
+                info.code_id = 1;
+                info.creator = "converter".to_owned();
+                info.admin = Some(OWNER.to_owned());
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => unimplemented!(),
+        });
+
+        let ctx = MigrateCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+        };
+        let resp = contract.migrate(ctx).unwrap();
+        assert_eq!(resp.events[0].ty, "migrate");
+
+        assert_eq!(
+            ownership_api::get_ownership(&deps.storage).unwrap().owner,
+            Some(Addr::unchecked(OWNER))
+        );
+        assert_eq!(
+            contract.jailed.load(&deps.storage).unwrap(),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            contract.tombstoned.load(&deps.storage).unwrap(),
+            Vec::<String>::new()
+        );
+        let config = contract.config.load(&deps.storage).unwrap();
+        assert_eq!(config.converter, Addr::unchecked("converter"));
+    }
+
     fn mock_dependencies() -> (OwnedDeps, StakingKnobs) {
         let bond_status = MockBondStatus::new(BondStatusResponse {
             cap: coin(0, "DOES NOT MATTER"),
@@ -1229,6 +2380,11 @@ mod tests {
                             to_json_binary(&*slash_ratio.borrow()).unwrap(),
                         ))
                     }
+                    _ => cosmwasm_std::SystemResult::Err(
+                        cosmwasm_std::SystemError::UnsupportedRequest {
+                            kind: "virtual_stake".to_owned(),
+                        },
+                    ),
                 }
             }
         };
@@ -1264,6 +2420,11 @@ mod tests {
             let mut mut_obj = self.0.borrow_mut();
             mut_obj.cap.amount = cap.into();
         }
+
+        fn update_delegated(&self, delegated: impl Into<Uint128>) {
+            let mut mut_obj = self.0.borrow_mut();
+            mut_obj.delegated.amount = delegated.into();
+        }
     }
 
     #[derive(Clone)]
@@ -1279,6 +2440,20 @@ mod tests {
         }
     }
 
+    fn set_active_set(deps: &mut OwnedDeps, validators: &[&str]) {
+        let denom = deps.as_ref().querier.query_bonded_denom().unwrap();
+        let validators: Vec<_> = validators
+            .iter()
+            .map(|val| cosmwasm_std::Validator {
+                address: val.to_string(),
+                commission: Default::default(),
+                max_commission: Default::default(),
+                max_change_rate: Default::default(),
+            })
+            .collect();
+        deps.querier.update_staking(&denom, &validators, &[]);
+    }
+
     fn set_reward_targets(storage: &mut dyn Storage, targets: &[&str]) {
         REWARD_TARGETS
             .save(
@@ -1292,6 +2467,7 @@ mod tests {
         fn quick_inst(&self, deps: DepsMut);
         fn push_rewards(&self, deps: &mut OwnedDeps, amount: u128) -> PushRewardsResult;
         fn hit_epoch(&self, deps: DepsMut) -> HitEpochResult;
+        fn hit_max_cap_changed(&self, deps: DepsMut) -> HitEpochResult;
         fn quick_bond(&self, deps: DepsMut, validator: &str, amount: u128);
         fn quick_unbond(&self, deps: DepsMut, validator: &str, amount: u128);
         fn quick_burn(
@@ -1308,24 +2484,41 @@ mod tests {
             slash_amount: Uint128,
         );
         fn unjail(&self, deps: DepsMut, val: &str);
-        fn tombstone(
+        fn slash(
             &self,
             deps: DepsMut,
             val: &str,
             nominal_slash_ratio: Decimal,
             slash_amount: Uint128,
         );
+        fn quick_withheld_rewards(
+            &self,
+            deps: Deps<VirtualStakeCustomQuery>,
+            epoch: u64,
+            val: &str,
+        ) -> WithheldRewardsResponse;
+        fn tombstone(
+            &self,
+            deps: DepsMut,
+            val: &str,
+            nominal_slash_ratio: Decimal,
+            slash_amount: Uint128,
+        ) -> HitEpochResult;
         fn add_val(&self, deps: DepsMut, val: &str);
         fn remove_val(&self, deps: DepsMut, val: &str);
+        fn quick_set_strategy(&self, deps: DepsMut, strategy: DistributionStrategy, epochs: u32);
     }
 
     impl VirtualStakingExt for VirtualStakingContract<'_> {
         fn quick_inst(&self, deps: DepsMut) {
-            self.instantiate(InstantiateCtx {
-                deps,
-                env: mock_env(),
-                info: mock_info("me", &[]),
-            })
+            self.instantiate(
+                InstantiateCtx {
+                    deps,
+                    env: mock_env(),
+                    info: mock_info("me", &[]),
+                },
+                None,
+            )
             .unwrap();
         }
 
@@ -1338,10 +2531,11 @@ mod tests {
                 .unwrap()
                 .amount
                 .u128();
-            deps.querier = MockQuerier::new(&[(
-                mock_env().contract.address.as_str(),
-                &coins(old_amount + amount, &denom),
-            )]);
+            // Only the balance changes, the custom queries are still answered
+            deps.querier.update_balance(
+                mock_env().contract.address,
+                coins(old_amount + amount, &denom),
+            );
 
             let result = PushRewardsResult::new(
                 self.reply_rewards(deps.as_mut(), mock_env())
@@ -1349,9 +2543,9 @@ mod tests {
                     .messages,
             );
 
-            if let PushRewardsResult::Batch(_) = result {
-                deps.querier =
-                    MockQuerier::new(&[(mock_env().contract.address.as_str(), &coins(0, denom))]);
+            if let PushRewardsResult::Batch(..) = result {
+                deps.querier
+                    .update_balance(mock_env().contract.address, coins(0, &denom));
             }
 
             result
@@ -1366,6 +2560,15 @@ mod tests {
             HitEpochResult::new(self.handle_epoch(deps).unwrap())
         }
 
+        #[track_caller]
+        fn hit_max_cap_changed(&self, deps: DepsMut) -> HitEpochResult {
+            let deps = SudoCtx {
+                deps,
+                env: mock_env(),
+            };
+            HitEpochResult::new(self.handle_max_cap_changed(deps).unwrap())
+        }
+
         fn quick_bond(&self, deps: DepsMut, validator: &str, amount: u128) {
             let denom = self.config.load(deps.storage).unwrap().denom;
 
@@ -1445,6 +2648,7 @@ mod tests {
                     slash_amount,
                     slash_ratio: nominal_slash_ratio.to_string(),
                 }]),
+                None,
             )
             .unwrap();
         }
@@ -1463,11 +2667,12 @@ mod tests {
                 Some(vec![val.to_string()]),
                 None,
                 None,
+                None,
             )
             .unwrap();
         }
 
-        fn tombstone(
+        fn slash(
             &self,
             deps: DepsMut,
             val: &str,
@@ -1478,7 +2683,6 @@ mod tests {
                 deps,
                 env: mock_env(),
             };
-            // We sent a slash along with the tombstone, as this is what the blockchain does
             self.handle_valset_update(
                 deps,
                 None,
@@ -1486,7 +2690,7 @@ mod tests {
                 None,
                 None,
                 None,
-                Some(vec![val.to_string()]),
+                None,
                 Some(vec![ValidatorSlash {
                     address: val.to_string(),
                     height: 0,
@@ -1497,10 +2701,65 @@ mod tests {
                     slash_amount,
                     slash_ratio: nominal_slash_ratio.to_string(),
                 }]),
+                None,
             )
             .unwrap();
         }
 
+        fn quick_withheld_rewards(
+            &self,
+            deps: Deps<VirtualStakeCustomQuery>,
+            epoch: u64,
+            val: &str,
+        ) -> WithheldRewardsResponse {
+            self.withheld_rewards(
+                QueryCtx {
+                    deps,
+                    env: mock_env(),
+                },
+                epoch,
+                val.to_string(),
+            )
+            .unwrap()
+        }
+
+        fn tombstone(
+            &self,
+            deps: DepsMut,
+            val: &str,
+            nominal_slash_ratio: Decimal,
+            slash_amount: Uint128,
+        ) -> HitEpochResult {
+            let deps = SudoCtx {
+                deps,
+                env: mock_env(),
+            };
+            // We sent a slash along with the tombstone, as this is what the blockchain does
+            let res = self
+                .handle_valset_update(
+                    deps,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(vec![val.to_string()]),
+                    Some(vec![ValidatorSlash {
+                        address: val.to_string(),
+                        height: 0,
+                        time: 0,
+                        infraction_height: 0,
+                        infraction_time: 0,
+                        power: 0,
+                        slash_amount,
+                        slash_ratio: nominal_slash_ratio.to_string(),
+                    }]),
+                    None,
+                )
+                .unwrap();
+            HitEpochResult::new(res)
+        }
+
         fn add_val(&self, deps: DepsMut, val: &str) {
             let val = cosmwasm_std::Validator {
                 address: val.to_string(),
@@ -1512,8 +2771,18 @@ mod tests {
                 deps,
                 env: mock_env(),
             };
-            self.handle_valset_update(deps, Some(vec![val]), None, None, None, None, None, None)
-                .unwrap();
+            self.handle_valset_update(
+                deps,
+                Some(vec![val]),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
         }
 
         fn remove_val(&self, deps: DepsMut, val: &str) {
@@ -1530,6 +2799,20 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+            )
+            .unwrap();
+        }
+
+        fn quick_set_strategy(&self, deps: DepsMut, strategy: DistributionStrategy, epochs: u32) {
+            self.set_strategy(
+                ExecCtx {
+                    deps,
+                    env: mock_env(),
+                    info: mock_info(OWNER, &[]),
+                },
+                strategy,
+                epochs,
             )
             .unwrap();
         }
@@ -1537,27 +2820,50 @@ mod tests {
 
     enum PushRewardsResult {
         Empty,
-        Batch(Vec<RewardInfo>),
+        /// Rewards sent to the converter, and the amount returned to the community pool
+        Batch(Vec<RewardInfo>, u128),
     }
 
     impl PushRewardsResult {
         fn new<C: cosmwasm_std::CustomMsg>(data: Vec<SubMsg<C>>) -> Self {
-            match &data[..] {
-                [] => Self::Empty,
-                [SubMsg {
-                    msg: CosmosMsg::Wasm(WasmMsg::Execute { msg: bin_msg, .. }),
-                    ..
-                }] => {
-                    if let converter_api::sv::ExecMsg::DistributeRewards { mut payments } =
-                        from_json(bin_msg).unwrap()
-                    {
-                        payments.sort();
-                        Self::Batch(payments)
-                    } else {
-                        panic!("failed to deserialize DistributeRewards msg")
+            if data.is_empty() {
+                return Self::Empty;
+            }
+
+            let mut rewards = vec![];
+            let mut returned = 0;
+            for SubMsg { msg, .. } in data {
+                match msg {
+                    CosmosMsg::Wasm(WasmMsg::Execute {
+                        msg: bin_msg,
+                        funds,
+                        ..
+                    }) => {
+                        if let converter_api::sv::ExecMsg::DistributeRewards { mut payments } =
+                            from_json(bin_msg).unwrap()
+                        {
+                            // The converter is sent exactly the rewards it distributes
+                            let sum: Uint128 = payments.iter().map(|p| p.reward).sum();
+                            assert_eq!(funds[0].amount, sum);
+                            payments.sort();
+                            rewards = payments;
+                        } else {
+                            panic!("failed to deserialize DistributeRewards msg")
+                        }
+                    }
+                    CosmosMsg::Distribution(DistributionMsg::FundCommunityPool { amount }) => {
+                        returned = amount[0].amount.u128()
                     }
+                    _ => panic!("invalid response"),
                 }
-                _ => panic!("invalid response"),
+            }
+            Self::Batch(rewards, returned)
+        }
+
+        fn returned(&self) -> u128 {
+            match self {
+                Self::Empty => 0,
+                Self::Batch(_, returned) => *returned,
             }
         }
 
@@ -1573,7 +2879,7 @@ mod tests {
         fn assert_eq(&self, expected: &[(&str, u128)]) {
             if expected.is_empty() {
                 self.assert_empty();
-            } else if let Self::Batch(rewards) = self {
+            } else if let Self::Batch(rewards, _) = self {
                 let mut expected = expected
                     .iter()
                     .map(|(val, reward)| RewardInfo {
@@ -1592,35 +2898,57 @@ mod tests {
     struct HitEpochResult {
         virtual_stake_msgs: Vec<VirtualStakeMsg>,
         withdraw_reward_msgs: Vec<String>,
+        converter_msgs: Vec<converter_api::sv::ExecMsg>,
     }
 
     impl HitEpochResult {
         fn new(data: Response<VirtualStakeCustomMsg>) -> Self {
-            use itertools::Either;
-            use itertools::Itertools as _;
-
-            let (virtual_stake_msgs, withdraw_reward_msgs) = data
-                .messages
-                .into_iter()
-                .partition_map(|SubMsg { msg, .. }| {
-                    if let CosmosMsg::Custom(VirtualStakeCustomMsg::VirtualStake(msg)) = msg {
-                        Either::Left(msg)
-                    } else if let CosmosMsg::Distribution(
-                        DistributionMsg::WithdrawDelegatorReward { validator },
-                    ) = msg
-                    {
-                        Either::Right(validator)
-                    } else {
-                        panic!("invalid message: {:?}", msg)
+            let mut virtual_stake_msgs = vec![];
+            let mut withdraw_reward_msgs = vec![];
+            let mut converter_msgs = vec![];
+
+            for SubMsg { msg, .. } in data.messages {
+                match msg {
+                    CosmosMsg::Custom(VirtualStakeCustomMsg::VirtualStake(msg)) => {
+                        virtual_stake_msgs.push(msg)
                     }
-                });
+                    CosmosMsg::Distribution(DistributionMsg::WithdrawDelegatorReward {
+                        validator,
+                    }) => withdraw_reward_msgs.push(validator),
+                    CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                        converter_msgs.push(from_json(msg).unwrap())
+                    }
+                    msg => panic!("invalid message: {:?}", msg),
+                }
+            }
 
             Self {
                 virtual_stake_msgs,
                 withdraw_reward_msgs,
+                converter_msgs,
             }
         }
 
+        #[track_caller]
+        fn assert_forced_unbonds(&self, expected: Option<&[(&str, u128)]>) -> &Self {
+            let reported = self.converter_msgs.iter().find_map(|msg| {
+                if let converter_api::sv::ExecMsg::MaxCapUpdate { unbonds, .. } = msg {
+                    Some(
+                        unbonds
+                            .iter()
+                            .map(|u| (u.validator.as_str(), u.amount.amount.u128()))
+                            .collect::<Vec<_>>(),
+                    )
+                } else {
+                    None
+                }
+            });
+
+            assert_eq!(reported.as_deref(), expected);
+
+            self
+        }
+
         #[track_caller]
         fn assert_no_bonding(&self) -> &Self {
             if !self.virtual_stake_msgs.is_empty() {
@@ -1,5 +1,7 @@
 use cosmwasm_std::{StdError, Uint128};
 use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
+use mesh_apis::ownership_api::OwnershipError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -10,21 +12,31 @@ pub enum ContractError {
     #[error("{0}")]
     Payment(#[from] PaymentError),
 
-    #[error("Unauthorized")]
-    Unauthorized,
+    #[error("{0}")]
+    Ownership(OwnershipError),
 
-    #[error("Wrong denom. Cannot stake {0}")]
-    WrongDenom(String),
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
     #[error("Cannot unbond {1} tokens from validator {0}, not enough staked")]
     InsufficientBond(String, Uint128),
 
-    #[error("Invalid Reply ID. Don't recognize {0}")]
-    InvalidReplyId(u64),
-
     #[error("Empty validators list")]
     NoValidators {},
 
     #[error("Virtual staking {0} has not enough delegated funds: {1}")]
     InsufficientDelegations(String, Uint128),
+
+    #[error("A strategy transition is already in progress")]
+    StrategyTransitionInProgress,
+}
+
+impl From<OwnershipError> for ContractError {
+    fn from(err: OwnershipError) -> Self {
+        match err {
+            // Unwrapped, so non owners get the same error as any other unauthorized caller
+            OwnershipError::Mesh(err) => ContractError::Mesh(err),
+            err => ContractError::Ownership(err),
+        }
+    }
 }
@@ -4,3 +4,4 @@ pub mod msg;
 #[cfg(test)]
 mod multitest;
 pub mod state;
+pub mod strategy;
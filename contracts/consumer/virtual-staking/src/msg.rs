@@ -1,6 +1,9 @@
 use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Decimal, Uint128};
+pub use mesh_apis::virtual_staking_api::ValidatorBondStatus;
 
-use crate::state::Config;
+use crate::state::{Config, WithholdingPolicy};
+use crate::strategy::{DistributionStrategy, StrategyTransition};
 
 #[cw_serde]
 pub struct ConfigResponse {
@@ -19,3 +22,26 @@ impl From<Config> for ConfigResponse {
         }
     }
 }
+
+#[cw_serde]
+pub struct StrategyResponse {
+    pub strategy: DistributionStrategy,
+    /// Transition to `strategy` in progress, if any
+    pub transition: Option<StrategyTransition>,
+}
+
+#[cw_serde]
+pub struct WithheldRewardsResponse {
+    pub policy: WithholdingPolicy,
+    /// Epoch in progress
+    pub current_epoch: u64,
+    /// Combined ratio the validator was slashed by during the epoch
+    pub slash_ratio: Decimal,
+    /// Rewards of the epoch withheld from the validator
+    pub withheld: Uint128,
+}
+
+#[cw_serde]
+pub struct AllBondStatusesResponse {
+    pub bonds: Vec<ValidatorBondStatus>,
+}
@@ -1,5 +1,6 @@
 use cosmwasm_std::{Addr, Decimal, Validator};
 use cw_multi_test::no_init;
+use mesh_apis::ownership_api::sv::mt::OwnershipApiProxy;
 use mesh_apis::virtual_staking_api::sv::mt::VirtualStakingApiProxy;
 use sylvia::multitest::Proxy;
 
@@ -60,6 +61,8 @@ fn setup<'a>(app: &'a App, args: SetupArgs<'a>) -> SetupResponse<'a> {
             JUNO.to_owned(),
             virtual_staking_code.code_id(),
             Some(admin.to_owned()),
+            None,
+            None,
         )
         .with_label("Juno Converter")
         .with_admin(admin)
@@ -117,6 +120,10 @@ fn instantiation() {
     // let's query virtual staking to find the owner
     let vs_config = virtual_staking.config().unwrap();
     assert_eq!(vs_config.converter, converter.contract_addr.to_string());
+
+    // the converter owner is passed along to virtual staking
+    let vs_ownership = virtual_staking.ownership().unwrap();
+    assert_eq!(vs_ownership.owner, Some(Addr::unchecked(owner)));
 }
 
 #[test]
@@ -170,6 +177,7 @@ fn valset_update_sudo() {
         None,
         Some(tombs),
         None,
+        None,
     );
     println!("res: {:?}", res);
     res.unwrap();
@@ -1,6 +1,8 @@
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::Addr;
 
+use crate::strategy::DistributionStrategy;
+
 #[cw_serde]
 pub struct Config {
     /// The denom we accept for staking
@@ -8,4 +10,29 @@ pub struct Config {
 
     /// The address of the converter contract (that is authorized to bond/unbond and will receive rewards)
     pub converter: Addr,
+
+    /// How the bonded tokens are spread across the validators
+    #[serde(default)]
+    pub strategy: DistributionStrategy,
+
+    /// What happens to the rewards held back from the validators slashed during the epoch
+    #[serde(default)]
+    pub withholding: WithholdingPolicy,
+}
+
+/// Destination of the rewards withheld from a validator slashed during the epoch they were
+/// earned in. The withheld part is the slash ratio of the rewards
+#[cw_serde]
+pub enum WithholdingPolicy {
+    /// Paid to the validators of the same rewards batch that were not slashed, proportionally
+    /// to their rewards. Returned to the community pool if there are none
+    Redistribute {},
+    /// Returned to the community pool
+    Return {},
+}
+
+impl Default for WithholdingPolicy {
+    fn default() -> Self {
+        WithholdingPolicy::Redistribute {}
+    }
 }
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Uint128;
+
+/// How the bonded virtual tokens are spread across the validators
+#[cw_serde]
+pub enum DistributionStrategy {
+    /// Every validator is bonded what the provider stakers selected, scaled down to the max cap
+    ProportionalToProviderSelection {},
+    /// The total requested is spread evenly across the active (not jailed or tombstoned)
+    /// validators
+    EvenAcrossActiveSet {},
+    /// As `ProportionalToProviderSelection`, but the stake selected for the `validators` is
+    /// spread over the other selected validators, proportionally to their stake
+    ExcludeList { validators: Vec<String> },
+}
+
+impl Default for DistributionStrategy {
+    fn default() -> Self {
+        DistributionStrategy::ProportionalToProviderSelection {}
+    }
+}
+
+/// Switch of strategy, executed gradually over `epochs` epochs
+#[cw_serde]
+pub struct StrategyTransition {
+    /// Strategy being moved away from
+    pub from: DistributionStrategy,
+    /// Epochs the transition takes
+    pub epochs: u32,
+    /// Epochs already elapsed
+    pub elapsed: u32,
+}
+
+/// Bond `requests`, scaled down so their sum doesn't exceed `max_cap`
+pub fn proportional(requests: &[(String, Uint128)], max_cap: Uint128) -> Vec<(String, Uint128)> {
+    let total: Uint128 = requests.iter().map(|(_, v)| v).sum();
+    requests
+        .iter()
+        .map(|(validator, v)| {
+            let v = if total > max_cap {
+                v.multiply_ratio(max_cap, total)
+            } else {
+                *v
+            };
+            (validator.clone(), v)
+        })
+        .collect()
+}
+
+/// Bond `requests` without the `excluded` validators, their stake being spread over the other
+/// ones proportionally. Scaled down so their sum doesn't exceed `max_cap`
+pub fn excluding(
+    requests: &[(String, Uint128)],
+    excluded: &[String],
+    max_cap: Uint128,
+) -> Vec<(String, Uint128)> {
+    let total: Uint128 = requests.iter().map(|(_, v)| v).sum();
+    let kept: Vec<_> = requests
+        .iter()
+        .filter(|(validator, _)| !excluded.contains(validator))
+        .cloned()
+        .collect();
+    let kept_total: Uint128 = kept.iter().map(|(_, v)| v).sum();
+    if kept_total.is_zero() {
+        return vec![];
+    }
+    let kept: Vec<_> = kept
+        .into_iter()
+        .map(|(validator, v)| (validator, v.multiply_ratio(total, kept_total)))
+        .collect();
+    proportional(&kept, max_cap)
+}
+
+/// Bond `total` (at most `max_cap`) evenly across `validators`. The remainder goes to the first
+/// ones, in address order
+pub fn even(
+    total: Uint128,
+    max_cap: Uint128,
+    mut validators: Vec<String>,
+) -> Vec<(String, Uint128)> {
+    if validators.is_empty() {
+        return vec![];
+    }
+    validators.sort();
+    validators.dedup();
+
+    let amount = total.min(max_cap);
+    let count = Uint128::new(validators.len() as u128);
+    let share = amount / count;
+    let remainder = (amount % count).u128() as usize;
+    validators
+        .into_iter()
+        .enumerate()
+        .map(|(i, validator)| {
+            let extra = if i < remainder { 1u128 } else { 0 };
+            (validator, share + Uint128::new(extra))
+        })
+        .filter(|(_, v)| !v.is_zero())
+        .collect()
+}
+
+/// Delegations `elapsed / epochs` of the way from `from` to `to`
+pub fn blend(
+    from: &[(String, Uint128)],
+    to: &[(String, Uint128)],
+    elapsed: u32,
+    epochs: u32,
+) -> Vec<(String, Uint128)> {
+    let elapsed = elapsed.min(epochs);
+    let mut blended = BTreeMap::<String, Uint128>::new();
+    for (validator, v) in from {
+        *blended.entry(validator.clone()).or_default() +=
+            v.multiply_ratio(epochs - elapsed, epochs);
+    }
+    for (validator, v) in to {
+        *blended.entry(validator.clone()).or_default() += v.multiply_ratio(elapsed, epochs);
+    }
+    blended.into_iter().filter(|(_, v)| !v.is_zero()).collect()
+}
@@ -1,5 +1,6 @@
 use cosmwasm_std::StdError;
 use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::VersionError;
 use thiserror::Error;
 
@@ -14,8 +15,8 @@ pub enum ContractError {
     #[error("{0}")]
     IbcVersion(#[from] VersionError),
 
-    #[error("The provided IBC channel is not open")]
-    IbcChannelNotOpen,
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
     #[error("You must start the channel handshake on this side, it doesn't support OpenTry")]
     IbcOpenTryDisallowed,
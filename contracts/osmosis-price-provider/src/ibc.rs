@@ -8,6 +8,7 @@ use cosmwasm_std::{
     StdError, Timestamp,
 };
 
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::{PriceFeedProviderAck, ProtocolVersion, RemotePriceFeedPacket};
 
 use crate::{contract::OsmosisPriceProvider, error::ContractError};
@@ -41,10 +42,7 @@ pub fn ibc_channel_open(
     // Check the version. If provided, ensure it is compatible.
     // If not provided, use our most recent version.
     let version = if channel.version.is_empty() {
-        ProtocolVersion {
-            protocol: PROTOCOL_NAME.to_string(),
-            version: SUPPORTED_IBC_PROTOCOL_VERSION.to_string(),
-        }
+        ProtocolVersion::new(PROTOCOL_NAME, SUPPORTED_IBC_PROTOCOL_VERSION)
     } else {
         let v: ProtocolVersion = from_json(channel.version.as_bytes())?;
         // if we can build a response to this, then it is compatible. And we use the highest version there
@@ -105,7 +103,7 @@ pub fn ibc_channel_close(
                 .iter()
                 .enumerate()
                 .find(|(_, c)| c == &msg.channel())
-                .ok_or(ContractError::IbcChannelNotOpen)?
+                .ok_or(MeshError::ChannelNotOpen)?
                 .0;
             v.remove(ix);
             Ok(v)
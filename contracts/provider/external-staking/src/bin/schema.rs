@@ -1,4 +1,7 @@
+use std::path::Path;
+
 use cosmwasm_schema::write_api;
+use mesh_apis::ibc::schema::export_schemas;
 
 use mesh_external_staking::contract::sv::{ContractExecMsg, ContractQueryMsg, InstantiateMsg};
 
@@ -9,4 +12,5 @@ fn main() {
         execute: ContractExecMsg,
         query: ContractQueryMsg,
     }
+    export_schemas(Path::new("schema/ibc"));
 }
@@ -0,0 +1,307 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{ensure, Decimal, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::{Bound, Item, Map};
+use std::cmp::min;
+
+use crate::error::ContractError;
+
+/// Max number of expired instant unbonds unlocked at once. The others are unlocked by the next
+/// buffer operations
+pub const UNLOCK_BATCH: usize = 30;
+
+/// Liquid tokens backing the instant unstakes.
+///
+/// Instantly unstaked tokens are released to the vault right away, while they are still
+/// unbonding on the consumer, and so still slashable. Their max slashing is locked in the
+/// buffer until their unbonding period is over, and their slashings are taken from it.
+#[cw_serde]
+#[derive(Default)]
+pub struct BufferState {
+    /// Tokens in the buffer, including the locked ones
+    pub total: Uint128,
+    /// Tokens backing the instant unstakes
+    pub locked: Uint128,
+    /// Tokens slashed from the buffer, not burned yet
+    pub slashed: Uint128,
+}
+
+impl BufferState {
+    pub fn free(&self) -> Uint128 {
+        self.total - self.locked
+    }
+}
+
+/// Instantly unstaked tokens, still unbonding on the consumer
+#[cw_serde]
+#[derive(Default)]
+pub struct InstantUnbond {
+    pub amount: Uint128,
+    /// Buffer tokens backing them
+    pub locked: Uint128,
+}
+
+/// Instant unstake waiting for its IBC ack
+#[cw_serde]
+pub struct PendingInstantUnstake {
+    /// Penalty paid, held by the contract until the unstake is committed
+    pub penalty: Uint128,
+    /// Buffer tokens reserved for it
+    pub locked: Uint128,
+}
+
+/// Tokens instantly unstaked during an epoch
+#[cw_serde]
+pub struct EpochUsage {
+    pub epoch: u64,
+    pub used: Uint128,
+}
+
+pub struct Buffer<'a> {
+    pub state: Item<'a, BufferState>,
+    /// Instant unbonds by `(release time in seconds, validator)`
+    pub unbonds: Map<'a, (u64, &'a str), InstantUnbond>,
+    /// Instant unstakes waiting for their IBC ack, by tx id
+    pub pending: Map<'a, u64, PendingInstantUnstake>,
+    pub usage: Item<'a, EpochUsage>,
+}
+
+impl<'a> Buffer<'a> {
+    pub const fn new(
+        state_key: &'a str,
+        unbonds_key: &'a str,
+        pending_key: &'a str,
+        usage_key: &'a str,
+    ) -> Self {
+        Self {
+            state: Item::new(state_key),
+            unbonds: Map::new(unbonds_key),
+            pending: Map::new(pending_key),
+            usage: Item::new(usage_key),
+        }
+    }
+
+    /// Unlocks the tokens backing the instant unbonds released by `now`, and returns the
+    /// updated buffer state
+    pub fn unlock_expired(
+        &self,
+        storage: &mut dyn Storage,
+        now: Timestamp,
+    ) -> StdResult<BufferState> {
+        let mut state = self.state.may_load(storage)?.unwrap_or_default();
+        let expired = self
+            .unbonds
+            .range(storage, None, None, Order::Ascending)
+            .take_while(|item| {
+                item.as_ref()
+                    .map_or(true, |((release_at, _), _)| *release_at <= now.seconds())
+            })
+            .take(UNLOCK_BATCH)
+            .collect::<StdResult<Vec<_>>>()?;
+        for ((release_at, validator), unbond) in expired {
+            self.unbonds.remove(storage, (release_at, &validator));
+            state.locked -= unbond.locked;
+        }
+        self.state.save(storage, &state)?;
+        Ok(state)
+    }
+
+    pub fn deposit(&self, storage: &mut dyn Storage, amount: Uint128) -> StdResult<()> {
+        let mut state = self.state.may_load(storage)?.unwrap_or_default();
+        state.total += amount;
+        self.state.save(storage, &state)
+    }
+
+    /// Takes `amount` free tokens out of the buffer
+    pub fn withdraw(
+        &self,
+        storage: &mut dyn Storage,
+        now: Timestamp,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let mut state = self.unlock_expired(storage, now)?;
+        ensure!(
+            state.free() >= amount,
+            ContractError::InsufficientBuffer(state.free())
+        );
+        state.total -= amount;
+        self.state.save(storage, &state)?;
+        Ok(())
+    }
+
+    /// Counts `amount` against the instant unstakes limit of the current epoch
+    pub fn use_quota(
+        &self,
+        storage: &mut dyn Storage,
+        now: Timestamp,
+        epoch_length: u64,
+        max_per_epoch: Uint128,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let epoch = now.seconds() / epoch_length;
+        let mut usage = self
+            .usage
+            .may_load(storage)?
+            .filter(|usage| usage.epoch == epoch)
+            .unwrap_or(EpochUsage {
+                epoch,
+                used: Uint128::zero(),
+            });
+        let remaining = max_per_epoch.saturating_sub(usage.used);
+        ensure!(
+            amount <= remaining,
+            ContractError::InstantUnstakeLimit(remaining)
+        );
+        usage.used += amount;
+        self.usage.save(storage, &usage)?;
+        Ok(())
+    }
+
+    /// Tokens instantly unstaked during the epoch of `now`
+    pub fn used_quota(
+        &self,
+        storage: &dyn Storage,
+        now: Timestamp,
+        epoch_length: u64,
+    ) -> StdResult<Uint128> {
+        let epoch = now.seconds() / epoch_length;
+        Ok(self
+            .usage
+            .may_load(storage)?
+            .filter(|usage| usage.epoch == epoch)
+            .map_or(Uint128::zero(), |usage| usage.used))
+    }
+
+    /// Reserves `lock` free tokens for the instant unstake `tx_id`, until it is acked
+    pub fn reserve(
+        &self,
+        storage: &mut dyn Storage,
+        now: Timestamp,
+        tx_id: u64,
+        penalty: Uint128,
+        lock: Uint128,
+    ) -> Result<(), ContractError> {
+        let mut state = self.unlock_expired(storage, now)?;
+        ensure!(
+            state.free() >= lock,
+            ContractError::InsufficientBuffer(state.free())
+        );
+        state.locked += lock;
+        self.state.save(storage, &state)?;
+
+        let pending = PendingInstantUnstake {
+            penalty,
+            locked: lock,
+        };
+        self.pending.save(storage, tx_id, &pending)?;
+        Ok(())
+    }
+
+    /// Commits the unstake `tx_id` of `amount` tokens from `validator`, if it is an instant one.
+    /// Its reserved tokens stay locked until `bonded_until`, if the tokens are still bonded on
+    /// the consumer. Returns the penalty paid for it, `None` if not an instant unstake
+    pub fn commit(
+        &self,
+        storage: &mut dyn Storage,
+        tx_id: u64,
+        validator: &str,
+        amount: Uint128,
+        bonded_until: Option<Timestamp>,
+    ) -> StdResult<Option<Uint128>> {
+        let Some(pending) = self.pending.may_load(storage, tx_id)? else {
+            return Ok(None);
+        };
+        self.pending.remove(storage, tx_id);
+
+        match bonded_until {
+            Some(release_at) => {
+                self.unbonds.update(
+                    storage,
+                    (release_at.seconds(), validator),
+                    |unbond| -> StdResult<_> {
+                        let mut unbond = unbond.unwrap_or_default();
+                        unbond.amount += amount;
+                        unbond.locked += pending.locked;
+                        Ok(unbond)
+                    },
+                )?;
+            }
+            None => self.unlock(storage, pending.locked)?,
+        }
+        Ok(Some(pending.penalty))
+    }
+
+    /// Rolls back the unstake `tx_id`, if it is an instant one. Returns the penalty paid for it,
+    /// to be refunded
+    pub fn rollback(&self, storage: &mut dyn Storage, tx_id: u64) -> StdResult<Option<Uint128>> {
+        let Some(pending) = self.pending.may_load(storage, tx_id)? else {
+            return Ok(None);
+        };
+        self.pending.remove(storage, tx_id);
+        self.unlock(storage, pending.locked)?;
+        Ok(Some(pending.penalty))
+    }
+
+    fn unlock(&self, storage: &mut dyn Storage, amount: Uint128) -> StdResult<()> {
+        let mut state = self.state.may_load(storage)?.unwrap_or_default();
+        state.locked -= amount;
+        self.state.save(storage, &state)
+    }
+
+    /// Slashes the instant unbonds of `validator` still unbonding at `now`, like the pending
+    /// unbonds of the stakes. The slashed tokens are taken from the buffer, to be burned.
+    /// Returns the slashed amount
+    pub fn slash(
+        &self,
+        storage: &mut dyn Storage,
+        now: Timestamp,
+        validator: &str,
+        slash_ratio: Decimal,
+        unbonding_period: u64,
+        infraction_time: u64,
+    ) -> StdResult<Uint128> {
+        let bound = Bound::inclusive((now.seconds() + 1, ""));
+        let unbonds = self
+            .unbonds
+            .range(storage, Some(bound), None, Order::Ascending)
+            .filter(|item| {
+                item.as_ref()
+                    .map_or(true, |((release_at, unbond_validator), _)| {
+                        unbond_validator == validator
+                            && release_at - unbonding_period > infraction_time
+                    })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut slashed = Uint128::zero();
+        for ((release_at, validator), mut unbond) in unbonds {
+            // The locked tokens are the max slashing of the unbond
+            let slash = min(unbond.amount * slash_ratio, unbond.locked);
+            unbond.amount -= slash;
+            unbond.locked -= slash;
+            self.unbonds
+                .save(storage, (release_at, &validator), &unbond)?;
+            slashed += slash;
+        }
+        if slashed.is_zero() {
+            return Ok(slashed);
+        }
+
+        let mut state = self.state.may_load(storage)?.unwrap_or_default();
+        state.total -= slashed;
+        state.locked -= slashed;
+        state.slashed += slashed;
+        self.state.save(storage, &state)?;
+        Ok(slashed)
+    }
+
+    /// Takes the tokens slashed from the buffer, to be burned
+    pub fn take_slashed(&self, storage: &mut dyn Storage) -> StdResult<Uint128> {
+        let mut state = self.state.may_load(storage)?.unwrap_or_default();
+        let slashed = state.slashed;
+        if !slashed.is_zero() {
+            state.slashed = Uint128::zero();
+            self.state.save(storage, &state)?;
+        }
+        Ok(slashed)
+    }
+}
@@ -1,32 +1,60 @@
 use cosmwasm_std::{
-    coin, ensure, ensure_eq, to_json_binary, Coin, Decimal, DepsMut, Env, Event, IbcMsg, Order,
-    Response, StdResult, Storage, Uint128, Uint256, WasmMsg,
+    coin, coins, ensure, ensure_eq, Addr, BankMsg, BlockInfo, Coin, CosmosMsg, Decimal, DepsMut,
+    Env, Event, IbcMsg, Order, Response, StdResult, Storage, Timestamp, Uint128, Uint64,
+    VoteOption, WasmMsg,
 };
-use cw2::set_contract_version;
-use cw_storage_plus::{Bounder, Item, Map};
-use cw_utils::{nonpayable, PaymentError};
-use std::cmp::min;
-use std::collections::HashSet;
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bound, Bounder, Item, Map};
+use cw_utils::{may_pay, must_pay, nonpayable, PaymentError};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashSet};
 
-use mesh_apis::converter_api::{RewardInfo, ValidatorSlashInfo};
+use mesh_apis::converter_api::{RewardInfo, ValidatorSlashInfo, ValidatorUptime};
 use sylvia::contract;
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx};
 
 use mesh_apis::cross_staking_api::{self};
-use mesh_apis::ibc::{AddValidator, ProviderPacket};
+use mesh_apis::error::MeshError;
+use mesh_apis::events::{RewardsEvent, StakeEvent, UnstakeEvent};
+use mesh_apis::ibc::{
+    AddValidator, BatchedUnstake, Features, ProviderPacket, StakeChecksum, ValidatorStake,
+    ValsetChunk, VoteWeight,
+};
+use mesh_apis::migration::{Upgrade, OWNERSHIP_VERSION};
+use mesh_apis::ownership_api;
+use mesh_apis::slash_evidence_api;
 use mesh_apis::vault_api::{SlashInfo, VaultApiHelper};
-use mesh_sync::{Tx, ValueRange};
+use mesh_sync::{PointsAlignment, Tx, ValueRange};
 
-use crate::crdt::{CrdtState, State};
+use crate::buffer::Buffer;
+use crate::crdt::{CrdtState, State, ValidatorMetadata};
+use crate::distribution;
+use crate::dust::RewardDust;
 use crate::error::ContractError;
-use crate::ibc::{packet_timeout, IBC_CHANNEL};
+use crate::history::TxHistory;
+use crate::ibc::{channel_features, load_channel, packet_timeout, CLOSED_CHANNEL, REOPEN_APPROVED};
+use crate::idempotency::IdempotencyKeys;
 use crate::msg::{
-    AllPendingRewards, AllTxsResponse, AuthorizedEndpointResponse, ConfigResponse,
-    IbcChannelResponse, ListActiveValidatorsResponse, ListValidatorsResponse, PendingRewards,
-    StakeInfo, StakesResponse, TxResponse, ValidatorPendingRewards,
+    AllPendingRewards, AllTxsResponse, AuthorizedEndpointResponse, AutoCompoundResponse, AutoStake,
+    BatchStake, BufferResponse, ConfigResponse, DefaultValidatorResponse, FeesCollectedResponse,
+    IbcChannelResponse, IdempotencyKeyResponse, LeavingValidatorInfo, LeavingValidatorsResponse,
+    ListActiveValidatorsResponse, ListValidatorsResponse, MissingSequencesResponse,
+    PendingPacketInfo, PendingPacketsResponse, PendingRewards, PendingRewardsByDenom,
+    PendingSlashInfo, PendingSlashesResponse, ProcessedPacketInfo, ProcessedPacketsResponse,
+    QueuedUnstakesResponse, ReceiveVirtualStake, RemoteVoteTallyResponse, RewardDustResponse,
+    SequenceRange, SnapshotInfo, SnapshotsResponse, StakeChecksumResponse, StakeInfo,
+    StakesResponse, TxHistoryResponse, TxResponse, UnbondingBucket, UnbondingScheduleResponse,
+    ValidatorCapacityResponse, ValidatorHealthResponse, ValidatorPendingRewards,
+    ValidatorSelection, VaultInfo, VaultsResponse,
 };
 use crate::stakes::Stakes;
-use crate::state::{Config, Distribution, SlashRatio, Stake};
+use crate::state::{
+    Config, Distribution, InstantUnstakeConfig, LeavingValidator, PenaltyDestination,
+    PendingPacket, PendingSlash, PendingUnbond, ProcessedPacket, ProtocolFee,
+    RewardsTransferConfig, SlashRatio, Snapshot, SnapshotProgress, Stake, StakeTxKind,
+    UnstakeQueue, ValidatorHealth, ValidatorPreferences, MAX_FEE_BPS,
+};
+use crate::vaults::{StakeSource, Vaults};
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -34,13 +62,70 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const DEFAULT_PAGE_LIMIT: u32 = 10;
 pub const MAX_PAGE_LIMIT: u32 = 30;
 
-pub const DISTRIBUTION_POINTS_SCALE: Uint256 = Uint256::from_u128(1_000_000_000);
+/// Max number of timed out packets sent again by a single `retry_packets` call
+pub const RETRY_PACKETS_BATCH: usize = 30;
+
+/// Max number of leaving validators removed at once, when their grace period is over
+pub const REMOVAL_BATCH: usize = 30;
+
+/// Max number of validator stakes released by a single `withdraw_unbonded` call, unless
+/// configured otherwise
+pub const DEFAULT_WITHDRAW_BATCH: u32 = 50;
+
+/// Max number of stakes slashed at once. Slashings of validators with more stakes are resumed
+/// by `continue_slashing`
+pub const SLASH_BATCH: usize = 30;
+
+/// Max number of stakes snapshotted by a single `take_snapshots` call
+pub const SNAPSHOT_BATCH: usize = 30;
+
+/// Max number of unstakes sent in a single `UnstakeBatch` packet. Fuller queues are sent right
+/// away
+pub const UNSTAKE_BATCH: usize = 50;
+
+/// Default size of the unbonding schedule buckets - one day
+pub const DEFAULT_UNBONDING_BUCKET_SECS: u64 = 24 * 60 * 60;
+
+/// Part of the compounded rewards paid to the caller of `crank_compound_rewards`
+pub const COMPOUND_CRANK_INCENTIVE: Decimal = Decimal::permille(5);
+
+/// Max number of validators in each list of the user staking preferences
+pub const MAX_PREFERRED_VALIDATORS: usize = 50;
 
 /// Aligns pagination limit
 fn clamp_page_limit(limit: Option<u32>) -> usize {
     limit.unwrap_or(DEFAULT_PAGE_LIMIT).max(MAX_PAGE_LIMIT) as usize
 }
 
+/// Display info of a validator, as reported by the consumer
+pub(crate) fn validator_metadata(validator: &AddValidator) -> ValidatorMetadata {
+    ValidatorMetadata {
+        commission: validator.commission,
+        moniker: validator.moniker.clone(),
+    }
+}
+
+/// Pays an instant unstake penalty out, to the configured destination
+fn penalty_msg(config: &Config, penalty: Uint128) -> Option<BankMsg> {
+    if penalty.is_zero() {
+        return None;
+    }
+    let amount = coins(penalty.u128(), &config.denom);
+    let destination = config
+        .instant_unstake
+        .as_ref()
+        .map(|instant| &instant.penalty_destination);
+    let msg = match destination {
+        Some(PenaltyDestination::RewardsPool { address }) => BankMsg::Send {
+            to_address: address.clone(),
+            amount,
+        },
+        // Penalties of the instant unstakes acked after it was disabled are burned
+        Some(PenaltyDestination::Burn {}) | None => BankMsg::Burn { amount },
+    };
+    Some(msg)
+}
+
 pub struct ExternalStakingContract<'a> {
     pub config: Item<'a, Config>,
     /// Stakes indexed by `(owner, validator)` pair
@@ -52,6 +137,66 @@ pub struct ExternalStakingContract<'a> {
     pub pending_txs: Map<'a, u64, Tx>,
     /// Valset CRDT
     pub val_set: CrdtState<'a>,
+    /// Consumer packets already processed, indexed by (channel id, IBC sequence)
+    pub processed_packets: Map<'a, (&'a str, u64), ProcessedPacket>,
+    /// Highest consumer packet sequence received on each channel
+    pub last_received_sequences: Map<'a, &'a str, u64>,
+    /// Gaps in the received sequences, indexed by (channel id, first missing sequence), with the
+    /// last missing sequence of the range as value
+    pub missing_sequences: Map<'a, (&'a str, u64), u64>,
+    /// Number of chunks received of the valset updates still being received, by height
+    pub valset_chunks: Map<'a, u64, u32>,
+    /// Provider packets that timed out, indexed by their IBC sequence, waiting to be retried
+    pub pending_packets: Map<'a, u64, PendingPacket>,
+    /// Users who opted in for permissionless compounding of their rewards
+    pub auto_compound: Map<'a, &'a Addr, ()>,
+    /// Last self-stake reported by the consumer for each validator
+    pub self_stakes: Map<'a, &'a str, Uint128>,
+    /// Max cross-stake accepted by the consumer on each capped validator
+    pub max_external_stakes: Map<'a, &'a str, Uint128>,
+    /// Validators removed from the consumer active set, during their removal grace period
+    pub leaving_validators: Map<'a, &'a str, LeavingValidator>,
+    /// Validator staked to by `receive_virtual_stake` with an empty msg, for each user
+    pub default_validators: Map<'a, &'a Addr, String>,
+    /// Validators each user denies or restricts their stakes to, if set
+    pub validator_preferences: Map<'a, &'a Addr, ValidatorPreferences>,
+    /// Rolling `StakeChecksum` of the total stake per validator, kept in sync by
+    /// `save_distribution`
+    pub stake_checksum: Item<'a, Uint64>,
+    /// Infractions already slashed for, by `(validator, infraction height)`, whether reported by
+    /// the consumer or through `submit_evidence`
+    pub slashed_infractions: Map<'a, (&'a str, u64), ()>,
+    /// Slashings not applied to all of the stakes yet, by `(validator, infraction height)`
+    pub pending_slashes: Map<'a, (&'a str, u64), PendingSlash>,
+    /// Stake snapshots, by `(user, validator, slot)`. Slots are a ring buffer of
+    /// `snapshot_capacity` entries per stake, indexed by the epoch modulo the capacity
+    pub snapshots: Map<'a, (&'a Addr, &'a str, u32), Snapshot>,
+    pub snapshot_progress: Item<'a, SnapshotProgress>,
+    /// Liquid tokens backing the instant unstakes
+    pub buffer: Buffer<'a>,
+    /// Denom of the in-flight rewards withdrawals, by tx id, when not the main rewards denom
+    pub pending_withdrawal_denoms: Map<'a, u64, String>,
+    /// Idempotency keys of the processed stakes and unstakes
+    pub idempotency_keys: IdempotencyKeys<'a>,
+    /// Protocol fees collected on the rewards and not withdrawn yet, by denom
+    pub fees_collected: Map<'a, &'a str, Uint128>,
+    /// In-flight protocol fees withdrawals, by tx id
+    pub pending_fee_withdrawals: Map<'a, u64, Coin>,
+    /// Votes relayed by `vote_remote`, by `(proposal id, user)`, with the weight they were cast
+    /// with
+    pub remote_votes: Map<'a, (u64, &'a Addr), VoteWeight>,
+    /// Tally of the votes relayed on each consumer proposal
+    pub remote_vote_tallies: Map<'a, u64, Vec<VoteWeight>>,
+    /// Recent stake, unstake and withdraw operations of the users
+    pub tx_history: TxHistory<'a>,
+    /// Last signing performance reported by the consumer for each validator
+    pub validator_health: Map<'a, &'a str, ValidatorHealth>,
+    /// Sub-unit rewards of the users, paid out once over the dust threshold
+    pub reward_dust: RewardDust<'a>,
+    /// Unstakes not sent to the consumer yet, with unstake batching
+    pub unstake_queue: Item<'a, UnstakeQueue>,
+    /// Additional vaults allowed to stake, and the vault of each user
+    pub vaults: Vaults<'a>,
 }
 
 impl Default for ExternalStakingContract<'_> {
@@ -64,6 +209,8 @@ impl Default for ExternalStakingContract<'_> {
 #[contract]
 #[sv::error(ContractError)]
 #[sv::messages(cross_staking_api as CrossStakingApi)]
+#[sv::messages(slash_evidence_api as SlashEvidenceApi)]
+#[sv::messages(ownership_api as OwnershipApi)]
 #[sv::messages(crate::test_methods as TestMethods)]
 impl ExternalStakingContract<'_> {
     pub fn new() -> Self {
@@ -74,7 +221,70 @@ impl ExternalStakingContract<'_> {
             pending_txs: Map::new("pending_txs"),
             tx_count: Item::new("tx_count"),
             val_set: CrdtState::new(),
+            processed_packets: Map::new("processed_packets"),
+            last_received_sequences: Map::new("last_received_sequences"),
+            missing_sequences: Map::new("missing_sequences"),
+            valset_chunks: Map::new("valset_chunks"),
+            pending_packets: Map::new("pending_packets"),
+            auto_compound: Map::new("auto_compound"),
+            self_stakes: Map::new("self_stakes"),
+            max_external_stakes: Map::new("max_external_stakes"),
+            leaving_validators: Map::new("leaving_validators"),
+            default_validators: Map::new("default_validators"),
+            validator_preferences: Map::new("validator_preferences"),
+            stake_checksum: Item::new("stake_checksum"),
+            slashed_infractions: Map::new("slashed_infractions"),
+            pending_slashes: Map::new("pending_slashes"),
+            snapshots: Map::new("snapshots"),
+            snapshot_progress: Item::new("snapshot_progress"),
+            buffer: Buffer::new(
+                "buffer",
+                "instant_unbonds",
+                "pending_instant_unstakes",
+                "instant_unstake_usage",
+            ),
+            pending_withdrawal_denoms: Map::new("pending_withdrawal_denoms"),
+            idempotency_keys: IdempotencyKeys::new(
+                "idempotency_keys",
+                "idempotency_keys__order",
+                "idempotency_keys__count",
+                "idempotency_keys__pending",
+            ),
+            fees_collected: Map::new("fees_collected"),
+            pending_fee_withdrawals: Map::new("pending_fee_withdrawals"),
+            remote_votes: Map::new("remote_votes"),
+            remote_vote_tallies: Map::new("remote_vote_tallies"),
+            tx_history: TxHistory::new(
+                "tx_history",
+                "tx_history__owners",
+                "tx_history__pending",
+                "tx_history__count",
+            ),
+            validator_health: Map::new("validator_health"),
+            reward_dust: RewardDust::new("reward_dust", "pending_dust_payouts"),
+            unstake_queue: Item::new("unstake_queue"),
+            vaults: Vaults::new("vaults", "stake_sources"),
+        }
+    }
+
+    /// Saves the validator distribution, updating the stake checksum if its total stake changed
+    fn save_distribution(
+        &self,
+        storage: &mut dyn Storage,
+        validator: &str,
+        old_total: Uint128,
+        distribution: &Distribution,
+    ) -> StdResult<()> {
+        if old_total != distribution.total_stake {
+            let checksum: StakeChecksum = self
+                .stake_checksum
+                .may_load(storage)?
+                .unwrap_or_default()
+                .into();
+            let checksum = checksum.update(validator, old_total, distribution.total_stake);
+            self.stake_checksum.save(storage, &checksum.into())?;
         }
+        self.distribution.save(storage, validator, distribution)
     }
 
     pub fn next_tx_id(&self, store: &mut dyn Storage) -> StdResult<u64> {
@@ -85,6 +295,7 @@ impl ExternalStakingContract<'_> {
         Ok(id)
     }
 
+    /// `owner` defaults to the sender if not set.
     #[allow(clippy::too_many_arguments)]
     #[sv::msg(instantiate)]
     pub fn instantiate(
@@ -96,6 +307,10 @@ impl ExternalStakingContract<'_> {
         unbonding_period: u64,
         remote_contact: crate::msg::AuthorizedEndpoint,
         slash_ratio: SlashRatio,
+        min_self_stake: Option<Uint128>,
+        removal_grace_period: u64,
+        max_withdraw_batch: Option<u32>,
+        owner: Option<String>,
     ) -> Result<Response, ContractError> {
         let vault = ctx.deps.api.addr_validate(&vault)?;
         let vault = VaultApiHelper(vault);
@@ -110,10 +325,28 @@ impl ExternalStakingContract<'_> {
             vault,
             unbonding_period,
             slash_ratio,
+            min_self_stake,
+            removal_grace_period,
+            max_withdraw_batch,
+            snapshot_interval: 0,
+            snapshot_capacity: 0,
+            instant_unstake: None,
+            extra_rewards_denoms: vec![],
+            rewards_transfer: None,
+            protocol_fee: None,
+            min_uptime: None,
+            dust_threshold: Uint128::zero(),
+            unstake_batching: false,
         };
 
         self.config.save(ctx.deps.storage, &config)?;
 
+        let owner = match owner {
+            Some(owner) => ctx.deps.api.addr_validate(&owner)?,
+            None => ctx.info.sender,
+        };
+        ownership_api::initialize_owner(ctx.deps.storage, Some(owner))?;
+
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
         remote_contact.validate()?;
@@ -128,234 +361,1752 @@ impl ExternalStakingContract<'_> {
                 "mesh-security",
             );
             crate::ibc::IBC_CHANNEL.save(ctx.deps.storage, &channel)?;
+            crate::ibc::IBC_FEATURES.save(ctx.deps.storage, &crate::ibc::SUPPORTED_FEATURES)?;
         }
 
         Ok(Response::new())
     }
 
-    /// In test code, this is called from `test_commit_stake`.
-    /// In non-test code, this is called from `ibc_packet_ack`
-    pub(crate) fn commit_stake(&self, deps: DepsMut, tx_id: u64) -> Result<WasmMsg, ContractError> {
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+    /// Migrates from an older version of the contract.
+    /// Contracts from before the ownership storage get their wasm admin as owner, as it was the
+    /// one approving channel re-openings and submitting evidence until then.
+    #[sv::msg(migrate)]
+    pub fn migrate(&self, ctx: MigrateCtx) -> Result<Response, ContractError> {
+        let stored = get_contract_version(ctx.deps.storage)?;
+        let upgrade = Upgrade::check(
+            &stored.contract,
+            &stored.version,
+            CONTRACT_NAME,
+            CONTRACT_VERSION,
+        )?;
+
+        if upgrade.crosses(OWNERSHIP_VERSION)? {
+            let admin = ownership_api::wasm_admin(&ctx.deps.querier, &ctx.env.contract.address)?;
+            ownership_api::migrate_owner(ctx.deps.storage, admin)?;
+        }
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-        // Verify tx is of the right type
-        ensure!(
-            matches!(tx, Tx::InFlightRemoteStaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
-        );
+        let event = Event::new("migrate")
+            .add_attribute("from_version", stored.version)
+            .add_attribute("to_version", CONTRACT_VERSION);
+        Ok(Response::new().add_event(event))
+    }
 
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteStaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
+    /// Records a received consumer packet, so a re-delivery of it is not applied twice.
+    /// Returns `false` if the packet was already processed.
+    ///
+    /// Called from `ibc_packet_receive`
+    pub(crate) fn record_packet(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        channel_id: &str,
+        sequence: u64,
+        packet_type: &str,
+    ) -> Result<bool, ContractError> {
+        if self.processed_packets.has(storage, (channel_id, sequence)) {
+            return Ok(false);
+        }
+
+        let processed = ProcessedPacket {
+            packet_type: packet_type.to_owned(),
+            height: block.height,
+            time: block.time,
         };
+        self.processed_packets
+            .save(storage, (channel_id, sequence), &processed)?;
+        Ok(true)
+    }
 
-        // Load stake
-        let mut stake = self
-            .stakes
-            .stake
-            .load(deps.storage, (&tx_user, &tx_validator))?;
+    /// Tracks the chunks received of the valset update at `height`. Every chunk is applied as
+    /// it comes, so a partially received update is the same as several smaller updates.
+    ///
+    /// Called from `ibc_packet_receive`
+    pub(crate) fn track_valset_chunk(
+        &self,
+        storage: &mut dyn Storage,
+        height: u64,
+        chunk: &ValsetChunk,
+    ) -> Result<Event, ContractError> {
+        let received = self
+            .valset_chunks
+            .may_load(storage, height)?
+            .unwrap_or_default()
+            + 1;
+        let complete = received >= chunk.total;
+        if complete {
+            self.valset_chunks.remove(storage, height);
+        } else {
+            self.valset_chunks.save(storage, height, &received)?;
+        }
 
-        // Load distribution
-        let mut distribution = self
-            .distribution
-            .may_load(deps.storage, &tx_validator)?
+        Ok(Event::new("valset_update_chunk")
+            .add_attribute("height", height.to_string())
+            .add_attribute("index", chunk.index.to_string())
+            .add_attribute("total", chunk.total.to_string())
+            .add_attribute("received", received.to_string())
+            .add_attribute("complete", complete.to_string()))
+    }
+
+    /// Tracks the sequence of a newly received consumer packet. Sequences skipped over are
+    /// recorded as missing until they are received, and reported with a `packet_gap` event.
+    ///
+    /// Called from `ibc_packet_receive`
+    pub(crate) fn track_sequence(
+        &self,
+        storage: &mut dyn Storage,
+        channel_id: &str,
+        sequence: u64,
+    ) -> Result<Option<Event>, ContractError> {
+        let last = self
+            .last_received_sequences
+            .may_load(storage, channel_id)?
             .unwrap_or_default();
 
-        // Commit stake (saturating up if slashed)
-        stake.stake.commit_add_saturating(tx_amount);
+        if sequence > last {
+            self.last_received_sequences
+                .save(storage, channel_id, &sequence)?;
+            if sequence == last + 1 {
+                return Ok(None);
+            }
+            let (first_missing, last_missing) = (last + 1, sequence - 1);
+            self.missing_sequences
+                .save(storage, (channel_id, first_missing), &last_missing)?;
+            let evt = Event::new("packet_gap")
+                .add_attribute("channel_id", channel_id)
+                .add_attribute("first_missing", first_missing.to_string())
+                .add_attribute("last_missing", last_missing.to_string());
+            return Ok(Some(evt));
+        }
 
-        // Distribution alignment
-        stake
-            .points_alignment
-            .stake_increased(tx_amount, distribution.points_per_stake);
-        distribution.total_stake += tx_amount;
+        // A late packet, filling (part of) a gap
+        let gap = self
+            .missing_sequences
+            .prefix(channel_id)
+            .range(
+                storage,
+                None,
+                Some(Bound::inclusive(sequence)),
+                Order::Descending,
+            )
+            .next()
+            .transpose()?;
+        if let Some((first, last)) = gap.filter(|(_, last)| *last >= sequence) {
+            self.missing_sequences.remove(storage, (channel_id, first));
+            if first < sequence {
+                self.missing_sequences
+                    .save(storage, (channel_id, first), &(sequence - 1))?;
+            }
+            if sequence < last {
+                self.missing_sequences
+                    .save(storage, (channel_id, sequence + 1), &last)?;
+            }
+        }
+        Ok(None)
+    }
 
-        // Save stake
-        self.stakes
-            .stake
-            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+    /// Queues a timed out packet, to be sent again with `retry_packets`.
+    /// Transactions of the packet are kept pending until it is finally acked.
+    ///
+    /// Called from `ibc_packet_timeout`
+    pub(crate) fn queue_timed_out_packet(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        sequence: u64,
+        packet: ProviderPacket,
+    ) -> Result<(), ContractError> {
+        let pending = PendingPacket {
+            packet,
+            timed_out_at: block.time,
+        };
+        self.pending_packets.save(storage, sequence, &pending)?;
+        Ok(())
+    }
 
-        // Save distribution
-        self.distribution
-            .save(deps.storage, &tx_validator, &distribution)?;
+    /// Sends the timed out packets again, with a fresh timeout. Permissionless, so relayer
+    /// operators can recover the channel traffic after an outage.
+    ///
+    /// At most `RETRY_PACKETS_BATCH` packets are sent, oldest first.
+    #[sv::msg(exec)]
+    pub fn retry_packets(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        // Remove tx
-        self.pending_txs.remove(deps.storage, tx_id);
+        let channel = load_channel(ctx.deps.storage)?;
+        let pending = self
+            .pending_packets
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .take(RETRY_PACKETS_BATCH)
+            .collect::<StdResult<Vec<_>>>()?;
 
-        // Call commit hook on vault
-        let cfg = self.config.load(deps.storage)?;
-        let msg = cfg.vault.commit_tx(tx_id)?;
-        Ok(msg)
+        let mut resp = Response::new()
+            .add_attribute("action", "retry_packets")
+            .add_attribute("retried", pending.len().to_string());
+        for (sequence, PendingPacket { packet, .. }) in pending {
+            self.pending_packets.remove(ctx.deps.storage, sequence);
+            let msg = IbcMsg::SendPacket {
+                channel_id: channel.endpoint.channel_id.clone(),
+                data: packet.encode(channel_features(ctx.deps.storage)?)?,
+                timeout: packet_timeout(&ctx.env, &packet),
+            };
+            resp = resp.add_message(msg).add_event(
+                Event::new("retry_packet")
+                    .add_attribute("sequence", sequence.to_string())
+                    .add_attribute("packet_type", packet.packet_type()),
+            );
+        }
+
+        Ok(resp)
     }
 
-    /// In test code, this is called from `test_rollback_stake`.
-    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
-    pub(crate) fn rollback_stake(
-        &self,
-        deps: DepsMut,
-        tx_id: u64,
-    ) -> Result<WasmMsg, ContractError> {
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+    /// Sends the checksum of the total stake per validator to the consumer, which compares it
+    /// with its own bookkeeping. Permissionless, meant to be called periodically.
+    ///
+    /// Fails while any tx is in flight, as the consumer may not agree on the stakes yet.
+    #[sv::msg(exec)]
+    pub fn send_stake_checksum(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        // Verify tx is of the right type
         ensure!(
-            matches!(tx, Tx::InFlightRemoteStaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
+            channel_features(ctx.deps.storage)?.contains(Features::STAKE_CHECKSUM),
+            ContractError::IbcFeatureNotSupported("stake checksums".to_owned())
+        );
+        ensure!(
+            self.pending_txs
+                .keys(ctx.deps.storage, None, None, Order::Ascending)
+                .next()
+                .is_none(),
+            ContractError::TxsInFlight
         );
 
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteStaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
-        };
-
-        // Load stake
-        let mut stake = self
-            .stakes
-            .stake
-            .load(deps.storage, (&tx_user, &tx_validator))?;
-
-        // Rollback add amount (saturating up if slashed)
-        stake.stake.rollback_add_saturating(tx_amount);
+        let checksum = self
+            .stake_checksum
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
 
-        // Save stake
-        self.stakes
-            .stake
-            .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_attribute("action", "send_stake_checksum")
+            .add_attribute("checksum", checksum.to_string());
 
-        // Remove tx
-        self.pending_txs.remove(deps.storage, tx_id);
+        let channel = load_channel(ctx.deps.storage)?;
+        let packet = ProviderPacket::StakeChecksum { checksum };
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel.endpoint.channel_id,
+            data: packet.encode(channel_features(ctx.deps.storage)?)?,
+            timeout: packet_timeout(&ctx.env, &packet),
+        };
+        // send packet if we are ibc enabled
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            crate::ibc::record_test_packet(ctx.deps.storage, &msg)?;
+        }
 
-        // Call rollback hook on vault
-        let cfg = self.config.load(deps.storage)?;
-        let msg = cfg.vault.rollback_tx(tx_id)?;
-        Ok(msg)
+        Ok(resp)
     }
 
-    /// Schedules tokens for release, adding them to the pending unbonds. After the unbonding period
-    /// passes, funds are ready to be released through a `withdraw_unbonded` call by the user.
+    /// Votes on a consumer governance proposal, with the cross-staked weight of the sender (its
+    /// committed stake over all the validators). The votes of all the users are tallied per
+    /// option, and the whole tally is relayed to the consumer in a `Vote` packet, for the
+    /// virtual staking contract to cast as a weighted vote.
+    ///
+    /// Voting again replaces the previous vote of the sender, at its current weight.
     #[sv::msg(exec)]
-    pub fn unstake(
+    pub fn vote_remote(
         &self,
         ctx: ExecCtx,
-        validator: String,
-        amount: Coin,
+        proposal_id: u64,
+        vote: VoteOption,
     ) -> Result<Response, ContractError> {
-        let ExecCtx { info, deps, env } = ctx;
-        nonpayable(&info)?;
-
-        let config = self.config.load(deps.storage)?;
-
-        ensure_eq!(
-            amount.denom,
-            config.denom,
-            ContractError::InvalidDenom(config.denom)
-        );
-
-        let mut stake = self
-            .stakes
-            .stake
-            .may_load(deps.storage, (&info.sender, &validator))?
-            .unwrap_or_default();
+        nonpayable(&ctx.info)?;
 
         ensure!(
-            stake.stake.low() >= amount.amount,
-            ContractError::NotEnoughStake(stake.stake.low())
+            channel_features(ctx.deps.storage)?.contains(Features::REMOTE_GOVERNANCE),
+            ContractError::IbcFeatureNotSupported("remote governance".to_owned())
         );
 
-        stake.stake.prepare_sub(amount.amount, Uint128::zero())?;
-
-        self.stakes
+        let weight = self
+            .stakes
             .stake
-            .save(deps.storage, (&info.sender, &validator), &stake)?;
-
-        // Create new tx
-        let tx_id = self.next_tx_id(deps.storage)?;
+            .prefix(&ctx.info.sender)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, stake)| stake.stake.low()))
+            .sum::<StdResult<Uint128>>()?;
+        ensure!(!weight.is_zero(), ContractError::NoVotingWeight);
 
-        // Save tx
-        let new_tx = Tx::InFlightRemoteUnstaking {
-            id: tx_id,
-            amount: amount.amount,
-            user: info.sender.clone(),
-            validator: validator.clone(),
-        };
-        self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+        let mut tally = self
+            .remote_vote_tallies
+            .may_load(ctx.deps.storage, proposal_id)?
+            .unwrap_or_default();
+        if let Some(previous) = self
+            .remote_votes
+            .may_load(ctx.deps.storage, (proposal_id, &ctx.info.sender))?
+        {
+            if let Some(entry) = tally.iter_mut().find(|e| e.option == previous.option) {
+                entry.weight -= previous.weight;
+            }
+        }
+        match tally.iter_mut().find(|e| e.option == vote) {
+            Some(entry) => entry.weight += weight,
+            None => tally.push(VoteWeight {
+                option: vote.clone(),
+                weight,
+            }),
+        }
+        tally.retain(|e| !e.weight.is_zero());
+        self.remote_vote_tallies
+            .save(ctx.deps.storage, proposal_id, &tally)?;
+        self.remote_votes.save(
+            ctx.deps.storage,
+            (proposal_id, &ctx.info.sender),
+            &VoteWeight {
+                option: vote.clone(),
+                weight,
+            },
+        )?;
 
         #[allow(unused_mut)]
         let mut resp = Response::new()
-            .add_attribute("action", "unstake")
-            .add_attribute("amount", amount.amount.to_string())
-            .add_attribute("owner", info.sender);
-
-        let channel = IBC_CHANNEL.load(deps.storage)?;
-        let packet = ProviderPacket::Unstake {
-            validator,
-            unstake: amount,
-            tx_id,
-        };
+            .add_attribute("action", "vote_remote")
+            .add_attribute("sender", ctx.info.sender.to_string())
+            .add_attribute("proposal_id", proposal_id.to_string())
+            .add_attribute("vote", format!("{:?}", vote))
+            .add_attribute("weight", weight.to_string());
+
+        let channel = load_channel(ctx.deps.storage)?;
+        let packet = ProviderPacket::Vote { proposal_id, tally };
         let msg = IbcMsg::SendPacket {
             channel_id: channel.endpoint.channel_id,
-            data: to_json_binary(&packet)?,
-            timeout: packet_timeout(&env),
+            data: packet.encode(channel_features(ctx.deps.storage)?)?,
+            timeout: packet_timeout(&ctx.env, &packet),
         };
         // send packet if we are ibc enabled
-        // TODO: send in test code when we can handle it
         #[cfg(not(any(test, feature = "mt")))]
         {
             resp = resp.add_message(msg);
         }
         #[cfg(any(test, feature = "mt"))]
         {
-            let _ = msg;
+            crate::ibc::record_test_packet(ctx.deps.storage, &msg)?;
         }
 
         Ok(resp)
     }
 
-    /// In test code, this is called from `test_commit_unstake`.
-    /// In non-test code, this is called from `ibc_packet_ack`
-    pub(crate) fn commit_unstake(
-        &self,
-        deps: DepsMut,
-        env: Env,
-        tx_id: u64,
-    ) -> Result<(), ContractError> {
-        use crate::state::PendingUnbond;
+    /// Allows a new channel, from the same (connection, port), to replace the closed one.
+    /// Only the owner can call this.
+    ///
+    /// Stakes, distribution and the validator set are kept, so cross-staking resumes on the new
+    /// channel once its handshake completes.
+    #[sv::msg(exec)]
+    pub fn approve_channel_reopen(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        // Load tx
-        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
 
-        // Verify tx is of the right type
-        ensure!(
-            matches!(tx, Tx::InFlightRemoteUnstaking { .. }),
-            ContractError::WrongTypeTx(tx_id, tx)
-        );
+        let closed = CLOSED_CHANNEL
+            .may_load(ctx.deps.storage)?
+            .ok_or(ContractError::IbcChannelNotClosed)?;
+        REOPEN_APPROVED.save(ctx.deps.storage, &true)?;
 
-        let (tx_amount, tx_user, tx_validator) = match tx {
-            Tx::InFlightRemoteUnstaking {
-                amount,
-                user,
-                validator,
-                ..
-            } => (amount, user, validator),
-            _ => unreachable!(),
-        };
+        let resp = Response::new()
+            .add_attribute("action", "approve_channel_reopen")
+            .add_attribute("closed_channel_id", closed.endpoint.channel_id);
+        Ok(resp)
+    }
 
-        let config = self.config.load(deps.storage)?;
+    /// Removes the leaving validators whose grace period is over, at most `REMOVAL_BATCH` of
+    /// them. Returns the removed validators.
+    ///
+    /// Called from `valset_update`, and from `finalize_removals`
+    fn remove_leaving_validators(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+    ) -> Result<Vec<String>, ContractError> {
+        let due = self
+            .leaving_validators
+            .range(storage, None, None, Order::Ascending)
+            .filter(|item| {
+                item.as_ref()
+                    .map_or(true, |(_, leaving)| leaving.finalize_at <= block.time)
+            })
+            .take(REMOVAL_BATCH)
+            .collect::<StdResult<Vec<_>>>()?;
 
-        // Load stake
-        let mut stake = self
+        for (valoper, leaving) in &due {
+            // Applied after any update received for the validator during the grace period
+            let latest = self
+                .val_set
+                .validator_at_height(storage, valoper, u64::MAX)?;
+            let (height, time) = match latest {
+                Some(latest) => (
+                    max(leaving.height, latest.start_height),
+                    max(leaving.time, latest.start_time),
+                ),
+                None => (leaving.height, leaving.time),
+            };
+            self.val_set
+                .remove_validator(storage, valoper, height, time)?;
+            self.leaving_validators.remove(storage, valoper);
+        }
+
+        Ok(due.into_iter().map(|(valoper, _)| valoper).collect())
+    }
+
+    /// Removes the validators whose removal grace period is over. Permissionless, as removals
+    /// are otherwise only finalized when a validator set update is received.
+    ///
+    /// At most `REMOVAL_BATCH` validators are removed.
+    #[sv::msg(exec)]
+    pub fn finalize_removals(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let removed = self.remove_leaving_validators(ctx.deps.storage, &ctx.env.block)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "finalize_removals")
+            .add_attribute("removed", removed.join(","));
+        Ok(resp)
+    }
+
+    /// Applies the oldest pending slashing of the validator to the next `SLASH_BATCH` stakes.
+    /// Permissionless, so anyone can complete slashings too large to fit in a single tx.
+    ///
+    /// The `remaining` attribute is set if the slashing is still not complete, in which case the
+    /// call should be repeated.
+    #[sv::msg(exec)]
+    pub fn continue_slashing(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let infraction_height = self
+            .pending_slashes
+            .prefix(&validator)
+            .keys(ctx.deps.storage, None, None, Order::Ascending)
+            .next()
+            .transpose()?
+            .ok_or_else(|| ContractError::NoPendingSlash(validator.clone()))?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let slash_msg = self.process_slashing(
+            &ctx.env,
+            ctx.deps.storage,
+            &config,
+            &validator,
+            infraction_height,
+        )?;
+        let remaining = self
+            .pending_slashes
+            .has(ctx.deps.storage, (&validator, infraction_height));
+
+        let mut resp = Response::new()
+            .add_attribute("action", "continue_slashing")
+            .add_attribute("validator", &validator)
+            .add_attribute("infraction_height", infraction_height.to_string())
+            .add_messages(slash_msg);
+        if remaining {
+            resp = resp.add_attribute("remaining", "true");
+        }
+        Ok(resp)
+    }
+
+    /// Takes stake snapshots every `interval` seconds, keeping the last `capacity` of them for
+    /// each stake. A zero interval disables the snapshots. Only the owner can call this.
+    ///
+    /// Existing snapshots are kept, but they may be overwritten earlier than `capacity`
+    /// epochs later if the interval or the capacity changed.
+    #[sv::msg(exec)]
+    pub fn set_snapshot_config(
+        &self,
+        ctx: ExecCtx,
+        interval: u64,
+        capacity: u32,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+        ensure!(
+            interval == 0 || capacity > 0,
+            ContractError::InvalidSnapshotCapacity
+        );
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.snapshot_interval = interval;
+        config.snapshot_capacity = capacity;
+        self.config.save(ctx.deps.storage, &config)?;
+        // Epochs are numbered after the interval, so the progress of the current one is lost
+        self.snapshot_progress.remove(ctx.deps.storage);
+
+        let resp = Response::new()
+            .add_attribute("action", "set_snapshot_config")
+            .add_attribute("interval", interval.to_string())
+            .add_attribute("capacity", capacity.to_string());
+        Ok(resp)
+    }
+
+    /// Records the stake and accumulated rewards of the next `SNAPSHOT_BATCH` stakes, for the
+    /// current epoch. Permissionless, it is meant to be called once per snapshot interval.
+    ///
+    /// The `remaining` attribute is set if not all of the stakes were snapshotted yet, in which
+    /// case the call should be repeated.
+    #[sv::msg(exec)]
+    pub fn take_snapshots(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure!(
+            config.snapshot_interval > 0,
+            ContractError::SnapshotsDisabled
+        );
+        let epoch = ctx.env.block.time.seconds() / config.snapshot_interval;
+        let slot = (epoch % u64::from(config.snapshot_capacity)) as u32;
+
+        let last = match self.snapshot_progress.may_load(ctx.deps.storage)? {
+            Some(progress) if progress.epoch == epoch => {
+                ensure!(!progress.complete, ContractError::SnapshotsTaken(epoch));
+                progress.last
+            }
+            _ => None,
+        };
+        let bound = last
+            .as_ref()
+            .map(|(user, validator)| Bound::exclusive((user, validator.as_str())));
+
+        let stakes = self
+            .stakes
+            .stake
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(SNAPSHOT_BATCH)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut distributions = BTreeMap::new();
+        for ((user, validator), stake) in &stakes {
+            if !distributions.contains_key(validator) {
+                let distribution = self
+                    .distribution
+                    .may_load(ctx.deps.storage, validator)?
+                    .unwrap_or_default();
+                distributions.insert(validator.clone(), distribution);
+            }
+            let rewards = Self::calculate_reward(stake, &distributions[validator])?;
+            let snapshot = Snapshot {
+                epoch,
+                time: ctx.env.block.time,
+                stake: stake.stake.low(),
+                accumulated_rewards: rewards + stake.withdrawn_funds,
+            };
+            self.snapshots
+                .save(ctx.deps.storage, (user, validator, slot), &snapshot)?;
+        }
+
+        let complete = stakes.len() < SNAPSHOT_BATCH;
+        let progress = SnapshotProgress {
+            epoch,
+            last: stakes.last().map(|(key, _)| key.clone()).or(last),
+            complete,
+        };
+        self.snapshot_progress.save(ctx.deps.storage, &progress)?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "take_snapshots")
+            .add_attribute("epoch", epoch.to_string())
+            .add_attribute("count", stakes.len().to_string());
+        if !complete {
+            resp = resp.add_attribute("remaining", "true");
+        }
+        Ok(resp)
+    }
+
+    /// Prepares a stake addition, to be committed or rolled back once the IBC packet is acked
+    fn prepare_stake(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        validator: &str,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        if !self.val_set.is_active_validator(storage, validator)? {
+            return Err(ContractError::ValidatorNotActive(validator.to_owned()));
+        }
+        self.ensure_allowed(storage, owner, validator)?;
+        if self.leaving_validators.has(storage, validator) {
+            return Err(ContractError::ValidatorLeaving(validator.to_owned()));
+        }
+        self.ensure_not_slashing(storage, validator)?;
+        if let Some(min_self_stake) = self.config.load(storage)?.min_self_stake {
+            let self_stake = self
+                .self_stakes
+                .may_load(storage, validator)?
+                .unwrap_or_default();
+            ensure!(
+                self_stake >= min_self_stake,
+                ContractError::SelfStakeTooLow(validator.to_owned())
+            );
+        }
+        if let Some(max_stake) = self.max_external_stakes.may_load(storage, validator)? {
+            let total_stake = self.validator_stake(storage, validator)?;
+            ensure!(
+                total_stake + amount <= max_stake,
+                ContractError::ValidatorCapReached(validator.to_owned(), max_stake)
+            );
+        }
+        let mut stake = self
+            .stakes
+            .stake
+            .may_load(storage, (owner, validator))?
+            .unwrap_or_default();
+
+        // Prepare stake addition and save stake.
+        // We don't check for max here, as this call can only come from the `vault` contract, which already
+        // performed the proper check.
+        stake.stake.prepare_add(amount, None)?;
+        self.stakes
+            .stake
+            .save(storage, (owner, validator), &stake)?;
+        Ok(())
+    }
+
+    /// Fails if the staking preferences of `owner` exclude `validator`
+    fn ensure_allowed(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        validator: &str,
+    ) -> Result<(), ContractError> {
+        let preferences = self
+            .validator_preferences
+            .may_load(storage, owner)?
+            .unwrap_or_default();
+        ensure!(
+            preferences.allows(validator),
+            ContractError::ValidatorExcluded {
+                user: owner.to_string(),
+                validator: validator.to_owned(),
+            }
+        );
+        Ok(())
+    }
+
+    /// Splits a remote staking tx into its owner and `(validator, amount)` pairs
+    fn remote_staking_tx(
+        tx_id: u64,
+        tx: Tx,
+    ) -> Result<(Addr, Vec<(String, Uint128)>), ContractError> {
+        match tx {
+            Tx::InFlightRemoteStaking {
+                amount,
+                user,
+                validator,
+                ..
+            } => Ok((user, vec![(validator, amount)])),
+            Tx::InFlightRemoteStakingBatch { user, stakes, .. } => Ok((user, stakes)),
+            Tx::InFlightCancelUnbond {
+                amount,
+                user,
+                validator,
+                ..
+            } => Ok((user, vec![(validator, amount)])),
+            tx => Err(ContractError::WrongTypeTx(tx_id, tx)),
+        }
+    }
+
+    /// Vault the stakes of a tx come from. A tx only stakes from a single vault
+    fn tx_vault(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+        stakes: &[(String, Uint128)],
+    ) -> StdResult<VaultApiHelper> {
+        let config = self.config.load(storage)?;
+        let source = match stakes.first() {
+            Some((validator, _)) => self.vaults.source(storage, &config, user, validator)?,
+            None => StakeSource::main(&config),
+        };
+        Ok(source.vault())
+    }
+
+    /// In test code, this is called from `test_commit_stake`.
+    /// In non-test code, this is called from `ibc_packet_ack`
+    ///
+    /// Cancelled unbonds are committed as well, without any vault hook as their lien was kept.
+    pub(crate) fn commit_stake(
+        &self,
+        deps: DepsMut,
+        tx_id: u64,
+    ) -> Result<Option<WasmMsg>, ContractError> {
+        // Load tx
+        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        let cancel_unbond = matches!(tx, Tx::InFlightCancelUnbond { .. });
+        self.tx_history.settle(deps.storage, tx_id, true)?;
+        self.idempotency_keys.settle(deps.storage, tx_id, true)?;
+
+        // Verify tx is of the right type
+        let (tx_user, tx_stakes) = Self::remote_staking_tx(tx_id, tx)?;
+        let vault = self.tx_vault(deps.storage, &tx_user, &tx_stakes)?;
+
+        for (tx_validator, tx_amount) in tx_stakes {
+            // Load stake
+            let mut stake = self
+                .stakes
+                .stake
+                .load(deps.storage, (&tx_user, &tx_validator))?;
+
+            // Load distribution
+            let mut distribution = self
+                .distribution
+                .may_load(deps.storage, &tx_validator)?
+                .unwrap_or_default();
+            let old_total = distribution.total_stake;
+
+            // Commit stake (saturating up if slashed)
+            stake.stake.commit_add_saturating(tx_amount);
+
+            // Distribution alignment
+            stake.stake_increased(tx_amount, &distribution);
+            distribution.total_stake += tx_amount;
+
+            // Save stake
+            self.stakes
+                .stake
+                .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+
+            // Save distribution
+            self.save_distribution(deps.storage, &tx_validator, old_total, &distribution)?;
+        }
+
+        // Remove tx
+        self.pending_txs.remove(deps.storage, tx_id);
+        if cancel_unbond {
+            return Ok(None);
+        }
+
+        // Call commit hook on the vault the stake comes from
+        let msg = vault.commit_tx(tx_id)?;
+        Ok(Some(msg))
+    }
+
+    /// In test code, this is called from `test_rollback_stake`.
+    /// In non-test code, this is called from `ibc_packet_ack`
+    ///
+    /// Cancelled unbonds are put back into the pending unbonds.
+    pub(crate) fn rollback_stake(
+        &self,
+        deps: DepsMut,
+        tx_id: u64,
+    ) -> Result<Option<WasmMsg>, ContractError> {
+        // Load tx
+        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        self.tx_history.settle(deps.storage, tx_id, false)?;
+        self.idempotency_keys.settle(deps.storage, tx_id, false)?;
+        let unbonds = match &tx {
+            Tx::InFlightCancelUnbond { unbonds, .. } => Some(
+                unbonds
+                    .iter()
+                    .map(|&(amount, release_at)| PendingUnbond { amount, release_at })
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
+        };
+
+        // Verify tx is of the right type
+        let (tx_user, tx_stakes) = Self::remote_staking_tx(tx_id, tx)?;
+        let vault = self.tx_vault(deps.storage, &tx_user, &tx_stakes)?;
+
+        for (tx_validator, tx_amount) in tx_stakes {
+            // Load stake
+            let mut stake = self
+                .stakes
+                .stake
+                .load(deps.storage, (&tx_user, &tx_validator))?;
+
+            // Rollback add amount (saturating up if slashed)
+            stake.stake.rollback_add_saturating(tx_amount);
+            if let Some(unbonds) = unbonds.clone() {
+                stake.restore_pending(unbonds);
+            }
+
+            // Save stake
+            self.stakes
+                .stake
+                .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
+        }
+
+        // Remove tx
+        self.pending_txs.remove(deps.storage, tx_id);
+        if unbonds.is_some() {
+            return Ok(None);
+        }
+
+        // Call rollback hook on the vault the stake comes from
+        let msg = vault.rollback_tx(tx_id)?;
+        Ok(Some(msg))
+    }
+
+    /// Schedules tokens for release, adding them to the pending unbonds. After the unbonding period
+    /// passes, funds are ready to be released through a `withdraw_unbonded` call by the user.
+    ///
+    /// With unstake batching, the unstake is queued instead of being sent to the consumer right
+    /// away, see `queue_unstake`.
+    ///
+    /// If `idempotency_key` is set, the unstake is rejected if the user already used it, so
+    /// retrying a tx that actually landed doesn't unstake twice.
+    #[sv::msg(exec)]
+    pub fn unstake(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        amount: Coin,
+        idempotency_key: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+        nonpayable(&info)?;
+
+        let (tx_id, msg) =
+            self.prepare_unstake(deps.storage, &env, &info.sender, &validator, &amount)?;
+        if let Some(key) = &idempotency_key {
+            self.idempotency_keys
+                .record(deps.storage, &info.sender, key, tx_id)?;
+        }
+        let msgs = if self.batches_unstakes(deps.storage)? {
+            self.queue_unstake(deps.storage, &env, tx_id)?
+        } else {
+            vec![msg]
+        };
+        let stake_tx_id = self.tx_history.record(
+            deps.storage,
+            &env.block,
+            &info.sender,
+            StakeTxKind::Unstake,
+            Some(&validator),
+            amount.clone(),
+            Some(tx_id),
+        )?;
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_event(Event::from(
+                UnstakeEvent::new(amount.clone())
+                    .delegator(&info.sender)
+                    .validator(&validator)
+                    .lienholder(&env.contract.address),
+            ))
+            .add_attribute("action", "unstake")
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("owner", info.sender)
+            .add_attribute("tx_id", tx_id.to_string())
+            .add_attribute("stake_tx_id", stake_tx_id.to_string());
+
+        // send packet if we are ibc enabled
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_messages(msgs);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            for msg in &msgs {
+                crate::ibc::record_test_packet(deps.storage, msg)?;
+            }
+        }
+
+        Ok(resp)
+    }
+
+    /// Sends the queued unstakes to the consumer right away, instead of with the first unstake
+    /// of a later block. Anyone can call this
+    #[sv::msg(exec)]
+    pub fn flush_unstakes(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+        nonpayable(&info)?;
+
+        let msgs = self.flush_unstake_queue(deps.storage, &env)?;
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_attribute("action", "flush_unstakes")
+            .add_attribute("flushed", (!msgs.is_empty()).to_string());
+
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_messages(msgs);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            for msg in &msgs {
+                crate::ibc::record_test_packet(deps.storage, msg)?;
+            }
+        }
+
+        Ok(resp)
+    }
+
+    /// Unstakes without waiting for the unbonding period, for a penalty of `penalty_rate` of the
+    /// amount, to be sent along in the staking denom. The tokens are released to the vault as
+    /// soon as the unstake is acked by the consumer. The penalty is then burned or sent to the
+    /// rewards pool, or refunded if the unstake fails.
+    ///
+    /// The tokens are still unbonding on the consumer until the unbonding period is over, and
+    /// so can still be slashed. The buffer must have enough free tokens to cover their max
+    /// slashing, which are locked until then. At most `max_per_epoch` tokens can be instantly
+    /// unstaked by all of the users during an epoch.
+    ///
+    /// `idempotency_key` is handled as in `unstake`.
+    #[sv::msg(exec)]
+    pub fn unstake_instant(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        amount: Coin,
+        idempotency_key: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+
+        let config = self.config.load(deps.storage)?;
+        let instant = config
+            .instant_unstake
+            .clone()
+            .ok_or(ContractError::InstantUnstakeDisabled)?;
+        ensure_eq!(
+            amount.denom,
+            config.denom,
+            MeshError::InvalidDenom(config.denom)
+        );
+        let penalty = amount.amount * instant.penalty_rate;
+        ensure!(
+            may_pay(&info, &config.denom)? == penalty,
+            ContractError::WrongPenalty(penalty)
+        );
+
+        self.buffer.use_quota(
+            deps.storage,
+            env.block.time,
+            instant.epoch_length,
+            instant.max_per_epoch,
+            amount.amount,
+        )?;
+        let (tx_id, msg) =
+            self.prepare_unstake(deps.storage, &env, &info.sender, &validator, &amount)?;
+        if let Some(key) = &idempotency_key {
+            self.idempotency_keys
+                .record(deps.storage, &info.sender, key, tx_id)?;
+        }
+        let max_slash = max(config.slash_ratio.double_sign, config.slash_ratio.offline);
+        let lock = amount.amount * max_slash;
+        self.buffer
+            .reserve(deps.storage, env.block.time, tx_id, penalty, lock)?;
+        let burn_msg = self.burn_slashed_buffer(deps.storage, &config.denom)?;
+        let stake_tx_id = self.tx_history.record(
+            deps.storage,
+            &env.block,
+            &info.sender,
+            StakeTxKind::InstantUnstake,
+            Some(&validator),
+            amount.clone(),
+            Some(tx_id),
+        )?;
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_messages(burn_msg)
+            .add_event(Event::from(
+                UnstakeEvent::new(amount.clone())
+                    .delegator(&info.sender)
+                    .validator(&validator)
+                    .lienholder(&env.contract.address),
+            ))
+            .add_attribute("action", "unstake_instant")
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("owner", info.sender)
+            .add_attribute("penalty", penalty.to_string())
+            .add_attribute("locked", lock.to_string())
+            .add_attribute("tx_id", tx_id.to_string())
+            .add_attribute("stake_tx_id", stake_tx_id.to_string());
+
+        // send packet if we are ibc enabled
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            crate::ibc::record_test_packet(deps.storage, &msg)?;
+        }
+
+        Ok(resp)
+    }
+
+    /// Cancels `amount` of the sender's tokens unbonding from `validator`, staking them again.
+    /// The most recent unbonds are cancelled first, and the released ones can't be cancelled.
+    ///
+    /// The consumer is sent a stake packet to re-bond the virtual stake. The cancelled unbonds
+    /// are restored if it fails. The vault lien is kept all along, so it isn't involved.
+    #[sv::msg(exec)]
+    pub fn cancel_unbond(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+        nonpayable(&info)?;
+
+        let config = self.config.load(deps.storage)?;
+        let source = self
+            .vaults
+            .source(deps.storage, &config, &info.sender, &validator)?;
+        ensure_eq!(
+            amount.denom,
+            source.denom,
+            MeshError::InvalidDenom(source.denom)
+        );
+        let channel = load_channel(deps.storage)?;
+
+        let mut stake = self
+            .stakes
+            .stake
+            .may_load(deps.storage, (&info.sender, &validator))?
+            .unwrap_or_default();
+        let unbonding = stake.unbonding(&env.block);
+        ensure!(
+            unbonding >= amount.amount,
+            ContractError::NotEnoughUnbonding(unbonding)
+        );
+        let unbonds = stake.cancel_pending(amount.amount);
+        self.stakes
+            .stake
+            .save(deps.storage, (&info.sender, &validator), &stake)?;
+        self.prepare_stake(deps.storage, &info.sender, &validator, amount.amount)?;
+
+        // Create new tx
+        let tx_id = self.next_tx_id(deps.storage)?;
+        let new_tx = Tx::InFlightCancelUnbond {
+            id: tx_id,
+            amount: amount.amount,
+            user: info.sender.clone(),
+            validator: validator.clone(),
+            unbonds: unbonds
+                .into_iter()
+                .map(|unbond| (unbond.amount, unbond.release_at))
+                .collect(),
+        };
+        self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+        let stake_tx_id = self.tx_history.record(
+            deps.storage,
+            &env.block,
+            &info.sender,
+            StakeTxKind::CancelUnbond,
+            Some(&validator),
+            amount.clone(),
+            Some(tx_id),
+        )?;
+
+        let packet = ProviderPacket::Stake {
+            validator: validator.clone(),
+            stake: amount.clone(),
+            tx_id,
+        };
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel.endpoint.channel_id,
+            data: packet.encode(channel_features(deps.storage)?)?,
+            timeout: packet_timeout(&env, &packet),
+        };
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_event(Event::from(
+                StakeEvent::new(amount.clone())
+                    .delegator(&info.sender)
+                    .validator(&validator)
+                    .lienholder(&env.contract.address),
+            ))
+            .add_attribute("action", "cancel_unbond")
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("owner", info.sender)
+            .add_attribute("tx_id", tx_id.to_string())
+            .add_attribute("stake_tx_id", stake_tx_id.to_string());
+
+        // send packet if we are ibc enabled
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            crate::ibc::record_test_packet(deps.storage, &msg)?;
+        }
+
+        Ok(resp)
+    }
+
+    /// Prepares an unstake, to be committed or rolled back once the IBC packet is acked.
+    /// Returns the tx id, and the message sending the packet
+    fn prepare_unstake(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        owner: &Addr,
+        validator: &str,
+        amount: &Coin,
+    ) -> Result<(u64, IbcMsg), ContractError> {
+        let config = self.config.load(storage)?;
+
+        // Unstaked in the denom of the vault the stake comes from
+        let source = self.vaults.source(storage, &config, owner, validator)?;
+        ensure_eq!(
+            amount.denom,
+            source.denom,
+            MeshError::InvalidDenom(source.denom)
+        );
+        self.ensure_not_slashing(storage, validator)?;
+
+        let mut stake = self
+            .stakes
+            .stake
+            .may_load(storage, (owner, validator))?
+            .unwrap_or_default();
+
+        ensure!(
+            stake.stake.low() >= amount.amount,
+            ContractError::NotEnoughStake(stake.stake.low())
+        );
+
+        stake.stake.prepare_sub(amount.amount, Uint128::zero())?;
+
+        self.stakes
+            .stake
+            .save(storage, (owner, validator), &stake)?;
+
+        // Create new tx
+        let tx_id = self.next_tx_id(storage)?;
+
+        // Save tx
+        let new_tx = Tx::InFlightRemoteUnstaking {
+            id: tx_id,
+            amount: amount.amount,
+            user: owner.clone(),
+            validator: validator.to_owned(),
+        };
+        self.pending_txs.save(storage, tx_id, &new_tx)?;
+
+        let channel = load_channel(storage)?;
+        let packet = ProviderPacket::Unstake {
+            validator: validator.to_owned(),
+            unstake: amount.clone(),
+            tx_id,
+        };
+        let msg = IbcMsg::SendPacket {
+            channel_id: channel.endpoint.channel_id,
+            data: packet.encode(channel_features(storage)?)?,
+            timeout: packet_timeout(env, &packet),
+        };
+        Ok((tx_id, msg))
+    }
+
+    /// Whether the unstakes are queued, rather than sent to the consumer right away
+    fn batches_unstakes(&self, storage: &dyn Storage) -> StdResult<bool> {
+        let config = self.config.load(storage)?;
+        Ok(config.unstake_batching && channel_features(storage)?.contains(Features::BATCH_UNSTAKE))
+    }
+
+    /// Queues the prepared unstake `tx_id`, to be sent along with the other unstakes of the
+    /// block. The unstakes queued in an earlier block are sent first, and a full queue is sent
+    /// right away. Returns the messages sending them
+    fn queue_unstake(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        tx_id: u64,
+    ) -> Result<Vec<IbcMsg>, ContractError> {
+        let mut msgs = vec![];
+        let mut queue = match self.unstake_queue.may_load(storage)? {
+            Some(queue) if queue.since < env.block.height => {
+                msgs.extend(self.unstake_batch_msgs(storage, env, queue.tx_ids)?);
+                None
+            }
+            queue => queue,
+        }
+        .unwrap_or_else(|| UnstakeQueue {
+            since: env.block.height,
+            tx_ids: vec![],
+        });
+
+        queue.tx_ids.push(tx_id);
+        if queue.tx_ids.len() >= UNSTAKE_BATCH {
+            msgs.extend(self.unstake_batch_msgs(storage, env, queue.tx_ids)?);
+            self.unstake_queue.remove(storage);
+        } else {
+            self.unstake_queue.save(storage, &queue)?;
+        }
+        Ok(msgs)
+    }
+
+    /// Empties the unstake queue, returning the messages sending its unstakes, if any
+    fn flush_unstake_queue(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+    ) -> Result<Vec<IbcMsg>, ContractError> {
+        let Some(queue) = self.unstake_queue.may_load(storage)? else {
+            return Ok(vec![]);
+        };
+        self.unstake_queue.remove(storage);
+        self.unstake_batch_msgs(storage, env, queue.tx_ids)
+    }
+
+    /// Messages sending the prepared unstakes `tx_ids`, in an `UnstakeBatch` packet per denom of
+    /// the vaults the stakes come from
+    fn unstake_batch_msgs(
+        &self,
+        storage: &dyn Storage,
+        env: &Env,
+        tx_ids: Vec<u64>,
+    ) -> Result<Vec<IbcMsg>, ContractError> {
+        let config = self.config.load(storage)?;
+        let mut batches: BTreeMap<String, Vec<BatchedUnstake>> = BTreeMap::new();
+        for tx_id in tx_ids {
+            match self.pending_txs.load(storage, tx_id)? {
+                Tx::InFlightRemoteUnstaking {
+                    amount,
+                    user,
+                    validator,
+                    ..
+                } => {
+                    let source = self.vaults.source(storage, &config, &user, &validator)?;
+                    batches
+                        .entry(source.denom)
+                        .or_default()
+                        .push(BatchedUnstake {
+                            validator,
+                            unstake: amount,
+                            tx_id,
+                        });
+                }
+                tx => return Err(ContractError::WrongTypeTx(tx_id, tx)),
+            }
+        }
+
+        let channel = load_channel(storage)?;
+        let features = channel_features(storage)?;
+        batches
+            .into_iter()
+            .map(|(denom, unstakes)| {
+                let packet = ProviderPacket::UnstakeBatch { unstakes, denom };
+                Ok::<_, ContractError>(IbcMsg::SendPacket {
+                    channel_id: channel.endpoint.channel_id.clone(),
+                    data: packet.encode(features)?,
+                    timeout: packet_timeout(env, &packet),
+                })
+            })
+            .collect()
+    }
+
+    /// Enables instant unstaking with the given parameters, or disables it if not set.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn set_instant_unstake_config(
+        &self,
+        ctx: ExecCtx,
+        instant_unstake: Option<InstantUnstakeConfig>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let instant_unstake = instant_unstake
+            .map(|mut instant| -> Result<_, ContractError> {
+                ensure!(
+                    instant.penalty_rate <= Decimal::one(),
+                    ContractError::InvalidInstantUnstakeConfig("penalty rate over 100%".to_owned())
+                );
+                ensure!(
+                    instant.epoch_length > 0,
+                    ContractError::InvalidInstantUnstakeConfig("zero epoch length".to_owned())
+                );
+                if let PenaltyDestination::RewardsPool { address } =
+                    &mut instant.penalty_destination
+                {
+                    *address = ctx.deps.api.addr_validate(address)?.into_string();
+                }
+                Ok(instant)
+            })
+            .transpose()?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        let enabled = instant_unstake.is_some();
+        config.instant_unstake = instant_unstake;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_instant_unstake_config")
+            .add_attribute("enabled", enabled.to_string());
+        Ok(resp)
+    }
+
+    /// Adds an additional rewards denom, e.g. for incentives paid by the consumer on top of the
+    /// main rewards. Rewards denoms can't be removed, not to lose the pending rewards.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn add_rewards_denom(
+        &self,
+        ctx: ExecCtx,
+        denom: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        ensure!(
+            !config.is_rewards_denom(&denom),
+            ContractError::RewardsDenomExists(denom)
+        );
+        config.extra_rewards_denoms.push(denom.clone());
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "add_rewards_denom")
+            .add_attribute("denom", denom);
+        Ok(resp)
+    }
+
+    /// Accepts the rewards transferred by the converter with ICS-20 transfers, or stops
+    /// accepting them if not set. The transferred denom is added to the rewards denoms if needed.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn set_rewards_transfer(
+        &self,
+        ctx: ExecCtx,
+        rewards_transfer: Option<RewardsTransferConfig>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        let rewards_transfer = rewards_transfer
+            .map(|mut transfer| -> Result<_, ContractError> {
+                transfer.sender = ctx.deps.api.addr_validate(&transfer.sender)?.into_string();
+                // The main rewards are withdrawn with IBC packets
+                ensure!(
+                    transfer.denom != config.rewards_denom,
+                    ContractError::RewardsDenomExists(transfer.denom)
+                );
+                Ok(transfer)
+            })
+            .transpose()?;
+
+        let mut resp = Response::new().add_attribute("action", "set_rewards_transfer");
+        if let Some(transfer) = &rewards_transfer {
+            if !config.is_rewards_denom(&transfer.denom) {
+                config.extra_rewards_denoms.push(transfer.denom.clone());
+            }
+            resp = resp
+                .add_attribute("sender", &transfer.sender)
+                .add_attribute("denom", &transfer.denom);
+        }
+        config.rewards_transfer = rewards_transfer;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(resp)
+    }
+
+    /// Sets the protocol fee taken on the rewards before they are distributed, or disables it.
+    /// Fees already collected stay withdrawable by the new collector
+    #[sv::msg(exec)]
+    pub fn set_protocol_fee(
+        &self,
+        ctx: ExecCtx,
+        protocol_fee: Option<ProtocolFee>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let protocol_fee = protocol_fee
+            .map(|mut fee| -> Result<_, ContractError> {
+                ensure!(
+                    fee.rate_bps <= MAX_FEE_BPS,
+                    ContractError::InvalidProtocolFee(MAX_FEE_BPS)
+                );
+                fee.collector = ctx.deps.api.addr_validate(&fee.collector)?.into_string();
+                Ok(fee)
+            })
+            .transpose()?;
+
+        let mut resp = Response::new().add_attribute("action", "set_protocol_fee");
+        if let Some(fee) = &protocol_fee {
+            resp = resp
+                .add_attribute("rate_bps", fee.rate_bps.to_string())
+                .add_attribute("collector", &fee.collector);
+        }
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.protocol_fee = protocol_fee;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(resp)
+    }
+
+    /// Sets the uptime under which the stakers of a validator are warned, or disables the
+    /// warnings
+    #[sv::msg(exec)]
+    pub fn set_min_uptime(
+        &self,
+        ctx: ExecCtx,
+        min_uptime: Option<Decimal>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        if let Some(min_uptime) = min_uptime {
+            ensure!(
+                min_uptime <= Decimal::one(),
+                ContractError::InvalidMinUptime
+            );
+        }
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.min_uptime = min_uptime;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let mut resp = Response::new().add_attribute("action", "set_min_uptime");
+        if let Some(min_uptime) = min_uptime {
+            resp = resp.add_attribute("min_uptime", min_uptime.to_string());
+        }
+        Ok(resp)
+    }
+
+    /// Sets the whole tokens the rewards dust of a user has to reach to be paid out. Zero pays it
+    /// out as soon as it reaches one token
+    #[sv::msg(exec)]
+    pub fn set_dust_threshold(
+        &self,
+        ctx: ExecCtx,
+        threshold: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.dust_threshold = threshold;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_dust_threshold")
+            .add_attribute("threshold", threshold.to_string()))
+    }
+
+    /// Enables or disables unstake batching. The unstakes are then queued, and sent to the
+    /// consumer once per block in a single packet, if the channel supports it. Disabling it sends
+    /// the queued unstakes right away.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn set_unstake_batching(
+        &self,
+        ctx: ExecCtx,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        let ExecCtx { info, deps, env } = ctx;
+        nonpayable(&info)?;
+
+        ownership_api::assert_owner(deps.storage, &info.sender)?;
+
+        let mut config = self.config.load(deps.storage)?;
+        config.unstake_batching = enabled;
+        self.config.save(deps.storage, &config)?;
+
+        let msgs = if enabled {
+            vec![]
+        } else {
+            self.flush_unstake_queue(deps.storage, &env)?
+        };
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_attribute("action", "set_unstake_batching")
+            .add_attribute("enabled", enabled.to_string());
+
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_messages(msgs);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            for msg in &msgs {
+                crate::ibc::record_test_packet(deps.storage, msg)?;
+            }
+        }
+
+        Ok(resp)
+    }
+
+    /// Allows another vault, bonding `denom`, to stake through this contract. `denom` must be
+    /// the staking denom, as the stakes of all the vaults share the validator totals and are
+    /// priced by the consumer in it. Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn add_vault(
+        &self,
+        ctx: ExecCtx,
+        vault: String,
+        denom: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(denom, config.denom, MeshError::InvalidDenom(config.denom));
+        let vault = ctx.deps.api.addr_validate(&vault)?;
+        self.vaults.allowed.save(ctx.deps.storage, &vault, &denom)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "add_vault")
+            .add_attribute("vault", vault)
+            .add_attribute("denom", denom);
+
+        Ok(resp)
+    }
+
+    /// Stops a vault added with `add_vault` from staking. The stakes it already sent are still
+    /// released and slashed through it. Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn remove_vault(&self, ctx: ExecCtx, vault: String) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let vault = ctx.deps.api.addr_validate(&vault)?;
+        ensure!(
+            self.vaults.allowed.has(ctx.deps.storage, &vault),
+            ContractError::UnknownVault(vault.into_string())
+        );
+        self.vaults.allowed.remove(ctx.deps.storage, &vault);
+
+        let resp = Response::new()
+            .add_attribute("action", "remove_vault")
+            .add_attribute("vault", vault);
+
+        Ok(resp)
+    }
+
+    /// Withdraws the protocol fees collected in `denom` (the main rewards denom by default) to
+    /// the fee collector. Transferred rewards are paid on this chain, the others are sent to
+    /// `remote_recipient` on the consumer chain, like the stakers rewards
+    #[sv::msg(exec)]
+    pub fn withdraw_fees(
+        &self,
+        ctx: ExecCtx,
+        denom: Option<String>,
+        remote_recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let collector = config
+            .protocol_fee
+            .as_ref()
+            .map(|fee| fee.collector.as_str());
+        ensure!(
+            collector == Some(ctx.info.sender.as_str()),
+            MeshError::Unauthorized
+        );
+
+        let denom = denom.unwrap_or_else(|| config.rewards_denom.clone());
+        let amount = self
+            .fees_collected
+            .may_load(ctx.deps.storage, &denom)?
+            .unwrap_or_default();
+        ensure!(!amount.is_zero(), ContractError::NoFees(denom));
+        self.fees_collected.remove(ctx.deps.storage, &denom);
+
+        let fees = coin(amount.u128(), &denom);
+        let transferred = config
+            .rewards_transfer
+            .as_ref()
+            .is_some_and(|transfer| transfer.denom == denom);
+        if transferred {
+            let resp = Response::new()
+                .add_message(BankMsg::Send {
+                    to_address: ctx.info.sender.to_string(),
+                    amount: vec![fees],
+                })
+                .add_attribute("action", "withdraw_fees")
+                .add_attribute("recipient", ctx.info.sender.as_str())
+                .add_attribute("amount", amount.to_string())
+                .add_attribute("denom", denom);
+            return Ok(resp);
+        }
+
+        #[allow(unused_mut)]
+        let mut resp = Response::new()
+            .add_attribute("action", "withdraw_fees")
+            .add_attribute("recipient", &remote_recipient)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", &denom);
+
+        // Acked as a rewards withdrawal, and credited back to the collected fees on failure
+        let tx_id = self.next_tx_id(ctx.deps.storage)?;
+        self.pending_fee_withdrawals
+            .save(ctx.deps.storage, tx_id, &fees)?;
+
+        let packet = ProviderPacket::TransferRewards {
+            rewards: fees,
+            recipient: remote_recipient,
+            tx_id,
+        };
+        let channel_id = load_channel(ctx.deps.storage)?.endpoint.channel_id;
+        let send_msg = IbcMsg::SendPacket {
+            channel_id,
+            data: packet.encode(channel_features(ctx.deps.storage)?)?,
+            timeout: packet_timeout(&ctx.env, &packet),
+        };
+
+        // TODO: send in test code when we can handle it
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            resp = resp.add_message(send_msg);
+        }
+        #[cfg(any(test, feature = "mt"))]
+        {
+            crate::ibc::record_test_packet(ctx.deps.storage, &send_msg)?;
+        }
+
+        Ok(resp)
+    }
+
+    /// Distributes the rewards transferred by the converter with an ICS-20 transfer, executed
+    /// by ibc-hooks from the transfer memo. The transferred tokens are sent along, and their
+    /// sum must match the `rewards`
+    #[sv::msg(exec)]
+    pub fn receive_rewards_transfer(
+        &self,
+        ctx: ExecCtx,
+        rewards: Vec<RewardInfo>,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let transfer = config
+            .rewards_transfer
+            .ok_or(ContractError::RewardsTransferDisabled)?;
+        ensure_eq!(ctx.info.sender, transfer.sender, MeshError::Unauthorized);
+
+        let sent = must_pay(&ctx.info, &transfer.denom)?;
+        let sum: Uint128 = rewards.iter().map(|reward_info| reward_info.reward).sum();
+        ensure!(
+            sum == sent,
+            ContractError::RewardsTransferMismatch { sum, sent }
+        );
+
+        let events = self.distribute_rewards_batch(ctx.deps, &rewards, &transfer.denom)?;
+
+        let resp = Response::new()
+            .add_events(events)
+            .add_attribute("action", "receive_rewards_transfer")
+            .add_attribute("amount", sent.to_string());
+        Ok(resp)
+    }
+
+    /// Adds the tokens sent along to the instant unstakes buffer. Permissionless
+    #[sv::msg(exec)]
+    pub fn deposit_buffer(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let amount = must_pay(&ctx.info, &denom)?;
+        self.buffer.deposit(ctx.deps.storage, amount)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "deposit_buffer")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("amount", amount.to_string());
+        Ok(resp)
+    }
+
+    /// Withdraws free tokens from the instant unstakes buffer, to the sender.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    pub fn withdraw_buffer(
+        &self,
+        ctx: ExecCtx,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        self.buffer
+            .withdraw(ctx.deps.storage, ctx.env.block.time, amount)?;
+        let burn_msg = self.burn_slashed_buffer(ctx.deps.storage, &denom)?;
+
+        let resp = Response::new()
+            .add_message(BankMsg::Send {
+                to_address: ctx.info.sender.to_string(),
+                amount: coins(amount.u128(), denom),
+            })
+            .add_messages(burn_msg)
+            .add_attribute("action", "withdraw_buffer")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("amount", amount.to_string());
+        Ok(resp)
+    }
+
+    /// Burns the tokens slashed from the buffer, if any
+    fn burn_slashed_buffer(
+        &self,
+        storage: &mut dyn Storage,
+        denom: &str,
+    ) -> StdResult<Option<BankMsg>> {
+        let slashed = self.buffer.take_slashed(storage)?;
+        let msg = (!slashed.is_zero()).then(|| BankMsg::Burn {
+            amount: coins(slashed.u128(), denom),
+        });
+        Ok(msg)
+    }
+
+    /// In test code, this is called from `test_commit_unstake`.
+    /// In non-test code, this is called from `ibc_packet_ack`
+    /// Instant unstakes are released to the vault right away, and their penalty paid out: the
+    /// messages doing so are returned.
+    pub(crate) fn commit_unstake(
+        &self,
+        deps: DepsMut,
+        env: Env,
+        tx_id: u64,
+    ) -> Result<Vec<CosmosMsg>, ContractError> {
+        // Load tx
+        let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        self.tx_history.settle(deps.storage, tx_id, true)?;
+        self.idempotency_keys.settle(deps.storage, tx_id, true)?;
+
+        // Verify tx is of the right type
+        ensure!(
+            matches!(tx, Tx::InFlightRemoteUnstaking { .. }),
+            ContractError::WrongTypeTx(tx_id, tx)
+        );
+
+        let (tx_amount, tx_user, tx_validator) = match tx {
+            Tx::InFlightRemoteUnstaking {
+                amount,
+                user,
+                validator,
+                ..
+            } => (amount, user, validator),
+            _ => unreachable!(),
+        };
+
+        let config = self.config.load(deps.storage)?;
+
+        // Load stake
+        let mut stake = self
             .stakes
             .stake
             .load(deps.storage, (&tx_user, &tx_validator))?;
@@ -365,6 +2116,7 @@ impl ExternalStakingContract<'_> {
             .distribution
             .may_load(deps.storage, &tx_validator)?
             .unwrap_or_default();
+        let old_total = distribution.total_stake;
 
         // Commit sub amount, saturating if slashed
         let amount = min(tx_amount, stake.stake.high());
@@ -382,13 +2134,34 @@ impl ExternalStakingContract<'_> {
         } else {
             env.block.time.plus_seconds(config.unbonding_period)
         };
-        let unbond = PendingUnbond { amount, release_at };
-        stake.pending_unbonds.push(unbond);
+        let bonded_until = (!immediate_release).then_some(release_at);
+        let mut msgs: Vec<CosmosMsg> = vec![];
+        match self
+            .buffer
+            .commit(deps.storage, tx_id, &tx_validator, amount, bonded_until)?
+        {
+            Some(penalty) => {
+                if !amount.is_zero() {
+                    let source =
+                        self.vaults
+                            .source(deps.storage, &config, &tx_user, &tx_validator)?;
+                    let msg = source.vault().release_cross_stake(
+                        tx_user.to_string(),
+                        coin(amount.u128(), &source.denom),
+                        vec![],
+                    )?;
+                    msgs.push(msg.into());
+                }
+                msgs.extend(penalty_msg(&config, penalty).map(CosmosMsg::from));
+            }
+            None => {
+                let unbond = PendingUnbond { amount, release_at };
+                stake.pending_unbonds.push(unbond);
+            }
+        }
 
         // Distribution alignment
-        stake
-            .points_alignment
-            .stake_decreased(amount, distribution.points_per_stake);
+        stake.stake_decreased(amount, &distribution);
         distribution.total_stake -= amount;
 
         // Save stake
@@ -397,19 +2170,26 @@ impl ExternalStakingContract<'_> {
             .save(deps.storage, (&tx_user, &tx_validator), &stake)?;
 
         // Save distribution
-        self.distribution
-            .save(deps.storage, &tx_validator, &distribution)?;
+        self.save_distribution(deps.storage, &tx_validator, old_total, &distribution)?;
 
         // Remove tx
         self.pending_txs.remove(deps.storage, tx_id);
-        Ok(())
+        Ok(msgs)
     }
 
     /// In test code, this is called from `test_rollback_unstake`.
-    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
-    pub(crate) fn rollback_unstake(&self, deps: DepsMut, tx_id: u64) -> Result<(), ContractError> {
+    /// In non-test code, this is called from `ibc_packet_ack`
+    ///
+    /// The penalty of instant unstakes is refunded, with the returned message.
+    pub(crate) fn rollback_unstake(
+        &self,
+        deps: DepsMut,
+        tx_id: u64,
+    ) -> Result<Option<BankMsg>, ContractError> {
         // Load tx
         let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        self.tx_history.settle(deps.storage, tx_id, false)?;
+        self.idempotency_keys.settle(deps.storage, tx_id, false)?;
 
         // Verify tx is of the right type
         ensure!(
@@ -442,7 +2222,17 @@ impl ExternalStakingContract<'_> {
 
         // Remove tx
         self.pending_txs.remove(deps.storage, tx_id);
-        Ok(())
+
+        let denom = self.config.load(deps.storage)?.denom;
+        let refund = self
+            .buffer
+            .rollback(deps.storage, tx_id)?
+            .filter(|penalty| !penalty.is_zero())
+            .map(|penalty| BankMsg::Send {
+                to_address: tx_user.to_string(),
+                amount: coins(penalty.u128(), denom),
+            });
+        Ok(refund)
     }
 
     /// In non-test code, this is called from `ibc_packet_ack`
@@ -473,7 +2263,11 @@ impl ExternalStakingContract<'_> {
                 valoper,
                 valinfo.infraction_height,
             )?;
-            if active {
+            // Unless it was already slashed for it, through `submit_evidence`
+            let infraction = (valoper.as_str(), valinfo.infraction_height);
+            if active && !self.slashed_infractions.has(deps.storage, infraction) {
+                self.slashed_infractions
+                    .save(deps.storage, infraction, &())?;
                 let slash_ratio = match valinfo.slash_ratio.parse::<Decimal>() {
                     Ok(ratio) => ratio,
                     Err(_) => {
@@ -488,11 +2282,10 @@ impl ExternalStakingContract<'_> {
                     valoper,
                     slash_ratio,
                     valinfo.slash_amount.amount,
+                    valinfo.infraction_height,
                     valinfo.infraction_time,
                 )?;
-                if let Some(msg) = slash_msg {
-                    msgs.push(msg)
-                }
+                msgs.extend(slash_msg);
             }
             // Maintenance
             valopers.insert(valoper.clone());
@@ -501,14 +2294,24 @@ impl ExternalStakingContract<'_> {
         for valoper in tombstoned {
             self.val_set
                 .tombstone_validator(deps.storage, valoper, height, time)?;
+            self.leaving_validators.remove(deps.storage, valoper);
             // Maintenance
             valopers.insert(valoper.clone());
         }
         // Process additions. Already existing validators will be updated and set to active.
         // If the validator is tombstoned, this will be ignored.
-        for AddValidator { valoper, pub_key } in additions {
-            self.val_set
-                .add_validator(deps.storage, valoper, pub_key, height, time)?;
+        for addition in additions {
+            let valoper = &addition.valoper;
+            self.val_set.add_validator(
+                deps.storage,
+                valoper,
+                &addition.pub_key,
+                validator_metadata(addition),
+                height,
+                time,
+            )?;
+            // Back before its grace period is over
+            self.leaving_validators.remove(deps.storage, valoper);
             // Maintenance
             valopers.insert(valoper.clone());
         }
@@ -516,17 +2319,36 @@ impl ExternalStakingContract<'_> {
         for valoper in jailed {
             self.val_set
                 .jail_validator(deps.storage, valoper, height, time)?;
+            self.leaving_validators.remove(deps.storage, valoper);
             // Maintenance
             valopers.insert(valoper.clone());
         }
         // Process removals. Non-existent validators will be ignored.
         // Filter out jailed validators, as they are already removed from the active validator set
+        // Active validators are only removed after the grace period, if any, but are not
+        // receiving new stakes in the meantime.
         let rms: HashSet<_> = removals.iter().collect();
         let j: HashSet<_> = jailed.iter().collect();
         let rms: Vec<_> = rms.difference(&j).collect();
+        let mut leaving = vec![];
         for valoper in rms {
-            self.val_set
-                .remove_validator(deps.storage, valoper, height, time)?;
+            if cfg.removal_grace_period > 0
+                && self.val_set.is_active_validator(deps.storage, valoper)?
+            {
+                if !self.leaving_validators.has(deps.storage, valoper) {
+                    let leaving_validator = LeavingValidator {
+                        height,
+                        time,
+                        finalize_at: env.block.time.plus_seconds(cfg.removal_grace_period),
+                    };
+                    self.leaving_validators
+                        .save(deps.storage, valoper, &leaving_validator)?;
+                }
+                leaving.push((*valoper).clone());
+            } else {
+                self.val_set
+                    .remove_validator(deps.storage, valoper, height, time)?;
+            }
             // Maintenance
             valopers.insert((*valoper).clone());
         }
@@ -534,12 +2356,23 @@ impl ExternalStakingContract<'_> {
         // validator must go to the active or the unbonded state.
 
         // Process updates. Non-existent and tombstoned validators will be ignored.
-        for AddValidator { valoper, pub_key } in updated {
-            self.val_set
-                .update_validator(deps.storage, valoper, pub_key, height, time)?;
+        for update in updated {
+            let valoper = &update.valoper;
+            self.val_set.update_validator(
+                deps.storage,
+                valoper,
+                &update.pub_key,
+                validator_metadata(update),
+                height,
+                time,
+            )?;
             // Maintenance
             valopers.insert(valoper.clone());
         }
+        // Finalize the removals whose grace period is over
+        let removed = self.remove_leaving_validators(deps.storage, &env.block)?;
+        valopers.extend(removed.iter().cloned());
+
         // Maintenance. Drain events that are older than unbonding period from now
         // Assumes time keeping is the same in both chains
         let max_time = env
@@ -564,6 +2397,12 @@ impl ExternalStakingContract<'_> {
         if !removals.is_empty() {
             event = event.add_attribute("removals", removals.join(","));
         }
+        if !leaving.is_empty() {
+            event = event.add_attribute("leaving", leaving.join(","));
+        }
+        if !removed.is_empty() {
+            event = event.add_attribute("removed", removed.join(","));
+        }
         if !updated.is_empty() {
             event = event.add_attribute(
                 "updated",
@@ -580,71 +2419,319 @@ impl ExternalStakingContract<'_> {
         if !unjailed.is_empty() {
             event = event.add_attribute("unjailed", unjailed.join(","));
         }
-        if !tombstoned.is_empty() {
-            event = event.add_attribute("tombstoned", tombstoned.join(","));
+        if !tombstoned.is_empty() {
+            event = event.add_attribute("tombstoned", tombstoned.join(","));
+        }
+        if !slashed.is_empty() {
+            event = event.add_attribute(
+                "slashed",
+                slashed
+                    .iter()
+                    .map(|v| v.address.clone())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            );
+        }
+        Ok((event, msgs))
+    }
+
+    /// Records the self-stakes reported for the given validators. Delegators of a validator
+    /// falling below the required minimum self-stake are notified with a
+    /// `self_stake_below_min` event each, as new stakes to it are blocked from now on.
+    ///
+    /// In test code, this is called from `test_set_active_validator`.
+    /// In non-test code, this is called from `ibc_packet_receive`
+    pub(crate) fn update_self_stakes<'v>(
+        &self,
+        storage: &mut dyn Storage,
+        validators: impl IntoIterator<Item = &'v AddValidator>,
+    ) -> Result<Vec<Event>, ContractError> {
+        let cfg = self.config.load(storage)?;
+        let mut events = vec![];
+        for AddValidator {
+            valoper,
+            self_stake,
+            ..
+        } in validators
+        {
+            let Some(self_stake) = *self_stake else {
+                continue;
+            };
+            let previous = self.self_stakes.may_load(storage, valoper)?;
+            self.self_stakes.save(storage, valoper, &self_stake)?;
+
+            let Some(min_self_stake) = cfg.min_self_stake else {
+                continue;
+            };
+            // Only notify when crossing the threshold
+            let was_above = previous.map_or(true, |previous| previous >= min_self_stake);
+            if self_stake >= min_self_stake || !was_above {
+                continue;
+            }
+            for (owner, stake) in self.stakes.stakes_by_validator(storage, valoper)? {
+                if stake.stake.high().is_zero() {
+                    continue;
+                }
+                events.push(
+                    Event::new("self_stake_below_min")
+                        .add_attribute("validator", valoper)
+                        .add_attribute("delegator", owner)
+                        .add_attribute("self_stake", self_stake.to_string())
+                        .add_attribute("min_self_stake", min_self_stake.to_string()),
+                );
+            }
+        }
+        Ok(events)
+    }
+
+    /// Records the uptimes reported for the given validators. The stakers of a validator whose
+    /// uptime drops below `min_uptime` get a `validator_health_warning` event each, so they can
+    /// move their stake before it's slashed for downtime.
+    ///
+    /// Called from `ibc_packet_receive`
+    pub(crate) fn update_validator_health(
+        &self,
+        storage: &mut dyn Storage,
+        height: u64,
+        time: u64,
+        uptimes: &[ValidatorUptime],
+    ) -> Result<Vec<Event>, ContractError> {
+        let cfg = self.config.load(storage)?;
+        let mut events = vec![];
+        for report in uptimes {
+            let uptime = report.uptime();
+            let previous = self.validator_health.may_load(storage, &report.address)?;
+            let health = ValidatorHealth {
+                missed_blocks: report.missed_blocks,
+                window: report.window,
+                uptime,
+                height,
+                time,
+            };
+            self.validator_health
+                .save(storage, &report.address, &health)?;
+
+            let Some(min_uptime) = cfg.min_uptime else {
+                continue;
+            };
+            // Only notify when crossing the threshold
+            let was_above = previous.map_or(true, |previous| previous.uptime >= min_uptime);
+            if uptime >= min_uptime || !was_above {
+                continue;
+            }
+            for (owner, stake) in self.stakes.stakes_by_validator(storage, &report.address)? {
+                if stake.stake.high().is_zero() {
+                    continue;
+                }
+                events.push(
+                    Event::new("validator_health_warning")
+                        .add_attribute("validator", &report.address)
+                        .add_attribute("delegator", owner)
+                        .add_attribute("uptime", uptime.to_string())
+                        .add_attribute("min_uptime", min_uptime.to_string()),
+                );
+            }
+        }
+        Ok(events)
+    }
+
+    /// Records the max cross-stakes reported for the given validators. A validator reported
+    /// without max is uncapped.
+    ///
+    /// In test code, this is called from `test_set_active_validator`.
+    /// In non-test code, this is called from `ibc_packet_receive`
+    pub(crate) fn update_max_external_stakes<'v>(
+        &self,
+        storage: &mut dyn Storage,
+        validators: impl IntoIterator<Item = &'v AddValidator>,
+    ) -> Result<(), ContractError> {
+        for AddValidator {
+            valoper,
+            max_external_stake,
+            ..
+        } in validators
+        {
+            match max_external_stake {
+                Some(max_stake) => self.max_external_stakes.save(storage, valoper, max_stake)?,
+                None => self.max_external_stakes.remove(storage, valoper),
+            }
         }
-        if !slashed.is_empty() {
-            event = event.add_attribute(
-                "slashed",
-                slashed
-                    .iter()
-                    .map(|v| v.address.clone())
-                    .collect::<Vec<String>>()
-                    .join(","),
-            );
+        Ok(())
+    }
+
+    /// Splits `amount` of `owner` across the validators selected by `strategy`.
+    /// The stake of a validator from this provider stands for its power, the consumer not
+    /// reporting it. Validators excluded by the owner preferences are skipped by the automatic
+    /// strategies. Leftovers of the split go to the first validator.
+    fn select_validators(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        strategy: ValidatorSelection,
+        amount: Uint128,
+    ) -> Result<Vec<BatchStake>, ContractError> {
+        let preferences = self
+            .validator_preferences
+            .may_load(storage, owner)?
+            .unwrap_or_default();
+        let allowed_powers = || -> StdResult<Vec<_>> {
+            Ok(self
+                .validator_powers(storage)?
+                .into_iter()
+                .filter(|(validator, _)| preferences.allows(validator))
+                .collect())
+        };
+        let weights: Vec<(String, Uint128)> = match strategy {
+            ValidatorSelection::TopN { n } => {
+                let mut powers = allowed_powers()?;
+                // Most staked first, in address order for the same stake
+                powers.sort_by(|(val1, power1), (val2, power2)| {
+                    power2.cmp(power1).then_with(|| val1.cmp(val2))
+                });
+                powers
+                    .into_iter()
+                    .take(n as usize)
+                    .map(|(validator, _)| (validator, Uint128::one()))
+                    .collect()
+            }
+            ValidatorSelection::ProportionalToPower {} => allowed_powers()?,
+            ValidatorSelection::Custom(weights) => weights
+                .into_iter()
+                .map(|(validator, weight)| (validator, Uint128::from(weight)))
+                .collect(),
+        };
+
+        let total: Uint128 = weights.iter().map(|(_, weight)| *weight).sum();
+        if total.is_zero() {
+            return Err(ContractError::InvalidValidatorSelection(
+                "no validator to stake on".to_owned(),
+            ));
         }
-        Ok((event, msgs))
+        let mut stakes: Vec<_> = weights
+            .into_iter()
+            .filter(|(_, weight)| !weight.is_zero())
+            .map(|(validator, weight)| BatchStake {
+                validator,
+                amount: amount.multiply_ratio(weight, total),
+            })
+            .collect();
+        let allocated: Uint128 = stakes.iter().map(|stake| stake.amount).sum();
+        stakes[0].amount += amount - allocated;
+        stakes.retain(|stake| !stake.amount.is_zero());
+        Ok(stakes)
+    }
+
+    /// Active validators able to receive new stakes, with their stake from this provider
+    fn validator_powers(&self, storage: &dyn Storage) -> StdResult<Vec<(String, Uint128)>> {
+        self.val_set
+            .list_active_validators(storage, None, usize::MAX)?
+            .into_iter()
+            .filter(|validator| !self.leaving_validators.has(storage, validator))
+            .map(|validator| {
+                let power = self
+                    .distribution
+                    .may_load(storage, &validator)?
+                    .map(|distribution| distribution.total_stake)
+                    .unwrap_or_default();
+                Ok((validator, power))
+            })
+            .collect()
+    }
+
+    /// Total stake on the validator, including the pending stake additions
+    fn validator_stake(&self, storage: &dyn Storage, validator: &str) -> StdResult<Uint128> {
+        self.stakes
+            .stake
+            .idx
+            .rev
+            .sub_prefix(validator.to_string())
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, stake)| stake.stake.high()))
+            .sum()
     }
 
-    /// Withdraws all of their released tokens to the calling user.
+    /// Withdraws all of their released tokens to the calling user, across all the validators,
+    /// with a single release per vault the stakes come from.
     ///
     /// Tokens to be claimed have to be unbond before by calling the `unbond` message, and
     /// their unbonding period must have passed.
+    ///
+    /// At most `max_withdraw_batch` validator stakes are released at once, so the call fits in
+    /// the gas limits. The `remaining` attribute is set if more released tokens are left, in
+    /// which case the call should be repeated.
     #[sv::msg(exec)]
     pub fn withdraw_unbonded(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
         let config = self.config.load(ctx.deps.storage)?;
+        let batch = config
+            .max_withdraw_batch
+            .unwrap_or(DEFAULT_WITHDRAW_BATCH)
+            .max(1) as usize;
 
+        // Only the stakes with released tokens, one more than the batch to know if any is left
+        let now = ctx.env.block.time;
         let stakes: Vec<_> = self
             .stakes
             .stake
             .prefix(&ctx.info.sender)
             .range(ctx.deps.storage, None, None, Order::Ascending)
+            .filter(|item| match item {
+                Ok((_, stake)) => stake
+                    .pending_unbonds
+                    .first()
+                    .is_some_and(|unbond| unbond.release_at <= now),
+                Err(_) => true,
+            })
+            .take(batch + 1)
             .collect::<Result<_, _>>()?;
+        let remaining = stakes.len() > batch;
 
-        let released: Uint128 = stakes
-            .into_iter()
-            .map(|(validator, mut stake)| -> Result<_, ContractError> {
-                let released = stake.release_pending(&ctx.env.block);
-
-                if !released.is_zero() {
-                    self.stakes.stake.save(
-                        ctx.deps.storage,
-                        (&ctx.info.sender, &validator),
-                        &stake,
-                    )?
-                }
-
-                Ok(released)
-            })
-            .try_fold(Uint128::zero(), |acc, released| {
-                released.map(|released| released + acc)
-            })?;
+        // Released tokens, by vault they go back to
+        let mut releases: BTreeMap<StakeSource, Uint128> = BTreeMap::new();
+        for (validator, mut stake) in stakes.into_iter().take(batch) {
+            let released = stake.release_pending(&ctx.env.block);
+            if released.is_zero() {
+                continue;
+            }
+            self.stakes
+                .stake
+                .save(ctx.deps.storage, (&ctx.info.sender, &validator), &stake)?;
+            let source =
+                self.vaults
+                    .source(ctx.deps.storage, &config, &ctx.info.sender, &validator)?;
+            *releases.entry(source).or_default() += released;
+        }
+        let total: Uint128 = releases.values().sum();
 
         let mut resp = Response::new()
             .add_attribute("action", "withdraw_unbonded")
             .add_attribute("owner", ctx.info.sender.to_string())
-            .add_attribute("amount", released.to_string());
+            .add_attribute("amount", total.to_string());
+        if remaining {
+            resp = resp.add_attribute("remaining", "true");
+        }
 
-        if !released.is_zero() {
-            let release_msg = config.vault.release_cross_stake(
-                ctx.info.sender.into_string(),
-                coin(released.u128(), &config.denom),
+        for (source, released) in releases {
+            let released = coin(released.u128(), &source.denom);
+            let stake_tx_id = self.tx_history.record(
+                ctx.deps.storage,
+                &ctx.env.block,
+                &ctx.info.sender,
+                StakeTxKind::WithdrawUnbonded,
+                None,
+                released.clone(),
+                None,
+            )?;
+            let release_msg = source.vault().release_cross_stake(
+                ctx.info.sender.to_string(),
+                released,
                 vec![],
             )?;
 
-            resp = resp.add_message(release_msg);
+            resp = resp
+                .add_message(release_msg)
+                .add_attribute("stake_tx_id", stake_tx_id.to_string());
         }
 
         Ok(resp)
@@ -662,40 +2749,66 @@ impl ExternalStakingContract<'_> {
     ) -> Result<Event, ContractError> {
         // check we have the proper denom
         let config = self.config.load(deps.storage)?;
-        ensure_eq!(
-            rewards.denom,
-            config.rewards_denom,
+        ensure!(
+            config.is_rewards_denom(&rewards.denom),
             PaymentError::MissingDenom(rewards.denom)
         );
 
-        self.distribute_rewards_unchecked(&mut deps, validator, rewards.amount)
+        self.distribute_rewards_unchecked(
+            &mut deps,
+            &config,
+            validator,
+            &rewards.denom,
+            rewards.amount,
+        )
     }
 
     fn distribute_rewards_unchecked(
         &self,
         deps: &mut DepsMut,
+        config: &Config,
         validator: &str,
+        denom: &str,
         amount: Uint128,
     ) -> Result<Event, ContractError> {
+        // The protocol fee is taken before crediting the stakers
+        let fee = config
+            .protocol_fee
+            .as_ref()
+            .map_or(Uint128::zero(), |fee| fee.fee(amount));
+        if !fee.is_zero() {
+            self.fees_collected
+                .update(deps.storage, denom, |collected| -> StdResult<_> {
+                    Ok(collected.unwrap_or_default() + fee)
+                })?;
+        }
+        let amount = amount - fee;
+
         let mut distribution = self
             .distribution
             .may_load(deps.storage, validator)?
             .unwrap_or_default();
 
-        let total_stake = Uint256::from(distribution.total_stake);
-        let points_distributed =
-            Uint256::from(amount) * DISTRIBUTION_POINTS_SCALE + distribution.points_leftover;
-        let points_per_stake = points_distributed / total_stake;
-
-        distribution.points_leftover = points_distributed - points_per_stake * total_stake;
-        distribution.points_per_stake += points_per_stake;
+        let total_stake = distribution.total_stake;
+        let (points_per_stake, points_leftover) = if denom == config.rewards_denom {
+            (
+                &mut distribution.points_per_stake,
+                &mut distribution.points_leftover,
+            )
+        } else {
+            let points = distribution.extra_points_mut(denom);
+            (&mut points.points_per_stake, &mut points.points_leftover)
+        };
+        distribution::distribute(points_per_stake, points_leftover, total_stake, amount)?;
 
         self.distribution
             .save(deps.storage, validator, &distribution)?;
 
         let event = Event::new("distribute_rewards")
             .add_attribute("validator", validator)
-            .add_attribute("amount", amount.to_string());
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("denom", denom)
+            .add_attribute("fee", fee.to_string());
 
         Ok(event)
     }
@@ -708,10 +2821,9 @@ impl ExternalStakingContract<'_> {
     ) -> Result<Vec<Event>, ContractError> {
         // check we have the proper denom
         let config = self.config.load(deps.storage)?;
-        ensure_eq!(
-            denom,
-            config.rewards_denom,
-            ContractError::InvalidDenom(config.rewards_denom)
+        ensure!(
+            config.is_rewards_denom(denom),
+            MeshError::InvalidDenom(config.rewards_denom)
         );
 
         rewards
@@ -719,7 +2831,9 @@ impl ExternalStakingContract<'_> {
             .map(|reward_info| {
                 self.distribute_rewards_unchecked(
                     &mut deps,
+                    &config,
                     &reward_info.validator,
+                    denom,
                     reward_info.reward,
                 )
             })
@@ -737,54 +2851,226 @@ impl ExternalStakingContract<'_> {
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        let stake = self
+        self.withdraw_rewards_of(
+            ctx.deps,
+            &ctx.env,
+            ctx.info.sender,
+            validator,
+            None,
+            remote_recipient,
+        )
+    }
+
+    /// Withdraw rewards in the given rewards denom (main or additional) from staking via given
+    /// validator
+    #[sv::msg(exec)]
+    pub fn withdraw_denom_rewards(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        denom: String,
+        /// Address on the consumer side to receive the rewards
+        remote_recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.withdraw_rewards_of(
+            ctx.deps,
+            &ctx.env,
+            ctx.info.sender,
+            validator,
+            Some(denom),
+            remote_recipient,
+        )
+    }
+
+    /// `withdraw_rewards` of `owner`, called either by the owner, or by the vault on behalf of
+    /// an operator. `denom` defaults to the main rewards denom
+    pub(crate) fn withdraw_rewards_of(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        owner: Addr,
+        validator: String,
+        denom: Option<String>,
+        remote_recipient: String,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        let denom = denom.unwrap_or_else(|| config.rewards_denom.clone());
+        ensure!(
+            config.is_rewards_denom(&denom),
+            ContractError::NotRewardsDenom(denom)
+        );
+
+        let mut stake = self
             .stakes
             .stake
-            .may_load(ctx.deps.storage, (&ctx.info.sender, &validator))?
+            .may_load(deps.storage, (&owner, &validator))?
             .unwrap_or_default();
 
         let distribution = self
             .distribution
-            .may_load(ctx.deps.storage, &validator)?
+            .may_load(deps.storage, &validator)?
             .unwrap_or_default();
 
-        let amount = Self::calculate_reward(&stake, &distribution)?;
-
-        if amount.is_zero() {
+        let amount = Self::calculate_denom_reward(&config, &stake, &distribution, &denom)?;
+
+        // Nothing accrues to fully unstaked stakes anymore, so their sub-unit remainder goes to
+        // the owner dust
+        let mut swept = false;
+        if stake.stake.high().is_zero() {
+            let staked = stake.stake.low();
+            let main_denom = denom == config.rewards_denom;
+            let points_per_stake = if main_denom {
+                Some(distribution.points_per_stake)
+            } else {
+                distribution
+                    .extra_points(&denom)
+                    .map(|points| points.points_per_stake)
+            };
+            if let Some(points_per_stake) = points_per_stake {
+                let alignment = if main_denom {
+                    &mut stake.points_alignment
+                } else {
+                    &mut stake.extra_rewards_mut(&denom).points_alignment
+                };
+                swept = !self
+                    .reward_dust
+                    .sweep(
+                        deps.storage,
+                        &owner,
+                        &denom,
+                        points_per_stake,
+                        staked,
+                        alignment,
+                    )?
+                    .is_zero();
+            }
+        }
+        if swept {
+            self.stakes
+                .stake
+                .save(deps.storage, (&owner, &validator), &stake)?;
+        }
+        let dust = self
+            .reward_dust
+            .payout(deps.storage, &owner, &denom, config.dust_threshold)?;
+
+        if amount.is_zero() && dust.is_zero() {
+            // Nothing to pay out yet, but the swept remainder is kept
+            if swept {
+                return Ok(Response::new()
+                    .add_attribute("action", "sweep_reward_dust")
+                    .add_attribute("owner", owner.to_string())
+                    .add_attribute("validator", &validator));
+            }
             return Err(ContractError::NoRewards);
         }
+        let total = amount + dust;
+
+        // Transferred rewards are already on this chain, and paid to the owner right away
+        let transferred = config
+            .rewards_transfer
+            .as_ref()
+            .is_some_and(|transfer| transfer.denom == denom);
+        if transferred {
+            if !amount.is_zero() {
+                stake.extra_rewards_mut(&denom).withdrawn_funds += amount;
+                self.stakes
+                    .stake
+                    .save(deps.storage, (&owner, &validator), &stake)?;
+            }
+
+            let rewards = coin(total.u128(), &denom);
+            let stake_tx_id = self.tx_history.record(
+                deps.storage,
+                &env.block,
+                &owner,
+                StakeTxKind::WithdrawRewards,
+                Some(&validator),
+                rewards.clone(),
+                None,
+            )?;
+            let mut resp = Response::new()
+                .add_event(Event::from(
+                    RewardsEvent::new(rewards.clone())
+                        .delegator(&owner)
+                        .validator(&validator)
+                        .lienholder(&env.contract.address),
+                ))
+                .add_message(BankMsg::Send {
+                    to_address: owner.to_string(),
+                    amount: vec![rewards],
+                })
+                .add_attribute("action", "withdraw_rewards")
+                .add_attribute("owner", owner.to_string())
+                .add_attribute("validator", &validator)
+                .add_attribute("recipient", owner.to_string())
+                .add_attribute("amount", total.to_string())
+                .add_attribute("stake_tx_id", stake_tx_id.to_string());
+            if !dust.is_zero() {
+                resp = resp.add_attribute("dust", dust.to_string());
+            }
+            return Ok(resp);
+        }
 
-        #[allow(unused_mut)]
         let mut resp = Response::new()
+            .add_event(Event::from(
+                RewardsEvent::new(coin(total.u128(), &denom))
+                    .delegator(&owner)
+                    .validator(&validator)
+                    .lienholder(&env.contract.address),
+            ))
             .add_attribute("action", "withdraw_rewards")
-            .add_attribute("owner", ctx.info.sender.to_string())
+            .add_attribute("owner", owner.to_string())
             .add_attribute("validator", &validator)
             .add_attribute("recipient", &remote_recipient)
-            .add_attribute("amount", amount.to_string());
+            .add_attribute("amount", total.to_string());
+        if !dust.is_zero() {
+            resp = resp.add_attribute("dust", dust.to_string());
+        }
 
         // prepare the pending tx
-        let tx_id = self.next_tx_id(ctx.deps.storage)?;
+        let tx_id = self.next_tx_id(deps.storage)?;
+        let stake_tx_id = self.tx_history.record(
+            deps.storage,
+            &env.block,
+            &owner,
+            StakeTxKind::WithdrawRewards,
+            Some(&validator),
+            coin(total.u128(), &denom),
+            Some(tx_id),
+        )?;
+        resp = resp
+            .add_attribute("tx_id", tx_id.to_string())
+            .add_attribute("stake_tx_id", stake_tx_id.to_string());
         let new_tx = Tx::InFlightTransferFunds {
             id: tx_id,
-            amount,
-            staker: ctx.info.sender,
+            amount: total,
+            staker: owner,
             validator,
         };
-        self.pending_txs.save(ctx.deps.storage, tx_id, &new_tx)?;
+        self.pending_txs.save(deps.storage, tx_id, &new_tx)?;
+        if denom != config.rewards_denom {
+            self.pending_withdrawal_denoms
+                .save(deps.storage, tx_id, &denom)?;
+        }
+        if !dust.is_zero() {
+            self.reward_dust.pending.save(deps.storage, tx_id, &dust)?;
+        }
 
         // Crate the IBC packet
-        let config = self.config.load(ctx.deps.storage)?;
-        let rewards = coin(amount.u128(), config.rewards_denom);
+        let rewards = coin(total.u128(), denom);
         let packet = ProviderPacket::TransferRewards {
             rewards,
             recipient: remote_recipient,
             tx_id,
         };
-        let channel_id = IBC_CHANNEL.load(ctx.deps.storage)?.endpoint.channel_id;
+        let channel_id = load_channel(deps.storage)?.endpoint.channel_id;
         let send_msg = IbcMsg::SendPacket {
             channel_id,
-            data: to_json_binary(&packet)?,
-            timeout: packet_timeout(&ctx.env),
+            data: packet.encode(channel_features(deps.storage)?)?,
+            timeout: packet_timeout(env, &packet),
         };
 
         // TODO: send in test code when we can handle it
@@ -794,25 +3080,260 @@ impl ExternalStakingContract<'_> {
         }
         #[cfg(any(test, feature = "mt"))]
         {
-            let _ = send_msg;
+            crate::ibc::record_test_packet(deps.storage, &send_msg)?;
+        }
+
+        Ok(resp)
+    }
+
+    /// Opts in or out of permissionless compounding of the sender rewards via
+    /// `crank_compound_rewards`
+    #[sv::msg(exec)]
+    pub fn set_auto_compound(
+        &self,
+        ctx: ExecCtx,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        if enabled {
+            self.auto_compound
+                .save(ctx.deps.storage, &ctx.info.sender, &())?;
+        } else {
+            self.auto_compound
+                .remove(ctx.deps.storage, &ctx.info.sender);
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "set_auto_compound")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("enabled", enabled.to_string());
+
+        Ok(resp)
+    }
+
+    /// Sets (or unsets with `None`) the validator the sender stakes to when `stake_remote` is called
+    /// on the vault with an empty msg
+    #[sv::msg(exec)]
+    pub fn set_default_validator(
+        &self,
+        ctx: ExecCtx,
+        validator: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "set_default_validator")
+            .add_attribute("owner", ctx.info.sender.as_str());
+        match validator {
+            Some(validator) => {
+                ensure!(
+                    self.val_set
+                        .is_active_validator(ctx.deps.storage, &validator)?,
+                    ContractError::ValidatorNotActive(validator)
+                );
+                self.ensure_allowed(ctx.deps.storage, &ctx.info.sender, &validator)?;
+                self.default_validators
+                    .save(ctx.deps.storage, &ctx.info.sender, &validator)?;
+                resp = resp.add_attribute("validator", validator);
+            }
+            None => {
+                self.default_validators
+                    .remove(ctx.deps.storage, &ctx.info.sender);
+            }
+        }
+
+        Ok(resp)
+    }
+
+    /// Sets the validators the sender never stakes to, and optionally the only ones they stake
+    /// to. Enforced on every new stake, whether explicit, automatic or compounded. Existing stakes
+    /// are left as they are
+    #[sv::msg(exec)]
+    pub fn set_validator_preferences(
+        &self,
+        ctx: ExecCtx,
+        denylist: Vec<String>,
+        allowlist: Option<Vec<String>>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let too_long = denylist.len() > MAX_PREFERRED_VALIDATORS
+            || allowlist
+                .as_ref()
+                .is_some_and(|allowed| allowed.len() > MAX_PREFERRED_VALIDATORS);
+        ensure!(
+            !too_long,
+            ContractError::TooManyPreferredValidators(MAX_PREFERRED_VALIDATORS)
+        );
+
+        let preferences = ValidatorPreferences {
+            denylist,
+            allowlist,
+        };
+        if preferences == ValidatorPreferences::default() {
+            self.validator_preferences
+                .remove(ctx.deps.storage, &ctx.info.sender);
+        } else {
+            self.validator_preferences
+                .save(ctx.deps.storage, &ctx.info.sender, &preferences)?;
+        }
+
+        let resp = Response::new()
+            .add_attribute("action", "set_validator_preferences")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("denied", preferences.denylist.len().to_string())
+            .add_attribute(
+                "allowed",
+                preferences
+                    .allowlist
+                    .map_or_else(|| "all".to_owned(), |allowed| allowed.len().to_string()),
+            );
+
+        Ok(resp)
+    }
+
+    /// Re-stakes the sender rewards from staking via given validator, on the same validator
+    #[sv::msg(exec)]
+    pub fn compound_rewards(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+    ) -> Result<Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        self.compound_rewards_for(ctx, owner, validator, Decimal::zero())
+    }
+
+    /// Re-stakes rewards of `owner`, who has to be opted in for auto-compounding.
+    /// Callable by anyone, `COMPOUND_CRANK_INCENTIVE` of the rewards is paid to the caller.
+    #[sv::msg(exec)]
+    pub fn crank_compound_rewards(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        validator: String,
+    ) -> Result<Response, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        ensure!(
+            self.auto_compound.has(ctx.deps.storage, &owner),
+            ContractError::AutoCompoundDisabled(owner.into_string())
+        );
+
+        self.compound_rewards_for(ctx, owner, validator, COMPOUND_CRANK_INCENTIVE)
+    }
+
+    /// Compounding is only possible if the rewards, transferred back from the consumer, are in the
+    /// denom of the vault the stake comes from, and held by this contract.
+    /// The rewards are marked as withdrawn, and sent to that vault to be bonded and virtually staked
+    /// as a regular stake, so `Distribution` is updated once the stake is committed. If the stake
+    /// is rolled back, the rewards stay in the vault as free collateral.
+    fn compound_rewards_for(
+        &self,
+        ctx: ExecCtx,
+        owner: Addr,
+        validator: String,
+        incentive: Decimal,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let source = self
+            .vaults
+            .source(ctx.deps.storage, &config, &owner, &validator)?;
+        ensure_eq!(
+            config.rewards_denom,
+            source.denom,
+            MeshError::InvalidDenom(source.denom)
+        );
+        self.ensure_allowed(ctx.deps.storage, &owner, &validator)?;
+
+        let mut stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&owner, &validator))?
+            .unwrap_or_default();
+
+        let distribution = self
+            .distribution
+            .may_load(ctx.deps.storage, &validator)?
+            .unwrap_or_default();
+
+        let amount = Self::calculate_reward(&stake, &distribution)?;
+        let fee = amount * incentive;
+        let restaked = amount - fee;
+        if restaked.is_zero() {
+            return Err(ContractError::NoRewards);
         }
 
+        stake.withdrawn_funds += amount;
+        self.stakes
+            .stake
+            .save(ctx.deps.storage, (&owner, &validator), &stake)?;
+
+        let msg = ReceiveVirtualStake::new(&validator).encode()?;
+        let compound_msg = source.vault().compound_stake(
+            owner.to_string(),
+            msg,
+            coins(restaked.u128(), &source.denom),
+        )?;
+
+        let mut resp = Response::new().add_message(compound_msg);
+        if !fee.is_zero() {
+            resp = resp.add_message(BankMsg::Send {
+                to_address: ctx.info.sender.to_string(),
+                amount: coins(fee.u128(), &config.rewards_denom),
+            });
+        }
+
+        let resp = resp
+            .add_attribute("action", "compound_rewards")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", owner)
+            .add_attribute("validator", validator)
+            .add_attribute("amount", restaked.to_string())
+            .add_attribute("incentive", fee.to_string());
+
         Ok(resp)
     }
 
     /// In test code, this is called from `test_rollback_withdraw_rewards`.
-    /// In non-test code, this is called from `ibc_packet_ack` or `ibc_packet_timeout`
+    /// In non-test code, this is called from `ibc_packet_ack`
     pub(crate) fn rollback_withdraw_rewards(
         &self,
         deps: DepsMut,
         tx_id: u64,
     ) -> Result<(), ContractError> {
+        // Failed fees withdrawals are credited back to the collected fees
+        if let Some(fees) = self.pending_fee_withdrawals.may_load(deps.storage, tx_id)? {
+            self.pending_fee_withdrawals.remove(deps.storage, tx_id);
+            self.fees_collected
+                .update(deps.storage, &fees.denom, |collected| -> StdResult<_> {
+                    Ok(collected.unwrap_or_default() + fees.amount)
+                })?;
+            return Ok(());
+        }
+
         let tx = self.pending_txs.load(deps.storage, tx_id)?;
+        self.tx_history.settle(deps.storage, tx_id, false)?;
 
         // Verify tx is of the right type and remove it from the map
         match tx {
-            Tx::InFlightTransferFunds { .. } => {
+            Tx::InFlightTransferFunds { ref staker, .. } => {
+                // The dust paid out along is credited back
+                if let Some(dust) = self.reward_dust.pending.may_load(deps.storage, tx_id)? {
+                    self.reward_dust.pending.remove(deps.storage, tx_id);
+                    let denom = match self
+                        .pending_withdrawal_denoms
+                        .may_load(deps.storage, tx_id)?
+                    {
+                        Some(denom) => denom,
+                        None => self.config.load(deps.storage)?.rewards_denom,
+                    };
+                    self.reward_dust
+                        .refund(deps.storage, staker, &denom, dust)?;
+                }
                 self.pending_txs.remove(deps.storage, tx_id);
+                self.pending_withdrawal_denoms.remove(deps.storage, tx_id);
             }
             _ => {
                 return Err(ContractError::WrongTypeTx(tx_id, tx));
@@ -829,9 +3350,15 @@ impl ExternalStakingContract<'_> {
         deps: DepsMut,
         tx_id: u64,
     ) -> Result<(), ContractError> {
+        if self.pending_fee_withdrawals.has(deps.storage, tx_id) {
+            self.pending_fee_withdrawals.remove(deps.storage, tx_id);
+            return Ok(());
+        }
+
         // Load tx
         let tx = self.pending_txs.load(deps.storage, tx_id)?;
         self.pending_txs.remove(deps.storage, tx_id);
+        self.tx_history.settle(deps.storage, tx_id, true)?;
 
         // Verify tx is of the right type and get data
         let (amount, staker, validator) = match tx {
@@ -846,12 +3373,32 @@ impl ExternalStakingContract<'_> {
             }
         };
 
+        let denom = self
+            .pending_withdrawal_denoms
+            .may_load(deps.storage, tx_id)?;
+        self.pending_withdrawal_denoms.remove(deps.storage, tx_id);
+
+        // The dust paid out along was already taken from the owner dust
+        let dust = self
+            .reward_dust
+            .pending
+            .may_load(deps.storage, tx_id)?
+            .unwrap_or_default();
+        self.reward_dust.pending.remove(deps.storage, tx_id);
+        let amount = amount - dust;
+        if amount.is_zero() {
+            return Ok(());
+        }
+
         // Update withdrawn_funds to hold this transfer
         let mut stake = self
             .stakes
             .stake
             .load(deps.storage, (&staker, &validator))?;
-        stake.withdrawn_funds += amount;
+        match denom {
+            Some(denom) => stake.extra_rewards_mut(&denom).withdrawn_funds += amount,
+            None => stake.withdrawn_funds += amount,
+        }
 
         self.stakes
             .stake
@@ -862,6 +3409,10 @@ impl ExternalStakingContract<'_> {
 
     /// Slashes a validator.
     ///
+    /// The slashing is applied to at most `SLASH_BATCH` stakes at once. If more are left, it is
+    /// kept as pending, to be resumed by `continue_slashing`. Stakes on the validator can't be
+    /// added or removed in the meantime.
+    ///
     /// In test code, this is called from `test_handle_slashing`.
     /// In non-test code, this is being called from `ibc_packet_receive` (in the `ConsumerPacket::RemoveValidators`
     /// handler)
@@ -874,34 +3425,84 @@ impl ExternalStakingContract<'_> {
         validator: &str,
         slash_ratio: Decimal,
         slash_amount: Uint128,
+        infraction_height: u64,
         infraction_time: u64,
-    ) -> Result<Option<WasmMsg>, ContractError> {
-        // Get the list of users staking via this validator
+    ) -> Result<Vec<WasmMsg>, ContractError> {
+        // Instant unbonds are slashed from the buffer
+        self.buffer.slash(
+            storage,
+            env.block.time,
+            validator,
+            slash_ratio,
+            config.unbonding_period,
+            infraction_time,
+        )?;
+
+        // Compute effective slash ratio, over the total stake on this validator
         // FIXME: It should be over the *historical* (at infraction height) stake. Not over the *current* stake
-        let users = self
+        let total_amount = self.validator_stake(storage, validator)?;
+        if total_amount.is_zero() {
+            return Ok(vec![]);
+        }
+
+        let pending = PendingSlash {
+            slash_ratio,
+            effective_slash_ratio: Decimal::from_ratio(slash_amount, total_amount),
+            infraction_time,
+            last_user: None,
+        };
+        self.pending_slashes
+            .save(storage, (validator, infraction_height), &pending)?;
+
+        self.process_slashing(env, storage, config, validator, infraction_height)
+    }
+
+    /// Applies a pending slashing to the next batch of stakes on the validator, returning the
+    /// messages slashing the associated collateral in the vaults, if any. The pending slashing is
+    /// removed once all of the stakes are processed.
+    fn process_slashing(
+        &self,
+        env: &Env,
+        storage: &mut dyn Storage,
+        config: &Config,
+        validator: &str,
+        infraction_height: u64,
+    ) -> Result<Vec<WasmMsg>, ContractError> {
+        let mut pending = self
+            .pending_slashes
+            .load(storage, (validator, infraction_height))?;
+
+        // Users staking via this validator, after the last processed one. One more than the batch
+        // to know if any is left
+        let bound = pending
+            .last_user
+            .as_ref()
+            .map(|user| Bound::exclusive((user.clone(), (user.clone(), validator.to_string()))));
+        let mut users = self
             .stakes
             .stake
             .idx
             .rev
             .sub_prefix(validator.to_string())
-            .range(storage, None, None, Order::Ascending)
+            .range(storage, bound, None, Order::Ascending)
+            .take(SLASH_BATCH + 1)
             .map(|item| {
                 let ((user, _), stake) = item?;
                 Ok::<_, ContractError>((user, stake))
             })
             .collect::<Result<Vec<_>, _>>()?;
-        if users.is_empty() {
-            return Ok(None);
+        if users.len() > SLASH_BATCH {
+            users.truncate(SLASH_BATCH);
+            pending.last_user = users.last().map(|(user, _)| user.clone());
+            self.pending_slashes
+                .save(storage, (validator, infraction_height), &pending)?;
+        } else {
+            self.pending_slashes
+                .remove(storage, (validator, infraction_height));
         }
-        // Compute effective slash ratio
-        let total_amount = users
-            .iter()
-            .map(|(_, stake)| stake.stake.high())
-            .sum::<Uint128>();
-        let effective_slash_ratio = Decimal::from_ratio(slash_amount, total_amount);
 
         // Slash their stake in passing
-        let mut slash_infos = vec![];
+        let mut slash_infos: BTreeMap<Addr, Vec<SlashInfo>> = BTreeMap::new();
         for (user, ref mut stake) in users {
             let stake_low = stake.stake.low();
             let stake_high = stake.stake.high();
@@ -911,7 +3512,7 @@ impl ExternalStakingContract<'_> {
             if stake_high.is_zero() {
                 continue;
             }
-            let stake_slash = stake_high * effective_slash_ratio;
+            let stake_slash = stake_high * pending.effective_slash_ratio;
             // Requires proper saturating methods in commit/rollback_stake/unstake
             stake.stake = ValueRange::new(
                 stake_low.saturating_sub(stake_slash),
@@ -923,36 +3524,86 @@ impl ExternalStakingContract<'_> {
                 .distribution
                 .may_load(storage, validator)?
                 .unwrap_or_default();
-            stake
-                .points_alignment
-                .stake_decreased(stake_slash, distribution.points_per_stake);
+            let old_total = distribution.total_stake;
+            stake.stake_decreased(stake_slash, &distribution);
             distribution.total_stake = distribution.total_stake.saturating_sub(stake_slash); // Don't fail if pending bond tx
-            self.distribution.save(storage, validator, &distribution)?;
+            self.save_distribution(storage, validator, old_total, &distribution)?;
 
             // Slash the unbondings. We use the nominal slash ratio here, like in the blockchain
             let pending_slashed = stake.slash_pending(
                 &env.block,
-                slash_ratio,
+                pending.slash_ratio,
                 config.unbonding_period,
-                infraction_time,
+                pending.infraction_time,
             );
 
             self.stakes.stake.save(storage, (&user, validator), stake)?;
 
-            slash_infos.push(SlashInfo {
-                user: user.to_string(),
-                slash: stake_slash + pending_slashed,
-            });
+            let source = self.vaults.source(storage, config, &user, validator)?;
+            slash_infos
+                .entry(source.vault)
+                .or_default()
+                .push(SlashInfo {
+                    user: user.to_string(),
+                    slash: stake_slash + pending_slashed,
+                });
         }
-        if slash_infos.is_empty() {
-            return Ok(None);
+
+        // Route associated users to the vault of their stake for slashing of their collateral
+        slash_infos
+            .into_iter()
+            .map(|(vault, slash_infos)| {
+                let msg = VaultApiHelper(vault).process_cross_slashing(slash_infos, validator)?;
+                Ok::<_, ContractError>(msg)
+            })
+            .collect()
+    }
+
+    /// Checks the stake of `user` on `validator` comes from `source`. Stakes with nothing staked
+    /// nor unbonding are moved to `source`
+    fn assign_source(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        user: &Addr,
+        validator: &str,
+        source: &StakeSource,
+    ) -> Result<(), ContractError> {
+        let current = self.vaults.source(storage, config, user, validator)?;
+        if current == *source {
+            return Ok(());
         }
+        let stake = self
+            .stakes
+            .stake
+            .may_load(storage, (user, validator))?
+            .unwrap_or_default();
+        ensure!(
+            current.can_switch(source, &stake),
+            ContractError::VaultMismatch(current.vault.into_string())
+        );
+        self.vaults
+            .set_source(storage, config, user, validator, source)?;
+        Ok(())
+    }
 
-        // Route associated users to vault for slashing of their collateral
-        let msg = config
-            .vault
-            .process_cross_slashing(slash_infos, validator)?;
-        Ok(Some(msg))
+    /// Fails if a slashing of the validator is still being applied
+    fn ensure_not_slashing(
+        &self,
+        storage: &dyn Storage,
+        validator: &str,
+    ) -> Result<(), ContractError> {
+        let slashing = self
+            .pending_slashes
+            .prefix(validator)
+            .keys(storage, None, None, Order::Ascending)
+            .next()
+            .is_some();
+        ensure!(
+            !slashing,
+            ContractError::SlashingInProgress(validator.to_owned())
+        );
+        Ok(())
     }
 
     /// Queries for contract configuration
@@ -962,6 +3613,41 @@ impl ExternalStakingContract<'_> {
         Ok(resp)
     }
 
+    /// Vaults allowed to stake
+    #[sv::msg(query)]
+    pub fn vaults(&self, ctx: QueryCtx) -> Result<VaultsResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let vaults = self
+            .vaults
+            .list(ctx.deps.storage, &config)?
+            .into_iter()
+            .map(|source| VaultInfo {
+                vault: source.vault.into_string(),
+                denom: source.denom,
+            })
+            .collect();
+        Ok(VaultsResponse { vaults })
+    }
+
+    /// Vault the stake of `user` on `validator` comes from, and its denom
+    #[sv::msg(query)]
+    pub fn stake_vault(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: String,
+    ) -> Result<VaultInfo, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let source = self
+            .vaults
+            .source(ctx.deps.storage, &config, &user, &validator)?;
+        Ok(VaultInfo {
+            vault: source.vault.into_string(),
+            denom: source.denom,
+        })
+    }
+
     /// Query for the endpoint that can connect
     #[sv::msg(query)]
     pub fn authorized_endpoint(
@@ -972,142 +3658,472 @@ impl ExternalStakingContract<'_> {
         Ok(resp)
     }
 
-    /// Query for the endpoint that can connect
-    #[sv::msg(query)]
-    pub fn ibc_channel(&self, ctx: QueryCtx) -> Result<IbcChannelResponse, ContractError> {
-        let channel = IBC_CHANNEL.load(ctx.deps.storage)?;
-        Ok(IbcChannelResponse { channel })
+    /// Query for the endpoint that can connect
+    #[sv::msg(query)]
+    pub fn ibc_channel(&self, ctx: QueryCtx) -> Result<IbcChannelResponse, ContractError> {
+        let channel = load_channel(ctx.deps.storage)?;
+        Ok(IbcChannelResponse { channel })
+    }
+
+    /// Show all external validators that we know to be active (and can delegate to)
+    #[sv::msg(query)]
+    pub fn list_active_validators(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<String>,
+        limit: Option<u64>,
+    ) -> Result<ListActiveValidatorsResponse, ContractError> {
+        let limit = limit.unwrap_or(100) as usize;
+        let validators =
+            self.val_set
+                .list_active_validators(ctx.deps.storage, start_after.as_deref(), limit)?;
+        Ok(ListActiveValidatorsResponse { validators })
+    }
+
+    /// Show all external validators that we know about, along with their state.
+    #[sv::msg(query)]
+    pub fn list_validators(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<String>,
+        limit: Option<u64>,
+    ) -> Result<ListValidatorsResponse, ContractError> {
+        let limit = limit.unwrap_or(100) as usize;
+        let validators = self
+            .val_set
+            .list_validators(ctx.deps.storage, start_after.as_deref(), limit)?
+            .into_iter()
+            .map(|(valoper, state, metadata)| {
+                Ok(crate::msg::ValidatorState {
+                    validator: valoper,
+                    state,
+                    commission: metadata.commission,
+                    moniker: metadata.moniker,
+                })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(ListValidatorsResponse { validators })
+    }
+
+    /// Queries for stake info
+    ///
+    /// If stake does not exist for (user, validator) pair, the zero-stake is returned
+    #[sv::msg(query)]
+    pub fn stake(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: String,
+    ) -> Result<Stake, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&user, &validator))?
+            .unwrap_or_default();
+
+        Ok(stake)
+    }
+
+    /// Paginated list of user stakes.
+    ///
+    /// `start_after` is the last validator of previous page
+    #[sv::msg(query)]
+    pub fn stakes(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<StakesResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let user = ctx.deps.api.addr_validate(&user)?;
+
+        let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
+
+        let stakes = self
+            .stakes
+            .stake
+            .prefix(&user)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                item.map(|(validator, stake)| {
+                    Ok::<StakeInfo, ContractError>(StakeInfo {
+                        owner: user.to_string(),
+                        validator,
+                        stake,
+                    })
+                })?
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        let resp = StakesResponse { stakes };
+
+        Ok(resp)
+    }
+
+    /// Checksum of the total stake per validator, as last sent (or to be sent) to the consumer
+    /// by `send_stake_checksum`
+    #[sv::msg(query)]
+    pub fn stake_checksum(&self, ctx: QueryCtx) -> Result<StakeChecksumResponse, ContractError> {
+        let checksum = self
+            .stake_checksum
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        Ok(StakeChecksumResponse { checksum })
+    }
+
+    /// Cross-staked weight voting for each option of a consumer governance proposal, as last
+    /// relayed by `vote_remote`
+    #[sv::msg(query)]
+    pub fn remote_vote_tally(
+        &self,
+        ctx: QueryCtx,
+        proposal_id: u64,
+    ) -> Result<RemoteVoteTallyResponse, ContractError> {
+        let tally = self
+            .remote_vote_tallies
+            .may_load(ctx.deps.storage, proposal_id)?
+            .unwrap_or_default();
+        Ok(RemoteVoteTallyResponse { tally })
+    }
+
+    /// Liquid tokens backing the instant unstakes
+    #[sv::msg(query)]
+    pub fn buffer(&self, ctx: QueryCtx) -> Result<BufferResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let state = self
+            .buffer
+            .state
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        let epoch_used = match config.instant_unstake {
+            Some(instant) => self.buffer.used_quota(
+                ctx.deps.storage,
+                ctx.env.block.time,
+                instant.epoch_length,
+            )?,
+            None => Uint128::zero(),
+        };
+
+        Ok(BufferResponse {
+            total: state.total,
+            locked: state.locked,
+            slashed: state.slashed,
+            epoch_used,
+        })
+    }
+
+    /// Protocol fees collected on the rewards and not withdrawn yet
+    #[sv::msg(query)]
+    pub fn fees_collected(&self, ctx: QueryCtx) -> Result<FeesCollectedResponse, ContractError> {
+        let fees = self
+            .fees_collected
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(denom, amount)| coin(amount.u128(), denom)))
+            .collect::<StdResult<_>>()?;
+        Ok(FeesCollectedResponse { fees })
+    }
+
+    /// User tokens in unbonding period, summed in time buckets of `bucket_secs` seconds (a day
+    /// by default), along with the tokens already released and claimable now.
+    ///
+    /// Only the unbondings from `validator` are reported if it is set, from all validators
+    /// otherwise.
+    #[sv::msg(query)]
+    pub fn unbonding_schedule(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: Option<String>,
+        bucket_secs: Option<u64>,
+    ) -> Result<UnbondingScheduleResponse, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let bucket_secs = bucket_secs.unwrap_or(DEFAULT_UNBONDING_BUCKET_SECS).max(1);
+
+        let stakes: Vec<Stake> = match validator {
+            Some(validator) => self
+                .stakes
+                .stake
+                .may_load(ctx.deps.storage, (&user, &validator))?
+                .into_iter()
+                .collect(),
+            None => self
+                .stakes
+                .stake
+                .prefix(&user)
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, stake)| stake))
+                .collect::<StdResult<_>>()?,
+        };
+
+        let now = ctx.env.block.time;
+        let mut claimable = Uint128::zero();
+        // Bucket end (in seconds) -> amount
+        let mut buckets = BTreeMap::<u64, Uint128>::new();
+        for unbond in stakes.iter().flat_map(|stake| &stake.pending_unbonds) {
+            if unbond.release_at <= now {
+                claimable += unbond.amount;
+            } else {
+                // Rounded up to the bucket end, in the last bucket on overflow
+                let secs =
+                    unbond.release_at.seconds() + u64::from(unbond.release_at.subsec_nanos() > 0);
+                let end = secs
+                    .checked_add(bucket_secs - 1)
+                    .map_or(u64::MAX, |secs| secs / bucket_secs * bucket_secs);
+                *buckets.entry(end).or_default() += unbond.amount;
+            }
+        }
+
+        let buckets = buckets
+            .into_iter()
+            .map(|(end, amount)| UnbondingBucket {
+                release_at: Timestamp::from_seconds(end),
+                amount,
+            })
+            .collect();
+
+        Ok(UnbondingScheduleResponse { claimable, buckets })
+    }
+
+    /// Queries a pending tx.
+    #[sv::msg(query)]
+    fn pending_tx(&self, ctx: QueryCtx, tx_id: u64) -> Result<TxResponse, ContractError> {
+        let resp = self.pending_txs.load(ctx.deps.storage, tx_id)?;
+        Ok(resp)
+    }
+
+    /// Queries for all pending txs.
+    /// Reports txs in descending order (newest first).
+    /// `start_after` is the last tx id included in previous page
+    #[sv::msg(query)]
+    fn all_pending_txs_desc(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<AllTxsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.and_then(Bounder::exclusive_bound);
+
+        let txs = self
+            .pending_txs
+            .range(ctx.deps.storage, None, bound, Order::Descending)
+            .map(|item| {
+                let (_id, tx) = item?;
+                Ok::<TxResponse, ContractError>(tx)
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        let resp = AllTxsResponse { txs };
+
+        Ok(resp)
     }
 
-    /// Show all external validators that we know to be active (and can delegate to)
+    /// Lists consumer packets already processed on the channel, for auditing relayer re-deliveries
     #[sv::msg(query)]
-    pub fn list_active_validators(
+    fn processed_packets(
         &self,
         ctx: QueryCtx,
-        start_after: Option<String>,
-        limit: Option<u64>,
-    ) -> Result<ListActiveValidatorsResponse, ContractError> {
-        let limit = limit.unwrap_or(100) as usize;
-        let validators =
-            self.val_set
-                .list_active_validators(ctx.deps.storage, start_after.as_deref(), limit)?;
-        Ok(ListActiveValidatorsResponse { validators })
-    }
+        channel_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<ProcessedPacketsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.and_then(Bounder::exclusive_bound);
 
-    /// Show all external validators that we know about, along with their state.
-    #[sv::msg(query)]
-    pub fn list_validators(
-        &self,
-        ctx: QueryCtx,
-        start_after: Option<String>,
-        limit: Option<u64>,
-    ) -> Result<ListValidatorsResponse, ContractError> {
-        let limit = limit.unwrap_or(100) as usize;
-        let validators = self
-            .val_set
-            .list_validators(ctx.deps.storage, start_after.as_deref(), limit)?
-            .into_iter()
-            .map(|(valoper, state)| {
-                Ok(crate::msg::ValidatorState {
-                    validator: valoper,
-                    state,
+        let packets = self
+            .processed_packets
+            .prefix(&channel_id)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let (sequence, packet) = item?;
+                Ok::<_, ContractError>(ProcessedPacketInfo {
+                    sequence,
+                    packet_type: packet.packet_type,
+                    height: packet.height,
+                    time: packet.time,
                 })
             })
-            .collect::<StdResult<Vec<_>>>()?;
-        Ok(ListValidatorsResponse { validators })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        Ok(ProcessedPacketsResponse { packets })
     }
 
-    /// Queries for stake info
-    ///
-    /// If stake does not exist for (user, validator) pair, the zero-stake is returned
+    /// Lists the gaps in the consumer packet sequences received on the channel, so relayer
+    /// operators can spot dropped packets. `start_after` is the first sequence of a gap.
     #[sv::msg(query)]
-    pub fn stake(
+    fn missing_sequences(
         &self,
         ctx: QueryCtx,
-        user: String,
-        validator: String,
-    ) -> Result<Stake, ContractError> {
-        let user = ctx.deps.api.addr_validate(&user)?;
-        let stake = self
-            .stakes
-            .stake
-            .may_load(ctx.deps.storage, (&user, &validator))?
+        channel_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<MissingSequencesResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.and_then(Bounder::exclusive_bound);
+
+        let last_received = self
+            .last_received_sequences
+            .may_load(ctx.deps.storage, &channel_id)?
             .unwrap_or_default();
+        let missing = self
+            .missing_sequences
+            .prefix(&channel_id)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let (first, last) = item?;
+                Ok::<_, ContractError>(SequenceRange { first, last })
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
 
-        Ok(stake)
+        Ok(MissingSequencesResponse {
+            last_received,
+            missing,
+        })
     }
 
-    /// Paginated list of user stakes.
-    ///
-    /// `start_after` is the last validator of previous page
+    /// Lists the validators removed from the consumer active set, during their removal
+    /// grace period
     #[sv::msg(query)]
-    pub fn stakes(
+    fn leaving_validators(
         &self,
         ctx: QueryCtx,
-        user: String,
         start_after: Option<String>,
         limit: Option<u32>,
-    ) -> Result<StakesResponse, ContractError> {
+    ) -> Result<LeavingValidatorsResponse, ContractError> {
         let limit = clamp_page_limit(limit);
-        let user = ctx.deps.api.addr_validate(&user)?;
-
         let bound = start_after.as_deref().and_then(Bounder::exclusive_bound);
 
-        let stakes = self
-            .stakes
-            .stake
-            .prefix(&user)
+        let validators = self
+            .leaving_validators
             .range(ctx.deps.storage, bound, None, Order::Ascending)
             .map(|item| {
-                item.map(|(validator, stake)| {
-                    Ok::<StakeInfo, ContractError>(StakeInfo {
-                        owner: user.to_string(),
-                        validator,
-                        stake,
-                    })
-                })?
+                let (validator, leaving) = item?;
+                Ok::<_, ContractError>(LeavingValidatorInfo {
+                    validator,
+                    finalize_at: leaving.finalize_at,
+                })
             })
             .take(limit)
             .collect::<Result<_, _>>()?;
 
-        let resp = StakesResponse { stakes };
-
-        Ok(resp)
+        Ok(LeavingValidatorsResponse { validators })
     }
 
-    /// Queries a pending tx.
+    /// Lists timed out provider packets waiting to be retried, oldest first
     #[sv::msg(query)]
-    fn pending_tx(&self, ctx: QueryCtx, tx_id: u64) -> Result<TxResponse, ContractError> {
-        let resp = self.pending_txs.load(ctx.deps.storage, tx_id)?;
-        Ok(resp)
+    fn pending_packets(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<PendingPacketsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.and_then(Bounder::exclusive_bound);
+
+        let packets = self
+            .pending_packets
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let (sequence, pending) = item?;
+                Ok::<_, ContractError>(PendingPacketInfo {
+                    sequence,
+                    packet: pending.packet,
+                    timed_out_at: pending.timed_out_at,
+                })
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        Ok(PendingPacketsResponse { packets })
     }
 
-    /// Queries for all pending txs.
-    /// Reports txs in descending order (newest first).
-    /// `start_after` is the last tx id included in previous page
+    /// Slashings of the validator not applied to all of its stakes yet, oldest first
     #[sv::msg(query)]
-    fn all_pending_txs_desc(
+    pub fn pending_slashes(
         &self,
         ctx: QueryCtx,
+        validator: String,
         start_after: Option<u64>,
         limit: Option<u32>,
-    ) -> Result<AllTxsResponse, ContractError> {
+    ) -> Result<PendingSlashesResponse, ContractError> {
         let limit = clamp_page_limit(limit);
         let bound = start_after.and_then(Bounder::exclusive_bound);
 
-        let txs = self
-            .pending_txs
-            .range(ctx.deps.storage, None, bound, Order::Descending)
+        let slashes = self
+            .pending_slashes
+            .prefix(&validator)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
             .map(|item| {
-                let (_id, tx) = item?;
-                Ok::<TxResponse, ContractError>(tx)
+                let (infraction_height, pending) = item?;
+                Ok::<_, ContractError>(PendingSlashInfo {
+                    infraction_height,
+                    slash_ratio: pending.slash_ratio,
+                    effective_slash_ratio: pending.effective_slash_ratio,
+                    last_user: pending.last_user.map(Addr::into_string),
+                })
             })
             .take(limit)
             .collect::<Result<_, _>>()?;
 
-        let resp = AllTxsResponse { txs };
+        Ok(PendingSlashesResponse { slashes })
+    }
 
-        Ok(resp)
+    /// Historical positions of the user, as recorded by `take_snapshots`. All of the user stakes
+    /// are returned, unless `validator` is set.
+    ///
+    /// Snapshots are ordered by epoch, and `start_after` is an epoch. As a page may end in the
+    /// middle of an epoch, the next one should start after the last complete epoch.
+    #[sv::msg(query)]
+    pub fn snapshots(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: Option<String>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<SnapshotsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let capacity = self.config.load(ctx.deps.storage)?.snapshot_capacity;
+
+        let entries = match &validator {
+            Some(validator) => self
+                .snapshots
+                .prefix((&user, validator.as_str()))
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(slot, snapshot)| ((validator.clone(), slot), snapshot)))
+                .collect::<StdResult<Vec<_>>>()?,
+            None => self
+                .snapshots
+                .sub_prefix(&user)
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?,
+        };
+
+        let mut snapshots: Vec<_> = entries
+            .into_iter()
+            // Slots over the capacity are left from a larger previous capacity
+            .filter(|((_, slot), snapshot)| {
+                *slot < capacity && start_after.map_or(true, |after| snapshot.epoch > after)
+            })
+            .map(|((validator, _), snapshot)| SnapshotInfo {
+                validator,
+                epoch: snapshot.epoch,
+                time: snapshot.time,
+                stake: snapshot.stake,
+                accumulated_rewards: snapshot.accumulated_rewards,
+            })
+            .collect();
+        snapshots.sort_by(|a, b| (a.epoch, &a.validator).cmp(&(b.epoch, &b.validator)));
+        snapshots.truncate(limit);
+
+        Ok(SnapshotsResponse { snapshots })
     }
 
     /// Returns how much rewards are to be withdrawn by particular user, from the particular
@@ -1140,6 +4156,193 @@ impl ExternalStakingContract<'_> {
         })
     }
 
+    /// Returns how much rewards are to be withdrawn by particular user, from the particular
+    /// validator staking, in every rewards denom
+    #[sv::msg(query)]
+    pub fn pending_rewards_by_denom(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        validator: String,
+    ) -> Result<PendingRewardsByDenom, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+
+        let stake = self
+            .stakes
+            .stake
+            .may_load(ctx.deps.storage, (&user, &validator))?
+            .unwrap_or_default();
+
+        let distribution = self
+            .distribution
+            .may_load(ctx.deps.storage, &validator)?
+            .unwrap_or_default();
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let rewards = std::iter::once(&config.rewards_denom)
+            .chain(&config.extra_rewards_denoms)
+            .map(|denom| {
+                let amount = Self::calculate_denom_reward(&config, &stake, &distribution, denom)?;
+                Ok::<_, ContractError>(coin(amount.u128(), denom))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(PendingRewardsByDenom { rewards })
+    }
+
+    /// Returns whether the user opted in for auto-compounding
+    #[sv::msg(query)]
+    pub fn auto_compound(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+    ) -> Result<AutoCompoundResponse, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let enabled = self.auto_compound.has(ctx.deps.storage, &owner);
+        Ok(AutoCompoundResponse { enabled })
+    }
+
+    /// Returns the validator the user stakes to with an empty `stake_remote` msg, if any
+    #[sv::msg(query)]
+    pub fn default_validator(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+    ) -> Result<DefaultValidatorResponse, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let validator = self.default_validators.may_load(ctx.deps.storage, &owner)?;
+        Ok(DefaultValidatorResponse { validator })
+    }
+
+    /// Returns whether the user already used `key` for a stake or an unstake. Only the most
+    /// recent keys are remembered
+    #[sv::msg(query)]
+    pub fn idempotency_key(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        key: String,
+    ) -> Result<IdempotencyKeyResponse, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let used = self
+            .idempotency_keys
+            .is_used(ctx.deps.storage, &owner, &key);
+        Ok(IdempotencyKeyResponse { used })
+    }
+
+    /// Recent stake, unstake and withdraw operations of the user, ordered by `stake_tx_id`.
+    /// Only the most recent operations over all of the users are kept
+    #[sv::msg(query)]
+    pub fn tx_history(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<TxHistoryResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let txs = self
+            .tx_history
+            .range(ctx.deps.storage, &user, start_after, limit)?;
+        Ok(TxHistoryResponse { txs })
+    }
+
+    /// Returns the validators the user denies or restricts their stakes to
+    #[sv::msg(query)]
+    pub fn validator_preferences(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+    ) -> Result<ValidatorPreferences, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let preferences = self
+            .validator_preferences
+            .may_load(ctx.deps.storage, &owner)?
+            .unwrap_or_default();
+        Ok(preferences)
+    }
+
+    /// Returns the last signing performance reported for the validator, and whether it's under
+    /// the min uptime
+    #[sv::msg(query)]
+    pub fn validator_health(
+        &self,
+        ctx: QueryCtx,
+        validator: String,
+    ) -> Result<ValidatorHealthResponse, ContractError> {
+        let min_uptime = self.config.load(ctx.deps.storage)?.min_uptime;
+        let health = self
+            .validator_health
+            .may_load(ctx.deps.storage, &validator)?;
+        let below_min = match (&health, min_uptime) {
+            (Some(health), Some(min_uptime)) => health.uptime < min_uptime,
+            _ => false,
+        };
+        Ok(ValidatorHealthResponse {
+            validator,
+            health,
+            min_uptime,
+            below_min,
+        })
+    }
+
+    /// Returns the sub-unit rewards accumulated by the user in `denom` (the main rewards denom by
+    /// default), not paid out yet
+    #[sv::msg(query)]
+    pub fn reward_dust(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        denom: Option<String>,
+    ) -> Result<RewardDustResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let denom = denom.unwrap_or(config.rewards_denom);
+        let points = self.reward_dust.balance(ctx.deps.storage, &owner, &denom)?;
+        let dust = Decimal::from_ratio(
+            Uint128::try_from(points)?,
+            Uint128::try_from(distribution::DISTRIBUTION_POINTS_SCALE)?,
+        );
+        Ok(RewardDustResponse {
+            owner: owner.into_string(),
+            denom,
+            dust,
+            threshold: max(config.dust_threshold, Uint128::one()),
+        })
+    }
+
+    /// Returns the unstakes queued with unstake batching, not sent to the consumer yet
+    #[sv::msg(query)]
+    pub fn queued_unstakes(&self, ctx: QueryCtx) -> Result<QueuedUnstakesResponse, ContractError> {
+        let queue = self.unstake_queue.may_load(ctx.deps.storage)?;
+        Ok(QueuedUnstakesResponse {
+            since: queue.as_ref().map(|queue| queue.since),
+            tx_ids: queue.map(|queue| queue.tx_ids).unwrap_or_default(),
+        })
+    }
+
+    /// Returns the stake on the validator, and how much more it can receive before reaching the
+    /// max cross-stake set by the consumer, if any
+    #[sv::msg(query)]
+    pub fn validator_capacity(
+        &self,
+        ctx: QueryCtx,
+        validator: String,
+    ) -> Result<ValidatorCapacityResponse, ContractError> {
+        let total_stake = self.validator_stake(ctx.deps.storage, &validator)?;
+        let max_stake = self
+            .max_external_stakes
+            .may_load(ctx.deps.storage, &validator)?;
+        let remaining = max_stake.map(|max_stake| max_stake.saturating_sub(total_stake));
+        Ok(ValidatorCapacityResponse {
+            validator,
+            total_stake,
+            max_stake,
+            remaining,
+        })
+    }
+
     /// Returns how much rewards are to be withdrawn by particular user, iterating over all validators.
     /// This is like stakes is to stake query, but for rewards.
     #[sv::msg(query)]
@@ -1194,21 +4397,56 @@ impl ExternalStakingContract<'_> {
     ) -> Result<Uint128, ContractError> {
         // Calculating rewards with always the `low` value of the range goes against the user in some
         // scenario (pending unstakes), but the possible errors are small and temporary.
-        let points = distribution.points_per_stake * Uint256::from(stake.stake.low());
+        let amount = distribution::pending_rewards(
+            distribution.points_per_stake,
+            stake.stake.low(),
+            stake.points_alignment,
+            stake.withdrawn_funds,
+        )?;
+
+        Ok(amount)
+    }
 
-        let points = stake.points_alignment.align(points);
-        let total = Uint128::try_from(points / DISTRIBUTION_POINTS_SCALE)?;
+    /// Like `calculate_reward`, in any of the rewards denoms
+    fn calculate_denom_reward(
+        config: &Config,
+        stake: &Stake,
+        distribution: &Distribution,
+        denom: &str,
+    ) -> Result<Uint128, ContractError> {
+        if denom == config.rewards_denom {
+            return Self::calculate_reward(stake, distribution);
+        }
+        // Not distributed yet
+        let Some(points) = distribution.extra_points(denom) else {
+            return Ok(Uint128::zero());
+        };
 
-        Ok(total - stake.withdrawn_funds)
+        // No alignment until the stake changes
+        let (alignment, withdrawn) = match stake.extra_rewards(denom) {
+            Some(rewards) => (rewards.points_alignment, rewards.withdrawn_funds),
+            None => (PointsAlignment::new(), Uint128::zero()),
+        };
+        let amount = distribution::pending_rewards(
+            points.points_per_stake,
+            stake.stake.low(),
+            alignment,
+            withdrawn,
+        )?;
+
+        Ok(amount)
     }
 }
 
 pub mod cross_staking {
-    use crate::msg::ReceiveVirtualStake;
+    use crate::msg::ReceiveVirtualStakeMsg;
 
     use super::*;
     use cosmwasm_std::{from_json, Binary};
-    use mesh_apis::{cross_staking_api::CrossStakingApi, local_staking_api::SlashRatioResponse};
+    use mesh_apis::cross_staking_api::{
+        CapabilitiesResponse, CrossStakingApi, TotalPendingRewardsResponse,
+    };
+    use mesh_apis::local_staking_api::SlashRatioResponse;
 
     #[contract(module=crate::contract)]
     #[sv::messages(mesh_apis::cross_staking_api as CrossStakingApi)]
@@ -1225,76 +4463,184 @@ pub mod cross_staking {
             msg: Binary,
         ) -> Result<Response, Self::Error> {
             let config = self.config.load(ctx.deps.storage)?;
-            ensure_eq!(ctx.info.sender, config.vault.0, ContractError::Unauthorized);
-
-            // sending proper denom
-            ensure_eq!(
-                amount.denom,
-                config.denom,
-                ContractError::InvalidDenom(config.denom)
-            );
+            let denom = self
+                .vaults
+                .denom(ctx.deps.storage, &config, &ctx.info.sender)?
+                .ok_or(MeshError::Unauthorized)?;
+
+            // sending the denom bonded by the vault
+            ensure_eq!(amount.denom, denom, MeshError::InvalidDenom(denom));
+            let source = StakeSource {
+                vault: ctx.info.sender.clone(),
+                denom,
+            };
 
             let owner = ctx.deps.api.addr_validate(&owner)?;
 
-            // parse and validate message
-            let msg: ReceiveVirtualStake = from_json(msg)?;
-            if !self
-                .val_set
-                .is_active_validator(ctx.deps.storage, &msg.validator)?
-            {
-                return Err(ContractError::ValidatorNotActive(msg.validator));
+            // no new cross-stakes while the channel is closed
+            let channel = load_channel(ctx.deps.storage)?;
+
+            // parse and validate message, and prepare the stakes.
+            // An empty message stakes to the owner default validator
+            let msg: ReceiveVirtualStakeMsg = if msg.is_empty() {
+                let validator = self
+                    .default_validators
+                    .may_load(ctx.deps.storage, &owner)?
+                    .ok_or_else(|| ContractError::NoDefaultValidator(owner.to_string()))?;
+                ReceiveVirtualStakeMsg::Stake(ReceiveVirtualStake::new(validator))
+            } else {
+                from_json(msg)?
+            };
+            let idempotency_key = match &msg {
+                ReceiveVirtualStakeMsg::Stake(msg) => msg.idempotency_key.as_ref(),
+                ReceiveVirtualStakeMsg::StakeBatch {
+                    idempotency_key, ..
+                } => idempotency_key.as_ref(),
+                ReceiveVirtualStakeMsg::Auto(msg) => msg.idempotency_key.as_ref(),
+            };
+            if let Some(key) = idempotency_key {
+                self.idempotency_keys
+                    .record(ctx.deps.storage, &owner, key, tx_id)?;
             }
-            let mut stake = self
-                .stakes
-                .stake
-                .may_load(ctx.deps.storage, (&owner, &msg.validator))?
-                .unwrap_or_default();
+            let msg = match msg {
+                ReceiveVirtualStakeMsg::Auto(AutoStake { strategy, .. }) => {
+                    let mut stakes =
+                        self.select_validators(ctx.deps.storage, &owner, strategy, amount.amount)?;
+                    if stakes.len() == 1 {
+                        let validator = stakes.remove(0).validator;
+                        ReceiveVirtualStakeMsg::Stake(ReceiveVirtualStake::new(validator))
+                    } else {
+                        ReceiveVirtualStakeMsg::StakeBatch {
+                            stakes,
+                            idempotency_key: None,
+                        }
+                    }
+                }
+                msg => msg,
+            };
+            let (new_tx, packet, staked) = match msg {
+                ReceiveVirtualStakeMsg::Stake(msg) => {
+                    msg.validate()?;
+                    self.assign_source(ctx.deps.storage, &config, &owner, &msg.validator, &source)?;
+                    self.prepare_stake(ctx.deps.storage, &owner, &msg.validator, amount.amount)?;
+                    let staked = vec![(msg.validator.clone(), amount.amount)];
+
+                    let new_tx = Tx::InFlightRemoteStaking {
+                        id: tx_id,
+                        amount: amount.amount,
+                        user: owner.clone(),
+                        validator: msg.validator.clone(),
+                    };
+                    let packet = ProviderPacket::Stake {
+                        validator: msg.validator,
+                        stake: amount.clone(),
+                        tx_id,
+                    };
+                    (new_tx, packet, staked)
+                }
+                ReceiveVirtualStakeMsg::StakeBatch { stakes, .. } => {
+                    ensure!(
+                        channel_features(ctx.deps.storage)?.contains(Features::BATCH_STAKE),
+                        ContractError::IbcFeatureNotSupported("batch stakes".to_owned())
+                    );
+                    let total: Uint128 = stakes.iter().map(|stake| stake.amount).sum();
+                    ensure!(
+                        !stakes.is_empty() && total == amount.amount,
+                        ContractError::InvalidBatchAmount(amount.amount)
+                    );
+
+                    for stake in &stakes {
+                        self.assign_source(
+                            ctx.deps.storage,
+                            &config,
+                            &owner,
+                            &stake.validator,
+                            &source,
+                        )?;
+                        self.prepare_stake(
+                            ctx.deps.storage,
+                            &owner,
+                            &stake.validator,
+                            stake.amount,
+                        )?;
+                    }
 
-            // Prepare stake addition and save stake.
-            // We don't check for max here, as this call can only come from the `vault` contract, which already
-            // performed the proper check.
-            stake.stake.prepare_add(amount.amount, None)?;
-            self.stakes
-                .stake
-                .save(ctx.deps.storage, (&owner, &msg.validator), &stake)?;
-
-            // Save tx
-            let new_tx = Tx::InFlightRemoteStaking {
-                id: tx_id,
-                amount: amount.amount,
-                user: owner.clone(),
-                validator: msg.validator.clone(),
+                    let staked: Vec<_> = stakes
+                        .iter()
+                        .map(|stake| (stake.validator.clone(), stake.amount))
+                        .collect();
+                    let new_tx = Tx::InFlightRemoteStakingBatch {
+                        id: tx_id,
+                        user: owner.clone(),
+                        stakes: staked.clone(),
+                    };
+                    let packet = ProviderPacket::StakeBatch {
+                        stakes: stakes
+                            .into_iter()
+                            .map(|stake| ValidatorStake {
+                                validator: stake.validator,
+                                stake: stake.amount,
+                            })
+                            .collect(),
+                        denom: amount.denom.clone(),
+                        tx_id,
+                    };
+                    (new_tx, packet, staked)
+                }
+                ReceiveVirtualStakeMsg::Auto(_) => unreachable!("resolved above"),
             };
+
+            // Save tx. The vaults number their txs independently, so their ids may clash
+            ensure!(
+                !self.pending_txs.has(ctx.deps.storage, tx_id),
+                ContractError::TxIdInUse(tx_id)
+            );
             self.pending_txs.save(ctx.deps.storage, tx_id, &new_tx)?;
+            let validator = match staked.as_slice() {
+                [(validator, _)] => Some(validator.as_str()),
+                _ => None,
+            };
+            let stake_tx_id = self.tx_history.record(
+                ctx.deps.storage,
+                &ctx.env.block,
+                &owner,
+                StakeTxKind::Stake,
+                validator,
+                amount.clone(),
+                Some(tx_id),
+            )?;
 
             let mut resp = Response::new();
 
-            let channel = IBC_CHANNEL.load(ctx.deps.storage)?;
-            let packet = ProviderPacket::Stake {
-                validator: msg.validator,
-                stake: amount.clone(),
-                tx_id,
-            };
             let msg = IbcMsg::SendPacket {
                 channel_id: channel.endpoint.channel_id,
-                data: to_json_binary(&packet)?,
-                timeout: packet_timeout(&ctx.env),
+                data: packet.encode(channel_features(ctx.deps.storage)?)?,
+                timeout: packet_timeout(&ctx.env, &packet),
             };
-            // add ibc packet if we are ibc enabled (skip in tests)
+            // add ibc packet if we are ibc enabled (recorded for the test relayer in tests)
             #[cfg(not(any(feature = "mt", test)))]
             {
                 resp = resp.add_message(msg);
             }
             #[cfg(any(feature = "mt", test))]
             {
-                let _ = msg;
+                crate::ibc::record_test_packet(ctx.deps.storage, &msg)?;
             }
 
             resp = resp
+                .add_events(staked.into_iter().map(|(validator, stake)| {
+                    Event::from(
+                        StakeEvent::new(coin(stake.u128(), &amount.denom))
+                            .delegator(&owner)
+                            .validator(validator)
+                            .lienholder(&ctx.env.contract.address),
+                    )
+                }))
                 .add_attribute("action", "receive_virtual_stake")
                 .add_attribute("owner", owner)
                 .add_attribute("amount", amount.amount.to_string())
-                .add_attribute("tx_id", tx_id.to_string());
+                .add_attribute("tx_id", tx_id.to_string())
+                .add_attribute("stake_tx_id", stake_tx_id.to_string());
 
             Ok(resp)
         }
@@ -1308,21 +4654,28 @@ pub mod cross_staking {
             validator: Option<String>,
         ) -> Result<Response, Self::Error> {
             let config = self.config.load(ctx.deps.storage)?;
-            ensure_eq!(ctx.info.sender, config.vault.0, ContractError::Unauthorized);
-
-            // sending proper denom
-            ensure_eq!(
-                amount.denom,
-                config.denom,
-                ContractError::InvalidDenom(config.denom)
-            );
-
             let owner = ctx.deps.api.addr_validate(&owner)?;
+            let denom = self
+                .vaults
+                .denom(ctx.deps.storage, &config, &ctx.info.sender)?
+                .ok_or(MeshError::Unauthorized)?;
+
+            // sending the denom bonded by the vault
+            ensure_eq!(amount.denom, denom, MeshError::InvalidDenom(denom));
+            let source = StakeSource {
+                vault: ctx.info.sender.clone(),
+                denom,
+            };
 
+            // Only the stakes coming from the calling vault are burned
             let stakes: Vec<_> = match validator {
                 Some(validator) => {
                     // Burn from validator
                     // TODO: Preferentially, i.e. burn remaining amount, if any, from other validators
+                    let current =
+                        self.vaults
+                            .source(ctx.deps.storage, &config, &owner, &validator)?;
+                    ensure!(current == source, MeshError::Unauthorized);
                     let stake = self
                         .stakes
                         .stake
@@ -1332,16 +4685,23 @@ pub mod cross_staking {
                 }
                 None => {
                     // Burn proportionally from all validators associated to the user
-                    self.stakes
-                        .stake
-                        .prefix(&owner)
-                        .range(ctx.deps.storage, None, None, Order::Ascending)
-                        .map(|item| {
-                            let (validator, stake) = item?;
-                            Ok::<_, Self::Error>((validator, stake.stake.high().u128()))
+                    let mut stakes = vec![];
+                    for item in self.stakes.stake.prefix(&owner).range(
+                        ctx.deps.storage,
+                        None,
+                        None,
+                        Order::Ascending,
+                    ) {
+                        let (validator, stake) = item?;
+                        let current =
+                            self.vaults
+                                .source(ctx.deps.storage, &config, &owner, &validator)?;
+                        if current == source {
                             // Burn takes precedence over any pending txs
-                        })
-                        .collect::<Result<_, _>>()?
+                            stakes.push((validator, stake.stake.high().u128()));
+                        }
+                    }
+                    stakes
                 }
             };
 
@@ -1379,11 +4739,10 @@ pub mod cross_staking {
                     .distribution
                     .may_load(ctx.deps.storage, validator)?
                     .unwrap_or_default();
+                let old_total = distribution.total_stake;
 
                 // Distribution alignment
-                stake
-                    .points_alignment
-                    .stake_decreased(burn_amount, distribution.points_per_stake);
+                stake.stake_decreased(burn_amount, &distribution);
                 distribution.total_stake -= burn_amount;
 
                 // Save stake
@@ -1392,29 +4751,28 @@ pub mod cross_staking {
                     .save(ctx.deps.storage, (&owner, validator), &stake)?;
 
                 // Save distribution
-                self.distribution
-                    .save(ctx.deps.storage, validator, &distribution)?;
+                self.save_distribution(ctx.deps.storage, validator, old_total, &distribution)?;
             }
 
-            let channel = IBC_CHANNEL.load(ctx.deps.storage)?;
+            let channel = load_channel(ctx.deps.storage)?;
             let packet = ProviderPacket::Burn {
                 validators: burns.iter().map(|v| v.0.to_string()).collect(),
                 burn: amount.clone(),
             };
             let msg = IbcMsg::SendPacket {
                 channel_id: channel.endpoint.channel_id,
-                data: to_json_binary(&packet)?,
-                timeout: packet_timeout(&ctx.env),
+                data: packet.encode(channel_features(ctx.deps.storage)?)?,
+                timeout: packet_timeout(&ctx.env, &packet),
             };
             let mut resp = Response::new();
-            // add ibc packet if we are ibc enabled (skip in tests)
+            // add ibc packet if we are ibc enabled (recorded for the test relayer in tests)
             #[cfg(not(any(feature = "mt", test)))]
             {
                 resp = resp.add_message(msg);
             }
             #[cfg(any(feature = "mt", test))]
             {
-                let _ = msg;
+                crate::ibc::record_test_packet(ctx.deps.storage, &msg)?;
             }
 
             resp = resp
@@ -1433,6 +4791,25 @@ pub mod cross_staking {
             Ok(resp)
         }
 
+        #[sv::msg(exec)]
+        fn withdraw_rewards_for(
+            &self,
+            ctx: ExecCtx,
+            owner: String,
+            validator: String,
+            remote_recipient: String,
+        ) -> Result<Response, Self::Error> {
+            let config = self.config.load(ctx.deps.storage)?;
+            let owner = ctx.deps.api.addr_validate(&owner)?;
+            let source = self
+                .vaults
+                .source(ctx.deps.storage, &config, &owner, &validator)?;
+            ensure_eq!(ctx.info.sender, source.vault, MeshError::Unauthorized);
+            nonpayable(&ctx.info)?;
+
+            self.withdraw_rewards_of(ctx.deps, &ctx.env, owner, validator, None, remote_recipient)
+        }
+
         #[sv::msg(query)]
         fn max_slash(&self, ctx: QueryCtx) -> Result<SlashRatioResponse, ContractError> {
             let Config { slash_ratio, .. } = self.config.load(ctx.deps.storage)?;
@@ -1441,6 +4818,205 @@ pub mod cross_staking {
                 slash_ratio_offline: slash_ratio.offline,
             })
         }
+
+        /// Instant unstaking is available once enabled by the owner, compounding when the rewards
+        /// are paid in the staked denom, and batch stakes if the consumer supports them
+        #[sv::msg(query)]
+        fn capabilities(&self, ctx: QueryCtx) -> Result<CapabilitiesResponse, ContractError> {
+            let config = self.config.load(ctx.deps.storage)?;
+            Ok(CapabilitiesResponse {
+                instant_unstake: config.instant_unstake.is_some(),
+                restake: config.rewards_denom == config.denom,
+                batch_stake: channel_features(ctx.deps.storage)?.contains(Features::BATCH_STAKE),
+                max_validators_per_user: None,
+            })
+        }
+
+        #[sv::msg(query)]
+        fn total_pending_rewards(
+            &self,
+            ctx: QueryCtx,
+            user: String,
+        ) -> Result<TotalPendingRewardsResponse, ContractError> {
+            let user = ctx.deps.api.addr_validate(&user)?;
+            let config = self.config.load(ctx.deps.storage)?;
+            let denoms: Vec<_> = std::iter::once(&config.rewards_denom)
+                .chain(&config.extra_rewards_denoms)
+                .collect();
+
+            let mut totals = vec![Uint128::zero(); denoms.len()];
+            for item in self.stakes.stake.prefix(&user).range(
+                ctx.deps.storage,
+                None,
+                None,
+                Order::Ascending,
+            ) {
+                let (validator, stake) = item?;
+                let distribution = self
+                    .distribution
+                    .may_load(ctx.deps.storage, &validator)?
+                    .unwrap_or_default();
+                for (total, denom) in totals.iter_mut().zip(&denoms) {
+                    *total += Self::calculate_denom_reward(&config, &stake, &distribution, denom)?;
+                }
+            }
+
+            let rewards = denoms
+                .into_iter()
+                .zip(totals)
+                .filter(|(_, amount)| !amount.is_zero())
+                .map(|(denom, amount)| coin(amount.u128(), denom))
+                .collect();
+            Ok(TotalPendingRewardsResponse { rewards })
+        }
+    }
+}
+
+pub mod slash_evidence {
+    use super::*;
+    use cosmwasm_std::Binary;
+    use mesh_apis::slash_evidence_api::{EvidenceType, SlashEvidenceApi};
+
+    #[contract(module=crate::contract)]
+    #[sv::messages(mesh_apis::slash_evidence_api as SlashEvidenceApi)]
+    impl SlashEvidenceApi for ExternalStakingContract<'_> {
+        type Error = ContractError;
+
+        /// Slashes the stake on `validator` for a double sign at the consumer `height`.
+        /// Only the owner can call this, as the proof cannot be verified here. It is
+        /// just recorded in the emitted event.
+        ///
+        /// The validator has to be active at `height`, according to the validator set updates
+        /// received from the consumer, and not already slashed for it.
+        #[sv::msg(exec)]
+        fn submit_evidence(
+            &self,
+            ctx: ExecCtx,
+            validator: String,
+            height: u64,
+            evidence_type: EvidenceType,
+            proof: Binary,
+        ) -> Result<Response, Self::Error> {
+            nonpayable(&ctx.info)?;
+
+            ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+            let infraction = (validator.as_str(), height);
+            ensure!(
+                !self.slashed_infractions.has(ctx.deps.storage, infraction),
+                ContractError::InfractionAlreadySlashed(validator.clone(), height)
+            );
+            let val_state = self
+                .val_set
+                .active_validator_at_height(ctx.deps.storage, &validator, height)?
+                .ok_or_else(|| ContractError::ValidatorNotActiveAt(validator.clone(), height))?;
+            self.slashed_infractions
+                .save(ctx.deps.storage, infraction, &())?;
+
+            let cfg = self.config.load(ctx.deps.storage)?;
+            let slash_ratio = match evidence_type {
+                EvidenceType::DoubleSign => cfg.slash_ratio.double_sign,
+            };
+            let total_stake = self
+                .stakes
+                .stake
+                .idx
+                .rev
+                .sub_prefix(validator.clone())
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, stake)| stake.stake.high()))
+                .sum::<StdResult<Uint128>>()?;
+
+            // The infraction time is not known, so the start of the validator state it happened
+            // in is used. This may slash some more pending unbonds, but never less
+            let slash_msg = self.handle_slashing(
+                &ctx.env,
+                ctx.deps.storage,
+                &cfg,
+                &validator,
+                slash_ratio,
+                total_stake * slash_ratio,
+                height,
+                val_state.start_time,
+            )?;
+
+            let resp = Response::new()
+                .add_attribute("action", "submit_evidence")
+                .add_attribute("validator", validator)
+                .add_attribute("height", height.to_string())
+                .add_attribute("evidence_type", format!("{evidence_type:?}"))
+                .add_attribute("proof", proof.to_base64())
+                .add_messages(slash_msg);
+            Ok(resp)
+        }
+    }
+}
+
+pub mod ownership {
+    use super::*;
+    use cosmwasm_std::Empty;
+    use mesh_apis::ownership_api::{Ownership, OwnershipApi};
+
+    #[contract(module=crate::contract)]
+    #[sv::messages(mesh_apis::ownership_api as OwnershipApi)]
+    impl OwnershipApi for ExternalStakingContract<'_> {
+        type Error = ContractError;
+        type ExecC = Empty;
+        type QueryC = Empty;
+
+        #[sv::msg(exec)]
+        fn propose_owner(
+            &self,
+            ctx: ExecCtx,
+            new_owner: String,
+            expiry: Option<Timestamp>,
+        ) -> Result<Response, Self::Error> {
+            nonpayable(&ctx.info)?;
+
+            let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+            let ownership = ownership_api::propose_owner(
+                ctx.deps.storage,
+                &ctx.env.block,
+                &ctx.info.sender,
+                new_owner,
+                expiry,
+            )?;
+
+            let resp = Response::new()
+                .add_attribute("action", "propose_owner")
+                .add_attributes(ownership.into_attributes());
+            Ok(resp)
+        }
+
+        #[sv::msg(exec)]
+        fn accept_owner(&self, ctx: ExecCtx) -> Result<Response, Self::Error> {
+            nonpayable(&ctx.info)?;
+
+            let ownership =
+                ownership_api::accept_owner(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+            let resp = Response::new()
+                .add_attribute("action", "accept_owner")
+                .add_attributes(ownership.into_attributes());
+            Ok(resp)
+        }
+
+        #[sv::msg(exec)]
+        fn renounce_owner(&self, ctx: ExecCtx) -> Result<Response, Self::Error> {
+            nonpayable(&ctx.info)?;
+
+            let ownership = ownership_api::renounce_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+            let resp = Response::new()
+                .add_attribute("action", "renounce_owner")
+                .add_attributes(ownership.into_attributes());
+            Ok(resp)
+        }
+
+        #[sv::msg(query)]
+        fn ownership(&self, ctx: QueryCtx) -> Result<Ownership, Self::Error> {
+            Ok(ownership_api::get_ownership(ctx.deps.storage)?)
+        }
     }
 }
 
@@ -1448,7 +5024,7 @@ pub mod cross_staking {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::{Attribute, Decimal, DepsMut};
+    use cosmwasm_std::{to_json_binary, Attribute, Decimal, DepsMut};
 
     use crate::crdt::State;
     use crate::msg::{AuthorizedEndpoint, ReceiveVirtualStake, ValidatorState};
@@ -1482,6 +5058,10 @@ mod tests {
                     double_sign: Decimal::percent(10),
                     offline: Decimal::percent(10),
                 },
+                None,
+                0,
+                None,
+                None,
             )
             .unwrap();
         let exec_ctx = ExecCtx {
@@ -1508,14 +5088,26 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "carl".to_string(),
                 pub_key: "carl_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
         let tombs = vec!["bob".to_string()];
@@ -1560,15 +5152,21 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Tombstoned {}
+                    state: State::Tombstoned {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "carl".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 }
             ]
         );
@@ -1584,10 +5182,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -1621,10 +5227,7 @@ mod tests {
                 OWNER.to_string(),
                 coin(100, "uosmo"),
                 1,
-                to_json_binary(&ReceiveVirtualStake {
-                    validator: "bob".to_string(),
-                })
-                .unwrap(),
+                ReceiveVirtualStake::new("bob").encode().unwrap(),
             )
             .unwrap();
         // Commit stake
@@ -1695,16 +5298,310 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Tombstoned {}
+                    state: State::Tombstoned {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
     }
 
+    #[test]
+    fn submitting_slash_evidence() {
+        use cosmwasm_std::Binary;
+        use mesh_apis::slash_evidence_api::{EvidenceType, SlashEvidenceApi};
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        let adds = vec![AddValidator {
+            valoper: "bob".to_string(),
+            pub_key: "bob_pub_key".to_string(),
+            self_stake: None,
+            max_external_stake: None,
+            commission: None,
+            moniker: None,
+        }];
+        contract
+            .valset_update(
+                ctx.deps.branch(),
+                ctx.env.clone(),
+                100,
+                1234,
+                &adds,
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+            )
+            .unwrap();
+
+        // Cross stake with bob
+        let stake_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info("vault_addr", &[]),
+        };
+        contract
+            .receive_virtual_stake(
+                stake_ctx,
+                OWNER.to_string(),
+                coin(100, OSMO),
+                1,
+                ReceiveVirtualStake::new("bob").encode().unwrap(),
+            )
+            .unwrap();
+        contract.commit_stake(ctx.deps.branch(), 1).unwrap();
+
+        let mut submit = |sender: &str, height: u64| {
+            let submit_ctx = ExecCtx {
+                deps: ctx.deps.branch(),
+                env: mock_env(),
+                info: mock_info(sender, &[]),
+            };
+            contract.submit_evidence(
+                submit_ctx,
+                "bob".to_string(),
+                height,
+                EvidenceType::DoubleSign,
+                Binary::from(b"proof".as_slice()),
+            )
+        };
+
+        // Only the owner can submit evidence
+        let err = submit(OWNER, 150).unwrap_err();
+        assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+        // Bob was not active yet
+        let err = submit(CREATOR, 50).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ValidatorNotActiveAt("bob".to_string(), 50)
+        );
+
+        let resp = submit(CREATOR, 150).unwrap();
+        assert_eq!(resp.messages.len(), 1);
+        assert_eq!(
+            resp.messages[0].msg,
+            WasmMsg::Execute {
+                contract_addr: "vault_addr".to_string(),
+                msg: to_json_binary(&CrossSlash {
+                    slashes: vec![SlashInfo {
+                        user: OWNER.to_string(),
+                        slash: Uint128::new(10),
+                    }],
+                    validator: "bob".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }
+            .into()
+        );
+
+        // The same infraction can't be slashed twice
+        let err = submit(CREATOR, 150).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InfractionAlreadySlashed("bob".to_string(), 150)
+        );
+
+        // Neither when reported by the consumer later on
+        let (_, msgs) = contract
+            .valset_update(
+                ctx.deps.branch(),
+                ctx.env.clone(),
+                200,
+                2345,
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[ValidatorSlashInfo {
+                    address: "bob".to_string(),
+                    infraction_height: 150,
+                    infraction_time: 1500,
+                    power: 100,
+                    slash_amount: coin(9, OSMO),
+                    slash_ratio: Decimal::percent(10).to_string(),
+                }],
+            )
+            .unwrap();
+        assert!(msgs.is_empty());
+    }
+
+    #[test]
+    fn slashing_in_batches() {
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        let adds = vec![AddValidator {
+            valoper: "bob".to_string(),
+            pub_key: "bob_pub_key".to_string(),
+            self_stake: None,
+            max_external_stake: None,
+            commission: None,
+            moniker: None,
+        }];
+        contract
+            .valset_update(
+                ctx.deps.branch(),
+                ctx.env.clone(),
+                100,
+                1234,
+                &adds,
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+            )
+            .unwrap();
+
+        // One more user than can be slashed at once cross stakes with bob
+        let users: Vec<_> = (0..=SLASH_BATCH).map(|i| format!("user{i:02}")).collect();
+        for (tx_id, user) in (1..).zip(&users) {
+            let stake_ctx = ExecCtx {
+                deps: ctx.deps.branch(),
+                env: mock_env(),
+                info: mock_info("vault_addr", &[]),
+            };
+            contract
+                .receive_virtual_stake(
+                    stake_ctx,
+                    user.clone(),
+                    coin(100, OSMO),
+                    tx_id,
+                    ReceiveVirtualStake::new("bob").encode().unwrap(),
+                )
+                .unwrap();
+            contract.commit_stake(ctx.deps.branch(), tx_id).unwrap();
+        }
+
+        let (_, msgs) = contract
+            .valset_update(
+                ctx.deps.branch(),
+                ctx.env.clone(),
+                200,
+                2345,
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[ValidatorSlashInfo {
+                    address: "bob".to_string(),
+                    infraction_height: 150,
+                    infraction_time: 1500,
+                    power: 100,
+                    slash_amount: coin(10 * users.len() as u128, OSMO),
+                    slash_ratio: Decimal::percent(10).to_string(),
+                }],
+            )
+            .unwrap();
+
+        // Only the first batch is slashed
+        let slash_msg = |users: &[String]| WasmMsg::Execute {
+            contract_addr: "vault_addr".to_string(),
+            msg: to_json_binary(&CrossSlash {
+                slashes: users
+                    .iter()
+                    .map(|user| SlashInfo {
+                        user: user.clone(),
+                        slash: Uint128::new(10),
+                    })
+                    .collect(),
+                validator: "bob".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        };
+        assert_eq!(msgs, vec![slash_msg(&users[..SLASH_BATCH])]);
+
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let pending = contract
+            .pending_slashes(query_ctx, "bob".to_string(), None, None)
+            .unwrap()
+            .slashes;
+        assert_eq!(
+            pending,
+            vec![PendingSlashInfo {
+                infraction_height: 150,
+                slash_ratio: Decimal::percent(10),
+                effective_slash_ratio: Decimal::percent(10),
+                last_user: Some(users[SLASH_BATCH - 1].clone()),
+            }]
+        );
+
+        // Stakes on bob can't be changed until the slashing is complete
+        let unstake_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(&users[SLASH_BATCH], &[]),
+        };
+        let err = contract
+            .unstake(unstake_ctx, "bob".to_string(), coin(10, OSMO), None)
+            .unwrap_err();
+        assert_eq!(err, ContractError::SlashingInProgress("bob".to_string()));
+
+        let continue_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(OWNER, &[]),
+        };
+        let resp = contract
+            .continue_slashing(continue_ctx, "bob".to_string())
+            .unwrap();
+        assert_eq!(resp.messages.len(), 1);
+        assert_eq!(
+            resp.messages[0].msg,
+            slash_msg(&users[SLASH_BATCH..]).into()
+        );
+        assert!(!resp.attributes.iter().any(|attr| attr.key == "remaining"));
+
+        let continue_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(OWNER, &[]),
+        };
+        let err = contract
+            .continue_slashing(continue_ctx, "bob".to_string())
+            .unwrap_err();
+        assert_eq!(err, ContractError::NoPendingSlash("bob".to_string()));
+
+        // All of the stakes are slashed, and can be changed again
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let stake = contract
+            .stake(query_ctx, users[SLASH_BATCH].clone(), "bob".to_string())
+            .unwrap();
+        assert_eq!(stake.stake.high().u128(), 90);
+        let unstake_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(&users[SLASH_BATCH], &[]),
+        };
+        contract
+            .unstake(unstake_ctx, "bob".to_string(), coin(10, OSMO), None)
+            .unwrap();
+    }
+
     #[test]
     fn valset_update_tombstoning_and_slashing_pending_bond() {
         let mut deps = mock_dependencies();
@@ -1715,10 +5612,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -1752,10 +5657,7 @@ mod tests {
                 OWNER.to_string(),
                 coin(100, "uosmo"),
                 1,
-                to_json_binary(&ReceiveVirtualStake {
-                    validator: "bob".to_string(),
-                })
-                .unwrap(),
+                ReceiveVirtualStake::new("bob").encode().unwrap(),
             )
             .unwrap();
         // Stake tx is pending
@@ -1825,11 +5727,15 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Tombstoned {}
+                    state: State::Tombstoned {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
@@ -1845,10 +5751,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -1882,10 +5796,7 @@ mod tests {
                 OWNER.to_string(),
                 coin(100, "uosmo"),
                 1,
-                to_json_binary(&ReceiveVirtualStake {
-                    validator: "bob".to_string(),
-                })
-                .unwrap(),
+                ReceiveVirtualStake::new("bob").encode().unwrap(),
             )
             .unwrap();
         // Commit stake
@@ -1900,7 +5811,7 @@ mod tests {
             info: owner_info,
         };
         contract
-            .unstake(stake_ctx, "bob".to_string(), coin(50, "uosmo"))
+            .unstake(stake_ctx, "bob".to_string(), coin(50, "uosmo"), None)
             .unwrap();
         // Unstake tx is pending
 
@@ -1969,11 +5880,15 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Tombstoned {}
+                    state: State::Tombstoned {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
@@ -1989,10 +5904,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -2050,11 +5973,15 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Tombstoned {}
+                    state: State::Tombstoned {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
@@ -2072,10 +5999,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -2132,15 +6067,21 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "carl".to_string(),
-                    state: State::Tombstoned {}
+                    state: State::Tombstoned {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
@@ -2156,10 +6097,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -2239,11 +6188,15 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Jailed {}
+                    state: State::Jailed {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
@@ -2259,10 +6212,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -2296,10 +6257,7 @@ mod tests {
                 OWNER.to_string(),
                 coin(100, "uosmo"),
                 1,
-                to_json_binary(&ReceiveVirtualStake {
-                    validator: "bob".to_string(),
-                })
-                .unwrap(),
+                ReceiveVirtualStake::new("bob").encode().unwrap(),
             )
             .unwrap();
         // Commit stake
@@ -2370,11 +6328,15 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Jailed {}
+                    state: State::Jailed {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
@@ -2390,10 +6352,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -2450,16 +6420,126 @@ mod tests {
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Unbonded {}
+                    state: State::Unbonded {},
+                    commission: None,
+                    moniker: None,
                 },
             ]
         );
     }
 
+    #[test]
+    fn valset_update_removal_grace_period() {
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+        contract
+            .config
+            .update(ctx.deps.storage, |mut cfg| -> StdResult<_> {
+                cfg.removal_grace_period = 100;
+                Ok(cfg)
+            })
+            .unwrap();
+
+        let update = |deps: DepsMut,
+                      env: Env,
+                      height: u64,
+                      additions: &[AddValidator],
+                      removals: &[String]| {
+            contract
+                .valset_update(
+                    deps,
+                    env,
+                    height,
+                    height * 10,
+                    additions,
+                    removals,
+                    &[],
+                    &[],
+                    &[],
+                    &[],
+                    &[],
+                )
+                .unwrap()
+                .0
+        };
+        let alice = AddValidator::mock("alice");
+        let removals = ["alice".to_string()];
+        let owner = Addr::unchecked(OWNER);
+
+        update(
+            ctx.deps.branch(),
+            ctx.env.clone(),
+            100,
+            &[alice.clone()],
+            &[],
+        );
+        let evt = update(ctx.deps.branch(), ctx.env.clone(), 200, &[], &removals);
+        assert_eq!(
+            evt.attributes,
+            vec![
+                Attribute::new("removals", "alice"),
+                Attribute::new("leaving", "alice")
+            ]
+        );
+
+        // Still in the set, but not receiving new stakes
+        let state = contract.val_set.validator_state(ctx.deps.storage, "alice");
+        assert_eq!(state.unwrap(), State::Active {});
+        let err = contract
+            .prepare_stake(ctx.deps.storage, &owner, "alice", Uint128::new(100))
+            .unwrap_err();
+        assert_eq!(err, ContractError::ValidatorLeaving("alice".to_string()));
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let leaving = contract.leaving_validators(query_ctx, None, None).unwrap();
+        assert_eq!(
+            leaving.validators,
+            vec![LeavingValidatorInfo {
+                validator: "alice".to_string(),
+                finalize_at: ctx.env.block.time.plus_seconds(100),
+            }]
+        );
+
+        // Back before the end of the grace period
+        update(ctx.deps.branch(), ctx.env.clone(), 300, &[alice], &[]);
+        contract
+            .prepare_stake(ctx.deps.storage, &owner, "alice", Uint128::new(100))
+            .unwrap();
+
+        // Removed again, and for good once the grace period is over
+        update(ctx.deps.branch(), ctx.env.clone(), 400, &[], &removals);
+        ctx.env.block.time = ctx.env.block.time.plus_seconds(100);
+        let finalize_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: ctx.env.clone(),
+            info: mock_info("anyone", &[]),
+        };
+        let resp = contract.finalize_removals(finalize_ctx).unwrap();
+        assert_eq!(
+            resp.attributes,
+            vec![
+                Attribute::new("action", "finalize_removals"),
+                Attribute::new("removed", "alice")
+            ]
+        );
+        let state = contract.val_set.validator_state(ctx.deps.storage, "alice");
+        assert_eq!(state.unwrap(), State::Unbonded {});
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let leaving = contract.leaving_validators(query_ctx, None, None).unwrap();
+        assert_eq!(leaving.validators, vec![]);
+    }
+
     #[test]
     fn valset_update_updates_keep_state() {
         let mut deps = mock_dependencies();
@@ -2470,10 +6550,18 @@ mod tests {
             AddValidator {
                 valoper: "alice".to_string(),
                 pub_key: "alice_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
             AddValidator {
                 valoper: "bob".to_string(),
                 pub_key: "bob_pub_key".to_string(),
+                self_stake: None,
+                max_external_stake: None,
+                commission: None,
+                moniker: None,
             },
         ];
 
@@ -2520,6 +6608,10 @@ mod tests {
         let upds = vec![AddValidator {
             valoper: "bob".to_string(),
             pub_key: "bob_pub_key_updated".to_string(),
+            self_stake: None,
+            max_external_stake: None,
+            commission: Some(Decimal::percent(10)),
+            moniker: Some("Bob".to_string()),
         }];
         let (evt, _msgs) = contract
             .valset_update(
@@ -2546,20 +6638,637 @@ mod tests {
             env: mock_env(),
         };
 
-        // Check that bob is still unbonded
+        // Check that bob is still unbonded, with the updated metadata
         let vals = contract.list_validators(query_ctx, None, None).unwrap();
         assert_eq!(
             vals.validators,
             vec![
                 ValidatorState {
                     validator: "alice".to_string(),
-                    state: State::Active {}
+                    state: State::Active {},
+                    commission: None,
+                    moniker: None,
                 },
                 ValidatorState {
                     validator: "bob".to_string(),
-                    state: State::Unbonded {}
+                    state: State::Unbonded {},
+                    commission: Some(Decimal::percent(10)),
+                    moniker: Some("Bob".to_string()),
                 },
             ]
         );
     }
+
+    #[test]
+    fn validator_health_warnings() {
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use mesh_apis::ibc::ConsumerPacket;
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        fn owner_ctx<'a>(ctx: &'a mut ExecCtx<'_>) -> ExecCtx<'a> {
+            ExecCtx {
+                deps: ctx.deps.branch(),
+                env: ctx.env.clone(),
+                info: mock_info(CREATOR, &[]),
+            }
+        }
+        let err = contract
+            .set_min_uptime(owner_ctx(&mut ctx), Some(Decimal::percent(101)))
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidMinUptime);
+        contract
+            .set_min_uptime(owner_ctx(&mut ctx), Some(Decimal::percent(90)))
+            .unwrap();
+
+        // A staker, and a user who unstaked everything
+        let stakes = [("user1", 100), ("user2", 0)];
+        for (user, amount) in stakes {
+            contract
+                .stakes
+                .stake
+                .save(
+                    ctx.deps.storage,
+                    (&Addr::unchecked(user), "alice"),
+                    &Stake::from_amount(Uint128::new(amount)),
+                )
+                .unwrap();
+        }
+
+        let uptime = |height: u64, missed_blocks: u64| ConsumerPacket::ValsetUpdate {
+            height,
+            time: height * 10,
+            additions: vec![],
+            removals: vec![],
+            updated: vec![],
+            jailed: vec![],
+            unjailed: vec![],
+            tombstoned: vec![],
+            slashed: vec![],
+            uptimes: vec![ValidatorUptime {
+                address: "alice".to_string(),
+                missed_blocks,
+                window: 1000,
+            }],
+            chunk: None,
+        };
+        let warnings = |ctx: &mut ExecCtx, sequence: u64, packet: ConsumerPacket| {
+            let mut msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+            msg.packet.sequence = sequence;
+            crate::ibc::ibc_packet_receive(ctx.deps.branch(), ctx.env.clone(), msg)
+                .unwrap()
+                .events
+                .into_iter()
+                .filter(|evt| evt.ty == "validator_health_warning")
+                .collect::<Vec<_>>()
+        };
+
+        // Above the min uptime
+        assert!(warnings(&mut ctx, 1, uptime(100, 50)).is_empty());
+
+        // The stakers are warned once the validator drops below it
+        assert_eq!(
+            warnings(&mut ctx, 2, uptime(200, 200)),
+            [Event::new("validator_health_warning")
+                .add_attribute("validator", "alice")
+                .add_attribute("delegator", "user1")
+                .add_attribute("uptime", "0.8")
+                .add_attribute("min_uptime", "0.9")]
+        );
+        // But only once
+        assert!(warnings(&mut ctx, 3, uptime(300, 300)).is_empty());
+
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let health = contract
+            .validator_health(query_ctx, "alice".to_string())
+            .unwrap();
+        assert_eq!(
+            health,
+            ValidatorHealthResponse {
+                validator: "alice".to_string(),
+                health: Some(ValidatorHealth {
+                    missed_blocks: 300,
+                    window: 1000,
+                    uptime: Decimal::percent(70),
+                    height: 300,
+                    time: 3000,
+                }),
+                min_uptime: Some(Decimal::percent(90)),
+                below_min: true,
+            }
+        );
+
+        // Until it recovers
+        assert!(warnings(&mut ctx, 4, uptime(400, 0)).is_empty());
+        assert_eq!(warnings(&mut ctx, 5, uptime(500, 150)).len(), 1);
+    }
+
+    #[test]
+    fn redelivered_packet_is_not_reapplied() {
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use mesh_apis::ibc::ConsumerPacket;
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        let packet = ConsumerPacket::ValsetUpdate {
+            height: 100,
+            time: 1234,
+            additions: vec![AddValidator::mock("alice")],
+            removals: vec![],
+            updated: vec![],
+            jailed: vec![],
+            unjailed: vec![],
+            tombstoned: vec![],
+            slashed: vec![],
+            uptimes: vec![],
+            chunk: None,
+        };
+        let msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+        let sequence = msg.packet.sequence;
+
+        let resp = crate::ibc::ibc_packet_receive(ctx.deps.branch(), ctx.env.clone(), msg.clone())
+            .unwrap();
+        // The valset update, and the gap before the mock packet sequence
+        assert_eq!(resp.events.len(), 2);
+        let ack = resp.acknowledgement;
+
+        // Same packet delivered again is acked, but not processed
+        let resp = crate::ibc::ibc_packet_receive(ctx.deps.branch(), ctx.env.clone(), msg).unwrap();
+        assert!(resp.events.is_empty());
+        assert_eq!(resp.acknowledgement, ack);
+        assert_eq!(
+            resp.attributes,
+            vec![
+                Attribute::new("action", "duplicate_packet"),
+                Attribute::new("packet_type", "valset_update"),
+                Attribute::new("sequence", sequence.to_string()),
+            ]
+        );
+
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let processed = contract
+            .processed_packets(query_ctx, "channel-172".to_string(), None, None)
+            .unwrap()
+            .packets;
+        assert_eq!(
+            processed,
+            vec![ProcessedPacketInfo {
+                sequence,
+                packet_type: "valset_update".to_string(),
+                height: ctx.env.block.height,
+                time: ctx.env.block.time,
+            }]
+        );
+    }
+
+    #[test]
+    fn chunked_valset_update_is_applied_as_received() {
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use mesh_apis::ibc::ConsumerPacket;
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        // 520 validators, sent in chunks of 200
+        let valopers: Vec<String> = (0..520).map(|i| format!("valoper{i:03}")).collect();
+        let chunk = |index: u32, valopers: &[String]| ConsumerPacket::ValsetUpdate {
+            height: 100,
+            time: 1234,
+            additions: valopers.iter().map(|v| AddValidator::mock(v)).collect(),
+            removals: vec![],
+            updated: vec![],
+            jailed: vec![],
+            unjailed: vec![],
+            tombstoned: vec![],
+            slashed: vec![],
+            uptimes: vec![],
+            chunk: Some(ValsetChunk { index, total: 3 }),
+        };
+        let receive = |ctx: &mut ExecCtx, sequence: u64, packet: ConsumerPacket| {
+            let mut msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+            msg.packet.sequence = sequence;
+            crate::ibc::ibc_packet_receive(ctx.deps.branch(), ctx.env.clone(), msg).unwrap()
+        };
+        let chunk_event = |index: u32, received: u32, complete: bool| {
+            Event::new("valset_update_chunk")
+                .add_attribute("height", "100")
+                .add_attribute("index", index.to_string())
+                .add_attribute("total", "3")
+                .add_attribute("received", received.to_string())
+                .add_attribute("complete", complete.to_string())
+        };
+
+        // Every chunk is applied on its own
+        let resp = receive(&mut ctx, 1, chunk(0, &valopers[..200]));
+        assert_eq!(resp.events[1], chunk_event(0, 1, false));
+        let resp = receive(&mut ctx, 2, chunk(1, &valopers[200..400]));
+        assert_eq!(resp.events[1], chunk_event(1, 2, false));
+        let active = contract
+            .val_set
+            .list_active_validators(ctx.deps.as_ref().storage, None, 1000)
+            .unwrap();
+        assert_eq!(active, valopers[..400]);
+        assert_eq!(
+            contract
+                .valset_chunks
+                .load(ctx.deps.as_ref().storage, 100)
+                .unwrap(),
+            2
+        );
+
+        // Until the update is complete
+        let resp = receive(&mut ctx, 3, chunk(2, &valopers[400..]));
+        assert_eq!(resp.events[1], chunk_event(2, 3, true));
+        let active = contract
+            .val_set
+            .list_active_validators(ctx.deps.as_ref().storage, None, 1000)
+            .unwrap();
+        assert_eq!(active, valopers);
+        assert!(!contract.valset_chunks.has(ctx.deps.as_ref().storage, 100));
+    }
+
+    #[test]
+    fn sequence_gaps_are_tracked() {
+        use cosmwasm_std::testing::mock_ibc_packet_recv;
+        use mesh_apis::ibc::ConsumerPacket;
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        let packet = ConsumerPacket::MaxCapUpdate {
+            max_cap: coin(100, OSMO),
+            unbonds: vec![],
+        };
+        let mut receive = |sequence| {
+            let mut msg = mock_ibc_packet_recv("channel-172", &packet).unwrap();
+            msg.packet.sequence = sequence;
+            crate::ibc::ibc_packet_receive(ctx.deps.branch(), ctx.env.clone(), msg).unwrap()
+        };
+
+        receive(1);
+        let resp = receive(5);
+        assert_eq!(resp.events.len(), 2);
+        assert_eq!(
+            resp.events[1],
+            Event::new("packet_gap")
+                .add_attribute("channel_id", "channel-172")
+                .add_attribute("first_missing", "2")
+                .add_attribute("last_missing", "4")
+        );
+        // Late packet splits the gap
+        let resp = receive(3);
+        assert_eq!(resp.events.len(), 1);
+
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let missing = contract
+            .missing_sequences(query_ctx, "channel-172".to_string(), None, None)
+            .unwrap();
+        assert_eq!(
+            missing,
+            MissingSequencesResponse {
+                last_received: 5,
+                missing: vec![
+                    SequenceRange { first: 2, last: 2 },
+                    SequenceRange { first: 4, last: 4 },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn timed_out_packet_is_retried() {
+        use cosmwasm_std::testing::mock_ibc_packet_timeout;
+        use cosmwasm_std::{CosmosMsg, SubMsg};
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        let packet = ProviderPacket::Burn {
+            validators: vec!["alice".to_string()],
+            burn: coin(100, OSMO),
+        };
+        let msg = mock_ibc_packet_timeout("channel-172", &packet).unwrap();
+        let sequence = msg.packet.sequence;
+
+        let resp = crate::ibc::ibc_packet_timeout(ctx.deps.branch(), ctx.env.clone(), msg).unwrap();
+        assert_eq!(
+            resp.attributes,
+            vec![
+                Attribute::new("action", "ibc_packet_timeout"),
+                Attribute::new("error", "timeout"),
+                Attribute::new("packet_type", "burn"),
+                Attribute::new("sequence", sequence.to_string()),
+            ]
+        );
+
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let pending = contract
+            .pending_packets(query_ctx, None, None)
+            .unwrap()
+            .packets;
+        assert_eq!(
+            pending,
+            vec![PendingPacketInfo {
+                sequence,
+                packet: packet.clone(),
+                timed_out_at: ctx.env.block.time,
+            }]
+        );
+
+        // Anyone can send the timed out packets again
+        let retry_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: ctx.env.clone(),
+            info: mock_info("relayer", &[]),
+        };
+        let resp = contract.retry_packets(retry_ctx).unwrap();
+        assert_eq!(
+            resp.messages,
+            vec![SubMsg::new(CosmosMsg::Ibc(IbcMsg::SendPacket {
+                channel_id: "channel-172".to_string(),
+                data: packet.encode(crate::ibc::SUPPORTED_FEATURES).unwrap(),
+                timeout: packet_timeout(&ctx.env, &packet),
+            }))]
+        );
+
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let pending = contract
+            .pending_packets(query_ctx, None, None)
+            .unwrap()
+            .packets;
+        assert_eq!(pending, vec![]);
+    }
+
+    #[test]
+    fn channel_close_and_reopen() {
+        use crate::ibc::{ibc_channel_close, ibc_channel_connect, ibc_channel_open};
+        use cosmwasm_std::testing::mock_ibc_channel;
+        use cosmwasm_std::{IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcOrder};
+        use mesh_apis::ibc::{ProtocolVersion, PROTOCOL_NAME};
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        // Channel from the authorized endpoint
+        let mock_channel = |channel_id| {
+            let mut channel = mock_ibc_channel(channel_id, IbcOrder::Unordered, "mesh-security");
+            channel.connection_id = "connection_id_1".to_string();
+            channel.counterparty_endpoint.port_id = "port_id_1".to_string();
+            channel
+        };
+        let channel = mock_channel("channel-172");
+        crate::ibc::IBC_CHANNEL
+            .save(ctx.deps.storage, &channel)
+            .unwrap();
+
+        contract
+            .valset_update(
+                ctx.deps.branch(),
+                ctx.env.clone(),
+                100,
+                1234,
+                &[AddValidator::mock("bob")],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+                &[],
+            )
+            .unwrap();
+        let stake = |deps: DepsMut, tx_id| {
+            let stake_ctx = ExecCtx {
+                deps,
+                env: mock_env(),
+                info: mock_info("vault_addr", &[]),
+            };
+            contract.receive_virtual_stake(
+                stake_ctx,
+                OWNER.to_string(),
+                coin(100, OSMO),
+                tx_id,
+                ReceiveVirtualStake::new("bob").encode().unwrap(),
+            )
+        };
+        stake(ctx.deps.branch(), 1).unwrap();
+        contract.commit_stake(ctx.deps.branch(), 1).unwrap();
+
+        // The channel can't be closed from our side
+        let err = ibc_channel_close(
+            ctx.deps.branch(),
+            ctx.env.clone(),
+            IbcChannelCloseMsg::new_init(channel.clone()),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::IbcCloseInitDisallowed);
+
+        ibc_channel_close(
+            ctx.deps.branch(),
+            ctx.env.clone(),
+            IbcChannelCloseMsg::new_confirm(channel.clone()),
+        )
+        .unwrap();
+
+        // Staking is frozen, but the existing stake is kept
+        let err = stake(ctx.deps.branch(), 2).unwrap_err();
+        assert_eq!(err, ContractError::Mesh(MeshError::ChannelNotOpen));
+        let query_ctx = QueryCtx {
+            deps: ctx.deps.as_ref(),
+            env: mock_env(),
+        };
+        let owner_stake = contract
+            .stake(query_ctx, OWNER.to_string(), "bob".to_string())
+            .unwrap();
+        assert_eq!(owner_stake.stake.high().u128(), 100);
+
+        // A new channel from the same connection and port
+        let mut new_channel = mock_channel("channel-173");
+        let version = ProtocolVersion::new(PROTOCOL_NAME, "0.11.0")
+            .to_string()
+            .unwrap();
+        new_channel.version = version.clone();
+        let open = || IbcChannelOpenMsg::new_try(new_channel.clone(), version.clone());
+
+        // Re-opening must be approved by the owner first
+        let err = ibc_channel_open(ctx.deps.branch(), ctx.env.clone(), open()).unwrap_err();
+        assert_eq!(err, ContractError::IbcReopenNotApproved);
+        let approve_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(OWNER, &[]),
+        };
+        let err = contract.approve_channel_reopen(approve_ctx).unwrap_err();
+        assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+        let approve_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(CREATOR, &[]),
+        };
+        contract.approve_channel_reopen(approve_ctx).unwrap();
+
+        ibc_channel_open(ctx.deps.branch(), ctx.env.clone(), open()).unwrap();
+        ibc_channel_connect(
+            ctx.deps.branch(),
+            ctx.env.clone(),
+            IbcChannelConnectMsg::new_confirm(new_channel.clone()),
+        )
+        .unwrap();
+        assert_eq!(load_channel(ctx.deps.storage).unwrap(), new_channel);
+
+        // Nothing left to approve, and staking resumes on the new channel
+        let approve_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info(CREATOR, &[]),
+        };
+        let err = contract.approve_channel_reopen(approve_ctx).unwrap_err();
+        assert_eq!(err, ContractError::IbcChannelNotClosed);
+        stake(ctx.deps.branch(), 2).unwrap();
+    }
+
+    #[test]
+    fn channel_features_negotiation() {
+        use crate::ibc::{ibc_channel_connect, ibc_channel_open, IBC_CHANNEL};
+        use crate::msg::{BatchStake, ReceiveVirtualStakeMsg};
+        use cosmwasm_std::testing::mock_ibc_channel;
+        use cosmwasm_std::{from_json, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcOrder};
+        use mesh_apis::ibc::ProtocolVersion;
+
+        let mut deps = mock_dependencies();
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+        IBC_CHANNEL.remove(ctx.deps.storage);
+
+        let mut channel = mock_ibc_channel("channel-173", IbcOrder::Unordered, "mesh-security");
+        channel.connection_id = "connection_id_1".to_string();
+        channel.counterparty_endpoint.port_id = "port_id_1".to_string();
+
+        // Consumer proposes batch rewards, max cap updates, and a feature we don't know about
+        let proposed = r#"{"protocol":"mesh-security","version":"0.12.0","features":1029}"#;
+        let resp = ibc_channel_open(
+            ctx.deps.branch(),
+            ctx.env.clone(),
+            IbcChannelOpenMsg::new_try(channel.clone(), proposed),
+        )
+        .unwrap()
+        .unwrap();
+        let version: ProtocolVersion = from_json(resp.version.as_bytes()).unwrap();
+        let negotiated = Features::BATCH_REWARDS.union(Features::MAX_CAP_UPDATE);
+        assert_eq!(version.version, "0.12.0");
+        assert_eq!(version.features, negotiated);
+
+        channel.version = resp.version;
+        ibc_channel_connect(
+            ctx.deps.branch(),
+            ctx.env.clone(),
+            IbcChannelConnectMsg::new_confirm(channel),
+        )
+        .unwrap();
+        assert_eq!(channel_features(ctx.deps.storage).unwrap(), negotiated);
+
+        // Batch stakes are not supported by the consumer
+        let stake_ctx = ExecCtx {
+            deps: ctx.deps.branch(),
+            env: mock_env(),
+            info: mock_info("vault_addr", &[]),
+        };
+        let msg = ReceiveVirtualStakeMsg::StakeBatch {
+            stakes: vec![BatchStake {
+                validator: "alice".to_string(),
+                amount: Uint128::new(100),
+            }],
+            idempotency_key: None,
+        };
+        let err = contract
+            .receive_virtual_stake(
+                stake_ctx,
+                OWNER.to_string(),
+                coin(100, OSMO),
+                1,
+                to_json_binary(&msg).unwrap(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::IbcFeatureNotSupported("batch stakes".to_string())
+        );
+    }
+
+    #[test]
+    fn migrating_from_baseline() {
+        use cosmwasm_std::testing::MockStorage;
+        use cosmwasm_std::{ContractInfoResponse, ContractResult, SystemResult, WasmQuery};
+
+        let mut deps = mock_dependencies();
+        let contract = ExternalStakingContract::new();
+
+        // Config and version of the baseline release, without owner
+        deps.storage.set(
+            b"config",
+            br#"{"denom":"uosmo","rewards_denom":"ujuno","vault":"vault_addr","unbonding_period":100,"slash_ratio":{"double_sign":"0.1","offline":"0.1"}}"#,
+        );
+        cw2::set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.10.0-alpha.1").unwrap();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::ContractInfo { .. } => {
+                let mut info = ContractInfoResponse::default();
+                info.code_id = 1;
+                info.creator = CREATOR.to_owned();
+                info.admin = Some("admin".to_owned());
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+            }
+            _ => unimplemented!(),
+        });
+
+        let ctx = MigrateCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+        };
+        let resp = contract.migrate(ctx).unwrap();
+        assert_eq!(
+            resp.events[0].attributes,
+            vec![
+                Attribute::new("from_version", "0.10.0-alpha.1"),
+                Attribute::new("to_version", CONTRACT_VERSION),
+            ]
+        );
+
+        // The wasm admin is the owner now
+        assert_eq!(
+            ownership_api::get_ownership(&deps.storage).unwrap().owner,
+            Some(Addr::unchecked("admin"))
+        );
+        let config = contract.config.load(&deps.storage).unwrap();
+        assert_eq!(config.removal_grace_period, 0);
+        assert!(!config.unstake_batching);
+
+        // Only the same contract can be migrated
+        let mut storage = MockStorage::new();
+        cw2::set_contract_version(&mut storage, "vault", CONTRACT_VERSION).unwrap();
+        deps.storage = storage;
+        let ctx = MigrateCtx {
+            deps: deps.as_mut(),
+            env: mock_env(),
+        };
+        let err = contract.migrate(ctx).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Mesh(MeshError::InvalidContractName("vault".to_owned()))
+        );
+    }
 }
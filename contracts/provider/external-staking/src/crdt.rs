@@ -1,5 +1,5 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Order, StdError, StdResult, Storage};
+use cosmwasm_std::{Decimal, Order, StdError, StdResult, Storage};
 use cw_storage_plus::{Bound, Map};
 use std::cmp::max;
 
@@ -32,6 +32,14 @@ impl ValidatorState {
         }
     }
 
+    /// Latest metadata reported for the validator, if any
+    pub fn get_metadata(&self) -> ValidatorMetadata {
+        self.0
+            .first()
+            .map(|val_state| val_state.metadata.clone())
+            .unwrap_or_default()
+    }
+
     pub fn is_active(&self) -> bool {
         !self.is_empty() && self.0[0].state == State::Active {}
     }
@@ -63,6 +71,19 @@ pub struct ValState {
     pub start_height: u64,
     pub start_time: u64,
     pub state: State,
+    #[serde(default)]
+    pub metadata: ValidatorMetadata,
+}
+
+/// Descriptive validator info relayed by the consumer, for display only.
+/// Kept along the pub key on every state change.
+#[cw_serde]
+#[derive(Default)]
+pub struct ValidatorMetadata {
+    /// Commission rate charged by the validator on the consumer
+    pub commission: Option<Decimal>,
+    /// Human readable name of the validator
+    pub moniker: Option<String>,
 }
 
 #[cw_serde]
@@ -87,6 +108,7 @@ impl ValState {
             start_height,
             start_time,
             state: State::Active {},
+            metadata: ValidatorMetadata::default(),
         }
     }
 }
@@ -105,7 +127,8 @@ impl<'a> CrdtState<'a> {
 
     /// Add a validator and set it to active.
     /// If the validator is tombstoned, this does nothing.
-    /// If the validator already exists, it will be set to `Active`, and its pubkey updated.
+    /// If the validator already exists, it will be set to `Active`, and its pubkey and metadata
+    /// updated.
     /// In test code, this is called from `test_set_active_validator`.
     /// In non-test code, this is called from `ibc_packet_receive`
     pub fn add_validator(
@@ -113,6 +136,7 @@ impl<'a> CrdtState<'a> {
         storage: &mut dyn Storage,
         valoper: &str,
         pub_key: &str,
+        metadata: ValidatorMetadata,
         height: u64,
         time: u64,
     ) -> Result<(), StdError> {
@@ -127,6 +151,7 @@ impl<'a> CrdtState<'a> {
                 start_height: height,
                 start_time: time,
                 state: State::Active {},
+                metadata,
             };
             validator_state.insert_unique(val_state);
             self.validators.save(storage, valoper, &validator_state)?;
@@ -142,6 +167,7 @@ impl<'a> CrdtState<'a> {
         storage: &mut dyn Storage,
         valoper: &str,
         pub_key: &str,
+        metadata: ValidatorMetadata,
         height: u64,
         time: u64,
     ) -> Result<(), StdError> {
@@ -164,6 +190,7 @@ impl<'a> CrdtState<'a> {
                 start_height: height,
                 start_time: time,
                 state: old.state,
+                metadata,
             };
             validator_state.insert_unique(val_state);
             self.validators.save(storage, valoper, &validator_state)?;
@@ -200,6 +227,7 @@ impl<'a> CrdtState<'a> {
                 start_height: height,
                 start_time: time,
                 state: State::Unbonded {},
+                metadata: old.metadata,
             };
             validator_state.insert_unique(val_state);
             self.validators.save(storage, valoper, &validator_state)?;
@@ -236,6 +264,7 @@ impl<'a> CrdtState<'a> {
                 start_height: height,
                 start_time: time,
                 state: State::Jailed {},
+                metadata: old.metadata,
             };
             validator_state.insert_unique(val_state);
             self.validators.save(storage, valoper, &validator_state)?;
@@ -262,12 +291,13 @@ impl<'a> CrdtState<'a> {
             // Drain events that are newer than `height` (this is the final registered event)
             validator_state.drain_newer(height);
 
-            // Insert tombstoning
+            // Insert tombstoning, keeping the latest metadata for display
             let val_state = ValState {
                 pub_key: "".to_string(), // FIXME? Keep pubkey
                 start_height: height,
                 start_time: time,
                 state: State::Tombstoned {},
+                metadata: validator_state.get_metadata(),
             };
             validator_state.insert_unique(val_state);
 
@@ -316,18 +346,19 @@ impl<'a> CrdtState<'a> {
             .collect()
     }
 
-    /// This returns the valoper address and latest state of all validators we are aware of
+    /// This returns the valoper address, latest state and metadata of all validators we are
+    /// aware of
     pub fn list_validators(
         &self,
         storage: &dyn Storage,
         start_after: Option<&str>,
         limit: usize,
-    ) -> StdResult<Vec<(String, State)>> {
+    ) -> StdResult<Vec<(String, State, ValidatorMetadata)>> {
         let start = start_after.map(Bound::exclusive);
         self.validators
             .range(storage, start, None, Order::Ascending)
             .map(|r| match r {
-                Ok((valoper, state)) => Ok((valoper, state.get_state())),
+                Ok((valoper, state)) => Ok((valoper, state.get_state(), state.get_metadata())),
                 Err(e) => Err(e),
             })
             .take(limit)
@@ -416,12 +447,33 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "alice_pub_key", 123, 1234)
-            .unwrap();
-        crdt.add_validator(&mut storage, "bob", "bob_pub_key", 200, 2345)
-            .unwrap();
-        crdt.add_validator(&mut storage, "carl", "carl_pub_key", 303, 3456)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pub_key",
+            ValidatorMetadata::default(),
+            123,
+            1234,
+        )
+        .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "bob",
+            "bob_pub_key",
+            ValidatorMetadata::default(),
+            200,
+            2345,
+        )
+        .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "carl",
+            "carl_pub_key",
+            ValidatorMetadata::default(),
+            303,
+            3456,
+        )
+        .unwrap();
         crdt.tombstone_validator(&mut storage, "bob", 201, 2346)
             .unwrap();
 
@@ -436,9 +488,21 @@ mod tests {
         assert_eq!(
             validators,
             vec![
-                ("alice".to_string(), State::Active {}),
-                ("bob".to_string(), State::Tombstoned {}),
-                ("carl".to_string(), State::Active {}),
+                (
+                    "alice".to_string(),
+                    State::Active {},
+                    ValidatorMetadata::default()
+                ),
+                (
+                    "bob".to_string(),
+                    State::Tombstoned {},
+                    ValidatorMetadata::default()
+                ),
+                (
+                    "carl".to_string(),
+                    State::Active {},
+                    ValidatorMetadata::default()
+                ),
             ]
         );
     }
@@ -451,12 +515,33 @@ mod tests {
 
         crdt.tombstone_validator(&mut storage, "bob", 199, 2344)
             .unwrap();
-        crdt.add_validator(&mut storage, "alice", "pk_a", 123, 1234)
-            .unwrap();
-        crdt.add_validator(&mut storage, "bob", "pk_b", 200, 2345)
-            .unwrap();
-        crdt.add_validator(&mut storage, "carl", "pk_c", 303, 3459)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "pk_a",
+            ValidatorMetadata::default(),
+            123,
+            1234,
+        )
+        .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "bob",
+            "pk_b",
+            ValidatorMetadata::default(),
+            200,
+            2345,
+        )
+        .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "carl",
+            "pk_c",
+            ValidatorMetadata::default(),
+            303,
+            3459,
+        )
+        .unwrap();
 
         assert!(crdt.is_active_validator(&storage, "alice").unwrap());
         assert!(!crdt.is_active_validator(&storage, "bob").unwrap());
@@ -469,9 +554,21 @@ mod tests {
         assert_eq!(
             validators,
             vec![
-                ("alice".to_string(), State::Active {}),
-                ("bob".to_string(), State::Tombstoned {}),
-                ("carl".to_string(), State::Active {}),
+                (
+                    "alice".to_string(),
+                    State::Active {},
+                    ValidatorMetadata::default()
+                ),
+                (
+                    "bob".to_string(),
+                    State::Tombstoned {},
+                    ValidatorMetadata::default()
+                ),
+                (
+                    "carl".to_string(),
+                    State::Active {},
+                    ValidatorMetadata::default()
+                ),
             ]
         );
     }
@@ -485,8 +582,15 @@ mod tests {
         // use two digits so numeric and alphabetic sort match (-2 is after -11, but -02 is before -11)
         let mut validators: Vec<_> = (0..20).map(|i| format!("validator-{:02}", i)).collect();
         for v in &validators {
-            crdt.add_validator(&mut storage, v, &format!("{}-pubkey", v), 123, 1234)
-                .unwrap();
+            crdt.add_validator(
+                &mut storage,
+                v,
+                &format!("{}-pubkey", v),
+                ValidatorMetadata::default(),
+                123,
+                1234,
+            )
+            .unwrap();
         }
         // in reverse order, so remove doesn't shift the indexes we will later read
         for i in [19, 17, 12, 11, 7, 4, 3] {
@@ -520,16 +624,44 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_1", 123, 1234)
-            .unwrap();
-        crdt.add_validator(&mut storage, "bob", "bob_pubkey_1", 200, 2345)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_1",
+            ValidatorMetadata::default(),
+            123,
+            1234,
+        )
+        .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "bob",
+            "bob_pubkey_1",
+            ValidatorMetadata::default(),
+            200,
+            2345,
+        )
+        .unwrap();
         // Add does update
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_2", 202, 2347)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_2",
+            ValidatorMetadata::default(),
+            202,
+            2347,
+        )
+        .unwrap();
         // Update does as well
-        crdt.update_validator(&mut storage, "alice", "alice_pubkey_3", 203, 2348)
-            .unwrap();
+        crdt.update_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_3",
+            ValidatorMetadata::default(),
+            203,
+            2348,
+        )
+        .unwrap();
 
         // Query before update
         let alice = crdt
@@ -541,7 +673,8 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(),
                 start_height: 123,
                 start_time: 1234,
-                state: State::Active {}
+                state: State::Active {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
@@ -555,7 +688,8 @@ mod tests {
                 pub_key: "alice_pubkey_2".to_string(),
                 start_height: 202,
                 start_time: 2347,
-                state: State::Active {}
+                state: State::Active {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
@@ -569,7 +703,8 @@ mod tests {
                 pub_key: "alice_pubkey_3".to_string(),
                 start_height: 203,
                 start_time: 2348,
-                state: State::Active {}
+                state: State::Active {},
+                metadata: ValidatorMetadata::default(),
             })
         );
     }
@@ -579,8 +714,15 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_1", 123, 1234)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_1",
+            ValidatorMetadata::default(),
+            123,
+            1234,
+        )
+        .unwrap();
         // Remove changes state
         crdt.remove_validator(&mut storage, "alice", 202, 2347)
             .unwrap();
@@ -593,7 +735,8 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(),
                 start_height: 123,
                 start_time: 1234,
-                state: State::Active {}
+                state: State::Active {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
@@ -605,13 +748,21 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(),
                 start_height: 202,
                 start_time: 2347,
-                state: State::Unbonded {}
+                state: State::Unbonded {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
         // Add it again
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_2", 300, 3456)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_2",
+            ValidatorMetadata::default(),
+            300,
+            3456,
+        )
+        .unwrap();
 
         // Query after last addition height
         let alice = crdt.validator_at_height(&storage, "alice", 500).unwrap();
@@ -621,7 +772,8 @@ mod tests {
                 pub_key: "alice_pubkey_2".to_string(), // Pubkey has been updated
                 start_height: 300,
                 start_time: 3456,
-                state: State::Active {} // Validator is active
+                state: State::Active {},
+                metadata: ValidatorMetadata::default(), // Validator is active
             })
         );
     }
@@ -631,8 +783,15 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_1", 100, 1234)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_1",
+            ValidatorMetadata::default(),
+            100,
+            1234,
+        )
+        .unwrap();
         // Jail changes state
         crdt.jail_validator(&mut storage, "alice", 200, 2345)
             .unwrap();
@@ -645,13 +804,21 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(),
                 start_height: 200,
                 start_time: 2345,
-                state: State::Jailed {}
+                state: State::Jailed {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
         // Unjail it to active
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_1", 300, 3456)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_1",
+            ValidatorMetadata::default(),
+            300,
+            3456,
+        )
+        .unwrap();
 
         // Query after unjailing addition height
         let alice = crdt.validator_at_height(&storage, "alice", 500).unwrap();
@@ -661,7 +828,8 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(), // Pubkey has been updated
                 start_height: 300,
                 start_time: 3456,
-                state: State::Active {} // Validator is active again
+                state: State::Active {},
+                metadata: ValidatorMetadata::default(), // Validator is active again
             })
         );
     }
@@ -671,8 +839,15 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "alice_pubkey_1", 100, 1234)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "alice_pubkey_1",
+            ValidatorMetadata::default(),
+            100,
+            1234,
+        )
+        .unwrap();
         // Jail changes state
         crdt.jail_validator(&mut storage, "alice", 200, 2345)
             .unwrap();
@@ -685,7 +860,8 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(),
                 start_height: 200,
                 start_time: 2345,
-                state: State::Jailed {}
+                state: State::Jailed {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
@@ -701,7 +877,8 @@ mod tests {
                 pub_key: "alice_pubkey_1".to_string(),
                 start_height: 300,
                 start_time: 3456,
-                state: State::Unbonded {}
+                state: State::Unbonded {},
+                metadata: ValidatorMetadata::default(),
             })
         );
     }
@@ -711,12 +888,26 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "pk_a", 100, 1234)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "pk_a",
+            ValidatorMetadata::default(),
+            100,
+            1234,
+        )
+        .unwrap();
         crdt.tombstone_validator(&mut storage, "bob", 100, 1234)
             .unwrap();
-        crdt.add_validator(&mut storage, "bob", "pk_b", 200, 2345)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "bob",
+            "pk_b",
+            ValidatorMetadata::default(),
+            200,
+            2345,
+        )
+        .unwrap();
 
         assert!(!crdt.is_active_validator(&storage, "bob").unwrap());
 
@@ -731,7 +922,8 @@ mod tests {
                 pub_key: "".to_string(),
                 start_height: 100,
                 start_time: 1234,
-                state: State::Tombstoned {}
+                state: State::Tombstoned {},
+                metadata: ValidatorMetadata::default(),
             })
         );
 
@@ -740,10 +932,24 @@ mod tests {
         assert_eq!(bob, None);
 
         // All the other state changes are a no op
-        crdt.add_validator(&mut storage, "bob", "pk_b", 300, 3456)
-            .unwrap();
-        crdt.update_validator(&mut storage, "bob", "pk_b", 400, 4567)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "bob",
+            "pk_b",
+            ValidatorMetadata::default(),
+            300,
+            3456,
+        )
+        .unwrap();
+        crdt.update_validator(
+            &mut storage,
+            "bob",
+            "pk_b",
+            ValidatorMetadata::default(),
+            400,
+            4567,
+        )
+        .unwrap();
         crdt.remove_validator(&mut storage, "bob", 500, 5678)
             .unwrap();
         crdt.jail_validator(&mut storage, "bob", 600, 6789).unwrap();
@@ -758,7 +964,8 @@ mod tests {
                 pub_key: "".to_string(),
                 start_height: 100,
                 start_time: 1234,
-                state: State::Tombstoned {}
+                state: State::Tombstoned {},
+                metadata: ValidatorMetadata::default(),
             })
         );
     }
@@ -768,10 +975,24 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "pk_a", 100, 1234)
-            .unwrap();
-        crdt.add_validator(&mut storage, "bob", "pk_b", 200, 2345)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "pk_a",
+            ValidatorMetadata::default(),
+            100,
+            1234,
+        )
+        .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "bob",
+            "pk_b",
+            ValidatorMetadata::default(),
+            200,
+            2345,
+        )
+        .unwrap();
         crdt.tombstone_validator(&mut storage, "bob", 199, 2344)
             .unwrap();
 
@@ -788,7 +1009,8 @@ mod tests {
                 pub_key: "".to_string(),
                 start_height: 199,
                 start_time: 2344,
-                state: State::Tombstoned {}
+                state: State::Tombstoned {},
+                metadata: ValidatorMetadata::default(),
             })
         );
     }
@@ -799,15 +1021,29 @@ mod tests {
         let mut storage = MemoryStorage::new();
         let crdt = CrdtState::new();
 
-        crdt.add_validator(&mut storage, "alice", "pk_a", 100, 1234)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "pk_a",
+            ValidatorMetadata::default(),
+            100,
+            1234,
+        )
+        .unwrap();
         assert!(crdt.is_active_validator(&storage, "alice").unwrap());
 
         crdt.remove_validator(&mut storage, "alice", 200, 2345)
             .unwrap();
         assert!(!crdt.is_active_validator(&storage, "alice").unwrap());
-        crdt.add_validator(&mut storage, "alice", "pk_b", 300, 3456)
-            .unwrap();
+        crdt.add_validator(
+            &mut storage,
+            "alice",
+            "pk_b",
+            ValidatorMetadata::default(),
+            300,
+            3456,
+        )
+        .unwrap();
         assert!(crdt.is_active_validator(&storage, "alice").unwrap());
 
         let alice_history = crdt
@@ -860,4 +1096,32 @@ mod tests {
         // State didn't change
         assert!(crdt.is_active_validator(&storage, "alice").unwrap());
     }
+
+    #[test]
+    fn metadata_follows_state_changes() {
+        let mut storage = MemoryStorage::new();
+        let crdt = CrdtState::new();
+
+        let metadata = ValidatorMetadata {
+            commission: Some(Decimal::percent(5)),
+            moniker: Some("Alice".to_string()),
+        };
+        crdt.add_validator(&mut storage, "alice", "pk_a", metadata.clone(), 100, 1234)
+            .unwrap();
+        // Jailing and tombstoning keep the latest reported metadata
+        crdt.jail_validator(&mut storage, "alice", 200, 2345)
+            .unwrap();
+        let validators = crdt.list_validators(&storage, None, 10).unwrap();
+        assert_eq!(
+            validators,
+            vec![("alice".to_string(), State::Jailed {}, metadata.clone())]
+        );
+        crdt.tombstone_validator(&mut storage, "alice", 300, 3456)
+            .unwrap();
+        let validators = crdt.list_validators(&storage, None, 10).unwrap();
+        assert_eq!(
+            validators,
+            vec![("alice".to_string(), State::Tombstoned {}, metadata)]
+        );
+    }
 }
@@ -0,0 +1,209 @@
+//! Rewards distribution math.
+//!
+//! The rewards of a validator are tracked as the points (i.e. fixed-point tokens, scaled by
+//! `DISTRIBUTION_POINTS_SCALE`) distributed per staked token, `points_per_stake`. Distributing
+//! `amount` tokens adds `amount * SCALE / total_stake` to it. The remainder of the division is kept
+//! in `points_leftover`, and added to the next distribution, so that no rewards are lost to
+//! rounding. Rewards distributed while nothing is staked are kept in the leftover as well.
+//!
+//! A stake is entitled to `points_per_stake * stake` points, aligned by its `PointsAlignment` for
+//! the points distributed before its stake changed. Rewards are these points rounded down to whole
+//! tokens, minus the tokens already withdrawn.
+//!
+//! The invariant is that the aligned points of all the stakes of a validator, plus the leftover,
+//! are exactly the distributed rewards, in points. So the rewards paid out never exceed the
+//! distributed ones, and fall short by less than one token per stake, plus the leftover.
+//!
+//! All the arithmetic is checked, and fails instead of wrapping around.
+
+use cosmwasm_std::{StdResult, Uint128, Uint256};
+use mesh_sync::PointsAlignment;
+
+/// Fixed-point scale of the distribution points: one token is `10^9` points
+pub const DISTRIBUTION_POINTS_SCALE: Uint256 = Uint256::from_u128(1_000_000_000);
+
+/// Distributes `amount` tokens over `total_stake`, updating the points of a rewards denom
+pub fn distribute(
+    points_per_stake: &mut Uint256,
+    points_leftover: &mut Uint256,
+    total_stake: Uint128,
+    amount: Uint128,
+) -> StdResult<()> {
+    let points = Uint256::from(amount)
+        .checked_mul(DISTRIBUTION_POINTS_SCALE)?
+        .checked_add(*points_leftover)?;
+    if total_stake.is_zero() {
+        *points_leftover = points;
+        return Ok(());
+    }
+
+    let total_stake = Uint256::from(total_stake);
+    *points_per_stake = points_per_stake.checked_add(points.checked_div(total_stake)?)?;
+    *points_leftover = points.checked_rem(total_stake)?;
+    Ok(())
+}
+
+/// Points a stake is entitled to, withdrawn ones included
+pub fn accrued_points(
+    points_per_stake: Uint256,
+    stake: Uint128,
+    alignment: PointsAlignment,
+) -> StdResult<Uint256> {
+    let points = points_per_stake.checked_mul(Uint256::from(stake))?;
+    Ok(alignment.align(points))
+}
+
+/// Rewards of a stake not withdrawn yet, in whole tokens
+pub fn pending_rewards(
+    points_per_stake: Uint256,
+    stake: Uint128,
+    alignment: PointsAlignment,
+    withdrawn: Uint128,
+) -> StdResult<Uint128> {
+    let points = accrued_points(points_per_stake, stake, alignment)?;
+    let total = Uint128::try_from(points.checked_div(DISTRIBUTION_POINTS_SCALE)?)?;
+    Ok(total.checked_sub(withdrawn)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proptest::prelude::*;
+    use std::cmp::min;
+
+    const USERS: usize = 4;
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Stake(usize, u128),
+        Unstake(usize, u128),
+        Distribute(u128),
+        Withdraw(usize),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (0..USERS, 1..1_000_000_000u128).prop_map(|(user, amount)| Op::Stake(user, amount)),
+            (0..USERS, 1..1_000_000_000u128).prop_map(|(user, amount)| Op::Unstake(user, amount)),
+            (0..1_000_000_000u128).prop_map(Op::Distribute),
+            (0..USERS).prop_map(Op::Withdraw),
+        ]
+    }
+
+    #[derive(Default)]
+    struct User {
+        stake: Uint128,
+        alignment: PointsAlignment,
+        withdrawn: Uint128,
+    }
+
+    #[test]
+    fn leftover_carried_over() {
+        let mut points_per_stake = Uint256::zero();
+        let mut points_leftover = Uint256::zero();
+
+        // Nothing staked, everything is kept for the next distribution
+        distribute(
+            &mut points_per_stake,
+            &mut points_leftover,
+            Uint128::zero(),
+            Uint128::new(10),
+        )
+        .unwrap();
+        assert_eq!(points_per_stake, Uint256::zero());
+        assert_eq!(points_leftover, Uint256::from(10_000_000_000u128));
+
+        distribute(
+            &mut points_per_stake,
+            &mut points_leftover,
+            Uint128::new(3),
+            Uint128::new(5),
+        )
+        .unwrap();
+        assert_eq!(points_per_stake, Uint256::from(5_000_000_000u128));
+        assert_eq!(points_leftover, Uint256::zero());
+
+        distribute(
+            &mut points_per_stake,
+            &mut points_leftover,
+            Uint128::new(7),
+            Uint128::new(1),
+        )
+        .unwrap();
+        assert_eq!(points_per_stake, Uint256::from(5_142_857_142u128));
+        assert_eq!(points_leftover, Uint256::from(6u128));
+    }
+
+    proptest! {
+        #[test]
+        fn no_rewards_created_or_lost(ops in prop::collection::vec(op(), 1..100)) {
+            let mut users: Vec<User> = (0..USERS).map(|_| User::default()).collect();
+            let mut points_per_stake = Uint256::zero();
+            let mut points_leftover = Uint256::zero();
+            let mut total_stake = Uint128::zero();
+            let mut distributed = Uint128::zero();
+
+            for op in ops {
+                match op {
+                    Op::Stake(user, amount) => {
+                        let amount = Uint128::new(amount);
+                        let user = &mut users[user];
+                        user.alignment.stake_increased(amount, points_per_stake);
+                        user.stake += amount;
+                        total_stake += amount;
+                    }
+                    Op::Unstake(user, amount) => {
+                        let user = &mut users[user];
+                        let amount = min(Uint128::new(amount), user.stake);
+                        user.alignment.stake_decreased(amount, points_per_stake);
+                        user.stake -= amount;
+                        total_stake -= amount;
+                    }
+                    Op::Distribute(amount) => {
+                        let amount = Uint128::new(amount);
+                        distribute(&mut points_per_stake, &mut points_leftover, total_stake, amount)
+                            .unwrap();
+                        distributed += amount;
+                    }
+                    Op::Withdraw(user) => {
+                        let user = &mut users[user];
+                        user.withdrawn += pending_rewards(
+                            points_per_stake,
+                            user.stake,
+                            user.alignment,
+                            user.withdrawn,
+                        )
+                        .unwrap();
+                    }
+                }
+
+                // Exact, in points
+                let mut points = Uint256::zero();
+                let mut paid = Uint128::zero();
+                for user in &users {
+                    points += accrued_points(points_per_stake, user.stake, user.alignment).unwrap();
+                    paid += user.withdrawn
+                        + pending_rewards(
+                            points_per_stake,
+                            user.stake,
+                            user.alignment,
+                            user.withdrawn,
+                        )
+                        .unwrap();
+                }
+                prop_assert_eq!(
+                    points + points_leftover,
+                    Uint256::from(distributed) * DISTRIBUTION_POINTS_SCALE
+                );
+
+                // In tokens, only rounded down
+                prop_assert!(paid <= distributed);
+                prop_assert!(
+                    Uint256::from(distributed - paid) * DISTRIBUTION_POINTS_SCALE
+                        < Uint256::from(USERS as u128) * DISTRIBUTION_POINTS_SCALE + points_leftover
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Rounding policy of the rewards withdrawals.
+//!
+//! Rewards are withdrawn in whole tokens, and the sub-unit remainder of the points accrued by a
+//! stake (see `distribution`) stays in the stake, carried over to its next withdrawals. Once the
+//! stake is fully unstaked, nothing accrues to it anymore, so its remainder could never be
+//! withdrawn. It is swept into the dust of its owner instead, on the next withdrawal from it.
+//!
+//! The dust of a user is kept per rewards denom, pooled over all of their stakes. When it
+//! reaches the dust threshold (in whole tokens, one by default), its whole tokens are paid out
+//! along with the next withdrawal of the user in that denom, from any validator, and only its
+//! sub-unit remainder is kept.
+//!
+//! So the paid rewards always reconcile with the distributed ones, up to the sub-unit remainders
+//! of the open stakes, the dust of the users below the threshold, and the distribution leftovers.
+
+use cosmwasm_std::{Addr, StdResult, Storage, Uint128, Uint256};
+use cw_storage_plus::Map;
+use mesh_sync::PointsAlignment;
+use std::cmp::max;
+
+use crate::distribution::{self, DISTRIBUTION_POINTS_SCALE};
+
+pub struct RewardDust<'a> {
+    /// Sub-unit rewards of the users, by `(owner, denom)`, in points
+    pub dust: Map<'a, (&'a Addr, &'a str), Uint256>,
+    /// Dust paid out with the in-flight rewards withdrawals, by tx id
+    pub pending: Map<'a, u64, Uint128>,
+}
+
+impl<'a> RewardDust<'a> {
+    pub const fn new(dust_key: &'a str, pending_key: &'a str) -> Self {
+        Self {
+            dust: Map::new(dust_key),
+            pending: Map::new(pending_key),
+        }
+    }
+
+    /// Dust of `owner` in `denom`, in points
+    pub fn balance(&self, storage: &dyn Storage, owner: &Addr, denom: &str) -> StdResult<Uint256> {
+        Ok(self
+            .dust
+            .may_load(storage, (owner, denom))?
+            .unwrap_or_default())
+    }
+
+    /// Moves the sub-unit remainder of the points accrued by a stake into the owner dust.
+    /// Returns the points moved
+    pub fn sweep(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        denom: &str,
+        points_per_stake: Uint256,
+        stake: Uint128,
+        alignment: &mut PointsAlignment,
+    ) -> StdResult<Uint256> {
+        let points = distribution::accrued_points(points_per_stake, stake, *alignment)?;
+        let remainder = points.checked_rem(DISTRIBUTION_POINTS_SCALE)?;
+        if remainder.is_zero() {
+            return Ok(remainder);
+        }
+
+        alignment.points_removed(remainder);
+        let dust = self
+            .balance(storage, owner, denom)?
+            .checked_add(remainder)?;
+        self.dust.save(storage, (owner, denom), &dust)?;
+        Ok(remainder)
+    }
+
+    /// Takes the whole tokens out of the owner dust, if it reached `threshold` tokens (at least
+    /// one). Returns the tokens taken
+    pub fn payout(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        denom: &str,
+        threshold: Uint128,
+    ) -> StdResult<Uint128> {
+        let dust = self.balance(storage, owner, denom)?;
+        let threshold =
+            Uint256::from(max(threshold, Uint128::one())).checked_mul(DISTRIBUTION_POINTS_SCALE)?;
+        if dust < threshold {
+            return Ok(Uint128::zero());
+        }
+
+        let tokens = dust.checked_div(DISTRIBUTION_POINTS_SCALE)?;
+        let remainder = dust.checked_rem(DISTRIBUTION_POINTS_SCALE)?;
+        if remainder.is_zero() {
+            self.dust.remove(storage, (owner, denom));
+        } else {
+            self.dust.save(storage, (owner, denom), &remainder)?;
+        }
+        Ok(Uint128::try_from(tokens)?)
+    }
+
+    /// Credits back to the owner dust tokens paid out by a failed withdrawal
+    pub fn refund(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        denom: &str,
+        tokens: Uint128,
+    ) -> StdResult<()> {
+        let points = Uint256::from(tokens).checked_mul(DISTRIBUTION_POINTS_SCALE)?;
+        let dust = self.balance(storage, owner, denom)?.checked_add(points)?;
+        self.dust.save(storage, (owner, denom), &dust)
+    }
+}
@@ -1,6 +1,8 @@
 use cosmwasm_std::{ConversionOverflowError, StdError, Uint128};
 use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::VersionError;
+use mesh_apis::ownership_api::OwnershipError;
 use mesh_sync::{RangeError, Tx};
 use thiserror::Error;
 
@@ -18,11 +20,8 @@ pub enum ContractError {
     #[error("{0}")]
     Conversion(#[from] ConversionOverflowError),
 
-    #[error("Unauthorized")]
-    Unauthorized,
-
-    #[error("Invalid denom, {0} expected")]
-    InvalidDenom(String),
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
     #[error("You cannot specify a slash ratio over 1.0 (100%)")]
     InvalidSlashRatio,
@@ -30,6 +29,9 @@ pub enum ContractError {
     #[error("Not enough tokens staked, up to {0} can be unbond")]
     NotEnoughStake(Uint128),
 
+    #[error("Not enough tokens unbonding, up to {0} can be cancelled")]
+    NotEnoughUnbonding(Uint128),
+
     #[error("Not enough tokens released, up to {0} can be claimed")]
     NotEnoughRelease(Uint128),
 
@@ -39,12 +41,56 @@ pub enum ContractError {
     #[error("Cannot stake to {0}, not listed as an active validator on consumer")]
     ValidatorNotActive(String),
 
-    #[error("Contract already has an open IBC channel")]
-    IbcChannelAlreadyOpen,
+    #[error("Validator {0} was not active on consumer at height {1}")]
+    ValidatorNotActiveAt(String, u64),
+
+    #[error("Validator {0} was already slashed for its infraction at height {1}")]
+    InfractionAlreadySlashed(String, u64),
+
+    #[error("Validator {0} is leaving the active set, it cannot receive new stakes")]
+    ValidatorLeaving(String),
+
+    #[error("Cannot stake to {0}, its self-stake is below the required minimum")]
+    SelfStakeTooLow(String),
+
+    #[error("Cannot stake to {0}, it would go over its max cross-stake of {1}")]
+    ValidatorCapReached(String, Uint128),
+
+    #[error("Batch stake amounts have to add up to {0}")]
+    InvalidBatchAmount(Uint128),
+
+    #[error("Invalid validator selection: {0}")]
+    InvalidValidatorSelection(String),
+
+    #[error("Stake snapshots are disabled")]
+    SnapshotsDisabled,
+
+    #[error("Stake snapshots were already taken for epoch {0}")]
+    SnapshotsTaken(u64),
+
+    #[error("Snapshot capacity must be positive")]
+    InvalidSnapshotCapacity,
 
     #[error("You must start the channel handshake on the other side, it doesn't support OpenInit")]
     IbcOpenInitDisallowed,
 
+    #[error(
+        "The IBC channel can only be closed from the other side, it doesn't support CloseInit"
+    )]
+    IbcCloseInitDisallowed,
+
+    #[error("The IBC channel is not closed")]
+    IbcChannelNotClosed,
+
+    #[error("The IBC counterparty doesn't support {0}")]
+    IbcFeatureNotSupported(String),
+
+    #[error("Some txs are still in flight, try again once they are acked")]
+    TxsInFlight,
+
+    #[error("Re-opening the closed IBC channel must be approved by the contract admin first")]
+    IbcReopenNotApproved,
+
     #[error("Invalid authorized endpoint: {0}")]
     InvalidEndpoint(String),
 
@@ -54,12 +100,97 @@ pub enum ContractError {
     #[error("No staking rewards to be withdrawn")]
     NoRewards,
 
+    #[error("{0} is not a rewards denom")]
+    NotRewardsDenom(String),
+
+    #[error("{0} is already a rewards denom")]
+    RewardsDenomExists(String),
+
+    #[error("Rewards transfers are not enabled")]
+    RewardsTransferDisabled,
+
+    #[error("Sum of rewards ({sum}) doesn't match funds transferred ({sent})")]
+    RewardsTransferMismatch { sum: Uint128, sent: Uint128 },
+
+    #[error("User {0} is not opted in for auto-compounding")]
+    AutoCompoundDisabled(String),
+
+    #[error("User {0} has no default validator to stake to")]
+    NoDefaultValidator(String),
+
+    #[error("Validator {validator} is excluded by the staking preferences of {user}")]
+    ValidatorExcluded { user: String, validator: String },
+
+    #[error("Too many validators in the staking preferences, at most {0}")]
+    TooManyPreferredValidators(usize),
+
     #[error("Validator '{0}' already tombstoned / not found at height {1}")]
     AlreadyTombstoned(String, u64),
 
     #[error("{0}")]
     Range(#[from] RangeError),
 
+    #[error("{0}")]
+    Ownership(OwnershipError),
+
     #[error("User {0} has not enough delegated funds: {1}")]
     InsufficientDelegations(String, Uint128),
+
+    #[error("Validator {0} is being slashed, try again once the slashing is complete")]
+    SlashingInProgress(String),
+
+    #[error("No pending slashing for validator {0}")]
+    NoPendingSlash(String),
+
+    #[error("Instant unstaking is disabled")]
+    InstantUnstakeDisabled,
+
+    #[error("Invalid instant unstake config: {0}")]
+    InvalidInstantUnstakeConfig(String),
+
+    #[error("Instant unstaking requires a penalty of {0} to be sent along")]
+    WrongPenalty(Uint128),
+
+    #[error("Not enough free tokens in the buffer: {0}")]
+    InsufficientBuffer(Uint128),
+
+    #[error("Instant unstake limit reached for this epoch, {0} left")]
+    InstantUnstakeLimit(Uint128),
+
+    #[error("Idempotency key {0} was already used")]
+    DuplicateIdempotencyKey(String),
+
+    #[error("Idempotency key must be between 1 and {0} bytes long")]
+    InvalidIdempotencyKey(usize),
+
+    #[error("Protocol fee must be at most {0} basis points")]
+    InvalidProtocolFee(u16),
+
+    #[error("Min uptime must be at most 100%")]
+    InvalidMinUptime,
+
+    #[error("No {0} fees collected")]
+    NoFees(String),
+
+    #[error("Stake comes from vault {0}, unstake it all to switch vaults")]
+    VaultMismatch(String),
+
+    #[error("Vault {0} is not an additional vault")]
+    UnknownVault(String),
+
+    #[error("Tx {0} of another vault is still pending, retry once it is settled")]
+    TxIdInUse(u64),
+
+    #[error("No cross-staked tokens to vote with")]
+    NoVotingWeight,
+}
+
+impl From<OwnershipError> for ContractError {
+    fn from(err: OwnershipError) -> Self {
+        match err {
+            // Unwrapped, so non owners get the same error as any other unauthorized caller
+            OwnershipError::Mesh(err) => ContractError::Mesh(err),
+            err => ContractError::Ownership(err),
+        }
+    }
 }
@@ -0,0 +1,143 @@
+use cosmwasm_std::{Addr, BlockInfo, Coin, Order, StdResult, Storage};
+use cw_storage_plus::{Bound, Item, Map};
+
+use crate::state::{StakeTx, StakeTxKind, StakeTxStatus};
+
+/// Max number of operations kept in the history, over all the users. The oldest ones are
+/// forgotten first
+pub const MAX_TX_HISTORY: u64 = 10_000;
+
+/// History of the stake, unstake and withdraw operations of the users, for indexers to join
+/// the user actions with the IBC acks of their packets.
+///
+/// Every operation gets a monotonically increasing `stake_tx_id`, and only the
+/// `MAX_TX_HISTORY` most recent ones are kept.
+pub struct TxHistory<'a> {
+    /// Operations by `(user, stake tx id)`
+    pub txs: Map<'a, (&'a Addr, u64), StakeTx>,
+    /// Owner of each operation, by stake tx id, to forget the oldest ones
+    pub owners: Map<'a, u64, Addr>,
+    /// Stake tx id of the operations waiting for an ack, by pending tx id
+    pub pending: Map<'a, u64, u64>,
+    /// Number of operations ever recorded
+    pub count: Item<'a, u64>,
+}
+
+impl<'a> TxHistory<'a> {
+    pub const fn new(
+        txs_key: &'a str,
+        owners_key: &'a str,
+        pending_key: &'a str,
+        count_key: &'a str,
+    ) -> Self {
+        Self {
+            txs: Map::new(txs_key),
+            owners: Map::new(owners_key),
+            pending: Map::new(pending_key),
+            count: Item::new(count_key),
+        }
+    }
+
+    /// Records an operation of `user`, returning its stake tx id. It is pending until
+    /// `settle`d if `tx_id` is set, and committed otherwise. Forgets the oldest operation if
+    /// there are too many
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        user: &Addr,
+        kind: StakeTxKind,
+        validator: Option<&str>,
+        amount: Coin,
+        tx_id: Option<u64>,
+    ) -> StdResult<u64> {
+        let stake_tx_id = self.count.may_load(storage)?.unwrap_or_default();
+        let status = match tx_id {
+            Some(tx_id) => {
+                self.pending.save(storage, tx_id, &stake_tx_id)?;
+                StakeTxStatus::Pending
+            }
+            None => StakeTxStatus::Committed,
+        };
+        let tx = StakeTx {
+            stake_tx_id,
+            kind,
+            validator: validator.map(str::to_owned),
+            amount,
+            tx_id,
+            status,
+            height: block.height,
+            time: block.time,
+        };
+        self.txs.save(storage, (user, stake_tx_id), &tx)?;
+        self.owners.save(storage, stake_tx_id, user)?;
+        self.count.save(storage, &(stake_tx_id + 1))?;
+
+        if stake_tx_id >= MAX_TX_HISTORY {
+            self.forget(storage, stake_tx_id - MAX_TX_HISTORY)?;
+        }
+        Ok(stake_tx_id)
+    }
+
+    /// Marks the operation of the pending tx `tx_id` as committed or rolled back, returning its
+    /// stake tx id. `None` if no recorded operation is waiting for this tx
+    pub fn settle(
+        &self,
+        storage: &mut dyn Storage,
+        tx_id: u64,
+        success: bool,
+    ) -> StdResult<Option<u64>> {
+        let Some(stake_tx_id) = self.pending.may_load(storage, tx_id)? else {
+            return Ok(None);
+        };
+        self.pending.remove(storage, tx_id);
+
+        if let Some(user) = self.owners.may_load(storage, stake_tx_id)? {
+            if let Some(mut tx) = self.txs.may_load(storage, (&user, stake_tx_id))? {
+                tx.status = if success {
+                    StakeTxStatus::Committed
+                } else {
+                    StakeTxStatus::RolledBack
+                };
+                self.txs.save(storage, (&user, stake_tx_id), &tx)?;
+            }
+        }
+        Ok(Some(stake_tx_id))
+    }
+
+    /// Operations of `user`, ordered by stake tx id
+    pub fn range(
+        &self,
+        storage: &dyn Storage,
+        user: &Addr,
+        start_after: Option<u64>,
+        limit: usize,
+    ) -> StdResult<Vec<StakeTx>> {
+        self.txs
+            .prefix(user)
+            .range(
+                storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| item.map(|(_, tx)| tx))
+            .collect()
+    }
+
+    fn forget(&self, storage: &mut dyn Storage, stake_tx_id: u64) -> StdResult<()> {
+        if let Some(user) = self.owners.may_load(storage, stake_tx_id)? {
+            self.owners.remove(storage, stake_tx_id);
+            if let Some(StakeTx {
+                tx_id: Some(tx_id), ..
+            }) = self.txs.may_load(storage, (&user, stake_tx_id))?
+            {
+                self.pending.remove(storage, tx_id);
+            }
+            self.txs.remove(storage, (&user, stake_tx_id));
+        }
+        Ok(())
+    }
+}
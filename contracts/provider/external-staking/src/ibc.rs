@@ -2,14 +2,16 @@
 use cosmwasm_std::entry_point;
 
 use cosmwasm_std::{
-    from_json, DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel,
+    from_json, Binary, DepsMut, Env, Event, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel,
     IbcChannelCloseMsg, IbcChannelConnectMsg, IbcChannelOpenMsg, IbcChannelOpenResponse,
     IbcPacketAckMsg, IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, IbcTimeout,
+    StdResult, Storage,
 };
 use cw_storage_plus::Item;
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::{
-    ack_success, validate_channel_order, AckWrapper, ConsumerPacket, DistributeAck,
-    ProtocolVersion, ProviderPacket, ValsetUpdateAck,
+    ack_success, validate_channel_order, AckWrapper, BatchedUnstake, ConsumerPacket, DistributeAck,
+    Features, MaxCapUpdateAck, ProtocolVersion, ProviderPacket, TimeoutPolicy, ValsetUpdateAck,
 };
 
 use crate::contract::ExternalStakingContract;
@@ -17,23 +19,83 @@ use crate::error::ContractError;
 use crate::msg::AuthorizedEndpoint;
 
 /// This is the maximum version of the Mesh Security protocol that we support
-const SUPPORTED_IBC_PROTOCOL_VERSION: &str = "0.11.0";
+const SUPPORTED_IBC_PROTOCOL_VERSION: &str = "0.12.0";
 /// This is the minimum version that we are compatible with
 const MIN_IBC_PROTOCOL_VERSION: &str = "0.11.0";
+/// Optional protocol features we support
+pub const SUPPORTED_FEATURES: Features = Features::BATCH_REWARDS
+    .union(Features::BATCH_STAKE)
+    .union(Features::MAX_CAP_UPDATE)
+    .union(Features::STAKE_CHECKSUM)
+    .union(Features::VERSIONED_PACKETS)
+    .union(Features::REMOTE_GOVERNANCE)
+    .union(Features::VALSET_CHUNKS)
+    .union(Features::VALIDATOR_HEALTH)
+    .union(Features::BATCH_UNSTAKE);
 
 // IBC specific state
 pub const AUTH_ENDPOINT: Item<AuthorizedEndpoint> = Item::new("auth_endpoint");
 pub const IBC_CHANNEL: Item<IbcChannel> = Item::new("ibc_channel");
+/// Features negotiated on the channel
+pub const IBC_FEATURES: Item<Features> = Item::new("ibc_features");
+/// The channel closed by the counterparty, kept until it is replaced by a re-opened one
+pub const CLOSED_CHANNEL: Item<IbcChannel> = Item::new("closed_channel");
+/// Set by the contract admin to allow a new channel to replace the closed one
+pub const REOPEN_APPROVED: Item<bool> = Item::new("reopen_approved");
 
-// If we don't hear anything within 10 minutes, let's abort, for better UX
-// This is long enough to allow some clock drift between chains
-const DEFAULT_TIMEOUT: u64 = 10 * 60;
+/// If we don't hear anything within 10 minutes, let's abort, for better UX.
+/// This is long enough to allow some clock drift between chains.
+/// Timed out packets are sent again by `retry_packets`, with no backoff.
+pub const TIMEOUT_POLICY: TimeoutPolicy = TimeoutPolicy::new(10 * 60);
 
-pub fn packet_timeout(env: &Env) -> IbcTimeout {
-    // No idea about their block time, but 24 hours ahead of our view of the clock
-    // should be decently in the future.
-    let timeout = env.block.time.plus_seconds(DEFAULT_TIMEOUT);
-    IbcTimeout::with_timestamp(timeout)
+pub fn packet_timeout(env: &Env, packet: &ProviderPacket) -> IbcTimeout {
+    TIMEOUT_POLICY.timeout(env.block.time, packet.packet_type())
+}
+
+/// Packets sent by test code, which can't handle IBC, in order. Read by the test relayers
+#[cfg(any(test, feature = "mt"))]
+pub const TEST_SENT_PACKETS: Item<Vec<ProviderPacket>> = Item::new("test_sent_packets");
+
+/// Records the packet sent by `msg` in `TEST_SENT_PACKETS`, as test code can't send it
+#[cfg(any(test, feature = "mt"))]
+pub fn record_test_packet(storage: &mut dyn Storage, msg: &cosmwasm_std::IbcMsg) -> StdResult<()> {
+    if let cosmwasm_std::IbcMsg::SendPacket { data, .. } = msg {
+        let mut packets = TEST_SENT_PACKETS.may_load(storage)?.unwrap_or_default();
+        packets.push(ProviderPacket::decode(data)?);
+        TEST_SENT_PACKETS.save(storage, &packets)?;
+    }
+    Ok(())
+}
+
+/// Loads the open channel, failing with `MeshError::ChannelNotOpen` if there is none.
+/// Any path sending packets to the consumer goes through this, so cross-staking is frozen
+/// while the channel is closed.
+pub fn load_channel(storage: &dyn Storage) -> Result<IbcChannel, ContractError> {
+    IBC_CHANNEL
+        .may_load(storage)?
+        .ok_or(ContractError::Mesh(MeshError::ChannelNotOpen))
+}
+
+/// Features negotiated on the channel. None for channels opened before features were added
+pub fn channel_features(storage: &dyn Storage) -> StdResult<Features> {
+    Ok(IBC_FEATURES.may_load(storage)?.unwrap_or_default())
+}
+
+/// After a channel close, a new channel can only be opened if the admin approved it, and it
+/// must come from the same (connection, port) as the closed one.
+fn ensure_reopen_allowed(storage: &dyn Storage, channel: &IbcChannel) -> Result<(), ContractError> {
+    let Some(closed) = CLOSED_CHANNEL.may_load(storage)? else {
+        return Ok(());
+    };
+    if !REOPEN_APPROVED.may_load(storage)?.unwrap_or_default() {
+        return Err(ContractError::IbcReopenNotApproved);
+    }
+    if closed.connection_id != channel.connection_id
+        || closed.counterparty_endpoint.port_id != channel.counterparty_endpoint.port_id
+    {
+        return Err(MeshError::Unauthorized.into());
+    }
+    Ok(())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -45,7 +107,7 @@ pub fn ibc_channel_open(
 ) -> Result<IbcChannelOpenResponse, ContractError> {
     // ensure we have no channel yet
     if IBC_CHANNEL.may_load(deps.storage)?.is_some() {
-        return Err(ContractError::IbcChannelAlreadyOpen);
+        return Err(MeshError::ChannelAlreadyOpen.into());
     }
     // ensure we are called with OpenInit
     let (channel, counterparty_version) = match msg {
@@ -65,13 +127,16 @@ pub fn ibc_channel_open(
         || authorized.port_id != channel.counterparty_endpoint.port_id
     {
         // FIXME: do we need a better error here?
-        return Err(ContractError::Unauthorized);
+        return Err(MeshError::Unauthorized.into());
     }
+    ensure_reopen_allowed(deps.storage, &channel)?;
 
     // we handshake with the counterparty version, it must not be empty
     let v: ProtocolVersion = from_json(counterparty_version.as_bytes())?;
     // if we can build a response to this, then it is compatible. And we use the highest version there
-    let version = v.build_response(SUPPORTED_IBC_PROTOCOL_VERSION, MIN_IBC_PROTOCOL_VERSION)?;
+    let version = v
+        .build_response(SUPPORTED_IBC_PROTOCOL_VERSION, MIN_IBC_PROTOCOL_VERSION)?
+        .with_features(v.features.intersection(SUPPORTED_FEATURES));
 
     let response = Ibc3ChannelOpenResponse {
         version: version.to_string()?,
@@ -88,27 +153,75 @@ pub fn ibc_channel_connect(
 ) -> Result<IbcBasicResponse, ContractError> {
     // ensure we have no channel yet
     if IBC_CHANNEL.may_load(deps.storage)?.is_some() {
-        return Err(ContractError::IbcChannelAlreadyOpen);
+        return Err(MeshError::ChannelAlreadyOpen.into());
     }
     // ensure we are called with OpenConfirm
     let channel = match msg {
         IbcChannelConnectMsg::OpenConfirm { channel } => channel,
         IbcChannelConnectMsg::OpenAck { .. } => return Err(ContractError::IbcOpenInitDisallowed),
     };
+    ensure_reopen_allowed(deps.storage, &channel)?;
+    // the channel version is the one we responded with in OpenTry
+    let version: ProtocolVersion = from_json(channel.version.as_bytes())?;
 
-    // Version negotiation over, we can only store the channel
+    // Version negotiation over, we can only store the channel and its features.
+    // If it replaces a closed one, stakes and distribution are kept as they are
     IBC_CHANNEL.save(deps.storage, &channel)?;
+    IBC_FEATURES.save(deps.storage, &version.features)?;
+    CLOSED_CHANNEL.remove(deps.storage);
+    REOPEN_APPROVED.remove(deps.storage);
 
-    Ok(IbcBasicResponse::default())
+    let resp = IbcBasicResponse::new()
+        .add_attribute("action", "ibc_channel_connect")
+        .add_attribute("channel_id", channel.endpoint.channel_id);
+    Ok(resp)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
+/// The channel is closed on the counterparty side. We keep all the stake accounting, but
+/// no new packets can be sent until the channel is re-opened (see `ensure_reopen_allowed`).
+/// Timed out in-flight packets are queued, and can be retried on the new channel.
 pub fn ibc_channel_close(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
-    _msg: IbcChannelCloseMsg,
+    msg: IbcChannelCloseMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    todo!();
+    let channel = match msg {
+        IbcChannelCloseMsg::CloseInit { .. } => return Err(ContractError::IbcCloseInitDisallowed),
+        IbcChannelCloseMsg::CloseConfirm { channel } => channel,
+    };
+    let open = load_channel(deps.storage)?;
+    if open.endpoint != channel.endpoint {
+        return Err(MeshError::Unauthorized.into());
+    }
+
+    IBC_CHANNEL.remove(deps.storage);
+    CLOSED_CHANNEL.save(deps.storage, &open)?;
+
+    let resp = IbcBasicResponse::new()
+        .add_attribute("action", "ibc_channel_close")
+        .add_attribute("channel_id", open.endpoint.channel_id);
+    Ok(resp)
+}
+
+/// Tx ids of the unstakes of a batch, as reported in events
+fn batch_tx_ids(unstakes: &[BatchedUnstake]) -> String {
+    unstakes
+        .iter()
+        .map(|unstake| unstake.tx_id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Success ack for the consumer packet
+fn consumer_packet_ack(packet: &ConsumerPacket) -> StdResult<Binary> {
+    match packet {
+        ConsumerPacket::ValsetUpdate { .. } => ack_success(&ValsetUpdateAck {}),
+        ConsumerPacket::Distribute { .. } | ConsumerPacket::DistributeBatch { .. } => {
+            ack_success(&DistributeAck {})
+        }
+        ConsumerPacket::MaxCapUpdate { .. } => ack_success(&MaxCapUpdateAck {}),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -119,11 +232,39 @@ pub fn ibc_packet_receive(
     msg: IbcPacketReceiveMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     // There is only one channel, so we don't need to switch.
-    // We also don't care about packet sequence as this is being ordered by height.
+    // Packets are applied as they come, sequences are only tracked to report gaps.
     // If a validator is in more than one of the events, the end result will depend on the
     // processing order below.
     let contract = ExternalStakingContract::new();
-    let packet: ConsumerPacket = from_json(msg.packet.data)?;
+    let channel_id = msg.packet.dest.channel_id;
+    let sequence = msg.packet.sequence;
+    let packet = ConsumerPacket::decode(&msg.packet.data)?;
+    let packet_type = packet.packet_type();
+
+    // A packet re-delivered by the relayer is acked again, but not re-applied, so rewards and
+    // slashing are not accounted twice
+    if !contract.record_packet(deps.storage, &env.block, &channel_id, sequence, packet_type)? {
+        let ack = consumer_packet_ack(&packet)?;
+        let resp = IbcReceiveResponse::new()
+            .set_ack(ack)
+            .add_attribute("action", "duplicate_packet")
+            .add_attribute("packet_type", packet_type)
+            .add_attribute("sequence", sequence.to_string());
+        return Ok(resp);
+    }
+    let gap_evt = contract.track_sequence(deps.storage, &channel_id, sequence)?;
+
+    let resp = receive_packet(deps, env, packet)?;
+    Ok(resp.add_events(gap_evt))
+}
+
+/// Applies the consumer packet, returning the response with its success ack
+pub(crate) fn receive_packet(
+    deps: DepsMut,
+    env: Env,
+    packet: ConsumerPacket,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let contract = ExternalStakingContract::new();
     let resp = match packet {
         ConsumerPacket::ValsetUpdate {
             height,
@@ -135,7 +276,17 @@ pub fn ibc_packet_receive(
             unjailed,
             tombstoned,
             slashed,
+            uptimes,
+            chunk,
         } => {
+            let chunk_evt = chunk
+                .map(|chunk| contract.track_valset_chunk(deps.storage, height, &chunk))
+                .transpose()?;
+            let self_stake_evts =
+                contract.update_self_stakes(deps.storage, additions.iter().chain(&updated))?;
+            contract.update_max_external_stakes(deps.storage, additions.iter().chain(&updated))?;
+            let health_evts =
+                contract.update_validator_health(deps.storage, height, time, &uptimes)?;
             let (evt, msgs) = contract.valset_update(
                 deps,
                 env,
@@ -153,6 +304,9 @@ pub fn ibc_packet_receive(
             IbcReceiveResponse::new()
                 .set_ack(ack)
                 .add_event(evt)
+                .add_events(chunk_evt)
+                .add_events(self_stake_evts)
+                .add_events(health_evts)
                 .add_messages(msgs)
         }
         ConsumerPacket::Distribute { validator, rewards } => {
@@ -165,9 +319,30 @@ pub fn ibc_packet_receive(
             let ack = ack_success(&DistributeAck {})?;
             IbcReceiveResponse::new().set_ack(ack).add_events(evts)
         }
+        ConsumerPacket::MaxCapUpdate { max_cap, unbonds } => {
+            // Informational only, stakes are kept as they are on the provider side
+            let evt = Event::new("max_cap_update")
+                .add_attribute("max_cap", max_cap.to_string())
+                .add_attribute(
+                    "validators",
+                    unbonds
+                        .iter()
+                        .map(|u| u.validator.clone())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                )
+                .add_attribute(
+                    "amounts",
+                    unbonds
+                        .iter()
+                        .map(|u| u.amount.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                );
+            let ack = ack_success(&MaxCapUpdateAck {})?;
+            IbcReceiveResponse::new().set_ack(ack).add_event(evt)
+        }
     };
-
-    // return empty success ack
     Ok(resp)
 }
 
@@ -178,16 +353,40 @@ pub fn ibc_packet_ack(
     env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let packet: ProviderPacket = from_json(&msg.original_packet.data)?;
-    let contract = ExternalStakingContract::new();
+    let packet = ProviderPacket::decode(&msg.original_packet.data)?;
     let ack: AckWrapper = from_json(&msg.acknowledgement.data)?;
+    packet_acked(deps, env, packet, ack)
+}
+
+/// Commits or rolls back the transaction of the acked provider packet
+pub(crate) fn packet_acked(
+    mut deps: DepsMut,
+    env: Env,
+    packet: ProviderPacket,
+    ack: AckWrapper,
+) -> Result<IbcBasicResponse, ContractError> {
+    let contract = ExternalStakingContract::new();
     let mut resp = IbcBasicResponse::new();
 
+    // Joins the ack with the user operation, for the indexers
+    let tx_id = match &packet {
+        ProviderPacket::Stake { tx_id, .. }
+        | ProviderPacket::StakeBatch { tx_id, .. }
+        | ProviderPacket::Unstake { tx_id, .. }
+        | ProviderPacket::TransferRewards { tx_id, .. } => Some(*tx_id),
+        _ => None,
+    };
+    if let Some(tx_id) = tx_id {
+        if let Some(stake_tx_id) = contract.tx_history.pending.may_load(deps.storage, tx_id)? {
+            resp = resp.add_attribute("stake_tx_id", stake_tx_id.to_string());
+        }
+    }
+
     match (packet, ack) {
         (ProviderPacket::Stake { tx_id, .. }, AckWrapper::Result(_)) => {
             let msg = contract.commit_stake(deps, tx_id)?;
             resp = resp
-                .add_message(msg)
+                .add_messages(msg)
                 .add_attribute("success", "true")
                 .add_attribute("tx_id", tx_id.to_string())
                 .add_attribute("packet_type", "stake");
@@ -195,25 +394,63 @@ pub fn ibc_packet_ack(
         (ProviderPacket::Stake { tx_id, .. }, AckWrapper::Error(e)) => {
             let msg = contract.rollback_stake(deps, tx_id)?;
             resp = resp
-                .add_message(msg)
+                .add_messages(msg)
                 .add_attribute("error", e)
                 .add_attribute("tx_id", tx_id.to_string())
                 .add_attribute("packet_type", "stake");
         }
+        (ProviderPacket::StakeBatch { tx_id, .. }, AckWrapper::Result(_)) => {
+            let msg = contract.commit_stake(deps, tx_id)?;
+            resp = resp
+                .add_messages(msg)
+                .add_attribute("success", "true")
+                .add_attribute("tx_id", tx_id.to_string())
+                .add_attribute("packet_type", "stake_batch");
+        }
+        (ProviderPacket::StakeBatch { tx_id, .. }, AckWrapper::Error(e)) => {
+            let msg = contract.rollback_stake(deps, tx_id)?;
+            resp = resp
+                .add_messages(msg)
+                .add_attribute("error", e)
+                .add_attribute("tx_id", tx_id.to_string())
+                .add_attribute("packet_type", "stake_batch");
+        }
         (ProviderPacket::Unstake { tx_id, .. }, AckWrapper::Result(_)) => {
-            contract.commit_unstake(deps, env, tx_id)?;
+            let msgs = contract.commit_unstake(deps, env, tx_id)?;
             resp = resp
+                .add_messages(msgs)
                 .add_attribute("success", "true")
                 .add_attribute("tx_id", tx_id.to_string())
                 .add_attribute("packet_type", "unstake");
         }
         (ProviderPacket::Unstake { tx_id, .. }, AckWrapper::Error(e)) => {
-            contract.rollback_unstake(deps, tx_id)?;
+            let msg = contract.rollback_unstake(deps, tx_id)?;
             resp = resp
+                .add_messages(msg)
                 .add_attribute("error", e)
                 .add_attribute("tx_id", tx_id.to_string())
                 .add_attribute("packet_type", "unstake");
         }
+        (ProviderPacket::UnstakeBatch { unstakes, .. }, AckWrapper::Result(_)) => {
+            for unstake in &unstakes {
+                let msgs = contract.commit_unstake(deps.branch(), env.clone(), unstake.tx_id)?;
+                resp = resp.add_messages(msgs);
+            }
+            resp = resp
+                .add_attribute("success", "true")
+                .add_attribute("tx_ids", batch_tx_ids(&unstakes))
+                .add_attribute("packet_type", "unstake_batch");
+        }
+        (ProviderPacket::UnstakeBatch { unstakes, .. }, AckWrapper::Error(e)) => {
+            for unstake in &unstakes {
+                let msg = contract.rollback_unstake(deps.branch(), unstake.tx_id)?;
+                resp = resp.add_messages(msg);
+            }
+            resp = resp
+                .add_attribute("error", e)
+                .add_attribute("tx_ids", batch_tx_ids(&unstakes))
+                .add_attribute("packet_type", "unstake_batch");
+        }
         (ProviderPacket::Burn { .. }, AckWrapper::Result(_)) => {
             resp = resp
                 .add_attribute("success", "true")
@@ -240,50 +477,50 @@ pub fn ibc_packet_ack(
                 .add_attribute("tx_id", tx_id.to_string())
                 .add_attribute("packet_type", "transfer_rewards");
         }
+        (ProviderPacket::StakeChecksum { .. }, AckWrapper::Result(_)) => {
+            resp = resp
+                .add_attribute("success", "true")
+                .add_attribute("packet_type", "stake_checksum");
+        }
+        (ProviderPacket::StakeChecksum { checksum }, AckWrapper::Error(e)) => {
+            resp = resp
+                .add_attribute("error", e)
+                .add_attribute("packet_type", "stake_checksum")
+                .add_attribute("checksum", checksum.to_string());
+        }
+        (ProviderPacket::Vote { .. }, AckWrapper::Result(_)) => {
+            resp = resp
+                .add_attribute("success", "true")
+                .add_attribute("packet_type", "vote");
+        }
+        (ProviderPacket::Vote { proposal_id, .. }, AckWrapper::Error(e)) => {
+            resp = resp
+                .add_attribute("error", e)
+                .add_attribute("packet_type", "vote")
+                .add_attribute("proposal_id", proposal_id.to_string());
+        }
     }
     Ok(resp)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-/// This should trigger a rollback of staking/unstaking/burning
+/// Timed out packets are queued, to be sent again with `retry_packets`.
+/// Their transactions stay pending until the packet is finally acked.
 pub fn ibc_packet_timeout(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let packet: ProviderPacket = from_json(msg.packet.data)?;
+    let packet = ProviderPacket::decode(&msg.packet.data)?;
     let contract = ExternalStakingContract::new();
-    let mut resp = IbcBasicResponse::new().add_attribute("action", "ibc_packet_timeout");
-    match packet {
-        ProviderPacket::Stake { tx_id, .. } => {
-            let msg = contract.rollback_stake(deps, tx_id)?;
-            resp = resp
-                .add_message(msg)
-                .add_attribute("error", "timeout")
-                .add_attribute("tx_id", tx_id.to_string())
-                .add_attribute("packet_type", "stake");
-        }
-        ProviderPacket::Unstake { tx_id, .. } => {
-            contract.rollback_unstake(deps, tx_id)?;
-            resp = resp
-                .add_attribute("error", "timeout")
-                .add_attribute("tx_id", tx_id.to_string())
-                .add_attribute("packet_type", "unstake");
-        }
-        ProviderPacket::Burn { validators, burn } => {
-            resp = resp
-                .add_attribute("error", "timeout")
-                .add_attribute("packet_type", "burn")
-                .add_attribute("validators", validators.join(","))
-                .add_attribute("amount", burn.amount.to_string());
-        }
-        ProviderPacket::TransferRewards { tx_id, .. } => {
-            contract.rollback_withdraw_rewards(deps, tx_id)?;
-            resp = resp
-                .add_attribute("error", "timeout")
-                .add_attribute("tx_id", tx_id.to_string())
-                .add_attribute("packet_type", "transfer_rewards");
-        }
-    };
+    let packet_type = packet.packet_type();
+    let sequence = msg.packet.sequence;
+    contract.queue_timed_out_packet(deps.storage, &env.block, sequence, packet)?;
+
+    let resp = IbcBasicResponse::new()
+        .add_attribute("action", "ibc_packet_timeout")
+        .add_attribute("error", "timeout")
+        .add_attribute("packet_type", packet_type)
+        .add_attribute("sequence", sequence.to_string());
     Ok(resp)
 }
@@ -0,0 +1,99 @@
+use cosmwasm_std::{ensure, Addr, StdResult, Storage};
+use cw_storage_plus::{Item, Map};
+
+use crate::error::ContractError;
+
+/// Max number of idempotency keys remembered, over all the users. The oldest ones are
+/// forgotten first
+pub const MAX_IDEMPOTENCY_KEYS: u64 = 10_000;
+
+/// Max length of an idempotency key, in bytes
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 64;
+
+/// Client-chosen keys of the processed stakes and unstakes, so a wallet retrying a tx that
+/// actually landed gets it rejected instead of applied twice.
+///
+/// Keys are scoped to the user, and only the `MAX_IDEMPOTENCY_KEYS` most recent ones are kept.
+/// The key of a tx rolled back by the consumer, on an error ack or a timeout, is released, so
+/// the user can retry with the same key.
+pub struct IdempotencyKeys<'a> {
+    /// Processed keys by `(user, key)`, with their insertion index
+    pub keys: Map<'a, (&'a Addr, &'a str), u64>,
+    /// `(user, key)` by insertion index, to forget the oldest keys
+    pub order: Map<'a, u64, (Addr, String)>,
+    /// Number of keys ever recorded
+    pub count: Item<'a, u64>,
+    /// Insertion index of the key by the id of its tx, until the tx is settled
+    pub pending: Map<'a, u64, u64>,
+}
+
+impl<'a> IdempotencyKeys<'a> {
+    pub const fn new(
+        keys_key: &'a str,
+        order_key: &'a str,
+        count_key: &'a str,
+        pending_key: &'a str,
+    ) -> Self {
+        Self {
+            keys: Map::new(keys_key),
+            order: Map::new(order_key),
+            count: Item::new(count_key),
+            pending: Map::new(pending_key),
+        }
+    }
+
+    pub fn is_used(&self, storage: &dyn Storage, user: &Addr, key: &str) -> bool {
+        self.keys.has(storage, (user, key))
+    }
+
+    /// Records `key` as used by `user` for the `tx_id` tx, failing if it already is. Forgets
+    /// the oldest key if there are too many
+    pub fn record(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        key: &str,
+        tx_id: u64,
+    ) -> Result<(), ContractError> {
+        ensure!(
+            !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN,
+            ContractError::InvalidIdempotencyKey(MAX_IDEMPOTENCY_KEY_LEN)
+        );
+        ensure!(
+            !self.is_used(storage, user, key),
+            ContractError::DuplicateIdempotencyKey(key.to_owned())
+        );
+
+        let index = self.count.may_load(storage)?.unwrap_or_default();
+        self.keys.save(storage, (user, key), &index)?;
+        self.order
+            .save(storage, index, &(user.clone(), key.to_owned()))?;
+        self.count.save(storage, &(index + 1))?;
+        self.pending.save(storage, tx_id, &index)?;
+
+        if index >= MAX_IDEMPOTENCY_KEYS {
+            self.forget(storage, index - MAX_IDEMPOTENCY_KEYS)?;
+        }
+        Ok(())
+    }
+
+    /// Settles the `tx_id` tx, releasing its key if it failed. Txs without a key are ignored
+    pub fn settle(&self, storage: &mut dyn Storage, tx_id: u64, success: bool) -> StdResult<()> {
+        let Some(index) = self.pending.may_load(storage, tx_id)? else {
+            return Ok(());
+        };
+        self.pending.remove(storage, tx_id);
+        if !success {
+            self.forget(storage, index)?;
+        }
+        Ok(())
+    }
+
+    fn forget(&self, storage: &mut dyn Storage, index: u64) -> StdResult<()> {
+        if let Some((user, key)) = self.order.may_load(storage, index)? {
+            self.order.remove(storage, index);
+            self.keys.remove(storage, (&user, &key));
+        }
+        Ok(())
+    }
+}
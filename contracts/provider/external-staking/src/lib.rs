@@ -1,12 +1,18 @@
+mod buffer;
 pub mod contract;
 pub mod crdt;
+pub mod distribution;
+mod dust;
 pub mod error;
+mod history;
 pub mod ibc;
+mod idempotency;
 pub mod msg;
-#[cfg(test)]
+// The multitests run the vault without the provider module custom messages
+#[cfg(all(test, not(feature = "provider-bindings")))]
 mod multitest;
-mod points_alignment;
 mod stakes;
 pub mod state;
 pub mod test_methods;
 pub mod test_methods_impl;
+mod vaults;
@@ -1,8 +1,11 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{coin, Coin, IbcChannel};
+use cosmwasm_std::{coin, Coin, Decimal, IbcChannel, Timestamp, Uint128, Uint64};
+use mesh_apis::ibc::{ProviderPacket, VoteWeight};
 
 use crate::crdt::State;
-use crate::state::Stake;
+use crate::state::{
+    InstantUnstakeConfig, ProtocolFee, RewardsTransferConfig, Stake, StakeTx, ValidatorHealth,
+};
 use crate::{error::ContractError, state::Config};
 
 #[cw_serde]
@@ -49,27 +52,105 @@ pub struct ListValidatorsResponse {
 pub struct ValidatorState {
     pub validator: String,
     pub state: State,
+    /// Commission rate on the consumer, if reported
+    pub commission: Option<Decimal>,
+    /// Human readable name, if reported
+    pub moniker: Option<String>,
+}
+
+/// Vaults allowed to stake, starting with the instantiation one
+#[cw_serde]
+pub struct VaultsResponse {
+    pub vaults: Vec<VaultInfo>,
+}
+
+/// Vault allowed to stake, or a stake comes from, and the denom it bonds
+#[cw_serde]
+pub struct VaultInfo {
+    pub vault: String,
+    pub denom: String,
 }
 
 /// Config information returned with query
 #[cw_serde]
 pub struct ConfigResponse {
     pub denom: String,
+    pub rewards_denom: String,
+    pub extra_rewards_denoms: Vec<String>,
     pub vault: String,
     /// In seconds
     pub unbonding_period: u64,
+    pub min_self_stake: Option<Uint128>,
+    /// In seconds
+    pub removal_grace_period: u64,
+    pub max_withdraw_batch: Option<u32>,
+    /// In seconds, zero if disabled
+    pub snapshot_interval: u64,
+    pub snapshot_capacity: u32,
+    pub instant_unstake: Option<InstantUnstakeConfig>,
+    pub rewards_transfer: Option<RewardsTransferConfig>,
+    pub protocol_fee: Option<ProtocolFee>,
+    pub min_uptime: Option<Decimal>,
+    pub dust_threshold: Uint128,
+    pub unstake_batching: bool,
 }
 
 impl From<Config> for ConfigResponse {
     fn from(value: Config) -> Self {
         Self {
             denom: value.denom,
+            rewards_denom: value.rewards_denom,
+            extra_rewards_denoms: value.extra_rewards_denoms,
             vault: value.vault.0.into(),
             unbonding_period: value.unbonding_period,
+            min_self_stake: value.min_self_stake,
+            removal_grace_period: value.removal_grace_period,
+            max_withdraw_batch: value.max_withdraw_batch,
+            snapshot_interval: value.snapshot_interval,
+            snapshot_capacity: value.snapshot_capacity,
+            instant_unstake: value.instant_unstake,
+            rewards_transfer: value.rewards_transfer,
+            protocol_fee: value.protocol_fee,
+            min_uptime: value.min_uptime,
+            dust_threshold: value.dust_threshold,
+            unstake_batching: value.unstake_batching,
         }
     }
 }
 
+#[cw_serde]
+pub struct FeesCollectedResponse {
+    /// Protocol fees collected and not withdrawn yet, by rewards denom
+    pub fees: Vec<Coin>,
+}
+
+/// Liquid tokens backing the instant unstakes
+#[cw_serde]
+pub struct BufferResponse {
+    /// Tokens in the buffer, including the locked ones
+    pub total: Uint128,
+    /// Tokens backing instant unstakes still unbonding on the consumer. Expired ones are
+    /// unlocked by the next buffer operation
+    pub locked: Uint128,
+    /// Tokens slashed from the buffer, burned by the next buffer operation
+    pub slashed: Uint128,
+    /// Tokens instantly unstaked during the current epoch, zero if instant unstaking is
+    /// disabled
+    pub epoch_used: Uint128,
+}
+
+#[cw_serde]
+pub struct LeavingValidatorInfo {
+    pub validator: String,
+    /// Block time the removal can be finalized at
+    pub finalize_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct LeavingValidatorsResponse {
+    pub validators: Vec<LeavingValidatorInfo>,
+}
+
 /// Stake-related information including user address and validator
 #[cw_serde]
 pub struct StakeInfo {
@@ -94,10 +175,66 @@ pub struct StakesResponse {
     pub stakes: Vec<StakeInfo>,
 }
 
+/// Response for the stake checksum query
+#[cw_serde]
+pub struct StakeChecksumResponse {
+    /// `mesh_apis::ibc::StakeChecksum` of the total stake per validator
+    pub checksum: Uint64,
+}
+
+/// Response for the remote vote tally query
+#[cw_serde]
+pub struct RemoteVoteTallyResponse {
+    /// Cross-staked weight voting for each option, in the provider-side denom
+    pub tally: Vec<VoteWeight>,
+}
+
+/// Unbonding tokens released within the same time bucket
+#[cw_serde]
+pub struct UnbondingBucket {
+    /// End of the bucket - all its tokens are released by then
+    pub release_at: Timestamp,
+    pub amount: Uint128,
+}
+
+/// User unbonding tokens, summed in time buckets
+#[cw_serde]
+pub struct UnbondingScheduleResponse {
+    /// Tokens already released, which can be withdrawn with `withdraw_unbonded`
+    pub claimable: Uint128,
+    /// Tokens still unbonding, ordered by release time
+    pub buckets: Vec<UnbondingBucket>,
+}
+
 /// Message to be sent as `msg` field on `receive_virtual_stake`
+pub use mesh_apis::cross_staking_api::ReceiveVirtualStake;
+/// Message to be sent as `msg` field on `receive_virtual_stake`, to select validators automatically
+pub use mesh_apis::cross_staking_api::{AutoStake, ValidatorSelection};
+
+/// Single validator stake, part of `ReceiveVirtualStakeMsg::StakeBatch`
 #[cw_serde]
-pub struct ReceiveVirtualStake {
+pub struct BatchStake {
     pub validator: String,
+    pub amount: Uint128,
+}
+
+/// Any message accepted as `msg` field on `receive_virtual_stake`.
+/// A plain `ReceiveVirtualStake` is still accepted as is, for compatibility.
+#[cw_serde]
+pub enum ReceiveVirtualStakeMsg {
+    /// Splits the staked amount across several validators, in a single tx and IBC packet.
+    /// Amounts have to add up to the staked amount.
+    StakeBatch {
+        stakes: Vec<BatchStake>,
+        /// Rejects the stake if already used by the owner, see `ReceiveVirtualStake`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        idempotency_key: Option<String>,
+    },
+    #[serde(untagged)]
+    Stake(ReceiveVirtualStake),
+    /// Splits the staked amount across the validators selected by the strategy, as a batch
+    #[serde(untagged)]
+    Auto(AutoStake),
 }
 
 /// User-related information including user address
@@ -118,6 +255,82 @@ pub struct PendingRewards {
     pub rewards: Coin,
 }
 
+/// Response for pending rewards query in all the rewards denoms, the main one first
+#[cw_serde]
+pub struct PendingRewardsByDenom {
+    pub rewards: Vec<Coin>,
+}
+
+/// Response for auto-compound query
+#[cw_serde]
+pub struct AutoCompoundResponse {
+    pub enabled: bool,
+}
+
+/// Response for default validator query
+#[cw_serde]
+pub struct DefaultValidatorResponse {
+    pub validator: Option<String>,
+}
+
+/// Response for idempotency key query
+#[cw_serde]
+pub struct IdempotencyKeyResponse {
+    /// Whether a stake or unstake with this key was already processed
+    pub used: bool,
+}
+
+/// Response for tx history query
+#[cw_serde]
+pub struct TxHistoryResponse {
+    /// Ordered by stake tx id
+    pub txs: Vec<StakeTx>,
+}
+
+/// Response for validator capacity query
+#[cw_serde]
+pub struct ValidatorCapacityResponse {
+    pub validator: String,
+    /// Total stake on the validator, including the pending stake additions
+    pub total_stake: Uint128,
+    /// Max cross-stake set by the consumer, if any
+    pub max_stake: Option<Uint128>,
+    /// Stake the validator can still receive, if capped
+    pub remaining: Option<Uint128>,
+}
+
+/// Response for validator health query
+#[cw_serde]
+pub struct ValidatorHealthResponse {
+    pub validator: String,
+    /// Last signing performance reported by the consumer, if any
+    pub health: Option<ValidatorHealth>,
+    /// Uptime under which the stakers are warned, if set
+    pub min_uptime: Option<Decimal>,
+    /// Whether the last reported uptime is under `min_uptime`
+    pub below_min: bool,
+}
+
+/// Response for reward dust query
+#[cw_serde]
+pub struct RewardDustResponse {
+    pub owner: String,
+    pub denom: String,
+    /// Sub-unit rewards accumulated by the owner, in tokens
+    pub dust: Decimal,
+    /// Whole tokens the dust has to reach to be paid out with the next withdrawal
+    pub threshold: Uint128,
+}
+
+/// Response for queued unstakes query
+#[cw_serde]
+pub struct QueuedUnstakesResponse {
+    /// Height of the block the queue was started in, if any unstake is queued
+    pub since: Option<u64>,
+    /// Tx ids of the queued unstakes, in order
+    pub tx_ids: Vec<u64>,
+}
+
 /// Response for pending rewards query on all validator
 #[cw_serde]
 pub struct AllPendingRewards {
@@ -147,3 +360,80 @@ pub type TxResponse = mesh_sync::Tx;
 pub struct AllTxsResponse {
     pub txs: Vec<TxResponse>,
 }
+
+/// Packets sent by test code, which can't handle IBC
+#[cw_serde]
+pub struct SentPacketsResponse {
+    pub packets: Vec<ProviderPacket>,
+}
+
+#[cw_serde]
+pub struct ProcessedPacketInfo {
+    /// IBC sequence of the packet
+    pub sequence: u64,
+    pub packet_type: String,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+#[cw_serde]
+pub struct ProcessedPacketsResponse {
+    pub packets: Vec<ProcessedPacketInfo>,
+}
+
+/// Range of consecutive sequences not received, both ends included
+#[cw_serde]
+pub struct SequenceRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+#[cw_serde]
+pub struct MissingSequencesResponse {
+    /// Highest sequence received on the channel, 0 if none yet
+    pub last_received: u64,
+    pub missing: Vec<SequenceRange>,
+}
+
+#[cw_serde]
+pub struct PendingPacketInfo {
+    /// IBC sequence of the timed out packet
+    pub sequence: u64,
+    pub packet: ProviderPacket,
+    pub timed_out_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct PendingPacketsResponse {
+    pub packets: Vec<PendingPacketInfo>,
+}
+
+#[cw_serde]
+pub struct PendingSlashInfo {
+    /// Consumer height of the infraction
+    pub infraction_height: u64,
+    pub slash_ratio: Decimal,
+    pub effective_slash_ratio: Decimal,
+    /// Last user whose stake was slashed already, if any
+    pub last_user: Option<String>,
+}
+
+#[cw_serde]
+pub struct PendingSlashesResponse {
+    pub slashes: Vec<PendingSlashInfo>,
+}
+
+#[cw_serde]
+pub struct SnapshotInfo {
+    pub validator: String,
+    pub epoch: u64,
+    pub time: Timestamp,
+    pub stake: Uint128,
+    pub accumulated_rewards: Uint128,
+}
+
+#[cw_serde]
+pub struct SnapshotsResponse {
+    /// Ordered by epoch, then validator
+    pub snapshots: Vec<SnapshotInfo>,
+}
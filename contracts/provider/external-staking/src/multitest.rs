@@ -2,7 +2,11 @@ mod utils;
 
 use anyhow::Result as AnyResult;
 
-use cosmwasm_std::{coin, coins, to_json_binary, Decimal, Uint128};
+use cosmwasm_std::{
+    coin, coins, to_json_binary, Addr, Binary, Coin, Decimal, Timestamp, Uint128, Uint64,
+    VoteOption,
+};
+use cw_utils::PaymentError;
 use mesh_native_staking::contract::sv::mt::CodeId as NativeStakingCodeId;
 use mesh_native_staking::contract::sv::InstantiateMsg as NativeStakingInstantiateMsg;
 use mesh_native_staking_proxy::contract::sv::mt::CodeId as NativeStakingProxyCodeId;
@@ -12,19 +16,29 @@ use mesh_vault::msg::{LocalStakingInfo, StakingInitInfo};
 
 use mesh_sync::ValueRange;
 
-use cw_multi_test::App as MtApp;
+use cw_multi_test::{App as MtApp, Executor};
 use sylvia::multitest::{App, Proxy};
 
 use crate::contract::sv::mt::ExternalStakingContractProxy;
 use crate::test_methods::sv::mt::TestMethodsProxy;
+use mesh_apis::converter_api::RewardInfo;
 use mesh_apis::cross_staking_api::sv::mt::CrossStakingApiProxy;
+use mesh_apis::error::MeshError;
+use mesh_apis::ibc::{AddValidator, BatchedUnstake, ProviderPacket, StakeChecksum, VoteWeight};
 use mesh_vault::contract::sv::mt::VaultContractProxy;
 
 use crate::contract::sv::mt::CodeId;
-use crate::contract::ExternalStakingContract;
+use crate::contract::{ExternalStakingContract, MAX_PREFERRED_VALIDATORS};
 use crate::error::ContractError;
-use crate::msg::{AuthorizedEndpoint, ReceiveVirtualStake, StakeInfo, ValidatorPendingRewards};
-use crate::state::{SlashRatio, Stake};
+use crate::msg::{
+    AuthorizedEndpoint, AutoStake, BatchStake, ReceiveVirtualStake, ReceiveVirtualStakeMsg,
+    StakeInfo, UnbondingBucket, UnbondingScheduleResponse, ValidatorPendingRewards,
+    ValidatorSelection, VaultInfo,
+};
+use crate::state::{
+    InstantUnstakeConfig, PenaltyDestination, ProtocolFee, RewardsTransferConfig, SlashRatio,
+    Stake, StakeTxKind, StakeTxStatus, ValidatorPreferences,
+};
 use utils::{
     assert_rewards, get_last_external_staking_pending_tx_id, AppExt as _, ContractExt as _,
     VaultExt as _,
@@ -60,6 +74,7 @@ fn setup<'app>(
         proxy_code_id: native_staking_proxy_code.code_id(),
         slash_ratio_dsign: Decimal::percent(LOCAL_SLASHING_PERCENTAGE_DSIGN),
         slash_ratio_offline: Decimal::percent(LOCAL_SLASHING_PERCENTAGE_OFFLINE),
+        owner: None,
     };
 
     let staking_init = StakingInitInfo {
@@ -70,7 +85,13 @@ fn setup<'app>(
     };
 
     let vault = vault_code
-        .instantiate(OSMO.to_owned(), Some(LocalStakingInfo::New(staking_init)))
+        .instantiate(
+            OSMO.to_owned(),
+            Some(LocalStakingInfo::New(staking_init)),
+            None,
+            None,
+            None,
+        )
         .call(owner)?;
 
     let remote_contact = AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz");
@@ -86,6 +107,10 @@ fn setup<'app>(
                 double_sign: Decimal::percent(SLASHING_PERCENTAGE),
                 offline: Decimal::percent(SLASHING_PERCENTAGE),
             },
+            None,
+            0,
+            None,
+            None,
         )
         .call(owner)?;
 
@@ -138,10 +163,7 @@ fn staking() {
 
     /*
     // Fail to stake on non-registered validator
-    let msg = to_json_binary(&ReceiveVirtualStake {
-        validator: "unknown".to_string(),
-    })
-    .unwrap();
+    let msg = ReceiveVirtualStake::new("unknown").encode().unwrap();
     println!("START");
     // FIXME: Sylvia panics here, with this line in ExecProxy::call
     //             .map_err(|err| err.downcast().unwrap())
@@ -219,14 +241,12 @@ fn staking() {
 }
 
 #[test]
-fn unstaking() {
-    let users = ["user1", "user2"];
-
-    let app =
-        App::new_with_balances(&[(users[0], &coins(300, OSMO)), (users[1], &coins(300, OSMO))]);
-
+fn staking_batch() {
+    let user = "user1";
     let owner = "owner";
 
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
     let (vault, contract) = setup(&app, owner, 100).unwrap();
 
     let validators = contract.activate_validators(["validator1", "validator2"]);
@@ -234,238 +254,357 @@ fn unstaking() {
     vault
         .bond()
         .with_funds(&coins(300, OSMO))
-        .call(users[0])
+        .call(user)
         .unwrap();
 
-    vault
-        .bond()
-        .with_funds(&coins(300, OSMO))
-        .call(users[1])
-        .unwrap();
+    let batch = |amounts: &[u128]| {
+        to_json_binary(&ReceiveVirtualStakeMsg::StakeBatch {
+            stakes: std::iter::zip(validators, amounts)
+                .map(|(validator, amount)| BatchStake {
+                    validator: validator.to_owned(),
+                    amount: Uint128::new(*amount),
+                })
+                .collect(),
+            idempotency_key: None,
+        })
+        .unwrap()
+    };
 
-    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
-    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
-    vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
+    // Amounts have to add up
+    let err = contract
+        .receive_virtual_stake(user.to_owned(), coin(100, OSMO), 1, batch(&[50, 60]))
+        .call(vault.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidBatchAmount(Uint128::new(100)));
 
-    // Properly unstake some tokens
-    // users[0] unstakes 50 from validators[0] - 150 left staken in 2 batches
-    // users[1] usntakes 60 from validators[0] - 240 left staken
-    contract
-        .unstake(validators[0].to_string(), coin(20, OSMO))
-        .call(users[0])
+    // Single tx for the whole batch
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(150, OSMO),
+            batch(&[50, 100]),
+        )
+        .call(user)
         .unwrap();
+    let txs = contract.all_pending_txs_desc(None, None).unwrap().txs;
+    assert_eq!(txs.len(), 1);
     contract
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .test_commit_stake(txs[0].id())
         .call("test")
         .unwrap();
 
-    contract
-        .unstake(validators[0].to_string(), coin(30, OSMO))
-        .call(users[0])
+    let stake = contract
+        .stake(user.to_owned(), validators[0].to_owned())
         .unwrap();
-    contract
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
-        .call("test")
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(50)));
+    let stake = contract
+        .stake(user.to_owned(), validators[1].to_owned())
         .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
 
-    contract
-        .unstake(validators[0].to_string(), coin(60, OSMO))
-        .call(users[1])
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(150)));
+
+    // Rolling back reverts all the stakes of the batch
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(150, OSMO),
+            batch(&[100, 50]),
+        )
+        .call(user)
         .unwrap();
     contract
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .test_rollback_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
         .call("test")
         .unwrap();
 
-    // Trying some unstakes over what is staken fails
-    let err = contract
-        .unstake(validators[1].to_string(), coin(110, OSMO))
-        .call(users[0])
-        .unwrap_err();
-    assert_eq!(err, ContractError::NotEnoughStake(100u128.into()));
+    let stakes = contract.stakes(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        stakes.stakes,
+        [
+            StakeInfo::new(user, validators[0], &Stake::from_amount(50u128.into())),
+            StakeInfo::new(user, validators[1], &Stake::from_amount(100u128.into()))
+        ]
+    );
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(150)));
+}
 
-    let err = contract
-        .unstake(validators[0].to_string(), coin(300, OSMO))
-        .call(users[1])
-        .unwrap_err();
-    assert_eq!(err, ContractError::NotEnoughStake(240u128.into()));
+#[test]
+fn staking_auto() {
+    let user = "user1";
+    let owner = "owner";
 
-    let err = contract
-        .unstake(validators[1].to_string(), coin(1, OSMO))
-        .call(users[1])
-        .unwrap_err();
-    assert_eq!(err, ContractError::NotEnoughStake(0u128.into()));
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
 
-    // Unstaken should be immediately visible on staken amount
-    let stake = contract
-        .stake(users[0].to_string(), validators[0].to_string())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(150)));
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    let stake = contract
-        .stake(users[0].to_string(), validators[1].to_string())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
 
-    let stake = contract
-        .stake(users[1].to_string(), validators[0].to_string())
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(240)));
+    vault.stake(&contract, user, validators[0], coin(300, OSMO));
+
+    let stake_auto = |amount: u128, strategy: ValidatorSelection| -> AnyResult<()> {
+        vault
+            .stake_remote_auto(
+                contract.contract_addr.to_string(),
+                coin(amount, OSMO),
+                strategy,
+            )
+            .call(user)?;
+        contract
+            .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")?;
+        Ok(())
+    };
+    let stakes = || -> Vec<u128> {
+        validators
+            .iter()
+            .map(|validator| {
+                contract
+                    .stake(user.to_owned(), validator.to_string())
+                    .unwrap()
+                    .stake
+                    .val()
+                    .unwrap()
+                    .u128()
+            })
+            .collect()
+    };
 
-    let stake = contract
-        .stake(users[1].to_string(), validators[1].to_string())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+    // Evenly across the most staked validators, in address order for the same stake
+    stake_auto(100, ValidatorSelection::TopN { n: 2 }).unwrap();
+    assert_eq!(stakes(), [350, 50, 0]);
 
-    // But not on vault side
-    let claim = vault
-        .claim(users[0].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+    // In proportion of the stakes, leftovers going to the first validator
+    stake_auto(90, ValidatorSelection::ProportionalToPower {}).unwrap();
+    assert_eq!(stakes(), [429, 61, 0]);
 
-    let claim = vault
-        .claim(users[1].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+    // In proportion of the given weights
+    let weights = vec![(validators[2].to_owned(), 1), (validators[1].to_owned(), 3)];
+    stake_auto(40, ValidatorSelection::Custom(weights)).unwrap();
+    assert_eq!(stakes(), [429, 91, 10]);
 
-    // Immediately withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    let msg = AutoStake::new(ValidatorSelection::Custom(vec![(
+        validators[2].to_owned(),
+        0,
+    )]))
+    .encode()
+    .unwrap();
+    let err = contract
+        .receive_virtual_stake(user.to_owned(), coin(10, OSMO), 1, msg)
+        .call(vault.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidValidatorSelection("no validator to stake on".to_owned())
+    );
+}
 
-    // Claims still not changed on the vault side
-    let claim = vault
-        .claim(users[0].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+#[test]
+fn validator_preferences() {
+    let user = "user1";
+    let owner = "owner";
 
-    let claim = vault
-        .claim(users[1].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
 
-    // Very short travel in future - too short for claims to release
-    app.app_mut().update_block(|block| {
-        block.height += 1;
-        block.time = block.time.plus_seconds(50);
-    });
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    // Withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
 
-    // Claims still not changed on the vault side - withdrawal to early
-    let claim = vault
-        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
         .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
 
-    let claim = vault
-        .claim(users[1].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+    let excluded = |validator: &str| ContractError::ValidatorExcluded {
+        user: user.to_owned(),
+        validator: validator.to_owned(),
+    };
+    let receive_stake = |msg: Binary| {
+        contract
+            .receive_virtual_stake(user.to_owned(), coin(10, OSMO), 1, msg)
+            .call(vault.contract_addr.as_str())
+    };
 
-    // Adding some more unstakes
-    // users[0] unstakes 70 from validators[0] - 80 left staken
-    // users[1] unstakes 90 from validators[1] = 10 left staken
-    contract
-        .unstake(validators[0].to_owned(), coin(70, OSMO))
-        .call(users[0])
-        .unwrap();
     contract
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
-        .call("test")
+        .set_validator_preferences(vec![validators[0].to_owned()], None)
+        .call(user)
         .unwrap();
+    assert_eq!(
+        contract.validator_preferences(user.to_owned()).unwrap(),
+        ValidatorPreferences {
+            denylist: vec![validators[0].to_owned()],
+            allowlist: None,
+        }
+    );
 
-    contract
-        .unstake(validators[1].to_owned(), coin(90, OSMO))
-        .call(users[0])
+    // Denied validators can't be staked to, explicitly or as the default validator
+    let err = receive_stake(ReceiveVirtualStake::new(validators[0]).encode().unwrap()).unwrap_err();
+    assert_eq!(err, excluded(validators[0]));
+    let err = contract
+        .set_default_validator(Some(validators[0].to_owned()))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, excluded(validators[0]));
+
+    // Automatic selections skip them
+    vault
+        .stake_remote_auto(
+            contract.contract_addr.to_string(),
+            coin(100, OSMO),
+            ValidatorSelection::TopN { n: 2 },
+        )
+        .call(user)
         .unwrap();
     contract
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
         .call("test")
         .unwrap();
+    let stakes = contract.stakes(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        stakes.stakes,
+        [
+            StakeInfo::new(user, validators[1], &Stake::from_amount(150u128.into())),
+            StakeInfo::new(user, validators[2], &Stake::from_amount(50u128.into()))
+        ]
+    );
 
-    // Verify proper stake values
-    let stake = contract
-        .stake(users[0].to_string(), validators[0].to_string())
+    // Only the allowed validators can be staked to
+    contract
+        .set_validator_preferences(vec![], Some(vec![validators[2].to_owned()]))
+        .call(user)
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(80)));
+    let err = receive_stake(ReceiveVirtualStake::new(validators[1]).encode().unwrap()).unwrap_err();
+    assert_eq!(err, excluded(validators[1]));
+    receive_stake(ReceiveVirtualStake::new(validators[2]).encode().unwrap()).unwrap();
 
-    let stake = contract
-        .stake(users[0].to_string(), validators[1].to_string())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(10)));
+    let err = contract
+        .set_validator_preferences(
+            vec!["validator".to_owned(); MAX_PREFERRED_VALIDATORS + 1],
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::TooManyPreferredValidators(MAX_PREFERRED_VALIDATORS)
+    );
 
-    let stake = contract
-        .stake(users[1].to_string(), validators[0].to_string())
+    // Cleared preferences allow every validator again
+    contract
+        .set_validator_preferences(vec![], None)
+        .call(user)
         .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(240)));
+    assert_eq!(
+        contract.validator_preferences(user.to_owned()).unwrap(),
+        ValidatorPreferences::default()
+    );
+    vault.stake(&contract, user, validators[0], coin(10, OSMO));
+}
 
-    let stake = contract
-        .stake(users[1].to_string(), validators[1].to_string())
-        .unwrap();
-    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(0)));
+#[test]
+fn staking_to_default_validator() {
+    let user = "user1";
+    let owner = "owner";
 
-    // Another timetravel - just enough for first batch of stakes to release,
-    // too early for second batch
-    app.app_mut().update_block(|block| {
-        block.height += 1;
-        block.time = block.time.plus_seconds(50);
-    });
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
 
-    // Withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    // Now claims on vault got reduced, but only for first batch amount
-    let claim = vault
-        .claim(users[0].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 250);
+    let validators = contract.activate_validators(["validator1", "validator2"]);
 
-    let claim = vault
-        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
         .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 240);
 
-    // Moving forward more, passing through second bath pending duration
-    app.app_mut().update_block(|block| {
-        block.height += 1;
-        block.time = block.time.plus_seconds(60);
-    });
+    // No default validator yet, so an empty msg can't be staked
+    assert_eq!(
+        contract
+            .default_validator(user.to_owned())
+            .unwrap()
+            .validator,
+        None
+    );
+    let err = contract
+        .receive_virtual_stake(user.to_owned(), coin(100, OSMO), 1, Binary::default())
+        .call(vault.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoDefaultValidator(user.to_owned()));
 
-    // Nothing gets released automatically, values just like before
-    let claim = vault
-        .claim(users[0].to_owned(), contract.contract_addr.to_string())
-        .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 250);
+    // Only active validators can be the default
+    let err = contract
+        .set_default_validator(Some("unknown".to_owned()))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ValidatorNotActive("unknown".to_owned()));
 
-    let claim = vault
-        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+    contract
+        .set_default_validator(Some(validators[1].to_owned()))
+        .call(user)
         .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 240);
-
-    // Withdrawing liens
-    contract.withdraw_unbonded().call(users[0]).unwrap();
-    contract.withdraw_unbonded().call(users[1]).unwrap();
+    assert_eq!(
+        contract
+            .default_validator(user.to_owned())
+            .unwrap()
+            .validator,
+        Some(validators[1].to_owned())
+    );
 
-    // Now everything is released
-    let claim = vault
-        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+    // Empty stake msgs go to the default validator, explicit ones are untouched
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(100, OSMO),
+            Binary::default(),
+        )
+        .call(user)
         .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 90);
-
-    let claim = vault
-        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+    contract
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
         .unwrap();
-    assert_eq!(claim.amount.val().unwrap().u128(), 240);
+    vault.stake(&contract, user, validators[0], coin(50, OSMO));
+
+    let stakes = contract.stakes(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        stakes.stakes,
+        [
+            StakeInfo::new(user, validators[0], &Stake::from_amount(50u128.into())),
+            StakeInfo::new(user, validators[1], &Stake::from_amount(100u128.into()))
+        ]
+    );
+
+    // Unset
+    contract.set_default_validator(None).call(user).unwrap();
+    assert_eq!(
+        contract
+            .default_validator(user.to_owned())
+            .unwrap()
+            .validator,
+        None
+    );
 }
 
 #[test]
-fn immediate_unstake_if_unbonded_validator() {
-    let user = "user1";
+fn idempotency_keys() {
+    let users = ["user1", "user2"];
 
-    let app = App::new_with_balances(&[(user, &coins(200, OSMO))]);
+    let app =
+        App::new_with_balances(&[(users[0], &coins(300, OSMO)), (users[1], &coins(300, OSMO))]);
 
     let owner = "owner";
 
@@ -473,101 +612,304 @@ fn immediate_unstake_if_unbonded_validator() {
 
     let validators = contract.activate_validators(["validator1"]);
 
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(300, OSMO))
+            .call(user)
+            .unwrap();
+    }
+
+    // A stake with a key can't be replayed by the same user
+    let stake_msg = ReceiveVirtualStake::new(validators[0])
+        .with_idempotency_key("stake-1")
+        .encode()
+        .unwrap();
     vault
-        .bond()
-        .with_funds(&coins(200, OSMO))
-        .call(user)
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(100, OSMO),
+            stake_msg.clone(),
+        )
+        .call(users[0])
         .unwrap();
-    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    contract
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    assert!(
+        contract
+            .idempotency_key(users[0].to_owned(), "stake-1".to_owned())
+            .unwrap()
+            .used
+    );
+    assert!(
+        !contract
+            .idempotency_key(users[1].to_owned(), "stake-1".to_owned())
+            .unwrap()
+            .used
+    );
 
-    contract.remove_validator(validators[0]);
+    let err = contract
+        .receive_virtual_stake(users[0].to_owned(), coin(100, OSMO), 1, stake_msg.clone())
+        .call(vault.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::DuplicateIdempotencyKey("stake-1".to_owned())
+    );
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(100, OSMO),
+            stake_msg,
+        )
+        .call(users[1])
+        .unwrap();
 
+    // The key of a stake rolled back on an error ack is released, so the stake can be retried
+    let stake_msg = ReceiveVirtualStake::new(validators[0])
+        .with_idempotency_key("stake-2")
+        .encode()
+        .unwrap();
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(100, OSMO),
+            stake_msg.clone(),
+        )
+        .call(users[0])
+        .unwrap();
     contract
-        .unstake(validators[0].to_string(), coin(200, OSMO))
-        .call(user)
+        .test_rollback_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    assert!(
+        !contract
+            .idempotency_key(users[0].to_owned(), "stake-2".to_owned())
+            .unwrap()
+            .used
+    );
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(100, OSMO),
+            stake_msg,
+        )
+        .call(users[0])
         .unwrap();
     contract
-        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
         .call("test")
         .unwrap();
-    contract.withdraw_unbonded().call(user).unwrap();
+    assert!(
+        contract
+            .idempotency_key(users[0].to_owned(), "stake-2".to_owned())
+            .unwrap()
+            .used
+    );
 
-    let err = vault
-        .claim(user.to_string(), contract.contract_addr.to_string())
+    // Unstakes with a key can't be replayed either
+    contract
+        .unstake(
+            validators[0].to_owned(),
+            coin(10, OSMO),
+            Some("unstake-1".to_owned()),
+        )
+        .call(users[0])
+        .unwrap();
+    let err = contract
+        .unstake(
+            validators[0].to_owned(),
+            coin(10, OSMO),
+            Some("unstake-1".to_owned()),
+        )
+        .call(users[0])
         .unwrap_err();
-    assert!(err
-        .to_string()
-        .contains(&mesh_vault::error::ContractError::NoClaim.to_string()));
+    assert_eq!(
+        err,
+        ContractError::DuplicateIdempotencyKey("unstake-1".to_owned())
+    );
+    let err = contract
+        .unstake(
+            validators[0].to_owned(),
+            coin(10, OSMO),
+            Some(String::new()),
+        )
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidIdempotencyKey(64));
+
+    // Unstakes without a key are not deduplicated
+    contract
+        .unstake(validators[0].to_owned(), coin(10, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    contract
+        .unstake(validators[0].to_owned(), coin(10, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    let stake = contract
+        .stake(users[0].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake.low(), Uint128::new(170));
+
+    // The key of a rolled back unstake is released as well
+    contract
+        .unstake(
+            validators[0].to_owned(),
+            coin(10, OSMO),
+            Some("unstake-2".to_owned()),
+        )
+        .call(users[0])
+        .unwrap();
+    contract
+        .test_rollback_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    contract
+        .unstake(
+            validators[0].to_owned(),
+            coin(10, OSMO),
+            Some("unstake-2".to_owned()),
+        )
+        .call(users[0])
+        .unwrap();
+    assert!(
+        contract
+            .idempotency_key(users[0].to_owned(), "unstake-2".to_owned())
+            .unwrap()
+            .used
+    );
 }
 
 #[test]
-fn immediate_unstake_if_tombstoned_validator() {
-    let user = "user1";
+fn tx_history() {
+    let users = ["user1", "user2"];
 
-    let app = App::new_with_balances(&[(user, &coins(200, OSMO))]);
+    let app = App::new_with_balances(&[(users[0], &coins(300, OSMO))]);
 
     let owner = "owner";
 
     let (vault, contract) = setup(&app, owner, 100).unwrap();
 
-    let validators = contract.activate_validators(["validator1"]);
+    let validator = contract.activate_validators(["validator1"])[0];
 
     vault
         .bond()
-        .with_funds(&coins(200, OSMO))
-        .call(user)
+        .with_funds(&coins(300, OSMO))
+        .call(users[0])
         .unwrap();
-    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[0], validator, coin(200, OSMO));
 
-    contract.tombstone_validator(validators[0]);
+    let stake_tx_id = |resp: &cw_multi_test::AppResponse| {
+        resp.events
+            .iter()
+            .flat_map(|e| &e.attributes)
+            .find(|a| a.key == "stake_tx_id")
+            .map(|a| a.value.clone())
+    };
 
+    // Every operation gets the next stake tx id, pending until acked
+    let resp = contract
+        .unstake(validator.to_owned(), coin(50, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    assert_eq!(stake_tx_id(&resp), Some("1".to_owned()));
     contract
-        .unstake(validators[0].to_string(), coin(200, OSMO))
-        .call(user)
+        .test_rollback_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    let resp = contract
+        .unstake(validator.to_owned(), coin(20, OSMO), None)
+        .call(users[0])
         .unwrap();
+    assert_eq!(stake_tx_id(&resp), Some("2".to_owned()));
     contract
         .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
         .call("test")
         .unwrap();
-    contract.withdraw_unbonded().call(user).unwrap();
 
-    let err = vault
-        .claim(user.to_string(), contract.contract_addr.to_string())
-        .unwrap_err();
-    assert!(err
-        .to_string()
-        .contains(&mesh_vault::error::ContractError::NoClaim.to_string()));
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    let resp = contract.withdraw_unbonded().call(users[0]).unwrap();
+    assert_eq!(stake_tx_id(&resp), Some("3".to_owned()));
+
+    let txs = contract
+        .tx_history(users[0].to_owned(), None, None)
+        .unwrap()
+        .txs;
+    let summary: Vec<_> = txs
+        .iter()
+        .map(|tx| {
+            (
+                tx.stake_tx_id,
+                tx.kind.clone(),
+                tx.amount.amount.u128(),
+                tx.status.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (0, StakeTxKind::Stake, 200, StakeTxStatus::Committed),
+            (1, StakeTxKind::Unstake, 50, StakeTxStatus::RolledBack),
+            (2, StakeTxKind::Unstake, 20, StakeTxStatus::Committed),
+            (
+                3,
+                StakeTxKind::WithdrawUnbonded,
+                20,
+                StakeTxStatus::Committed
+            ),
+        ]
+    );
+    assert_eq!(txs[1].validator, Some(validator.to_owned()));
+    assert!(txs[1].tx_id.is_some());
+    assert_eq!(txs[3].validator, None);
+    assert_eq!(txs[3].tx_id, None);
+
+    // Paginated by stake tx id
+    let txs = contract
+        .tx_history(users[0].to_owned(), Some(2), None)
+        .unwrap()
+        .txs;
+    assert_eq!(txs.len(), 1);
+    assert_eq!(txs[0].stake_tx_id, 3);
+
+    // Other users have no history
+    let txs = contract
+        .tx_history(users[1].to_owned(), None, None)
+        .unwrap()
+        .txs;
+    assert!(txs.is_empty());
 }
 
 #[test]
-fn distribution() {
-    let owner = "owner";
+fn unstaking() {
     let users = ["user1", "user2"];
-    let remote = ["remote1", "remote2"];
 
-    let app = App::new_with_balances(&[
-        (users[0], &coins(600, OSMO)),
-        (users[1], &coins(600, OSMO)),
-        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
-    ]);
+    let app =
+        App::new_with_balances(&[(users[0], &coins(300, OSMO)), (users[1], &coins(300, OSMO))]);
+
+    let owner = "owner";
 
     let (vault, contract) = setup(&app, owner, 100).unwrap();
 
     let validators = contract.activate_validators(["validator1", "validator2"]);
 
-    // Weights proportion:
-    // 2/5 of validators[0] to users[0]
-    // 3/5 of validators[0] to users[1]
-    // all of validators[1] to users[1]
     vault
         .bond()
-        .with_funds(&coins(600, OSMO))
+        .with_funds(&coins(300, OSMO))
         .call(users[0])
         .unwrap();
 
     vault
         .bond()
-        .with_funds(&coins(600, OSMO))
+        .with_funds(&coins(300, OSMO))
         .call(users[1])
         .unwrap();
 
@@ -575,39 +917,1119 @@ fn distribution() {
     vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
     vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
 
-    // Start with equal distribution:
-    // 20 tokens for users[0]
-    // 30 tokens for users[1]
+    // Properly unstake some tokens
+    // users[0] unstakes 50 from validators[0] - 150 left staken in 2 batches
+    // users[1] usntakes 60 from validators[0] - 240 left staken
     contract
-        .test_distribute_rewards(validators[0].to_owned(), coin(50, STAR))
-        .call(owner)
+        .unstake(validators[0].to_string(), coin(20, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
         .unwrap();
 
-    // Only users[0] stakes on validators[1]
-    // 30 tokens for users[0]
     contract
-        .test_distribute_rewards(validators[1].to_owned(), coin(30, STAR))
-        .call(owner)
+        .unstake(validators[0].to_string(), coin(30, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
         .unwrap();
 
-    // Check how much rewards are pending for withdrawal
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 20);
+    contract
+        .unstake(validators[0].to_string(), coin(60, OSMO), None)
+        .call(users[1])
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    // Trying some unstakes over what is staken fails
+    let err = contract
+        .unstake(validators[1].to_string(), coin(110, OSMO), None)
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotEnoughStake(100u128.into()));
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    let err = contract
+        .unstake(validators[0].to_string(), coin(300, OSMO), None)
+        .call(users[1])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotEnoughStake(240u128.into()));
+
+    let err = contract
+        .unstake(validators[1].to_string(), coin(1, OSMO), None)
+        .call(users[1])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotEnoughStake(0u128.into()));
+
+    // Unstaken should be immediately visible on staken amount
+    let stake = contract
+        .stake(users[0].to_string(), validators[0].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(150)));
+
+    let stake = contract
+        .stake(users[0].to_string(), validators[1].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+
+    let stake = contract
+        .stake(users[1].to_string(), validators[0].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(240)));
+
+    let stake = contract
+        .stake(users[1].to_string(), validators[1].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+
+    // But not on vault side
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+
+    let claim = vault
+        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+
+    // Immediately withdrawing liens
+    contract.withdraw_unbonded().call(users[0]).unwrap();
+    contract.withdraw_unbonded().call(users[1]).unwrap();
+
+    // Claims still not changed on the vault side
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+
+    let claim = vault
+        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+
+    // Very short travel in future - too short for claims to release
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(50);
+    });
+
+    // Withdrawing liens
+    contract.withdraw_unbonded().call(users[0]).unwrap();
+    contract.withdraw_unbonded().call(users[1]).unwrap();
+
+    // Claims still not changed on the vault side - withdrawal to early
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+
+    let claim = vault
+        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 300);
+
+    // Adding some more unstakes
+    // users[0] unstakes 70 from validators[0] - 80 left staken
+    // users[1] unstakes 90 from validators[1] = 10 left staken
+    contract
+        .unstake(validators[0].to_owned(), coin(70, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    contract
+        .unstake(validators[1].to_owned(), coin(90, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+
+    // Verify proper stake values
+    let stake = contract
+        .stake(users[0].to_string(), validators[0].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(80)));
+
+    let stake = contract
+        .stake(users[0].to_string(), validators[1].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(10)));
+
+    let stake = contract
+        .stake(users[1].to_string(), validators[0].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(240)));
+
+    let stake = contract
+        .stake(users[1].to_string(), validators[1].to_string())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(0)));
+
+    // Unbondings summed by release time, rounded up to whole seconds
+    let now = app.block_info().time;
+    let bucket_end = |secs: u64| Timestamp::from_seconds(now.plus_seconds(secs).seconds() + 1);
+    let schedule = contract
+        .unbonding_schedule(users[0].to_owned(), None, Some(1))
+        .unwrap();
+    assert_eq!(
+        schedule,
+        UnbondingScheduleResponse {
+            claimable: Uint128::zero(),
+            buckets: vec![
+                UnbondingBucket {
+                    release_at: bucket_end(50),
+                    amount: Uint128::new(50),
+                },
+                UnbondingBucket {
+                    release_at: bucket_end(100),
+                    amount: Uint128::new(160),
+                },
+            ],
+        }
+    );
+    let schedule = contract
+        .unbonding_schedule(users[0].to_owned(), Some(validators[1].to_owned()), Some(1))
+        .unwrap();
+    assert_eq!(
+        schedule.buckets,
+        [UnbondingBucket {
+            release_at: bucket_end(100),
+            amount: Uint128::new(90),
+        }]
+    );
+    // Day long buckets by default
+    let schedule = contract
+        .unbonding_schedule(users[0].to_owned(), None, None)
+        .unwrap();
+    assert_eq!(schedule.buckets.len(), 1);
+    assert_eq!(schedule.buckets[0].amount, Uint128::new(210));
+
+    // Another timetravel - just enough for first batch of stakes to release,
+    // too early for second batch
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(50);
+    });
+
+    // Released unbondings are claimable until withdrawn
+    let schedule = contract
+        .unbonding_schedule(users[0].to_owned(), None, Some(1))
+        .unwrap();
+    assert_eq!(schedule.claimable, Uint128::new(50));
+    assert_eq!(schedule.buckets.len(), 1);
+
+    // Withdrawing liens
+    contract.withdraw_unbonded().call(users[0]).unwrap();
+    contract.withdraw_unbonded().call(users[1]).unwrap();
+
+    // Now claims on vault got reduced, but only for first batch amount
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 250);
+
+    let claim = vault
+        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 240);
+
+    // Moving forward more, passing through second bath pending duration
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(60);
+    });
+
+    // Nothing gets released automatically, values just like before
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 250);
+
+    let claim = vault
+        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 240);
+
+    // Withdrawing liens
+    contract.withdraw_unbonded().call(users[0]).unwrap();
+    contract.withdraw_unbonded().call(users[1]).unwrap();
+
+    // Now everything is released
+    let claim = vault
+        .claim(users[0].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 90);
+
+    let claim = vault
+        .claim(users[1].to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 240);
+}
+
+#[test]
+fn withdraw_unbonded_in_batches() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, _) = setup(&app, owner, 100).unwrap();
+
+    let contract = CodeId::store_code(&app)
+        .instantiate(
+            OSMO.to_owned(),
+            STAR.to_owned(),
+            vault.contract_addr.to_string(),
+            100,
+            AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+            SlashRatio {
+                double_sign: Decimal::percent(SLASHING_PERCENTAGE),
+                offline: Decimal::percent(SLASHING_PERCENTAGE),
+            },
+            None,
+            0,
+            Some(2),
+            None,
+        )
+        .call(owner)
+        .unwrap();
+    assert_eq!(contract.config().unwrap().max_withdraw_batch, Some(2));
+
+    let validators = contract.activate_validators(["validator1", "validator2", "validator3"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+
+    for validator in validators {
+        vault.stake(&contract, user, validator, coin(100, OSMO));
+        contract
+            .unstake(validator.to_owned(), coin(100, OSMO), None)
+            .call(user)
+            .unwrap();
+        contract
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+
+    let has_remaining = |resp: &cw_multi_test::AppResponse| {
+        resp.events
+            .iter()
+            .flat_map(|e| &e.attributes)
+            .any(|a| a.key == "remaining")
+    };
+
+    // First two validators released, one left
+    let resp = contract.withdraw_unbonded().call(user).unwrap();
+    assert!(has_remaining(&resp));
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+
+    // And the last one, removing the lien
+    let resp = contract.withdraw_unbonded().call(user).unwrap();
+    assert!(!has_remaining(&resp));
+    vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap_err();
+}
+
+#[test]
+fn unstake_batching() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(300, OSMO)), (users[1], &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(300, OSMO))
+            .call(user)
+            .unwrap();
+    }
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[1], validators[1], coin(300, OSMO));
+
+    // Only the owner can enable it
+    let err = contract
+        .set_unstake_batching(true)
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    contract.set_unstake_batching(true).call(owner).unwrap();
+    assert!(contract.config().unwrap().unstake_batching);
+
+    let sent = || contract.test_sent_packets().unwrap().packets;
+    let sent_before = sent().len();
+
+    // The unstakes of the block are queued, not sent
+    contract
+        .unstake(validators[0].to_owned(), coin(50, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    let tx1 = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .unstake(validators[1].to_owned(), coin(100, OSMO), None)
+        .call(users[1])
+        .unwrap();
+    let tx2 = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    assert_eq!(sent().len(), sent_before);
+    let queued = contract.queued_unstakes().unwrap();
+    assert_eq!(queued.since, Some(app.app().block_info().height));
+    assert_eq!(queued.tx_ids, [tx1, tx2]);
+
+    // But they are already accounted for
+    let stake = contract
+        .stake(users[0].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(
+        stake.stake,
+        ValueRange::new(Uint128::new(150), Uint128::new(200))
+    );
+
+    // The first unstake of a later block sends them in a single packet
+    app.app_mut().update_block(|block| block.height += 1);
+    contract
+        .unstake(validators[0].to_owned(), coin(20, OSMO), None)
+        .call(users[0])
+        .unwrap();
+    let tx3 = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    let batch = ProviderPacket::UnstakeBatch {
+        unstakes: vec![
+            BatchedUnstake {
+                validator: validators[0].to_owned(),
+                unstake: Uint128::new(50),
+                tx_id: tx1,
+            },
+            BatchedUnstake {
+                validator: validators[1].to_owned(),
+                unstake: Uint128::new(100),
+                tx_id: tx2,
+            },
+        ],
+        denom: OSMO.to_owned(),
+    };
+    assert_eq!(sent().last(), Some(&batch));
+    assert_eq!(contract.queued_unstakes().unwrap().tx_ids, [tx3]);
+
+    // The ack commits all of them
+    contract.test_packet_ack(batch, true).call("test").unwrap();
+    let stake = contract
+        .stake(users[0].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(
+        stake.stake,
+        ValueRange::new(Uint128::new(130), Uint128::new(150))
+    );
+    let stake = contract
+        .stake(users[1].to_owned(), validators[1].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(200)));
+
+    // Anyone can flush the queue right away
+    contract.flush_unstakes().call(users[1]).unwrap();
+    let batch = ProviderPacket::UnstakeBatch {
+        unstakes: vec![BatchedUnstake {
+            validator: validators[0].to_owned(),
+            unstake: Uint128::new(20),
+            tx_id: tx3,
+        }],
+        denom: OSMO.to_owned(),
+    };
+    assert_eq!(sent().last(), Some(&batch));
+    assert_eq!(contract.queued_unstakes().unwrap().since, None);
+
+    // A failed batch rolls back all of its unstakes
+    contract.test_packet_ack(batch, false).call("test").unwrap();
+    let stake = contract
+        .stake(users[0].to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(150)));
+
+    // Disabling it sends the queued unstakes
+    contract
+        .unstake(validators[1].to_owned(), coin(10, OSMO), None)
+        .call(users[1])
+        .unwrap();
+    let tx4 = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract.set_unstake_batching(false).call(owner).unwrap();
+    assert_eq!(
+        sent().last(),
+        Some(&ProviderPacket::UnstakeBatch {
+            unstakes: vec![BatchedUnstake {
+                validator: validators[1].to_owned(),
+                unstake: Uint128::new(10),
+                tx_id: tx4,
+            }],
+            denom: OSMO.to_owned(),
+        })
+    );
+
+    // Unstakes are then sent one by one again
+    contract
+        .unstake(validators[1].to_owned(), coin(10, OSMO), None)
+        .call(users[1])
+        .unwrap();
+    assert!(matches!(
+        sent().last(),
+        Some(ProviderPacket::Unstake { .. })
+    ));
+    assert!(contract.queued_unstakes().unwrap().tx_ids.is_empty());
+}
+
+#[test]
+fn cancel_unbond() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(300, OSMO));
+
+    // Two unstakes of 100, 50 seconds apart
+    for _ in 0..2 {
+        app.app_mut().update_block(|block| {
+            block.height += 1;
+            block.time = block.time.plus_seconds(50);
+        });
+        contract
+            .unstake(validators[0].to_owned(), coin(100, OSMO), None)
+            .call(user)
+            .unwrap();
+        contract
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+
+    // Only the unbonding tokens can be cancelled
+    let err = contract
+        .cancel_unbond(validators[0].to_owned(), coin(250, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotEnoughUnbonding(Uint128::new(200)));
+
+    // Cancelling the whole last unbond, and half of the first one
+    contract
+        .cancel_unbond(validators[0].to_owned(), coin(150, OSMO))
+        .call(user)
+        .unwrap();
+    let stake = contract
+        .stake(user.to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(
+        stake.stake,
+        ValueRange::new(Uint128::new(100), Uint128::new(250))
+    );
+    contract
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    let stake = contract
+        .stake(user.to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(250)));
+
+    // The rest of the first unbond is released
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(50);
+    });
+    contract.withdraw_unbonded().call(user).unwrap();
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 250);
+
+    let err = contract
+        .cancel_unbond(validators[0].to_owned(), coin(1, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotEnoughUnbonding(Uint128::zero()));
+
+    // A failed cancel puts the unbond back
+    contract
+        .unstake(validators[0].to_owned(), coin(50, OSMO), None)
+        .call(user)
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    contract
+        .cancel_unbond(validators[0].to_owned(), coin(20, OSMO))
+        .call(user)
+        .unwrap();
+    contract
+        .test_rollback_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    let stake = contract
+        .stake(user.to_owned(), validators[0].to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(200)));
+
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    contract.withdraw_unbonded().call(user).unwrap();
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 200);
+}
+
+#[test]
+fn stake_checksum() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+
+    // Nothing staked yet
+    let checksum = contract.stake_checksum().unwrap().checksum;
+    assert_eq!(checksum, Uint64::from(StakeChecksum::default()));
+
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    vault.stake(&contract, user, validators[1], coin(100, OSMO));
+
+    let checksum = contract.stake_checksum().unwrap().checksum;
+    let expected = StakeChecksum::from_stakes([
+        (validators[0], Uint128::new(200)),
+        (validators[1], Uint128::new(100)),
+    ]);
+    assert_eq!(checksum, Uint64::from(expected));
+    contract.send_stake_checksum().call(user).unwrap();
+
+    // Not sent while the consumer may not have the same stakes
+    contract
+        .unstake(validators[0].to_owned(), coin(50, OSMO), None)
+        .call(user)
+        .unwrap();
+    let err = contract.send_stake_checksum().call(user).unwrap_err();
+    assert_eq!(err, ContractError::TxsInFlight);
+
+    // Updated on commit
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    let checksum = contract.stake_checksum().unwrap().checksum;
+    let expected = StakeChecksum::from_stakes([
+        (validators[0], Uint128::new(150)),
+        (validators[1], Uint128::new(100)),
+    ]);
+    assert_eq!(checksum, Uint64::from(expected));
+    contract.send_stake_checksum().call(user).unwrap();
+}
+
+#[test]
+fn vote_remote() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(300, OSMO)), (users[1], &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    // No cross-stake, no voice
+    let err = contract
+        .vote_remote(1, VoteOption::Yes)
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoVotingWeight);
+
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(300, OSMO))
+            .call(user)
+            .unwrap();
+    }
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
+    vault.stake(&contract, users[1], validators[0], coin(100, OSMO));
+
+    // The weight is the stake over all the validators
+    contract
+        .vote_remote(1, VoteOption::Yes)
+        .call(users[0])
+        .unwrap();
+    contract
+        .vote_remote(1, VoteOption::No)
+        .call(users[1])
+        .unwrap();
+    let expected = vec![
+        VoteWeight {
+            option: VoteOption::Yes,
+            weight: Uint128::new(300),
+        },
+        VoteWeight {
+            option: VoteOption::No,
+            weight: Uint128::new(100),
+        },
+    ];
+    assert_eq!(contract.remote_vote_tally(1).unwrap().tally, expected);
+    assert_eq!(
+        contract.test_sent_packets().unwrap().packets.last(),
+        Some(&ProviderPacket::Vote {
+            proposal_id: 1,
+            tally: expected,
+        })
+    );
+
+    // Voting again replaces the previous vote
+    contract
+        .vote_remote(1, VoteOption::No)
+        .call(users[0])
+        .unwrap();
+    let expected = vec![VoteWeight {
+        option: VoteOption::No,
+        weight: Uint128::new(400),
+    }];
+    assert_eq!(contract.remote_vote_tally(1).unwrap().tally, expected);
+    assert_eq!(
+        contract.test_sent_packets().unwrap().packets.last(),
+        Some(&ProviderPacket::Vote {
+            proposal_id: 1,
+            tally: expected,
+        })
+    );
+
+    // Other proposals are tallied separately
+    assert_eq!(contract.remote_vote_tally(2).unwrap().tally, vec![]);
+}
+
+#[test]
+fn stake_snapshots() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+
+    let err = contract.take_snapshots().call(user).unwrap_err();
+    assert_eq!(err, ContractError::SnapshotsDisabled);
+
+    // Only the owner can enable the snapshots
+    let err = contract.set_snapshot_config(100, 2).call(user).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    contract.set_snapshot_config(100, 2).call(owner).unwrap();
+
+    let next_epoch = || {
+        app.app_mut().update_block(|block| {
+            block.time = block.time.plus_seconds(100);
+        })
+    };
+
+    contract.take_snapshots().call(user).unwrap();
+    let err = contract.take_snapshots().call(user).unwrap_err();
+    assert!(matches!(err, ContractError::SnapshotsTaken(_)));
+
+    next_epoch();
+    vault.stake(&contract, user, validators[1], coin(50, OSMO));
+    contract
+        .test_distribute_rewards(validators[0].to_owned(), coin(20, STAR))
+        .call("test")
+        .unwrap();
+    contract.take_snapshots().call(user).unwrap();
+
+    // The first epoch gets overwritten
+    next_epoch();
+    contract
+        .test_distribute_rewards(validators[1].to_owned(), coin(10, STAR))
+        .call("test")
+        .unwrap();
+    contract.take_snapshots().call(user).unwrap();
+
+    let snapshots = contract
+        .snapshots(user.to_owned(), None, None, None)
+        .unwrap()
+        .snapshots;
+    let positions: Vec<_> = snapshots
+        .iter()
+        .map(|snapshot| {
+            (
+                snapshot.validator.as_str(),
+                snapshot.stake.u128(),
+                snapshot.accumulated_rewards.u128(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        positions,
+        [
+            (validators[0], 100, 20),
+            (validators[1], 50, 0),
+            (validators[0], 100, 20),
+            (validators[1], 50, 10),
+        ]
+    );
+    let epoch = snapshots[0].epoch;
+    assert_eq!(snapshots[2].epoch, epoch + 1);
+
+    // Withdrawn rewards are still accounted for
+    contract
+        .withdraw_rewards(validators[0].to_owned(), user.to_owned())
+        .call(user)
+        .unwrap();
+    contract
+        .test_commit_withdraw_rewards(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    next_epoch();
+    contract.take_snapshots().call(user).unwrap();
+
+    let snapshots = contract
+        .snapshots(
+            user.to_owned(),
+            Some(validators[0].to_owned()),
+            Some(epoch + 1),
+            None,
+        )
+        .unwrap()
+        .snapshots;
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].epoch, epoch + 2);
+    assert_eq!(snapshots[0].accumulated_rewards.u128(), 20);
+}
+
+#[test]
+fn immediate_unstake_if_unbonded_validator() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(200, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(200, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+
+    contract.remove_validator(validators[0]);
+
+    contract
+        .unstake(validators[0].to_string(), coin(200, OSMO), None)
+        .call(user)
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    contract.withdraw_unbonded().call(user).unwrap();
+
+    let err = vault
+        .claim(user.to_string(), contract.contract_addr.to_string())
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains(&mesh_vault::error::ContractError::NoClaim.to_string()));
+}
+
+#[test]
+fn immediate_unstake_if_tombstoned_validator() {
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(200, OSMO))]);
+
+    let owner = "owner";
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(200, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+
+    contract.tombstone_validator(validators[0]);
+
+    contract
+        .unstake(validators[0].to_string(), coin(200, OSMO), None)
+        .call(user)
+        .unwrap();
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    contract.withdraw_unbonded().call(user).unwrap();
+
+    let err = vault
+        .claim(user.to_string(), contract.contract_addr.to_string())
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains(&mesh_vault::error::ContractError::NoClaim.to_string()));
+}
+
+#[test]
+fn instant_unstake() {
+    let user = "user1";
+    let owner = "owner";
+
+    let app = App::new_with_balances(&[(user, &coins(400, OSMO)), (owner, &coins(100, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validators[0], coin(200, OSMO));
+
+    let err = contract
+        .unstake_instant(validators[0].to_string(), coin(100, OSMO), None)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InstantUnstakeDisabled);
+    let capabilities = contract.capabilities().unwrap();
+    assert!(!capabilities.instant_unstake);
+    // Rewards are paid in another denom, so can't be restaked
+    assert!(!capabilities.restake);
+    assert_eq!(capabilities.max_validators_per_user, None);
+
+    let config = InstantUnstakeConfig {
+        penalty_rate: Decimal::percent(10),
+        penalty_destination: PenaltyDestination::Burn {},
+        epoch_length: 1000,
+        max_per_epoch: Uint128::new(150),
+    };
+    let err = contract
+        .set_instant_unstake_config(Some(config.clone()))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    contract
+        .set_instant_unstake_config(Some(config))
+        .call(owner)
+        .unwrap();
+    assert!(contract.capabilities().unwrap().instant_unstake);
+
+    // The penalty has to be paid, and the buffer has to cover the max slashing (10%)
+    let err = contract
+        .unstake_instant(validators[0].to_string(), coin(100, OSMO), None)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::WrongPenalty(Uint128::new(10)));
+    let err = contract
+        .unstake_instant(validators[0].to_string(), coin(100, OSMO), None)
+        .with_funds(&coins(10, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBuffer(Uint128::zero()));
+
+    contract
+        .deposit_buffer()
+        .with_funds(&coins(20, OSMO))
+        .call(owner)
+        .unwrap();
+    contract
+        .unstake_instant(validators[0].to_string(), coin(100, OSMO), None)
+        .with_funds(&coins(10, OSMO))
+        .call(user)
+        .unwrap();
+    let buffer = contract.buffer().unwrap();
+    assert_eq!(buffer.total.u128(), 20);
+    assert_eq!(buffer.locked.u128(), 10);
+    assert_eq!(buffer.epoch_used.u128(), 100);
+
+    let err = contract
+        .unstake_instant(validators[0].to_string(), coin(60, OSMO), None)
+        .with_funds(&coins(6, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InstantUnstakeLimit(Uint128::new(50)));
+
+    // Released as soon as acked, and the penalty is burned
+    contract
+        .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount.val().unwrap().u128(), 100);
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&contract.contract_addr, OSMO)
+            .unwrap(),
+        coin(20, OSMO)
+    );
+
+    // Slashing of the instantly unstaked tokens is taken from the buffer
+    contract
+        .test_handle_slashing(validators[0].to_string(), Uint128::new(10))
+        .call("test")
+        .unwrap();
+    let buffer = contract.buffer().unwrap();
+    assert_eq!(buffer.total.u128(), 10);
+    assert_eq!(buffer.locked.u128(), 0);
+    assert_eq!(buffer.slashed.u128(), 10);
+
+    let err = contract
+        .withdraw_buffer(Uint128::new(11))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientBuffer(Uint128::new(10)));
+    contract
+        .withdraw_buffer(Uint128::new(10))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(owner, OSMO).unwrap(),
+        coin(90, OSMO)
+    );
+    // Slashed tokens are burned along
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&contract.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+}
+
+#[test]
+fn distribution() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = ["remote1", "remote2"];
+
+    let app = App::new_with_balances(&[
+        (users[0], &coins(600, OSMO)),
+        (users[1], &coins(600, OSMO)),
+        (owner, &[coin(1000, STAR), coin(1000, OSMO)]),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    // Weights proportion:
+    // 2/5 of validators[0] to users[0]
+    // 3/5 of validators[0] to users[1]
+    // all of validators[1] to users[1]
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[0])
+        .unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[1])
+        .unwrap();
+
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
+    vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
+
+    // Start with equal distribution:
+    // 20 tokens for users[0]
+    // 30 tokens for users[1]
+    contract
+        .test_distribute_rewards(validators[0].to_owned(), coin(50, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Only users[0] stakes on validators[1]
+    // 30 tokens for users[0]
+    contract
+        .test_distribute_rewards(validators[1].to_owned(), coin(30, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Check how much rewards are pending for withdrawal
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 20);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 30);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 30);
 
     let rewards = contract
         .pending_rewards(users[1].to_owned(), validators[1].to_owned())
@@ -805,7 +2227,7 @@ fn distribution() {
     // 200 tokens staken by user[0]
     // 200 tokens staken by user[1]
     contract
-        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .unstake(validators[0].to_owned(), coin(100, OSMO), None)
         .call(users[1])
         .unwrap();
     contract
@@ -823,10 +2245,7 @@ fn distribution() {
         .stake_remote(
             contract.contract_addr.to_string(),
             coin(300, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validators[1].to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validators[1]).encode().unwrap(),
         )
         .call(users[1])
         .unwrap();
@@ -935,7 +2354,7 @@ fn distribution() {
     // 3/5 rewards to users[0]
     // 2/5 rewards to users[1]
     contract
-        .unstake(validators[0].to_owned(), coin(50, OSMO))
+        .unstake(validators[0].to_owned(), coin(50, OSMO), None)
         .call(users[0])
         .unwrap();
     contract
@@ -944,7 +2363,7 @@ fn distribution() {
         .unwrap();
 
     contract
-        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .unstake(validators[0].to_owned(), coin(100, OSMO), None)
         .call(users[1])
         .unwrap();
     contract
@@ -1039,89 +2458,287 @@ fn distribution() {
         .rewards;
     assert_eq!(rewards.amount.u128(), 29);
 
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 0);
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 0);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 30);
+
+    // Final distribution - 10 tokens to both validators
+    // 6 tokens to users[0] via validators[0] (leftover as it was)
+    // 4 tokens to users[1] via validators[0] (leftover as it was)
+    // 2 tokens to users[0] via validators[1] (~0.5 leftover)
+    // 7 tokens to users[1] via validators[1] (~0.5 lefover)
+    contract
+        .test_distribute_rewards(validators[0].to_owned(), coin(10, STAR))
+        .call(owner)
+        .unwrap();
+
+    contract
+        .test_distribute_rewards(validators[1].to_owned(), coin(10, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Check withdrawals and accounts
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 6);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 33);
+
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 2);
+
+    let rewards = contract
+        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 37);
+
+    let all_rewards = contract
+        .all_pending_rewards(users[0].to_owned(), None, None)
+        .unwrap();
+    let expected = vec![
+        ValidatorPendingRewards::new(validators[0], 6, STAR),
+        ValidatorPendingRewards::new(validators[1], 2, STAR),
+    ];
+    assert_eq!(all_rewards.rewards, expected);
+
+    let all_rewards = contract
+        .all_pending_rewards(users[1].to_owned(), None, None)
+        .unwrap();
+    let expected = vec![
+        ValidatorPendingRewards::new(validators[0], 33, STAR),
+        ValidatorPendingRewards::new(validators[1], 37, STAR),
+    ];
+    assert_eq!(all_rewards.rewards, expected);
+
+    // And try to withdraw all, previous balances:
+    contract
+        .withdraw_rewards(validators[0].to_string(), remote[0].to_owned())
+        .call(users[0])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[1].to_string(), remote[0].to_owned())
+        .call(users[0])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[0].to_string(), remote[1].to_owned())
+        .call(users[1])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+
+    contract
+        .withdraw_rewards(validators[1].to_string(), remote[1].to_owned())
+        .call(users[1])
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
+    contract
+        .test_commit_withdraw_rewards(tx_id)
+        .call(users[0])
+        .unwrap();
+}
+
+#[test]
+fn batch_distribution() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(600, OSMO)), (users[1], &coins(600, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[0])
+        .unwrap();
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[1])
+        .unwrap();
+
+    vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
+    vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
+
+    contract
+        .distribute_batch(owner, STAR, &[(validators[0], 50), (validators[1], 30)])
+        .unwrap();
+
+    assert_rewards!(contract, users[0], validators[0], 20);
+    assert_rewards!(contract, users[1], validators[0], 30);
+    assert_rewards!(contract, users[0], validators[1], 30);
+    assert_rewards!(contract, users[1], validators[1], 0);
+
+    contract
+        .distribute_batch(owner, STAR, &[(validators[0], 100), (validators[1], 30)])
+        .unwrap();
+
+    assert_rewards!(contract, users[0], validators[0], 60);
+    assert_rewards!(contract, users[1], validators[0], 90);
+    assert_rewards!(contract, users[0], validators[1], 60);
+    assert_rewards!(contract, users[1], validators[1], 0);
+}
+
+#[test]
+fn batch_distribution_invalid_token() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(user)
+        .unwrap();
+
+    vault.stake(&contract, user, validator, coin(200, OSMO));
+
+    let err = contract
+        .distribute_batch(owner, "supertoken", &[(validator, 50)])
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Mesh(MeshError::InvalidDenom(STAR.to_string()))
+    );
+}
+
+#[test]
+fn extra_rewards_denoms() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = ["remote1", "remote2"];
+    let incentive = "incentive";
+
+    let app =
+        App::new_with_balances(&[(users[0], &coins(600, OSMO)), (users[1], &coins(600, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[0])
+        .unwrap();
+    vault
+        .bond()
+        .with_funds(&coins(600, OSMO))
+        .call(users[1])
+        .unwrap();
+
+    vault.stake(&contract, users[0], validator, coin(200, OSMO));
 
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 30);
+    // Unknown denoms are not distributed
+    let err = contract
+        .test_distribute_rewards(validator.to_owned(), coin(40, incentive))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Payment(PaymentError::MissingDenom(incentive.to_owned()))
+    );
 
-    // Final distribution - 10 tokens to both validators
-    // 6 tokens to users[0] via validators[0] (leftover as it was)
-    // 4 tokens to users[1] via validators[0] (leftover as it was)
-    // 2 tokens to users[0] via validators[1] (~0.5 leftover)
-    // 7 tokens to users[1] via validators[1] (~0.5 lefover)
+    // Only the owner adds rewards denoms, and only once
+    let err = contract
+        .add_rewards_denom(incentive.to_owned())
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
     contract
-        .test_distribute_rewards(validators[0].to_owned(), coin(10, STAR))
+        .add_rewards_denom(incentive.to_owned())
         .call(owner)
         .unwrap();
+    let err = contract
+        .add_rewards_denom(STAR.to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::RewardsDenomExists(STAR.to_owned()));
+    let config = contract.config().unwrap();
+    assert_eq!(config.rewards_denom, STAR);
+    assert_eq!(config.extra_rewards_denoms, [incentive]);
 
+    // Every denom is distributed on its own
     contract
-        .test_distribute_rewards(validators[1].to_owned(), coin(10, STAR))
+        .test_distribute_rewards(validator.to_owned(), coin(20, STAR))
         .call(owner)
         .unwrap();
-
-    // Check withdrawals and accounts
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 6);
-
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[0].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 33);
-
-    let rewards = contract
-        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 2);
-
-    let rewards = contract
-        .pending_rewards(users[1].to_owned(), validators[1].to_owned())
-        .unwrap()
-        .rewards;
-    assert_eq!(rewards.amount.u128(), 37);
-
-    let all_rewards = contract
-        .all_pending_rewards(users[0].to_owned(), None, None)
-        .unwrap();
-    let expected = vec![
-        ValidatorPendingRewards::new(validators[0], 6, STAR),
-        ValidatorPendingRewards::new(validators[1], 2, STAR),
-    ];
-    assert_eq!(all_rewards.rewards, expected);
-
-    let all_rewards = contract
-        .all_pending_rewards(users[1].to_owned(), None, None)
+    contract
+        .test_distribute_rewards(validator.to_owned(), coin(40, incentive))
+        .call(owner)
         .unwrap();
-    let expected = vec![
-        ValidatorPendingRewards::new(validators[0], 33, STAR),
-        ValidatorPendingRewards::new(validators[1], 37, STAR),
-    ];
-    assert_eq!(all_rewards.rewards, expected);
+    let rewards = |user: &str| {
+        contract
+            .pending_rewards_by_denom(user.to_owned(), validator.to_owned())
+            .unwrap()
+            .rewards
+    };
+    assert_eq!(rewards(users[0]), [coin(20, STAR), coin(40, incentive)]);
+    assert_rewards!(contract, users[0], validator, 20);
 
-    // And try to withdraw all, previous balances:
+    // Later stakes are aligned in every denom
+    vault.stake(&contract, users[1], validator, coin(200, OSMO));
     contract
-        .withdraw_rewards(validators[0].to_string(), remote[0].to_owned())
-        .call(users[0])
+        .distribute_batch(owner, incentive, &[(validator, 40)])
         .unwrap();
-    let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
-    contract
-        .test_commit_withdraw_rewards(tx_id)
+    assert_eq!(rewards(users[0]), [coin(20, STAR), coin(60, incentive)]);
+    assert_eq!(rewards(users[1]), [coin(0, STAR), coin(20, incentive)]);
+
+    // Withdrawals are per denom
+    let err = contract
+        .withdraw_denom_rewards(validator.to_owned(), OSMO.to_owned(), remote[0].to_owned())
         .call(users[0])
-        .unwrap();
+        .unwrap_err();
+    assert_eq!(err, ContractError::NotRewardsDenom(OSMO.to_owned()));
 
     contract
-        .withdraw_rewards(validators[1].to_string(), remote[0].to_owned())
+        .withdraw_denom_rewards(
+            validator.to_owned(),
+            incentive.to_owned(),
+            remote[0].to_owned(),
+        )
         .call(users[0])
         .unwrap();
     let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
@@ -1129,40 +2746,53 @@ fn distribution() {
         .test_commit_withdraw_rewards(tx_id)
         .call(users[0])
         .unwrap();
+    assert_eq!(rewards(users[0]), [coin(20, STAR), coin(0, incentive)]);
 
+    // And restored when rolled back
     contract
-        .withdraw_rewards(validators[0].to_string(), remote[1].to_owned())
+        .withdraw_denom_rewards(
+            validator.to_owned(),
+            incentive.to_owned(),
+            remote[1].to_owned(),
+        )
         .call(users[1])
         .unwrap();
     let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
     contract
-        .test_commit_withdraw_rewards(tx_id)
-        .call(users[0])
+        .test_rollback_withdraw_rewards(tx_id)
+        .call(users[1])
         .unwrap();
+    assert_eq!(rewards(users[1]), [coin(0, STAR), coin(20, incentive)]);
 
+    // The main denom is withdrawn as before
     contract
-        .withdraw_rewards(validators[1].to_string(), remote[1].to_owned())
-        .call(users[1])
+        .withdraw_rewards(validator.to_owned(), remote[0].to_owned())
+        .call(users[0])
         .unwrap();
     let tx_id = get_last_external_staking_pending_tx_id(&contract).unwrap();
     contract
         .test_commit_withdraw_rewards(tx_id)
         .call(users[0])
         .unwrap();
+    assert_eq!(rewards(users[0]), [coin(0, STAR), coin(0, incentive)]);
 }
 
 #[test]
-fn batch_distribution() {
+fn rewards_transfer() {
     let owner = "owner";
     let users = ["user1", "user2"];
+    let hook = "hook";
+    let transferred = "ibc/juno";
 
-    let app =
-        App::new_with_balances(&[(users[0], &coins(600, OSMO)), (users[1], &coins(600, OSMO))]);
+    let app = App::new_with_balances(&[
+        (users[0], &coins(600, OSMO)),
+        (users[1], &coins(600, OSMO)),
+        (hook, &coins(1000, transferred)),
+    ]);
 
     let (vault, contract) = setup(&app, owner, 100).unwrap();
 
     let validators = contract.activate_validators(["validator1", "validator2"]);
-
     vault
         .bond()
         .with_funds(&coins(600, OSMO))
@@ -1173,53 +2803,386 @@ fn batch_distribution() {
         .with_funds(&coins(600, OSMO))
         .call(users[1])
         .unwrap();
-
     vault.stake(&contract, users[0], validators[0], coin(200, OSMO));
-    vault.stake(&contract, users[0], validators[1], coin(100, OSMO));
-    vault.stake(&contract, users[1], validators[0], coin(300, OSMO));
+    vault.stake(&contract, users[1], validators[0], coin(200, OSMO));
+    vault.stake(&contract, users[1], validators[1], coin(100, OSMO));
+
+    let rewards = vec![
+        RewardInfo {
+            validator: validators[0].to_owned(),
+            reward: Uint128::new(100),
+        },
+        RewardInfo {
+            validator: validators[1].to_owned(),
+            reward: Uint128::new(30),
+        },
+    ];
+
+    // Transfers are rejected until enabled
+    let err = contract
+        .receive_rewards_transfer(rewards.clone())
+        .with_funds(&coins(130, transferred))
+        .call(hook)
+        .unwrap_err();
+    assert_eq!(err, ContractError::RewardsTransferDisabled);
 
+    let transfer_config = RewardsTransferConfig {
+        sender: hook.to_owned(),
+        denom: transferred.to_owned(),
+    };
+    let err = contract
+        .set_rewards_transfer(Some(transfer_config.clone()))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = contract
+        .set_rewards_transfer(Some(RewardsTransferConfig {
+            sender: hook.to_owned(),
+            denom: STAR.to_owned(),
+        }))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::RewardsDenomExists(STAR.to_owned()));
     contract
-        .distribute_batch(owner, STAR, &[(validators[0], 50), (validators[1], 30)])
+        .set_rewards_transfer(Some(transfer_config.clone()))
+        .call(owner)
         .unwrap();
+    let config = contract.config().unwrap();
+    assert_eq!(config.rewards_transfer, Some(transfer_config));
+    assert_eq!(config.extra_rewards_denoms, [transferred]);
 
-    assert_rewards!(contract, users[0], validators[0], 20);
-    assert_rewards!(contract, users[1], validators[0], 30);
-    assert_rewards!(contract, users[0], validators[1], 30);
-    assert_rewards!(contract, users[1], validators[1], 0);
+    // Only the hook sender, with the exact sum of the rewards
+    let err = contract
+        .receive_rewards_transfer(rewards.clone())
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = contract
+        .receive_rewards_transfer(rewards.clone())
+        .with_funds(&coins(120, transferred))
+        .call(hook)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RewardsTransferMismatch {
+            sum: Uint128::new(130),
+            sent: Uint128::new(120)
+        }
+    );
+
+    contract
+        .receive_rewards_transfer(rewards)
+        .with_funds(&coins(130, transferred))
+        .call(hook)
+        .unwrap();
+    let pending = |user: &str, validator: &str| {
+        contract
+            .pending_rewards_by_denom(user.to_owned(), validator.to_owned())
+            .unwrap()
+            .rewards
+    };
+    assert_eq!(
+        pending(users[0], validators[0]),
+        [coin(0, STAR), coin(50, transferred)]
+    );
+    assert_eq!(
+        pending(users[1], validators[0]),
+        [coin(0, STAR), coin(50, transferred)]
+    );
+    assert_eq!(
+        pending(users[1], validators[1]),
+        [coin(0, STAR), coin(30, transferred)]
+    );
+
+    // Transferred rewards are paid on this chain, without an IBC round trip
+    contract
+        .withdraw_denom_rewards(
+            validators[0].to_owned(),
+            transferred.to_owned(),
+            "remote1".to_owned(),
+        )
+        .call(users[0])
+        .unwrap();
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(users[0], transferred)
+            .unwrap(),
+        coin(50, transferred)
+    );
+    assert_eq!(
+        pending(users[0], validators[0]),
+        [coin(0, STAR), coin(0, transferred)]
+    );
+    let err = contract
+        .withdraw_denom_rewards(
+            validators[0].to_owned(),
+            transferred.to_owned(),
+            "remote1".to_owned(),
+        )
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+
+    // Disabled again, the denom stays a rewards denom
+    contract.set_rewards_transfer(None).call(owner).unwrap();
+    let config = contract.config().unwrap();
+    assert_eq!(config.rewards_transfer, None);
+    assert_eq!(config.extra_rewards_denoms, [transferred]);
+}
+
+#[test]
+fn protocol_fee() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let dao = "dao";
+    let hook = "hook";
+    let transferred = "ibc/juno";
+
+    let app = App::new_with_balances(&[
+        (users[0], &coins(600, OSMO)),
+        (users[1], &coins(600, OSMO)),
+        (hook, &coins(100, transferred)),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    let validators = contract.activate_validators(["validator1"]);
+    for user in users {
+        vault
+            .bond()
+            .with_funds(&coins(600, OSMO))
+            .call(user)
+            .unwrap();
+        vault.stake(&contract, user, validators[0], coin(200, OSMO));
+    }
+
+    let fee = ProtocolFee {
+        rate_bps: 1000,
+        collector: dao.to_owned(),
+    };
+    let err = contract
+        .set_protocol_fee(Some(fee.clone()))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = contract
+        .set_protocol_fee(Some(ProtocolFee {
+            rate_bps: 10_001,
+            collector: dao.to_owned(),
+        }))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidProtocolFee(10_000));
+    contract
+        .set_protocol_fee(Some(fee.clone()))
+        .call(owner)
+        .unwrap();
+    assert_eq!(contract.config().unwrap().protocol_fee, Some(fee));
+
+    // 10% of the rewards are collected, the rest is distributed to the stakers
+    contract
+        .test_distribute_rewards(validators[0].to_owned(), coin(100, STAR))
+        .call(owner)
+        .unwrap();
+    for user in users {
+        let rewards = contract
+            .pending_rewards(user.to_owned(), validators[0].to_owned())
+            .unwrap()
+            .rewards;
+        assert_eq!(rewards, coin(45, STAR));
+    }
+    assert_eq!(contract.fees_collected().unwrap().fees, [coin(10, STAR)]);
+
+    // Only the collector withdraws the fees
+    let err = contract
+        .withdraw_fees(None, "remote".to_owned())
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    contract
+        .withdraw_fees(None, "remote".to_owned())
+        .call(dao)
+        .unwrap();
+    assert_eq!(contract.fees_collected().unwrap().fees, []);
+    let err = contract
+        .withdraw_fees(None, "remote".to_owned())
+        .call(dao)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoFees(STAR.to_owned()));
+
+    // A failed transfer credits the fees back
+    let last_transfer_id = || match contract.test_sent_packets().unwrap().packets.last() {
+        Some(ProviderPacket::TransferRewards { rewards, tx_id, .. }) => {
+            assert_eq!(rewards, &coin(10, STAR));
+            *tx_id
+        }
+        packet => panic!("unexpected packet: {packet:?}"),
+    };
+    contract
+        .test_rollback_withdraw_rewards(last_transfer_id())
+        .call(owner)
+        .unwrap();
+    assert_eq!(contract.fees_collected().unwrap().fees, [coin(10, STAR)]);
+    contract
+        .withdraw_fees(Some(STAR.to_owned()), "remote".to_owned())
+        .call(dao)
+        .unwrap();
+    contract
+        .test_commit_withdraw_rewards(last_transfer_id())
+        .call(owner)
+        .unwrap();
+    assert_eq!(contract.fees_collected().unwrap().fees, []);
+
+    // Fees on the transferred rewards are paid on this chain
+    contract
+        .set_rewards_transfer(Some(RewardsTransferConfig {
+            sender: hook.to_owned(),
+            denom: transferred.to_owned(),
+        }))
+        .call(owner)
+        .unwrap();
+    contract
+        .receive_rewards_transfer(vec![RewardInfo {
+            validator: validators[0].to_owned(),
+            reward: Uint128::new(100),
+        }])
+        .with_funds(&coins(100, transferred))
+        .call(hook)
+        .unwrap();
+    assert_eq!(
+        contract.fees_collected().unwrap().fees,
+        [coin(10, transferred)]
+    );
+    contract
+        .withdraw_fees(Some(transferred.to_owned()), "remote".to_owned())
+        .call(dao)
+        .unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(dao, transferred).unwrap(),
+        coin(10, transferred)
+    );
+
+    // Disabled, the rewards are distributed in full
+    contract.set_protocol_fee(None).call(owner).unwrap();
+    contract
+        .test_distribute_rewards(validators[0].to_owned(), coin(100, STAR))
+        .call(owner)
+        .unwrap();
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[0].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards, coin(95, STAR));
+    assert_eq!(contract.fees_collected().unwrap().fees, []);
+}
+
+#[test]
+fn compound_rewards() {
+    let owner = "owner";
+    let user = "user1";
+    let bot = "bot";
+    let consumer = "consumer";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO)), (consumer, &coins(1000, OSMO))]);
+
+    let (vault, star_contract) = setup(&app, owner, 100).unwrap();
+
+    // Rewards can only be compounded if paid in the staking denom
+    let err = star_contract
+        .compound_rewards("validator1".to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Mesh(MeshError::InvalidDenom(OSMO.to_owned()))
+    );
+
+    let contract = CodeId::store_code(&app)
+        .instantiate(
+            OSMO.to_owned(),
+            OSMO.to_owned(),
+            vault.contract_addr.to_string(),
+            100,
+            AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+            SlashRatio {
+                double_sign: Decimal::percent(SLASHING_PERCENTAGE),
+                offline: Decimal::percent(SLASHING_PERCENTAGE),
+            },
+            None,
+            0,
+            None,
+            None,
+        )
+        .call(owner)
+        .unwrap();
+
+    let validator = contract.activate_validators(["validator1"])[0];
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(100, OSMO));
 
+    // Rewards distributed, and transferred back from the consumer
     contract
-        .distribute_batch(owner, STAR, &[(validators[0], 100), (validators[1], 30)])
+        .test_distribute_rewards(validator.to_owned(), coin(1000, OSMO))
+        .call("test")
+        .unwrap();
+    app.app_mut()
+        .send_tokens(
+            Addr::unchecked(consumer),
+            contract.contract_addr.clone(),
+            &coins(1000, OSMO),
+        )
         .unwrap();
 
-    assert_rewards!(contract, users[0], validators[0], 60);
-    assert_rewards!(contract, users[1], validators[0], 90);
-    assert_rewards!(contract, users[0], validators[1], 60);
-    assert_rewards!(contract, users[1], validators[1], 0);
-}
-
-#[test]
-fn batch_distribution_invalid_token() {
-    let owner = "owner";
-    let user = "user1";
+    // Cranking needs the user to opt in
+    let err = contract
+        .crank_compound_rewards(user.to_owned(), validator.to_owned())
+        .call(bot)
+        .unwrap_err();
+    assert_eq!(err, ContractError::AutoCompoundDisabled(user.to_owned()));
 
-    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
+    contract.set_auto_compound(true).call(user).unwrap();
+    assert!(contract.auto_compound(user.to_owned()).unwrap().enabled);
 
-    let (vault, contract) = setup(&app, owner, 100).unwrap();
+    contract
+        .crank_compound_rewards(user.to_owned(), validator.to_owned())
+        .call(bot)
+        .unwrap();
+    contract
+        .test_commit_stake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
 
-    let validator = contract.activate_validators(["validator1"])[0];
+    // Rewards re-staked, minus the crank incentive
+    let stake = contract
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(1095)));
+    assert_rewards!(contract, user, validator, 0);
 
-    vault
-        .bond()
-        .with_funds(&coins(600, OSMO))
-        .call(user)
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.bonded, Uint128::new(1295));
+    let claim = vault
+        .claim(user.to_owned(), contract.contract_addr.to_string())
         .unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(1095)));
 
-    vault.stake(&contract, user, validator, coin(200, OSMO));
+    assert_eq!(
+        app.app().wrap().query_balance(bot, OSMO).unwrap(),
+        coin(5, OSMO)
+    );
 
+    // Nothing left to compound
     let err = contract
-        .distribute_batch(owner, "supertoken", &[(validator, 50)])
+        .compound_rewards(validator.to_owned())
+        .call(user)
         .unwrap_err();
-    assert_eq!(err, ContractError::InvalidDenom(STAR.to_string()));
+    assert_eq!(err, ContractError::NoRewards);
 }
 
 #[test]
@@ -1246,7 +3209,7 @@ fn slashing() {
     // Unstake some tokens
     // user unstakes 50 from validators[0] - 150 left staked in 2 batches
     contract
-        .unstake(validators[0].to_string(), coin(50, OSMO))
+        .unstake(validators[0].to_string(), coin(50, OSMO), None)
         .call(user)
         .unwrap();
     contract
@@ -1289,7 +3252,7 @@ fn slashing() {
     // Adding some more unstakes
     // user unstakes 70 from validators[0] - 80 left staken
     contract
-        .unstake(validators[0].to_owned(), coin(70, OSMO))
+        .unstake(validators[0].to_owned(), coin(70, OSMO), None)
         .call(user)
         .unwrap();
     contract
@@ -1298,7 +3261,7 @@ fn slashing() {
         .unwrap();
 
     contract
-        .unstake(validators[1].to_owned(), coin(90, OSMO))
+        .unstake(validators[1].to_owned(), coin(90, OSMO), None)
         .call(user)
         .unwrap();
     contract
@@ -1390,7 +3353,7 @@ fn slashing_pending_tx_partial_unbond() {
 
     // Unstake some tokens
     contract
-        .unstake(validators[0].to_string(), coin(50, OSMO))
+        .unstake(validators[0].to_string(), coin(50, OSMO), None)
         .call(user)
         .unwrap();
 
@@ -1476,7 +3439,7 @@ fn slashing_pending_tx_full_unbond() {
 
     // Unstake all tokens
     contract
-        .unstake(validators[0].to_string(), coin(200, OSMO))
+        .unstake(validators[0].to_string(), coin(200, OSMO), None)
         .call(user)
         .unwrap();
 
@@ -1559,7 +3522,7 @@ fn slashing_pending_tx_full_unbond_rolled_back() {
 
     // Unstake all tokens
     contract
-        .unstake(validators[0].to_string(), coin(200, OSMO))
+        .unstake(validators[0].to_string(), coin(200, OSMO), None)
         .call(user)
         .unwrap();
 
@@ -1644,10 +3607,7 @@ fn slashing_pending_tx_bond() {
         .stake_remote(
             contract.contract_addr.to_string(),
             coin(50, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validators[0].into(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validators[0]).encode().unwrap(),
         )
         .call(user)
         .unwrap();
@@ -1729,10 +3689,7 @@ fn slashing_pending_tx_bond_rolled_back() {
         .stake_remote(
             contract.contract_addr.to_string(),
             coin(50, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validators[0].into(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validators[0]).encode().unwrap(),
         )
         .call(user)
         .unwrap();
@@ -1787,3 +3744,461 @@ fn slashing_pending_tx_bond_rolled_back() {
         .unwrap();
     assert_eq!(claim.amount.val().unwrap().u128(), 230);
 }
+
+#[test]
+fn self_stake_requirement() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, _) = setup(&app, owner, 100).unwrap();
+
+    let contract = CodeId::store_code(&app)
+        .instantiate(
+            OSMO.to_owned(),
+            STAR.to_owned(),
+            vault.contract_addr.to_string(),
+            100,
+            AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+            SlashRatio {
+                double_sign: Decimal::percent(SLASHING_PERCENTAGE),
+                offline: Decimal::percent(SLASHING_PERCENTAGE),
+            },
+            Some(Uint128::new(1000)),
+            0,
+            None,
+            None,
+        )
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        contract.config().unwrap().min_self_stake,
+        Some(Uint128::new(1000))
+    );
+
+    let set_validator = |validator: &str, self_stake: Option<u128>| {
+        let validator = AddValidator {
+            self_stake: self_stake.map(Uint128::new),
+            ..AddValidator::mock(validator)
+        };
+        contract
+            .test_set_active_validator(validator, 100, 1234)
+            .call("test")
+            .unwrap()
+    };
+    set_validator("validator1", Some(1000));
+    set_validator("validator2", Some(999));
+    set_validator("validator3", None);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, "validator1", coin(100, OSMO));
+
+    // Validators below the minimum, or not reporting their self-stake, are blocked
+    for validator in ["validator2", "validator3"] {
+        let err = contract
+            .receive_virtual_stake(
+                user.to_owned(),
+                coin(100, OSMO),
+                1,
+                ReceiveVirtualStake::new(validator).encode().unwrap(),
+            )
+            .call(vault.contract_addr.as_str())
+            .unwrap_err();
+        assert_eq!(err, ContractError::SelfStakeTooLow(validator.to_owned()));
+    }
+
+    // Existing delegators are notified once the self-stake drops below the minimum
+    let resp = set_validator("validator1", Some(500));
+    let notification = resp
+        .events
+        .iter()
+        .find(|e| e.ty == "wasm-self_stake_below_min")
+        .unwrap();
+    assert!(notification
+        .attributes
+        .iter()
+        .any(|a| a.key == "delegator" && a.value == user));
+
+    // But only when crossing the threshold
+    let resp = set_validator("validator1", Some(400));
+    assert!(!resp
+        .events
+        .iter()
+        .any(|e| e.ty == "wasm-self_stake_below_min"));
+}
+
+#[test]
+fn validator_stake_cap() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator1";
+
+    let app = App::new_with_balances(&[(user, &coins(300, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+
+    contract
+        .test_set_active_validator(
+            AddValidator {
+                max_external_stake: Some(Uint128::new(150)),
+                ..AddValidator::mock(validator)
+            },
+            100,
+            1234,
+        )
+        .call("test")
+        .unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    vault.stake(&contract, user, validator, coin(100, OSMO));
+
+    let capacity = contract.validator_capacity(validator.to_owned()).unwrap();
+    assert_eq!(capacity.total_stake, Uint128::new(100));
+    assert_eq!(capacity.max_stake, Some(Uint128::new(150)));
+    assert_eq!(capacity.remaining, Some(Uint128::new(50)));
+
+    // Going over the cap is rejected
+    let err = contract
+        .receive_virtual_stake(
+            user.to_owned(),
+            coin(60, OSMO),
+            1,
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(vault.contract_addr.as_str())
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorCapReached(validator.to_owned(), Uint128::new(150))
+    );
+
+    // Up to the cap is fine
+    vault.stake(&contract, user, validator, coin(50, OSMO));
+    let capacity = contract.validator_capacity(validator.to_owned()).unwrap();
+    assert_eq!(capacity.remaining, Some(Uint128::zero()));
+
+    // The consumer lifting the cap allows new stakes again
+    contract
+        .test_set_active_validator(AddValidator::mock(validator), 100, 1234)
+        .call("test")
+        .unwrap();
+    let capacity = contract.validator_capacity(validator.to_owned()).unwrap();
+    assert_eq!(capacity.max_stake, None);
+    assert_eq!(capacity.remaining, None);
+    vault.stake(&contract, user, validator, coin(50, OSMO));
+}
+
+#[test]
+fn reward_dust() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let remote = "remote";
+
+    let app = App::new_with_balances(&[
+        (users[0], &coins(200, OSMO)),
+        (users[1], &coins(400, OSMO)),
+        (owner, &coins(1000, STAR)),
+    ]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(200, OSMO))
+        .call(users[0])
+        .unwrap();
+    vault
+        .bond()
+        .with_funds(&coins(400, OSMO))
+        .call(users[1])
+        .unwrap();
+    for validator in validators {
+        vault.stake(&contract, users[0], validator, coin(100, OSMO));
+        vault.stake(&contract, users[1], validator, coin(200, OSMO));
+    }
+
+    // ~1.67 tokens for users[0] on each validator
+    for validator in validators {
+        contract
+            .test_distribute_rewards(validator.to_owned(), coin(5, STAR))
+            .call(owner)
+            .unwrap();
+    }
+
+    // Fully unstaked, so nothing will be added to the remainders anymore
+    for validator in validators {
+        contract
+            .unstake(validator.to_owned(), coin(100, OSMO), None)
+            .call(users[0])
+            .unwrap();
+        contract
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+
+    let attr = |resp: &cw_multi_test::AppResponse, key: &str| {
+        resp.events
+            .iter()
+            .flat_map(|e| &e.attributes)
+            .find(|a| a.key == key)
+            .map(|a| a.value.clone())
+    };
+    let dust = |user: &str| contract.reward_dust(user.to_owned(), None).unwrap().dust;
+
+    // The remainder of the first stake is swept into the dust, which is still under one token
+    let resp = contract
+        .withdraw_rewards(validators[0].to_owned(), remote.to_owned())
+        .call(users[0])
+        .unwrap();
+    assert_eq!(attr(&resp, "amount"), Some("1".to_owned()));
+    assert_eq!(attr(&resp, "dust"), None);
+    contract
+        .test_commit_withdraw_rewards(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    assert_eq!(
+        dust(users[0]),
+        Decimal::from_ratio(666_666_600u128, 1_000_000_000u128)
+    );
+
+    // The second one gets it over one token, paid out along
+    let resp = contract
+        .withdraw_rewards(validators[1].to_owned(), remote.to_owned())
+        .call(users[0])
+        .unwrap();
+    assert_eq!(attr(&resp, "amount"), Some("2".to_owned()));
+    assert_eq!(attr(&resp, "dust"), Some("1".to_owned()));
+    assert_eq!(
+        dust(users[0]),
+        Decimal::from_ratio(333_333_200u128, 1_000_000_000u128)
+    );
+
+    // Failed withdrawals credit it back
+    contract
+        .test_rollback_withdraw_rewards(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    assert_eq!(
+        dust(users[0]),
+        Decimal::from_ratio(1_333_333_200u128, 1_000_000_000u128)
+    );
+
+    let resp = contract
+        .withdraw_rewards(validators[1].to_owned(), remote.to_owned())
+        .call(users[0])
+        .unwrap();
+    assert_eq!(attr(&resp, "amount"), Some("2".to_owned()));
+    contract
+        .test_commit_withdraw_rewards(get_last_external_staking_pending_tx_id(&contract).unwrap())
+        .call("test")
+        .unwrap();
+    // Only the stake part counts as withdrawn from the stake
+    let rewards = contract
+        .pending_rewards(users[0].to_owned(), validators[1].to_owned())
+        .unwrap()
+        .rewards;
+    assert_eq!(rewards.amount.u128(), 0);
+
+    // Nothing left to withdraw, the rest of the dust waits for the next rewards
+    let err = contract
+        .withdraw_rewards(validators[0].to_owned(), remote.to_owned())
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+
+    // Open stakes keep their remainder
+    contract
+        .withdraw_rewards(validators[0].to_owned(), remote.to_owned())
+        .call(users[1])
+        .unwrap();
+    assert_eq!(dust(users[1]), Decimal::zero());
+
+    // Only the owner sets the threshold
+    let err = contract
+        .set_dust_threshold(Uint128::new(5))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    contract
+        .set_dust_threshold(Uint128::new(5))
+        .call(owner)
+        .unwrap();
+    assert_eq!(contract.config().unwrap().dust_threshold, Uint128::new(5));
+    let dust = contract.reward_dust(users[0].to_owned(), None).unwrap();
+    assert_eq!(dust.denom, STAR);
+    assert_eq!(dust.threshold, Uint128::new(5));
+}
+
+#[test]
+fn multiple_vaults() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = App::new_with_balances(&[(user, &coins(600, OSMO))]);
+
+    let (vault, contract) = setup(&app, owner, 100).unwrap();
+    let other_vault = VaultCodeId::store_code(&app)
+        .instantiate(OSMO.to_owned(), None, None, None, None)
+        .call(owner)
+        .unwrap();
+
+    let validators = contract.activate_validators(["validator1", "validator2"]);
+
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    other_vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+
+    // Only allowed vaults can stake
+    let receive_stake = |validator: &str, amount: Coin, vault: &Addr| {
+        contract
+            .receive_virtual_stake(
+                user.to_owned(),
+                amount,
+                1,
+                ReceiveVirtualStake::new(validator).encode().unwrap(),
+            )
+            .call(vault.as_str())
+    };
+    let err = receive_stake(validators[1], coin(10, OSMO), &other_vault.contract_addr).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    let err = contract
+        .add_vault(other_vault.contract_addr.to_string(), OSMO.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    // Vaults bonding another denom are rejected, their stakes couldn't be priced by the consumer
+    let err = contract
+        .add_vault(other_vault.contract_addr.to_string(), "atom".to_owned())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Mesh(MeshError::InvalidDenom(OSMO.to_owned()))
+    );
+    contract
+        .add_vault(other_vault.contract_addr.to_string(), OSMO.to_owned())
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        contract.vaults().unwrap().vaults,
+        vec![
+            VaultInfo {
+                vault: vault.contract_addr.to_string(),
+                denom: OSMO.to_owned(),
+            },
+            VaultInfo {
+                vault: other_vault.contract_addr.to_string(),
+                denom: OSMO.to_owned(),
+            },
+        ]
+    );
+
+    // The same user stakes from both vaults, each stake being committed with its own vault
+    vault.stake(&contract, user, validators[0], coin(100, OSMO));
+    other_vault.stake(&contract, user, validators[1], coin(100, OSMO));
+    assert_eq!(
+        contract
+            .stake_vault(user.to_owned(), validators[0].to_owned())
+            .unwrap(),
+        VaultInfo {
+            vault: vault.contract_addr.to_string(),
+            denom: OSMO.to_owned(),
+        }
+    );
+    assert_eq!(
+        contract
+            .stake_vault(user.to_owned(), validators[1].to_owned())
+            .unwrap(),
+        VaultInfo {
+            vault: other_vault.contract_addr.to_string(),
+            denom: OSMO.to_owned(),
+        }
+    );
+
+    // A stake comes from a single vault at a time
+    let err = receive_stake(validators[0], coin(10, OSMO), &other_vault.contract_addr).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::VaultMismatch(vault.contract_addr.to_string())
+    );
+
+    // Slashings go to the vault of the slashed stake only
+    contract
+        .test_handle_slashing(validators[1].to_string(), Uint128::new(10))
+        .call("test")
+        .unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().bonded,
+        Uint128::new(300)
+    );
+    assert_eq!(
+        other_vault.account(user.to_owned()).unwrap().bonded,
+        Uint128::new(290)
+    );
+
+    // And so do the releases
+    for (validator, amount) in [
+        (validators[0], coin(50, OSMO)),
+        (validators[1], coin(90, OSMO)),
+    ] {
+        contract
+            .unstake(validator.to_owned(), amount, None)
+            .call(user)
+            .unwrap();
+        contract
+            .test_commit_unstake(get_last_external_staking_pending_tx_id(&contract).unwrap())
+            .call("test")
+            .unwrap();
+    }
+    app.app_mut().update_block(|block| {
+        block.height += 1;
+        block.time = block.time.plus_seconds(100);
+    });
+    contract.withdraw_unbonded().call(user).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(250))
+    );
+    assert_eq!(
+        other_vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(290))
+    );
+
+    // Fully unstaked, the stake can come from another vault
+    receive_stake(validators[1], coin(10, OSMO), &vault.contract_addr).unwrap();
+
+    // Removed vaults can't stake anymore
+    contract
+        .remove_vault(other_vault.contract_addr.to_string())
+        .call(owner)
+        .unwrap();
+    let err = receive_stake(validators[0], coin(10, OSMO), &other_vault.contract_addr).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = contract
+        .remove_vault(other_vault.contract_addr.to_string())
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UnknownVault(other_vault.contract_addr.to_string())
+    );
+}
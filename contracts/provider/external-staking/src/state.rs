@@ -1,9 +1,9 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{BlockInfo, Decimal, Timestamp, Uint128, Uint256};
+use cosmwasm_std::{Addr, BlockInfo, Coin, Decimal, Timestamp, Uint128, Uint256};
+use mesh_apis::ibc::ProviderPacket;
 use mesh_apis::vault_api::VaultApiHelper;
-use mesh_sync::ValueRange;
-
-use crate::points_alignment::PointsAlignment;
+use mesh_sync::{PointsAlignment, ValueRange};
+use std::cmp::min;
 
 /// Contract configuration
 #[cw_serde]
@@ -12,12 +12,112 @@ pub struct Config {
     pub denom: String,
     /// Rewards token for this contract (remote IBC token)
     pub rewards_denom: String,
+    /// Additional rewards tokens (remote IBC tokens), e.g. incentives paid by the consumer on top
+    /// of `rewards_denom`. They are distributed and withdrawn separately
+    #[serde(default)]
+    pub extra_rewards_denoms: Vec<String>,
     /// Vault contract address
     pub vault: VaultApiHelper,
     /// Unbonding period for claims in seconds
     pub unbonding_period: u64,
     /// The slash ratio
     pub slash_ratio: SlashRatio,
+    /// Minimum self-stake (in the consumer-side staking denom) a remote validator has to
+    /// keep to receive new stakes, if required
+    #[serde(default)]
+    pub min_self_stake: Option<Uint128>,
+    /// Time (in seconds) a validator removed from the consumer active set is kept before
+    /// being removed here. Zero removes it right away
+    #[serde(default)]
+    pub removal_grace_period: u64,
+    /// Max number of validator stakes released by a single `withdraw_unbonded` call.
+    /// `DEFAULT_WITHDRAW_BATCH` if not set
+    #[serde(default)]
+    pub max_withdraw_batch: Option<u32>,
+    /// Time (in seconds) between two stake snapshots. Zero disables the snapshots
+    #[serde(default)]
+    pub snapshot_interval: u64,
+    /// Number of snapshots kept per stake, older ones being overwritten
+    #[serde(default)]
+    pub snapshot_capacity: u32,
+    /// Instant unstaking parameters, disabled if not set
+    #[serde(default)]
+    pub instant_unstake: Option<InstantUnstakeConfig>,
+    /// Rewards transferred by the converter with ICS-20 transfers, not accepted if not set
+    #[serde(default)]
+    pub rewards_transfer: Option<RewardsTransferConfig>,
+    /// Protocol fee taken on the distributed rewards, if any
+    #[serde(default)]
+    pub protocol_fee: Option<ProtocolFee>,
+    /// Uptime under which the stakers of a validator are warned, if set
+    #[serde(default)]
+    pub min_uptime: Option<Decimal>,
+    /// Whole tokens the rewards dust of a user has to reach to be paid out. One if zero
+    #[serde(default)]
+    pub dust_threshold: Uint128,
+    /// Whether the unstakes are queued, and sent to the consumer once per block in a single
+    /// `UnstakeBatch` packet. Only if the channel supports it
+    #[serde(default)]
+    pub unstake_batching: bool,
+}
+
+impl Config {
+    /// Whether `denom` is one of the rewards denoms, main or additional
+    pub fn is_rewards_denom(&self, denom: &str) -> bool {
+        self.rewards_denom == denom || self.extra_rewards_denoms.iter().any(|d| d == denom)
+    }
+}
+
+/// Parameters of `receive_rewards_transfer`
+#[cw_serde]
+pub struct RewardsTransferConfig {
+    /// Sender of the `receive_rewards_transfer` calls, i.e. the ibc-hooks intermediary address
+    /// of the converter on the ICS-20 channel
+    pub sender: String,
+    /// Local (IBC) denom of the transferred rewards. It is one of the additional rewards denoms,
+    /// withdrawn on this chain
+    pub denom: String,
+}
+
+/// Protocol fee taken on the rewards, before they are distributed to the stakers
+#[cw_serde]
+pub struct ProtocolFee {
+    /// Part of the rewards taken, in basis points
+    pub rate_bps: u16,
+    /// Address allowed to withdraw the collected fees, i.e. the provider DAO
+    pub collector: String,
+}
+
+impl ProtocolFee {
+    /// Fee taken on `rewards`, rounded down
+    pub fn fee(&self, rewards: Uint128) -> Uint128 {
+        rewards.multiply_ratio(self.rate_bps, MAX_FEE_BPS)
+    }
+}
+
+/// Basis points of a 100% fee
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Parameters of `unstake_instant`
+#[cw_serde]
+pub struct InstantUnstakeConfig {
+    /// Part of the unstaked amount paid as penalty
+    pub penalty_rate: Decimal,
+    /// Where the penalties go
+    pub penalty_destination: PenaltyDestination,
+    /// Length of the epochs, in seconds
+    pub epoch_length: u64,
+    /// Max amount instantly unstaked by all of the users during an epoch
+    pub max_per_epoch: Uint128,
+}
+
+#[cw_serde]
+pub enum PenaltyDestination {
+    Burn {},
+    /// Sent to the given rewards pool contract
+    RewardsPool {
+        address: String,
+    },
 }
 
 #[cw_serde]
@@ -45,6 +145,19 @@ pub struct Stake {
     pub points_alignment: PointsAlignment,
     /// Tokens already withdrawn by this user
     pub withdrawn_funds: Uint128,
+    /// Distribution alignment of the additional rewards denoms. A denom is only added once the
+    /// stake changes after it was distributed, its alignment is zero until then
+    #[serde(default)]
+    pub extra_rewards: Vec<DenomAlignment>,
+}
+
+/// Distribution alignment of a stake for an additional rewards denom
+#[cw_serde]
+pub struct DenomAlignment {
+    pub denom: String,
+    pub points_alignment: PointsAlignment,
+    /// Tokens of this denom already withdrawn by this user
+    pub withdrawn_funds: Uint128,
 }
 
 impl Stake {
@@ -55,6 +168,53 @@ impl Stake {
             ..Default::default()
         }
     }
+
+    pub fn extra_rewards(&self, denom: &str) -> Option<&DenomAlignment> {
+        self.extra_rewards
+            .iter()
+            .find(|rewards| rewards.denom == denom)
+    }
+
+    pub fn extra_rewards_mut(&mut self, denom: &str) -> &mut DenomAlignment {
+        let idx = match self
+            .extra_rewards
+            .iter()
+            .position(|rewards| rewards.denom == denom)
+        {
+            Some(idx) => idx,
+            None => {
+                self.extra_rewards.push(DenomAlignment {
+                    denom: denom.to_owned(),
+                    points_alignment: PointsAlignment::new(),
+                    withdrawn_funds: Uint128::zero(),
+                });
+                self.extra_rewards.len() - 1
+            }
+        };
+        &mut self.extra_rewards[idx]
+    }
+
+    /// Aligns the points of all the rewards denoms of `distribution` with a stake increase
+    pub fn stake_increased(&mut self, amount: Uint128, distribution: &Distribution) {
+        self.points_alignment
+            .stake_increased(amount, distribution.points_per_stake);
+        for points in &distribution.extra_points {
+            self.extra_rewards_mut(&points.denom)
+                .points_alignment
+                .stake_increased(amount, points.points_per_stake);
+        }
+    }
+
+    /// Aligns the points of all the rewards denoms of `distribution` with a stake decrease
+    pub fn stake_decreased(&mut self, amount: Uint128, distribution: &Distribution) {
+        self.points_alignment
+            .stake_decreased(amount, distribution.points_per_stake);
+        for points in &distribution.extra_points {
+            self.extra_rewards_mut(&points.denom)
+                .points_alignment
+                .stake_decreased(amount, points.points_per_stake);
+        }
+    }
 }
 
 /// Description of tokens in unbonding period
@@ -92,6 +252,52 @@ impl Stake {
             .sum()
     }
 
+    /// Amount of tokens in `pending_unbonds` not released yet
+    pub fn unbonding(&self, info: &BlockInfo) -> Uint128 {
+        self.pending_unbonds
+            .iter()
+            .filter(|pending| pending.release_at > info.time)
+            .map(|pending| pending.amount)
+            .sum()
+    }
+
+    /// Removes `amount` from the entries of `pending_unbonds`, the newest ones first, returning
+    /// the removed entries (oldest first).
+    ///
+    /// The caller has to check at least `amount` is still unbonding, so that no released entry
+    /// is touched.
+    pub fn cancel_pending(&mut self, amount: Uint128) -> Vec<PendingUnbond> {
+        let mut cancelled = vec![];
+        let mut left = amount;
+        while !left.is_zero() {
+            let Some(pending) = self.pending_unbonds.last_mut() else {
+                break;
+            };
+            let taken = min(pending.amount, left);
+            pending.amount -= taken;
+            left -= taken;
+            cancelled.push(PendingUnbond {
+                amount: taken,
+                release_at: pending.release_at,
+            });
+            if pending.amount.is_zero() {
+                self.pending_unbonds.pop();
+            }
+        }
+        cancelled.reverse();
+        cancelled
+    }
+
+    /// Puts back cancelled entries into `pending_unbonds`, keeping it sorted
+    pub fn restore_pending(&mut self, unbonds: impl IntoIterator<Item = PendingUnbond>) {
+        for unbond in unbonds {
+            let idx = self
+                .pending_unbonds
+                .partition_point(|pending| pending.release_at <= unbond.release_at);
+            self.pending_unbonds.insert(idx, unbond);
+        }
+    }
+
     /// Slashes all the entries in `pending_unbonds`, returning total slashed amount.
     pub fn slash_pending(
         &mut self,
@@ -126,4 +332,194 @@ pub struct Distribution {
     pub points_per_stake: Uint256,
     /// Points which were not distributed previously
     pub points_leftover: Uint256,
+    /// Points of the additional rewards denoms, added on their first distribution
+    #[serde(default)]
+    pub extra_points: Vec<DenomPoints>,
+}
+
+/// Distribution points of an additional rewards denom
+#[cw_serde]
+pub struct DenomPoints {
+    pub denom: String,
+    pub points_per_stake: Uint256,
+    pub points_leftover: Uint256,
+}
+
+impl Distribution {
+    pub fn extra_points(&self, denom: &str) -> Option<&DenomPoints> {
+        self.extra_points
+            .iter()
+            .find(|points| points.denom == denom)
+    }
+
+    pub fn extra_points_mut(&mut self, denom: &str) -> &mut DenomPoints {
+        let idx = match self
+            .extra_points
+            .iter()
+            .position(|points| points.denom == denom)
+        {
+            Some(idx) => idx,
+            None => {
+                self.extra_points.push(DenomPoints {
+                    denom: denom.to_owned(),
+                    points_per_stake: Uint256::zero(),
+                    points_leftover: Uint256::zero(),
+                });
+                self.extra_points.len() - 1
+            }
+        };
+        &mut self.extra_points[idx]
+    }
+}
+
+/// Validators a user restricts their stakes to, whether staked explicitly or automatically
+#[cw_serde]
+#[derive(Default)]
+pub struct ValidatorPreferences {
+    /// Validators never staked to
+    pub denylist: Vec<String>,
+    /// If set, the only validators staked to
+    pub allowlist: Option<Vec<String>>,
+}
+
+impl ValidatorPreferences {
+    /// Whether the preferences let `validator` receive stakes
+    pub fn allows(&self, validator: &str) -> bool {
+        !self.denylist.iter().any(|denied| denied == validator)
+            && self
+                .allowlist
+                .as_ref()
+                .map_or(true, |allowed| allowed.iter().any(|v| v == validator))
+    }
+}
+
+/// Unstakes waiting to be sent to the consumer in a single `UnstakeBatch` packet
+#[cw_serde]
+pub struct UnstakeQueue {
+    /// Height of the block the queue was started in
+    pub since: u64,
+    /// Tx ids of the queued unstakes, in order
+    pub tx_ids: Vec<u64>,
+}
+
+/// Signing performance of a validator, as last reported by the consumer
+#[cw_serde]
+pub struct ValidatorHealth {
+    /// Blocks missed over the signing window
+    pub missed_blocks: u64,
+    /// Length of the signing window, in blocks
+    pub window: u64,
+    /// Part of the window blocks signed
+    pub uptime: Decimal,
+    /// Consumer height of the report
+    pub height: u64,
+    /// Consumer time (in seconds) of the report
+    pub time: u64,
+}
+
+/// Validator removed from the consumer active set, waiting for the grace period to be over
+/// to be removed. It can't receive new stakes in the meantime
+#[cw_serde]
+pub struct LeavingValidator {
+    /// Consumer height of the removal
+    pub height: u64,
+    /// Consumer time (in seconds) of the removal
+    pub time: u64,
+    /// Block time the removal can be finalized at
+    pub finalize_at: Timestamp,
+}
+
+/// Slashing of a validator being applied to its stakes, in batches. Users are processed in
+/// order, so the last processed one is enough to resume
+#[cw_serde]
+pub struct PendingSlash {
+    /// Nominal slash ratio, applied to the pending unbonds
+    pub slash_ratio: Decimal,
+    /// Ratio of the stake to be slashed, computed when the slashing started
+    pub effective_slash_ratio: Decimal,
+    /// Consumer time (in seconds) of the infraction
+    pub infraction_time: u64,
+    /// Last user whose stake was slashed, if any
+    pub last_user: Option<Addr>,
+}
+
+/// Position of a user on a validator, as recorded by `take_snapshots`
+#[cw_serde]
+pub struct Snapshot {
+    /// Snapshot epoch, i.e. the block time divided by the snapshot interval
+    pub epoch: u64,
+    /// Block time the snapshot was taken at
+    pub time: Timestamp,
+    /// Tokens staked, not including the pending unbonds
+    pub stake: Uint128,
+    /// Rewards earned by the stake so far, including the withdrawn ones
+    pub accumulated_rewards: Uint128,
+}
+
+/// Progress of the snapshots of the current epoch. Stakes are processed in order, so the last
+/// snapshotted one is enough to resume
+#[cw_serde]
+pub struct SnapshotProgress {
+    pub epoch: u64,
+    /// Last `(user, validator)` stake snapshotted, if any
+    pub last: Option<(Addr, String)>,
+    /// Whether all the stakes were snapshotted for the epoch
+    pub complete: bool,
+}
+
+/// Provider packet that timed out, waiting to be sent again
+#[cw_serde]
+pub struct PendingPacket {
+    pub packet: ProviderPacket,
+    /// Block time the timeout was processed at
+    pub timed_out_at: Timestamp,
+}
+
+/// Consumer packet that was already processed, kept to detect re-deliveries
+#[cw_serde]
+pub struct ProcessedPacket {
+    /// Type of the packet, as reported in events
+    pub packet_type: String,
+    /// Block height the packet was processed at
+    pub height: u64,
+    /// Block time the packet was processed at
+    pub time: Timestamp,
+}
+
+/// Kind of a user operation recorded in the tx history
+#[cw_serde]
+pub enum StakeTxKind {
+    Stake,
+    CancelUnbond,
+    Unstake,
+    InstantUnstake,
+    WithdrawUnbonded,
+    WithdrawRewards,
+}
+
+/// Outcome of a recorded operation. Operations sending an IBC packet are pending until it is
+/// acked, the others are committed right away
+#[cw_serde]
+pub enum StakeTxStatus {
+    Pending,
+    Committed,
+    RolledBack,
+}
+
+/// Stake, unstake or withdraw operation of a user, as recorded in the tx history
+#[cw_serde]
+pub struct StakeTx {
+    /// Monotonically increasing id, emitted as the `stake_tx_id` attribute
+    pub stake_tx_id: u64,
+    pub kind: StakeTxKind,
+    /// Not set for batch stakes, and withdrawals over all of the validators
+    pub validator: Option<String>,
+    pub amount: Coin,
+    /// Id of the pending tx, also set in the IBC packet and its ack events, if a packet was sent
+    pub tx_id: Option<u64>,
+    pub status: StakeTxStatus,
+    /// Block height the operation was made at
+    pub height: u64,
+    /// Block time the operation was made at
+    pub time: Timestamp,
 }
@@ -1,8 +1,10 @@
 use cosmwasm_std::{Coin, Response, StdError, Uint128};
 use mesh_apis::converter_api::RewardInfo;
-use mesh_apis::ibc::AddValidator;
+use mesh_apis::ibc::{AddValidator, ConsumerPacket, ProviderPacket};
 use sylvia::interface;
-use sylvia::types::ExecCtx;
+use sylvia::types::{ExecCtx, QueryCtx};
+
+use crate::msg::SentPacketsResponse;
 
 /// Interface to work around lack of support for IBC in `cw-multi-test`
 /// This interface is for test usage only
@@ -99,4 +101,25 @@ pub trait TestMethods {
         validator: String,
         slash_amount: Uint128,
     ) -> Result<Response, Self::Error>;
+
+    /// Applies a consumer packet, as if received over IBC.
+    #[sv::msg(exec)]
+    fn test_receive_packet(
+        &self,
+        ctx: ExecCtx,
+        packet: ConsumerPacket,
+    ) -> Result<Response, Self::Error>;
+
+    /// Handles the ack of a provider packet, as if received over IBC.
+    #[sv::msg(exec)]
+    fn test_packet_ack(
+        &self,
+        ctx: ExecCtx,
+        packet: ProviderPacket,
+        success: bool,
+    ) -> Result<Response, Self::Error>;
+
+    /// Packets sent so far, in order. They are recorded instead of sent, to be relayed by tests.
+    #[sv::msg(query)]
+    fn test_sent_packets(&self, ctx: QueryCtx) -> Result<SentPacketsResponse, Self::Error>;
 }
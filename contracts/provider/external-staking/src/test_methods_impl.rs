@@ -2,11 +2,15 @@ use crate::contract::ExternalStakingContract;
 use crate::error::ContractError;
 use crate::test_methods::TestMethods;
 
+use crate::msg::SentPacketsResponse;
+
 use cosmwasm_std::{Coin, Response, Uint128};
 use mesh_apis::converter_api::RewardInfo;
-use mesh_apis::ibc::AddValidator;
+#[cfg(not(any(test, feature = "mt")))]
+use mesh_apis::error::MeshError;
+use mesh_apis::ibc::{AddValidator, ConsumerPacket, ProviderPacket};
 use sylvia::contract;
-use sylvia::types::ExecCtx;
+use sylvia::types::{ExecCtx, QueryCtx};
 
 /// These methods are for test usage only
 #[contract(module=crate::contract)]
@@ -20,12 +24,12 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(any(feature = "mt", test))]
         {
             let msg = self.commit_stake(ctx.deps, tx_id)?;
-            Ok(Response::new().add_message(msg))
+            Ok(Response::new().add_messages(msg))
         }
         #[cfg(not(any(feature = "mt", test)))]
         {
             let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -35,12 +39,12 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(any(test, feature = "mt"))]
         {
             let msg = self.rollback_stake(ctx.deps, tx_id)?;
-            Ok(Response::new().add_message(msg))
+            Ok(Response::new().add_messages(msg))
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -55,15 +59,22 @@ impl TestMethods for ExternalStakingContract<'_> {
     ) -> Result<Response, ContractError> {
         #[cfg(any(feature = "mt", test))]
         {
-            let AddValidator { valoper, pub_key } = validator;
-            self.val_set
-                .add_validator(ctx.deps.storage, &valoper, &pub_key, height, time)?;
-            Ok(Response::new())
+            self.val_set.add_validator(
+                ctx.deps.storage,
+                &validator.valoper,
+                &validator.pub_key,
+                crate::contract::validator_metadata(&validator),
+                height,
+                time,
+            )?;
+            self.update_max_external_stakes(ctx.deps.storage, [&validator])?;
+            let events = self.update_self_stakes(ctx.deps.storage, &[validator])?;
+            Ok(Response::new().add_events(events))
         }
         #[cfg(not(any(feature = "mt", test)))]
         {
             let _ = (ctx, validator, height, time);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -85,7 +96,7 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(not(any(feature = "mt", test)))]
         {
             let _ = (ctx, valoper, height, time);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -107,7 +118,7 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(not(any(feature = "mt", test)))]
         {
             let _ = (ctx, valoper, height, time);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -116,13 +127,13 @@ impl TestMethods for ExternalStakingContract<'_> {
     fn test_commit_unstake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
         #[cfg(any(test, feature = "mt"))]
         {
-            self.commit_unstake(ctx.deps, ctx.env, tx_id)?;
-            Ok(Response::new())
+            let msgs = self.commit_unstake(ctx.deps, ctx.env, tx_id)?;
+            Ok(Response::new().add_messages(msgs))
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -131,13 +142,13 @@ impl TestMethods for ExternalStakingContract<'_> {
     fn test_rollback_unstake(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
         #[cfg(any(test, feature = "mt"))]
         {
-            self.rollback_unstake(ctx.deps, tx_id)?;
-            Ok(Response::new())
+            let msg = self.rollback_unstake(ctx.deps, tx_id)?;
+            Ok(Response::new().add_messages(msg))
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -157,7 +168,7 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, validator, rewards);
-            Err(ContractError::Unauthorized)
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -177,7 +188,7 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, denom, rewards);
-            Err(ContractError::Unauthorized)
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -196,7 +207,7 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -215,7 +226,7 @@ impl TestMethods for ExternalStakingContract<'_> {
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, tx_id);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -237,17 +248,81 @@ impl TestMethods for ExternalStakingContract<'_> {
                 &validator,
                 cfg.slash_ratio.double_sign, // TODO: Add slash ratio parameter
                 slash_amount,
+                ctx.env.block.height,
                 0, // TODO: Add infraction time parameter
             )?;
-            match slash_msg {
-                Some(msg) => Ok(Response::new().add_message(msg)),
-                None => Ok(Response::new()),
-            }
+            Ok(Response::new().add_messages(slash_msg))
         }
         #[cfg(not(any(test, feature = "mt")))]
         {
             let _ = (ctx, validator, slash_amount);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// Applies a consumer packet, as if received over IBC.
+    #[sv::msg(exec)]
+    fn test_receive_packet(
+        &self,
+        ctx: ExecCtx,
+        packet: ConsumerPacket,
+    ) -> Result<Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let resp = crate::ibc::receive_packet(ctx.deps, ctx.env, packet)?;
+            Ok(Response::new()
+                .add_submessages(resp.messages)
+                .add_attributes(resp.attributes)
+                .add_events(resp.events))
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// Handles the ack of a provider packet, as if received over IBC.
+    #[sv::msg(exec)]
+    fn test_packet_ack(
+        &self,
+        ctx: ExecCtx,
+        packet: ProviderPacket,
+        success: bool,
+    ) -> Result<Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let ack = match success {
+                true => mesh_apis::ibc::AckWrapper::Result(cosmwasm_std::Binary::default()),
+                false => mesh_apis::ibc::AckWrapper::Error("test error".to_owned()),
+            };
+            let resp = crate::ibc::packet_acked(ctx.deps, ctx.env, packet, ack)?;
+            Ok(Response::new()
+                .add_submessages(resp.messages)
+                .add_attributes(resp.attributes)
+                .add_events(resp.events))
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, packet, success);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    /// Packets sent so far, in order. They are recorded instead of sent, to be relayed by tests.
+    #[sv::msg(query)]
+    fn test_sent_packets(&self, ctx: QueryCtx) -> Result<SentPacketsResponse, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let packets = crate::ibc::TEST_SENT_PACKETS
+                .may_load(ctx.deps.storage)?
+                .unwrap_or_default();
+            Ok(SentPacketsResponse { packets })
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = ctx;
+            Err(MeshError::Unauthorized.into())
         }
     }
 }
@@ -0,0 +1,113 @@
+//! Vaults the cross-stakes come from.
+//!
+//! Besides the vault set at instantiation, the owner can allow more vaults to stake through this
+//! contract. All of them bond `Config::denom`, as the stakes share the validator totals and are
+//! priced by the consumer in that denom. Every `(user, validator)` stake comes from a single
+//! vault, the one it was first staked from, and its unbonds, rollbacks, compounded rewards and
+//! slashings go back to that vault. A stake can come from another vault once it has nothing
+//! staked nor unbonding.
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Order, StdResult, Storage};
+use cw_storage_plus::Map;
+use mesh_apis::vault_api::VaultApiHelper;
+
+use crate::state::{Config, Stake};
+
+/// Vault a stake comes from, and the denom it bonds
+#[cw_serde]
+#[derive(Eq, PartialOrd, Ord)]
+pub struct StakeSource {
+    pub vault: Addr,
+    pub denom: String,
+}
+
+impl StakeSource {
+    /// The instantiation vault, source of the stakes by default
+    pub fn main(config: &Config) -> Self {
+        Self {
+            vault: config.vault.0.clone(),
+            denom: config.denom.clone(),
+        }
+    }
+
+    pub fn vault(&self) -> VaultApiHelper {
+        VaultApiHelper(self.vault.clone())
+    }
+
+    /// Whether the stake can come from `other` instead
+    pub fn can_switch(&self, other: &StakeSource, stake: &Stake) -> bool {
+        self == other || (stake.stake.high().is_zero() && stake.pending_unbonds.is_empty())
+    }
+}
+
+pub struct Vaults<'a> {
+    /// Vaults allowed on top of `Config::vault`, with the denom they bond
+    pub allowed: Map<'a, &'a Addr, String>,
+    /// Source of each `(user, validator)` stake, when not `Config::vault`
+    pub sources: Map<'a, (&'a Addr, &'a str), StakeSource>,
+}
+
+impl<'a> Vaults<'a> {
+    pub const fn new(allowed_key: &'a str, sources_key: &'a str) -> Self {
+        Self {
+            allowed: Map::new(allowed_key),
+            sources: Map::new(sources_key),
+        }
+    }
+
+    /// Denom bonded by `vault`, if it is allowed to stake
+    pub fn denom(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+        vault: &Addr,
+    ) -> StdResult<Option<String>> {
+        if *vault == config.vault.0 {
+            return Ok(Some(config.denom.clone()));
+        }
+        self.allowed.may_load(storage, vault)
+    }
+
+    /// All the allowed vaults with their denom, starting with `Config::vault`
+    pub fn list(&self, storage: &dyn Storage, config: &Config) -> StdResult<Vec<StakeSource>> {
+        let mut vaults = vec![StakeSource::main(config)];
+        for item in self.allowed.range(storage, None, None, Order::Ascending) {
+            let (vault, denom) = item?;
+            vaults.push(StakeSource { vault, denom });
+        }
+        Ok(vaults)
+    }
+
+    /// Source of the stake of `user` on `validator`
+    pub fn source(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+        user: &Addr,
+        validator: &str,
+    ) -> StdResult<StakeSource> {
+        let source = self
+            .sources
+            .may_load(storage, (user, validator))?
+            .unwrap_or_else(|| StakeSource::main(config));
+        Ok(source)
+    }
+
+    /// Sets the source of the stake of `user` on `validator`
+    pub fn set_source(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        user: &Addr,
+        validator: &str,
+        source: &StakeSource,
+    ) -> StdResult<()> {
+        if *source == StakeSource::main(config) {
+            self.sources.remove(storage, (user, validator));
+            Ok(())
+        } else {
+            self.sources.save(storage, (user, validator), source)
+        }
+    }
+}
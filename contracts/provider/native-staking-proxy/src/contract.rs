@@ -1,19 +1,27 @@
 use cosmwasm_std::WasmMsg::Execute;
 use cosmwasm_std::{
-    coin, ensure_eq, to_json_binary, Coin, DistributionMsg, GovMsg, Response, StakingMsg,
-    VoteOption, WeightedVoteOption,
+    coin, coins, ensure, ensure_eq, to_json_binary, BankMsg, Coin, Deps, DistributionMsg, GovMsg,
+    Order, Response, StakingMsg, StdResult, Storage, Timestamp, Uint128, VoteOption,
+    WeightedVoteOption,
 };
 use cw2::set_contract_version;
-use cw_storage_plus::Item;
+use cw_storage_plus::{Item, Map};
+use std::cmp::min;
 
 use cw_utils::{must_pay, nonpayable};
 use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
 use sylvia::{contract, schemars};
 
+use mesh_apis::error::MeshError;
+use mesh_apis::local_staking_api::{LocalStakingApiHelper, PendingRewardsResponse};
+
 use crate::error::ContractError;
-use crate::msg::{ConfigResponse, OwnerMsg};
+use crate::msg::{
+    ConfigResponse, DelegationResponse, DelegationsResponse, OwnerMsg, RedelegationResponse,
+    RedelegationsResponse,
+};
 use crate::native_staking_callback;
-use crate::state::Config;
+use crate::state::{Config, Redelegation};
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -21,6 +29,11 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub struct NativeStakingProxyContract<'a> {
     config: Item<'a, Config>,
     burned: Item<'a, u128>,
+    /// Amount delegated to each validator, as tracked on stake / unstake / restake / burn.
+    /// Slashes on the native chain are not reflected
+    delegations: Map<'a, &'a str, Uint128>,
+    /// Redelegations made to each validator, possibly completed already
+    redelegations: Map<'a, &'a str, Vec<Redelegation>>,
 }
 
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
@@ -31,7 +44,66 @@ impl NativeStakingProxyContract<'_> {
         Self {
             config: Item::new("config"),
             burned: Item::new("burned"),
+            delegations: Map::new("delegations"),
+            redelegations: Map::new("redelegations"),
+        }
+    }
+
+    /// Redelegations to `validator` not completed at `now`
+    fn incoming_redelegations(
+        &self,
+        storage: &dyn Storage,
+        validator: &str,
+        now: Timestamp,
+    ) -> StdResult<Vec<Redelegation>> {
+        let mut redelegations = self
+            .redelegations
+            .may_load(storage, validator)?
+            .unwrap_or_default();
+        redelegations.retain(|redelegation| redelegation.completion > now);
+        Ok(redelegations)
+    }
+
+    fn add_delegation(
+        &self,
+        storage: &mut dyn Storage,
+        validator: &str,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        self.delegations
+            .update(storage, validator, |delegated| -> StdResult<_> {
+                Ok(delegated.unwrap_or_default() + amount)
+            })?;
+        Ok(())
+    }
+
+    /// Removes `amount` from the tracked delegation, failing if not enough is delegated
+    /// Fails unless the parent contract lets the staked tokens carry governance power
+    fn ensure_voting_enabled(deps: Deps, cfg: &Config) -> Result<(), ContractError> {
+        let power = LocalStakingApiHelper(cfg.parent.clone()).governance_power(deps)?;
+        ensure!(power.enabled, ContractError::VotingDisabled);
+        Ok(())
+    }
+
+    fn sub_delegation(
+        &self,
+        storage: &mut dyn Storage,
+        validator: &str,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let delegated = self
+            .delegations
+            .may_load(storage, validator)?
+            .unwrap_or_default();
+        let remaining = delegated
+            .checked_sub(amount)
+            .map_err(|_| ContractError::InsufficientDelegation(validator.to_owned(), delegated))?;
+        if remaining.is_zero() {
+            self.delegations.remove(storage, validator);
+        } else {
+            self.delegations.save(storage, validator, &remaining)?;
         }
+        Ok(())
     }
 
     /// The caller of the instantiation will be the native-staking contract.
@@ -48,6 +120,8 @@ impl NativeStakingProxyContract<'_> {
             denom,
             parent: ctx.info.sender.clone(),
             owner: ctx.deps.api.addr_validate(&owner)?,
+            auto_restake: None,
+            consolidating: false,
         };
         self.config.save(ctx.deps.storage, &config)?;
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -63,9 +137,9 @@ impl NativeStakingProxyContract<'_> {
         };
         let res = self.stake(exec_ctx, validator)?;
 
-        // Set owner as recipient of future withdrawals
+        // Set parent as recipient of future withdrawals, the rewards are distributed there
         let set_withdrawal = DistributionMsg::SetWithdrawAddress {
-            address: config.owner.into_string(),
+            address: config.parent.into_string(),
         };
 
         // Pass owner to caller's reply handler
@@ -78,9 +152,10 @@ impl NativeStakingProxyContract<'_> {
     #[sv::msg(exec)]
     fn stake(&self, ctx: ExecCtx, validator: String) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.parent, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.parent, ctx.info.sender, MeshError::Unauthorized);
 
         let amount = must_pay(&ctx.info, &cfg.denom)?;
+        self.add_delegation(ctx.deps.storage, &validator, amount)?;
 
         let amount = coin(amount.u128(), cfg.denom);
         let msg = StakingMsg::Delegate { validator, amount };
@@ -88,8 +163,12 @@ impl NativeStakingProxyContract<'_> {
         Ok(Response::new().add_message(msg))
     }
 
-    /// Burn `amount` tokens from the given validator, if set.
-    /// If `validator` is not set, undelegate evenly from all validators the user has stake.
+    /// Burns `amount` tokens of the user stake.
+    ///
+    /// Unbonded tokens not released yet are burned first, right away. The rest is undelegated
+    /// from the given validator, if set, then evenly from all the validators the user has stake
+    /// in, and burned once unbonded. Redelegating is never an option, as it doesn't free any
+    /// tokens.
     /// Can only be called by the parent contract
     #[sv::msg(exec)]
     fn burn(
@@ -99,86 +178,140 @@ impl NativeStakingProxyContract<'_> {
         amount: Coin,
     ) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.parent, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.parent, ctx.info.sender, MeshError::Unauthorized);
 
         nonpayable(&ctx.info)?;
 
         // Check denom
-        ensure_eq!(
-            amount.denom,
-            cfg.denom,
-            ContractError::InvalidDenom(amount.denom)
-        );
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
 
-        let delegations = match validator {
-            Some(validator) => {
-                match ctx
-                    .deps
-                    .querier
-                    .query_delegation(ctx.env.contract.address.clone(), validator)?
-                    .map(|full_delegation| {
-                        (
-                            full_delegation.validator,
-                            full_delegation.amount.amount.u128(),
-                        )
-                    }) {
-                    Some(delegation) => vec![delegation],
-                    None => vec![],
-                }
-            }
-            None => ctx
-                .deps
-                .querier
-                .query_all_delegations(ctx.env.contract.address.clone())?
-                .iter()
-                .map(|delegation| {
-                    (
-                        delegation.validator.clone(),
-                        delegation.amount.amount.u128(),
-                    )
-                })
-                .collect::<Vec<_>>(),
-        };
+        let mut resp = Response::new();
+        let mut to_burn = amount.amount.u128();
 
-        // Error if no validators
-        if delegations.is_empty() {
-            return Err(ContractError::InsufficientDelegations(
-                ctx.env.contract.address.to_string(),
-                amount.amount,
-            ));
+        // Liquid tokens, not already set apart for a previous burn
+        let balance = ctx
+            .deps
+            .querier
+            .query_balance(ctx.env.contract.address.clone(), &cfg.denom)?;
+        let pending_burns = self.burned.load(ctx.deps.storage)?;
+        let liquid = min(balance.amount.u128().saturating_sub(pending_burns), to_burn);
+        if liquid > 0 {
+            resp = resp.add_message(BankMsg::Burn {
+                amount: coins(liquid, &cfg.denom),
+            });
+            to_burn -= liquid;
+        }
+        if to_burn == 0 {
+            return Ok(resp);
         }
 
-        let (burned, burns) = mesh_burn::distribute_burn(&delegations, amount.amount.u128());
-
-        // Bail if we don't have enough delegations
-        if burned < amount.amount.u128() {
-            return Err(ContractError::InsufficientDelegations(
-                ctx.env.contract.address.to_string(),
-                amount.amount,
-            ));
+        let mut delegations = ctx
+            .deps
+            .querier
+            .query_all_delegations(ctx.env.contract.address.clone())?
+            .into_iter()
+            .map(|delegation| (delegation.validator, delegation.amount.amount.u128()))
+            .collect::<Vec<_>>();
+
+        // Preferred validator first, the others only cover what it can't
+        let mut undelegations = vec![];
+        if let Some(validator) = validator {
+            if let Some((_, delegated)) = delegations.iter_mut().find(|(v, _)| *v == validator) {
+                let undelegated = min(*delegated, to_burn);
+                *delegated -= undelegated;
+                to_burn -= undelegated;
+                undelegations.push((validator, undelegated));
+            }
+        }
+        delegations.retain(|(_, delegated)| *delegated > 0);
+        if to_burn > 0 {
+            // Bail if we don't have enough delegations
+            if delegations.is_empty() {
+                return Err(ContractError::InsufficientDelegations(
+                    ctx.env.contract.address.to_string(),
+                    amount.amount,
+                ));
+            }
+            let (burned, burns) = mesh_burn::distribute_burn(&delegations, to_burn);
+            if burned < to_burn {
+                return Err(ContractError::InsufficientDelegations(
+                    ctx.env.contract.address.to_string(),
+                    amount.amount,
+                ));
+            }
+            undelegations.extend(
+                burns
+                    .into_iter()
+                    .map(|(validator, undelegated)| (validator.clone(), undelegated)),
+            );
         }
 
         // Build undelegate messages
         // FIXME: Use an "immediate unbonding" message for undelegation
-        let mut undelegate_msgs = vec![];
-        for (validator, burn_amount) in burns {
-            let undelegate_msg = StakingMsg::Undelegate {
-                validator: validator.to_string(),
-                amount: coin(burn_amount, &cfg.denom),
-            };
-            undelegate_msgs.push(undelegate_msg);
+        let mut undelegated_total = 0;
+        for (validator, undelegated) in undelegations {
+            // The tracked delegation may be higher than the actual one, if slashed
+            let delegated = self
+                .delegations
+                .may_load(ctx.deps.storage, &validator)?
+                .unwrap_or_default();
+            let remaining = delegated.saturating_sub(Uint128::new(undelegated));
+            if remaining.is_zero() {
+                self.delegations.remove(ctx.deps.storage, &validator);
+            } else {
+                self.delegations
+                    .save(ctx.deps.storage, &validator, &remaining)?;
+            }
+            undelegated_total += undelegated;
+            resp = resp.add_message(StakingMsg::Undelegate {
+                validator,
+                amount: coin(undelegated, &cfg.denom),
+            });
         }
 
-        // Accounting trick to avoid burning stake
+        // Undelegated tokens are burned by `release_unbonded`, once unbonded
         self.burned.update(ctx.deps.storage, |old| {
-            Ok::<_, ContractError>(old + amount.amount.u128())
+            Ok::<_, ContractError>(old + undelegated_total)
         })?;
 
-        Ok(Response::new().add_messages(undelegate_msgs))
+        Ok(resp)
+    }
+
+    /// Undelegates all the stake of this proxy, to merge it into the default proxy of the owner.
+    /// Once unbonded, `release_unbonded` sends the tokens to the parent via `merge_proxy_stake`,
+    /// which stakes them with the other proxy.
+    /// Can only be called by the parent contract
+    #[sv::msg(exec)]
+    fn consolidate(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        let mut cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.parent, ctx.info.sender, MeshError::Unauthorized);
+
+        nonpayable(&ctx.info)?;
+
+        let msgs: Vec<_> = ctx
+            .deps
+            .querier
+            .query_all_delegations(ctx.env.contract.address)?
+            .into_iter()
+            .map(|delegation| StakingMsg::Undelegate {
+                validator: delegation.validator,
+                amount: delegation.amount,
+            })
+            .collect();
+        self.delegations.clear(ctx.deps.storage);
+
+        cfg.consolidating = true;
+        cfg.auto_restake = None;
+        self.config.save(ctx.deps.storage, &cfg)?;
+
+        Ok(Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "consolidate"))
     }
 
     /// Re-stakes the given amount from the one validator to another on behalf of the calling user.
-    /// Returns an error if the user doesn't have such stake
+    /// Returns an error if the user doesn't have such stake, or if stake redelegated to the source
+    /// validator is still being redelegated, as the staking module rejects redelegation chains
     #[sv::msg(exec)]
     fn restake(
         &self,
@@ -188,15 +321,39 @@ impl NativeStakingProxyContract<'_> {
         amount: Coin,
     ) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
 
         nonpayable(&ctx.info)?;
 
-        ensure_eq!(
-            amount.denom,
-            cfg.denom,
-            ContractError::InvalidDenom(amount.denom)
-        );
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
+
+        let now = ctx.env.block.time;
+        let incoming = self.incoming_redelegations(ctx.deps.storage, &src_validator, now)?;
+        if let Some(completion) = incoming.iter().map(|r| r.completion).max() {
+            return Err(ContractError::RedelegationInProgress(
+                src_validator,
+                completion,
+            ));
+        }
+        self.redelegations.remove(ctx.deps.storage, &src_validator);
+
+        // The redelegation completes after the unbonding period, as known by the parent contract
+        let unbonding_period = LocalStakingApiHelper(cfg.parent)
+            .unbonding_period(ctx.deps.as_ref())?
+            .unbonding_period;
+        if unbonding_period > 0 {
+            let mut redelegations =
+                self.incoming_redelegations(ctx.deps.storage, &dst_validator, now)?;
+            redelegations.push(Redelegation {
+                src_validator: src_validator.clone(),
+                completion: now.plus_seconds(unbonding_period),
+            });
+            self.redelegations
+                .save(ctx.deps.storage, &dst_validator, &redelegations)?;
+        }
+
+        self.sub_delegation(ctx.deps.storage, &src_validator, amount.amount)?;
+        self.add_delegation(ctx.deps.storage, &dst_validator, amount.amount)?;
 
         let msg = StakingMsg::Redelegate {
             src_validator,
@@ -206,6 +363,36 @@ impl NativeStakingProxyContract<'_> {
         Ok(Response::new().add_message(msg))
     }
 
+    /// Re-delegates the matured unbondings to `validator` in `release_unbonded`, instead of
+    /// releasing them to the parent, so they stay staked (and locked as vault collateral).
+    /// Releasing to the parent is restored with `None`
+    #[sv::msg(exec)]
+    fn set_auto_restake(
+        &self,
+        ctx: ExecCtx,
+        validator: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
+
+        nonpayable(&ctx.info)?;
+
+        if let Some(validator) = &validator {
+            ensure!(
+                ctx.deps.querier.query_validator(validator)?.is_some(),
+                ContractError::ValidatorNotFound(validator.clone())
+            );
+        }
+        cfg.auto_restake = validator;
+        self.config.save(ctx.deps.storage, &cfg)?;
+
+        let mut resp = Response::new().add_attribute("action", "set_auto_restake");
+        if let Some(validator) = &cfg.auto_restake {
+            resp = resp.add_attribute("validator", validator);
+        }
+        Ok(resp)
+    }
+
     /// Vote with the user's stake (over all delegations)
     #[sv::msg(exec)]
     fn vote(
@@ -215,9 +402,10 @@ impl NativeStakingProxyContract<'_> {
         vote: VoteOption,
     ) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
 
         nonpayable(&ctx.info)?;
+        Self::ensure_voting_enabled(ctx.deps.as_ref(), &cfg)?;
 
         let msg = GovMsg::Vote { proposal_id, vote };
         Ok(Response::new().add_message(msg))
@@ -232,9 +420,10 @@ impl NativeStakingProxyContract<'_> {
         vote: Vec<WeightedVoteOption>,
     ) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
 
         nonpayable(&ctx.info)?;
+        Self::ensure_voting_enabled(ctx.deps.as_ref(), &cfg)?;
 
         let msg = GovMsg::VoteWeighted {
             proposal_id,
@@ -244,16 +433,26 @@ impl NativeStakingProxyContract<'_> {
     }
 
     /// If the caller has any delegations, withdraw all rewards from those delegations and
-    /// send the tokens to the caller.
+    /// send the tokens to the parent contract, which distributes them among all its users.
+    /// The owner's share can then be withdrawn from the parent (see `withdraw_rewards_partial`).
+    /// Can be called by the owner, or by the parent contract on its behalf.
     /// NOTE: must make sure not to release unbonded tokens
     #[sv::msg(exec)]
     fn withdraw_rewards(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+        ensure!(
+            ctx.info.sender == cfg.owner || ctx.info.sender == cfg.parent,
+            MeshError::Unauthorized
+        );
 
         nonpayable(&ctx.info)?;
 
-        // Withdraw all delegations to the owner (already set as withdrawal address in instantiate)
+        // Proxies instantiated before the rewards were pooled have the owner as withdrawal address
+        let set_withdrawal = DistributionMsg::SetWithdrawAddress {
+            address: cfg.parent.to_string(),
+        };
+
+        // Withdraw all delegations to the parent
         let msgs: Vec<_> = ctx
             .deps
             .querier
@@ -263,10 +462,38 @@ impl NativeStakingProxyContract<'_> {
                 validator: delegation.validator,
             })
             .collect();
-        let res = Response::new().add_messages(msgs);
+
+        // And have the parent distribute them
+        let msg = to_json_binary(&native_staking_callback::sv::ExecMsg::DistributeProxyRewards {})?;
+        let wasm_msg = Execute {
+            contract_addr: cfg.parent.into_string(),
+            msg,
+            funds: vec![],
+        };
+        let res = Response::new()
+            .add_message(set_withdrawal)
+            .add_messages(msgs)
+            .add_message(wasm_msg);
         Ok(res)
     }
 
+    /// Withdraws `amount` of the owner's rewards pooled in the parent contract, leaving the rest
+    /// pending. The rewards are sent to the owner
+    #[sv::msg(exec)]
+    fn withdraw_rewards_partial(
+        &self,
+        ctx: ExecCtx,
+        amount: Coin,
+    ) -> Result<Response, ContractError> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
+
+        nonpayable(&ctx.info)?;
+
+        let msg = LocalStakingApiHelper(cfg.parent).withdraw_rewards_partial(amount)?;
+        Ok(Response::new().add_message(msg))
+    }
+
     /// Unstakes the given amount from the given validator on behalf of the calling user.
     /// Returns an error if the user doesn't have such stake.
     /// After the unbonding period, it will allow the user to claim the tokens (returning to vault)
@@ -278,27 +505,27 @@ impl NativeStakingProxyContract<'_> {
         amount: Coin,
     ) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
 
         nonpayable(&ctx.info)?;
 
-        ensure_eq!(
-            amount.denom,
-            cfg.denom,
-            ContractError::InvalidDenom(amount.denom)
-        );
+        ensure_eq!(amount.denom, cfg.denom, MeshError::InvalidDenom(cfg.denom));
+
+        self.sub_delegation(ctx.deps.storage, &validator, amount.amount)?;
 
         let msg = StakingMsg::Undelegate { validator, amount };
         Ok(Response::new().add_message(msg))
     }
 
     /// Releases any tokens that have fully unbonded from a previous unstake.
-    /// This will go back to the parent via `release_proxy_stake`.
-    /// Errors if the proxy doesn't have any liquid tokens
+    /// This will go back to the parent via `release_proxy_stake`, or be re-delegated to the
+    /// `auto_restake` validator if set. Once consolidated, they are merged into the default
+    /// proxy of the owner via `merge_proxy_stake` instead.
+    /// Tokens undelegated by `burn` are burned first, as they unbond.
     #[sv::msg(exec)]
     fn release_unbonded(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.owner, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.owner, ctx.info.sender, MeshError::Unauthorized);
 
         nonpayable(&ctx.info)?;
 
@@ -306,15 +533,52 @@ impl NativeStakingProxyContract<'_> {
         let balance = ctx
             .deps
             .querier
-            .query_balance(ctx.env.contract.address, cfg.denom)?;
-        // But discount burned stake
-        // FIXME: this is not accurate, as it doesn't take into account the unbonding period
-        let burned = self.burned.load(ctx.deps.storage)?;
-        let balance = coin(balance.amount.u128().saturating_sub(burned), &balance.denom);
+            .query_balance(ctx.env.contract.address, &cfg.denom)?
+            .amount
+            .u128();
+        // But burn the stake pending to be burned first
+        let pending_burns = self.burned.load(ctx.deps.storage)?;
+        let burned = min(balance, pending_burns);
+        let released = balance - burned;
+
+        let mut resp = Response::new();
+        if burned > 0 {
+            self.burned
+                .save(ctx.deps.storage, &(pending_burns - burned))?;
+            resp = resp.add_message(BankMsg::Burn {
+                amount: coins(burned, &cfg.denom),
+            });
+        }
 
         // Short circuit if there are no funds to send
-        if balance.amount.is_zero() {
-            return Ok(Response::new());
+        if released == 0 {
+            return Ok(resp);
+        }
+
+        // Staked with the default proxy of the owner, the parent keeps accounting them as staked
+        if cfg.consolidating {
+            let msg = to_json_binary(&native_staking_callback::sv::ExecMsg::MergeProxyStake {})?;
+            let wasm_msg = Execute {
+                contract_addr: cfg.parent.to_string(),
+                msg,
+                funds: coins(released, &cfg.denom),
+            };
+            return Ok(resp
+                .add_message(wasm_msg)
+                .add_attribute("merged", released.to_string()));
+        }
+
+        // Staked again instead, the parent keeps accounting them as staked
+        if let Some(validator) = cfg.auto_restake {
+            self.add_delegation(ctx.deps.storage, &validator, Uint128::new(released))?;
+            let msg = StakingMsg::Delegate {
+                validator: validator.clone(),
+                amount: coin(released, &cfg.denom),
+            };
+            return Ok(resp
+                .add_message(msg)
+                .add_attribute("restaked", released.to_string())
+                .add_attribute("validator", validator));
         }
 
         // Send them to the parent contract via `release_proxy_stake`
@@ -323,15 +587,85 @@ impl NativeStakingProxyContract<'_> {
         let wasm_msg = Execute {
             contract_addr: cfg.parent.to_string(),
             msg,
-            funds: vec![balance],
+            funds: coins(released, &cfg.denom),
         };
-        Ok(Response::new().add_message(wasm_msg))
+        Ok(resp.add_message(wasm_msg))
     }
 
     #[sv::msg(query)]
     fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
         Ok(self.config.load(ctx.deps.storage)?)
     }
+
+    /// Owner's rewards pooled in the parent contract, not withdrawn yet
+    #[sv::msg(query)]
+    fn pending_rewards(&self, ctx: QueryCtx) -> Result<PendingRewardsResponse, ContractError> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+        let rewards =
+            LocalStakingApiHelper(cfg.parent).pending_rewards(ctx.deps, cfg.owner.into_string())?;
+        Ok(rewards)
+    }
+
+    /// Amount delegated to the validator by this proxy, as tracked by the contract
+    #[sv::msg(query)]
+    fn delegation(
+        &self,
+        ctx: QueryCtx,
+        validator: String,
+    ) -> Result<DelegationResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let amount = self
+            .delegations
+            .may_load(ctx.deps.storage, &validator)?
+            .unwrap_or_default();
+        Ok(DelegationResponse {
+            validator,
+            amount: coin(amount.u128(), denom),
+        })
+    }
+
+    /// All the delegations of this proxy, as tracked by the contract
+    #[sv::msg(query)]
+    fn delegations(&self, ctx: QueryCtx) -> Result<DelegationsResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let delegations = self
+            .delegations
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (validator, amount) = item?;
+                Ok(DelegationResponse {
+                    validator,
+                    amount: coin(amount.u128(), &denom),
+                })
+            })
+            .collect::<StdResult<_>>()?;
+        Ok(DelegationsResponse { delegations })
+    }
+
+    /// Redelegations made by this proxy that are not completed yet. Their destination validators
+    /// can't be redelegated from until then
+    #[sv::msg(query)]
+    fn redelegations(&self, ctx: QueryCtx) -> Result<RedelegationsResponse, ContractError> {
+        let now = ctx.env.block.time;
+        let mut redelegations = vec![];
+        for item in self
+            .redelegations
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (dst_validator, entries) = item?;
+            redelegations.extend(
+                entries
+                    .into_iter()
+                    .filter(|entry| entry.completion > now)
+                    .map(|entry| RedelegationResponse {
+                        src_validator: entry.src_validator,
+                        dst_validator: dst_validator.clone(),
+                        completion: entry.completion,
+                    }),
+            );
+        }
+        Ok(RedelegationsResponse { redelegations })
+    }
 }
 
 // Some unit tests, due to mt limitations / unsupported msgs
@@ -340,11 +674,16 @@ mod tests {
     use super::*;
     use cosmwasm_std::DistributionMsg::SetWithdrawAddress;
     use cosmwasm_std::GovMsg::{Vote, VoteWeighted};
-    use cosmwasm_std::{CosmosMsg, Decimal, DepsMut};
+    use cosmwasm_std::{
+        ContractResult, CosmosMsg, Decimal, DepsMut, OwnedDeps, SystemResult, WasmQuery,
+    };
 
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    };
     use cosmwasm_std::VoteOption::Yes;
     use cw_utils::PaymentError;
+    use mesh_apis::local_staking_api::GovernancePowerResponse;
 
     static OSMO: &str = "uosmo";
     static CREATOR: &str = "staking"; // The creator of the proxy contract(s) is the staking contract
@@ -374,6 +713,20 @@ mod tests {
         (exec_ctx, contract)
     }
 
+    /// Mocks the governance power query of the parent contract
+    fn mock_governance_power(
+        deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+        enabled: bool,
+    ) {
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == CREATOR => {
+                let resp = to_json_binary(&GovernancePowerResponse { enabled }).unwrap();
+                SystemResult::Ok(ContractResult::Ok(resp))
+            }
+            _ => panic!("Unexpected query: {query:?}"),
+        });
+    }
+
     // Extra checks of instantiate returned messages and data
     #[test]
     fn instantiating() {
@@ -404,7 +757,7 @@ mod tests {
         assert_eq!(
             res.messages[1].msg,
             CosmosMsg::Distribution(SetWithdrawAddress {
-                address: OWNER.to_owned(),
+                address: CREATOR.to_owned(),
             })
         );
 
@@ -421,6 +774,7 @@ mod tests {
     #[test]
     fn voting() {
         let mut deps = mock_dependencies();
+        mock_governance_power(&mut deps, true);
         let (mut ctx, contract) = do_instantiate(deps.as_mut());
 
         // The owner can vote
@@ -450,17 +804,24 @@ mod tests {
         // Nobody else can vote
         ctx.info = mock_info("somebody", &[]);
         let res = contract.vote(ctx.branch(), proposal_id, vote.clone());
-        assert!(matches!(res.unwrap_err(), ContractError::Unauthorized {}));
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Mesh(MeshError::Unauthorized)
+        ));
 
         // Not even the creator
         ctx.info = mock_info(CREATOR, &[]);
         let res = contract.vote(ctx, proposal_id, vote);
-        assert!(matches!(res.unwrap_err(), ContractError::Unauthorized {}));
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Mesh(MeshError::Unauthorized)
+        ));
     }
 
     #[test]
     fn weighted_voting() {
         let mut deps = mock_dependencies();
+        mock_governance_power(&mut deps, true);
         let (mut ctx, contract) = do_instantiate(deps.as_mut());
 
         // The owner can weighted vote
@@ -493,11 +854,35 @@ mod tests {
         // Nobody else can vote
         ctx.info = mock_info("somebody", &[]);
         let res = contract.vote_weighted(ctx.branch(), proposal_id, vote.clone());
-        assert!(matches!(res.unwrap_err(), ContractError::Unauthorized {}));
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Mesh(MeshError::Unauthorized)
+        ));
 
         // Not even the creator
         ctx.info = mock_info(CREATOR, &[]);
         let res = contract.vote_weighted(ctx, proposal_id, vote);
-        assert!(matches!(res.unwrap_err(), ContractError::Unauthorized {}));
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Mesh(MeshError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn voting_disabled() {
+        let mut deps = mock_dependencies();
+        mock_governance_power(&mut deps, false);
+        let (mut ctx, contract) = do_instantiate(deps.as_mut());
+
+        // The parent contract doesn't let the stake vote
+        let res = contract.vote(ctx.branch(), 1, Yes);
+        assert_eq!(res.unwrap_err(), ContractError::VotingDisabled);
+
+        let vote = vec![WeightedVoteOption {
+            option: Yes,
+            weight: Decimal::one(),
+        }];
+        let res = contract.vote_weighted(ctx, 1, vote);
+        assert_eq!(res.unwrap_err(), ContractError::VotingDisabled);
     }
 }
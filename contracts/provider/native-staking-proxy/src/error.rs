@@ -1,5 +1,6 @@
-use cosmwasm_std::{StdError, Uint128};
+use cosmwasm_std::{StdError, Timestamp, Uint128};
 use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -10,15 +11,21 @@ pub enum ContractError {
     #[error("{0}")]
     Payment(#[from] PaymentError),
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
-    #[error("Try to send wrong denom: {0}")]
-    InvalidDenom(String),
+    #[error("Voting with the staked tokens is disabled")]
+    VotingDisabled,
 
     #[error("Validator {0} has not enough delegated funds: {1}")]
     InsufficientDelegation(String, Uint128),
 
     #[error("Native proxy {0} has not enough delegated funds: {1}")]
     InsufficientDelegations(String, Uint128),
+
+    #[error("Validator {0} not found")]
+    ValidatorNotFound(String),
+
+    #[error("Redelegation to {0} in progress, it can't be redelegated from until {1}")]
+    RedelegationInProgress(String, Timestamp),
 }
@@ -1,7 +1,8 @@
 pub mod contract;
 pub mod error;
 pub mod msg;
-#[cfg(test)]
+// The multitests run the vault without the provider module custom messages
+#[cfg(all(test, not(feature = "provider-bindings")))]
 mod multitest;
 pub mod native_staking_callback;
 mod state;
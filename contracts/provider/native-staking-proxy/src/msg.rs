@@ -1,8 +1,33 @@
 use crate::state::Config;
 use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Coin, Timestamp};
 
 pub type ConfigResponse = Config;
 
+#[cw_serde]
+pub struct DelegationResponse {
+    pub validator: String,
+    pub amount: Coin,
+}
+
+#[cw_serde]
+pub struct DelegationsResponse {
+    pub delegations: Vec<DelegationResponse>,
+}
+
+#[cw_serde]
+pub struct RedelegationResponse {
+    pub src_validator: String,
+    pub dst_validator: String,
+    /// When the redelegation completes. Until then, `dst_validator` can't be redelegated from
+    pub completion: Timestamp,
+}
+
+#[cw_serde]
+pub struct RedelegationsResponse {
+    pub redelegations: Vec<RedelegationResponse>,
+}
+
 /// The message that is binary encoded in a proxy contract's `Instantiate` message's data
 #[cw_serde]
 pub struct OwnerMsg {
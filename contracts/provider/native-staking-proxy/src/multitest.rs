@@ -1,12 +1,15 @@
 use anyhow::Result as AnyResult;
 
 use cosmwasm_std::testing::mock_env;
-use cosmwasm_std::{coin, coins, to_json_binary, Addr, Decimal, Validator};
+use cosmwasm_std::{coin, coins, to_json_binary, Addr, Decimal, Uint128, Validator};
 
 use cw_multi_test::{App as MtApp, StakingInfo};
 
 use sylvia::multitest::{App, Proxy};
 
+use mesh_apis::error::MeshError;
+use mesh_native_staking::contract::sv::mt::NativeStakingContractProxy;
+use mesh_native_staking::contract::NativeStakingContract;
 use mesh_vault::contract::sv::mt::VaultContractProxy;
 use mesh_vault::contract::VaultContract;
 use mesh_vault::msg::LocalStakingInfo;
@@ -14,7 +17,8 @@ use mesh_vault::msg::LocalStakingInfo;
 use crate::contract;
 use crate::contract::sv::mt::NativeStakingProxyContractProxy;
 use crate::contract::NativeStakingProxyContract;
-use crate::msg::ConfigResponse;
+use crate::error::ContractError;
+use crate::msg::{ConfigResponse, DelegationResponse, RedelegationResponse};
 
 const OSMO: &str = "uosmo";
 const UNBONDING_PERIOD: u64 = 17 * 24 * 60 * 60; // 7 days
@@ -74,6 +78,7 @@ fn setup<'app>(
             proxy_code_id: staking_proxy_code.code_id(),
             slash_ratio_dsign: Decimal::percent(5),
             slash_ratio_offline: Decimal::percent(5),
+            owner: Some(owner.to_owned()),
         })
         .unwrap(),
         label: None,
@@ -84,6 +89,9 @@ fn setup<'app>(
         .instantiate(
             OSMO.to_owned(),
             Some(LocalStakingInfo::New(staking_init_info)),
+            None,
+            None,
+            None,
         )
         .with_label("Vault")
         .call(owner)
@@ -103,6 +111,7 @@ fn setup<'app>(
                 coin(100, OSMO),
                 to_json_binary(&mesh_native_staking::msg::StakeMsg {
                     validator: validator.to_owned(),
+                    proxy: None,
                 })
                 .unwrap(),
             )
@@ -138,6 +147,8 @@ fn instantiation() {
             denom: OSMO.to_owned(),
             parent: Addr::unchecked(staking_addr), // parent is the staking contract
             owner: Addr::unchecked(user),          // owner is the user
+            auto_restake: None,
+            consolidating: false,
         }
     );
 
@@ -180,6 +191,7 @@ fn staking() {
             coin(20, OSMO),
             to_json_binary(&mesh_native_staking::msg::StakeMsg {
                 validator: validator.to_owned(),
+                proxy: None,
             })
             .unwrap(),
         )
@@ -194,6 +206,14 @@ fn staking() {
             .unwrap(),
         coin(0, OSMO)
     );
+    // Which is tracked by the proxy
+    assert_eq!(
+        staking_proxy.delegation(validator.to_owned()).unwrap(),
+        DelegationResponse {
+            validator: validator.to_owned(),
+            amount: coin(120, OSMO),
+        }
+    );
     let delegation = app
         .app()
         .wrap()
@@ -237,10 +257,131 @@ fn restaking() {
     let delegation2 = app
         .app()
         .wrap()
-        .query_delegation(staking_proxy.contract_addr, validator2.to_owned())
+        .query_delegation(staking_proxy.contract_addr.clone(), validator2.to_owned())
         .unwrap()
         .unwrap();
     assert_eq!(delegation2.amount, coin(30, OSMO));
+
+    // And tracked by the proxy
+    let delegations = staking_proxy.delegations().unwrap().delegations;
+    assert_eq!(
+        delegations,
+        [
+            DelegationResponse {
+                validator: validator.to_owned(),
+                amount: coin(70, OSMO),
+            },
+            DelegationResponse {
+                validator: validator2.to_owned(),
+                amount: coin(30, OSMO),
+            },
+        ]
+    );
+}
+
+#[test]
+fn chained_restaking() {
+    let owner = "vault_admin";
+
+    let staking_addr = "contract1"; // Second contract (instantiated by vault)
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let validators = ["validator1", "validator2", "validator3"];
+
+    let app = init_app(user, &validators); // Fund user, create validators
+    setup(&app, owner, user, &validators[..1]).unwrap();
+
+    // The redelegations are tracked once the unbonding period is known
+    let staking: Proxy<'_, MtApp, NativeStakingContract<'_>> =
+        Proxy::new(Addr::unchecked(staking_addr), &app);
+    staking
+        .set_unbonding_period(UNBONDING_PERIOD)
+        .call(owner)
+        .unwrap();
+
+    let staking_proxy: Proxy<'_, MtApp, NativeStakingProxyContract<'_>> =
+        Proxy::new(Addr::unchecked(proxy_addr), &app);
+
+    staking_proxy
+        .restake(
+            validators[0].to_owned(),
+            validators[1].to_owned(),
+            coin(30, OSMO),
+        )
+        .call(user)
+        .unwrap();
+    let completion = app.app().block_info().time.plus_seconds(UNBONDING_PERIOD);
+    assert_eq!(
+        staking_proxy.redelegations().unwrap().redelegations,
+        [RedelegationResponse {
+            src_validator: validators[0].to_owned(),
+            dst_validator: validators[1].to_owned(),
+            completion,
+        }]
+    );
+
+    // The redelegated stake can't be redelegated again until the redelegation completes
+    let err = staking_proxy
+        .restake(
+            validators[1].to_owned(),
+            validators[2].to_owned(),
+            coin(10, OSMO),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::RedelegationInProgress(validators[1].to_owned(), completion)
+    );
+
+    // The source validator can still be redelegated from
+    staking_proxy
+        .restake(
+            validators[0].to_owned(),
+            validators[2].to_owned(),
+            coin(10, OSMO),
+        )
+        .call(user)
+        .unwrap();
+    assert_eq!(
+        staking_proxy.redelegations().unwrap().redelegations.len(),
+        2
+    );
+
+    // Once completed
+    app.app_mut().update_block(|block| {
+        block.time = block.time.plus_seconds(UNBONDING_PERIOD);
+        block.height += UNBONDING_PERIOD / 5;
+    });
+    assert_eq!(staking_proxy.redelegations().unwrap().redelegations, []);
+    staking_proxy
+        .restake(
+            validators[1].to_owned(),
+            validators[2].to_owned(),
+            coin(10, OSMO),
+        )
+        .call(user)
+        .unwrap();
+
+    let delegations = staking_proxy.delegations().unwrap().delegations;
+    assert_eq!(
+        delegations,
+        [
+            DelegationResponse {
+                validator: validators[0].to_owned(),
+                amount: coin(60, OSMO),
+            },
+            DelegationResponse {
+                validator: validators[1].to_owned(),
+                amount: coin(20, OSMO),
+            },
+            DelegationResponse {
+                validator: validators[2].to_owned(),
+                amount: coin(20, OSMO),
+            },
+        ]
+    );
 }
 
 #[test]
@@ -264,6 +405,23 @@ fn unstaking() {
         .unstake(validator.to_owned(), coin(50, OSMO))
         .call(user)
         .unwrap();
+    assert_eq!(
+        staking_proxy
+            .delegation(validator.to_owned())
+            .unwrap()
+            .amount,
+        coin(50, OSMO)
+    );
+
+    // Can't unstake more than delegated
+    let err = staking_proxy
+        .unstake(validator.to_owned(), coin(60, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientDelegation(validator.to_owned(), 50u128.into())
+    );
 
     // Check that funds have been unstaked
     let delegation = app
@@ -351,16 +509,14 @@ fn burning() {
         coin(10, OSMO)
     );
 
-    // But they cannot be released
+    // But they are burned instead of being released
     staking_proxy.release_unbonded().call(user).unwrap();
-
-    // Check that the contract still has the funds (they are being effectively "burned")
     assert_eq!(
         app.app()
             .wrap()
             .query_balance(staking_proxy.contract_addr, OSMO)
             .unwrap(),
-        coin(10, OSMO)
+        coin(0, OSMO)
     );
 }
 
@@ -433,16 +589,71 @@ fn burning_multiple_delegations() {
         coin(15, OSMO)
     );
 
-    // But they cannot be released
+    // But they are burned instead of being released
     staking_proxy.release_unbonded().call(user).unwrap();
-
-    // Check that the contract still has the funds (they are being effectively "burned")
     assert_eq!(
         app.app()
             .wrap()
             .query_balance(staking_proxy.contract_addr, OSMO)
             .unwrap(),
-        coin(15, OSMO)
+        coin(0, OSMO)
+    );
+}
+
+#[test]
+fn burning_unbonded_first() {
+    let owner = "vault_admin";
+
+    let staking_addr = "contract1"; // Second contract (instantiated by vault on instantiation)
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let validators = ["validator1", "validator2"]; // Where to stake / unstake
+
+    let app = init_app(user, &validators); // Fund user, create validator
+    setup(&app, owner, user, &validators).unwrap();
+
+    // Access staking proxy instance
+    let staking_proxy: Proxy<'_, MtApp, NativeStakingProxyContract<'_>> =
+        Proxy::new(Addr::unchecked(proxy_addr), &app);
+
+    // Unbond some tokens, without releasing them
+    staking_proxy
+        .unstake(validators[1].to_owned(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+
+    // The unbonded tokens are burned right away, the rest is undelegated from the validator
+    // first, then from the others
+    staking_proxy
+        .burn(Some(validators[0].to_owned()), coin(170, OSMO))
+        .call(staking_addr)
+        .unwrap();
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(staking_proxy.contract_addr.clone(), OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+    let delegations = staking_proxy.delegations().unwrap().delegations;
+    assert_eq!(
+        delegations,
+        [DelegationResponse {
+            validator: validators[1].to_owned(),
+            amount: coin(30, OSMO),
+        }]
+    );
+
+    // Not enough left to burn
+    let err = staking_proxy
+        .burn(None, coin(31, OSMO))
+        .call(staking_addr)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InsufficientDelegations(proxy_addr.to_owned(), 31u128.into())
     );
 }
 
@@ -492,6 +703,95 @@ fn releasing_unbonded() {
     );
 }
 
+#[test]
+fn auto_restaking() {
+    let owner = "vault_admin";
+
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let validators = ["validator1", "validator2"];
+
+    let app = init_app(user, &validators); // Fund user, create validators
+    let vault = setup(&app, owner, user, &validators).unwrap();
+
+    // Access staking proxy instance
+    let staking_proxy: Proxy<'_, MtApp, NativeStakingProxyContract<'_>> =
+        Proxy::new(Addr::unchecked(proxy_addr), &app);
+
+    // Only the owner, to an existing validator
+    let err = staking_proxy
+        .set_auto_restake(Some(validators[1].to_owned()))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = staking_proxy
+        .set_auto_restake(Some("validator3".to_owned()))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ValidatorNotFound("validator3".to_owned())
+    );
+    staking_proxy
+        .set_auto_restake(Some(validators[1].to_owned()))
+        .call(user)
+        .unwrap();
+    assert_eq!(
+        staking_proxy.config().unwrap().auto_restake,
+        Some(validators[1].to_owned())
+    );
+
+    // The matured unbonding is delegated to validator2, instead of going back to the vault
+    staking_proxy
+        .unstake(validators[0].to_owned(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+    staking_proxy.release_unbonded().call(user).unwrap();
+
+    let delegation = app
+        .app()
+        .wrap()
+        .query_delegation(
+            staking_proxy.contract_addr.clone(),
+            validators[1].to_owned(),
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(delegation.amount, coin(200, OSMO));
+    assert_eq!(
+        staking_proxy
+            .delegation(validators[1].to_owned())
+            .unwrap()
+            .amount,
+        coin(200, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(vault.contract_addr.clone(), OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // Disabled, the unbondings are released to the vault again
+    staking_proxy.set_auto_restake(None).call(user).unwrap();
+    staking_proxy
+        .unstake(validators[1].to_owned(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+    staking_proxy.release_unbonded().call(user).unwrap();
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(50, OSMO)
+    );
+}
+
 #[test]
 fn withdrawing_rewards() {
     let owner = "vault_admin";
@@ -527,13 +827,17 @@ fn withdrawing_rewards() {
     // Withdraw rewards
     staking_proxy.withdraw_rewards().call(user).unwrap();
 
-    // User now has some rewards
-    let current_funds = app.app().wrap().query_balance(user, OSMO).unwrap();
-    assert!(current_funds.amount > original_user_funds.amount);
-
-    // Staking hasn't received any rewards
+    // Staking has received the rewards, all pending for the single user
     let staking_funds = app.app().wrap().query_balance(staking_addr, OSMO).unwrap();
-    assert_eq!(original_staking_funds, staking_funds);
+    assert!(staking_funds.amount > original_staking_funds.amount);
+    assert_eq!(
+        staking_proxy.pending_rewards().unwrap().rewards.amount,
+        staking_funds.amount - original_staking_funds.amount
+    );
+
+    // User hasn't received them yet
+    let current_funds = app.app().wrap().query_balance(user, OSMO).unwrap();
+    assert_eq!(original_user_funds, current_funds);
 
     // Vault hasn't received any rewards
     let vault_funds = app
@@ -544,6 +848,60 @@ fn withdrawing_rewards() {
     assert_eq!(original_vault_funds, vault_funds);
 }
 
+#[test]
+fn withdrawing_pooled_rewards_partially() {
+    let owner = "vault_admin";
+
+    let staking_addr = "contract1"; // Second contract (instantiated by vault)
+    let proxy_addr = "contract2"; // Third contract (instantiated by staking contract on stake)
+
+    let user = "user1"; // One who wants to local stake (uses the proxy)
+    let validator = "validator1"; // Where to stake / unstake
+
+    let app = init_app(user, &[validator]); // Fund user, create validator
+    setup(&app, owner, user, &[validator]).unwrap();
+
+    let staking: Proxy<'_, MtApp, NativeStakingContract<'_>> =
+        Proxy::new(Addr::unchecked(staking_addr), &app);
+    let staking_proxy: Proxy<'_, MtApp, NativeStakingProxyContract<'_>> =
+        Proxy::new(Addr::unchecked(proxy_addr), &app);
+
+    // Rewards pooled in the staking contract, all for the single user
+    staking
+        .distribute_rewards()
+        .with_funds(&coins(100, OSMO))
+        .call(user)
+        .unwrap();
+    assert_eq!(
+        staking_proxy.pending_rewards().unwrap().rewards,
+        coin(100, OSMO)
+    );
+
+    // Only the owner can withdraw them
+    let err = staking_proxy
+        .withdraw_rewards_partial(coin(30, OSMO))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    let original_user_funds = app.app().wrap().query_balance(user, OSMO).unwrap();
+    staking_proxy
+        .withdraw_rewards_partial(coin(30, OSMO))
+        .call(user)
+        .unwrap();
+
+    // The rest is still pending
+    assert_eq!(
+        staking_proxy.pending_rewards().unwrap().rewards,
+        coin(70, OSMO)
+    );
+    let current_funds = app.app().wrap().query_balance(user, OSMO).unwrap();
+    assert_eq!(
+        current_funds.amount,
+        original_user_funds.amount + Uint128::new(30)
+    );
+}
+
 fn process_staking_unbondings(app: &App<MtApp>) {
     // Advance unbonding period
     app.app_mut().update_block(|block| {
@@ -12,4 +12,16 @@ pub trait NativeStakingCallback {
     /// The native-staking contract will then send those tokens back to vault and release the claim.
     #[sv::msg(exec)]
     fn release_proxy_stake(&self, _ctx: ExecCtx) -> Result<Response, Self::Error>;
+
+    /// This sends the unbonded tokens of a consolidated proxy to native-staking. (See info.funds)
+    /// The native-staking contract stakes them with the default proxy of the user, so they stay
+    /// staked (and locked as vault collateral).
+    #[sv::msg(exec)]
+    fn merge_proxy_stake(&self, _ctx: ExecCtx) -> Result<Response, Self::Error>;
+
+    /// This is called after the proxy withdrew the rewards of its delegations, which the
+    /// distribution module sent to native-staking (the withdrawal address of the proxy).
+    /// The native-staking contract will then distribute them among all its users.
+    #[sv::msg(exec)]
+    fn distribute_proxy_rewards(&self, _ctx: ExecCtx) -> Result<Response, Self::Error>;
 }
@@ -1,5 +1,5 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Timestamp};
 
 #[cw_serde]
 pub struct Config {
@@ -11,4 +11,23 @@ pub struct Config {
 
     /// The address of the parent contract (where we get and return stake)
     pub parent: Addr,
+
+    /// Validator the matured unbondings are re-delegated to by `release_unbonded`, instead of
+    /// being released to the parent, if set
+    #[serde(default)]
+    pub auto_restake: Option<String>,
+
+    /// Whether this proxy is being merged into the default proxy of the owner. Its unbonded
+    /// tokens are then staked with the other proxy, instead of being released to the parent
+    #[serde(default)]
+    pub consolidating: bool,
+}
+
+/// Redelegation made by the proxy to a validator, not completed yet
+#[cw_serde]
+pub struct Redelegation {
+    /// Validator the stake was redelegated from
+    pub src_validator: String,
+    /// When the redelegation completes
+    pub completion: Timestamp,
 }
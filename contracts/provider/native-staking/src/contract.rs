@@ -1,36 +1,60 @@
 use cosmwasm_std::Order::Ascending;
 use cosmwasm_std::{
-    from_json, Addr, Decimal, DepsMut, Event, Reply, Response, StdResult, SubMsgResponse, WasmMsg,
+    coin, ensure, from_json, to_json_binary, Addr, BankMsg, Decimal, DepsMut, Env, Event, Reply,
+    Response, StdResult, Storage, SubMsgResponse, Uint128, Uint256, WasmMsg,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw_storage_plus::{Item, Map};
-use cw_utils::parse_instantiate_response_data;
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx, SudoCtx};
+use cw_utils::{must_pay, nonpayable, parse_instantiate_response_data};
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx, ReplyCtx, SudoCtx};
 use sylvia::{contract, schemars};
 
+use mesh_apis::error::MeshError;
+use mesh_apis::events::RewardsEvent;
 use mesh_apis::local_staking_api;
+use mesh_apis::migration::{Upgrade, OWNERSHIP_VERSION};
+use mesh_apis::ownership_api;
 use mesh_apis::vault_api::{SlashInfo, VaultApiHelper};
 use mesh_native_staking_proxy::msg::OwnerMsg;
 use mesh_native_staking_proxy::native_staking_callback;
 
 use crate::error::ContractError;
-use crate::msg::{ConfigResponse, OwnerByProxyResponse, ProxyByOwnerResponse};
-use crate::state::Config;
+use crate::msg::{
+    ConfigResponse, OwnerByProxyResponse, ProxiesResponse, ProxyByOwnerResponse, ProxyInfo,
+};
+use crate::state::{Config, Distribution, Stake};
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const REPLY_ID_INSTANTIATE: u64 = 2;
+pub const REPLY_ID_WITHDRAW_REWARDS: u64 = 3;
+
+/// Aligns rewards distribution computations, see `external-staking` for the details
+pub const DISTRIBUTION_POINTS_SCALE: Uint256 = Uint256::from_u128(1_000_000_000);
 
 pub struct NativeStakingContract<'a> {
     pub config: Item<'a, Config>,
     /// Map of proxy contract address by owner address
     pub proxy_by_owner: Map<'a, &'a Addr, Addr>,
-    /// Reverse map of owner address by proxy contract address
+    /// Reverse map of owner address by proxy contract address, for all the proxies
     pub owner_by_proxy: Map<'a, &'a Addr, Addr>,
+    /// Additional proxies of the owners, besides the default one, by (owner, label)
+    pub labeled_proxies: Map<'a, (&'a Addr, &'a str), Addr>,
+    /// Label of the proxy being instantiated, for the reply. Not set for the default proxy
+    pub pending_proxy_label: Item<'a, String>,
+    /// Proxies being merged into the default proxy of their owner, with the validator their
+    /// stake is delegated to there
+    pub consolidating: Map<'a, &'a Addr, String>,
     /// Map of delegators per validator
     // This is used for prefixing and ranging during slashing
     pub delegators: Map<'a, (&'a str, &'a Addr), bool>,
+    /// Stake of every user, for rewards distribution
+    pub stakes: Map<'a, &'a Addr, Stake>,
+    /// Rewards distribution over all the users stake
+    pub distribution: Item<'a, Distribution>,
+    /// Owner whose rewards are being withdrawn along with its proxies ones, for the reply
+    pub pending_rewards_owner: Item<'a, Addr>,
 }
 
 pub(crate) enum SlashingReason {
@@ -43,17 +67,25 @@ pub(crate) enum SlashingReason {
 #[sv::error(ContractError)]
 #[sv::messages(local_staking_api as LocalStakingApi)]
 #[sv::messages(native_staking_callback as NativeStakingCallback)]
+#[sv::messages(ownership_api as OwnershipApi)]
 impl NativeStakingContract<'_> {
     pub const fn new() -> Self {
         Self {
             config: Item::new("config"),
             proxy_by_owner: Map::new("proxies"),
             owner_by_proxy: Map::new("owners"),
+            labeled_proxies: Map::new("labeled_proxies"),
+            pending_proxy_label: Item::new("pending_proxy_label"),
+            consolidating: Map::new("consolidating"),
             delegators: Map::new("delegators"),
+            stakes: Map::new("stakes"),
+            distribution: Item::new("distribution"),
+            pending_rewards_owner: Item::new("pending_rewards_owner"),
         }
     }
 
-    /// The caller of the instantiation will be the vault contract
+    /// The caller of the instantiation will be the vault contract.
+    /// `owner` is left unset by default, as the vault can't manage this contract.
     #[sv::msg(instantiate)]
     pub fn instantiate(
         &self,
@@ -62,6 +94,7 @@ impl NativeStakingContract<'_> {
         proxy_code_id: u64,
         slash_ratio_dsign: Decimal,
         slash_ratio_offline: Decimal,
+        owner: Option<String>,
     ) -> Result<Response, ContractError> {
         if slash_ratio_dsign > Decimal::one() || slash_ratio_offline > Decimal::one() {
             return Err(ContractError::InvalidSlashRatio);
@@ -73,12 +106,42 @@ impl NativeStakingContract<'_> {
             vault: VaultApiHelper(ctx.info.sender),
             slash_ratio_dsign,
             slash_ratio_offline,
+            voting_enabled: true,
+            unbonding_period: 0,
         };
         self.config.save(ctx.deps.storage, &config)?;
+        let owner = owner
+            .map(|owner| ctx.deps.api.addr_validate(&owner))
+            .transpose()?;
+        ownership_api::initialize_owner(ctx.deps.storage, owner)?;
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
         Ok(Response::new())
     }
 
+    /// Migrates from an older version of the contract.
+    /// Contracts from before the ownership storage get their wasm admin as owner.
+    #[sv::msg(migrate)]
+    pub fn migrate(&self, ctx: MigrateCtx) -> Result<Response, ContractError> {
+        let stored = get_contract_version(ctx.deps.storage)?;
+        let upgrade = Upgrade::check(
+            &stored.contract,
+            &stored.version,
+            CONTRACT_NAME,
+            CONTRACT_VERSION,
+        )?;
+
+        if upgrade.crosses(OWNERSHIP_VERSION)? {
+            let admin = ownership_api::wasm_admin(&ctx.deps.querier, &ctx.env.contract.address)?;
+            ownership_api::migrate_owner(ctx.deps.storage, admin)?;
+        }
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        let event = Event::new("migrate")
+            .add_attribute("from_version", stored.version)
+            .add_attribute("to_version", CONTRACT_VERSION);
+        Ok(Response::new().add_event(event))
+    }
+
     /// This is called every time there's a change of the active validator set that implies slashing.
     /// In test code, this is called from `test_handle_jailing`.
     /// In non-test code, this is called from `sudo`.
@@ -142,15 +205,20 @@ impl NativeStakingContract<'_> {
 
         let mut slash_infos = vec![];
         for (owner, _) in &owners {
-            // Get owner proxy address
-            let proxy = self.proxy_by_owner.load(deps.storage, owner)?;
-            // Get proxy's delegation (pre-slashing?) amount over validator
+            let proxies = self.load_proxies(deps.storage, owner)?;
+            if proxies.is_empty() {
+                return Err(ContractError::NoProxy(owner.to_string()));
+            }
+            // Get proxies' delegation (pre-slashing?) amount over validator
             // TODO: Confirm queried delegation amounts are pre- or post-slashing
-            let delegation = deps
-                .querier
-                .query_delegation(proxy, validator)?
-                .map(|full_delegation| full_delegation.amount.amount)
-                .unwrap_or_default();
+            let mut delegation = Uint128::zero();
+            for (_, proxy) in proxies {
+                delegation += deps
+                    .querier
+                    .query_delegation(proxy, validator)?
+                    .map(|full_delegation| full_delegation.amount.amount)
+                    .unwrap_or_default();
+            }
 
             if delegation.is_zero() {
                 // Maintenance: Remove delegator from map in passing
@@ -175,6 +243,281 @@ impl NativeStakingContract<'_> {
         Ok(Some(msg))
     }
 
+    /// Distributes the sent rewards among all the users, proportionally to the amount of tokens
+    /// they have staked.
+    /// The staking rewards of the users' proxies are distributed when the proxies withdraw them
+    /// (see `distribute_proxy_rewards`). This is for any other rewards, sent by anyone
+    #[sv::msg(exec)]
+    fn distribute_rewards(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+        let amount = must_pay(&ctx.info, &cfg.denom)?;
+
+        let mut distribution = self
+            .distribution
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        ensure!(!distribution.total_stake.is_zero(), ContractError::NoStake);
+
+        Self::distribute(&mut distribution, amount);
+        self.distribution.save(ctx.deps.storage, &distribution)?;
+
+        let event = Event::from(
+            RewardsEvent::new(coin(amount.u128(), &cfg.denom))
+                .lienholder(&ctx.env.contract.address),
+        );
+        Ok(Response::new().add_event(event))
+    }
+
+    /// Adds `amount` of rewards to the points of every staked token
+    pub(crate) fn distribute(distribution: &mut Distribution, amount: Uint128) {
+        let total_stake = Uint256::from(distribution.total_stake);
+        let points_distributed =
+            Uint256::from(amount) * DISTRIBUTION_POINTS_SCALE + distribution.points_leftover;
+        let points_per_stake = points_distributed / total_stake;
+
+        distribution.points_leftover = points_distributed - points_per_stake * total_stake;
+        distribution.points_per_stake += points_per_stake;
+        distribution.held += amount;
+    }
+
+    /// Withdraws the caller's share of the distributed rewards
+    #[sv::msg(exec)]
+    fn withdraw_rewards(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.withdraw_user_rewards(
+            ctx.deps.storage,
+            &ctx.env.contract.address,
+            &ctx.info.sender,
+            None,
+        )
+    }
+
+    /// Sends `amount` (all of them if not set) of the pending rewards of `user` to it
+    pub(crate) fn withdraw_user_rewards(
+        &self,
+        storage: &mut dyn Storage,
+        contract: &Addr,
+        user: &Addr,
+        amount: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        let mut stake = self.stakes.may_load(storage, user)?.unwrap_or_default();
+        let mut distribution = self.distribution.may_load(storage)?.unwrap_or_default();
+
+        let pending = Self::calculate_reward(&stake, &distribution)?;
+        if pending.is_zero() {
+            return Err(ContractError::NoRewards);
+        }
+        let amount = amount.unwrap_or(pending);
+        ensure!(
+            amount <= pending,
+            ContractError::InsufficientRewards(pending)
+        );
+        if amount.is_zero() {
+            return Err(ContractError::NoRewards);
+        }
+
+        stake.withdrawn_funds += amount;
+        self.stakes.save(storage, user, &stake)?;
+        distribution.held -= amount;
+        self.distribution.save(storage, &distribution)?;
+
+        let denom = self.config.load(storage)?.denom;
+        let rewards = coin(amount.u128(), denom);
+        let event = Event::from(
+            RewardsEvent::new(rewards.clone())
+                .delegator(user)
+                .lienholder(contract),
+        );
+        let msg = BankMsg::Send {
+            to_address: user.to_string(),
+            amount: vec![rewards],
+        };
+        Ok(Response::new().add_message(msg).add_event(event))
+    }
+
+    /// Accounts `amount` of new stake of `owner`, aligning its rewards points so the rewards
+    /// distributed before are not affected
+    pub(crate) fn increase_stake(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let mut distribution = self.distribution.may_load(storage)?.unwrap_or_default();
+        let mut stake = self.stakes.may_load(storage, owner)?.unwrap_or_default();
+
+        stake.stake += amount;
+        stake
+            .points_alignment
+            .stake_increased(amount, distribution.points_per_stake);
+        distribution.total_stake += amount;
+
+        self.stakes.save(storage, owner, &stake)?;
+        self.distribution.save(storage, &distribution)?;
+        Ok(())
+    }
+
+    /// Accounts `amount` of stake of `owner` being released or burned, aligning its rewards
+    /// points so the rewards distributed before are not affected
+    pub(crate) fn decrease_stake(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let mut distribution = self.distribution.may_load(storage)?.unwrap_or_default();
+        let mut stake = self.stakes.may_load(storage, owner)?.unwrap_or_default();
+
+        // Stake accounted before rewards distribution was in place is not tracked
+        let amount = amount.min(stake.stake);
+        stake.stake -= amount;
+        stake
+            .points_alignment
+            .stake_decreased(amount, distribution.points_per_stake);
+        distribution.total_stake -= amount;
+
+        self.stakes.save(storage, owner, &stake)?;
+        self.distribution.save(storage, &distribution)?;
+        Ok(())
+    }
+
+    /// Calculates the rewards of a user, based on its `Stake` and the current `Distribution`
+    pub(crate) fn calculate_reward(
+        stake: &Stake,
+        distribution: &Distribution,
+    ) -> Result<Uint128, ContractError> {
+        let points = distribution.points_per_stake * Uint256::from(stake.stake);
+        let points = stake.points_alignment.align(points);
+        let total = Uint128::try_from(points / DISTRIBUTION_POINTS_SCALE)?;
+
+        Ok(total - stake.withdrawn_funds)
+    }
+
+    /// Enables or disables voting with the locally staked tokens, through the user proxies.
+    /// Only the owner can call this
+    #[sv::msg(exec)]
+    fn set_voting_enabled(&self, ctx: ExecCtx, enabled: bool) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.voting_enabled = enabled;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_voting_enabled")
+            .add_attribute("enabled", enabled.to_string());
+        Ok(resp)
+    }
+
+    /// Sets the unbonding period of the chain, in seconds, so that the user proxies know when
+    /// redelegations complete. Only the owner can call this
+    #[sv::msg(exec)]
+    fn set_unbonding_period(
+        &self,
+        ctx: ExecCtx,
+        unbonding_period: u64,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.unbonding_period = unbonding_period;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_unbonding_period")
+            .add_attribute("unbonding_period", unbonding_period.to_string());
+        Ok(resp)
+    }
+
+    /// Merges the additional proxies of the caller into its default proxy.
+    ///
+    /// The stake of the merged proxies is undelegated, as delegations can't move between
+    /// delegators, and delegated to `validator` with the default proxy once unbonded. The stake
+    /// is accounted as staked all along, so the rewards and the vault liens are not affected.
+    /// Tokens the merged proxies were already unbonding are merged as well
+    #[sv::msg(exec)]
+    fn consolidate_proxies(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let owner = ctx.info.sender;
+        ensure!(
+            self.proxy_by_owner.has(ctx.deps.storage, &owner),
+            ContractError::NoProxy(owner.into_string())
+        );
+        ensure!(
+            ctx.deps.querier.query_validator(&validator)?.is_some(),
+            ContractError::ValidatorNotFound(validator)
+        );
+
+        let labeled = self
+            .labeled_proxies
+            .prefix(&owner)
+            .range(ctx.deps.storage, None, None, Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        ensure!(!labeled.is_empty(), ContractError::NoProxiesToConsolidate);
+
+        let mut msgs = vec![];
+        for (label, proxy) in &labeled {
+            self.labeled_proxies
+                .remove(ctx.deps.storage, (&owner, label.as_str()));
+            self.consolidating
+                .save(ctx.deps.storage, proxy, &validator)?;
+            let msg =
+                to_json_binary(&mesh_native_staking_proxy::contract::sv::ExecMsg::Consolidate {})?;
+            msgs.push(WasmMsg::Execute {
+                contract_addr: proxy.to_string(),
+                msg,
+                funds: vec![],
+            });
+        }
+        // Merged stake is delegated to the validator once unbonded
+        self.delegators
+            .save(ctx.deps.storage, (&validator, &owner), &true)?;
+
+        let proxies = labeled
+            .iter()
+            .map(|(_, proxy)| proxy.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        Ok(Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "consolidate_proxies")
+            .add_attribute("owner", owner)
+            .add_attribute("proxies", proxies)
+            .add_attribute("validator", validator))
+    }
+
+    /// Proxies of `owner`, the default one first, with their labels. Proxies being merged are
+    /// not included
+    pub(crate) fn load_proxies(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+    ) -> StdResult<Vec<(Option<String>, Addr)>> {
+        let mut proxies = vec![];
+        if let Some(proxy) = self.proxy_by_owner.may_load(storage, owner)? {
+            proxies.push((None, proxy));
+        }
+        for item in self
+            .labeled_proxies
+            .prefix(owner)
+            .range(storage, None, None, Ascending)
+        {
+            let (label, proxy) = item?;
+            proxies.push((Some(label), proxy));
+        }
+        Ok(proxies)
+    }
+
     #[sv::msg(query)]
     fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
         self.config.load(ctx.deps.storage).map_err(Into::into)
@@ -184,7 +527,20 @@ impl NativeStakingContract<'_> {
     fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
         match reply.id {
             REPLY_ID_INSTANTIATE => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
-            _ => Err(ContractError::InvalidReplyId(reply.id)),
+            REPLY_ID_WITHDRAW_REWARDS => self.reply_withdraw_rewards(ctx.deps, ctx.env),
+            _ => Err(MeshError::InvalidReplyId(reply.id).into()),
+        }
+    }
+
+    /// Sends its rewards to the owner, once the rewards of its proxies are distributed
+    fn reply_withdraw_rewards(&self, deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+        let owner = self.pending_rewards_owner.load(deps.storage)?;
+        self.pending_rewards_owner.remove(deps.storage);
+
+        match self.withdraw_user_rewards(deps.storage, &env.contract.address, &owner, None) {
+            // The proxies delegations had no rewards either
+            Err(ContractError::NoRewards) => Ok(Response::new()),
+            res => res,
         }
     }
 
@@ -200,8 +556,19 @@ impl NativeStakingContract<'_> {
         let owner_data: OwnerMsg =
             from_json(init_data.data.ok_or(ContractError::NoInstantiateData {})?)?;
         let owner_addr = deps.api.addr_validate(&owner_data.owner)?;
-        self.proxy_by_owner
-            .save(deps.storage, &owner_addr, &proxy_addr)?;
+        match self.pending_proxy_label.may_load(deps.storage)? {
+            Some(label) => {
+                self.pending_proxy_label.remove(deps.storage);
+                self.labeled_proxies.save(
+                    deps.storage,
+                    (&owner_addr, label.as_str()),
+                    &proxy_addr,
+                )?;
+            }
+            None => self
+                .proxy_by_owner
+                .save(deps.storage, &owner_addr, &proxy_addr)?,
+        }
         self.owner_by_proxy
             .save(deps.storage, &proxy_addr, &owner_addr)?;
 
@@ -234,6 +601,21 @@ impl NativeStakingContract<'_> {
         })
     }
 
+    /// All the proxies of `owner`, the default one first
+    #[sv::msg(query)]
+    fn proxies(&self, ctx: QueryCtx, owner: String) -> Result<ProxiesResponse, ContractError> {
+        let owner_addr = ctx.deps.api.addr_validate(&owner)?;
+        let proxies = self
+            .load_proxies(ctx.deps.storage, &owner_addr)?
+            .into_iter()
+            .map(|(label, proxy)| ProxyInfo {
+                label,
+                proxy: proxy.into_string(),
+            })
+            .collect();
+        Ok(ProxiesResponse { proxies })
+    }
+
     /// Jails validators temporarily or permanently.
     /// Method used for test only.
     #[sv::msg(exec)]
@@ -260,7 +642,7 @@ impl NativeStakingContract<'_> {
         #[cfg(not(any(feature = "mt", test)))]
         {
             let _ = (ctx, jailed, tombstoned);
-            Err(ContractError::Unauthorized {})
+            Err(MeshError::Unauthorized.into())
         }
     }
 
@@ -1,5 +1,7 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{ConversionOverflowError, StdError, Uint128};
 use cw_utils::{ParseReplyError, PaymentError};
+use mesh_apis::error::MeshError;
+use mesh_apis::ownership_api::OwnershipError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -7,17 +9,20 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Conversion(#[from] ConversionOverflowError),
+
     #[error("{0}")]
     ParseReply(#[from] ParseReplyError),
 
     #[error("{0}")]
     Payment(#[from] PaymentError),
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("{0}")]
+    Ownership(OwnershipError),
 
-    #[error("Invalid reply id: {0}")]
-    InvalidReplyId(u64),
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
     #[error("Missing instantiate reply data")]
     NoInstantiateData {},
@@ -25,6 +30,37 @@ pub enum ContractError {
     #[error("Missing proxy contract for {0}")]
     NoProxy(String),
 
+    #[error("Proxy labels can't be empty")]
+    InvalidProxyLabel,
+
+    #[error("No additional proxies to consolidate")]
+    NoProxiesToConsolidate,
+
+    #[error("Validator {0} not found")]
+    ValidatorNotFound(String),
+
     #[error("You cannot specify a slash ratio over 1.0 (100%)")]
     InvalidSlashRatio,
+
+    #[error("No stake to distribute rewards to")]
+    NoStake,
+
+    #[error("No rewards to be withdrawn")]
+    NoRewards,
+
+    #[error("Rewards must be withdrawn in {0}")]
+    InvalidRewardsDenom(String),
+
+    #[error("Only {0} rewards can be withdrawn")]
+    InsufficientRewards(Uint128),
+}
+
+impl From<OwnershipError> for ContractError {
+    fn from(err: OwnershipError) -> Self {
+        match err {
+            // Unwrapped, so non owners get the same error as any other unauthorized caller
+            OwnershipError::Mesh(err) => ContractError::Mesh(err),
+            err => ContractError::Ownership(err),
+        }
+    }
 }
@@ -2,7 +2,9 @@ pub mod contract;
 pub mod error;
 mod local_staking_api;
 pub mod msg;
-#[cfg(test)]
+// The multitests run the vault without the provider module custom messages
+#[cfg(all(test, not(feature = "provider-bindings")))]
 mod multitest;
 mod native_staking_callback;
+mod ownership_api;
 mod state;
@@ -1,11 +1,19 @@
-use cosmwasm_std::{ensure_eq, from_json, to_json_binary, Binary, Coin, Response, SubMsg, WasmMsg};
+use cosmwasm_std::{
+    coin, ensure, ensure_eq, from_json, to_json_binary, Binary, Coin, Event, ReplyOn, Response,
+    SubMsg, Uint128, WasmMsg,
+};
 use cw_utils::{must_pay, nonpayable};
 use sylvia::types::{ExecCtx, QueryCtx};
 
+use mesh_apis::error::MeshError;
+use mesh_apis::events::StakeEvent;
 #[allow(unused_imports)]
-use mesh_apis::local_staking_api::{self, LocalStakingApi, SlashRatioResponse};
+use mesh_apis::local_staking_api::{
+    self, GovernancePowerResponse, LocalStakingApi, PendingRewardsResponse, SlashRatioResponse,
+    UnbondingPeriodResponse,
+};
 
-use crate::contract::{NativeStakingContract, REPLY_ID_INSTANTIATE};
+use crate::contract::{NativeStakingContract, REPLY_ID_INSTANTIATE, REPLY_ID_WITHDRAW_REWARDS};
 use crate::error::ContractError;
 use crate::msg::StakeMsg;
 
@@ -25,26 +33,53 @@ impl LocalStakingApi for NativeStakingContract<'_> {
     ) -> Result<Response, Self::Error> {
         // Can only be called by the vault
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.vault.0, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.vault.0, ctx.info.sender, MeshError::Unauthorized);
 
         // Assert funds are passed in
-        let _paid = must_pay(&ctx.info, &cfg.denom)?;
+        let paid = must_pay(&ctx.info, &cfg.denom)?;
 
-        // Parse message to find validator to stake on
-        let StakeMsg { validator } = from_json(msg)?;
+        // Parse message to find validator to stake on, and the proxy to stake with
+        let StakeMsg {
+            validator,
+            proxy: label,
+        } = from_json(msg)?;
+        ensure!(
+            label.as_ref().map_or(true, |label| !label.is_empty()),
+            ContractError::InvalidProxyLabel
+        );
 
         let owner_addr = ctx.deps.api.addr_validate(&owner)?;
+        let event = Event::from(
+            StakeEvent::new(coin(paid.u128(), &cfg.denom))
+                .delegator(&owner)
+                .validator(&validator)
+                .lienholder(&ctx.env.contract.address),
+        );
 
         // Add it to the delegators map
         self.delegators
             .save(ctx.deps.storage, (&validator, &owner_addr), &true)?;
+        self.increase_stake(ctx.deps.storage, &owner_addr, paid)?;
 
         // Look up if there is a proxy to match. Instantiate or call stake on existing
-        match self
-            .proxy_by_owner
-            .may_load(ctx.deps.storage, &owner_addr)?
-        {
+        let proxy = match &label {
+            None => self
+                .proxy_by_owner
+                .may_load(ctx.deps.storage, &owner_addr)?,
+            Some(label) => self
+                .labeled_proxies
+                .may_load(ctx.deps.storage, (&owner_addr, label.as_str()))?,
+        };
+        match proxy {
             None => {
+                // The reply registers the proxy under this label
+                let proxy_label = match label {
+                    None => format!("LSP for {owner}"),
+                    Some(label) => {
+                        self.pending_proxy_label.save(ctx.deps.storage, &label)?;
+                        format!("LSP {label} for {owner}")
+                    }
+                };
                 // Instantiate proxy contract and send funds to stake, with reply handling on success
                 let msg =
                     to_json_binary(&mesh_native_staking_proxy::contract::sv::InstantiateMsg {
@@ -57,10 +92,10 @@ impl LocalStakingApi for NativeStakingContract<'_> {
                     code_id: cfg.proxy_code_id,
                     msg,
                     funds: ctx.info.funds,
-                    label: format!("LSP for {owner}"),
+                    label: proxy_label,
                 };
                 let sub_msg = SubMsg::reply_on_success(wasm_msg, REPLY_ID_INSTANTIATE);
-                Ok(Response::new().add_submessage(sub_msg))
+                Ok(Response::new().add_submessage(sub_msg).add_event(event))
             }
             Some(proxy_addr) => {
                 // Send stake message with funds to the proxy contract
@@ -73,13 +108,14 @@ impl LocalStakingApi for NativeStakingContract<'_> {
                     msg,
                     funds: ctx.info.funds,
                 };
-                Ok(Response::new().add_message(wasm_msg))
+                Ok(Response::new().add_message(wasm_msg).add_event(event))
             }
         }
     }
 
     /// Burns stake. This is called when the user's collateral is slashed and, as part of slashing
     /// propagation, the native staking contract needs to burn / discount the indicated slashing amount.
+    /// Unbonded tokens not released by the user's proxy yet are burned first.
     /// If `validator` is set, undelegate preferentially from it first.
     /// If it is not set, undelegate evenly from all validators the user has stake in.
     fn burn_stake(
@@ -91,33 +127,131 @@ impl LocalStakingApi for NativeStakingContract<'_> {
     ) -> Result<Response, Self::Error> {
         // Can only be called by the vault
         let cfg = self.config.load(ctx.deps.storage)?;
-        ensure_eq!(cfg.vault.0, ctx.info.sender, ContractError::Unauthorized {});
+        ensure_eq!(cfg.vault.0, ctx.info.sender, MeshError::Unauthorized);
         // Assert no funds are passed in
         nonpayable(&ctx.info)?;
 
         let owner_addr = ctx.deps.api.addr_validate(&owner)?;
+        self.decrease_stake(ctx.deps.storage, &owner_addr, amount.amount)?;
 
-        // Look up if there is a proxy to match. Fail or call burn on existing
-        match self
-            .proxy_by_owner
-            .may_load(ctx.deps.storage, &owner_addr)?
-        {
-            None => Err(ContractError::NoProxy(owner)),
-            Some(proxy_addr) => {
-                // Send burn message to the proxy contract
-                let msg =
-                    to_json_binary(&mesh_native_staking_proxy::contract::sv::ExecMsg::Burn {
-                        validator,
-                        amount,
-                    })?;
-                let wasm_msg = WasmMsg::Execute {
-                    contract_addr: proxy_addr.into(),
-                    msg,
-                    funds: ctx.info.funds,
-                };
-                Ok(Response::new().add_message(wasm_msg))
+        // Look up the proxies to match. Fail or call burn on existing
+        let proxies = self.load_proxies(ctx.deps.storage, &owner_addr)?;
+        if proxies.is_empty() {
+            return Err(ContractError::NoProxy(owner));
+        }
+
+        // Burned from the proxies in order, up to their delegations. The default proxy covers
+        // what they can't, if any
+        let mut burns = vec![];
+        let mut to_burn = amount.amount;
+        for (_, proxy) in proxies {
+            let delegated: Uint128 = if to_burn.is_zero() {
+                Uint128::zero()
+            } else {
+                ctx.deps
+                    .querier
+                    .query_all_delegations(&proxy)?
+                    .iter()
+                    .map(|delegation| delegation.amount.amount)
+                    .sum()
+            };
+            let burned = delegated.min(to_burn);
+            to_burn -= burned;
+            burns.push((proxy, burned));
+        }
+        burns[0].1 += to_burn;
+
+        let mut resp = Response::new();
+        for (proxy, burned) in burns {
+            if burned.is_zero() {
+                continue;
             }
+            // Send burn message to the proxy contract
+            let msg = to_json_binary(&mesh_native_staking_proxy::contract::sv::ExecMsg::Burn {
+                validator: validator.clone(),
+                amount: coin(burned.u128(), &amount.denom),
+            })?;
+            let wasm_msg = WasmMsg::Execute {
+                contract_addr: proxy.into(),
+                msg,
+                funds: vec![],
+            };
+            resp = resp.add_message(wasm_msg);
+        }
+        Ok(resp)
+    }
+
+    /// Withdraws `amount` of the sender's accumulated rewards, leaving the rest pending.
+    /// The sender is either the user, or the user's staking proxy (if any), in which case the
+    /// rewards are sent to the user.
+    fn withdraw_rewards_partial(
+        &self,
+        ctx: ExecCtx,
+        amount: Coin,
+    ) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            amount.denom,
+            cfg.denom,
+            ContractError::InvalidRewardsDenom(cfg.denom)
+        );
+
+        let user = self
+            .owner_by_proxy
+            .may_load(ctx.deps.storage, &ctx.info.sender)?
+            .unwrap_or(ctx.info.sender);
+        self.withdraw_user_rewards(
+            ctx.deps.storage,
+            &ctx.env.contract.address,
+            &user,
+            Some(amount.amount),
+        )
+    }
+
+    /// Withdraws all the rewards of `owner`, sending them to it. This is called by the vault
+    /// contract, so users can collect their local staking rewards through it.
+    /// The rewards of the delegations of all the owner proxies are withdrawn and distributed here
+    /// first, so the owner's share of them is sent along (in the reply).
+    fn withdraw_rewards_for(&self, ctx: ExecCtx, owner: String) -> Result<Response, Self::Error> {
+        // Can only be called by the vault
+        let cfg = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(cfg.vault.0, ctx.info.sender, MeshError::Unauthorized);
+        nonpayable(&ctx.info)?;
+
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let proxies = self.load_proxies(ctx.deps.storage, &owner)?;
+
+        if proxies.is_empty() {
+            return self.withdraw_user_rewards(
+                ctx.deps.storage,
+                &ctx.env.contract.address,
+                &owner,
+                None,
+            );
+        }
+
+        let mut msgs = proxies
+            .into_iter()
+            .map(|(_, proxy)| {
+                let msg = to_json_binary(
+                    &mesh_native_staking_proxy::contract::sv::ExecMsg::WithdrawRewards {},
+                )?;
+                Ok(SubMsg::new(WasmMsg::Execute {
+                    contract_addr: proxy.into(),
+                    msg,
+                    funds: vec![],
+                }))
+            })
+            .collect::<Result<Vec<_>, ContractError>>()?;
+        // The owner's rewards are sent once the last proxy rewards are distributed
+        if let Some(last) = msgs.last_mut() {
+            last.id = REPLY_ID_WITHDRAW_REWARDS;
+            last.reply_on = ReplyOn::Success;
         }
+        self.pending_rewards_owner.save(ctx.deps.storage, &owner)?;
+        Ok(Response::new().add_submessages(msgs))
     }
 
     /// Returns the maximum percentage that can be slashed
@@ -132,4 +266,43 @@ impl LocalStakingApi for NativeStakingContract<'_> {
             slash_ratio_offline,
         })
     }
+
+    /// Returns the accumulated rewards of `user`, not withdrawn yet
+    fn pending_rewards(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+    ) -> Result<PendingRewardsResponse, Self::Error> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let stake = self
+            .stakes
+            .may_load(ctx.deps.storage, &user)?
+            .unwrap_or_default();
+        let distribution = self
+            .distribution
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+
+        let amount = Self::calculate_reward(&stake, &distribution)?;
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        Ok(PendingRewardsResponse {
+            rewards: coin(amount.u128(), denom),
+        })
+    }
+
+    /// Returns whether the locally staked tokens carry governance power
+    fn governance_power(&self, ctx: QueryCtx) -> Result<GovernancePowerResponse, Self::Error> {
+        let config = self.config.load(ctx.deps.storage)?;
+        Ok(GovernancePowerResponse {
+            enabled: config.voting_enabled,
+        })
+    }
+
+    /// Returns the unbonding period set by the owner
+    fn unbonding_period(&self, ctx: QueryCtx) -> Result<UnbondingPeriodResponse, Self::Error> {
+        let config = self.config.load(ctx.deps.storage)?;
+        Ok(UnbondingPeriodResponse {
+            unbonding_period: config.unbonding_period,
+        })
+    }
 }
@@ -13,8 +13,24 @@ pub struct OwnerByProxyResponse {
     pub owner: String,
 }
 
+#[cw_serde]
+pub struct ProxyInfo {
+    /// Label of the proxy, `None` for the default one
+    pub label: Option<String>,
+    pub proxy: String,
+}
+
+#[cw_serde]
+pub struct ProxiesResponse {
+    pub proxies: Vec<ProxyInfo>,
+}
+
 /// The message that is binary encoded in `receive_stake(..msg)`
 #[cw_serde]
 pub struct StakeMsg {
     pub validator: String,
+    /// Label of the proxy of the user to stake with, e.g. one per staking strategy.
+    /// The default proxy if not set. Proxies are created on their first stake
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
 }
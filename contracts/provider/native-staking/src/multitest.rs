@@ -5,11 +5,13 @@ use cosmwasm_std::{
 use cw_multi_test::{App as MtApp, StakingInfo};
 use sylvia::multitest::{App, Proxy};
 
+use mesh_apis::error::MeshError;
 use mesh_apis::local_staking_api::sv::mt::LocalStakingApiProxy;
 use mesh_native_staking_proxy::contract::sv::mt::{
     CodeId as NativeStakingProxyCodeId, NativeStakingProxyContractProxy,
 };
 use mesh_native_staking_proxy::contract::NativeStakingProxyContract;
+use mesh_native_staking_proxy::native_staking_callback::sv::mt::NativeStakingCallbackProxy;
 use mesh_sync::ValueRange;
 use mesh_vault::contract::sv::mt::VaultContractProxy;
 use mesh_vault::msg::LocalStakingInfo;
@@ -118,6 +120,7 @@ fn instantiation() {
             staking_proxy_code.code_id(),
             slashing_rate_dsign(),
             slashing_rate_offline(),
+            None,
         )
         .with_label("Staking")
         .call(owner)
@@ -130,6 +133,77 @@ fn instantiation() {
     assert_eq!(res.slash_ratio_dsign, slashing_rate_dsign());
 }
 
+#[test]
+fn toggling_voting() {
+    let app = app(&[], &[]);
+
+    let vault = "vault";
+    let owner = "owner";
+
+    let staking_proxy_code = NativeStakingProxyCodeId::store_code(&app);
+    let staking_code = contract::sv::mt::CodeId::store_code(&app);
+
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate_dsign(),
+            slashing_rate_offline(),
+            Some(owner.to_owned()),
+        )
+        .with_label("Staking")
+        .call(vault)
+        .unwrap();
+
+    // Voting is enabled by default
+    assert!(staking.governance_power().unwrap().enabled);
+
+    // Only the owner can disable it
+    let err = staking.set_voting_enabled(false).call(vault).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    staking.set_voting_enabled(false).call(owner).unwrap();
+    assert!(!staking.governance_power().unwrap().enabled);
+    assert!(!staking.config().unwrap().voting_enabled);
+
+    staking.set_voting_enabled(true).call(owner).unwrap();
+    assert!(staking.governance_power().unwrap().enabled);
+}
+
+#[test]
+fn setting_unbonding_period() {
+    let app = app(&[], &[]);
+
+    let vault = "vault";
+    let owner = "owner";
+
+    let staking_proxy_code = NativeStakingProxyCodeId::store_code(&app);
+    let staking_code = contract::sv::mt::CodeId::store_code(&app);
+
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate_dsign(),
+            slashing_rate_offline(),
+            Some(owner.to_owned()),
+        )
+        .with_label("Staking")
+        .call(vault)
+        .unwrap();
+
+    // Unknown by default
+    assert_eq!(staking.unbonding_period().unwrap().unbonding_period, 0);
+
+    // Only the owner can set it
+    let err = staking.set_unbonding_period(1000).call(vault).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    staking.set_unbonding_period(1000).call(owner).unwrap();
+    assert_eq!(staking.unbonding_period().unwrap().unbonding_period, 1000);
+    assert_eq!(staking.config().unwrap().unbonding_period, 1000);
+}
+
 #[test]
 fn receiving_stake() {
     let owner = "vault"; // Owner of the staking contract (i. e. the vault contract)
@@ -152,6 +226,7 @@ fn receiving_stake() {
             staking_proxy_code.code_id(),
             slashing_rate_dsign(),
             slashing_rate_offline(),
+            None,
         )
         .with_label("Staking")
         .call(owner)
@@ -167,6 +242,7 @@ fn receiving_stake() {
     // Receive some stake on behalf of user1 for validator
     let stake_msg = to_json_binary(&msg::StakeMsg {
         validator: validator.to_owned(),
+        proxy: None,
     })
     .unwrap();
     staking
@@ -190,6 +266,7 @@ fn receiving_stake() {
     // Stake some more
     let stake_msg = to_json_binary(&msg::StakeMsg {
         validator: validator.to_owned(),
+        proxy: None,
     })
     .unwrap();
     staking
@@ -220,6 +297,7 @@ fn receiving_stake() {
     // Receive some stake on behalf of user2 for validator
     let stake_msg = to_json_binary(&msg::StakeMsg {
         validator: validator.to_owned(),
+        proxy: None,
     })
     .unwrap();
     staking
@@ -269,6 +347,7 @@ fn releasing_proxy_stake() {
             proxy_code_id: staking_proxy_code.code_id(),
             slash_ratio_dsign: slashing_rate_dsign(),
             slash_ratio_offline: slashing_rate_offline(),
+            owner: None,
         })
         .unwrap(),
         label: None,
@@ -279,6 +358,9 @@ fn releasing_proxy_stake() {
         .instantiate(
             OSMO.to_owned(),
             Some(LocalStakingInfo::New(staking_init_info)),
+            None,
+            None,
+            None,
         )
         .with_label("Vault")
         .call(owner)
@@ -314,6 +396,7 @@ fn releasing_proxy_stake() {
             coin(100, OSMO),
             to_json_binary(&msg::StakeMsg {
                 validator: validator.to_owned(),
+                proxy: None,
             })
             .unwrap(),
         )
@@ -358,6 +441,118 @@ fn releasing_proxy_stake() {
     assert_eq!(claims.claims, []);
 }
 
+#[test]
+fn multiple_proxies_and_consolidation() {
+    let owner = "vault"; // Owner of the staking contract (i. e. the vault contract)
+
+    let user = "user1";
+    let validator1 = "validator1";
+    let validator2 = "validator2";
+
+    let app = app(&[(owner, (300, OSMO))], &[validator1, validator2]);
+
+    let staking_proxy_code = NativeStakingProxyCodeId::store_code(&app);
+    let staking_code = contract::sv::mt::CodeId::store_code(&app);
+
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate_dsign(),
+            slashing_rate_offline(),
+            None,
+        )
+        .with_label("Staking")
+        .call(owner)
+        .unwrap();
+
+    let stake = |validator: &str, proxy: Option<&str>, amount: u128| {
+        let stake_msg = to_json_binary(&msg::StakeMsg {
+            validator: validator.to_owned(),
+            proxy: proxy.map(str::to_owned),
+        })
+        .unwrap();
+        staking
+            .receive_stake(user.to_owned(), stake_msg)
+            .with_funds(&coins(amount, OSMO))
+            .call(owner)
+    };
+
+    // The default proxy, and another one for a different strategy
+    stake(validator1, None, 100).unwrap();
+    stake(validator2, Some("strategy"), 50).unwrap();
+    stake(validator2, Some("strategy"), 30).unwrap();
+    let err = stake(validator2, Some(""), 10).unwrap_err();
+    assert_eq!(err, ContractError::InvalidProxyLabel);
+
+    let proxy1 = staking.proxy_by_owner(user.to_owned()).unwrap().proxy;
+    let proxies = staking.proxies(user.to_owned()).unwrap().proxies;
+    assert_eq!(proxies.len(), 2);
+    assert_eq!(
+        proxies[0],
+        msg::ProxyInfo {
+            label: None,
+            proxy: proxy1.clone(),
+        }
+    );
+    assert_eq!(proxies[1].label.as_deref(), Some("strategy"));
+    let proxy2 = proxies[1].proxy.clone();
+    assert_eq!(
+        staking.owner_by_proxy(proxy2.clone()).unwrap(),
+        OwnerByProxyResponse {
+            owner: user.to_owned(),
+        }
+    );
+    assert_delegations(&app, &proxy1, &[(validator1, 100)]);
+    assert_delegations(&app, &proxy2, &[(validator2, 80)]);
+
+    // Merged into the default proxy, to validator1
+    let err = staking
+        .consolidate_proxies(validator1.to_owned())
+        .call("user2")
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoProxy("user2".to_owned()));
+    let err = staking
+        .consolidate_proxies("unknown".to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::ValidatorNotFound("unknown".to_owned()));
+
+    staking
+        .consolidate_proxies(validator1.to_owned())
+        .call(user)
+        .unwrap();
+    assert_eq!(
+        staking.proxies(user.to_owned()).unwrap().proxies,
+        vec![msg::ProxyInfo {
+            label: None,
+            proxy: proxy1.clone(),
+        }]
+    );
+    let err = staking
+        .consolidate_proxies(validator1.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoProxiesToConsolidate);
+
+    // Once unbonded, the stake is delegated with the default proxy
+    let merged_proxy: Proxy<'_, MtApp, NativeStakingProxyContract<'_>> =
+        Proxy::new(Addr::unchecked(&proxy2), &app);
+    app.update_block(advance_unbonding_period);
+    merged_proxy.release_unbonded().call(user).unwrap();
+    assert_delegations(&app, &proxy1, &[(validator1, 180)]);
+    assert_eq!(
+        app.app().wrap().query_balance(&proxy2, OSMO).unwrap(),
+        coin(0, OSMO)
+    );
+
+    // A new proxy can be created with the same label
+    stake(validator2, Some("strategy"), 20).unwrap();
+    let proxies = staking.proxies(user.to_owned()).unwrap().proxies;
+    assert_eq!(proxies.len(), 2);
+    assert_ne!(proxies[1].proxy, proxy2);
+}
+
 pub fn advance_unbonding_period(block: &mut cosmwasm_std::BlockInfo) {
     // Default unbonding time in cw_multi_test is 60, from looking at the code...
     // Wish I could find this somewhere in this setup somewhere.
@@ -365,3 +560,274 @@ pub fn advance_unbonding_period(block: &mut cosmwasm_std::BlockInfo) {
     block.time = block.time.plus_seconds(5 * UNBONDING_TIME);
     block.height += UNBONDING_TIME;
 }
+
+#[test]
+fn distributing_rewards() {
+    let owner = "vault"; // Owner of the staking contract (i. e. the vault contract)
+    let distributor = "distributor"; // Whoever pools the staking rewards
+
+    let user1 = "user1";
+    let user2 = "user2";
+
+    let validator = "validator1";
+
+    let app = app(
+        &[(owner, (600, OSMO)), (distributor, (400, OSMO))],
+        &[validator],
+    );
+
+    let staking_proxy_code = NativeStakingProxyCodeId::store_code(&app);
+    let staking_code = contract::sv::mt::CodeId::store_code(&app);
+
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate_dsign(),
+            slashing_rate_offline(),
+            None,
+        )
+        .with_label("Staking")
+        .call(owner)
+        .unwrap();
+
+    // Nobody to distribute rewards to yet
+    let err = staking
+        .distribute_rewards()
+        .with_funds(&coins(100, OSMO))
+        .call(distributor)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoStake);
+
+    let stake_msg = to_json_binary(&msg::StakeMsg {
+        validator: validator.to_owned(),
+        proxy: None,
+    })
+    .unwrap();
+    staking
+        .receive_stake(user1.to_owned(), stake_msg.clone())
+        .with_funds(&coins(100, OSMO))
+        .call(owner)
+        .unwrap();
+    staking
+        .receive_stake(user2.to_owned(), stake_msg.clone())
+        .with_funds(&coins(300, OSMO))
+        .call(owner)
+        .unwrap();
+
+    // Rewards are shared pro rata
+    staking
+        .distribute_rewards()
+        .with_funds(&coins(100, OSMO))
+        .call(distributor)
+        .unwrap();
+    let pending = |user: &str| {
+        staking
+            .pending_rewards(user.to_owned())
+            .unwrap()
+            .rewards
+            .amount
+            .u128()
+    };
+    assert_eq!(pending(user1), 25);
+    assert_eq!(pending(user2), 75);
+
+    staking.withdraw_rewards().call(user1).unwrap();
+    assert_eq!(pending(user1), 0);
+    assert_eq!(
+        app.app().wrap().query_balance(user1, OSMO).unwrap().amount,
+        Uint128::new(25)
+    );
+    let err = staking.withdraw_rewards().call(user1).unwrap_err();
+    assert_eq!(err, ContractError::NoRewards);
+
+    // New stake doesn't get a share of the previous rewards
+    staking
+        .receive_stake(user1.to_owned(), stake_msg)
+        .with_funds(&coins(200, OSMO))
+        .call(owner)
+        .unwrap();
+    assert_eq!(pending(user1), 0);
+    assert_eq!(pending(user2), 75);
+
+    staking
+        .distribute_rewards()
+        .with_funds(&coins(300, OSMO))
+        .call(distributor)
+        .unwrap();
+    assert_eq!(pending(user1), 150);
+    assert_eq!(pending(user2), 225);
+
+    staking.withdraw_rewards().call(user2).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(user2, OSMO).unwrap().amount,
+        Uint128::new(225)
+    );
+
+    // Rewards can be withdrawn partially
+    let err = staking
+        .withdraw_rewards_partial(coin(151, OSMO))
+        .call(user1)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientRewards(Uint128::new(150)));
+    let err = staking
+        .withdraw_rewards_partial(coin(40, "uatom"))
+        .call(user1)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidRewardsDenom(OSMO.to_owned()));
+
+    staking
+        .withdraw_rewards_partial(coin(40, OSMO))
+        .call(user1)
+        .unwrap();
+    assert_eq!(pending(user1), 110);
+    assert_eq!(
+        app.app().wrap().query_balance(user1, OSMO).unwrap().amount,
+        Uint128::new(65)
+    );
+}
+
+#[test]
+fn distributing_proxy_rewards() {
+    let owner = "vault"; // Owner of the staking contract (i. e. the vault contract)
+
+    let user1 = "user1";
+    let user2 = "user2";
+
+    let validator = "validator1";
+
+    let app = app(&[(owner, (400, OSMO))], &[validator]);
+
+    let staking_proxy_code = NativeStakingProxyCodeId::store_code(&app);
+    let staking_code = contract::sv::mt::CodeId::store_code(&app);
+
+    let staking = staking_code
+        .instantiate(
+            OSMO.to_owned(),
+            staking_proxy_code.code_id(),
+            slashing_rate_dsign(),
+            slashing_rate_offline(),
+            None,
+        )
+        .with_label("Staking")
+        .call(owner)
+        .unwrap();
+
+    let stake_msg = to_json_binary(&msg::StakeMsg {
+        validator: validator.to_owned(),
+        proxy: None,
+    })
+    .unwrap();
+    staking
+        .receive_stake(user1.to_owned(), stake_msg.clone())
+        .with_funds(&coins(100, OSMO))
+        .call(owner)
+        .unwrap();
+    staking
+        .receive_stake(user2.to_owned(), stake_msg)
+        .with_funds(&coins(300, OSMO))
+        .call(owner)
+        .unwrap();
+
+    let proxy1 = staking.proxy_by_owner(user1.to_owned()).unwrap().proxy;
+    let staking_proxy1: Proxy<'_, MtApp, NativeStakingProxyContract<'_>> =
+        Proxy::new(Addr::unchecked(&proxy1), &app);
+
+    // A year of staking rewards (10% APR) of the user1 proxy
+    app.update_block(|block| {
+        block.time = block.time.plus_seconds(365 * 24 * 3600);
+    });
+
+    // Only the proxies can ask for their rewards to be distributed
+    let err = staking.distribute_proxy_rewards().call(user1).unwrap_err();
+    assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+
+    // They are withdrawn to the staking contract, and shared pro rata
+    staking_proxy1.withdraw_rewards().call(user1).unwrap();
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&staking.contract_addr, OSMO)
+            .unwrap()
+            .amount,
+        Uint128::new(10)
+    );
+    assert_eq!(
+        app.app().wrap().query_balance(user1, OSMO).unwrap().amount,
+        Uint128::zero()
+    );
+    let pending = |user: &str| {
+        staking
+            .pending_rewards(user.to_owned())
+            .unwrap()
+            .rewards
+            .amount
+            .u128()
+    };
+    assert_eq!(pending(user1), 2);
+    assert_eq!(pending(user2), 7);
+
+    // The rewards already distributed are not distributed again
+    staking.distribute_proxy_rewards().call(&proxy1).unwrap();
+    assert_eq!(pending(user1), 2);
+    assert_eq!(pending(user2), 7);
+
+    staking.withdraw_rewards().call(user2).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(user2, OSMO).unwrap().amount,
+        Uint128::new(7)
+    );
+    assert_eq!(pending(user1), 2);
+}
+
+#[test]
+fn migrating_from_baseline() {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{ContractInfoResponse, ContractResult, SystemResult, WasmQuery};
+    use mesh_apis::ownership_api;
+    use sylvia::types::MigrateCtx;
+
+    let mut deps = mock_dependencies();
+    let contract = contract::NativeStakingContract::new();
+
+    // Config and version of the baseline release, without owner
+    deps.as_mut().storage.set(
+        b"config",
+        br#"{"denom":"OSMO","proxy_code_id":1,"vault":"vault","slash_ratio_dsign":"0.15","slash_ratio_offline":"0.1"}"#,
+    );
+    cw2::set_contract_version(
+        deps.as_mut().storage,
+        contract::CONTRACT_NAME,
+        "0.10.0-alpha.1",
+    )
+    .unwrap();
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::ContractInfo { .. } => {
+            let mut info = ContractInfoResponse::default();
+            info.code_id = 1;
+            info.creator = "creator".to_owned();
+            info.admin = Some("admin".to_owned());
+            SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+        }
+        _ => unimplemented!(),
+    });
+
+    let ctx = MigrateCtx {
+        deps: deps.as_mut(),
+        env: mock_env(),
+    };
+    let resp = contract.migrate(ctx).unwrap();
+    assert_eq!(resp.events[0].ty, "migrate");
+
+    // The wasm admin becomes the owner
+    assert_eq!(
+        ownership_api::get_ownership(&deps.storage).unwrap().owner,
+        Some(Addr::unchecked("admin"))
+    );
+    let config = contract.config.load(&deps.storage).unwrap();
+    assert!(config.voting_enabled);
+    assert_eq!(
+        cw2::get_contract_version(&deps.storage).unwrap().version,
+        contract::CONTRACT_VERSION
+    );
+}
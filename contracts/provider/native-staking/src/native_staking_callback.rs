@@ -1,7 +1,8 @@
-use cosmwasm_std::Response;
-use cw_utils::must_pay;
+use cosmwasm_std::{coin, to_json_binary, Event, Response, WasmMsg};
+use cw_utils::{must_pay, nonpayable};
 use sylvia::types::ExecCtx;
 
+use mesh_apis::events::{RewardsEvent, UnstakeEvent};
 #[allow(unused_imports)]
 use mesh_native_staking_proxy::native_staking_callback::{self, NativeStakingCallback};
 
@@ -18,19 +19,100 @@ impl NativeStakingCallback for NativeStakingContract<'_> {
         let cfg = self.config.load(ctx.deps.storage)?;
 
         // Assert funds are passed in
-        let _paid = must_pay(&ctx.info, &cfg.denom)?;
+        let paid = must_pay(&ctx.info, &cfg.denom)?;
 
         // Look up account owner by proxy address (info.sender). This asserts the caller is a valid
         // proxy
         let owner_addr = self
             .owner_by_proxy
             .load(ctx.deps.storage, &ctx.info.sender)?;
+        self.decrease_stake(ctx.deps.storage, &owner_addr, paid)?;
+
+        let event = Event::from(
+            UnstakeEvent::new(coin(paid.u128(), &cfg.denom))
+                .delegator(&owner_addr)
+                .lienholder(&ctx.env.contract.address),
+        );
 
         // Send the tokens to the vault contract
         let msg = cfg
             .vault
             .release_local_stake(owner_addr.to_string(), ctx.info.funds)?;
 
-        Ok(Response::new().add_message(msg))
+        Ok(Response::new().add_message(msg).add_event(event))
+    }
+
+    /// This sends the unbonded tokens of a consolidated proxy to native-staking. (See info.funds)
+    /// They are staked with the default proxy of the user, to the validator chosen on
+    /// consolidation. The stake was accounted all along, so the vault is not involved.
+    fn merge_proxy_stake(&self, ctx: ExecCtx) -> Result<Response, Self::Error> {
+        let cfg = self.config.load(ctx.deps.storage)?;
+
+        // Assert funds are passed in
+        let paid = must_pay(&ctx.info, &cfg.denom)?;
+
+        // Asserts the caller is a proxy being merged
+        let validator = self
+            .consolidating
+            .load(ctx.deps.storage, &ctx.info.sender)?;
+        let owner_addr = self
+            .owner_by_proxy
+            .load(ctx.deps.storage, &ctx.info.sender)?;
+        let proxy_addr = self.proxy_by_owner.load(ctx.deps.storage, &owner_addr)?;
+
+        let msg = to_json_binary(&mesh_native_staking_proxy::contract::sv::ExecMsg::Stake {
+            validator: validator.clone(),
+        })?;
+        let wasm_msg = WasmMsg::Execute {
+            contract_addr: proxy_addr.into(),
+            msg,
+            funds: ctx.info.funds,
+        };
+        Ok(Response::new()
+            .add_message(wasm_msg)
+            .add_attribute("action", "merge_proxy_stake")
+            .add_attribute("owner", owner_addr)
+            .add_attribute("proxy", ctx.info.sender)
+            .add_attribute("validator", validator)
+            .add_attribute("amount", paid.to_string()))
+    }
+
+    /// This is called by a proxy after withdrawing the rewards of its delegations to this
+    /// contract. The rewards not distributed yet (the balance not held for the users) are
+    /// distributed among all the users, proportionally to their stake.
+    fn distribute_proxy_rewards(&self, ctx: ExecCtx) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+        let cfg = self.config.load(ctx.deps.storage)?;
+
+        // Asserts the caller is a proxy
+        self.owner_by_proxy
+            .load(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut distribution = self
+            .distribution
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        // Nobody to distribute to, the rewards are kept for the next distribution
+        if distribution.total_stake.is_zero() {
+            return Ok(Response::new());
+        }
+
+        let balance = ctx
+            .deps
+            .querier
+            .query_balance(&ctx.env.contract.address, &cfg.denom)?;
+        let amount = balance.amount.saturating_sub(distribution.held);
+        if amount.is_zero() {
+            return Ok(Response::new());
+        }
+
+        Self::distribute(&mut distribution, amount);
+        self.distribution.save(ctx.deps.storage, &distribution)?;
+
+        let event = Event::from(
+            RewardsEvent::new(coin(amount.u128(), &cfg.denom))
+                .lienholder(&ctx.env.contract.address),
+        );
+        Ok(Response::new().add_event(event))
     }
 }
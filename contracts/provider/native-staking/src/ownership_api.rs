@@ -0,0 +1,65 @@
+use cosmwasm_std::{Empty, Response, Timestamp};
+use cw_utils::nonpayable;
+use sylvia::types::{ExecCtx, QueryCtx};
+
+#[allow(unused_imports)]
+use mesh_apis::ownership_api::{self, Ownership, OwnershipApi};
+
+use crate::contract::NativeStakingContract;
+use crate::error::ContractError;
+
+impl OwnershipApi for NativeStakingContract<'_> {
+    type Error = ContractError;
+    type ExecC = Empty;
+    type QueryC = Empty;
+
+    fn propose_owner(
+        &self,
+        ctx: ExecCtx,
+        new_owner: String,
+        expiry: Option<Timestamp>,
+    ) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+        let ownership = ownership_api::propose_owner(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            new_owner,
+            expiry,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "propose_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn accept_owner(&self, ctx: ExecCtx) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership =
+            ownership_api::accept_owner(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "accept_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn renounce_owner(&self, ctx: ExecCtx) -> Result<Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership = ownership_api::renounce_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "renounce_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn ownership(&self, ctx: QueryCtx) -> Result<Ownership, Self::Error> {
+        Ok(ownership_api::get_ownership(ctx.deps.storage)?)
+    }
+}
@@ -1,6 +1,7 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Decimal;
+use cosmwasm_std::{Decimal, Uint128, Uint256};
 use mesh_apis::vault_api::VaultApiHelper;
+use mesh_sync::PointsAlignment;
 
 #[cw_serde]
 pub struct Config {
@@ -18,4 +19,44 @@ pub struct Config {
 
     /// The slash ratio for being offline
     pub slash_ratio_offline: Decimal,
+
+    /// Whether the users can vote with their stake, through their proxy
+    #[serde(default = "def_true")]
+    pub voting_enabled: bool,
+
+    /// Unbonding period of the chain, in seconds, set by the owner. The user proxies rely on it
+    /// to tell when redelegations complete
+    #[serde(default)]
+    pub unbonding_period: u64,
+}
+
+fn def_true() -> bool {
+    true
+}
+
+/// Stake of a single user, including rewards distribution alignment
+#[cw_serde]
+#[derive(Default)]
+pub struct Stake {
+    /// How many tokens the user has staked via this contract
+    pub stake: Uint128,
+    /// Points alignment is how much points should be added/subtracted from points calculated per
+    /// user due to stake changes.
+    pub points_alignment: PointsAlignment,
+    /// Tokens already withdrawn by this user
+    pub withdrawn_funds: Uint128,
+}
+
+/// Rewards distribution information, shared by all users of this contract
+#[cw_serde]
+#[derive(Default)]
+pub struct Distribution {
+    /// Total tokens staked by all users
+    pub total_stake: Uint128,
+    /// Points user is eligible to by single token staked
+    pub points_per_stake: Uint256,
+    /// Points which were not distributed previously
+    pub points_leftover: Uint256,
+    /// Rewards distributed and not withdrawn yet, held by this contract
+    pub held: Uint128,
 }
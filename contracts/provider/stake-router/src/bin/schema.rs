@@ -0,0 +1,12 @@
+use cosmwasm_schema::write_api;
+
+use mesh_stake_router::contract::sv::{ContractExecMsg, ContractQueryMsg, InstantiateMsg};
+
+#[cfg(not(tarpaulin_include))]
+fn main() {
+    write_api! {
+        instantiate: InstantiateMsg,
+        execute: ContractExecMsg,
+        query: ContractQueryMsg,
+    }
+}
@@ -0,0 +1,289 @@
+use cosmwasm_std::{
+    ensure, ensure_eq, to_json_binary, Addr, Coin, Decimal, Deps, Response, StdResult, Uint128,
+    WasmMsg,
+};
+use cw2::set_contract_version;
+use cw_storage_plus::Item;
+use cw_utils::nonpayable;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+use sylvia::{contract, schemars};
+
+use mesh_apis::cross_staking_api::CrossStakingApiHelper;
+use mesh_apis::error::MeshError;
+use mesh_apis::local_staking_api::{LocalStakingApiHelper, SlashRatioResponse};
+use mesh_vault::contract::sv::{ExecMsg as VaultExecMsg, QueryMsg as VaultQueryMsg};
+use mesh_vault::msg::{AccountResponse, ConfigResponse as VaultConfigResponse, GrantedMsg};
+
+use crate::error::ContractError;
+use crate::msg::{
+    Allocation, AllocationResponse, ConfigResponse, StakeMsg, StakeTargetInfo, Strategy,
+};
+use crate::state::{Config, StakeTarget};
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Lower bound of the lienholder risk, so lienholders that can't slash don't take it all
+pub const MIN_RISK: Decimal = Decimal::permille(1);
+
+pub struct StakeRouterContract<'a> {
+    pub config: Item<'a, Config>,
+}
+
+#[cfg_attr(not(feature = "library"), sylvia::entry_points)]
+#[contract]
+#[sv::error(ContractError)]
+impl StakeRouterContract<'_> {
+    pub const fn new() -> Self {
+        Self {
+            config: Item::new("config"),
+        }
+    }
+
+    /// The sender becomes the owner, allowed to update the stake targets.
+    #[sv::msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx,
+        vault: String,
+        targets: Vec<StakeTargetInfo>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = Config {
+            owner: ctx.info.sender,
+            vault: ctx.deps.api.addr_validate(&vault)?,
+            targets: Self::validate_targets(ctx.deps.as_ref(), targets)?,
+        };
+        self.config.save(ctx.deps.storage, &config)?;
+
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        Ok(Response::new())
+    }
+
+    /// Replaces the stake targets. Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_targets(
+        &self,
+        ctx: ExecCtx,
+        targets: Vec<StakeTargetInfo>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(ctx.info.sender, config.owner, MeshError::Unauthorized);
+
+        config.targets = Self::validate_targets(ctx.deps.as_ref(), targets)?;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_targets")
+            .add_attribute("targets", config.targets.len().to_string());
+
+        Ok(resp)
+    }
+
+    /// Stakes `total` of the sender's free vault collateral, split across the targets
+    /// following `strategy`.
+    ///
+    /// Stakes are executed on the sender's behalf, so the sender has to grant this contract
+    /// the `stake_remote` and `stake_local` (if any local target) permissions on the vault first.
+    #[sv::msg(exec)]
+    fn diversify(
+        &self,
+        ctx: ExecCtx,
+        total: Coin,
+        strategy: Strategy,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ensure!(!total.amount.is_zero(), ContractError::ZeroAmount);
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let account: AccountResponse = ctx.deps.querier.query_wasm_smart(
+            &config.vault,
+            &VaultQueryMsg::Account {
+                account: ctx.info.sender.to_string(),
+            },
+        )?;
+        ensure!(
+            account.denom == total.denom,
+            MeshError::InvalidDenom(account.denom)
+        );
+        let free = account.free.low();
+        ensure!(
+            free >= total.amount,
+            MeshError::InsufficientCollateral(free)
+        );
+
+        let local_staking = self.local_staking(ctx.deps.as_ref(), &config)?;
+        let allocations = self.allocate(
+            ctx.deps.as_ref(),
+            &config,
+            local_staking.as_ref(),
+            total.amount,
+            strategy,
+        )?;
+
+        let mut resp = Response::new()
+            .add_attribute("action", "diversify")
+            .add_attribute("owner", ctx.info.sender.as_str())
+            .add_attribute("total", total.to_string());
+        for (target, amount) in allocations {
+            let amount = Coin {
+                denom: total.denom.clone(),
+                amount,
+            };
+            let msg = to_json_binary(&StakeMsg {
+                validator: target.validator,
+            })?;
+            let granted = if local_staking.as_ref() == Some(&target.lienholder) {
+                GrantedMsg::StakeLocal { amount, msg }
+            } else {
+                GrantedMsg::StakeRemote {
+                    contract: target.lienholder.into_string(),
+                    amount,
+                    msg,
+                }
+            };
+            let exec = WasmMsg::Execute {
+                contract_addr: config.vault.to_string(),
+                msg: to_json_binary(&VaultExecMsg::ExecGranted {
+                    granter: ctx.info.sender.to_string(),
+                    msg: granted,
+                })?,
+                funds: vec![],
+            };
+            resp = resp.add_message(exec);
+        }
+
+        Ok(resp)
+    }
+
+    #[sv::msg(query)]
+    fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let resp = ConfigResponse {
+            owner: config.owner.into_string(),
+            vault: config.vault.into_string(),
+            targets: config
+                .targets
+                .into_iter()
+                .map(|target| StakeTargetInfo {
+                    lienholder: target.lienholder.into_string(),
+                    validator: target.validator,
+                })
+                .collect(),
+        };
+        Ok(resp)
+    }
+
+    /// Previews how `total` would be split across the targets by `diversify`
+    #[sv::msg(query)]
+    fn allocation(
+        &self,
+        ctx: QueryCtx,
+        total: Uint128,
+        strategy: Strategy,
+    ) -> Result<AllocationResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let local_staking = self.local_staking(ctx.deps, &config)?;
+        let allocations = self
+            .allocate(ctx.deps, &config, local_staking.as_ref(), total, strategy)?
+            .into_iter()
+            .map(|(target, amount)| Allocation {
+                lienholder: target.lienholder.into_string(),
+                validator: target.validator,
+                amount,
+            })
+            .collect();
+        Ok(AllocationResponse { allocations })
+    }
+
+    fn validate_targets(
+        deps: Deps,
+        targets: Vec<StakeTargetInfo>,
+    ) -> Result<Vec<StakeTarget>, ContractError> {
+        targets
+            .into_iter()
+            .map(|target| {
+                Ok(StakeTarget {
+                    lienholder: deps.api.addr_validate(&target.lienholder)?,
+                    validator: target.validator,
+                })
+            })
+            .collect()
+    }
+
+    /// Local staking contract of the vault, if any
+    fn local_staking(&self, deps: Deps, config: &Config) -> StdResult<Option<Addr>> {
+        let vault_config: VaultConfigResponse = deps
+            .querier
+            .query_wasm_smart(&config.vault, &VaultQueryMsg::Config {})?;
+        Ok(vault_config.local_staking.map(Addr::unchecked))
+    }
+
+    /// Risk of staking through the lienholder, being its max slashing ratio
+    fn lienholder_risk(deps: Deps, lienholder: &Addr, local: bool) -> StdResult<Decimal> {
+        let SlashRatioResponse {
+            slash_ratio_dsign,
+            slash_ratio_offline,
+        } = if local {
+            LocalStakingApiHelper(lienholder.clone()).max_slash(deps)?
+        } else {
+            CrossStakingApiHelper(lienholder.clone()).max_slash(deps)?
+        };
+        Ok(slash_ratio_dsign.max(slash_ratio_offline).max(MIN_RISK))
+    }
+
+    /// Splits `total` across the targets following `strategy`. Rounding leftovers go to the
+    /// first target. Targets with nothing to stake are skipped.
+    fn allocate(
+        &self,
+        deps: Deps,
+        config: &Config,
+        local_staking: Option<&Addr>,
+        total: Uint128,
+        strategy: Strategy,
+    ) -> Result<Vec<(StakeTarget, Uint128)>, ContractError> {
+        ensure!(!config.targets.is_empty(), ContractError::NoTargets);
+
+        let weights = config
+            .targets
+            .iter()
+            .map(|target| match strategy {
+                Strategy::EqualWeight => Ok(Decimal::one()),
+                Strategy::RiskWeighted => {
+                    let local = local_staking == Some(&target.lienholder);
+                    let risk = Self::lienholder_risk(deps, &target.lienholder, local)?;
+                    Ok(Decimal::one() / risk)
+                }
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+        let total_weight = weights.iter().sum::<Decimal>();
+
+        let mut allocations: Vec<_> = config
+            .targets
+            .iter()
+            .zip(weights)
+            .map(|(target, weight)| {
+                let amount = total.multiply_ratio(weight.atomics(), total_weight.atomics());
+                (target.clone(), amount)
+            })
+            .collect();
+        let allocated = allocations
+            .iter()
+            .map(|(_, amount)| *amount)
+            .sum::<Uint128>();
+        allocations[0].1 += total - allocated;
+
+        allocations.retain(|(_, amount)| !amount.is_zero());
+        Ok(allocations)
+    }
+}
+
+impl Default for StakeRouterContract<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
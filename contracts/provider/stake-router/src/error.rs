@@ -0,0 +1,22 @@
+use cosmwasm_std::StdError;
+use cw_utils::PaymentError;
+use mesh_apis::error::MeshError;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
+
+    #[error("No stake targets configured")]
+    NoTargets,
+
+    #[error("Stake amount has to be positive")]
+    ZeroAmount,
+}
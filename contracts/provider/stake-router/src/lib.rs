@@ -0,0 +1,7 @@
+pub mod contract;
+pub mod error;
+pub mod msg;
+// The multitests run the vault without the provider module custom messages
+#[cfg(all(test, not(feature = "provider-bindings")))]
+mod multitest;
+pub mod state;
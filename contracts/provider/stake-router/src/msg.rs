@@ -0,0 +1,43 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Uint128;
+
+/// Named strategies to split a stake across the targets
+#[cw_serde]
+#[derive(Copy)]
+pub enum Strategy {
+    /// The same amount to every target
+    EqualWeight,
+    /// Amounts inversely proportional to the lienholder risk, being its max slashing ratio
+    RiskWeighted,
+}
+
+#[cw_serde]
+pub struct StakeTargetInfo {
+    pub lienholder: String,
+    pub validator: String,
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub owner: String,
+    pub vault: String,
+    pub targets: Vec<StakeTargetInfo>,
+}
+
+#[cw_serde]
+pub struct Allocation {
+    pub lienholder: String,
+    pub validator: String,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct AllocationResponse {
+    pub allocations: Vec<Allocation>,
+}
+
+/// Stake message accepted by both the local and the cross staking contracts
+#[cw_serde]
+pub struct StakeMsg {
+    pub validator: String,
+}
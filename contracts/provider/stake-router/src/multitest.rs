@@ -0,0 +1,223 @@
+use cosmwasm_std::{coin, coins, to_json_binary, Addr, Decimal, Uint128, Validator};
+use cw_multi_test::{App as MtApp, StakingInfo};
+use cw_utils::Expiration;
+use mesh_apis::error::MeshError;
+use mesh_apis::ibc::AddValidator;
+use mesh_external_staking::contract::sv::mt::CodeId as ExternalStakingCodeId;
+use mesh_external_staking::contract::ExternalStakingContract;
+use mesh_external_staking::msg::AuthorizedEndpoint;
+use mesh_external_staking::state::SlashRatio;
+use mesh_external_staking::test_methods::sv::mt::TestMethodsProxy;
+use mesh_native_staking::contract::sv::mt::CodeId as NativeStakingCodeId;
+use mesh_native_staking::contract::sv::InstantiateMsg as NativeStakingInstantiateMsg;
+use mesh_native_staking_proxy::contract::sv::mt::CodeId as NativeStakingProxyCodeId;
+use mesh_vault::contract::sv::mt::{CodeId as VaultCodeId, VaultContractProxy};
+use mesh_vault::contract::VaultContract;
+use mesh_vault::msg::{GrantedMsgType, LocalStakingInfo, StakingInitInfo};
+use sylvia::multitest::{App, Proxy};
+
+use crate::contract::sv::mt::{CodeId, StakeRouterContractProxy};
+use crate::error::ContractError;
+use crate::msg::{Allocation, StakeTargetInfo, Strategy};
+
+const OSMO: &str = "OSMO";
+const STAR: &str = "star";
+
+const LOCAL_VALIDATOR: &str = "local";
+const REMOTE_VALIDATOR: &str = "remote";
+
+fn init_app(user: &str, amount: u128) -> App<MtApp> {
+    let app = App::custom(|router, _api, storage| {
+        router
+            .bank
+            .init_balance(storage, &Addr::unchecked(user), coins(amount, OSMO))
+            .unwrap();
+        router
+            .staking
+            .setup(
+                storage,
+                StakingInfo {
+                    bonded_denom: OSMO.to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+    });
+
+    let block_info = app.block_info();
+    app.app_mut()
+        .init_modules(|router, api, storage| {
+            router.staking.add_validator(
+                api,
+                storage,
+                &block_info,
+                Validator {
+                    address: LOCAL_VALIDATOR.to_string(),
+                    commission: Decimal::zero(),
+                    max_commission: Decimal::zero(),
+                    max_change_rate: Decimal::zero(),
+                },
+            )
+        })
+        .unwrap();
+
+    app
+}
+
+fn setup_vault<'app>(
+    app: &'app App<MtApp>,
+    owner: &'app str,
+) -> Proxy<'app, MtApp, VaultContract<'app>> {
+    let native_staking_inst_msg = NativeStakingInstantiateMsg {
+        denom: OSMO.to_string(),
+        slash_ratio_dsign: Decimal::percent(10),
+        slash_ratio_offline: Decimal::percent(10),
+        owner: None,
+        proxy_code_id: NativeStakingProxyCodeId::store_code(app).code_id(),
+    };
+    let local_staking = LocalStakingInfo::New(StakingInitInfo {
+        admin: None,
+        code_id: NativeStakingCodeId::store_code(app).code_id(),
+        msg: to_json_binary(&native_staking_inst_msg).unwrap(),
+        label: None,
+    });
+
+    VaultCodeId::store_code(app)
+        .instantiate(OSMO.to_owned(), Some(local_staking), None, None, None)
+        .call(owner)
+        .unwrap()
+}
+
+fn setup_cross_staking<'app>(
+    app: &'app App<MtApp>,
+    owner: &'app str,
+    vault: &Proxy<'app, MtApp, VaultContract<'app>>,
+    slash_percent: u64,
+) -> Proxy<'app, MtApp, ExternalStakingContract<'app>> {
+    let cross_staking = ExternalStakingCodeId::store_code(app)
+        .instantiate(
+            OSMO.to_owned(),
+            STAR.to_owned(),
+            vault.contract_addr.to_string(),
+            100,
+            AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+            SlashRatio {
+                double_sign: Decimal::percent(slash_percent),
+                offline: Decimal::percent(slash_percent),
+            },
+            None,
+            0,
+            None,
+            None,
+        )
+        .call(owner)
+        .unwrap();
+
+    cross_staking
+        .test_set_active_validator(AddValidator::mock(REMOTE_VALIDATOR), 100, 1234)
+        .call("test")
+        .unwrap();
+
+    cross_staking
+}
+
+#[test]
+fn diversify() {
+    let owner = "owner";
+    let user = "user";
+
+    let app = init_app(user, 1000);
+
+    let vault = setup_vault(&app, owner);
+    let local_staking = vault.config().unwrap().local_staking.unwrap();
+    let cross_staking1 = setup_cross_staking(&app, owner, &vault, 10);
+    let cross_staking2 = setup_cross_staking(&app, owner, &vault, 5);
+
+    let targets = vec![
+        StakeTargetInfo {
+            lienholder: local_staking.clone(),
+            validator: LOCAL_VALIDATOR.to_owned(),
+        },
+        StakeTargetInfo {
+            lienholder: cross_staking1.contract_addr.to_string(),
+            validator: REMOTE_VALIDATOR.to_owned(),
+        },
+        StakeTargetInfo {
+            lienholder: cross_staking2.contract_addr.to_string(),
+            validator: REMOTE_VALIDATOR.to_owned(),
+        },
+    ];
+    let router = CodeId::store_code(&app)
+        .instantiate(vault.contract_addr.to_string(), vec![])
+        .call(owner)
+        .unwrap();
+
+    // Only the owner can set the targets
+    let err = router.set_targets(targets.clone()).call(user).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = router
+        .allocation(Uint128::new(100), Strategy::EqualWeight)
+        .unwrap_err();
+    assert!(err.to_string().contains("No stake targets configured"));
+    router.set_targets(targets).call(owner).unwrap();
+
+    // Lower max slashing gets a bigger share
+    let allocation = |total, strategy| {
+        router
+            .allocation(Uint128::new(total), strategy)
+            .unwrap()
+            .allocations
+            .into_iter()
+            .map(|Allocation { amount, .. }| amount.u128())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(allocation(400, Strategy::RiskWeighted), [100, 100, 200]);
+    // Rounding leftovers go to the first target
+    assert_eq!(allocation(200, Strategy::EqualWeight), [68, 66, 66]);
+
+    vault
+        .bond()
+        .with_funds(&coins(1000, OSMO))
+        .call(user)
+        .unwrap();
+
+    // Not more than the free collateral
+    let err = router
+        .diversify(coin(1001, OSMO), Strategy::EqualWeight)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Mesh(MeshError::InsufficientCollateral(Uint128::new(1000)))
+    );
+
+    for msg_type in [GrantedMsgType::StakeLocal, GrantedMsgType::StakeRemote] {
+        vault
+            .grant(
+                router.contract_addr.to_string(),
+                msg_type,
+                Expiration::Never {},
+                None,
+            )
+            .call(user)
+            .unwrap();
+    }
+    router
+        .diversify(coin(600, OSMO), Strategy::EqualWeight)
+        .call(user)
+        .unwrap();
+
+    let liens = vault.account_details(user.to_owned()).unwrap().liens;
+    let liens: Vec<_> = liens
+        .into_iter()
+        .map(|lien| (lien.lienholder, lien.amount.high().u128()))
+        .collect();
+    assert_eq!(liens.len(), 3);
+    for lienholder in [
+        local_staking,
+        cross_staking1.contract_addr.to_string(),
+        cross_staking2.contract_addr.to_string(),
+    ] {
+        assert!(liens.contains(&(lienholder, 200)));
+    }
+}
@@ -0,0 +1,22 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Addr;
+
+#[cw_serde]
+pub struct Config {
+    /// Contract owner, allowed to update the stake targets
+    pub owner: Addr,
+    /// Vault the collateral is staked from
+    pub vault: Addr,
+    /// Lienholder / validator pairs stakes are split across
+    pub targets: Vec<StakeTarget>,
+}
+
+/// A single stake destination
+#[cw_serde]
+pub struct StakeTarget {
+    /// Lienholder contract. If it is the vault's local staking contract, the stake is local,
+    /// otherwise it is a cross-stake
+    pub lienholder: Addr,
+    /// Validator to stake to, through the lienholder
+    pub validator: String,
+}
@@ -1,42 +1,140 @@
 use cosmwasm_std::{
-    coin, ensure, Addr, BankMsg, Binary, Coin, Decimal, DepsMut, Fraction, Order, Reply, Response,
-    StdResult, Storage, SubMsg, SubMsgResponse, Uint128, WasmMsg,
+    coin, coins, ensure, ensure_eq, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut,
+    Empty, Env, Event, Fraction, Order, Reply, Response, StdResult, Storage, SubMsg,
+    SubMsgResponse, Timestamp, Uint128, WasmMsg,
 };
-use cw2::set_contract_version;
-use cw_storage_plus::{Bounder, Item, Map};
-use cw_utils::{must_pay, nonpayable, parse_instantiate_response_data};
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::{Bound, Bounder, IndexedMap, Item, Map};
+use cw_utils::{must_pay, nonpayable, parse_instantiate_response_data, Expiration};
 use std::cmp::min;
+use std::collections::BTreeMap;
 
-use mesh_apis::cross_staking_api::CrossStakingApiHelper;
+use mesh_apis::cross_staking_api::{AutoStake, CrossStakingApiHelper, ValidatorSelection};
+use mesh_apis::error::MeshError;
+use mesh_apis::events::{SlashEvent, StakeEvent, UnstakeEvent};
 use mesh_apis::local_staking_api::{
     sv::LocalStakingApiQueryMsg, LocalStakingApiHelper, SlashRatioResponse,
 };
+use mesh_apis::migration::Upgrade;
+use mesh_apis::ownership_api::{self, Ownership, OwnershipApi};
 use mesh_apis::vault_api::{self, SlashInfo, VaultApi};
+use mesh_apis::vault_hook_api::{VaultHookApiHelper, VaultHookMsg};
+use mesh_apis::vault_strategy_api::VaultStrategyApiHelper;
 use mesh_sync::Tx::InFlightStaking;
 use mesh_sync::{max_range, ValueRange};
-use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx};
+use sylvia::types::{ExecCtx, InstantiateCtx, MigrateCtx, QueryCtx, ReplyCtx, SudoCtx};
 use sylvia::{contract, schemars};
 
 use crate::error::ContractError;
+use crate::grants::Grants;
+use crate::invariants;
+use crate::liens::{self, LienIndexes};
+use crate::metadata;
+use crate::migrations;
 use crate::msg::{
-    AccountClaimsResponse, AccountDetailsResponse, AccountResponse, AllAccountsResponse,
-    AllAccountsResponseItem, AllActiveExternalStakingResponse, AllTxsResponse, AllTxsResponseItem,
-    ConfigResponse, LienResponse, LocalStakingInfo, TxResponse,
+    AccountClaimsResponse, AccountDetailsResponse, AccountLiensResponse, AccountResponse,
+    AllAccountsResponse, AllAccountsResponseItem, AllActiveExternalStakingResponse, AllTxsResponse,
+    AllTxsResponseItem, AutoRestakeResponse, BatchItem, ChainExposure, ClaimsResponse,
+    ConfigResponse, DenomMetadata, DenomMetadataResponse, ExposureByChainResponse, GrantedMsg,
+    GrantedMsgType, GrantsResponse, HooksResponse, InvariantsResponse, LienDetails, LienOrder,
+    LienResponse, LienholderKind, LienholderRewards, LienholderStake, LienholderUser,
+    LocalStakingInfo, PausedLienholdersResponse, PendingAllRewardsResponse, PendingClaim,
+    PermitNonceResponse, SimulationResponse, StakeRemotePermit, StrategyDepositResponse,
+    TxResponse, UnbondingClaim, UsersByLienholderResponse, UtilizationResponse, VaultStatsResponse,
+};
+use crate::permits::Permits;
+use crate::provider;
+use crate::receipt;
+use crate::state::{
+    AutoRestake, Config, Lien, LocalStaking, StrategyDeposit, UserInfo, VaultStats,
 };
-use crate::state::{Config, Lien, LocalStaking, UserInfo};
 use crate::txs::Txs;
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const REPLY_ID_INSTANTIATE: u64 = 1;
+pub const REPLY_ID_HOOK: u64 = 2;
+
+/// Maximum number of registered hook contracts
+pub const MAX_HOOKS: usize = 10;
+/// Gas available to every hook call, so a misbehaving hook can't make the vault txs run out
+/// of gas
+pub const HOOK_GAS_LIMIT: u64 = 500_000;
 
 pub const DEFAULT_PAGE_LIMIT: u32 = 10;
 pub const MAX_PAGE_LIMIT: u32 = 30;
 
+/// Maximum number of accounts in a custodian batch
+pub const MAX_BATCH_SIZE: usize = 50;
+
+/// Maximum number of distinct lienholders of an account, unless configured otherwise
+pub const DEFAULT_MAX_LIENHOLDERS: u32 = 16;
+
+#[cfg(not(feature = "provider-bindings"))]
+pub mod custom {
+    pub type VaultMsg = cosmwasm_std::Empty;
+    pub type Response = cosmwasm_std::Response<VaultMsg>;
+}
+#[cfg(feature = "provider-bindings")]
+pub mod custom {
+    pub type VaultMsg = mesh_bindings::ProviderCustomMsg;
+    pub type Response = cosmwasm_std::Response<VaultMsg>;
+}
+
+/// Checks `user` can unbond `amount`
+fn check_unbond(config: &Config, user: &UserInfo, amount: &Coin) -> Result<(), ContractError> {
+    ensure!(
+        config.denom == amount.denom,
+        MeshError::InvalidDenom(config.denom.clone())
+    );
+    let free_collateral = user.free_collateral();
+    ensure!(
+        free_collateral.low() >= amount.amount,
+        ContractError::ClaimsLocked(free_collateral)
+    );
+    ensure!(
+        amount.amount >= config.min_unbond || amount.amount == user.collateral,
+        ContractError::UnbondTooSmall(config.min_unbond)
+    );
+    Ok(())
+}
+
+/// Simulation outcome, `result` being the account after the operation. `user` is the account
+/// before it
+fn simulation_response(
+    result: Result<UserInfo, ContractError>,
+    user: &UserInfo,
+) -> SimulationResponse {
+    match result {
+        Ok(after) => SimulationResponse {
+            success: true,
+            error: None,
+            free: after.free_collateral(),
+        },
+        Err(err) => SimulationResponse {
+            success: false,
+            error: Some(err.to_string()),
+            free: user.free_collateral(),
+        },
+    }
+}
+
+/// Fails if the vault-wide utilization of `stats` is over the configured cap
+fn check_stats_utilization(stats: &VaultStats, config: &Config) -> Result<(), ContractError> {
+    let Some(max) = config.max_utilization else {
+        return Ok(());
+    };
+    ensure!(
+        stats.utilization() <= max,
+        ContractError::UtilizationCapReached(max)
+    );
+    Ok(())
+}
+
 /// Aligns pagination limit
 fn clamp_page_limit(limit: Option<u32>) -> usize {
-    limit.unwrap_or(DEFAULT_PAGE_LIMIT).max(MAX_PAGE_LIMIT) as usize
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize
 }
 
 /// Default falseness for serde
@@ -44,6 +142,34 @@ fn def_false() -> bool {
     false
 }
 
+/// Outcome of the custodian batch item at `index`, completed by the caller
+fn batch_item_event(index: usize, item: &BatchItem) -> Event {
+    Event::new("batch_item")
+        .add_attribute("index", index.to_string())
+        .add_attribute("account", &item.account)
+        .add_attribute("amount", item.amount.to_string())
+}
+
+/// One slash event per slashed user
+fn slash_events(
+    slashes: &[SlashInfo],
+    denom: &str,
+    validator: &str,
+    lienholder: &Addr,
+) -> Vec<Event> {
+    slashes
+        .iter()
+        .map(|slash| {
+            Event::from(
+                SlashEvent::new(coin(slash.slash.u128(), denom))
+                    .delegator(&slash.user)
+                    .validator(validator)
+                    .lienholder(lienholder),
+            )
+        })
+        .collect()
+}
+
 pub struct VaultContract<'a> {
     /// General contract configuration
     pub config: Item<'a, Config>,
@@ -52,7 +178,7 @@ pub struct VaultContract<'a> {
     /// All liens in the protocol
     ///
     /// Liens are indexed with (user, lien_holder), as this pair has to be unique
-    pub liens: Map<'a, (&'a Addr, &'a Addr), Lien>,
+    pub liens: IndexedMap<'a, (&'a Addr, &'a Addr), Lien, LienIndexes<'a>>,
     /// Per-user information
     pub users: Map<'a, &'a Addr, UserInfo>,
     /// All active external staking contracts in use by this vault
@@ -60,23 +186,204 @@ pub struct VaultContract<'a> {
     /// Pending txs information
     pub tx_count: Item<'a, u64>,
     pub pending: Txs<'a>,
+    /// Per-user auto-restaking of released claims
+    pub auto_restake: Map<'a, &'a Addr, AutoRestake>,
+    /// Lienholders not accepting new stakes
+    pub paused_lienholders: Map<'a, &'a Addr, ()>,
+    /// Permissions to execute vault messages on behalf of other accounts
+    pub grants: Grants<'a>,
+    /// Keys and nonces of the off-chain signed approvals
+    pub permits: Permits<'a>,
+    /// Consumer chain id of the registered lienholders, for exposure reporting
+    pub lienholder_chains: Map<'a, &'a Addr, String>,
+    /// Protocol-wide totals, kept in sync by `save_user`
+    pub stats: Item<'a, VaultStats>,
+    /// Sum of the (high) lien amounts per lienholder, kept in sync by `save_lien` and
+    /// `remove_lien`
+    pub lienholder_totals: Map<'a, &'a Addr, Uint128>,
+    /// Delayed cross stake releases, by `(release time in nanoseconds, owner, lienholder)`
+    pub pending_releases: Map<'a, (u64, &'a Addr, &'a Addr), Uint128>,
+    /// Sum of the pending releases of every owner
+    pub incoming_collateral: Map<'a, &'a Addr, Uint128>,
+    /// Contracts notified of the vault activity, see `VaultHookMsg`
+    pub hooks: Map<'a, &'a Addr, ()>,
+    /// Unbonded collateral waiting for the unbonding period, by `(owner, release time in
+    /// nanoseconds)`
+    pub unbonding_claims: Map<'a, (&'a Addr, u64), Uint128>,
+    /// Free collateral deposited into the yield strategy, by owner
+    pub strategy_deposits: Map<'a, &'a Addr, StrategyDeposit>,
+    /// Total shares of the vault deposits in the yield strategy
+    pub strategy_shares: Item<'a, Uint128>,
+    /// Collateral bonded by the provider module from vesting accounts, by owner. Part of their
+    /// collateral, but kept by the module until handed back to the vesting account
+    pub vesting: Map<'a, &'a Addr, Uint128>,
 }
 
 #[cfg_attr(not(feature = "library"), sylvia::entry_points)]
 #[contract]
 #[sv::error(ContractError)]
 #[sv::messages(vault_api as VaultApi)]
+#[sv::messages(ownership_api as OwnershipApi)]
+#[sv::custom(msg=custom::VaultMsg)]
+#[cfg_attr(
+    feature = "debug-invariants",
+    sv::override_entry_point(exec=crate::invariants::execute(crate::contract::sv::ContractExecMsg))
+)]
 impl VaultContract<'_> {
     pub fn new() -> Self {
         Self {
             config: Item::new("config"),
             local_staking: Item::new("local_staking"),
-            liens: Map::new("liens"),
+            liens: liens::liens("liens", "liens__amount", "liens__lienholder"),
             users: Map::new("users"),
             pending: Txs::new("pending_txs", "users"),
             tx_count: Item::new("tx_count"),
             active_external: Map::new("active_external"),
+            auto_restake: Map::new("auto_restake"),
+            paused_lienholders: Map::new("paused_lienholders"),
+            lienholder_chains: Map::new("lienholder_chains"),
+            grants: Grants::new("grants", "grant_reward_recipients"),
+            permits: Permits::new("permit_nonces"),
+            stats: Item::new("stats"),
+            lienholder_totals: Map::new("lienholder_totals"),
+            pending_releases: Map::new("pending_releases"),
+            incoming_collateral: Map::new("incoming_collateral"),
+            hooks: Map::new("hooks"),
+            unbonding_claims: Map::new("unbonding_claims"),
+            strategy_deposits: Map::new("strategy_deposits"),
+            strategy_shares: Item::new("strategy_shares"),
+            vesting: Map::new("vesting_collateral"),
+        }
+    }
+
+    /// Saves the user info, updating the vault stats with the changes
+    fn save_user(&self, storage: &mut dyn Storage, owner: &Addr, user: &UserInfo) -> StdResult<()> {
+        let stats = self.stats_with_user(storage, owner, user)?;
+        self.stats.save(storage, &stats)?;
+        self.users.save(storage, owner, user)
+    }
+
+    /// Vault stats with the account of `owner` replaced by `user`
+    fn stats_with_user(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        user: &UserInfo,
+    ) -> StdResult<VaultStats> {
+        let mut stats = self.stats.may_load(storage)?.unwrap_or_default();
+        let (old_collateral, old_slashable) = match self.users.may_load(storage, owner)? {
+            Some(old) => (old.collateral, old.total_slashable.high()),
+            None => {
+                stats.accounts += 1;
+                (Uint128::zero(), Uint128::zero())
+            }
+        };
+        stats.total_collateral = stats.total_collateral + user.collateral - old_collateral;
+        stats.total_slashable = stats.total_slashable + user.total_slashable.high() - old_slashable;
+        Ok(stats)
+    }
+
+    /// Saves the lien, updating its lienholder total with the changes
+    fn save_lien(
+        &self,
+        storage: &mut dyn Storage,
+        key: (&Addr, &Addr),
+        lien: &Lien,
+    ) -> StdResult<()> {
+        let old = self
+            .liens
+            .may_load(storage, key)?
+            .map_or(Uint128::zero(), |old| old.amount.high());
+        self.lienholder_totals
+            .update(storage, key.1, |total| -> StdResult<_> {
+                Ok(total.unwrap_or_default() + lien.amount.high() - old)
+            })?;
+        self.liens.save(storage, key, lien)
+    }
+
+    /// Removes the lien, updating its lienholder total
+    fn remove_lien(&self, storage: &mut dyn Storage, key: (&Addr, &Addr)) -> StdResult<()> {
+        let Some(old) = self.liens.may_load(storage, key)? else {
+            return Ok(());
+        };
+        self.lienholder_totals
+            .update(storage, key.1, |total| -> StdResult<_> {
+                Ok(total.unwrap_or_default() - old.amount.high())
+            })?;
+        self.liens.remove(storage, key)
+    }
+
+    /// Submessages notifying all the hooks of `msg`. Hook failures are caught by `reply`, so
+    /// they don't revert the vault tx
+    fn hook_msgs(
+        &self,
+        storage: &dyn Storage,
+        msg: VaultHookMsg,
+    ) -> StdResult<Vec<SubMsg<custom::VaultMsg>>> {
+        self.hooks
+            .keys(storage, None, None, Order::Ascending)
+            .map(|hook| {
+                let msg = VaultHookApiHelper(hook?).vault_hook(msg.clone())?;
+                Ok(SubMsg::reply_on_error(msg, REPLY_ID_HOOK).with_gas_limit(HOOK_GAS_LIMIT))
+            })
+            .collect()
+    }
+
+    /// `AfterStakeChange` hook submessages, with the (committed) stake of `owner` on
+    /// `lienholder` after the change
+    fn stake_change_hook_msgs(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        lienholder: &Addr,
+    ) -> StdResult<Vec<SubMsg<custom::VaultMsg>>> {
+        let amount = self
+            .liens
+            .may_load(storage, (owner, lienholder))?
+            .map_or(Uint128::zero(), |lien| lien.amount.low());
+        let msg = VaultHookMsg::AfterStakeChange {
+            owner: owner.to_string(),
+            lienholder: lienholder.to_string(),
+            amount,
+        };
+        self.hook_msgs(storage, msg)
+    }
+
+    /// Withdraws all the strategy deposits of `owner` back to its collateral, at the current
+    /// value of its shares. Returns the withdrawn value, and the strategy withdrawal msgs, which
+    /// must run before anything spending the collateral.
+    fn withdraw_strategy(
+        &self,
+        deps: DepsMut,
+        contract: &Addr,
+        owner: &Addr,
+    ) -> Result<(Uint128, Vec<CosmosMsg<custom::VaultMsg>>), ContractError> {
+        let deposit = match self.strategy_deposits.may_load(deps.storage, owner)? {
+            Some(deposit) => deposit,
+            None => return Ok((Uint128::zero(), vec![])),
+        };
+        // Deposits are only made with a strategy set, and it can't be unset while in use
+        let config = self.config.load(deps.storage)?;
+        let strategy = VaultStrategyApiHelper(config.strategy.ok_or(ContractError::NoStrategy)?);
+
+        let total_shares = self.strategy_shares.load(deps.storage)?;
+        let total_value = strategy.deposit_value(deps.as_ref(), contract)?;
+        let value = total_value.multiply_ratio(deposit.shares, total_shares);
+
+        self.strategy_shares
+            .save(deps.storage, &(total_shares - deposit.shares))?;
+        self.strategy_deposits.remove(deps.storage, owner);
+
+        let mut user = self.users.load(deps.storage, owner)?;
+        user.collateral += value;
+        self.save_user(deps.storage, owner, &user)?;
+
+        let mut msgs = vec![];
+        if !value.is_zero() {
+            msgs.push(strategy.withdraw(value)?.into());
+            msgs.extend(provider::bond_msg(owner, coin(value.u128(), config.denom)));
         }
+        Ok((value, msgs))
     }
 
     pub fn next_tx_id(&self, store: &mut dyn Storage) -> StdResult<u64> {
@@ -85,19 +392,56 @@ impl VaultContract<'_> {
         Ok(id)
     }
 
+    /// If the owner is not set in the message, it defaults to info.sender.
+    /// If `receipt_subdenom` is set, a tokenfactory receipt token is created, and minted for
+    /// bonded collateral.
     #[sv::msg(instantiate)]
     pub fn instantiate(
         &self,
         ctx: InstantiateCtx,
         denom: String,
         local_staking: Option<LocalStakingInfo>,
-    ) -> Result<Response, ContractError> {
+        owner: Option<String>,
+        receipt_subdenom: Option<String>,
+        // display metadata of `denom`, taken from the bank module if not set
+        denom_metadata: Option<DenomMetadata>,
+    ) -> Result<custom::Response, ContractError> {
         nonpayable(&ctx.info)?;
+        if let Some(metadata) = &denom_metadata {
+            ensure!(
+                !metadata.display.is_empty() && !metadata.symbol.is_empty(),
+                ContractError::InvalidDenomMetadata
+            );
+        }
 
-        let config = Config { denom };
+        let owner = match owner {
+            Some(owner) => ctx.deps.api.addr_validate(&owner)?,
+            None => ctx.info.sender.clone(),
+        };
+        let contract = &ctx.env.contract.address;
+        let receipt_denom = receipt_subdenom
+            .as_ref()
+            .map(|subdenom| receipt::receipt_denom(contract, subdenom));
+        let config = Config {
+            denom,
+            receipt_denom,
+            min_bond: Uint128::zero(),
+            min_unbond: Uint128::zero(),
+            max_utilization: None,
+            unbonding_period: None,
+            strategy: None,
+            max_lienholders: None,
+            denom_metadata,
+        };
         self.config.save(ctx.deps.storage, &config)?;
+        ownership_api::initialize_owner(ctx.deps.storage, Some(owner))?;
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+        let mut resp = Response::new();
+        if let Some(subdenom) = receipt_subdenom {
+            resp = resp.add_message(receipt::create_denom_msg(contract, &subdenom));
+        }
+
         if let Some(local_staking) = local_staking {
             match local_staking {
                 LocalStakingInfo::Existing(exist) => {
@@ -116,7 +460,7 @@ impl VaultContract<'_> {
 
                     self.local_staking
                         .save(ctx.deps.storage, &Some(local_staking))?;
-                    Ok(Response::new())
+                    Ok(resp)
                 }
                 LocalStakingInfo::New(local_staking) => {
                     // instantiate local_staking and handle reply
@@ -130,66 +474,420 @@ impl VaultContract<'_> {
                             .unwrap_or_else(|| "Mesh Security Local Staking".to_string()),
                     };
                     let sub_msg = SubMsg::reply_on_success(msg, REPLY_ID_INSTANTIATE);
-                    Ok(Response::new().add_submessage(sub_msg))
+                    Ok(resp.add_submessage(sub_msg))
                 }
             }
         } else {
             self.local_staking.save(ctx.deps.storage, &None)?;
-            Ok(Response::new())
+            Ok(resp)
         }
     }
 
+    /// Migrates from an older version of the contract, running the state migrations of all the
+    /// versions in between, in order. They are listed in the emitted `migrate` event.
+    #[sv::msg(migrate)]
+    pub fn migrate(&self, ctx: MigrateCtx) -> Result<custom::Response, ContractError> {
+        let MigrateCtx { mut deps, env } = ctx;
+        let stored = get_contract_version(deps.storage)?;
+        let upgrade = Upgrade::check(
+            &stored.contract,
+            &stored.version,
+            CONTRACT_NAME,
+            CONTRACT_VERSION,
+        )?;
+
+        let applied = migrations::run_migrations(self, deps.branch(), &env, &upgrade)?;
+        set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        let event = Event::new("migrate")
+            .add_attribute("from_version", stored.version)
+            .add_attribute("to_version", CONTRACT_VERSION)
+            .add_attribute("migrations", applied.join(","));
+        Ok(Response::new().add_event(event))
+    }
+
+    /// Bonds collateral. If enabled, the same amount of receipt tokens is minted to the sender.
     #[sv::msg(exec)]
-    fn bond(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
-        let amount = must_pay(&ctx.info, &denom)?;
+    fn bond(&self, ctx: ExecCtx) -> Result<custom::Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        self.bond_for(ctx, owner)
+    }
+
+    /// `bond` on behalf of `owner`, either the sender or a granter
+    fn bond_for(&self, ctx: ExecCtx, owner: Addr) -> Result<custom::Response, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let amount = must_pay(&ctx.info, &config.denom)?;
+        let display = metadata::display_attributes(ctx.deps.as_ref(), &config, amount);
+
+        let resp = self
+            .bond_collateral(ctx.deps.storage, &ctx.env, &config, &owner, amount)?
+            .add_attribute("action", "bond")
+            .add_attribute("sender", owner)
+            .add_attribute("amount", amount.to_string())
+            .add_attributes(display);
+
+        Ok(resp)
+    }
+
+    /// Bonds `amount` as collateral of `owner`. If enabled, the same amount of receipt tokens
+    /// is minted to the owner.
+    fn bond_collateral(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        config: &Config,
+        owner: &Addr,
+        amount: Uint128,
+    ) -> Result<custom::Response, ContractError> {
+        let hook_msgs = self.credit_collateral(storage, config, owner, amount)?;
+
+        let mut resp = Response::new().add_messages(provider::bond_msg(
+            owner,
+            coin(amount.u128(), &config.denom),
+        ));
+        if let Some(receipt_denom) = &config.receipt_denom {
+            resp = resp.add_message(receipt::mint_msg(
+                &env.contract.address,
+                receipt_denom,
+                amount,
+                owner,
+            ));
+        }
+
+        Ok(resp.add_submessages(hook_msgs))
+    }
+
+    /// Adds `amount` to the collateral of `owner`, returning the hook messages of the bond
+    fn credit_collateral(
+        &self,
+        storage: &mut dyn Storage,
+        config: &Config,
+        owner: &Addr,
+        amount: Uint128,
+    ) -> Result<Vec<SubMsg<custom::VaultMsg>>, ContractError> {
+        ensure!(
+            amount >= config.min_bond,
+            ContractError::BondTooSmall(config.min_bond)
+        );
+
+        let mut user = self.users.may_load(storage, owner)?.unwrap_or_default();
+        user.collateral += amount;
+        self.save_user(storage, owner, &user)?;
+
+        let hook_msgs = self.hook_msgs(
+            storage,
+            VaultHookMsg::AfterBond {
+                owner: owner.to_string(),
+                amount: coin(amount.u128(), &config.denom),
+            },
+        )?;
+        Ok(hook_msgs)
+    }
+
+    /// Collateral of `owner` the vesting one is kept in, i.e. not sent out to local staking
+    fn vesting_capacity(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        user: &UserInfo,
+    ) -> StdResult<Uint128> {
+        let local = match self.local_staking.load(storage)? {
+            Some(local_staking) => self
+                .liens
+                .may_load(storage, (owner, &local_staking.contract.0))?
+                .map(|lien| lien.amount.high())
+                .unwrap_or_default(),
+            None => Uint128::zero(),
+        };
+        Ok(user.collateral.saturating_sub(local))
+    }
+
+    /// Vesting collateral of `owner`, never more than their collateral once slashed
+    fn vesting_collateral(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        user: &UserInfo,
+    ) -> StdResult<Uint128> {
+        let vesting = self.vesting.may_load(storage, owner)?.unwrap_or_default();
+        Ok(min(vesting, user.collateral))
+    }
+
+    /// Fails if collateral of `owner` left the vault or the module at the expense of their
+    /// `vesting` collateral, taken before it left, which only the module can hold
+    fn check_vesting(
+        &self,
+        storage: &dyn Storage,
+        owner: &Addr,
+        vesting: Uint128,
+    ) -> Result<(), ContractError> {
+        let user = self.users.may_load(storage, owner)?.unwrap_or_default();
+        ensure!(
+            vesting <= self.vesting_capacity(storage, owner, &user)?,
+            ContractError::VestingLocked
+        );
+        Ok(())
+    }
+
+    /// Bonds collateral for many accounts at once, with the funds sent by the caller (i.e. a
+    /// custodian). Every account must have granted the caller `bond` messages.
+    ///
+    /// Failing items are skipped, and their funds sent back to the caller. The outcome of every
+    /// item is reported in a `batch_item` event.
+    #[sv::msg(exec)]
+    fn bond_batch(
+        &self,
+        ctx: ExecCtx,
+        items: Vec<BatchItem>,
+    ) -> Result<custom::Response, ContractError> {
+        ensure!(
+            !items.is_empty() && items.len() <= MAX_BATCH_SIZE,
+            ContractError::InvalidBatchSize(MAX_BATCH_SIZE)
+        );
+        let config = self.config.load(ctx.deps.storage)?;
+        let total: Uint128 = items.iter().map(|item| item.amount).sum();
+        let sent = must_pay(&ctx.info, &config.denom)?;
+        ensure!(sent == total, ContractError::InvalidBatchFunds(total));
+
+        let mut resp = Response::new();
+        let mut failed = 0u32;
+        let mut refund = Uint128::zero();
+        for (index, item) in items.iter().enumerate() {
+            let result = ctx
+                .deps
+                .api
+                .addr_validate(&item.account)
+                .map_err(ContractError::from)
+                .and_then(|account| {
+                    self.grants.check(
+                        ctx.deps.storage,
+                        &ctx.env.block,
+                        &account,
+                        &ctx.info.sender,
+                        GrantedMsgType::Bond,
+                    )?;
+                    self.bond_collateral(ctx.deps.storage, &ctx.env, &config, &account, item.amount)
+                });
+            let event = batch_item_event(index, item);
+            match result {
+                Ok(bonded) => {
+                    resp = resp
+                        .add_submessages(bonded.messages)
+                        .add_event(event.add_attribute("status", "ok"));
+                }
+                Err(err) => {
+                    failed += 1;
+                    refund += item.amount;
+                    resp = resp.add_event(
+                        event
+                            .add_attribute("status", "failed")
+                            .add_attribute("error", err.to_string()),
+                    );
+                }
+            }
+        }
+
+        if !refund.is_zero() {
+            resp = resp.add_message(BankMsg::Send {
+                to_address: ctx.info.sender.to_string(),
+                amount: coins(refund.u128(), &config.denom),
+            });
+        }
+
+        let resp = resp
+            .add_attribute("action", "bond_batch")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("items", items.len().to_string())
+            .add_attribute("failed", failed.to_string())
+            .add_attribute("refund", refund.to_string());
+
+        Ok(resp)
+    }
+
+    /// Unbonds free collateral. If enabled, the same amount of receipt tokens has to be sent
+    /// along, and is burned.
+    #[sv::msg(exec)]
+    fn unbond(&self, mut ctx: ExecCtx, amount: Coin) -> Result<custom::Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        let config = self.config.load(ctx.deps.storage)?;
+        match &config.receipt_denom {
+            Some(receipt_denom) => receipt::must_return(&ctx.info, receipt_denom, amount.amount)?,
+            None => nonpayable(&ctx.info)?,
+        }
+        let (_, strategy_msgs) =
+            self.withdraw_strategy(ctx.deps.branch(), &ctx.env.contract.address, &owner)?;
 
         let mut user = self
             .users
-            .may_load(ctx.deps.storage, &ctx.info.sender)?
+            .may_load(ctx.deps.storage, &owner)?
             .unwrap_or_default();
-        user.collateral += amount;
-        self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
+        check_unbond(&config, &user, &amount)?;
+        let vesting = self.vesting_collateral(ctx.deps.storage, &owner, &user)?;
 
-        let resp = Response::new()
-            .add_attribute("action", "bond")
-            .add_attribute("sender", ctx.info.sender)
-            .add_attribute("amount", amount.to_string());
+        user.collateral -= amount.amount;
+        self.save_user(ctx.deps.storage, &owner, &user)?;
+        let display = metadata::display_attributes(ctx.deps.as_ref(), &config, amount.amount);
+
+        // Vesting collateral is unbonded last, and handed back by the module to the vesting
+        // account. The rest goes through the vault
+        let capacity = self.vesting_capacity(ctx.deps.storage, &owner, &user)?;
+        let returned = min(vesting.saturating_sub(capacity), amount.amount);
+        let liquid = amount.amount - returned;
+        let mut resp = Response::new().add_messages(strategy_msgs);
+        if !returned.is_zero() {
+            let remaining = vesting - returned;
+            if remaining.is_zero() {
+                self.vesting.remove(ctx.deps.storage, &owner);
+            } else {
+                self.vesting.save(ctx.deps.storage, &owner, &remaining)?;
+            }
+            resp = resp
+                .add_messages(provider::return_vesting_msg(
+                    &owner,
+                    coin(returned.u128(), &config.denom),
+                    remaining,
+                ))
+                .add_event(
+                    Event::new("vesting_handoff")
+                        .add_attribute("delegator", &owner)
+                        .add_attribute("amount", returned.to_string())
+                        .add_attribute("remaining", remaining.to_string()),
+                );
+        }
+
+        // Back in the vault, either right away or kept for the claim
+        if !liquid.is_zero() {
+            resp = resp.add_messages(provider::unbond_msg(
+                &owner,
+                coin(liquid.u128(), &config.denom),
+            ));
+            match config.unbonding_period {
+                Some(period) => {
+                    // Kept slashable until withdrawn with `claim_matured`
+                    let release_at = ctx.env.block.time.plus_seconds(period);
+                    self.unbonding_claims.update(
+                        ctx.deps.storage,
+                        (&owner, release_at.nanos()),
+                        |claim| -> StdResult<_> { Ok(claim.unwrap_or_default() + liquid) },
+                    )?;
+                    resp = resp.add_attribute("release_at", release_at.to_string());
+                }
+                None => {
+                    resp = resp.add_message(BankMsg::Send {
+                        to_address: owner.to_string(),
+                        amount: coins(liquid.u128(), &config.denom),
+                    });
+                }
+            }
+        }
+        if let Some(receipt_denom) = config.receipt_denom {
+            resp = resp.add_message(receipt::burn_msg(
+                &ctx.env.contract.address,
+                &receipt_denom,
+                amount.amount,
+            ));
+        }
+
+        let hook_msgs = self.hook_msgs(
+            ctx.deps.storage,
+            VaultHookMsg::AfterUnbond {
+                owner: owner.to_string(),
+                amount: amount.clone(),
+            },
+        )?;
+
+        let resp = resp
+            .add_submessages(hook_msgs)
+            .add_attribute("action", "unbond")
+            .add_attribute("sender", owner)
+            .add_attribute("amount", amount.to_string())
+            .add_attributes(display);
 
         Ok(resp)
     }
 
+    /// Moves free (non-liened) collateral from the sender to the recipient account,
+    /// without a round trip through unbond / bond.
+    /// If enabled, the same amount of receipt tokens has to be sent along, and is forwarded to
+    /// the recipient.
     #[sv::msg(exec)]
-    fn unbond(&self, ctx: ExecCtx, amount: Coin) -> Result<Response, ContractError> {
-        nonpayable(&ctx.info)?;
+    fn transfer_collateral(
+        &self,
+        ctx: ExecCtx,
+        recipient: String,
+        amount: Coin,
+    ) -> Result<custom::Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        let config = self.config.load(ctx.deps.storage)?;
+        match &config.receipt_denom {
+            Some(receipt_denom) => receipt::must_return(&ctx.info, receipt_denom, amount.amount)?,
+            None => nonpayable(&ctx.info)?,
+        }
 
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let denom = config.denom;
+        ensure!(denom == amount.denom, MeshError::InvalidDenom(denom));
+        ensure!(
+            amount.amount >= config.min_bond,
+            ContractError::BondTooSmall(config.min_bond)
+        );
 
-        ensure!(denom == amount.denom, ContractError::UnexpectedDenom(denom));
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
 
-        let mut user = self
+        let mut sender = self
             .users
-            .may_load(ctx.deps.storage, &ctx.info.sender)?
+            .may_load(ctx.deps.storage, &owner)?
             .unwrap_or_default();
 
-        let free_collateral = user.free_collateral();
+        let free_collateral = sender.free_collateral();
         ensure!(
             free_collateral.low() >= amount.amount,
             ContractError::ClaimsLocked(free_collateral)
         );
 
-        user.collateral -= amount.amount;
-        self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
+        let vesting = self.vesting_collateral(ctx.deps.storage, &owner, &sender)?;
+        sender.collateral -= amount.amount;
+        ensure!(sender.verify_collateral(), MeshError::InsufficientBalance);
+        self.save_user(ctx.deps.storage, &owner, &sender)?;
+        self.check_vesting(ctx.deps.storage, &owner, vesting)?;
 
-        let msg = BankMsg::Send {
-            to_address: ctx.info.sender.to_string(),
-            amount: vec![amount.clone()],
-        };
+        // Loaded after saving the sender, so a self-transfer is a no-op
+        let mut receiver = self
+            .users
+            .may_load(ctx.deps.storage, &recipient)?
+            .unwrap_or_default();
+        receiver.collateral += amount.amount;
+        self.save_user(ctx.deps.storage, &recipient, &receiver)?;
+
+        let mut hook_msgs = self.hook_msgs(
+            ctx.deps.storage,
+            VaultHookMsg::AfterUnbond {
+                owner: owner.to_string(),
+                amount: amount.clone(),
+            },
+        )?;
+        hook_msgs.extend(self.hook_msgs(
+            ctx.deps.storage,
+            VaultHookMsg::AfterBond {
+                owner: recipient.to_string(),
+                amount: amount.clone(),
+            },
+        )?);
+
+        let mut resp = Response::new()
+            .add_messages(provider::unbond_msg(&owner, amount.clone()))
+            .add_messages(provider::bond_msg(&recipient, amount.clone()))
+            .add_submessages(hook_msgs);
+        if let Some(receipt_denom) = config.receipt_denom {
+            resp = resp.add_message(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![coin(amount.amount.u128(), receipt_denom)],
+            });
+        }
 
-        let resp = Response::new()
-            .add_message(msg)
-            .add_attribute("action", "unbond")
-            .add_attribute("sender", ctx.info.sender)
+        let resp = resp
+            .add_attribute("action", "transfer_collateral")
+            .add_attribute("sender", owner)
+            .add_attribute("recipient", recipient)
             .add_attribute("amount", amount.to_string());
 
         Ok(resp)
@@ -198,16 +896,51 @@ impl VaultContract<'_> {
     /// This assigns a claim of amount tokens to the remote contract, which can take some action with it
     #[sv::msg(exec)]
     fn stake_remote(
+        &self,
+        ctx: ExecCtx,
+        // address of the contract to virtually stake on
+        contract: String,
+        // amount to stake on that contract
+        amount: Coin,
+        // action to take with that stake
+        msg: Binary,
+    ) -> Result<custom::Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        self.stake_remote_for(ctx, owner, contract, amount, msg)
+    }
+
+    /// `stake_remote`, letting the remote contract split the stake across the validators
+    /// selected by `strategy`, instead of picking them manually
+    #[sv::msg(exec)]
+    fn stake_remote_auto(
+        &self,
+        ctx: ExecCtx,
+        // address of the contract to virtually stake on
+        contract: String,
+        // amount to stake on that contract
+        amount: Coin,
+        strategy: ValidatorSelection,
+    ) -> Result<custom::Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        let msg = AutoStake::new(strategy).encode()?;
+        self.stake_remote_for(ctx, owner, contract, amount, msg)
+    }
+
+    /// `stake_remote` on behalf of `owner`, either the sender or a granter
+    fn stake_remote_for(
         &self,
         mut ctx: ExecCtx,
+        owner: Addr,
         // address of the contract to virtually stake on
         contract: String,
         // amount to stake on that contract
         amount: Coin,
         // action to take with that stake
         msg: Binary,
-    ) -> Result<Response, ContractError> {
+    ) -> Result<custom::Response, ContractError> {
         nonpayable(&ctx.info)?;
+        let (_, strategy_msgs) =
+            self.withdraw_strategy(ctx.deps.branch(), &ctx.env.contract.address, &owner)?;
 
         let config = self.config.load(ctx.deps.storage)?;
         let contract = ctx.deps.api.addr_validate(&contract)?;
@@ -215,16 +948,18 @@ impl VaultContract<'_> {
         let slashable = contract.max_slash(ctx.deps.as_ref())?;
 
         let tx_id = self.stake(
-            &mut ctx,
+            ctx.deps.storage,
+            &owner,
             &config,
             &contract.0,
             slashable.slash_ratio_dsign,
             amount.clone(),
             true,
         )?;
+        self.check_utilization(ctx.deps.storage, &config)?;
 
         let stake_msg = contract.receive_virtual_stake(
-            ctx.info.sender.to_string(),
+            owner.to_string(),
             amount.clone(),
             tx_id,
             msg,
@@ -233,107 +968,1441 @@ impl VaultContract<'_> {
 
         self.active_external
             .save(ctx.deps.storage, &contract.0, &())?;
+        let display = metadata::display_attributes(ctx.deps.as_ref(), &config, amount.amount);
 
         let resp = Response::new()
+            .add_messages(strategy_msgs)
             .add_message(stake_msg)
+            .add_event(Event::from(
+                StakeEvent::new(amount.clone())
+                    .delegator(&owner)
+                    .lienholder(&contract.0),
+            ))
             .add_attribute("action", "stake_remote")
-            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("sender", owner)
             .add_attribute("amount", amount.amount.to_string())
-            .add_attribute("tx_id", tx_id.to_string());
+            .add_attribute("tx_id", tx_id.to_string())
+            .add_attributes(display);
+
+        Ok(resp)
+    }
+
+    /// `stake_remote` for many accounts at once, with the same `msg`. Every account must have
+    /// granted the caller (i.e. a custodian) `stake_remote` messages.
+    ///
+    /// Failing items are skipped, and the outcome of every item is reported in a `batch_item`
+    /// event. The max collateral utilization is checked for the whole batch.
+    #[sv::msg(exec)]
+    fn stake_remote_batch(
+        &self,
+        mut ctx: ExecCtx,
+        // address of the contract to virtually stake on
+        contract: String,
+        items: Vec<BatchItem>,
+        // action to take with every stake
+        msg: Binary,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ensure!(
+            !items.is_empty() && items.len() <= MAX_BATCH_SIZE,
+            ContractError::InvalidBatchSize(MAX_BATCH_SIZE)
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+        let contract = CrossStakingApiHelper(contract);
+        let slashable = contract.max_slash(ctx.deps.as_ref())?;
+
+        let mut resp = Response::new();
+        let mut strategy_msgs = vec![];
+        let mut failed = 0u32;
+        for (index, item) in items.iter().enumerate() {
+            let amount = coin(item.amount.u128(), &config.denom);
+            let result = ctx
+                .deps
+                .api
+                .addr_validate(&item.account)
+                .map_err(ContractError::from)
+                .and_then(|account| {
+                    self.grants.check(
+                        ctx.deps.storage,
+                        &ctx.env.block,
+                        &account,
+                        &ctx.info.sender,
+                        GrantedMsgType::StakeRemote,
+                    )?;
+                    // Kept even if the stake fails, as the deposits are back to the collateral
+                    let (_, msgs) = self.withdraw_strategy(
+                        ctx.deps.branch(),
+                        &ctx.env.contract.address,
+                        &account,
+                    )?;
+                    strategy_msgs.extend(msgs);
+                    let tx_id = self.stake(
+                        ctx.deps.storage,
+                        &account,
+                        &config,
+                        &contract.0,
+                        slashable.slash_ratio_dsign,
+                        amount.clone(),
+                        true,
+                    )?;
+                    let stake_msg = contract.receive_virtual_stake(
+                        account.to_string(),
+                        amount.clone(),
+                        tx_id,
+                        msg.clone(),
+                        vec![],
+                    )?;
+                    Ok((account, tx_id, stake_msg))
+                });
+            let event = batch_item_event(index, item);
+            match result {
+                Ok((account, tx_id, stake_msg)) => {
+                    resp = resp
+                        .add_message(stake_msg)
+                        .add_event(Event::from(
+                            StakeEvent::new(amount)
+                                .delegator(&account)
+                                .lienholder(&contract.0),
+                        ))
+                        .add_event(
+                            event
+                                .add_attribute("status", "ok")
+                                .add_attribute("tx_id", tx_id.to_string()),
+                        );
+                }
+                Err(err) => {
+                    failed += 1;
+                    resp = resp.add_event(
+                        event
+                            .add_attribute("status", "failed")
+                            .add_attribute("error", err.to_string()),
+                    );
+                }
+            }
+        }
+
+        if (failed as usize) < items.len() {
+            self.check_utilization(ctx.deps.storage, &config)?;
+            self.active_external
+                .save(ctx.deps.storage, &contract.0, &())?;
+        }
+
+        let resp = resp
+            .add_messages(strategy_msgs)
+            .add_attribute("action", "stake_remote_batch")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("items", items.len().to_string())
+            .add_attribute("failed", failed.to_string());
+
+        Ok(resp)
+    }
+
+    /// This sends actual tokens to the local staking contract
+    #[sv::msg(exec)]
+    fn stake_local(
+        &self,
+        ctx: ExecCtx,
+        // amount to stake on that contract
+        amount: Coin,
+        // action to take with that stake
+        msg: Binary,
+    ) -> Result<custom::Response, ContractError> {
+        let owner = ctx.info.sender.clone();
+        self.stake_local_for(ctx, owner, amount, msg)
+    }
+
+    /// Asks the cross-staking `contract` to withdraw the rewards of `owner`, on behalf of a grantee.
+    /// The `contract` must hold a lien of `owner`, so only the cross-staking contracts the owner
+    /// staked with are called. Rewards go to the recipient `owner` set in the grant.
+    fn withdraw_rewards_for(
+        &self,
+        ctx: ExecCtx,
+        owner: Addr,
+        contract: String,
+        validator: String,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let remote_recipient =
+            self.grants
+                .reward_recipient(ctx.deps.storage, &owner, &ctx.info.sender)?;
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+        ensure!(
+            self.liens.has(ctx.deps.storage, (&owner, &contract)),
+            ContractError::UnknownLienholder
+        );
+        let contract = CrossStakingApiHelper(contract);
+        let msg = contract.withdraw_rewards_for(&owner, validator, remote_recipient.clone())?;
+
+        let resp = Response::new()
+            .add_message(msg)
+            .add_attribute("action", "withdraw_rewards")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", owner)
+            .add_attribute("lienholder", contract.0)
+            .add_attribute("recipient", remote_recipient);
+
+        Ok(resp)
+    }
+
+    /// `stake_local` on behalf of `owner`, either the sender or a granter
+    fn stake_local_for(
+        &self,
+        mut ctx: ExecCtx,
+        owner: Addr,
+        // amount to stake on that contract
+        amount: Coin,
+        // action to take with that stake
+        msg: Binary,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        // The withdrawn deposits are back before the stake is sent along
+        let (_, strategy_msgs) =
+            self.withdraw_strategy(ctx.deps.branch(), &ctx.env.contract.address, &owner)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        if let Some(local_staking) = self.local_staking.load(ctx.deps.storage)? {
+            let user = self
+                .users
+                .may_load(ctx.deps.storage, &owner)?
+                .unwrap_or_default();
+            let vesting = self.vesting_collateral(ctx.deps.storage, &owner, &user)?;
+            self.stake(
+                ctx.deps.storage,
+                &owner,
+                &config,
+                &local_staking.contract.0,
+                local_staking.max_slash,
+                amount.clone(),
+                false,
+            )?;
+            self.check_utilization(ctx.deps.storage, &config)?;
+            self.check_vesting(ctx.deps.storage, &owner, vesting)?;
+
+            let stake_msg = local_staking.contract.receive_stake(
+                owner.to_string(),
+                msg,
+                vec![amount.clone()],
+            )?;
+            let hook_msgs =
+                self.stake_change_hook_msgs(ctx.deps.storage, &owner, &local_staking.contract.0)?;
+            let display = metadata::display_attributes(ctx.deps.as_ref(), &config, amount.amount);
+
+            let resp = Response::new()
+                .add_messages(strategy_msgs)
+                .add_messages(provider::unbond_msg(&owner, amount.clone()))
+                .add_message(stake_msg)
+                .add_submessages(hook_msgs)
+                .add_event(Event::from(
+                    StakeEvent::new(amount.clone())
+                        .delegator(&owner)
+                        .lienholder(&local_staking.contract.0),
+                ))
+                .add_attribute("action", "stake_local")
+                .add_attribute("sender", owner)
+                .add_attribute("amount", amount.amount.to_string())
+                .add_attributes(display);
+
+            Ok(resp)
+        } else {
+            Err(ContractError::NoLocalStaking)
+        }
+    }
+
+    /// Opts in to auto-restaking of released claims.
+    ///
+    /// Claims released back to the vault by any lienholder other than `lienholder` are
+    /// re-staked to `lienholder` (with `msg`, i.e. the validator to stake on), up to `cap`
+    /// tokens every `period` seconds. Whatever goes over the cap becomes free collateral.
+    #[sv::msg(exec)]
+    fn set_auto_restake(
+        &self,
+        ctx: ExecCtx,
+        lienholder: String,
+        msg: Binary,
+        cap: Uint128,
+        period: u64,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        ensure!(
+            !cap.is_zero() && period > 0,
+            ContractError::InvalidAutoRestake
+        );
+
+        let lienholder = ctx.deps.api.addr_validate(&lienholder)?;
+        let restake = AutoRestake {
+            lienholder: lienholder.clone(),
+            msg,
+            cap,
+            period,
+            period_start: ctx.env.block.time,
+            restaked: Uint128::zero(),
+        };
+        self.auto_restake
+            .save(ctx.deps.storage, &ctx.info.sender, &restake)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_auto_restake")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("lienholder", lienholder)
+            .add_attribute("cap", cap.to_string())
+            .add_attribute("period", period.to_string());
+
+        Ok(resp)
+    }
+
+    /// Opts out of auto-restaking. Released claims become free collateral again.
+    #[sv::msg(exec)]
+    fn clear_auto_restake(&self, ctx: ExecCtx) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ensure!(
+            self.auto_restake.has(ctx.deps.storage, &ctx.info.sender),
+            ContractError::NoAutoRestake
+        );
+        self.auto_restake.remove(ctx.deps.storage, &ctx.info.sender);
+
+        let resp = Response::new()
+            .add_attribute("action", "clear_auto_restake")
+            .add_attribute("sender", ctx.info.sender);
+
+        Ok(resp)
+    }
+
+    /// Blocks new stakes (local or remote) to the given lienholder.
+    /// Existing stakes can still be unstaked and released.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn pause_lienholder(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let lienholder = ctx.deps.api.addr_validate(&address)?;
+        self.paused_lienholders
+            .save(ctx.deps.storage, &lienholder, &())?;
+
+        let resp = Response::new()
+            .add_attribute("action", "pause_lienholder")
+            .add_attribute("lienholder", lienholder);
+
+        Ok(resp)
+    }
+
+    /// Registers the consumer chain a lienholder belongs to, or unregisters it if no `chain_id`
+    /// is given. Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_lienholder_chain(
+        &self,
+        ctx: ExecCtx,
+        lienholder: String,
+        chain_id: Option<String>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let lienholder = ctx.deps.api.addr_validate(&lienholder)?;
+        let mut resp = Response::new()
+            .add_attribute("action", "set_lienholder_chain")
+            .add_attribute("lienholder", &lienholder);
+        match chain_id {
+            Some(chain_id) => {
+                self.lienholder_chains
+                    .save(ctx.deps.storage, &lienholder, &chain_id)?;
+                resp = resp.add_attribute("chain_id", chain_id);
+            }
+            None => self.lienholder_chains.remove(ctx.deps.storage, &lienholder),
+        }
+
+        Ok(resp)
+    }
+
+    /// Updates the smallest amounts accepted by `bond` and `unbond`.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn update_bond_limits(
+        &self,
+        ctx: ExecCtx,
+        min_bond: Uint128,
+        min_unbond: Uint128,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.min_bond = min_bond;
+        config.min_unbond = min_unbond;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "update_bond_limits")
+            .add_attribute("min_bond", min_bond.to_string())
+            .add_attribute("min_unbond", min_unbond.to_string());
+
+        Ok(resp)
+    }
+
+    /// Sets the max ratio of the total slashable collateral to the total bonded collateral,
+    /// enforced on `stake_local` and `stake_remote`. `None` removes the cap.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_max_utilization(
+        &self,
+        ctx: ExecCtx,
+        max_utilization: Option<Decimal>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        if let Some(max) = max_utilization {
+            ensure!(
+                !max.is_zero() && max <= Decimal::one(),
+                ContractError::InvalidMaxUtilization
+            );
+        }
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.max_utilization = max_utilization;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_max_utilization")
+            .add_attribute(
+                "max_utilization",
+                max_utilization.map_or_else(|| "none".to_owned(), |max| max.to_string()),
+            );
+
+        Ok(resp)
+    }
+
+    /// Sets the delay in seconds before unbonded collateral can be withdrawn. `None` sends
+    /// unbonded collateral right away. Only applies to collateral unbonded from now on.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_unbonding_period(
+        &self,
+        ctx: ExecCtx,
+        unbonding_period: Option<u64>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.unbonding_period = unbonding_period;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_unbonding_period")
+            .add_attribute(
+                "unbonding_period",
+                unbonding_period.map_or_else(|| "none".to_owned(), |period| period.to_string()),
+            );
+
+        Ok(resp)
+    }
+
+    /// Sets the max number of distinct lienholders of an account, enforced on `stake_local` and
+    /// `stake_remote`. `None` restores the default. Accounts already over it keep their liens.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_max_lienholders(
+        &self,
+        ctx: ExecCtx,
+        max_lienholders: Option<u32>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        ensure!(
+            max_lienholders != Some(0),
+            ContractError::InvalidMaxLienholders
+        );
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.max_lienholders = max_lienholders;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_max_lienholders")
+            .add_attribute("max_lienholders", config.max_lienholders().to_string());
+
+        Ok(resp)
+    }
+
+    /// Sets the yield strategy contract free collateral can be deposited into, or removes it
+    /// with `None`. It can't be changed while deposits are outstanding.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn set_strategy(
+        &self,
+        ctx: ExecCtx,
+        strategy: Option<String>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let shares = self
+            .strategy_shares
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        ensure!(shares.is_zero(), ContractError::StrategyInUse);
+
+        let strategy = strategy
+            .map(|addr| ctx.deps.api.addr_validate(&addr))
+            .transpose()?;
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.strategy = strategy.clone();
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "set_strategy")
+            .add_attribute(
+                "strategy",
+                strategy.map_or_else(|| "none".to_owned(), Addr::into_string),
+            );
+
+        Ok(resp)
+    }
+
+    /// Deposits free collateral of the sender into the yield strategy. The deposit is no longer
+    /// collateral until withdrawn, with its yield, by `withdraw_from_strategy`, or forcibly by
+    /// the next stake or unbond of the sender.
+    #[sv::msg(exec)]
+    fn deposit_to_strategy(
+        &self,
+        ctx: ExecCtx,
+        amount: Coin,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let strategy = VaultStrategyApiHelper(config.strategy.ok_or(ContractError::NoStrategy)?);
+        ensure_eq!(
+            amount.denom,
+            config.denom,
+            MeshError::InvalidDenom(config.denom)
+        );
+
+        let owner = ctx.info.sender;
+        let mut user = self
+            .users
+            .may_load(ctx.deps.storage, &owner)?
+            .unwrap_or_default();
+        let free_collateral = user.free_collateral();
+        ensure!(
+            free_collateral.low() >= amount.amount,
+            ContractError::ClaimsLocked(free_collateral)
+        );
+
+        // Shares are minted at the current value of the vault deposits, so the yield accrued
+        // so far goes to the previous depositors
+        let total_shares = self
+            .strategy_shares
+            .may_load(ctx.deps.storage)?
+            .unwrap_or_default();
+        let shares = if total_shares.is_zero() {
+            amount.amount
+        } else {
+            let total_value =
+                strategy.deposit_value(ctx.deps.as_ref(), &ctx.env.contract.address)?;
+            if total_value.is_zero() {
+                amount.amount
+            } else {
+                amount.amount.multiply_ratio(total_shares, total_value)
+            }
+        };
+        ensure!(!shares.is_zero(), ContractError::StrategyDepositTooSmall);
+
+        let vesting = self.vesting_collateral(ctx.deps.storage, &owner, &user)?;
+        user.collateral -= amount.amount;
+        self.save_user(ctx.deps.storage, &owner, &user)?;
+        self.check_vesting(ctx.deps.storage, &owner, vesting)?;
+        self.strategy_shares
+            .save(ctx.deps.storage, &(total_shares + shares))?;
+        self.strategy_deposits
+            .update(ctx.deps.storage, &owner, |deposit| -> StdResult<_> {
+                let mut deposit = deposit.unwrap_or_default();
+                deposit.shares += shares;
+                deposit.principal += amount.amount;
+                Ok(deposit)
+            })?;
+
+        let resp = Response::new()
+            .add_messages(provider::unbond_msg(&owner, amount.clone()))
+            .add_message(strategy.deposit(vec![amount.clone()])?)
+            .add_attribute("action", "deposit_to_strategy")
+            .add_attribute("sender", owner)
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("shares", shares.to_string());
+
+        Ok(resp)
+    }
+
+    /// Withdraws all the strategy deposits of the sender, with their yield, back to its free
+    /// collateral
+    #[sv::msg(exec)]
+    fn withdraw_from_strategy(&self, ctx: ExecCtx) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let owner = ctx.info.sender;
+        ensure!(
+            self.strategy_deposits.has(ctx.deps.storage, &owner),
+            ContractError::NoStrategyDeposit
+        );
+        let (value, msgs) = self.withdraw_strategy(ctx.deps, &ctx.env.contract.address, &owner)?;
+
+        let resp = Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "withdraw_from_strategy")
+            .add_attribute("sender", owner)
+            .add_attribute("amount", value.to_string());
+
+        Ok(resp)
+    }
+
+    /// Withdraws the sender unbonding claims that are due, at most `limit` of them.
+    /// The `remaining` attribute is set if more claims are due.
+    #[sv::msg(exec)]
+    fn claim_matured(
+        &self,
+        ctx: ExecCtx,
+        limit: Option<u32>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let limit = clamp_page_limit(limit);
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let owner = ctx.info.sender;
+        let now = ctx.env.block.time.nanos();
+
+        let mut due = self
+            .unbonding_claims
+            .prefix(&owner)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .take_while(|item| {
+                item.as_ref()
+                    .map_or(true, |(release_at, _)| *release_at <= now)
+            })
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+        let remaining = due.len() > limit;
+        due.truncate(limit);
+        ensure!(!due.is_empty(), ContractError::NoClaim);
+
+        let mut claimed = Uint128::zero();
+        for (release_at, amount) in due {
+            self.unbonding_claims
+                .remove(ctx.deps.storage, (&owner, release_at));
+            claimed += amount;
+        }
+
+        let mut resp = Response::new()
+            .add_message(BankMsg::Send {
+                to_address: owner.to_string(),
+                amount: vec![coin(claimed.u128(), denom)],
+            })
+            .add_attribute("action", "claim_matured")
+            .add_attribute("sender", &owner)
+            .add_attribute("amount", claimed.to_string());
+        if remaining {
+            resp = resp.add_attribute("remaining", "true");
+        }
+        Ok(resp)
+    }
+
+    /// Withdraws all the local staking rewards of the sender, including the rewards of the
+    /// delegations of its staking proxies. The local staking contract sends them to the sender
+    #[sv::msg(exec)]
+    fn withdraw_local_rewards(&self, ctx: ExecCtx) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let local_staking = self
+            .local_staking
+            .load(ctx.deps.storage)?
+            .ok_or(ContractError::NoLocalStaking)?;
+        let msg = local_staking
+            .contract
+            .withdraw_rewards_for(&ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_message(msg)
+            .add_attribute("action", "withdraw_local_rewards")
+            .add_attribute("sender", ctx.info.sender);
+        Ok(resp)
+    }
+
+    /// Allows new stakes to a previously paused lienholder again.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn unpause_lienholder(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let lienholder = ctx.deps.api.addr_validate(&address)?;
+        self.paused_lienholders
+            .remove(ctx.deps.storage, &lienholder);
+
+        let resp = Response::new()
+            .add_attribute("action", "unpause_lienholder")
+            .add_attribute("lienholder", lienholder);
+
+        Ok(resp)
+    }
+
+    /// Registers a hook contract, notified of bonds, unbonds and stake changes with
+    /// `VaultHookMsg`. Hook failures don't revert the vault txs.
+    /// Only the owner can call this.
+    #[sv::msg(exec)]
+    fn add_hook(&self, ctx: ExecCtx, address: String) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let hook = ctx.deps.api.addr_validate(&address)?;
+        ensure!(
+            !self.hooks.has(ctx.deps.storage, &hook),
+            ContractError::HookAlreadyRegistered(hook)
+        );
+        let count = self
+            .hooks
+            .keys(ctx.deps.storage, None, None, Order::Ascending)
+            .count();
+        ensure!(count < MAX_HOOKS, ContractError::TooManyHooks(MAX_HOOKS));
+        self.hooks.save(ctx.deps.storage, &hook, &())?;
+
+        let resp = Response::new()
+            .add_attribute("action", "add_hook")
+            .add_attribute("hook", hook);
+
+        Ok(resp)
+    }
+
+    /// Unregisters a hook contract. Only the owner can call this.
+    #[sv::msg(exec)]
+    fn remove_hook(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        ownership_api::assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let hook = ctx.deps.api.addr_validate(&address)?;
+        ensure!(
+            self.hooks.has(ctx.deps.storage, &hook),
+            ContractError::HookNotRegistered(hook)
+        );
+        self.hooks.remove(ctx.deps.storage, &hook);
+
+        let resp = Response::new()
+            .add_attribute("action", "remove_hook")
+            .add_attribute("hook", hook);
+
+        Ok(resp)
+    }
+
+    /// Finalizes the delayed cross stake releases that are due, at most `limit` of them.
+    /// Permissionless, the `remaining` attribute is set if more releases are due.
+    ///
+    /// Claims slashed in the meantime are only released for what is left of them.
+    /// Released claims are not auto-restaked.
+    #[sv::msg(exec)]
+    fn finalize_releases(
+        &self,
+        ctx: ExecCtx,
+        limit: Option<u32>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let limit = clamp_page_limit(limit);
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let now = ctx.env.block.time.nanos();
+
+        let mut due = self
+            .pending_releases
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .take_while(|item| {
+                item.as_ref()
+                    .map_or(true, |((release_at, _, _), _)| *release_at <= now)
+            })
+            .take(limit + 1)
+            .collect::<StdResult<Vec<_>>>()?;
+        let remaining = due.len() > limit;
+        due.truncate(limit);
+
+        let mut resp = Response::new();
+        for ((release_at, owner, lienholder), amount) in due {
+            self.pending_releases
+                .remove(ctx.deps.storage, (release_at, &owner, &lienholder));
+            self.incoming_collateral.update(
+                ctx.deps.storage,
+                &owner,
+                |incoming| -> StdResult<_> { Ok(incoming.unwrap_or_default() - amount) },
+            )?;
+
+            let lien = self
+                .liens
+                .may_load(ctx.deps.storage, (&owner, &lienholder))?;
+            let released = min(
+                amount,
+                lien.map_or(Uint128::zero(), |lien| lien.amount.low()),
+            );
+            if released.is_zero() {
+                continue;
+            }
+            let released = coin(released.u128(), &denom);
+            self.unstake(ctx.deps.storage, &lienholder, &owner, released.clone())?;
+            let hook_msgs = self.stake_change_hook_msgs(ctx.deps.storage, &owner, &lienholder)?;
+            resp = resp.add_submessages(hook_msgs).add_event(Event::from(
+                UnstakeEvent::new(released)
+                    .delegator(&owner)
+                    .lienholder(&lienholder),
+            ));
+        }
+
+        resp = resp.add_attribute("action", "finalize_releases");
+        if remaining {
+            resp = resp.add_attribute("remaining", "true");
+        }
+        Ok(resp)
+    }
+
+    /// Allows `grantee` to execute `msg_type` messages on behalf of the sender, until `expiration`.
+    /// Replaces any previous grant of the same type.
+    /// `withdraw_rewards` grants take the consumer-side `remote_recipient` of the rewards.
+    #[sv::msg(exec)]
+    fn grant(
+        &self,
+        ctx: ExecCtx,
+        grantee: String,
+        msg_type: GrantedMsgType,
+        expiration: Expiration,
+        remote_recipient: Option<String>,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let grantee = ctx.deps.api.addr_validate(&grantee)?;
+        self.grants.grant(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            &grantee,
+            msg_type,
+            expiration,
+            remote_recipient,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "grant")
+            .add_attribute("granter", ctx.info.sender)
+            .add_attribute("grantee", grantee)
+            .add_attribute("msg_type", msg_type.as_str())
+            .add_attribute("expiration", expiration.to_string());
+
+        Ok(resp)
+    }
+
+    /// Revokes a grant previously given to `grantee`
+    #[sv::msg(exec)]
+    fn revoke(
+        &self,
+        ctx: ExecCtx,
+        grantee: String,
+        msg_type: GrantedMsgType,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let grantee = ctx.deps.api.addr_validate(&grantee)?;
+        self.grants
+            .revoke(ctx.deps.storage, &ctx.info.sender, &grantee, msg_type)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "revoke")
+            .add_attribute("granter", ctx.info.sender)
+            .add_attribute("grantee", grantee)
+            .add_attribute("msg_type", msg_type.as_str());
+
+        Ok(resp)
+    }
+
+    /// Invalidates a permit signed by the sender but not submitted yet
+    #[sv::msg(exec)]
+    fn invalidate_permit(
+        &self,
+        ctx: ExecCtx,
+        nonce: u64,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.permits
+            .use_nonce(ctx.deps.storage, &ctx.info.sender, nonce)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "invalidate_permit")
+            .add_attribute("owner", ctx.info.sender)
+            .add_attribute("nonce", nonce.to_string());
+        Ok(resp)
+    }
+
+    /// `stake_remote` on behalf of the owner of `permit`, who signed it off-chain (see
+    /// `permits::permit_hash`) with the secp256k1 key of its account, `pubkey` (compressed).
+    /// Anyone can submit it, e.g. a relayer paying the fees
+    #[sv::msg(exec)]
+    fn stake_remote_permit(
+        &self,
+        ctx: ExecCtx,
+        permit: StakeRemotePermit,
+        pubkey: Binary,
+        signature: Binary,
+    ) -> Result<custom::Response, ContractError> {
+        let owner = self.permits.verify(
+            ctx.deps.api,
+            ctx.deps.storage,
+            &ctx.env,
+            &permit,
+            &pubkey,
+            &signature,
+        )?;
+
+        let relayer = ctx.info.sender.clone();
+        let StakeRemotePermit {
+            lienholder,
+            amount,
+            msg,
+            nonce,
+            ..
+        } = permit;
+        let resp = self.stake_remote_for(ctx, owner, lienholder, amount, msg)?;
+        Ok(resp
+            .add_attribute("relayer", relayer)
+            .add_attribute("nonce", nonce.to_string()))
+    }
+
+    /// Executes `msg` on behalf of `granter`. The sender needs a valid grant for the message type.
+    /// Funds sent along (i.e. receipt tokens) are handled as if sent by the granter.
+    #[sv::msg(exec)]
+    fn exec_granted(
+        &self,
+        ctx: ExecCtx,
+        granter: String,
+        msg: GrantedMsg,
+    ) -> Result<custom::Response, ContractError> {
+        let granter = ctx.deps.api.addr_validate(&granter)?;
+        self.grants.check(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &granter,
+            &ctx.info.sender,
+            msg.msg_type(),
+        )?;
+
+        let grantee = ctx.info.sender.clone();
+        let resp = match msg {
+            GrantedMsg::Bond {} => self.bond_for(ctx, granter)?,
+            GrantedMsg::StakeRemote {
+                contract,
+                amount,
+                msg,
+            } => self.stake_remote_for(ctx, granter, contract, amount, msg)?,
+            GrantedMsg::StakeLocal { amount, msg } => {
+                self.stake_local_for(ctx, granter, amount, msg)?
+            }
+            GrantedMsg::WithdrawRewards {
+                contract,
+                validator,
+            } => self.withdraw_rewards_for(ctx, granter, contract, validator)?,
+        };
+
+        Ok(resp.add_attribute("grantee", grantee))
+    }
+
+    /// This is only used for tests.
+    /// Overwrites the cached `max_lien` of `account`, to get it out of sync with its liens
+    #[sv::msg(exec)]
+    fn test_set_max_lien(
+        &self,
+        ctx: ExecCtx,
+        account: String,
+        max_lien: ValueRange<Uint128>,
+    ) -> Result<custom::Response, ContractError> {
+        #[cfg(any(test, feature = "mt"))]
+        {
+            let account = ctx.deps.api.addr_validate(&account)?;
+            let mut user = self.users.load(ctx.deps.storage, &account)?;
+            user.max_lien = max_lien;
+            self.users.save(ctx.deps.storage, &account, &user)?;
+            Ok(Response::new())
+        }
+        #[cfg(not(any(test, feature = "mt")))]
+        {
+            let _ = (ctx, account, max_lien);
+            Err(MeshError::Unauthorized.into())
+        }
+    }
+
+    #[sv::msg(query)]
+    fn account(&self, ctx: QueryCtx, account: String) -> Result<AccountResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let account = ctx.deps.api.addr_validate(&account)?;
+
+        let user = self
+            .users
+            .may_load(ctx.deps.storage, &account)?
+            .unwrap_or_default();
+        Ok(AccountResponse {
+            denom,
+            bonded: user.collateral,
+            free: user.free_collateral(),
+        })
+    }
+
+    /// Full account summary, including all its liens and pending claims.
+    /// Not paginated, as the number of lienholders per account is small.
+    #[sv::msg(query)]
+    fn account_details(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+    ) -> Result<AccountDetailsResponse, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        let account = ctx.deps.api.addr_validate(&account)?;
+
+        let user = self
+            .users
+            .may_load(ctx.deps.storage, &account)?
+            .unwrap_or_default();
+
+        let local_staking = self
+            .local_staking
+            .may_load(ctx.deps.storage)?
+            .flatten()
+            .map(|local_staking| local_staking.contract.0);
+        let liens = self
+            .liens
+            .prefix(&account)
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (lienholder, lien) = item?;
+                Ok::<_, ContractError>(LienDetails {
+                    local: local_staking.as_ref() == Some(&lienholder),
+                    lienholder: lienholder.into_string(),
+                    amount: lien.amount,
+                    slashable: lien.slashable,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let pending_claims = self
+            .pending
+            .txs_by_user(ctx.deps.storage, &account)?
+            .into_iter()
+            .filter_map(|tx| match tx {
+                InFlightStaking {
+                    id,
+                    amount,
+                    lienholder,
+                    ..
+                } => Some(PendingClaim {
+                    tx_id: id,
+                    lienholder: lienholder.into_string(),
+                    amount,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Ok(AccountDetailsResponse {
+            denom,
+            bonded: user.collateral,
+            free: user.free_collateral(),
+            max_lien: user.max_lien,
+            total_slashable: user.total_slashable,
+            liens,
+            pending_claims,
+            incoming: self
+                .incoming_collateral
+                .may_load(ctx.deps.storage, &account)?
+                .unwrap_or_default(),
+        })
+    }
+
+    #[sv::msg(query)]
+    fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let local_staking = self.local_staking.load(ctx.deps.storage)?;
+        let max_lienholders = config.max_lienholders();
+
+        let resp = ConfigResponse {
+            denom: config.denom,
+            local_staking: local_staking.map(|ls| ls.contract.0.into()),
+            receipt_denom: config.receipt_denom,
+            min_bond: config.min_bond,
+            min_unbond: config.min_unbond,
+            max_utilization: config.max_utilization,
+            unbonding_period: config.unbonding_period,
+            max_lienholders,
+            strategy: config.strategy.map(Addr::into),
+        };
+
+        Ok(resp)
+    }
+
+    /// Rewards of `user` not withdrawn yet, from the local staking contract and from every
+    /// cross-staking contract it stakes with.
+    /// The local rewards don't include the ones of the staking proxies delegations, which are
+    /// kept by the distribution module until withdrawn.
+    #[sv::msg(query)]
+    fn pending_all_rewards(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+    ) -> Result<PendingAllRewardsResponse, ContractError> {
+        let user = ctx.deps.api.addr_validate(&user)?;
+        let mut totals: BTreeMap<String, Uint128> = BTreeMap::new();
+
+        let local_staking = self
+            .local_staking
+            .may_load(ctx.deps.storage)?
+            .flatten()
+            .map(|local_staking| local_staking.contract);
+        let local = local_staking
+            .as_ref()
+            .map(|contract| contract.pending_rewards(ctx.deps, user.to_string()))
+            .transpose()?
+            .map(|resp| resp.rewards);
+        if let Some(rewards) = &local {
+            *totals.entry(rewards.denom.clone()).or_default() += rewards.amount;
+        }
+
+        let mut cross = vec![];
+        for lienholder in
+            self.liens
+                .prefix(&user)
+                .keys(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let lienholder = lienholder?;
+            if local_staking.as_ref().map(|contract| &contract.0) == Some(&lienholder) {
+                continue;
+            }
+            let rewards = CrossStakingApiHelper(lienholder.clone())
+                .total_pending_rewards(ctx.deps, user.to_string())?
+                .rewards;
+            if rewards.is_empty() {
+                continue;
+            }
+            for reward in &rewards {
+                *totals.entry(reward.denom.clone()).or_default() += reward.amount;
+            }
+            cross.push(LienholderRewards {
+                lienholder: lienholder.into_string(),
+                rewards,
+            });
+        }
+
+        let total = totals
+            .into_iter()
+            .filter(|(_, amount)| !amount.is_zero())
+            .map(|(denom, amount)| coin(amount.u128(), denom))
+            .collect();
+        Ok(PendingAllRewardsResponse {
+            local,
+            cross,
+            total,
+        })
+    }
+
+    /// Display metadata of the collateral denom, as used for the `display_amount` event
+    /// attributes
+    #[sv::msg(query)]
+    fn denom_metadata(&self, ctx: QueryCtx) -> Result<DenomMetadataResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        Ok(metadata::denom_metadata(ctx.deps, &config))
+    }
+
+    /// Protocol-wide totals, maintained incrementally so they don't need any iteration over
+    /// the accounts
+    #[sv::msg(query)]
+    fn vault_stats(&self, ctx: QueryCtx) -> Result<VaultStatsResponse, ContractError> {
+        let stats = self.stats.may_load(ctx.deps.storage)?.unwrap_or_default();
+        let local_staking = self
+            .local_staking
+            .load(ctx.deps.storage)?
+            .map(|local_staking| local_staking.contract.0);
+
+        let mut total_local_staked = Uint128::zero();
+        let mut cross_staked = vec![];
+        for item in self
+            .lienholder_totals
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (lienholder, amount) = item?;
+            if local_staking.as_ref() == Some(&lienholder) {
+                total_local_staked = amount;
+            } else {
+                cross_staked.push(LienholderStake {
+                    lienholder: lienholder.into_string(),
+                    amount,
+                });
+            }
+        }
+
+        Ok(VaultStatsResponse {
+            total_collateral: stats.total_collateral,
+            total_local_staked,
+            cross_staked,
+            accounts: stats.accounts,
+            total_slashable: stats.total_slashable,
+        })
+    }
+
+    /// Recomputes the cached values (`max_lien`, `total_slashable`, vault stats, lienholder
+    /// totals, incoming collateral) from the raw data, and returns any discrepancy with the
+    /// stored ones. Checks only `account` if set, all the accounts and the protocol-wide values
+    /// otherwise, which may not fit in the query gas limit of a large deployment
+    #[sv::msg(query)]
+    fn check_invariants(
+        &self,
+        ctx: QueryCtx,
+        account: Option<String>,
+    ) -> Result<InvariantsResponse, ContractError> {
+        let account = account
+            .map(|account| ctx.deps.api.addr_validate(&account))
+            .transpose()?;
+        let (accounts, discrepancies) =
+            invariants::check_invariants(self, ctx.deps.storage, account.as_ref())?;
+        Ok(InvariantsResponse {
+            accounts,
+            discrepancies,
+        })
+    }
+
+    /// Grants given by `granter` to `grantee`, including expired ones
+    #[sv::msg(query)]
+    fn grants(
+        &self,
+        ctx: QueryCtx,
+        granter: String,
+        grantee: String,
+    ) -> Result<GrantsResponse, ContractError> {
+        let granter = ctx.deps.api.addr_validate(&granter)?;
+        let grantee = ctx.deps.api.addr_validate(&grantee)?;
+        let grants = self
+            .grants
+            .grants_by_pair(ctx.deps.storage, &granter, &grantee)?;
+        Ok(GrantsResponse { grants })
+    }
+
+    /// Whether the nonce was already used by a permit of `owner`, or invalidated
+    #[sv::msg(query)]
+    fn permit_nonce(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        nonce: u64,
+    ) -> Result<PermitNonceResponse, ContractError> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let used = self.permits.nonces.has(ctx.deps.storage, (&owner, nonce));
+        Ok(PermitNonceResponse { used })
+    }
+
+    #[sv::msg(query)]
+    fn paused_lienholders(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<PausedLienholdersResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let start_after = start_after.map(Addr::unchecked);
+        let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
+
+        let lienholders = self
+            .paused_lienholders
+            .keys(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|addr| addr.map(Addr::into_string))
+            .take(limit)
+            .collect::<StdResult<_>>()?;
 
-        Ok(resp)
+        Ok(PausedLienholdersResponse { lienholders })
     }
 
-    /// This sends actual tokens to the local staking contract
-    #[sv::msg(exec)]
-    fn stake_local(
+    /// Whether `account` could stake `amount` on `lienholder` (the local staking contract or a
+    /// cross-staking one) right now, with the same checks as the stake execs, and its free
+    /// collateral after the stake
+    #[sv::msg(query)]
+    fn simulate_stake(
         &self,
-        mut ctx: ExecCtx,
-        // amount to stake on that contract
+        ctx: QueryCtx,
+        account: String,
+        lienholder: String,
         amount: Coin,
-        // action to take with that stake
-        msg: Binary,
-    ) -> Result<Response, ContractError> {
-        nonpayable(&ctx.info)?;
-
+    ) -> Result<SimulationResponse, ContractError> {
         let config = self.config.load(ctx.deps.storage)?;
-        if let Some(local_staking) = self.local_staking.load(ctx.deps.storage)? {
-            self.stake(
-                &mut ctx,
-                &config,
-                &local_staking.contract.0,
-                local_staking.max_slash,
-                amount.clone(),
-                false,
-            )?;
-
-            let stake_msg = local_staking.contract.receive_stake(
-                ctx.info.sender.to_string(),
-                msg,
-                vec![amount.clone()],
-            )?;
-
-            let resp = Response::new()
-                .add_message(stake_msg)
-                .add_attribute("action", "stake_local")
-                .add_attribute("sender", ctx.info.sender)
-                .add_attribute("amount", amount.amount.to_string());
+        let account = ctx.deps.api.addr_validate(&account)?;
+        let lienholder = ctx.deps.api.addr_validate(&lienholder)?;
 
-            Ok(resp)
-        } else {
-            Err(ContractError::NoLocalStaking)
-        }
+        let user = self
+            .users
+            .may_load(ctx.deps.storage, &account)?
+            .unwrap_or_default();
+        let result = self.simulated_stake(ctx.deps, &account, &config, &lienholder, amount);
+        Ok(simulation_response(result, &user))
     }
 
+    /// Whether `account` could unbond `amount` right now, with the same checks as `unbond`, and
+    /// its free collateral after the unbond. The receipt tokens to return, if enabled, are not
+    /// checked
     #[sv::msg(query)]
-    fn account(&self, ctx: QueryCtx, account: String) -> Result<AccountResponse, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+    fn simulate_unbond(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+        amount: Coin,
+    ) -> Result<SimulationResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
         let account = ctx.deps.api.addr_validate(&account)?;
 
         let user = self
             .users
             .may_load(ctx.deps.storage, &account)?
             .unwrap_or_default();
-        Ok(AccountResponse {
-            denom,
-            bonded: user.collateral,
-            free: user.free_collateral(),
+        let result = check_unbond(&config, &user, &amount).map(|_| {
+            let mut user = user.clone();
+            user.collateral -= amount.amount;
+            user
+        });
+        Ok(simulation_response(result, &user))
+    }
+
+    /// Share of the bonded collateral that is slashable, over all the accounts
+    #[sv::msg(query)]
+    fn utilization(&self, ctx: QueryCtx) -> Result<UtilizationResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let stats = self.stats.may_load(ctx.deps.storage)?.unwrap_or_default();
+
+        Ok(UtilizationResponse {
+            total_collateral: stats.total_collateral,
+            total_slashable: stats.total_slashable,
+            utilization: stats.utilization(),
+            max_utilization: config.max_utilization,
         })
     }
 
+    /// All the registered hook contracts
     #[sv::msg(query)]
-    fn account_details(
+    fn hooks(&self, ctx: QueryCtx) -> Result<HooksResponse, ContractError> {
+        let hooks = self
+            .hooks
+            .keys(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|addr| addr.map(Addr::into_string))
+            .collect::<StdResult<_>>()?;
+
+        Ok(HooksResponse { hooks })
+    }
+
+    /// Strategy deposits of `account`, with their current value
+    #[sv::msg(query)]
+    fn strategy_deposit(
         &self,
         ctx: QueryCtx,
         account: String,
-    ) -> Result<AccountDetailsResponse, ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
+    ) -> Result<StrategyDepositResponse, ContractError> {
         let account = ctx.deps.api.addr_validate(&account)?;
-
-        let user = self
-            .users
+        let deposit = self
+            .strategy_deposits
             .may_load(ctx.deps.storage, &account)?
             .unwrap_or_default();
-        Ok(AccountDetailsResponse {
-            denom,
-            bonded: user.collateral,
-            free: user.free_collateral(),
-            max_lien: user.max_lien,
-            total_slashable: user.total_slashable,
+
+        let value = match self.config.load(ctx.deps.storage)?.strategy {
+            Some(strategy) if !deposit.shares.is_zero() => {
+                let total_shares = self.strategy_shares.load(ctx.deps.storage)?;
+                VaultStrategyApiHelper(strategy)
+                    .deposit_value(ctx.deps, &ctx.env.contract.address)?
+                    .multiply_ratio(deposit.shares, total_shares)
+            }
+            _ => Uint128::zero(),
+        };
+
+        Ok(StrategyDepositResponse {
+            shares: deposit.shares,
+            principal: deposit.principal,
+            value,
         })
     }
 
+    /// Liens and max slashable collateral aggregated per consumer chain, over all the lienholders
+    /// registered with `set_lienholder_chain`. Chains are ordered by id.
+    ///
+    /// `start_after` is the last chain id of the previous page, and it will not be included
     #[sv::msg(query)]
-    fn config(&self, ctx: QueryCtx) -> Result<ConfigResponse, ContractError> {
-        let config = self.config.load(ctx.deps.storage)?;
-        let local_staking = self.local_staking.load(ctx.deps.storage)?;
+    fn exposure_by_chain(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<ExposureByChainResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
 
-        let resp = ConfigResponse {
-            denom: config.denom,
-            local_staking: local_staking.map(|ls| ls.contract.0.into()),
-        };
+        let mut lienholders_by_chain = BTreeMap::<String, Vec<Addr>>::new();
+        for item in self
+            .lienholder_chains
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+        {
+            let (lienholder, chain_id) = item?;
+            lienholders_by_chain
+                .entry(chain_id)
+                .or_default()
+                .push(lienholder);
+        }
 
-        Ok(resp)
+        let chains = lienholders_by_chain
+            .into_iter()
+            .filter(|(chain_id, _)| start_after.as_ref().map_or(true, |start| chain_id > start))
+            .take(limit)
+            .map(|(chain_id, lienholders)| {
+                let mut liens = Uint128::zero();
+                let mut max_slash = Uint128::zero();
+                for lienholder in &lienholders {
+                    for item in self.liens.idx.lienholder.prefix(lienholder.clone()).range(
+                        ctx.deps.storage,
+                        None,
+                        None,
+                        Order::Ascending,
+                    ) {
+                        let (_, lien) = item?;
+                        liens += lien.amount.high();
+                        max_slash += lien.amount.high() * lien.slashable;
+                    }
+                }
+                Ok(ChainExposure {
+                    chain_id,
+                    lienholders: lienholders.into_iter().map(Addr::into_string).collect(),
+                    liens,
+                    max_slash,
+                })
+            })
+            .collect::<Result<_, ContractError>>()?;
+
+        Ok(ExposureByChainResponse { chains })
     }
 
     #[sv::msg(query)]
@@ -369,6 +2438,30 @@ impl VaultContract<'_> {
             .ok_or(ContractError::NoClaim)
     }
 
+    /// Returns the auto-restaking preferences of an account
+    #[sv::msg(query)]
+    fn auto_restake(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+    ) -> Result<AutoRestakeResponse, ContractError> {
+        let account = ctx.deps.api.addr_validate(&account)?;
+
+        let restake = self
+            .auto_restake
+            .may_load(ctx.deps.storage, &account)?
+            .ok_or(ContractError::NoAutoRestake)?;
+
+        Ok(AutoRestakeResponse {
+            lienholder: restake.lienholder.to_string(),
+            msg: restake.msg,
+            cap: restake.cap,
+            period: restake.period,
+            period_start: restake.period_start,
+            restaked: restake.restaked,
+        })
+    }
+
     /// Returns paginated claims list for an user
     ///
     /// `start_after` is a last lienholder of the previous page, and it will not be included
@@ -404,6 +2497,202 @@ impl VaultContract<'_> {
         Ok(resp)
     }
 
+    /// Account unbonding claims, by release time. Matured claims are withdrawn with
+    /// `claim_matured`.
+    ///
+    /// `start_after` is the release time (in nanoseconds) of the last claim of the previous page,
+    /// and it will not be included
+    #[sv::msg(query)]
+    fn claims(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> Result<ClaimsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let bound = start_after.map(Bound::exclusive);
+
+        let account = Addr::unchecked(account);
+        let claims = self
+            .unbonding_claims
+            .prefix(&account)
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .map(|item| {
+                let (release_at, amount) = item?;
+                Ok::<_, ContractError>(UnbondingClaim {
+                    amount,
+                    release_at: Timestamp::from_nanos(release_at),
+                })
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        let resp = ClaimsResponse { claims };
+
+        Ok(resp)
+    }
+
+    /// Account liens, ordered by lienholder or by amount (descending), and optionally
+    /// filtered to the local or remote lienholders.
+    ///
+    /// `start_after` is a last lienholder of the previous page, and it will not be included
+    #[sv::msg(query)]
+    fn account_liens(
+        &self,
+        ctx: QueryCtx,
+        account: String,
+        #[serde(default)] order: LienOrder,
+        kind: Option<LienholderKind>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<AccountLiensResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let account = Addr::unchecked(account);
+        let start_after = start_after.map(Addr::unchecked);
+
+        let local_staking = self
+            .local_staking
+            .may_load(ctx.deps.storage)?
+            .flatten()
+            .map(|local_staking| local_staking.contract.0);
+
+        let items: Box<dyn Iterator<Item = StdResult<(Addr, Lien)>> + '_> = match order {
+            LienOrder::Lienholder => {
+                let bound = start_after.as_ref().and_then(Bounder::exclusive_bound);
+                self.liens
+                    .prefix(&account)
+                    .range(ctx.deps.storage, bound, None, Order::Ascending)
+            }
+            LienOrder::AmountDesc => {
+                let bound = match start_after {
+                    Some(lienholder) => {
+                        let lien = self
+                            .liens
+                            .may_load(ctx.deps.storage, (&account, &lienholder))?
+                            .ok_or(ContractError::UnknownLienholder)?;
+                        let amount = lien.amount.high().u128();
+                        Some(Bound::exclusive((amount, (account.clone(), lienholder))))
+                    }
+                    None => None,
+                };
+                Box::new(
+                    self.liens
+                        .idx
+                        .amount
+                        .sub_prefix(account)
+                        .range(ctx.deps.storage, None, bound, Order::Descending)
+                        .map(|item| item.map(|((_, lienholder), lien)| (lienholder, lien))),
+                )
+            }
+        };
+
+        let liens = items
+            .filter(|item| {
+                let Ok((lienholder, _)) = item else {
+                    return true;
+                };
+                let local = local_staking.as_ref() == Some(lienholder);
+                match kind {
+                    None => true,
+                    Some(LienholderKind::Local) => local,
+                    Some(LienholderKind::Remote) => !local,
+                }
+            })
+            .map(|item| {
+                let (lienholder, lien) = item?;
+                Ok::<_, ContractError>(LienDetails {
+                    local: local_staking.as_ref() == Some(&lienholder),
+                    lienholder: lienholder.into_string(),
+                    amount: lien.amount,
+                    slashable: lien.slashable,
+                })
+            })
+            .take(limit)
+            .collect::<Result<_, _>>()?;
+
+        Ok(AccountLiensResponse { liens })
+    }
+
+    /// Users with a lien on `lienholder`, ordered by user, through the lienholder index.
+    ///
+    /// `start_after` is the last user of the previous page, and it will not be included
+    #[sv::msg(query)]
+    fn users_by_lienholder(
+        &self,
+        ctx: QueryCtx,
+        lienholder: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<UsersByLienholderResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let lienholder = Addr::unchecked(lienholder);
+        let bound =
+            start_after.map(|user| Bound::exclusive((Addr::unchecked(user), lienholder.clone())));
+
+        let users = self
+            .liens
+            .idx
+            .lienholder
+            .prefix(lienholder.clone())
+            .range(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let ((user, _), lien) = item?;
+                Ok(LienholderUser {
+                    user: user.into_string(),
+                    amount: lien.amount,
+                    slashable: lien.slashable,
+                })
+            })
+            .collect::<StdResult<_>>()?;
+
+        Ok(UsersByLienholderResponse { users })
+    }
+
+    /// As `all_accounts`, but only the accounts with a lien on `lienholder`, ordered by user,
+    /// through the lienholder index.
+    ///
+    /// `start_after` is the last account included in previous page
+    #[sv::msg(query)]
+    fn accounts_by_lienholder(
+        &self,
+        ctx: QueryCtx,
+        lienholder: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> Result<AllAccountsResponse, ContractError> {
+        let limit = clamp_page_limit(limit);
+        let lienholder = Addr::unchecked(lienholder);
+        let bound =
+            start_after.map(|user| Bound::exclusive((Addr::unchecked(user), lienholder.clone())));
+
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+
+        let accounts = self
+            .liens
+            .idx
+            .lienholder
+            .prefix(lienholder.clone())
+            .keys(ctx.deps.storage, bound, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                let (user, _) = item?;
+                let account = self.users.load(ctx.deps.storage, &user)?;
+                Ok(AllAccountsResponseItem {
+                    user: user.into_string(),
+                    account: AccountResponse {
+                        denom: denom.clone(),
+                        bonded: account.collateral,
+                        free: account.free_collateral(),
+                    },
+                })
+            })
+            .collect::<StdResult<_>>()?;
+
+        Ok(AllAccountsResponse { accounts })
+    }
+
     /// Queries for all users ever performing action in the system, paginating over
     /// them.
     ///
@@ -490,18 +2779,24 @@ impl VaultContract<'_> {
     }
 
     #[sv::msg(reply)]
-    fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
+    fn reply(&self, ctx: ReplyCtx, reply: Reply) -> Result<custom::Response, ContractError> {
         match reply.id {
             REPLY_ID_INSTANTIATE => self.reply_init_callback(ctx.deps, reply.result.unwrap()),
-            _ => Err(ContractError::InvalidReplyId(reply.id)),
+            REPLY_ID_HOOK => Ok(self.reply_hook_failed(reply.result.unwrap_err())),
+            _ => Err(MeshError::InvalidReplyId(reply.id).into()),
         }
     }
 
+    /// Hook calls only reply on error. The error is reported, and otherwise ignored
+    fn reply_hook_failed(&self, error: String) -> custom::Response {
+        Response::new().add_event(Event::new("hook_failed").add_attribute("error", error))
+    }
+
     fn reply_init_callback(
         &self,
         deps: DepsMut,
         reply: SubMsgResponse,
-    ) -> Result<Response, ContractError> {
+    ) -> Result<custom::Response, ContractError> {
         let init_data = parse_instantiate_response_data(&reply.data.unwrap())?;
         let local_staking = Addr::unchecked(init_data.contract_address);
 
@@ -516,96 +2811,181 @@ impl VaultContract<'_> {
             contract: LocalStakingApiHelper(local_staking),
             max_slash: slash_ratio_dsign,
         };
-
-        self.local_staking
-            .save(deps.storage, &Some(local_staking))?;
-
-        Ok(Response::new())
+
+        self.local_staking
+            .save(deps.storage, &Some(local_staking))?;
+
+        Ok(Response::new())
+    }
+
+    /// Updates the local stake for staking on any contract
+    ///
+    /// Stake (both local and remote) is usually called by the tokens owner, so the `sender` is
+    /// passed as the `owner` address. Auto-restaking is the exception, as it happens on
+    /// behalf of the owner when a lienholder releases a claim.
+    ///
+    /// Config is taken in argument as it sometimes is used outside of this function, so
+    /// we want to avoid double-fetching it
+    ///
+    /// Remote indicates if the stake is remote or local. Remote staking involves transaction
+    /// processing.
+    #[allow(clippy::too_many_arguments)]
+    /// Fails if the vault-wide utilization is over the configured cap. To be called after a new
+    /// stake is accounted for
+    fn check_utilization(
+        &self,
+        storage: &dyn Storage,
+        config: &Config,
+    ) -> Result<(), ContractError> {
+        let stats = self.stats.may_load(storage)?.unwrap_or_default();
+        check_stats_utilization(&stats, config)
+    }
+
+    fn stake(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        config: &Config,
+        lienholder: &Addr,
+        slashable: Decimal,
+        amount: Coin,
+        remote: bool,
+    ) -> Result<u64, ContractError> {
+        let (lien, user) = self.staked(
+            storage,
+            owner,
+            config,
+            lienholder,
+            slashable,
+            amount.clone(),
+            remote,
+        )?;
+        let amount = amount.amount;
+
+        self.save_lien(storage, (owner, lienholder), &lien)?;
+        self.save_user(storage, owner, &user)?;
+        let tx_id = if remote {
+            // Create new tx
+            let tx_id = self.next_tx_id(storage)?;
+
+            let new_tx = InFlightStaking {
+                id: tx_id,
+                amount,
+                slashable,
+                user: owner.clone(),
+                lienholder: lienholder.clone(),
+            };
+            self.pending.txs.save(storage, tx_id, &new_tx)?;
+            tx_id
+        } else {
+            0
+        };
+        Ok(tx_id)
     }
 
-    /// Updates the local stake for staking on any contract
-    ///
-    /// Stake (both local and remote) is always called by the tokens owner, so the `sender` is
-    /// ued as an owner address.
-    ///
-    /// Config is taken in argument as it sometimes is used outside of this function, so
-    /// we want to avoid double-fetching it
-    ///
-    /// Remote indicates if the stake is remote or local. Remote staking involves transaction
-    /// processing.
-    fn stake(
+    /// Lien and account of `owner` after staking `amount` on `lienholder`, checking the
+    /// collateral covers it. Nothing is saved
+    #[allow(clippy::too_many_arguments)]
+    fn staked(
         &self,
-        ctx: &mut ExecCtx,
+        storage: &dyn Storage,
+        owner: &Addr,
         config: &Config,
         lienholder: &Addr,
         slashable: Decimal,
         amount: Coin,
         remote: bool,
-    ) -> Result<u64, ContractError> {
+    ) -> Result<(Lien, UserInfo), ContractError> {
         ensure!(
             amount.denom == config.denom,
-            ContractError::UnexpectedDenom(config.denom.clone())
+            MeshError::InvalidDenom(config.denom.clone())
+        );
+        ensure!(
+            !self.paused_lienholders.has(storage, lienholder),
+            ContractError::LienholderPaused(lienholder.clone())
         );
 
         let amount = amount.amount;
-        let mut lien = self
-            .liens
-            .may_load(ctx.deps.storage, (&ctx.info.sender, lienholder))?
-            .unwrap_or_else(|| Lien {
-                amount: ValueRange::new_val(Uint128::zero()),
-                slashable,
-            });
-        let mut user = self
-            .users
-            .may_load(ctx.deps.storage, &ctx.info.sender)?
-            .unwrap_or_default();
+        let mut lien = match self.liens.may_load(storage, (owner, lienholder))? {
+            Some(lien) => lien,
+            None => {
+                let max = config.max_lienholders();
+                let lienholders = self
+                    .liens
+                    .prefix(owner)
+                    .keys_raw(storage, None, None, Order::Ascending)
+                    .take(max as usize)
+                    .count();
+                ensure!(
+                    lienholders < max as usize,
+                    ContractError::TooManyLienholders(max)
+                );
+                Lien {
+                    amount: ValueRange::new_val(Uint128::zero()),
+                    slashable,
+                }
+            }
+        };
+        let mut user = self.users.may_load(storage, owner)?.unwrap_or_default();
         if remote {
             lien.amount
                 .prepare_add(amount, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .map_err(|_| MeshError::InsufficientBalance)?;
             // Tentative value
             user.max_lien = max_range(user.max_lien, lien.amount);
             user.total_slashable
                 .prepare_add(amount * lien.slashable, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .map_err(|_| MeshError::InsufficientBalance)?;
         } else {
             // Update lien immediately
             lien.amount
                 .add(amount, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .map_err(|_| MeshError::InsufficientBalance)?;
             // Update max lien and total slashable immediately
             user.max_lien = max_range(user.max_lien, lien.amount);
             user.total_slashable
                 .add(amount * lien.slashable, user.collateral)
-                .map_err(|_| ContractError::InsufficentBalance)?;
+                .map_err(|_| MeshError::InsufficientBalance)?;
         }
 
-        ensure!(user.verify_collateral(), ContractError::InsufficentBalance);
-
-        self.liens
-            .save(ctx.deps.storage, (&ctx.info.sender, lienholder), &lien)?;
-        self.users.save(ctx.deps.storage, &ctx.info.sender, &user)?;
-        let tx_id = if remote {
-            // Create new tx
-            let tx_id = self.next_tx_id(ctx.deps.storage)?;
+        ensure!(user.verify_collateral(), MeshError::InsufficientBalance);
+        Ok((lien, user))
+    }
 
-            let new_tx = InFlightStaking {
-                id: tx_id,
-                amount,
-                slashable,
-                user: ctx.info.sender.clone(),
-                lienholder: lienholder.clone(),
-            };
-            self.pending.txs.save(ctx.deps.storage, tx_id, &new_tx)?;
-            tx_id
-        } else {
-            0
+    /// Account of `owner` after staking `amount` on `lienholder`, going through the same checks
+    /// as `stake_local` or `stake_remote`
+    fn simulated_stake(
+        &self,
+        deps: Deps,
+        owner: &Addr,
+        config: &Config,
+        lienholder: &Addr,
+        amount: Coin,
+    ) -> Result<UserInfo, ContractError> {
+        let local_staking = self.local_staking.load(deps.storage)?;
+        let (slashable, remote) = match local_staking {
+            Some(local) if local.contract.0 == *lienholder => (local.max_slash, false),
+            _ => {
+                let contract = CrossStakingApiHelper(lienholder.clone());
+                (contract.max_slash(deps)?.slash_ratio_dsign, true)
+            }
         };
-        Ok(tx_id)
+        let (_, user) = self.staked(
+            deps.storage,
+            owner,
+            config,
+            lienholder,
+            slashable,
+            amount,
+            remote,
+        )?;
+        let stats = self.stats_with_user(deps.storage, owner, &user)?;
+        check_stats_utilization(&stats, config)?;
+        Ok(user)
     }
 
-    /// Commits a pending stake
-    fn commit_stake(&self, ctx: &mut ExecCtx, tx_id: u64) -> Result<(), ContractError> {
+    /// Commits a pending stake. Returns the staking user and the lienholder
+    fn commit_stake(&self, ctx: &mut ExecCtx, tx_id: u64) -> Result<(Addr, Addr), ContractError> {
         // Load tx
         let tx = self.pending.txs.load(ctx.deps.storage, tx_id)?;
 
@@ -641,8 +3021,7 @@ impl VaultContract<'_> {
         // Commit it
         lien.amount.commit_add(tx_amount);
         // Save it
-        self.liens
-            .save(ctx.deps.storage, (&tx_user, &tx_lienholder), &lien)?;
+        self.save_lien(ctx.deps.storage, (&tx_user, &tx_lienholder), &lien)?;
         // Load user
         let mut user = self.users.load(ctx.deps.storage, &tx_user)?;
         // Update max lien definitive value (it depends on the lien's value range)
@@ -650,12 +3029,12 @@ impl VaultContract<'_> {
         // Commit total slashable
         user.total_slashable.commit_add(tx_amount * lien.slashable);
         // Save it
-        self.users.save(ctx.deps.storage, &tx_user, &user)?;
+        self.save_user(ctx.deps.storage, &tx_user, &user)?;
 
         // Remove tx
         self.pending.txs.remove(ctx.deps.storage, tx_id)?;
 
-        Ok(())
+        Ok((tx_user, tx_lienholder))
     }
 
     /// Rollbacks a pending tx
@@ -697,12 +3076,10 @@ impl VaultContract<'_> {
         lien.amount.rollback_add(tx_amount);
         if lien.amount.high().u128() == 0 {
             // Remove lien if it's empty
-            self.liens
-                .remove(ctx.deps.storage, (&tx_user, &tx_lienholder));
+            self.remove_lien(ctx.deps.storage, (&tx_user, &tx_lienholder))?;
         } else {
             // Save lien
-            self.liens
-                .save(ctx.deps.storage, (&tx_user, &tx_lienholder), &lien)?;
+            self.save_lien(ctx.deps.storage, (&tx_user, &tx_lienholder), &lien)?;
         }
 
         // Load user
@@ -714,7 +3091,7 @@ impl VaultContract<'_> {
         self.recalculate_max_lien(ctx.deps.storage, &tx_user, &mut user)?;
 
         user.total_slashable.rollback_add(tx_amount * tx_slashable);
-        self.users.save(ctx.deps.storage, &tx_user, &user)?;
+        self.save_user(ctx.deps.storage, &tx_user, &user)?;
 
         // Remove tx
         self.pending.txs.remove(ctx.deps.storage, tx_id)?;
@@ -742,16 +3119,21 @@ impl VaultContract<'_> {
     /// Updates the local stake for unstaking from any contract
     ///
     /// The unstake (both local and remote) is always called by the staking contract
-    /// (aka lien_holder), so the `sender` address is used for that.
-    fn unstake(&self, ctx: &mut ExecCtx, owner: String, amount: Coin) -> Result<(), ContractError> {
-        let denom = self.config.load(ctx.deps.storage)?.denom;
-        ensure!(amount.denom == denom, ContractError::UnexpectedDenom(denom));
+    /// (aka lien_holder), or finalized on its behalf for delayed releases.
+    fn unstake(
+        &self,
+        storage: &mut dyn Storage,
+        lienholder: &Addr,
+        owner: &Addr,
+        amount: Coin,
+    ) -> Result<(), ContractError> {
+        let denom = self.config.load(storage)?.denom;
+        ensure!(amount.denom == denom, MeshError::InvalidDenom(denom));
         let amount = amount.amount;
 
-        let owner = Addr::unchecked(owner);
         let mut lien = self
             .liens
-            .may_load(ctx.deps.storage, (&owner, &ctx.info.sender))?
+            .may_load(storage, (owner, lienholder))?
             .ok_or(ContractError::UnknownLienholder)?;
 
         let slashable = lien.slashable;
@@ -761,27 +3143,230 @@ impl VaultContract<'_> {
 
         if lien.amount.high().u128() == 0 {
             // Remove lien if it's empty
-            self.liens
-                .remove(ctx.deps.storage, (&owner, &ctx.info.sender));
+            self.remove_lien(storage, (owner, lienholder))?;
         } else {
             // Save lien
-            self.liens
-                .save(ctx.deps.storage, (&owner, &ctx.info.sender), &lien)?;
+            self.save_lien(storage, (owner, lienholder), &lien)?;
         }
 
-        let mut user = self.users.load(ctx.deps.storage, &owner)?;
+        let mut user = self.users.load(storage, owner)?;
 
         // Max lien has to be recalculated from scratch; the just saved lien
         // is already written to storage
-        self.recalculate_max_lien(ctx.deps.storage, &owner, &mut user)?;
+        self.recalculate_max_lien(storage, owner, &mut user)?;
 
         user.total_slashable
             .sub(amount * slashable, Uint128::zero())?;
-        self.users.save(ctx.deps.storage, &owner, &user)?;
+        self.save_user(storage, owner, &user)?;
 
         Ok(())
     }
 
+    /// Re-stakes (part of) a just released claim, following the owner's auto-restake
+    /// preferences.
+    ///
+    /// Claims released by the auto-restake lienholder itself are never re-staked, so the
+    /// owner can still unstake from it. Failing to re-stake is not an error: the released
+    /// amount just stays as free collateral.
+    ///
+    /// Returns the re-staked amount and the messages to send it to the lienholder, if any.
+    #[allow(clippy::type_complexity)]
+    fn auto_restake_released(
+        &self,
+        ctx: &mut ExecCtx,
+        owner: &Addr,
+        released: Uint128,
+    ) -> Result<Option<(Uint128, Vec<CosmosMsg<custom::VaultMsg>>)>, ContractError> {
+        let mut restake = match self.auto_restake.may_load(ctx.deps.storage, owner)? {
+            Some(restake) if restake.lienholder != ctx.info.sender => restake,
+            _ => return Ok(None),
+        };
+        let amount = min(released, restake.available(ctx.env.block.time));
+        if amount.is_zero() {
+            return Ok(None);
+        }
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let stake = coin(amount.u128(), &config.denom);
+        let msgs = match self.local_staking.load(ctx.deps.storage)? {
+            Some(local_staking) if local_staking.contract.0 == restake.lienholder => {
+                if self
+                    .stake(
+                        ctx.deps.storage,
+                        owner,
+                        &config,
+                        &restake.lienholder,
+                        local_staking.max_slash,
+                        stake.clone(),
+                        false,
+                    )
+                    .is_err()
+                {
+                    return Ok(None);
+                }
+                let stake_msg = local_staking.contract.receive_stake(
+                    owner.to_string(),
+                    restake.msg.clone(),
+                    vec![stake.clone()],
+                )?;
+                provider::unbond_msg(owner, stake)
+                    .into_iter()
+                    .chain([stake_msg.into()])
+                    .collect()
+            }
+            _ => {
+                let contract = CrossStakingApiHelper(restake.lienholder.clone());
+                let slashable = match contract.max_slash(ctx.deps.as_ref()) {
+                    Ok(slashable) => slashable,
+                    Err(_) => return Ok(None),
+                };
+                let tx_id = match self.stake(
+                    ctx.deps.storage,
+                    owner,
+                    &config,
+                    &contract.0,
+                    slashable.slash_ratio_dsign,
+                    stake.clone(),
+                    true,
+                ) {
+                    Ok(tx_id) => tx_id,
+                    Err(_) => return Ok(None),
+                };
+                self.active_external
+                    .save(ctx.deps.storage, &contract.0, &())?;
+                vec![contract
+                    .receive_virtual_stake(
+                        owner.to_string(),
+                        stake,
+                        tx_id,
+                        restake.msg.clone(),
+                        vec![],
+                    )?
+                    .into()]
+            }
+        };
+
+        restake.restaked += amount;
+        self.auto_restake.save(ctx.deps.storage, owner, &restake)?;
+
+        Ok(Some((amount, msgs)))
+    }
+
+    /// Called by the provider module when it bonds `amount` of the vesting tokens of `delegator`.
+    /// The tokens are already locked in the module, and are handed back to the vesting account
+    /// once unbonded, instead of going through the vault.
+    #[sv::msg(sudo)]
+    fn bond_vesting(
+        &self,
+        ctx: SudoCtx,
+        delegator: String,
+        amount: Coin,
+    ) -> Result<custom::Response, ContractError> {
+        ensure!(
+            cfg!(feature = "provider-bindings"),
+            ContractError::NoProviderModule
+        );
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure!(
+            amount.denom == config.denom,
+            MeshError::InvalidDenom(config.denom)
+        );
+        ensure!(
+            config.receipt_denom.is_none(),
+            ContractError::VestingWithReceipts
+        );
+        let delegator = ctx.deps.api.addr_validate(&delegator)?;
+
+        let hook_msgs =
+            self.credit_collateral(ctx.deps.storage, &config, &delegator, amount.amount)?;
+        let vesting =
+            self.vesting
+                .update(ctx.deps.storage, &delegator, |vesting| -> StdResult<_> {
+                    Ok(vesting.unwrap_or_default() + amount.amount)
+                })?;
+
+        let resp = Response::new()
+            .add_submessages(hook_msgs)
+            .add_attribute("action", "bond_vesting")
+            .add_attribute("delegator", delegator)
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("vesting", vesting.to_string());
+        Ok(resp)
+    }
+
+    /// Called by the provider module when the collateral of `delegator` it holds is slashed by
+    /// the native staking module, i.e. `amount` was already burned outside of the vault.
+    ///
+    /// The collateral is reduced by the burned amount, and all of the delegator liens in the
+    /// same proportion, burning the cut from the lienholders stakes.
+    #[sv::msg(sudo)]
+    fn slash_delegator(
+        &self,
+        ctx: SudoCtx,
+        delegator: String,
+        amount: Coin,
+    ) -> Result<custom::Response, ContractError> {
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        ensure!(amount.denom == denom, MeshError::InvalidDenom(denom));
+        let delegator = ctx.deps.api.addr_validate(&delegator)?;
+
+        let mut user = self
+            .users
+            .may_load(ctx.deps.storage, &delegator)?
+            .unwrap_or_default();
+        // Never more than what the vault accounts for
+        let slashed = min(amount.amount, user.collateral);
+
+        let mut msgs = vec![];
+        if !slashed.is_zero() {
+            let native_staking = self.local_staking.load(ctx.deps.storage)?;
+            let liens = self
+                .liens
+                .prefix(&delegator)
+                .range(ctx.deps.storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (lienholder, mut lien) in liens {
+                let cut_low = lien.amount.low().multiply_ratio(slashed, user.collateral);
+                let cut_high = lien.amount.high().multiply_ratio(slashed, user.collateral);
+                if cut_high.is_zero() {
+                    continue;
+                }
+                // Adjust the user's total slashable amount
+                user.total_slashable = ValueRange::new(
+                    user.total_slashable.low() - cut_low * lien.slashable,
+                    user.total_slashable.high() - cut_high * lien.slashable,
+                );
+                lien.amount =
+                    ValueRange::new(lien.amount.low() - cut_low, lien.amount.high() - cut_high);
+                if lien.amount.high().is_zero() {
+                    self.remove_lien(ctx.deps.storage, (&delegator, &lienholder))?;
+                } else {
+                    self.save_lien(ctx.deps.storage, (&delegator, &lienholder), &lien)?;
+                }
+                let burn_msg = self.burn_stake(
+                    &delegator,
+                    &denom,
+                    &native_staking,
+                    &lienholder,
+                    cut_high, // High amount for simplicity
+                    None,
+                )?;
+                msgs.push(burn_msg);
+            }
+
+            user.collateral -= slashed;
+            self.recalculate_max_lien(ctx.deps.storage, &delegator, &mut user)?;
+            self.save_user(ctx.deps.storage, &delegator, &user)?;
+        }
+
+        let resp = Response::new()
+            .add_messages(msgs)
+            .add_attribute("action", "slash_delegator")
+            .add_attribute("delegator", delegator)
+            .add_attribute("amount", slashed.to_string());
+        Ok(resp)
+    }
+
     /// Processes a (remote or local) slashing event.
     ///
     /// This slashes the users that have funds delegated to the validator involved in the
@@ -803,15 +3388,20 @@ impl VaultContract<'_> {
             let mut lien = self
                 .liens
                 .load(ctx.deps.storage, (&slash_user, &lien_holder))?;
-            let slash_amount = slash.slash;
+            // What the lien doesn't cover anymore (released since the infraction) is taken
+            // from the collateral still unbonding
+            let slash_amount = min(slash.slash, lien.amount.low());
+            let unbonding_slash = slash.slash - slash_amount;
+            if !unbonding_slash.is_zero() {
+                self.slash_unbonding(ctx.deps.storage, &slash_user, unbonding_slash)?;
+            }
             let mut user_info = self.users.load(ctx.deps.storage, &slash_user)?;
             let new_collateral = user_info.collateral - slash_amount;
 
             // Slash user
             lien.amount.sub(slash_amount, Uint128::zero())?;
             // Save lien
-            self.liens
-                .save(ctx.deps.storage, (&slash_user, &lien_holder), &lien)?;
+            self.save_lien(ctx.deps.storage, (&slash_user, &lien_holder), &lien)?;
             // Adjust total slashable and max lien
             user_info
                 .total_slashable
@@ -837,11 +3427,42 @@ impl VaultContract<'_> {
             // Recompute max lien
             self.recalculate_max_lien(ctx.deps.storage, &slash_user, &mut user_info)?;
             // Save user info
-            self.users.save(ctx.deps.storage, &slash_user, &user_info)?;
+            self.save_user(ctx.deps.storage, &slash_user, &user_info)?;
         }
         Ok(msgs)
     }
 
+    /// Slashes `amount` from the user unbonding claims, the latest ones first
+    fn slash_unbonding(
+        &self,
+        storage: &mut dyn Storage,
+        user: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let claims = self
+            .unbonding_claims
+            .prefix(user)
+            .range(storage, None, None, Order::Descending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut left = amount;
+        for (release_at, claim) in claims {
+            if left.is_zero() {
+                break;
+            }
+            let slashed = min(claim, left);
+            left -= slashed;
+            if slashed == claim {
+                self.unbonding_claims.remove(storage, (user, release_at));
+            } else {
+                self.unbonding_claims
+                    .save(storage, (user, release_at), &(claim - slashed))?;
+            }
+        }
+        ensure!(left.is_zero(), ContractError::InsufficientLien);
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn propagate_slash(
         &self,
@@ -880,7 +3501,7 @@ impl VaultContract<'_> {
                 );
                 // Keep the invariant over the lien
                 lien.amount = ValueRange::new(new_low_amount, new_high_amount);
-                self.liens.save(storage, (user, &lien_holder), &lien)?;
+                self.save_lien(storage, (user, &lien_holder), &lien)?;
                 // Remove the required amount from the user's stake
                 let validator = if lien_holder == slashed_lien_holder {
                     Some(slashed_validator.to_string())
@@ -928,7 +3549,7 @@ impl VaultContract<'_> {
                     .sub(sub_amount * lien.slashable, Uint128::zero())?;
                 // Keep the invariant over the lien
                 lien.amount.sub(sub_amount, Uint128::zero())?;
-                self.liens.save(storage, (user, &lien_holder), &lien)?;
+                self.save_lien(storage, (user, &lien_holder), &lien)?;
                 // Remove the required amount from the user's stake
                 let validator = if lien_holder == slashed_lien_holder {
                     Some(slashed_validator.to_string())
@@ -981,6 +3602,8 @@ impl Default for VaultContract<'_> {
 
 impl VaultApi for VaultContract<'_> {
     type Error = ContractError;
+    type ExecC = custom::VaultMsg;
+    type QueryC = Empty;
 
     /// This must be called by the remote staking contract to release this claim
     fn release_cross_stake(
@@ -990,17 +3613,88 @@ impl VaultApi for VaultContract<'_> {
         owner: String,
         // amount to unstake on that contract
         amount: Coin,
-    ) -> Result<Response, ContractError> {
+    ) -> Result<custom::Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        self.unstake(&mut ctx, owner.clone(), amount.clone())?;
+        self.unstake(
+            ctx.deps.storage,
+            &ctx.info.sender,
+            &Addr::unchecked(&owner),
+            amount.clone(),
+        )?;
+        let hook_msgs = self.stake_change_hook_msgs(
+            ctx.deps.storage,
+            &Addr::unchecked(&owner),
+            &ctx.info.sender,
+        )?;
 
-        let resp = Response::new()
+        let mut resp = Response::new()
+            .add_submessages(hook_msgs)
+            .add_event(Event::from(
+                UnstakeEvent::new(amount.clone())
+                    .delegator(&owner)
+                    .lienholder(&ctx.info.sender),
+            ))
             .add_attribute("action", "release_cross_stake")
-            .add_attribute("sender", ctx.info.sender)
-            .add_attribute("owner", owner)
+            .add_attribute("sender", ctx.info.sender.clone())
+            .add_attribute("owner", owner.clone())
             .add_attribute("amount", amount.amount.to_string());
 
+        if let Some((restaked, msgs)) =
+            self.auto_restake_released(&mut ctx, &Addr::unchecked(owner), amount.amount)?
+        {
+            resp = resp
+                .add_messages(msgs)
+                .add_attribute("auto_restaked", restaked.to_string());
+        }
+
+        Ok(resp)
+    }
+
+    /// This can be called by the remote staking contract to release this claim at `release_at`
+    fn release_cross_stake_delayed(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        amount: Coin,
+        release_at: Timestamp,
+    ) -> Result<custom::Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let denom = self.config.load(ctx.deps.storage)?.denom;
+        ensure!(amount.denom == denom, MeshError::InvalidDenom(denom));
+        ensure!(
+            release_at > ctx.env.block.time,
+            ContractError::InvalidReleaseTime
+        );
+
+        let owner_addr = Addr::unchecked(&owner);
+        let lien = self
+            .liens
+            .may_load(ctx.deps.storage, (&owner_addr, &ctx.info.sender))?
+            .ok_or(ContractError::UnknownLienholder)?;
+        ensure!(
+            lien.amount.low() >= amount.amount,
+            ContractError::InsufficientLien
+        );
+
+        self.pending_releases.update(
+            ctx.deps.storage,
+            (release_at.nanos(), &owner_addr, &ctx.info.sender),
+            |pending| -> StdResult<_> { Ok(pending.unwrap_or_default() + amount.amount) },
+        )?;
+        self.incoming_collateral.update(
+            ctx.deps.storage,
+            &owner_addr,
+            |incoming| -> StdResult<_> { Ok(incoming.unwrap_or_default() + amount.amount) },
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "release_cross_stake_delayed")
+            .add_attribute("sender", ctx.info.sender)
+            .add_attribute("owner", owner)
+            .add_attribute("amount", amount.amount.to_string())
+            .add_attribute("release_at", release_at.to_string());
         Ok(resp)
     }
 
@@ -1011,17 +3705,120 @@ impl VaultApi for VaultContract<'_> {
         mut ctx: ExecCtx,
         // address of the user who originally called stake_remote
         owner: String,
-    ) -> Result<Response, ContractError> {
+    ) -> Result<custom::Response, ContractError> {
         let denom = self.config.load(ctx.deps.storage)?.denom;
         let amount = must_pay(&ctx.info, &denom)?;
 
-        self.unstake(&mut ctx, owner.clone(), coin(amount.u128(), denom))?;
+        self.unstake(
+            ctx.deps.storage,
+            &ctx.info.sender,
+            &Addr::unchecked(&owner),
+            coin(amount.u128(), &denom),
+        )?;
+        let hook_msgs = self.stake_change_hook_msgs(
+            ctx.deps.storage,
+            &Addr::unchecked(&owner),
+            &ctx.info.sender,
+        )?;
 
-        let resp = Response::new()
+        // The returned funds are collateral again
+        let mut resp = Response::new()
+            .add_messages(provider::bond_msg(
+                &Addr::unchecked(&owner),
+                coin(amount.u128(), &denom),
+            ))
+            .add_submessages(hook_msgs)
+            .add_event(Event::from(
+                UnstakeEvent::new(coin(amount.u128(), denom))
+                    .delegator(&owner)
+                    .lienholder(&ctx.info.sender),
+            ))
             .add_attribute("action", "release_cross_stake")
+            .add_attribute("sender", ctx.info.sender.clone())
+            .add_attribute("owner", owner.clone())
+            .add_attribute("amount", amount.to_string());
+
+        if let Some((restaked, msgs)) =
+            self.auto_restake_released(&mut ctx, &Addr::unchecked(owner), amount)?
+        {
+            resp = resp
+                .add_messages(msgs)
+                .add_attribute("auto_restaked", restaked.to_string());
+        }
+
+        Ok(resp)
+    }
+
+    /// This must be called by the remote staking contract to re-stake rewards of `owner`.
+    /// Tokens sent along are bonded as `owner` collateral, and virtually staked on the sender.
+    /// If enabled, receipt tokens are minted to `owner`.
+    fn compound_stake(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        msg: Binary,
+    ) -> Result<custom::Response, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let amount = must_pay(&ctx.info, &config.denom)?;
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+
+        // Only a lienholder the owner already staked on can compound
+        let contract = CrossStakingApiHelper(ctx.info.sender.clone());
+        ensure!(
+            self.liens.has(ctx.deps.storage, (&owner, &contract.0)),
+            ContractError::UnknownLienholder
+        );
+
+        let mut user = self
+            .users
+            .may_load(ctx.deps.storage, &owner)?
+            .unwrap_or_default();
+        user.collateral += amount;
+        self.save_user(ctx.deps.storage, &owner, &user)?;
+
+        let slashable = contract.max_slash(ctx.deps.as_ref())?;
+        let stake = coin(amount.u128(), &config.denom);
+        let tx_id = self.stake(
+            ctx.deps.storage,
+            &owner,
+            &config,
+            &contract.0,
+            slashable.slash_ratio_dsign,
+            stake.clone(),
+            true,
+        )?;
+        let stake_msg =
+            contract.receive_virtual_stake(owner.to_string(), stake.clone(), tx_id, msg, vec![])?;
+        let hook_msgs = self.hook_msgs(
+            ctx.deps.storage,
+            VaultHookMsg::AfterBond {
+                owner: owner.to_string(),
+                amount: stake,
+            },
+        )?;
+
+        let mut resp = Response::new()
+            .add_messages(provider::bond_msg(
+                &owner,
+                coin(amount.u128(), &config.denom),
+            ))
+            .add_message(stake_msg)
+            .add_submessages(hook_msgs);
+        if let Some(receipt_denom) = config.receipt_denom {
+            resp = resp.add_message(receipt::mint_msg(
+                &ctx.env.contract.address,
+                &receipt_denom,
+                amount,
+                &owner,
+            ));
+        }
+
+        let resp = resp
+            .add_attribute("action", "compound_stake")
             .add_attribute("sender", ctx.info.sender)
             .add_attribute("owner", owner)
-            .add_attribute("amount", amount.to_string());
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("tx_id", tx_id.to_string());
 
         Ok(resp)
     }
@@ -1032,13 +3829,15 @@ impl VaultApi for VaultContract<'_> {
         mut ctx: ExecCtx,
         slashes: Vec<SlashInfo>,
         validator: String,
-    ) -> Result<Response, Self::Error> {
+    ) -> Result<custom::Response, Self::Error> {
         nonpayable(&ctx.info)?;
 
         let msgs = self.slash(&mut ctx, &slashes, &validator)?;
+        let denom = self.config.load(ctx.deps.storage)?.denom;
 
         let resp = Response::new()
             .add_messages(msgs)
+            .add_events(slash_events(&slashes, &denom, &validator, &ctx.info.sender))
             .add_attribute("action", "local_slash")
             .add_attribute("lien_holder", ctx.info.sender)
             .add_attribute("validator", validator.to_string())
@@ -1060,13 +3859,15 @@ impl VaultApi for VaultContract<'_> {
         mut ctx: ExecCtx,
         slashes: Vec<SlashInfo>,
         validator: String,
-    ) -> Result<Response, Self::Error> {
+    ) -> Result<custom::Response, Self::Error> {
         nonpayable(&ctx.info)?;
 
         let msgs = self.slash(&mut ctx, &slashes, &validator)?;
+        let denom = self.config.load(ctx.deps.storage)?.denom;
 
         let resp = Response::new()
             .add_messages(msgs)
+            .add_events(slash_events(&slashes, &denom, &validator, &ctx.info.sender))
             .add_attribute("action", "cross_slash")
             .add_attribute("lien_holder", ctx.info.sender)
             .add_attribute("validator", validator.to_string())
@@ -1082,10 +3883,12 @@ impl VaultApi for VaultContract<'_> {
         Ok(resp)
     }
 
-    fn commit_tx(&self, mut ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
-        self.commit_stake(&mut ctx, tx_id)?;
+    fn commit_tx(&self, mut ctx: ExecCtx, tx_id: u64) -> Result<custom::Response, ContractError> {
+        let (user, lienholder) = self.commit_stake(&mut ctx, tx_id)?;
+        let hook_msgs = self.stake_change_hook_msgs(ctx.deps.storage, &user, &lienholder)?;
 
         let resp = Response::new()
+            .add_submessages(hook_msgs)
             .add_attribute("action", "commit_tx")
             .add_attribute("sender", ctx.info.sender)
             .add_attribute("tx_id", tx_id.to_string());
@@ -1093,7 +3896,7 @@ impl VaultApi for VaultContract<'_> {
         Ok(resp)
     }
 
-    fn rollback_tx(&self, mut ctx: ExecCtx, tx_id: u64) -> Result<Response, ContractError> {
+    fn rollback_tx(&self, mut ctx: ExecCtx, tx_id: u64) -> Result<custom::Response, ContractError> {
         self.rollback_stake(&mut ctx, tx_id)?;
 
         let resp = Response::new()
@@ -1103,3 +3906,59 @@ impl VaultApi for VaultContract<'_> {
         Ok(resp)
     }
 }
+
+impl OwnershipApi for VaultContract<'_> {
+    type Error = ContractError;
+    type ExecC = custom::VaultMsg;
+    type QueryC = Empty;
+
+    fn propose_owner(
+        &self,
+        ctx: ExecCtx,
+        new_owner: String,
+        expiry: Option<Timestamp>,
+    ) -> Result<custom::Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+        let ownership = ownership_api::propose_owner(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            new_owner,
+            expiry,
+        )?;
+
+        let resp = Response::new()
+            .add_attribute("action", "propose_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn accept_owner(&self, ctx: ExecCtx) -> Result<custom::Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership =
+            ownership_api::accept_owner(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "accept_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn renounce_owner(&self, ctx: ExecCtx) -> Result<custom::Response, Self::Error> {
+        nonpayable(&ctx.info)?;
+
+        let ownership = ownership_api::renounce_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let resp = Response::new()
+            .add_attribute("action", "renounce_owner")
+            .add_attributes(ownership.into_attributes());
+        Ok(resp)
+    }
+
+    fn ownership(&self, ctx: QueryCtx) -> Result<Ownership, Self::Error> {
+        Ok(ownership_api::get_ownership(ctx.deps.storage)?)
+    }
+}
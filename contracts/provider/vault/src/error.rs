@@ -1,5 +1,7 @@
-use cosmwasm_std::{Addr, StdError, Uint128};
+use cosmwasm_std::{Addr, Decimal, StdError, Uint128};
 use cw_utils::{ParseReplyError, PaymentError};
+use mesh_apis::error::MeshError;
+use mesh_apis::ownership_api::OwnershipError;
 use mesh_sync::{RangeError, Tx, ValueRange};
 use thiserror::Error;
 
@@ -17,17 +19,23 @@ pub enum ContractError {
     #[error("{0}")]
     Range(#[from] RangeError),
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("{0}")]
+    Ownership(OwnershipError),
 
-    #[error("All denoms are expected to be {0}")]
-    UnexpectedDenom(String),
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
 
     #[error("Claim is locked, only {0} can be unbonded")]
     ClaimsLocked(ValueRange<Uint128>),
 
-    #[error("The address doesn't have sufficient balance for this operation")]
-    InsufficentBalance,
+    #[error("At least {0} tokens have to be bonded")]
+    BondTooSmall(Uint128),
+
+    #[error("At least {0} tokens have to be unbonded")]
+    UnbondTooSmall(Uint128),
+
+    #[error("Exactly {0} receipt tokens have to be returned")]
+    InvalidReceiptAmount(Uint128),
 
     #[error("The lienholder doesn't have any claims")]
     UnknownLienholder,
@@ -35,8 +43,8 @@ pub enum ContractError {
     #[error("The lienholder doesn't have enough claims for the action")]
     InsufficientLien,
 
-    #[error("Invalid reply id: {0}")]
-    InvalidReplyId(u64),
+    #[error("Release time must be in the future")]
+    InvalidReleaseTime,
 
     #[error("The tx {0} exists but is of the wrong type: {1}")]
     WrongTypeTx(u64, Tx),
@@ -49,4 +57,101 @@ pub enum ContractError {
 
     #[error("No claim found")]
     NoClaim,
+
+    #[error("Auto-restake cap and period must be greater than zero")]
+    InvalidAutoRestake,
+
+    #[error("No auto-restake configured")]
+    NoAutoRestake,
+
+    #[error("Lienholder {0} is paused")]
+    LienholderPaused(Addr),
+
+    #[error("No valid {0} grant")]
+    NoGrant(String),
+
+    #[error("Grant is already expired")]
+    GrantExpired,
+
+    #[error("A remote recipient is required by withdraw_rewards grants only, got a {0} grant")]
+    InvalidGrantRecipient(String),
+
+    #[error("Permits must be signed by compressed secp256k1 keys of bech32 accounts")]
+    InvalidPermitKey,
+
+    #[error("Permit key is not the one of {0}")]
+    PermitKeyMismatch(String),
+
+    #[error("Permit is expired")]
+    PermitExpired,
+
+    #[error("Permit nonce {0} already used")]
+    PermitNonceUsed(u64),
+
+    #[error("Invalid permit signature")]
+    InvalidPermitSignature,
+
+    #[error("Hook {0} is already registered")]
+    HookAlreadyRegistered(Addr),
+
+    #[error("Hook {0} is not registered")]
+    HookNotRegistered(Addr),
+
+    #[error("Too many hooks, at most {0} can be registered")]
+    TooManyHooks(usize),
+
+    #[error("Max utilization must be greater than zero and at most one")]
+    InvalidMaxUtilization,
+
+    #[error("Stake would exceed the max collateral utilization of {0}")]
+    UtilizationCapReached(Decimal),
+
+    #[error("No yield strategy configured")]
+    NoStrategy,
+
+    #[error("The yield strategy can't be changed while deposits are outstanding")]
+    StrategyInUse,
+
+    #[error("No strategy deposit to withdraw")]
+    NoStrategyDeposit,
+
+    #[error("Strategy deposit too small to mint any share")]
+    StrategyDepositTooSmall,
+
+    #[error("Batches must have between 1 and {0} items")]
+    InvalidBatchSize(usize),
+
+    #[error("Exactly {0} tokens have to be sent along with the batch")]
+    InvalidBatchFunds(Uint128),
+
+    #[error("Max lienholders must be greater than zero")]
+    InvalidMaxLienholders,
+
+    #[error("Too many lienholders, an account can stake on at most {0}")]
+    TooManyLienholders(u32),
+
+    #[error("Denom metadata must have a display unit and a symbol")]
+    InvalidDenomMetadata,
+
+    #[error("Vault invariant violated: {0}")]
+    InvariantViolated(String),
+
+    #[error("Vesting collateral is only bonded through the provider module")]
+    NoProviderModule,
+
+    #[error("Vesting collateral can't be bonded while receipt tokens are enabled")]
+    VestingWithReceipts,
+
+    #[error("Vesting collateral can't leave the provider module")]
+    VestingLocked,
+}
+
+impl From<OwnershipError> for ContractError {
+    fn from(err: OwnershipError) -> Self {
+        match err {
+            // Unwrapped, so non owners get the same error as any other unauthorized caller
+            OwnershipError::Mesh(err) => ContractError::Mesh(err),
+            err => ContractError::Ownership(err),
+        }
+    }
 }
@@ -0,0 +1,131 @@
+use cosmwasm_std::{Addr, BlockInfo, Order, StdResult, Storage};
+use cw_storage_plus::Map;
+use cw_utils::Expiration;
+
+use crate::error::ContractError;
+use crate::msg::{GrantInfo, GrantedMsgType};
+
+/// Authz-like grants, allowing a grantee to execute specific vault messages on behalf of a
+/// granter, until the grant expires or is revoked.
+pub struct Grants<'a> {
+    /// Grant expirations, indexed by `(granter, grantee, msg_type)`
+    pub grants: Map<'a, (&'a Addr, &'a Addr, &'a str), Expiration>,
+    /// Consumer-side recipient of the rewards withdrawn by a `withdraw_rewards` grantee, set by
+    /// the granter so the grantee can't redirect them. Indexed by `(granter, grantee)`
+    pub reward_recipients: Map<'a, (&'a Addr, &'a Addr), String>,
+}
+
+impl<'a> Grants<'a> {
+    pub const fn new(storage_key: &'a str, recipients_key: &'a str) -> Self {
+        Self {
+            grants: Map::new(storage_key),
+            reward_recipients: Map::new(recipients_key),
+        }
+    }
+
+    /// Creates or replaces a grant. `withdraw_rewards` grants need the `remote_recipient` of the
+    /// rewards, which other grants don't take
+    #[allow(clippy::too_many_arguments)]
+    pub fn grant(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        granter: &Addr,
+        grantee: &Addr,
+        msg_type: GrantedMsgType,
+        expiration: Expiration,
+        remote_recipient: Option<String>,
+    ) -> Result<(), ContractError> {
+        if expiration.is_expired(block) {
+            return Err(ContractError::GrantExpired);
+        }
+        match (msg_type, remote_recipient) {
+            (GrantedMsgType::WithdrawRewards, Some(recipient)) if !recipient.is_empty() => {
+                self.reward_recipients
+                    .save(storage, (granter, grantee), &recipient)?;
+            }
+            (GrantedMsgType::WithdrawRewards, _) | (_, Some(_)) => {
+                return Err(ContractError::InvalidGrantRecipient(
+                    msg_type.as_str().to_owned(),
+                ));
+            }
+            _ => {}
+        }
+        self.grants
+            .save(storage, (granter, grantee, msg_type.as_str()), &expiration)?;
+        Ok(())
+    }
+
+    pub fn revoke(
+        &self,
+        storage: &mut dyn Storage,
+        granter: &Addr,
+        grantee: &Addr,
+        msg_type: GrantedMsgType,
+    ) -> Result<(), ContractError> {
+        let key = (granter, grantee, msg_type.as_str());
+        if !self.grants.has(storage, key) {
+            return Err(ContractError::NoGrant(msg_type.as_str().to_owned()));
+        }
+        self.grants.remove(storage, key);
+        if msg_type == GrantedMsgType::WithdrawRewards {
+            self.reward_recipients.remove(storage, (granter, grantee));
+        }
+        Ok(())
+    }
+
+    /// Recipient of the rewards withdrawn by `grantee` on behalf of `granter`
+    pub fn reward_recipient(
+        &self,
+        storage: &dyn Storage,
+        granter: &Addr,
+        grantee: &Addr,
+    ) -> StdResult<String> {
+        self.reward_recipients.load(storage, (granter, grantee))
+    }
+
+    /// Fails unless `grantee` holds a non-expired grant from `granter` for `msg_type`
+    pub fn check(
+        &self,
+        storage: &dyn Storage,
+        block: &BlockInfo,
+        granter: &Addr,
+        grantee: &Addr,
+        msg_type: GrantedMsgType,
+    ) -> Result<(), ContractError> {
+        match self
+            .grants
+            .may_load(storage, (granter, grantee, msg_type.as_str()))?
+        {
+            Some(expiration) if !expiration.is_expired(block) => Ok(()),
+            _ => Err(ContractError::NoGrant(msg_type.as_str().to_owned())),
+        }
+    }
+
+    /// All grants from `granter` to `grantee`, including expired ones
+    pub fn grants_by_pair(
+        &self,
+        storage: &dyn Storage,
+        granter: &Addr,
+        grantee: &Addr,
+    ) -> StdResult<Vec<GrantInfo>> {
+        self.grants
+            .prefix((granter, grantee))
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| {
+                let (msg_type, expiration) = item?;
+                let remote_recipient = if msg_type == GrantedMsgType::WithdrawRewards.as_str() {
+                    self.reward_recipients
+                        .may_load(storage, (granter, grantee))?
+                } else {
+                    None
+                };
+                Ok(GrantInfo {
+                    msg_type,
+                    expiration,
+                    remote_recipient,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,239 @@
+//! Consistency checks of the vault state.
+//!
+//! The per-account `max_lien` and `total_slashable`, the vault stats, the lienholder totals and
+//! the incoming collateral are all cached, and updated incrementally. The checks recompute them
+//! from the raw liens, users and pending releases, and report any difference with the cached
+//! values. They are exposed with the `check_invariants` query, for auditing live deployments.
+//!
+//! With the `debug-invariants` feature, they also run after every execute (in multitest too),
+//! failing the execution on any discrepancy.
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{Addr, Order, StdResult, Storage, Uint128};
+use mesh_sync::{max_range, ValueRange};
+
+use crate::contract::VaultContract;
+use crate::error::ContractError;
+use crate::msg::InvariantDiscrepancy;
+use crate::state::UserInfo;
+
+fn discrepancy(
+    invariant: &str,
+    account: Option<&Addr>,
+    lienholder: Option<&Addr>,
+    expected: impl ToString,
+    actual: impl ToString,
+) -> InvariantDiscrepancy {
+    InvariantDiscrepancy {
+        invariant: invariant.to_owned(),
+        account: account.map(Addr::to_string),
+        lienholder: lienholder.map(Addr::to_string),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+    }
+}
+
+/// Checks the cached values of the account `owner` against its liens
+fn check_account(
+    contract: &VaultContract,
+    storage: &dyn Storage,
+    owner: &Addr,
+    user: &UserInfo,
+    discrepancies: &mut Vec<InvariantDiscrepancy>,
+) -> StdResult<()> {
+    let mut max_lien = ValueRange::new_val(Uint128::zero());
+    let mut total_slashable = ValueRange::new_val(Uint128::zero());
+    let mut liens = 0u128;
+    for item in contract
+        .liens
+        .prefix(owner)
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (_, lien) = item?;
+        max_lien = max_range(max_lien, lien.amount);
+        total_slashable = ValueRange::new(
+            total_slashable.low() + lien.amount.low() * lien.slashable,
+            total_slashable.high() + lien.amount.high() * lien.slashable,
+        );
+        liens += 1;
+    }
+
+    if user.max_lien != max_lien {
+        discrepancies.push(discrepancy(
+            "max_lien",
+            Some(owner),
+            None,
+            max_lien,
+            user.max_lien,
+        ));
+    }
+    // The slashable amounts are rounded down on every stake change, so they may drift from the
+    // recomputed total by a few units. Only a difference beyond one unit per lien is reported
+    let tolerance = Uint128::new(liens);
+    let drifted = |cached: Uint128, expected: Uint128| cached.abs_diff(expected) > tolerance;
+    if drifted(user.total_slashable.low(), total_slashable.low())
+        || drifted(user.total_slashable.high(), total_slashable.high())
+    {
+        discrepancies.push(discrepancy(
+            "total_slashable",
+            Some(owner),
+            None,
+            total_slashable,
+            user.total_slashable,
+        ));
+    }
+    if !user.verify_collateral() {
+        discrepancies.push(discrepancy(
+            "collateral_coverage",
+            Some(owner),
+            None,
+            user.used_collateral().high(),
+            user.collateral,
+        ));
+    }
+    Ok(())
+}
+
+/// Recomputes the cached values of the vault, of `account` only if set, and returns the
+/// discrepancies with the stored ones, along with the number of accounts checked.
+/// The protocol-wide values are only checked along with all the accounts
+pub fn check_invariants(
+    contract: &VaultContract,
+    storage: &dyn Storage,
+    account: Option<&Addr>,
+) -> Result<(u64, Vec<InvariantDiscrepancy>), ContractError> {
+    let mut discrepancies = vec![];
+
+    if let Some(owner) = account {
+        let user = contract.users.may_load(storage, owner)?.unwrap_or_default();
+        check_account(contract, storage, owner, &user, &mut discrepancies)?;
+        return Ok((1, discrepancies));
+    }
+
+    // Accounts, and the vault stats over all of them
+    let mut accounts = 0u64;
+    let mut total_collateral = Uint128::zero();
+    let mut total_slashable = Uint128::zero();
+    for item in contract.users.range(storage, None, None, Order::Ascending) {
+        let (owner, user) = item?;
+        check_account(contract, storage, &owner, &user, &mut discrepancies)?;
+        accounts += 1;
+        total_collateral += user.collateral;
+        total_slashable += user.total_slashable.high();
+    }
+    let stats = contract.stats.may_load(storage)?.unwrap_or_default();
+    if stats.accounts != accounts {
+        discrepancies.push(discrepancy(
+            "stats.accounts",
+            None,
+            None,
+            accounts,
+            stats.accounts,
+        ));
+    }
+    if stats.total_collateral != total_collateral {
+        discrepancies.push(discrepancy(
+            "stats.total_collateral",
+            None,
+            None,
+            total_collateral,
+            stats.total_collateral,
+        ));
+    }
+    if stats.total_slashable != total_slashable {
+        discrepancies.push(discrepancy(
+            "stats.total_slashable",
+            None,
+            None,
+            total_slashable,
+            stats.total_slashable,
+        ));
+    }
+
+    // Lienholder totals, over all the liens
+    let mut lienholder_totals: BTreeMap<Addr, Uint128> = BTreeMap::new();
+    for item in contract.liens.range(storage, None, None, Order::Ascending) {
+        let ((_, lienholder), lien) = item?;
+        *lienholder_totals.entry(lienholder).or_default() += lien.amount.high();
+    }
+    for item in contract
+        .lienholder_totals
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (lienholder, cached) = item?;
+        let expected = lienholder_totals.remove(&lienholder).unwrap_or_default();
+        if cached != expected {
+            discrepancies.push(discrepancy(
+                "lienholder_total",
+                None,
+                Some(&lienholder),
+                expected,
+                cached,
+            ));
+        }
+    }
+    for (lienholder, expected) in lienholder_totals {
+        discrepancies.push(discrepancy(
+            "lienholder_total",
+            None,
+            Some(&lienholder),
+            expected,
+            Uint128::zero(),
+        ));
+    }
+
+    // Incoming collateral, over all the pending releases
+    let mut incoming: BTreeMap<Addr, Uint128> = BTreeMap::new();
+    for item in contract
+        .pending_releases
+        .range(storage, None, None, Order::Ascending)
+    {
+        let ((_, owner, _), amount) = item?;
+        *incoming.entry(owner).or_default() += amount;
+    }
+    for item in contract
+        .incoming_collateral
+        .range(storage, None, None, Order::Ascending)
+    {
+        let (owner, cached) = item?;
+        let expected = incoming.remove(&owner).unwrap_or_default();
+        if cached != expected {
+            discrepancies.push(discrepancy(
+                "incoming_collateral",
+                Some(&owner),
+                None,
+                expected,
+                cached,
+            ));
+        }
+    }
+    for (owner, expected) in incoming {
+        discrepancies.push(discrepancy(
+            "incoming_collateral",
+            Some(&owner),
+            None,
+            expected,
+            Uint128::zero(),
+        ));
+    }
+
+    Ok((accounts, discrepancies))
+}
+
+/// Executes `msg`, then checks the invariants of the whole vault, failing on any discrepancy
+#[cfg(feature = "debug-invariants")]
+pub fn execute(
+    mut deps: cosmwasm_std::DepsMut,
+    env: cosmwasm_std::Env,
+    info: cosmwasm_std::MessageInfo,
+    msg: crate::contract::sv::ContractExecMsg,
+) -> Result<crate::contract::custom::Response, ContractError> {
+    let contract = VaultContract::new();
+    let resp = msg.dispatch(&contract, (deps.branch(), env, info))?;
+
+    let (_, discrepancies) = check_invariants(&contract, deps.storage, None)?;
+    if let Some(discrepancy) = discrepancies.into_iter().next() {
+        return Err(ContractError::InvariantViolated(discrepancy.invariant));
+    }
+    Ok(resp)
+}
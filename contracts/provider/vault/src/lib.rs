@@ -1,7 +1,18 @@
 pub mod contract;
 pub mod error;
+pub mod grants;
+pub mod invariants;
+pub mod liens;
+pub mod metadata;
+pub mod migrations;
 pub mod msg;
-#[cfg(test)]
+// The multitests run the vault along with contracts without the provider module custom messages
+#[cfg(all(test, not(feature = "provider-bindings")))]
 mod multitest;
+pub mod permits;
+pub mod provider;
+#[cfg(all(test, feature = "provider-bindings"))]
+mod provider_multitest;
+pub mod receipt;
 mod state;
 pub mod txs;
@@ -0,0 +1,45 @@
+use crate::state::Lien;
+use cosmwasm_std::Addr;
+use cw_storage_plus::{Index, IndexList, IndexedMap, KeyDeserialize, MultiIndex};
+
+pub struct LienIndexes<'a> {
+    // Last type param defines the pk deserialization type
+    pub amount: MultiIndex<'a, (Addr, u128), Lien, (Addr, Addr)>,
+    pub lienholder: MultiIndex<'a, Addr, Lien, (Addr, Addr)>,
+}
+
+impl<'a> IndexList<Lien> for LienIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Lien>> + '_> {
+        let v: Vec<&dyn Index<Lien>> = vec![&self.amount, &self.lienholder];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Liens indexed with (user, lien_holder), with a secondary index over (user, lien amount),
+/// allowing to list user liens sorted by their (high) amount, and one over the lienholder,
+/// allowing to list all the liens of a lienholder
+pub fn liens<'a>(
+    storage_key: &'a str,
+    amount_subkey: &'a str,
+    lienholder_subkey: &'a str,
+) -> IndexedMap<'a, (&'a Addr, &'a Addr), Lien, LienIndexes<'a>> {
+    let indexes = LienIndexes {
+        amount: MultiIndex::new(
+            |pk, lien| {
+                let (user, _) = <(Addr, Addr)>::from_slice(pk).unwrap(); // mustn't fail
+                (user, lien.amount.high().u128())
+            },
+            storage_key,
+            amount_subkey,
+        ),
+        lienholder: MultiIndex::new(
+            |pk, _| {
+                let (_, lienholder) = <(Addr, Addr)>::from_slice(pk).unwrap(); // mustn't fail
+                lienholder
+            },
+            storage_key,
+            lienholder_subkey,
+        ),
+    };
+    IndexedMap::new(storage_key, indexes)
+}
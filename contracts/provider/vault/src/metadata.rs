@@ -0,0 +1,82 @@
+//! Display metadata of the collateral denom.
+//!
+//! Taken from the instantiation params if set there, otherwise from the chain's bank metadata
+//! of the denom. Without either, amounts are displayed in the base denom, with no decimals.
+use cosmwasm_std::{Deps, Uint128};
+
+use crate::msg::{DenomMetadata, DenomMetadataResponse};
+use crate::state::Config;
+
+/// Resolves the display metadata of the collateral denom
+pub fn denom_metadata(deps: Deps, config: &Config) -> DenomMetadataResponse {
+    let metadata = config
+        .denom_metadata
+        .clone()
+        .or_else(|| chain_metadata(deps, &config.denom));
+    match metadata {
+        Some(DenomMetadata {
+            decimals,
+            display,
+            symbol,
+        }) => DenomMetadataResponse {
+            denom: config.denom.clone(),
+            decimals,
+            display,
+            symbol,
+        },
+        None => DenomMetadataResponse {
+            denom: config.denom.clone(),
+            decimals: 0,
+            display: config.denom.clone(),
+            symbol: config.denom.clone(),
+        },
+    }
+}
+
+/// Display metadata from the bank module, if the denom has a display unit registered there
+fn chain_metadata(deps: Deps, denom: &str) -> Option<DenomMetadata> {
+    let metadata = deps.querier.query_denom_metadata(denom).ok()?;
+    let unit = metadata
+        .denom_units
+        .iter()
+        .find(|unit| !metadata.display.is_empty() && unit.denom == metadata.display)?;
+    let symbol = if metadata.symbol.is_empty() {
+        metadata.display.clone()
+    } else {
+        metadata.symbol.clone()
+    };
+    Some(DenomMetadata {
+        decimals: unit.exponent,
+        display: metadata.display.clone(),
+        symbol,
+    })
+}
+
+/// Formats a base denom `amount` in display units, e.g. `1500000` with 6 decimals as `1.5`
+pub fn display_amount(amount: Uint128, decimals: u32) -> String {
+    let digits = amount.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_owned()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// `display_amount` and `display_denom` event attributes of a base denom `amount`
+pub fn display_attributes(deps: Deps, config: &Config, amount: Uint128) -> Vec<(String, String)> {
+    let metadata = denom_metadata(deps, config);
+    vec![
+        (
+            "display_amount".to_owned(),
+            display_amount(amount, metadata.decimals),
+        ),
+        ("display_denom".to_owned(), metadata.display),
+    ]
+}
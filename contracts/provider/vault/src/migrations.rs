@@ -0,0 +1,142 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, DepsMut, Env, Order, StdResult, Uint128};
+use cw_storage_plus::Item;
+use mesh_apis::migration::{Upgrade, OWNERSHIP_VERSION};
+use mesh_apis::ownership_api;
+use std::collections::BTreeMap;
+
+use crate::contract::VaultContract;
+use crate::error::ContractError;
+use crate::state::{Config, VaultStats};
+
+/// State migration, run when migrating from a version older than `version`
+pub struct Migration {
+    pub version: &'static str,
+    /// Short description, reported in the migration event
+    pub name: &'static str,
+    pub run: fn(&VaultContract, DepsMut, &Env) -> Result<(), ContractError>,
+}
+
+/// All the state migrations, ordered by version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: OWNERSHIP_VERSION,
+        name: "reindex_liens",
+        run: reindex_liens,
+    },
+    Migration {
+        version: OWNERSHIP_VERSION,
+        name: "move_owner",
+        run: move_owner,
+    },
+    Migration {
+        version: OWNERSHIP_VERSION,
+        name: "init_stats",
+        run: init_stats,
+    },
+];
+
+/// Runs, in order, the migrations of the versions crossed by `upgrade`.
+/// Returns the names of the migrations that were run
+pub fn run_migrations(
+    contract: &VaultContract,
+    mut deps: DepsMut,
+    env: &Env,
+    upgrade: &Upgrade,
+) -> Result<Vec<&'static str>, ContractError> {
+    let mut applied = vec![];
+    for migration in MIGRATIONS {
+        if upgrade.crosses(migration.version)? {
+            (migration.run)(contract, deps.branch(), env)?;
+            applied.push(migration.name);
+        }
+    }
+    Ok(applied)
+}
+
+/// Liens used to be a plain map, without the amount and lienholder indexes. Saving them again
+/// builds their index entries
+fn reindex_liens(contract: &VaultContract, deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+    let storage = deps.storage;
+    let liens = contract
+        .liens
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for ((user, lienholder), lien) in liens {
+        contract.liens.save(storage, (&user, &lienholder), &lien)?;
+    }
+    Ok(())
+}
+
+/// `Config` from before the owner was moved to the shared ownership storage. The baseline
+/// release had no owner at all, only the denom
+#[cw_serde]
+struct LegacyConfig {
+    denom: String,
+    #[serde(default)]
+    owner: Option<Addr>,
+    #[serde(default)]
+    receipt_denom: Option<String>,
+    #[serde(default)]
+    min_bond: Uint128,
+    #[serde(default)]
+    min_unbond: Uint128,
+}
+
+/// Moves the owner from the config to the shared ownership storage. Vaults without owner in
+/// their config get their wasm admin as owner
+fn move_owner(contract: &VaultContract, deps: DepsMut, env: &Env) -> Result<(), ContractError> {
+    let legacy: Item<LegacyConfig> = Item::new("config");
+    let LegacyConfig {
+        denom,
+        owner,
+        receipt_denom,
+        min_bond,
+        min_unbond,
+    } = legacy.load(deps.storage)?;
+    let owner = match owner {
+        Some(owner) => Some(owner),
+        None => ownership_api::wasm_admin(&deps.querier, &env.contract.address)?,
+    };
+
+    let config = Config {
+        denom,
+        receipt_denom,
+        min_bond,
+        min_unbond,
+        max_utilization: None,
+        unbonding_period: None,
+        strategy: None,
+        max_lienholders: None,
+        denom_metadata: None,
+    };
+    contract.config.save(deps.storage, &config)?;
+    ownership_api::migrate_owner(deps.storage, owner)?;
+    Ok(())
+}
+
+/// The vault stats and lienholder totals used not to be maintained. Computes them once from all
+/// the users and liens, they are kept in sync from then on
+fn init_stats(contract: &VaultContract, deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+    let storage = deps.storage;
+    let mut stats = VaultStats::default();
+    for item in contract.users.range(storage, None, None, Order::Ascending) {
+        let (_, user) = item?;
+        stats.total_collateral += user.collateral;
+        stats.total_slashable += user.total_slashable.high();
+        stats.accounts += 1;
+    }
+    contract.stats.save(storage, &stats)?;
+
+    let mut totals = BTreeMap::<Addr, Uint128>::new();
+    for item in contract.liens.range(storage, None, None, Order::Ascending) {
+        let ((_, lienholder), lien) = item?;
+        *totals.entry(lienholder).or_default() += lien.amount.high();
+    }
+    for (lienholder, total) in totals {
+        contract
+            .lienholder_totals
+            .save(storage, &lienholder, &total)?;
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Binary, Uint128};
+use cosmwasm_std::{Binary, Coin, Decimal, Timestamp, Uint128};
+use cw_utils::Expiration;
 use mesh_sync::{Tx, ValueRange};
 
 /// This is the info used to construct the native staking contract
@@ -40,6 +41,18 @@ pub struct AccountResponse {
     pub free: ValueRange<Uint128>,
 }
 
+#[cw_serde]
+pub struct UnbondingClaim {
+    pub amount: Uint128,
+    /// Time the claim can be withdrawn at, with `claim_matured`
+    pub release_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct ClaimsResponse {
+    pub claims: Vec<UnbondingClaim>,
+}
+
 #[cw_serde]
 pub struct AccountDetailsResponse {
     // Everything is denom, changing all Uint128 to coin with the same denom seems very inefficient
@@ -48,6 +61,29 @@ pub struct AccountDetailsResponse {
     pub free: ValueRange<Uint128>,
     pub max_lien: ValueRange<Uint128>,
     pub total_slashable: ValueRange<Uint128>,
+    /// All the account liens
+    pub liens: Vec<LienDetails>,
+    /// Claims waiting for the lienholder to commit or roll back their stake
+    pub pending_claims: Vec<PendingClaim>,
+    /// Collateral to be freed by the pending delayed releases
+    pub incoming: Uint128,
+}
+
+#[cw_serde]
+pub struct LienDetails {
+    pub lienholder: String,
+    pub amount: ValueRange<Uint128>,
+    /// Slashable part of the lien
+    pub slashable: Decimal,
+    /// Whether the lienholder is the local staking contract
+    pub local: bool,
+}
+
+#[cw_serde]
+pub struct PendingClaim {
+    pub tx_id: u64,
+    pub lienholder: String,
+    pub amount: Uint128,
 }
 
 impl AccountResponse {
@@ -76,16 +112,305 @@ pub struct AccountClaimsResponse {
     pub claims: Vec<LienResponse>,
 }
 
+#[cw_serde]
+pub struct AccountLiensResponse {
+    pub liens: Vec<LienDetails>,
+}
+
+#[cw_serde]
+pub struct UsersByLienholderResponse {
+    pub users: Vec<LienholderUser>,
+}
+
+#[cw_serde]
+pub struct LienholderUser {
+    pub user: String,
+    pub amount: ValueRange<Uint128>,
+    pub slashable: Decimal,
+}
+
+/// Ordering of the `account_liens` query results
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum LienOrder {
+    /// By lienholder address, ascending
+    #[default]
+    Lienholder,
+    /// By lien (high) amount, descending
+    AmountDesc,
+}
+
+/// Lienholder filter of the `account_liens` query
+#[cw_serde]
+#[derive(Copy)]
+pub enum LienholderKind {
+    /// The local staking contract
+    Local,
+    /// Cross-staking contracts
+    Remote,
+}
+
 #[cw_serde]
 pub struct LienResponse {
     pub lienholder: String,
     pub amount: ValueRange<Uint128>,
 }
 
+#[cw_serde]
+pub struct AutoRestakeResponse {
+    pub lienholder: String,
+    pub msg: Binary,
+    pub cap: Uint128,
+    pub period: u64,
+    pub period_start: Timestamp,
+    pub restaked: Uint128,
+}
+
 #[cw_serde]
 pub struct ConfigResponse {
     pub denom: String,
     pub local_staking: Option<String>,
+    pub receipt_denom: Option<String>,
+    pub min_bond: Uint128,
+    pub min_unbond: Uint128,
+    pub max_utilization: Option<Decimal>,
+    pub unbonding_period: Option<u64>,
+    pub strategy: Option<String>,
+    pub max_lienholders: u32,
+}
+
+/// Display metadata of the collateral denom, set at instantiation
+#[cw_serde]
+pub struct DenomMetadata {
+    /// Exponent of the display unit, e.g. 6 for `uosmo` displayed in `osmo`
+    pub decimals: u32,
+    /// Display unit, e.g. `osmo`
+    pub display: String,
+    /// Ticker symbol, e.g. `OSMO`
+    pub symbol: String,
+}
+
+#[cw_serde]
+pub struct DenomMetadataResponse {
+    /// Base denom of the collateral
+    pub denom: String,
+    pub decimals: u32,
+    pub display: String,
+    pub symbol: String,
+}
+
+/// Rewards of a user from a single cross-staking lienholder
+#[cw_serde]
+pub struct LienholderRewards {
+    pub lienholder: String,
+    pub rewards: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct PendingAllRewardsResponse {
+    /// Rewards pooled in the local staking contract, if any is configured
+    pub local: Option<Coin>,
+    /// Rewards of every cross-staking lienholder with any
+    pub cross: Vec<LienholderRewards>,
+    /// Sum of all the rewards, by denom
+    pub total: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct LienholderStake {
+    pub lienholder: String,
+    /// Sum of the (high) lien amounts
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct VaultStatsResponse {
+    pub total_collateral: Uint128,
+    /// Sum of the (high) liens of the local staking contract
+    pub total_local_staked: Uint128,
+    /// Sum of the (high) liens of every cross-staking lienholder
+    pub cross_staked: Vec<LienholderStake>,
+    pub accounts: u64,
+    /// Sum of the (high) total slashable amounts of all the accounts
+    pub total_slashable: Uint128,
+}
+
+/// Difference between a cached value of the vault and the one recomputed from the raw data
+#[cw_serde]
+pub struct InvariantDiscrepancy {
+    /// Name of the cached value, e.g. `max_lien` or `stats.total_collateral`
+    pub invariant: String,
+    /// Account of the value, for the per-account ones
+    pub account: Option<String>,
+    /// Lienholder of the value, for the per-lienholder ones
+    pub lienholder: Option<String>,
+    /// Value recomputed from the raw data
+    pub expected: String,
+    /// Cached value
+    pub actual: String,
+}
+
+#[cw_serde]
+pub struct InvariantsResponse {
+    /// Number of accounts checked
+    pub accounts: u64,
+    pub discrepancies: Vec<InvariantDiscrepancy>,
+}
+
+#[cw_serde]
+pub struct PausedLienholdersResponse {
+    pub lienholders: Vec<String>,
+}
+
+#[cw_serde]
+pub struct UtilizationResponse {
+    pub total_collateral: Uint128,
+    /// Sum of the (high) total slashable amounts of all the accounts
+    pub total_slashable: Uint128,
+    /// `total_slashable / total_collateral`, zero if nothing is bonded
+    pub utilization: Decimal,
+    pub max_utilization: Option<Decimal>,
+}
+
+/// Outcome of a simulated stake or unbond
+#[cw_serde]
+pub struct SimulationResponse {
+    /// Whether the operation would succeed
+    pub success: bool,
+    /// Why the operation would fail, if it would
+    pub error: Option<String>,
+    /// Free collateral of the account after the operation, unchanged if it would fail
+    pub free: ValueRange<Uint128>,
+}
+
+#[cw_serde]
+pub struct StrategyDepositResponse {
+    /// Shares of the vault deposits in the strategy
+    pub shares: Uint128,
+    /// Deposited amount
+    pub principal: Uint128,
+    /// Current value of the shares, including the accrued yield
+    pub value: Uint128,
+}
+
+#[cw_serde]
+pub struct HooksResponse {
+    pub hooks: Vec<String>,
+}
+
+/// Aggregated liens of all the lienholders registered for a consumer chain
+#[cw_serde]
+pub struct ChainExposure {
+    pub chain_id: String,
+    pub lienholders: Vec<String>,
+    /// Sum of the (high) lien amounts
+    pub liens: Uint128,
+    /// Sum of the lien amounts times their slashable ratio, being the most collateral the
+    /// chain can slash
+    pub max_slash: Uint128,
+}
+
+#[cw_serde]
+pub struct ExposureByChainResponse {
+    pub chains: Vec<ChainExposure>,
+}
+
+/// Vault messages an account can grant another address permission to execute on its behalf
+#[cw_serde]
+#[derive(Copy)]
+pub enum GrantedMsgType {
+    Bond,
+    StakeRemote,
+    StakeLocal,
+    WithdrawRewards,
+}
+
+impl GrantedMsgType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrantedMsgType::Bond => "bond",
+            GrantedMsgType::StakeRemote => "stake_remote",
+            GrantedMsgType::StakeLocal => "stake_local",
+            GrantedMsgType::WithdrawRewards => "withdraw_rewards",
+        }
+    }
+}
+
+/// Vault message executed by a grantee on behalf of the granter.
+/// Arguments are the same as in the corresponding exec message.
+#[cw_serde]
+pub enum GrantedMsg {
+    /// Bonds the funds sent along as collateral of the granter
+    Bond {},
+    StakeRemote {
+        contract: String,
+        amount: Coin,
+        msg: Binary,
+    },
+    StakeLocal {
+        amount: Coin,
+        msg: Binary,
+    },
+    /// Withdraws the granter rewards from staking via `validator` on the cross-staking
+    /// `contract`, to the consumer-side recipient set by the granter in the grant.
+    /// Not available with local staking, whose rewards are withdrawn by the user proxy.
+    WithdrawRewards {
+        contract: String,
+        validator: String,
+    },
+}
+
+impl GrantedMsg {
+    pub fn msg_type(&self) -> GrantedMsgType {
+        match self {
+            GrantedMsg::Bond { .. } => GrantedMsgType::Bond,
+            GrantedMsg::StakeRemote { .. } => GrantedMsgType::StakeRemote,
+            GrantedMsg::StakeLocal { .. } => GrantedMsgType::StakeLocal,
+            GrantedMsg::WithdrawRewards { .. } => GrantedMsgType::WithdrawRewards,
+        }
+    }
+}
+
+/// Item of a custodian batch, on behalf of `account`
+#[cw_serde]
+pub struct BatchItem {
+    pub account: String,
+    /// Amount in the collateral denom
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct GrantInfo {
+    /// Granted message type, as in `GrantedMsgType::as_str`
+    pub msg_type: String,
+    pub expiration: Expiration,
+    /// Recipient of the withdrawn rewards, for `withdraw_rewards` grants
+    pub remote_recipient: Option<String>,
+}
+
+#[cw_serde]
+pub struct GrantsResponse {
+    pub grants: Vec<GrantInfo>,
+}
+
+/// `stake_remote` approval signed off-chain by `owner`, and submitted by anyone (i.e. a relayer)
+#[cw_serde]
+pub struct StakeRemotePermit {
+    pub owner: String,
+    /// Contract to virtually stake on
+    pub lienholder: String,
+    pub amount: Coin,
+    /// Action to take with the stake, as in `stake_remote`
+    pub msg: Binary,
+    /// Any number not used by the owner yet, so the permit can only be submitted once
+    pub nonce: u64,
+    /// Time (in seconds) from which the permit is rejected
+    pub expiry: u64,
+}
+
+#[cw_serde]
+pub struct PermitNonceResponse {
+    pub used: bool,
 }
 
 #[cw_serde]
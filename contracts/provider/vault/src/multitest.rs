@@ -1,5 +1,14 @@
-use cosmwasm_std::{coin, coins, to_json_binary, Addr, Decimal, Uint128, Validator};
-use cw_multi_test::{App as MtApp, StakingInfo};
+mod cross_staking;
+mod hook_mock;
+mod strategy_mock;
+
+use cosmwasm_std::{
+    coin, coins, to_json_binary, Addr, Attribute, Binary, Decimal, Order, StdError, StdResult,
+    Uint128, Validator,
+};
+use cw_multi_test::{App as MtApp, StakingInfo, StargateAccepting};
+use cw_utils::{Expiration, PaymentError};
+use mesh_apis::error::MeshError;
 use mesh_apis::ibc::AddValidator;
 use mesh_external_staking::contract::sv::mt::ExternalStakingContractProxy;
 use mesh_external_staking::contract::ExternalStakingContract;
@@ -14,7 +23,13 @@ use mesh_sync::Tx::InFlightStaking;
 use mesh_sync::{Tx, ValueRange};
 use sylvia::multitest::{App, Proxy};
 
+use mesh_apis::cross_staking_api::sv::mt::CrossStakingApiProxy;
+use mesh_apis::local_staking_api::sv::mt::LocalStakingApiProxy;
+use mesh_apis::ownership_api::sv::mt::OwnershipApiProxy;
+use mesh_apis::ownership_api::OwnershipError;
 use mesh_apis::vault_api::sv::mt::VaultApiProxy;
+use mesh_apis::vault_api::SlashInfo;
+use mesh_apis::vault_hook_api::VaultHookMsg;
 use mesh_external_staking::test_methods::sv::mt::TestMethodsProxy;
 
 use crate::contract;
@@ -22,9 +37,18 @@ use crate::contract::sv::mt::VaultContractProxy;
 use crate::contract::VaultContract;
 use crate::error::ContractError;
 use crate::msg::{
-    AccountResponse, AllAccountsResponseItem, AllActiveExternalStakingResponse, LienResponse,
-    LocalStakingInfo, StakingInitInfo,
+    AccountResponse, AllAccountsResponseItem, AllActiveExternalStakingResponse, BatchItem,
+    ChainExposure, DenomMetadata, DenomMetadataResponse, GrantInfo, GrantedMsg, GrantedMsgType,
+    InvariantDiscrepancy, LienDetails, LienOrder, LienResponse, LienholderKind, LienholderRewards,
+    LienholderStake, LienholderUser, LocalStakingInfo, PendingAllRewardsResponse, PendingClaim,
+    SimulationResponse, StakeRemotePermit, StakingInitInfo, StrategyDepositResponse,
+    UnbondingClaim, UtilizationResponse, VaultStatsResponse,
 };
+use crate::multitest::cross_staking::sv::mt::CrossStakingMockProxy;
+use crate::multitest::cross_staking::FailureMode;
+use crate::multitest::hook_mock::sv::mt::HookMockProxy;
+use crate::multitest::strategy_mock::sv::mt::StrategyMockProxy;
+use crate::permits::{permit_hash, pubkey_address};
 
 const OSMO: &str = "OSMO";
 const STAR: &str = "star";
@@ -134,6 +158,7 @@ fn setup_inner<'app>(
             denom: OSMO.to_string(),
             slash_ratio_dsign: Decimal::percent(10),
             slash_ratio_offline: Decimal::percent(10),
+            owner: None,
             proxy_code_id: native_staking_proxy_code.code_id(),
         };
 
@@ -148,7 +173,7 @@ fn setup_inner<'app>(
     };
 
     let vault = vault_code
-        .instantiate(OSMO.to_owned(), staking_init_info)
+        .instantiate(OSMO.to_owned(), staking_init_info, None, None, None)
         .with_label("Vault")
         .call(owner)
         .unwrap();
@@ -183,6 +208,10 @@ fn setup_cross_stake<'app>(
                 double_sign: Decimal::percent(slash_percent),
                 offline: Decimal::percent(slash_percent),
             },
+            None,
+            0,
+            None,
+            None,
         )
         .call(owner)
         .unwrap()
@@ -223,6 +252,7 @@ fn stake_locally(
 ) -> Result<cw_multi_test::AppResponse, ContractError> {
     let msg = mesh_native_staking::msg::StakeMsg {
         validator: validator.to_string(),
+        proxy: None,
     };
 
     vault
@@ -242,10 +272,7 @@ fn stake_remotely(
             .stake_remote(
                 cross_staking.contract_addr.to_string(),
                 coin(*amount, OSMO),
-                to_json_binary(&ReceiveVirtualStake {
-                    validator: validator.to_string(),
-                })
-                .unwrap(),
+                ReceiveVirtualStake::new(*validator).encode().unwrap(),
             )
             .call(user)
             .unwrap();
@@ -309,6 +336,13 @@ fn skip_time(app: &App<MtApp>, skip_time: u64) {
     app.app_mut().set_block(block_info);
 }
 
+/// `err` as returned by a failing query, which only keeps the message of the contract error
+fn query_error(err: ContractError) -> ContractError {
+    ContractError::Std(StdError::generic_err(format!(
+        "Querier contract error: {err}"
+    )))
+}
+
 #[test]
 fn instantiation() {
     let owner = "owner";
@@ -449,301 +483,366 @@ fn bonding() {
 }
 
 #[test]
-fn local_staking_disabled() {
+fn bond_limits() {
     let owner = "owner";
     let user = "user1";
-    let local_val = "local";
-    let remote_val = "remote";
-
-    let mut app = init_app(&[user], &[300]);
-    add_local_validator(&mut app, local_val);
-
-    let (vault, cross_staking) = setup_without_local_staking(&app, owner, SLASHING_PERCENTAGE, 100);
 
-    set_active_validators(&cross_staking, &[remote_val]);
+    let app = init_app(&[user], &[300]);
 
-    bond(&vault, user, 300);
+    let (vault, _local_staking, _cross_staking) = setup(&app, owner, 0, 100);
 
-    assert_eq!(
-        stake_locally(&vault, user, 100, local_val).unwrap_err(),
-        ContractError::NoLocalStaking
-    );
-    assert_eq!(vault.config().unwrap().local_staking, None);
+    // Only the owner can update the limits
+    let err = vault
+        .update_bond_limits(Uint128::new(50), Uint128::new(20))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
 
-    // cross-staking still works
     vault
-        .stake_remote(
-            cross_staking.contract_addr.to_string(),
-            coin(100, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: remote_val.to_string(),
-            })
-            .unwrap(),
-        )
-        .call(user)
+        .update_bond_limits(Uint128::new(50), Uint128::new(20))
+        .call(owner)
         .unwrap();
+    let config = vault.config().unwrap();
+    assert_eq!(config.min_bond, Uint128::new(50));
+    assert_eq!(config.min_unbond, Uint128::new(20));
 
-    let acc = vault.account(user.to_owned()).unwrap();
+    // Dust bonds are rejected
+    let err = vault
+        .bond()
+        .with_funds(&coins(49, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::BondTooSmall(Uint128::new(50)));
+
+    bond(&vault, user, 50);
+
+    // Dust unbonds are rejected
+    let err = vault.unbond(coin(19, OSMO)).call(user).unwrap_err();
+    assert_eq!(err, ContractError::UnbondTooSmall(Uint128::new(20)));
+
+    vault.unbond(coin(35, OSMO)).call(user).unwrap();
+
+    // Unbonding all the remaining collateral is always allowed
+    vault.unbond(coin(15, OSMO)).call(user).unwrap();
     assert_eq!(
-        acc,
+        vault.account(user.to_owned()).unwrap(),
         AccountResponse {
             denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new(Uint128::new(200), Uint128::new(300)),
+            bonded: Uint128::zero(),
+            free: ValueRange::new_val(Uint128::zero()),
         }
     );
 }
 
 #[test]
-fn stake_local() {
+fn transfer_collateral() {
     let owner = "owner";
     let user = "user1";
-    let val = "validator";
+    let recipient = "user2";
+    let validator = "validator";
 
-    let mut app = init_app(&[user], &[300]);
-    add_local_validator(&mut app, val);
+    let app = init_app(&[user], &[300]);
 
-    let (vault, local_staking, _cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[validator]);
 
     bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[200]);
 
+    // Liened collateral cannot be transferred
+    let err = vault
+        .transfer_collateral(recipient.to_owned(), coin(150, OSMO))
+        .call(user)
+        .unwrap_err();
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(300)),
-        }
-    );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(claims.claims, []);
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(300, OSMO)
+        err,
+        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(100)))
     );
+
+    let err = vault
+        .transfer_collateral(recipient.to_owned(), coin(50, STAR))
+        .call(user)
+        .unwrap_err();
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&local_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
+        err,
+        ContractError::Mesh(MeshError::InvalidDenom(OSMO.to_owned()))
     );
 
-    // Staking locally
-    stake_locally(&vault, user, 100, val).unwrap();
+    vault
+        .transfer_collateral(recipient.to_owned(), coin(80, OSMO))
+        .call(user)
+        .unwrap();
 
     assert_eq!(
         vault.account(user.to_owned()).unwrap(),
         AccountResponse {
             denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(200)),
+            bonded: Uint128::new(220),
+            free: ValueRange::new_val(Uint128::new(20)),
         }
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(100))
-        }]
+        vault.account(recipient.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(80),
+            free: ValueRange::new_val(Uint128::new(80)),
+        }
     );
+    // No tokens moved
     assert_eq!(
         app.app()
             .wrap()
             .query_balance(&vault.contract_addr, OSMO)
             .unwrap(),
-        coin(200, OSMO)
+        coin(300, OSMO)
     );
 
-    stake_locally(&vault, user, 150, val).unwrap();
+    // Recipient can use the transferred collateral right away
+    vault.unbond(coin(80, OSMO)).call(recipient).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(recipient, OSMO).unwrap(),
+        coin(80, OSMO)
+    );
+}
+
+#[test]
+fn receipt_token() {
+    use crate::msg::ConfigResponse;
+    use cosmwasm_std::Coin;
+    use cw_multi_test::Executor;
+
+    let owner = "owner";
+    let user = "user1";
 
+    // Tokenfactory messages are accepted, but not executed. The sylvia multitest helpers
+    // require the default (failing) stargate module, so the raw multitest app is used
+    let mut app = cw_multi_test::AppBuilder::new()
+        .with_stargate(StargateAccepting)
+        .build(|router, _api, storage| {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(user), coins(300, OSMO))
+                .unwrap();
+        });
+
+    let code_id = app.store_code(Box::new(VaultContract::new()));
+    let vault = app
+        .instantiate_contract(
+            code_id,
+            Addr::unchecked(owner),
+            &contract::sv::InstantiateMsg::new(
+                OSMO.to_owned(),
+                None,
+                None,
+                Some("receipt".to_owned()),
+                None,
+            ),
+            &[],
+            "Vault",
+            None,
+        )
+        .unwrap();
+    let receipt_denom = format!("factory/{}/receipt", vault);
+    let config: ConfigResponse = app
+        .wrap()
+        .query_wasm_smart(&vault, &contract::sv::QueryMsg::Config {})
+        .unwrap();
+    assert_eq!(config.receipt_denom, Some(receipt_denom.clone()));
+
+    let user_addr = Addr::unchecked(user);
+    app.execute_contract(
+        user_addr.clone(),
+        vault.clone(),
+        &contract::sv::ExecMsg::Bond {},
+        &coins(100, OSMO),
+    )
+    .unwrap();
+    // Stand-in for the minted receipt tokens
+    app.init_modules(|router, _, storage| {
+        router.bank.init_balance(
+            storage,
+            &user_addr,
+            vec![coin(200, OSMO), coin(100, &receipt_denom)],
+        )
+    })
+    .unwrap();
+
+    // Unbonding requires the receipt tokens back
+    let mut unbond = |funds: &[Coin]| {
+        app.execute_contract(
+            user_addr.clone(),
+            vault.clone(),
+            &contract::sv::ExecMsg::Unbond {
+                amount: coin(50, OSMO),
+            },
+            funds,
+        )
+        .map_err(|err| err.downcast::<ContractError>().unwrap())
+    };
+    let err = unbond(&[]).unwrap_err();
+    assert_eq!(err, ContractError::Payment(PaymentError::NoFunds {}));
+    let err = unbond(&coins(40, &receipt_denom)).unwrap_err();
+    assert_eq!(err, ContractError::InvalidReceiptAmount(Uint128::new(50)));
+    unbond(&coins(50, &receipt_denom)).unwrap();
+
+    let account: AccountResponse = app
+        .wrap()
+        .query_wasm_smart(
+            &vault,
+            &contract::sv::QueryMsg::Account {
+                account: user.to_owned(),
+            },
+        )
+        .unwrap();
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
+        account,
         AccountResponse {
             denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
+            bonded: Uint128::new(50),
             free: ValueRange::new_val(Uint128::new(50)),
         }
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(250))
-        }]
-    );
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(50, OSMO)
+        app.wrap().query_all_balances(user).unwrap(),
+        vec![coin(250, OSMO), coin(50, &receipt_denom)]
     );
+}
 
-    // Cannot stake over collateral
-
-    let err = stake_locally(&vault, user, 150, val).unwrap_err();
-
-    assert_eq!(err, ContractError::InsufficentBalance);
+#[test]
+fn denom_metadata() {
+    let owner = "owner";
+    let user = "user1";
 
-    // Cannot unbond used collateral
+    let app = init_app(&[user], &[3_000_000]);
+    let code_id = contract::sv::mt::CodeId::store_code(&app);
 
-    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
+    // Nothing configured, nor registered in the bank module: the base denom is displayed
+    let vault = code_id
+        .instantiate(OSMO.to_owned(), None, None, None, None)
+        .call(owner)
+        .unwrap();
     assert_eq!(
-        err,
-        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(50)))
+        vault.denom_metadata().unwrap(),
+        DenomMetadataResponse {
+            denom: OSMO.to_owned(),
+            decimals: 0,
+            display: OSMO.to_owned(),
+            symbol: OSMO.to_owned(),
+        }
     );
 
-    // Unstaking
+    let err = code_id
+        .instantiate(
+            OSMO.to_owned(),
+            None,
+            None,
+            None,
+            Some(DenomMetadata {
+                decimals: 6,
+                display: "".to_owned(),
+                symbol: "OSMO".to_owned(),
+            }),
+        )
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidDenomMetadata);
 
-    let proxy = proxy_for_user(&local_staking, user, &app);
-    proxy
-        .unstake(val.to_string(), coin(50, OSMO))
-        .call(user)
+    let vault = code_id
+        .instantiate(
+            OSMO.to_owned(),
+            None,
+            None,
+            None,
+            Some(DenomMetadata {
+                decimals: 6,
+                display: "osmo".to_owned(),
+                symbol: "OSMO".to_owned(),
+            }),
+        )
+        .call(owner)
         .unwrap();
-    process_staking_unbondings(&app);
-    proxy.release_unbonded().call(user).unwrap();
-
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
+        vault.denom_metadata().unwrap(),
+        DenomMetadataResponse {
             denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(100)),
+            decimals: 6,
+            display: "osmo".to_owned(),
+            symbol: "OSMO".to_owned(),
         }
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(200))
-        }]
-    );
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(100, OSMO)
-    );
 
-    proxy
-        .unstake(val.to_string(), coin(100, OSMO))
+    // Amounts are also emitted in display units
+    let display_attrs = |resp: &cw_multi_test::AppResponse| -> Vec<Attribute> {
+        resp.events
+            .iter()
+            .flat_map(|event| event.attributes.iter())
+            .filter(|attr| attr.key.starts_with("display_"))
+            .cloned()
+            .collect()
+    };
+    let resp = vault
+        .bond()
+        .with_funds(&coins(1_500_000, OSMO))
         .call(user)
         .unwrap();
-    process_staking_unbondings(&app);
-    proxy.release_unbonded().call(user).unwrap();
-
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(200)),
-        }
+        display_attrs(&resp),
+        vec![
+            Attribute::new("display_amount", "1.5"),
+            Attribute::new("display_denom", "osmo"),
+        ]
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    let resp = vault.unbond(coin(500_001, OSMO)).call(user).unwrap();
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: local_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(100))
-        }]
+        display_attrs(&resp),
+        vec![
+            Attribute::new("display_amount", "0.500001"),
+            Attribute::new("display_denom", "osmo"),
+        ]
     );
+    let resp = vault.unbond(coin(999_999, OSMO)).call(user).unwrap();
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(200, OSMO)
+        display_attrs(&resp),
+        vec![
+            Attribute::new("display_amount", "0.999999"),
+            Attribute::new("display_denom", "osmo"),
+        ]
     );
-
-    // Cannot unstake over the lien
-
-    // TODO: catch subcall error here
-    // let err = proxy
-    //     .unstake(val.to_string(), coin(200, OSMO))
-    //     .call(user)
-    //     .unwrap_err();
-    // assert_eq!(
-    //     err,
-    //     mesh_native_staking_proxy::error::ContractError::Unauthorized {}
-    // );
 }
 
 #[test]
-fn stake_cross() {
+fn local_staking_disabled() {
     let owner = "owner";
     let user = "user1";
+    let local_val = "local";
+    let remote_val = "remote";
 
-    let app = init_app(&[user], &[300]);
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, local_val);
 
-    let unbond_period = 100;
-    let (vault, _local_staking, cross_staking) =
-        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
+    let (vault, cross_staking) = setup_without_local_staking(&app, owner, SLASHING_PERCENTAGE, 100);
 
-    // Set active validator
-    let validator = "validator";
-    set_active_validators(&cross_staking, &[validator]);
+    set_active_validators(&cross_staking, &[remote_val]);
 
-    // Bond some tokens
     bond(&vault, user, 300);
 
     assert_eq!(
-        vault.account(user.to_owned()).unwrap(),
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(300)),
-        }
-    );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(claims.claims, []);
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(300, OSMO)
-    );
-    assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
+        stake_locally(&vault, user, 100, local_val).unwrap_err(),
+        ContractError::NoLocalStaking
     );
+    assert_eq!(vault.config().unwrap().local_staking, None);
 
-    let res = vault.active_external_staking().unwrap();
-    assert_eq!(res, AllActiveExternalStakingResponse { contracts: vec![] });
-
-    // Staking remotely
+    // cross-staking still works
     vault
         .stake_remote(
             cross_staking.contract_addr.to_string(),
             coin(100, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(remote_val).encode().unwrap(),
         )
         .call(user)
         .unwrap();
 
-    let res = vault.active_external_staking().unwrap();
-    assert_eq!(
-        res,
-        AllActiveExternalStakingResponse {
-            contracts: vec![cross_staking.contract_addr.to_string()],
-        }
-    );
-
     let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
         acc,
@@ -753,169 +852,1675 @@ fn stake_cross() {
             free: ValueRange::new(Uint128::new(200), Uint128::new(300)),
         }
     );
+}
 
-    // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
-    // This should be through `IbcPacketAckMsg`
-    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
-    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
-    cross_staking
-        .test_commit_stake(last_external_staking_tx)
-        .call("test")
-        .unwrap();
+#[test]
+fn account_liens() {
+    let owner = "owner";
+    let user = "user1";
+    let local_val = "local";
+    let remote_val = "remote";
 
-    let acc = vault.account(user.to_owned()).unwrap();
-    assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(200)),
-        }
-    );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, local_staking, cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let cross_staking2 = setup_cross_stake(&app, owner, &vault, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking1, &[remote_val]);
+    set_active_validators(&cross_staking2, &[remote_val]);
+
+    bond(&vault, user, 1000);
+    stake_locally(&vault, user, 200, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking1, user, &[remote_val], &[100]);
+    stake_remotely(&vault, &cross_staking2, user, &[remote_val], &[300]);
+
+    let lienholders = |order, kind, start_after: Option<&str>, limit| {
+        vault
+            .account_liens(
+                user.to_owned(),
+                order,
+                kind,
+                start_after.map(str::to_owned),
+                limit,
+            )
+            .unwrap()
+            .liens
+            .into_iter()
+            .map(|lien| (lien.lienholder, lien.amount.high().u128()))
+            .collect::<Vec<_>>()
+    };
+
+    let local = local_staking.contract_addr.to_string();
+    let remote1 = cross_staking1.contract_addr.to_string();
+    let remote2 = cross_staking2.contract_addr.to_string();
+
+    // Sorted by amount, descending
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: cross_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(100))
-        }]
+        lienholders(LienOrder::AmountDesc, None, None, None),
+        [
+            (remote2.clone(), 300),
+            (local.clone(), 200),
+            (remote1.clone(), 100)
+        ]
     );
+    // Paginated by amount
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(300, OSMO)
+        lienholders(LienOrder::AmountDesc, None, None, Some(1)),
+        [(remote2.clone(), 300)]
     );
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
+        lienholders(LienOrder::AmountDesc, None, Some(&remote2), None),
+        [(local.clone(), 200), (remote1.clone(), 100)]
     );
 
-    // second stake remote
-    vault
-        .stake_remote(
-            cross_staking.contract_addr.to_string(),
-            coin(150, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
-        )
-        .call(user)
-        .unwrap();
-
-    let acc = vault.account(user.to_owned()).unwrap();
+    // Filtered by lienholder kind
     assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new(Uint128::new(50), Uint128::new(200)),
-        }
+        lienholders(
+            LienOrder::AmountDesc,
+            Some(LienholderKind::Remote),
+            None,
+            None
+        ),
+        [(remote2.clone(), 300), (remote1.clone(), 100)]
     );
-
-    // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
-    // This should be through `IbcPacketAckMsg`
-    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
-    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
-    cross_staking
-        .test_commit_stake(last_external_staking_tx)
-        .call("test")
-        .unwrap();
-
-    let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
-        }
+        lienholders(
+            LienOrder::Lienholder,
+            Some(LienholderKind::Local),
+            None,
+            None
+        ),
+        [(local.clone(), 200)]
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+
+    // Amount index follows lien updates
+    stake_remotely(&vault, &cross_staking1, user, &[remote_val], &[250]);
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: cross_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(250))
-        }]
+        lienholders(
+            LienOrder::AmountDesc,
+            Some(LienholderKind::Remote),
+            None,
+            None
+        ),
+        [(remote1, 350), (remote2, 300)]
     );
+}
+
+#[test]
+fn users_by_lienholder() {
+    let owner = "owner";
+    let users = ["user1", "user2", "user3"];
+    let remote_val = "remote";
+
+    let app = init_app(&users, &[1000, 1000, 1000]);
+
+    let (vault, cross_staking1) =
+        setup_without_local_staking(&app, owner, SLASHING_PERCENTAGE, 100);
+    let cross_staking2 = setup_cross_stake(&app, owner, &vault, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking1, &[remote_val]);
+    set_active_validators(&cross_staking2, &[remote_val]);
+
+    for user in users {
+        bond(&vault, user, 1000);
+    }
+    stake_remotely(&vault, &cross_staking1, users[0], &[remote_val], &[100]);
+    stake_remotely(&vault, &cross_staking2, users[1], &[remote_val], &[200]);
+    stake_remotely(&vault, &cross_staking1, users[2], &[remote_val], &[300]);
+
+    let lien_users = |lienholder: &Addr, start_after: Option<&str>, limit| {
+        vault
+            .users_by_lienholder(
+                lienholder.to_string(),
+                start_after.map(str::to_owned),
+                limit,
+            )
+            .unwrap()
+            .users
+    };
+
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(300, OSMO)
+        lien_users(&cross_staking1.contract_addr, None, None),
+        [
+            LienholderUser {
+                user: users[0].to_owned(),
+                amount: ValueRange::new_val(Uint128::new(100)),
+                slashable: Decimal::percent(SLASHING_PERCENTAGE),
+            },
+            LienholderUser {
+                user: users[2].to_owned(),
+                amount: ValueRange::new_val(Uint128::new(300)),
+                slashable: Decimal::percent(SLASHING_PERCENTAGE),
+            },
+        ]
     );
+    let page = lien_users(&cross_staking1.contract_addr, None, Some(1));
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].user, users[0]);
+    let page = lien_users(&cross_staking1.contract_addr, Some(users[0]), None);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].user, users[2]);
+
+    let page = lien_users(&cross_staking2.contract_addr, None, None);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].user, users[1]);
+
+    // Same users, with their account summary
+    let accounts = vault
+        .accounts_by_lienholder(cross_staking1.contract_addr.to_string(), None, None)
+        .unwrap()
+        .accounts;
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
+        accounts,
+        [
+            AllAccountsResponseItem {
+                user: users[0].to_owned(),
+                account: AccountResponse::new(
+                    OSMO,
+                    Uint128::new(1000),
+                    ValueRange::new_val(Uint128::new(900))
+                ),
+            },
+            AllAccountsResponseItem {
+                user: users[2].to_owned(),
+                account: AccountResponse::new(
+                    OSMO,
+                    Uint128::new(1000),
+                    ValueRange::new_val(Uint128::new(700))
+                ),
+            },
+        ]
     );
+    let page = vault
+        .accounts_by_lienholder(
+            cross_staking1.contract_addr.to_string(),
+            Some(users[0].to_owned()),
+            Some(1),
+        )
+        .unwrap()
+        .accounts;
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].user, users[2]);
+}
 
-    // Cannot stake over collateral
+#[test]
+fn exposure_by_chain() {
+    let owner = "owner";
+    let user = "user1";
+    let remote_val = "remote";
+
+    let app = init_app(&[user], &[1000]);
+
+    let (vault, _local_staking, cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let cross_staking2 = setup_cross_stake(&app, owner, &vault, 20, 100);
+    let cross_staking3 = setup_cross_stake(&app, owner, &vault, SLASHING_PERCENTAGE, 100);
+    for cross_staking in [&cross_staking1, &cross_staking2, &cross_staking3] {
+        set_active_validators(cross_staking, &[remote_val]);
+    }
 
+    bond(&vault, user, 1000);
+    stake_remotely(&vault, &cross_staking1, user, &[remote_val], &[100]);
+    stake_remotely(&vault, &cross_staking2, user, &[remote_val], &[300]);
+    stake_remotely(&vault, &cross_staking3, user, &[remote_val], &[200]);
+
+    // Only the owner can register lienholders
     let err = vault
-        .stake_remote(
-            cross_staking.contract_addr.to_string(),
-            coin(150, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+        .set_lienholder_chain(
+            cross_staking1.contract_addr.to_string(),
+            Some("chain-a".to_owned()),
         )
         .call(user)
         .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
 
-    let acc = vault.account(user.to_owned()).unwrap();
+    for (cross_staking, chain_id) in [
+        (&cross_staking1, "chain-a"),
+        (&cross_staking2, "chain-a"),
+        (&cross_staking3, "chain-b"),
+    ] {
+        vault
+            .set_lienholder_chain(
+                cross_staking.contract_addr.to_string(),
+                Some(chain_id.to_owned()),
+            )
+            .call(owner)
+            .unwrap();
+    }
+
+    let mut chain_a_lienholders = vec![
+        cross_staking1.contract_addr.to_string(),
+        cross_staking2.contract_addr.to_string(),
+    ];
+    chain_a_lienholders.sort();
+    let chain_a = ChainExposure {
+        chain_id: "chain-a".to_owned(),
+        lienholders: chain_a_lienholders,
+        liens: Uint128::new(400),
+        // 10% of 100, and 20% of 300
+        max_slash: Uint128::new(70),
+    };
+    let chain_b = ChainExposure {
+        chain_id: "chain-b".to_owned(),
+        lienholders: vec![cross_staking3.contract_addr.to_string()],
+        liens: Uint128::new(200),
+        max_slash: Uint128::new(20),
+    };
     assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
-        }
+        vault.exposure_by_chain(None, None).unwrap().chains,
+        [chain_a.clone(), chain_b.clone()]
     );
-
-    assert_eq!(err, ContractError::InsufficentBalance);
-
-    // Cannot unbond used collateral
-
-    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
     assert_eq!(
-        err,
-        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(50)))
+        vault
+            .exposure_by_chain(Some("chain-a".to_owned()), None)
+            .unwrap()
+            .chains,
+        [chain_b]
     );
 
-    // Unstake does not free collateral on vault right away
-    cross_staking
-        .unstake(validator.to_owned(), coin(50, OSMO))
-        .call(user)
+    // Unregistered lienholders are not accounted
+    vault
+        .set_lienholder_chain(cross_staking3.contract_addr.to_string(), None)
+        .call(owner)
         .unwrap();
-
-    let acc = vault.account(user.to_owned()).unwrap();
     assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
-        }
+        vault.exposure_by_chain(None, None).unwrap().chains,
+        [chain_a]
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
-    assert_eq!(
-        claims.claims,
-        [LienResponse {
+}
+
+#[test]
+fn vault_stats() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&users, &[1000, 500]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    bond(&vault, users[0], 1000);
+    bond(&vault, users[1], 500);
+    stake_locally(&vault, users[0], 200, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking, users[0], &[remote_val], &[300]);
+    stake_remotely(&vault, &cross_staking, users[1], &[remote_val], &[100]);
+
+    let cross_staked = |amount| {
+        vec![LienholderStake {
             lienholder: cross_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(250))
+            amount: Uint128::new(amount),
         }]
-    );
+    };
+    assert_eq!(
+        vault.vault_stats().unwrap(),
+        VaultStatsResponse {
+            total_collateral: Uint128::new(1500),
+            total_local_staked: Uint128::new(200),
+            cross_staked: cross_staked(400),
+            accounts: 2,
+            // 10% of every lien
+            total_slashable: Uint128::new(60),
+        }
+    );
+
+    // Pending stakes are accounted for, as in the liens
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(50, OSMO),
+            ReceiveVirtualStake::new(remote_val).encode().unwrap(),
+        )
+        .call(users[1])
+        .unwrap();
+    let stats = vault.vault_stats().unwrap();
+    assert_eq!(stats.cross_staked, cross_staked(450));
+    assert_eq!(stats.total_slashable, Uint128::new(65));
+
+    // And removed when rolled back
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_rollback_stake(tx_id)
+        .call("test")
+        .unwrap();
+    vault.unbond(coin(400, OSMO)).call(users[1]).unwrap();
+    let stats = vault.vault_stats().unwrap();
+    assert_eq!(stats.total_collateral, Uint128::new(1100));
+    assert_eq!(stats.cross_staked, cross_staked(400));
+    assert_eq!(stats.total_slashable, Uint128::new(60));
+    assert_eq!(stats.accounts, 2);
+}
+
+#[test]
+fn stake_remote_with_permit() {
+    use k256::ecdsa::signature::hazmat::PrehashSigner;
+    use k256::ecdsa::{Signature, SigningKey};
+
+    let owner = "owner";
+    let relayer = "relayer";
+    let remote_val = "remote";
+
+    // Permits are signed with the key of the user account
+    let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+    let pubkey = Binary::from(key.verifying_key().to_encoded_point(true).as_bytes());
+    let user = pubkey_address("osmo", &pubkey).unwrap();
+    let user = user.as_str();
+
+    let app = init_app(&[user], &[1000]);
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+    bond(&vault, user, 1000);
+
+    let block = app.block_info();
+    let permit = |amount: u128, nonce: u64, expiry: u64| StakeRemotePermit {
+        owner: user.to_owned(),
+        lienholder: cross_staking.contract_addr.to_string(),
+        amount: coin(amount, OSMO),
+        msg: ReceiveVirtualStake::new(remote_val).encode().unwrap(),
+        nonce,
+        expiry,
+    };
+    let sign = |permit: &StakeRemotePermit| {
+        let hash = permit_hash(&block.chain_id, vault.contract_addr.as_str(), permit).unwrap();
+        let signature: Signature = key.sign_prehash(&hash).unwrap();
+        Binary::from(signature.to_bytes().as_slice())
+    };
+    let expiry = block.time.seconds() + 100;
+
+    // The key has to be the one of the user account
+    let permit1 = permit(300, 1, expiry);
+    let err = vault
+        .stake_remote_permit(permit1.clone(), Binary::from(&[1u8; 20]), sign(&permit1))
+        .call(relayer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidPermitKey);
+    let other_key = SigningKey::from_bytes(&[8u8; 32].into()).unwrap();
+    let other_pubkey = Binary::from(other_key.verifying_key().to_encoded_point(true).as_bytes());
+    let err = vault
+        .stake_remote_permit(permit1.clone(), other_pubkey, sign(&permit1))
+        .call(relayer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::PermitKeyMismatch(user.to_owned()));
+
+    // The relayer stakes on behalf of the user
+    vault
+        .stake_remote_permit(permit1.clone(), pubkey.clone(), sign(&permit1))
+        .call(relayer)
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking.test_commit_stake(tx_id).call("test").unwrap();
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.free, ValueRange::new_val(Uint128::new(700)));
+    assert!(vault.permit_nonce(user.to_owned(), 1).unwrap().used);
+
+    // Only once
+    let err = vault
+        .stake_remote_permit(permit1.clone(), pubkey.clone(), sign(&permit1))
+        .call(relayer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::PermitNonceUsed(1));
+
+    // Tampered, expired and invalidated permits are rejected
+    let permit2 = permit(100, 2, expiry);
+    let err = vault
+        .stake_remote_permit(permit(900, 2, expiry), pubkey.clone(), sign(&permit2))
+        .call(relayer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidPermitSignature);
+    let expired = permit(100, 2, block.time.seconds());
+    let err = vault
+        .stake_remote_permit(expired.clone(), pubkey.clone(), sign(&expired))
+        .call(relayer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::PermitExpired);
+    vault.invalidate_permit(2).call(user).unwrap();
+    let err = vault
+        .stake_remote_permit(permit2.clone(), pubkey, sign(&permit2))
+        .call(relayer)
+        .unwrap_err();
+    assert_eq!(err, ContractError::PermitNonceUsed(2));
+}
+
+#[test]
+fn checking_invariants() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&users, &[1000, 500]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    bond(&vault, users[0], 1000);
+    bond(&vault, users[1], 500);
+    stake_locally(&vault, users[0], 200, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking, users[0], &[remote_val], &[300]);
+    stake_remotely(&vault, &cross_staking, users[1], &[remote_val], &[100]);
+    vault.unbond(coin(100, OSMO)).call(users[1]).unwrap();
+
+    let check = vault.check_invariants(None).unwrap();
+    assert_eq!(check.accounts, 2);
+    assert_eq!(check.discrepancies, []);
+
+    // A cached value out of sync is reported, for the account and over all of them
+    vault
+        .test_set_max_lien(users[0].to_owned(), ValueRange::new_val(Uint128::new(1)))
+        .call(owner)
+        .unwrap();
+    let discrepancy = InvariantDiscrepancy {
+        invariant: "max_lien".to_owned(),
+        account: Some(users[0].to_owned()),
+        lienholder: None,
+        expected: ValueRange::new_val(Uint128::new(300)).to_string(),
+        actual: ValueRange::new_val(Uint128::new(1)).to_string(),
+    };
+    let check = vault.check_invariants(Some(users[0].to_owned())).unwrap();
+    assert_eq!(check.accounts, 1);
+    assert_eq!(check.discrepancies, [discrepancy.clone()]);
+    let check = vault.check_invariants(None).unwrap();
+    assert_eq!(check.discrepancies, [discrepancy]);
+    let check = vault.check_invariants(Some(users[1].to_owned())).unwrap();
+    assert_eq!(check.discrepancies, []);
+}
+
+#[test]
+fn utilization_cap() {
+    let owner = "owner";
+    let users = ["user1", "user2"];
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&users, &[600, 400]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    bond(&vault, users[0], 600);
+    bond(&vault, users[1], 400);
+
+    // Only the owner can set the cap, between zero (excluded) and one
+    let err = vault
+        .set_max_utilization(Some(Decimal::percent(5)))
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = vault
+        .set_max_utilization(Some(Decimal::zero()))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidMaxUtilization);
+    let err = vault
+        .set_max_utilization(Some(Decimal::percent(101)))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidMaxUtilization);
+
+    // At most 50 slashable tokens out of 1000
+    vault
+        .set_max_utilization(Some(Decimal::percent(5)))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        vault.config().unwrap().max_utilization,
+        Some(Decimal::percent(5))
+    );
+
+    stake_locally(&vault, users[0], 200, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking, users[1], &[remote_val], &[300]);
+    assert_eq!(
+        vault.utilization().unwrap(),
+        UtilizationResponse {
+            total_collateral: Uint128::new(1000),
+            total_slashable: Uint128::new(50),
+            utilization: Decimal::percent(5),
+            max_utilization: Some(Decimal::percent(5)),
+        }
+    );
+
+    // Both local and remote stakes are capped, whichever account they come from
+    let err = stake_locally(&vault, users[1], 10, local_val).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UtilizationCapReached(Decimal::percent(5))
+    );
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(10, OSMO),
+            ReceiveVirtualStake::new(remote_val).encode().unwrap(),
+        )
+        .call(users[0])
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UtilizationCapReached(Decimal::percent(5))
+    );
+
+    // Unbonding is not capped, but leaves even less room for new stakes
+    vault.unbond(coin(100, OSMO)).call(users[0]).unwrap();
+    assert_eq!(
+        vault.utilization().unwrap().utilization,
+        Decimal::from_ratio(50u128, 900u128)
+    );
+    let err = stake_locally(&vault, users[0], 10, local_val).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UtilizationCapReached(Decimal::percent(5))
+    );
+
+    // Removing the cap lifts the limit
+    vault.set_max_utilization(None).call(owner).unwrap();
+    stake_locally(&vault, users[0], 100, local_val).unwrap();
+    let utilization = vault.utilization().unwrap();
+    assert_eq!(utilization.total_slashable, Uint128::new(60));
+    assert_eq!(
+        utilization.utilization,
+        Decimal::from_ratio(60u128, 900u128)
+    );
+    assert_eq!(utilization.max_utilization, None);
+}
+
+#[test]
+fn max_lienholders() {
+    let owner = "owner";
+    let user = "user1";
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, _local_staking, cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let cross_staking2 = setup_cross_stake(&app, owner, &vault, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking1, &[remote_val]);
+    set_active_validators(&cross_staking2, &[remote_val]);
+    assert_eq!(
+        vault.config().unwrap().max_lienholders,
+        contract::DEFAULT_MAX_LIENHOLDERS
+    );
+
+    bond(&vault, user, 1000);
+
+    // Only the owner can set the limit, greater than zero
+    let err = vault.set_max_lienholders(Some(2)).call(user).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    let err = vault.set_max_lienholders(Some(0)).call(owner).unwrap_err();
+    assert_eq!(err, ContractError::InvalidMaxLienholders);
+
+    vault.set_max_lienholders(Some(2)).call(owner).unwrap();
+    assert_eq!(vault.config().unwrap().max_lienholders, 2);
+
+    // The local staking counts as a lienholder
+    stake_locally(&vault, user, 100, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking1, user, &[remote_val], &[100]);
+
+    let err = vault
+        .stake_remote(
+            cross_staking2.contract_addr.to_string(),
+            coin(100, OSMO),
+            ReceiveVirtualStake::new(remote_val).encode().unwrap(),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::TooManyLienholders(2));
+
+    // Existing lienholders can still be staked on
+    stake_locally(&vault, user, 100, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking1, user, &[remote_val], &[100]);
+
+    // Raising the limit allows a new one
+    vault.set_max_lienholders(Some(3)).call(owner).unwrap();
+    stake_remotely(&vault, &cross_staking2, user, &[remote_val], &[100]);
+    let acc = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(acc.claims.len(), 3);
+}
+
+#[test]
+fn simulate_stake_and_unbond() {
+    let owner = "owner";
+    let user = "user1";
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+    bond(&vault, user, 600);
+
+    let simulate_stake = |lienholder: &Addr, amount: u128| {
+        vault
+            .simulate_stake(user.to_owned(), lienholder.to_string(), coin(amount, OSMO))
+            .unwrap()
+    };
+
+    // Simulations match the actual stakes
+    let local = local_staking.contract_addr.clone();
+    let simulation = simulate_stake(&local, 200);
+    assert!(simulation.success);
+    assert_eq!(simulation.error, None);
+    stake_locally(&vault, user, 200, local_val).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        simulation.free
+    );
+
+    let remote = cross_staking.contract_addr.clone();
+    let simulation = simulate_stake(&remote, 500);
+    assert!(simulation.success);
+    // Remote stakes are in flight until committed, as simulated
+    assert_eq!(
+        simulation.free,
+        ValueRange::new(Uint128::new(100), Uint128::new(400))
+    );
+    stake_remotely(&vault, &cross_staking, user, &[remote_val], &[500]);
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(simulation.free.low())
+    );
+
+    // Failures are reported, with the account unchanged
+    let free = vault.account(user.to_owned()).unwrap().free;
+    assert_eq!(
+        simulate_stake(&remote, 700),
+        SimulationResponse {
+            success: false,
+            error: Some(ContractError::Mesh(MeshError::InsufficientBalance).to_string()),
+            free,
+        }
+    );
+    let simulation = vault
+        .simulate_stake(user.to_owned(), local.to_string(), coin(10, STAR))
+        .unwrap();
+    assert_eq!(
+        simulation.error,
+        Some(ContractError::Mesh(MeshError::InvalidDenom(OSMO.to_owned())).to_string())
+    );
+
+    vault
+        .set_max_utilization(Some(Decimal::percent(5)))
+        .call(owner)
+        .unwrap();
+    assert_eq!(
+        simulate_stake(&local, 10).error,
+        Some(ContractError::UtilizationCapReached(Decimal::percent(5)).to_string())
+    );
+    vault.set_max_utilization(None).call(owner).unwrap();
+
+    // Unbonds too
+    let simulation = vault
+        .simulate_unbond(user.to_owned(), coin(free.low().u128() + 1, OSMO))
+        .unwrap();
+    assert_eq!(
+        simulation.error,
+        Some(ContractError::ClaimsLocked(free).to_string())
+    );
+    let simulation = vault
+        .simulate_unbond(user.to_owned(), coin(50, OSMO))
+        .unwrap();
+    assert!(simulation.success);
+    vault.unbond(coin(50, OSMO)).call(user).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        simulation.free
+    );
+}
+
+#[test]
+fn delayed_cross_release() {
+    let owner = "owner";
+    let user = "user1";
+    let remote_val = "remote";
+
+    let app = init_app(&[user], &[1000]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    bond(&vault, user, 1000);
+    stake_remotely(&vault, &cross_staking, user, &[remote_val], &[300]);
+
+    let lienholder = cross_staking.contract_addr.as_str();
+    let now = app.app().block_info().time;
+    let release_at = now.plus_seconds(100);
+
+    // Only lienholders can release claims, in the future
+    let err = vault
+        .release_cross_stake_delayed(user.to_owned(), coin(100, OSMO), release_at)
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownLienholder);
+    let err = vault
+        .release_cross_stake_delayed(user.to_owned(), coin(100, OSMO), now)
+        .call(lienholder)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidReleaseTime);
+    let err = vault
+        .release_cross_stake_delayed(user.to_owned(), coin(400, OSMO), release_at)
+        .call(lienholder)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InsufficientLien);
+
+    vault
+        .release_cross_stake_delayed(user.to_owned(), coin(100, OSMO), release_at)
+        .call(lienholder)
+        .unwrap();
+
+    // Reported as incoming, but not free yet
+    let details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(details.incoming.u128(), 100);
+    assert_eq!(details.free, ValueRange::new_val(Uint128::new(700)));
+
+    // Nothing due yet
+    vault.finalize_releases(None).call(user).unwrap();
+    let details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(details.incoming.u128(), 100);
+
+    skip_time(&app, 100);
+    vault.finalize_releases(None).call(user).unwrap();
+    let details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(details.incoming.u128(), 0);
+    assert_eq!(details.free, ValueRange::new_val(Uint128::new(800)));
+    assert_eq!(
+        details.liens[0].amount,
+        ValueRange::new_val(Uint128::new(200))
+    );
+}
+
+#[test]
+fn unbonding_period() {
+    let owner = "owner";
+    let user = "user1";
+    let remote_val = "remote";
+
+    let app = init_app(&[user], &[1000]);
+
+    let (vault, _local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    // Only the owner can set the unbonding period
+    let err = vault
+        .set_unbonding_period(Some(100))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    vault.set_unbonding_period(Some(100)).call(owner).unwrap();
+    assert_eq!(vault.config().unwrap().unbonding_period, Some(100));
+
+    bond(&vault, user, 1000);
+    stake_remotely(&vault, &cross_staking, user, &[remote_val], &[300]);
+
+    // Most of the stake is released before the slashing evidence arrives
+    let lienholder = cross_staking.contract_addr.as_str();
+    vault
+        .release_cross_stake(user.to_owned(), coin(200, OSMO))
+        .call(lienholder)
+        .unwrap();
+
+    // Unbonded collateral is claimed, not sent
+    let release_at = app.app().block_info().time.plus_seconds(100);
+    vault.unbond(coin(800, OSMO)).call(user).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(0, OSMO)
+    );
+    assert_eq!(
+        vault.claims(user.to_owned(), None, None).unwrap().claims,
+        [UnbondingClaim {
+            amount: Uint128::new(800),
+            release_at,
+        }]
+    );
+
+    // The slash not covered by the lien is taken from the claim
+    vault
+        .cross_slash(
+            vec![SlashInfo {
+                user: user.to_owned(),
+                slash: Uint128::new(150),
+            }],
+            remote_val.to_owned(),
+        )
+        .call(lienholder)
+        .unwrap();
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(acc.bonded.u128(), 100);
+    assert_eq!(
+        vault.claims(user.to_owned(), None, None).unwrap().claims,
+        [UnbondingClaim {
+            amount: Uint128::new(750),
+            release_at,
+        }]
+    );
+
+    // Nothing to withdraw until the claim matures
+    let err = vault.claim_matured(None).call(user).unwrap_err();
+    assert_eq!(err, ContractError::NoClaim);
+
+    skip_time(&app, 100);
+    vault.claim_matured(None).call(user).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(750, OSMO)
+    );
+    assert_eq!(
+        vault.claims(user.to_owned(), None, None).unwrap().claims,
+        []
+    );
+
+    // Without a period, unbonded collateral is sent right away
+    vault.set_unbonding_period(None).call(owner).unwrap();
+    vault.unbond(coin(100, OSMO)).call(user).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(850, OSMO)
+    );
+}
+
+#[test]
+fn hooks() {
+    let owner = "owner";
+    let user = "user1";
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    let hook = hook_mock::sv::mt::CodeId::store_code(&app)
+        .instantiate()
+        .call(owner)
+        .unwrap();
+    let hook_addr = hook.contract_addr.to_string();
+
+    // Only the owner manages hooks, and only once each
+    let err = vault.add_hook(hook_addr.clone()).call(user).unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    vault.add_hook(hook_addr.clone()).call(owner).unwrap();
+    let err = vault.add_hook(hook_addr.clone()).call(owner).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::HookAlreadyRegistered(hook.contract_addr.clone())
+    );
+    assert_eq!(vault.hooks().unwrap().hooks, [hook_addr.clone()]);
+
+    bond(&vault, user, 1000);
+    stake_locally(&vault, user, 200, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[remote_val], &[300]);
+    assert_eq!(
+        hook.calls().unwrap().calls,
+        [
+            VaultHookMsg::AfterBond {
+                owner: user.to_owned(),
+                amount: coin(1000, OSMO),
+            },
+            VaultHookMsg::AfterStakeChange {
+                owner: user.to_owned(),
+                lienholder: local_staking.contract_addr.to_string(),
+                amount: Uint128::new(200),
+            },
+            // Reported once committed
+            VaultHookMsg::AfterStakeChange {
+                owner: user.to_owned(),
+                lienholder: cross_staking.contract_addr.to_string(),
+                amount: Uint128::new(300),
+            },
+        ]
+    );
+
+    // Hook failures don't revert the vault txs
+    hook.set_failing(true).call(owner).unwrap();
+    let resp = vault.unbond(coin(100, OSMO)).call(user).unwrap();
+    assert!(resp
+        .events
+        .iter()
+        .any(|event| event.ty == "wasm-hook_failed"));
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().bonded,
+        Uint128::new(900)
+    );
+    assert_eq!(hook.calls().unwrap().calls.len(), 3);
+
+    hook.set_failing(false).call(owner).unwrap();
+    vault.unbond(coin(100, OSMO)).call(user).unwrap();
+    assert_eq!(
+        hook.calls().unwrap().calls.last().unwrap(),
+        &VaultHookMsg::AfterUnbond {
+            owner: user.to_owned(),
+            amount: coin(100, OSMO),
+        }
+    );
+
+    vault.remove_hook(hook_addr.clone()).call(owner).unwrap();
+    assert_eq!(vault.hooks().unwrap().hooks, Vec::<String>::new());
+    let err = vault.remove_hook(hook_addr).call(owner).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::HookNotRegistered(hook.contract_addr.clone())
+    );
+    bond(&vault, user, 100);
+    assert_eq!(hook.calls().unwrap().calls.len(), 4);
+}
+
+#[test]
+fn strategy_deposits() {
+    let owner = "owner";
+    let user = "user1";
+    let funder = "funder";
+    let local_val = "local";
+
+    let mut app = init_app(&[user, funder], &[1000, 100]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, _local_staking, _cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    bond(&vault, user, 1000);
+
+    let strategy = strategy_mock::sv::mt::CodeId::store_code(&app)
+        .instantiate(OSMO.to_owned())
+        .call(owner)
+        .unwrap();
+    let strategy_addr = strategy.contract_addr.to_string();
+
+    // Nothing to deposit to until the owner sets a strategy
+    let err = vault
+        .deposit_to_strategy(coin(600, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoStrategy);
+    let err = vault
+        .set_strategy(Some(strategy_addr.clone()))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    vault
+        .set_strategy(Some(strategy_addr.clone()))
+        .call(owner)
+        .unwrap();
+    assert_eq!(vault.config().unwrap().strategy, Some(strategy_addr));
+
+    // Deposits are taken from the free collateral
+    vault
+        .deposit_to_strategy(coin(600, OSMO))
+        .call(user)
+        .unwrap();
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(acc.bonded, Uint128::new(400));
+    assert_eq!(acc.free, ValueRange::new_val(Uint128::new(400)));
+    let err = vault
+        .deposit_to_strategy(coin(500, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(400)))
+    );
+    let err = vault.set_strategy(None).call(owner).unwrap_err();
+    assert_eq!(err, ContractError::StrategyInUse);
+
+    // The yield accrues to the depositors
+    strategy
+        .accrue(vault.contract_addr.to_string())
+        .with_funds(&coins(60, OSMO))
+        .call(funder)
+        .unwrap();
+    assert_eq!(
+        vault.strategy_deposit(user.to_owned()).unwrap(),
+        StrategyDepositResponse {
+            shares: Uint128::new(600),
+            principal: Uint128::new(600),
+            value: Uint128::new(660),
+        }
+    );
+
+    // Staking withdraws the deposits back to the collateral first
+    stake_locally(&vault, user, 200, local_val).unwrap();
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(acc.bonded, Uint128::new(1060));
+    assert_eq!(acc.free, ValueRange::new_val(Uint128::new(860)));
+    assert_eq!(
+        vault.strategy_deposit(user.to_owned()).unwrap(),
+        StrategyDepositResponse {
+            shares: Uint128::zero(),
+            principal: Uint128::zero(),
+            value: Uint128::zero(),
+        }
+    );
+    let err = vault.withdraw_from_strategy().call(user).unwrap_err();
+    assert_eq!(err, ContractError::NoStrategyDeposit);
+
+    // And so does unbonding, so that the whole free collateral can be unbonded
+    vault
+        .deposit_to_strategy(coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    vault.unbond(coin(860, OSMO)).call(user).unwrap();
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(860, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&strategy.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+}
+
+#[test]
+fn stake_local() {
+    let owner = "owner";
+    let user = "user1";
+    let val = "validator";
+
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, val);
+
+    let (vault, local_staking, _cross_staking1) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    bond(&vault, user, 300);
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(300)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(claims.claims, []);
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&local_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // Staking locally
+    stake_locally(&vault, user, 100, val).unwrap();
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(200)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(100))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(200, OSMO)
+    );
+
+    stake_locally(&vault, user, 150, val).unwrap();
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(250))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(50, OSMO)
+    );
+
+    // Cannot stake over collateral
+
+    let err = stake_locally(&vault, user, 150, val).unwrap_err();
+
+    assert_eq!(err, ContractError::Mesh(MeshError::InsufficientBalance));
+
+    // Cannot unbond used collateral
+
+    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(50)))
+    );
+
+    // Unstaking
+
+    let proxy = proxy_for_user(&local_staking, user, &app);
+    proxy
+        .unstake(val.to_string(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+    proxy.release_unbonded().call(user).unwrap();
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(100)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(200))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(100, OSMO)
+    );
+
+    proxy
+        .unstake(val.to_string(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    process_staking_unbondings(&app);
+    proxy.release_unbonded().call(user).unwrap();
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(200)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: local_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(100))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(200, OSMO)
+    );
+
+    // Cannot unstake over the lien
+
+    // TODO: catch subcall error here
+    // let err = proxy
+    //     .unstake(val.to_string(), coin(200, OSMO))
+    //     .call(user)
+    //     .unwrap_err();
+    // assert_eq!(
+    //     err,
+    //     mesh_native_staking_proxy::error::ContractError::Mesh(MeshError::Unauthorized)
+    // );
+}
+
+#[test]
+fn withdrawing_all_rewards() {
+    let owner = "owner";
+    let user = "user1";
+    let distributor = "distributor";
+    let local_val = "local";
+    let remote_val = "remote";
+
+    let mut app = init_app(&[user, distributor], &[1000, 300]);
+    add_local_validator(&mut app, local_val);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[remote_val]);
+
+    bond(&vault, user, 1000);
+    stake_locally(&vault, user, 300, local_val).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[remote_val], &[200]);
+
+    // Nothing to collect yet
+    assert_eq!(
+        vault.pending_all_rewards(user.to_owned()).unwrap(),
+        PendingAllRewardsResponse {
+            local: Some(coin(0, OSMO)),
+            cross: vec![],
+            total: vec![],
+        }
+    );
+
+    local_staking
+        .distribute_rewards()
+        .with_funds(&coins(300, OSMO))
+        .call(distributor)
+        .unwrap();
+    cross_staking
+        .test_distribute_rewards(remote_val.to_owned(), coin(20, STAR))
+        .call(owner)
+        .unwrap();
+
+    // Local and cross-staking rewards, all together
+    assert_eq!(
+        vault.pending_all_rewards(user.to_owned()).unwrap(),
+        PendingAllRewardsResponse {
+            local: Some(coin(300, OSMO)),
+            cross: vec![LienholderRewards {
+                lienholder: cross_staking.contract_addr.to_string(),
+                rewards: vec![coin(20, STAR)],
+            }],
+            total: vec![coin(300, OSMO), coin(20, STAR)],
+        }
+    );
+
+    // Only the vault can withdraw the local rewards on behalf of the user
+    let err = local_staking
+        .withdraw_rewards_for(user.to_owned())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        mesh_native_staking::error::ContractError::Mesh(MeshError::Unauthorized)
+    );
+
+    // The pooled rewards and the ones of the proxy delegation go to the user in one go
+    skip_time(&app, 3600 * 24 * 365);
+    vault.withdraw_local_rewards().call(user).unwrap();
+    let balance = app.app().wrap().query_balance(user, OSMO).unwrap();
+    assert!(balance.amount > Uint128::new(300));
+
+    assert_eq!(
+        vault.pending_all_rewards(user.to_owned()).unwrap(),
+        PendingAllRewardsResponse {
+            local: Some(coin(0, OSMO)),
+            cross: vec![LienholderRewards {
+                lienholder: cross_staking.contract_addr.to_string(),
+                rewards: vec![coin(20, STAR)],
+            }],
+            total: vec![coin(20, STAR)],
+        }
+    );
+}
+
+#[test]
+fn stake_cross() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let unbond_period = 100;
+    let (vault, _local_staking, cross_staking) =
+        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
+
+    // Set active validator
+    let validator = "validator";
+    set_active_validators(&cross_staking, &[validator]);
+
+    // Bond some tokens
+    bond(&vault, user, 300);
+
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap(),
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(300)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(claims.claims, []);
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    let res = vault.active_external_staking().unwrap();
+    assert_eq!(res, AllActiveExternalStakingResponse { contracts: vec![] });
+
+    // Staking remotely
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(100, OSMO),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(user)
+        .unwrap();
+
+    let res = vault.active_external_staking().unwrap();
+    assert_eq!(
+        res,
+        AllActiveExternalStakingResponse {
+            contracts: vec![cross_staking.contract_addr.to_string()],
+        }
+    );
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new(Uint128::new(200), Uint128::new(300)),
+        }
+    );
+
+    // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
+    // This should be through `IbcPacketAckMsg`
+    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
+    cross_staking
+        .test_commit_stake(last_external_staking_tx)
+        .call("test")
+        .unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(200)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(100))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // second stake remote
+    vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(150, OSMO),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(user)
+        .unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new(Uint128::new(50), Uint128::new(200)),
+        }
+    );
+
+    // TODO: Hardcoded external-staking's commit_stake call (lack of IBC support yet).
+    // This should be through `IbcPacketAckMsg`
+    let last_external_staking_tx = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    println!("last_external_staking_tx: {:?}", last_external_staking_tx);
+    cross_staking
+        .test_commit_stake(last_external_staking_tx)
+        .call("test")
+        .unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(250))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // Cannot stake over collateral
+
+    let err = vault
+        .stake_remote(
+            cross_staking.contract_addr.to_string(),
+            coin(150, OSMO),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(user)
+        .unwrap_err();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+
+    assert_eq!(err, ContractError::Mesh(MeshError::InsufficientBalance));
+
+    // Cannot unbond used collateral
+
+    let err = vault.unbond(coin(100, OSMO)).call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::ClaimsLocked(ValueRange::new_val(Uint128::new(50)))
+    );
+
+    // Unstake does not free collateral on vault right away
+    cross_staking
+        .unstake(validator.to_owned(), coin(50, OSMO), None)
+        .call(user)
+        .unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(250))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // External staking contract will wait for `unbond_period` after receiving
+    // confirmation through the IBC channel that `unstake` was successfully executed.
+    skip_time(&app, unbond_period);
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_commit_unstake(tx_id)
+        .call("test")
+        .unwrap();
+
+    // No tokens is withdrawn before unbonding period is over
+    let insufficient_time = 99;
+    skip_time(&app, insufficient_time);
+
+    cross_staking.withdraw_unbonded().call(user).unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(50)),
+        }
+    );
+
+    // After the unbonding period user can withdraw unbonded tokens
+    let remaining_time = 1;
+    skip_time(&app, remaining_time);
+
+    cross_staking.withdraw_unbonded().call(user).unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(100)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(200))
+        }]
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(300, OSMO)
+    );
+    assert_eq!(
+        app.app()
+            .wrap()
+            .query_balance(&cross_staking.contract_addr, OSMO)
+            .unwrap(),
+        coin(0, OSMO)
+    );
+
+    // Unstake and receive callback through the IBC.
+    // Wait for the unbonding period and withdraw unbonded tokens.
+    cross_staking
+        .unstake(validator.to_owned(), coin(100, OSMO), None)
+        .call(user)
+        .unwrap();
+
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking
+        .test_commit_unstake(tx_id)
+        .call("test")
+        .unwrap();
+
+    skip_time(&app, unbond_period);
+
+    cross_staking.withdraw_unbonded().call(user).unwrap();
+
+    let acc = vault.account(user.to_owned()).unwrap();
+    assert_eq!(
+        acc,
+        AccountResponse {
+            denom: OSMO.to_owned(),
+            bonded: Uint128::new(300),
+            free: ValueRange::new_val(Uint128::new(200)),
+        }
+    );
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [LienResponse {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new_val(Uint128::new(100))
+        }]
+    );
+
     assert_eq!(
         app.app()
             .wrap()
@@ -931,126 +2536,584 @@ fn stake_cross() {
         coin(0, OSMO)
     );
 
-    // External staking contract will wait for `unbond_period` after receiving
-    // confirmation through the IBC channel that `unstake` was successfully executed.
-    skip_time(&app, unbond_period);
-    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    // Cannot unstake over the lien
+    // Error not verified as it is swallowed by intermediate contract
+    // in this scenario
     cross_staking
-        .test_commit_unstake(tx_id)
-        .call("test")
-        .unwrap();
+        .unstake(user.to_owned(), coin(300, OSMO), None)
+        .call(owner)
+        .unwrap_err();
+}
 
-    // No tokens is withdrawn before unbonding period is over
-    let insufficient_time = 99;
-    skip_time(&app, insufficient_time);
+#[test]
+fn auto_restake_released_claims() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+    let local_validator = "local-validator";
+    let unbond_period = 100;
+    let restake_period = 1000;
 
-    cross_staking.withdraw_unbonded().call(user).unwrap();
+    let mut app = init_app(&[user], &[300]);
+    add_local_validator(&mut app, local_validator);
 
-    let acc = vault.account(user.to_owned()).unwrap();
-    assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(50)),
-        }
-    );
+    let (vault, local_staking, cross_staking) =
+        setup(&app, owner, SLASHING_PERCENTAGE, unbond_period);
 
-    // After the unbonding period user can withdraw unbonded tokens
-    let remaining_time = 1;
-    skip_time(&app, remaining_time);
+    set_active_validators(&cross_staking, &[validator]);
 
-    cross_staking.withdraw_unbonded().call(user).unwrap();
+    bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[200]);
 
-    let acc = vault.account(user.to_owned()).unwrap();
+    let restake_msg = to_json_binary(&mesh_native_staking::msg::StakeMsg {
+        validator: local_validator.to_string(),
+        proxy: None,
+    })
+    .unwrap();
+
+    // Cap and period have to be set
+    let err = vault
+        .set_auto_restake(
+            local_staking.contract_addr.to_string(),
+            restake_msg.clone(),
+            Uint128::zero(),
+            restake_period,
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidAutoRestake);
+
+    vault
+        .set_auto_restake(
+            local_staking.contract_addr.to_string(),
+            restake_msg,
+            Uint128::new(60),
+            restake_period,
+        )
+        .call(user)
+        .unwrap();
+
+    let unstake = |amount: u128| {
+        cross_staking
+            .unstake(validator.to_owned(), coin(amount, OSMO), None)
+            .call(user)
+            .unwrap();
+        let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+        cross_staking
+            .test_commit_unstake(tx_id)
+            .call("test")
+            .unwrap();
+        skip_time(&app, unbond_period);
+        cross_staking.withdraw_unbonded().call(user).unwrap();
+    };
+
+    // Released claim is re-staked locally, up to the cap
+    unstake(100);
+
+    let cross_claim = vault
+        .claim(user.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(cross_claim.amount, ValueRange::new_val(Uint128::new(100)));
+    let local_claim = vault
+        .claim(user.to_owned(), local_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(local_claim.amount, ValueRange::new_val(Uint128::new(60)));
     assert_eq!(
-        acc,
+        vault.account(user.to_owned()).unwrap(),
         AccountResponse {
             denom: OSMO.to_owned(),
             bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(100)),
+            free: ValueRange::new_val(Uint128::new(200)),
         }
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: cross_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(200))
-        }]
+        app.app()
+            .wrap()
+            .query_balance(&vault.contract_addr, OSMO)
+            .unwrap(),
+        coin(240, OSMO)
     );
+    let restake = vault.auto_restake(user.to_owned()).unwrap();
+    assert_eq!(restake.restaked, Uint128::new(60));
+
+    // Cap is exhausted for the current period, so the released claim becomes free collateral
+    unstake(50);
+
+    let local_claim = vault
+        .claim(user.to_owned(), local_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(local_claim.amount, ValueRange::new_val(Uint128::new(60)));
     assert_eq!(
         app.app()
             .wrap()
             .query_balance(&vault.contract_addr, OSMO)
             .unwrap(),
-        coin(300, OSMO)
+        coin(240, OSMO)
     );
+
+    // A new period starts, so re-staking resumes
+    skip_time(&app, restake_period);
+    unstake(50);
+
+    let err = vault
+        .claim(user.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap_err();
+    assert_eq!(err, query_error(ContractError::NoClaim));
+    let local_claim = vault
+        .claim(user.to_owned(), local_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(local_claim.amount, ValueRange::new_val(Uint128::new(110)));
     assert_eq!(
         app.app()
             .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
+            .query_balance(&vault.contract_addr, OSMO)
             .unwrap(),
-        coin(0, OSMO)
+        coin(190, OSMO)
     );
+    let restake = vault.auto_restake(user.to_owned()).unwrap();
+    assert_eq!(restake.restaked, Uint128::new(50));
 
-    // Unstake and receive callback through the IBC.
-    // Wait for the unbonding period and withdraw unbonded tokens.
-    cross_staking
-        .unstake(validator.to_owned(), coin(100, OSMO))
+    // Opting out
+    vault.clear_auto_restake().call(user).unwrap();
+    let err = vault.auto_restake(user.to_owned()).unwrap_err();
+    assert_eq!(err, query_error(ContractError::NoAutoRestake));
+}
+
+#[test]
+fn pause_lienholder() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    let lienholder = cross_staking.contract_addr.to_string();
+    assert_eq!(
+        vault.ownership().unwrap().owner,
+        Some(Addr::unchecked(owner))
+    );
+
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[100]);
+
+    // Only the owner can pause
+    let err = vault
+        .pause_lienholder(lienholder.clone())
         .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    vault
+        .pause_lienholder(lienholder.clone())
+        .call(owner)
         .unwrap();
+    assert_eq!(
+        vault.paused_lienholders(None, None).unwrap().lienholders,
+        vec![lienholder.clone()]
+    );
+
+    // New stakes are rejected
+    let err = vault
+        .stake_remote(
+            lienholder.clone(),
+            coin(50, OSMO),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::LienholderPaused(cross_staking.contract_addr.clone())
+    );
 
+    // Unstaking and releasing still works
+    cross_staking
+        .unstake(validator.to_owned(), coin(40, OSMO), None)
+        .call(user)
+        .unwrap();
     let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
     cross_staking
         .test_commit_unstake(tx_id)
         .call("test")
         .unwrap();
+    skip_time(&app, 100);
+    cross_staking.withdraw_unbonded().call(user).unwrap();
 
-    skip_time(&app, unbond_period);
+    let claim = vault.claim(user.to_owned(), lienholder.clone()).unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(60)));
 
-    cross_staking.withdraw_unbonded().call(user).unwrap();
+    // Unpausing allows staking again
+    let err = vault
+        .unpause_lienholder(lienholder.clone())
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
 
-    let acc = vault.account(user.to_owned()).unwrap();
+    vault
+        .unpause_lienholder(lienholder.clone())
+        .call(owner)
+        .unwrap();
+    assert!(vault
+        .paused_lienholders(None, None)
+        .unwrap()
+        .lienholders
+        .is_empty());
+
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[50]);
+    let claim = vault.claim(user.to_owned(), lienholder).unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(110)));
+}
+
+#[test]
+fn granted_operations() {
+    let owner = "owner";
+    let user = "user1";
+    let operator = "operator";
+    let validator = "validator";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 300);
+
+    let stake_msg = |amount: u128| GrantedMsg::StakeRemote {
+        contract: cross_staking.contract_addr.to_string(),
+        amount: coin(amount, OSMO),
+        msg: ReceiveVirtualStake::new(validator).encode().unwrap(),
+    };
+
+    // No grant yet
+    let err = vault
+        .exec_granted(user.to_owned(), stake_msg(100))
+        .call(operator)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoGrant("stake_remote".to_owned()));
+
+    let height = app.block_info().height;
+    let err = vault
+        .grant(
+            operator.to_owned(),
+            GrantedMsgType::StakeRemote,
+            Expiration::AtHeight(height),
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::GrantExpired);
+
+    vault
+        .grant(
+            operator.to_owned(),
+            GrantedMsgType::StakeRemote,
+            Expiration::AtHeight(height + 100),
+            None,
+        )
+        .call(user)
+        .unwrap();
+    vault
+        .grant(
+            operator.to_owned(),
+            GrantedMsgType::StakeLocal,
+            Expiration::AtHeight(height + 1),
+            None,
+        )
+        .call(user)
+        .unwrap();
     assert_eq!(
-        acc,
-        AccountResponse {
-            denom: OSMO.to_owned(),
-            bonded: Uint128::new(300),
-            free: ValueRange::new_val(Uint128::new(200)),
+        vault
+            .grants(user.to_owned(), operator.to_owned())
+            .unwrap()
+            .grants,
+        vec![
+            GrantInfo {
+                msg_type: "stake_local".to_owned(),
+                expiration: Expiration::AtHeight(height + 1),
+                remote_recipient: None,
+            },
+            GrantInfo {
+                msg_type: "stake_remote".to_owned(),
+                expiration: Expiration::AtHeight(height + 100),
+                remote_recipient: None,
+            },
+        ]
+    );
+
+    // Operator stakes on behalf of the user
+    vault
+        .exec_granted(user.to_owned(), stake_msg(100))
+        .call(operator)
+        .unwrap();
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking.test_commit_stake(tx_id).call("test").unwrap();
+
+    let claim = vault
+        .claim(user.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap();
+    assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(100)));
+    let stake = cross_staking
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(100)));
+
+    // Operator withdraws the rewards on behalf of the user, to the recipient set by the user
+    let err = vault
+        .grant(
+            operator.to_owned(),
+            GrantedMsgType::WithdrawRewards,
+            Expiration::Never {},
+            None,
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidGrantRecipient("withdraw_rewards".to_owned())
+    );
+    let err = vault
+        .grant(
+            operator.to_owned(),
+            GrantedMsgType::StakeLocal,
+            Expiration::Never {},
+            Some("remote".to_owned()),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidGrantRecipient("stake_local".to_owned())
+    );
+    vault
+        .grant(
+            operator.to_owned(),
+            GrantedMsgType::WithdrawRewards,
+            Expiration::Never {},
+            Some("remote".to_owned()),
+        )
+        .call(user)
+        .unwrap();
+    let grants = vault
+        .grants(user.to_owned(), operator.to_owned())
+        .unwrap()
+        .grants;
+    assert_eq!(grants[2].remote_recipient, Some("remote".to_owned()));
+    cross_staking
+        .test_distribute_rewards(validator.to_owned(), coin(20, STAR))
+        .call(owner)
+        .unwrap();
+    let withdraw_msg = |contract: &Addr| GrantedMsg::WithdrawRewards {
+        contract: contract.to_string(),
+        validator: validator.to_owned(),
+    };
+    // Only from the user lienholders
+    let err = vault
+        .exec_granted(user.to_owned(), withdraw_msg(&vault.contract_addr))
+        .call(operator)
+        .unwrap_err();
+    assert_eq!(err, ContractError::UnknownLienholder);
+    let resp = vault
+        .exec_granted(user.to_owned(), withdraw_msg(&cross_staking.contract_addr))
+        .call(operator)
+        .unwrap();
+    let attr = |key: &str| {
+        resp.events
+            .iter()
+            .filter(|ev| ev.ty == "wasm")
+            .flat_map(|ev| &ev.attributes)
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.clone())
+    };
+    assert_eq!(attr("sender"), Some(operator.to_owned()));
+    assert_eq!(attr("owner"), Some(user.to_owned()));
+    assert_eq!(attr("recipient"), Some("remote".to_owned()));
+    let txs = cross_staking.all_pending_txs_desc(None, None).unwrap().txs;
+    assert!(matches!(
+        &txs[0],
+        Tx::InFlightTransferFunds { staker, amount, .. }
+            if staker.as_str() == user && amount.u128() == 20
+    ));
+    // The cross-staking contract only accepts it from the vault
+    let err = cross_staking
+        .withdraw_rewards_for(user.to_owned(), validator.to_owned(), "remote".to_owned())
+        .call(operator)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        mesh_external_staking::error::ContractError::Mesh(MeshError::Unauthorized)
+    );
+
+    // Other message types are not granted
+    let err = vault
+        .exec_granted(user.to_owned(), GrantedMsg::Bond {})
+        .call(operator)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoGrant("bond".to_owned()));
+
+    // Grants expire
+    app.app_mut().update_block(|block| block.height += 1);
+    let err = vault
+        .exec_granted(
+            user.to_owned(),
+            GrantedMsg::StakeLocal {
+                amount: coin(50, OSMO),
+                msg: Binary::default(),
+            },
+        )
+        .call(operator)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoGrant("stake_local".to_owned()));
+
+    // And can be revoked
+    vault
+        .revoke(operator.to_owned(), GrantedMsgType::StakeRemote)
+        .call(user)
+        .unwrap();
+    let err = vault
+        .exec_granted(user.to_owned(), stake_msg(100))
+        .call(operator)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoGrant("stake_remote".to_owned()));
+    let err = vault
+        .revoke(operator.to_owned(), GrantedMsgType::StakeRemote)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::NoGrant("stake_remote".to_owned()));
+}
+
+#[test]
+fn custodian_batches() {
+    let owner = "owner";
+    let custodian = "custodian";
+    let alice = "alice";
+    let bob = "bob";
+    let carol = "carol";
+    let validator = "validator";
+
+    let app = init_app(&[custodian], &[500]);
+
+    let (vault, _, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[validator]);
+
+    // Carol doesn't approve the custodian
+    for user in [alice, bob] {
+        for msg_type in [GrantedMsgType::Bond, GrantedMsgType::StakeRemote] {
+            vault
+                .grant(custodian.to_owned(), msg_type, Expiration::Never {}, None)
+                .call(user)
+                .unwrap();
         }
+    }
+
+    let item = |account: &str, amount: u128| BatchItem {
+        account: account.to_owned(),
+        amount: Uint128::new(amount),
+    };
+    let outcomes = |resp: &cw_multi_test::AppResponse| -> Vec<(String, String)> {
+        resp.events
+            .iter()
+            .filter(|e| e.ty == "wasm-batch_item")
+            .map(|e| {
+                let attr = |key: &str| {
+                    e.attributes
+                        .iter()
+                        .find(|a| a.key == key)
+                        .map(|a| a.value.clone())
+                        .unwrap()
+                };
+                (attr("account"), attr("status"))
+            })
+            .collect()
+    };
+
+    // Batches are bounded, and paid for exactly
+    let err = vault.bond_batch(vec![]).call(custodian).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidBatchSize(contract::MAX_BATCH_SIZE)
     );
-    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    let err = vault
+        .bond_batch(vec![item(alice, 1); contract::MAX_BATCH_SIZE + 1])
+        .with_funds(&coins(contract::MAX_BATCH_SIZE as u128 + 1, OSMO))
+        .call(custodian)
+        .unwrap_err();
     assert_eq!(
-        claims.claims,
-        [LienResponse {
-            lienholder: cross_staking.contract_addr.to_string(),
-            amount: ValueRange::new_val(Uint128::new(100))
-        }]
+        err,
+        ContractError::InvalidBatchSize(contract::MAX_BATCH_SIZE)
     );
+    let err = vault
+        .bond_batch(vec![item(alice, 100), item(bob, 150)])
+        .with_funds(&coins(200, OSMO))
+        .call(custodian)
+        .unwrap_err();
+    assert_eq!(err, ContractError::InvalidBatchFunds(Uint128::new(250)));
 
+    // Carol's item fails, and its funds are sent back
+    let resp = vault
+        .bond_batch(vec![item(alice, 100), item(bob, 150), item(carol, 50)])
+        .with_funds(&coins(300, OSMO))
+        .call(custodian)
+        .unwrap();
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&vault.contract_addr, OSMO)
-            .unwrap(),
-        coin(300, OSMO)
+        outcomes(&resp),
+        vec![
+            (alice.to_owned(), "ok".to_owned()),
+            (bob.to_owned(), "ok".to_owned()),
+            (carol.to_owned(), "failed".to_owned()),
+        ]
     );
+    assert_eq!(vault.account(alice.to_owned()).unwrap().bonded.u128(), 100);
+    assert_eq!(vault.account(bob.to_owned()).unwrap().bonded.u128(), 150);
+    assert_eq!(vault.account(carol.to_owned()).unwrap().bonded.u128(), 0);
     assert_eq!(
-        app.app()
-            .wrap()
-            .query_balance(&cross_staking.contract_addr, OSMO)
-            .unwrap(),
-        coin(0, OSMO)
+        app.app().wrap().query_balance(custodian, OSMO).unwrap(),
+        coin(250, OSMO)
     );
 
-    // Cannot unstake over the lien
-    // Error not verified as it is swallowed by intermediate contract
-    // in this scenario
-    cross_staking
-        .unstake(user.to_owned(), coin(300, OSMO))
-        .call(owner)
+    // Bob doesn't have enough collateral, and carol didn't approve the custodian
+    let resp = vault
+        .stake_remote_batch(
+            cross_staking.contract_addr.to_string(),
+            vec![item(alice, 80), item(bob, 200), item(carol, 10)],
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(custodian)
+        .unwrap();
+    assert_eq!(
+        outcomes(&resp),
+        vec![
+            (alice.to_owned(), "ok".to_owned()),
+            (bob.to_owned(), "failed".to_owned()),
+            (carol.to_owned(), "failed".to_owned()),
+        ]
+    );
+    let tx_id = get_last_external_staking_pending_tx_id(&cross_staking).unwrap();
+    cross_staking.test_commit_stake(tx_id).call("test").unwrap();
+    let stake = cross_staking
+        .stake(alice.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(80)));
+    let err = vault
+        .claim(bob.to_owned(), cross_staking.contract_addr.to_string())
+        .unwrap_err();
+    assert_eq!(err, query_error(ContractError::NoClaim));
+
+    // Single accounts can be bonded for through the granted message as well
+    let err = vault
+        .exec_granted(carol.to_owned(), GrantedMsg::Bond {})
+        .with_funds(&coins(50, OSMO))
+        .call(custodian)
         .unwrap_err();
+    assert_eq!(err, ContractError::NoGrant("bond".to_owned()));
+    vault
+        .exec_granted(alice.to_owned(), GrantedMsg::Bond {})
+        .with_funds(&coins(50, OSMO))
+        .call(custodian)
+        .unwrap();
+    assert_eq!(vault.account(alice.to_owned()).unwrap().bonded.u128(), 150);
 }
 
 #[test]
@@ -1119,10 +3182,7 @@ fn stake_cross_txs() {
         .stake_remote(
             cross_staking.contract_addr.to_string(),
             coin(100, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
         )
         .call(user)
         .unwrap();
@@ -1137,10 +3197,7 @@ fn stake_cross_txs() {
         .stake_remote(
             cross_staking.contract_addr.to_string(),
             coin(50, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
         )
         .call(user)
         .unwrap();
@@ -1152,10 +3209,7 @@ fn stake_cross_txs() {
         .stake_remote(
             cross_staking.contract_addr.to_string(),
             coin(100, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
         )
         .call(user2)
         .unwrap();
@@ -1195,6 +3249,32 @@ fn stake_cross_txs() {
             ValueRange::new(Uint128::new(150), Uint128::new(300))
         )
     );
+    // Account details include the liens and pending claims
+    let acc_details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(
+        acc_details.liens,
+        [LienDetails {
+            lienholder: cross_staking.contract_addr.to_string(),
+            amount: ValueRange::new(Uint128::zero(), Uint128::new(150)),
+            slashable: Decimal::percent(SLASHING_PERCENTAGE),
+            local: false,
+        }]
+    );
+    assert_eq!(
+        acc_details.pending_claims,
+        [
+            PendingClaim {
+                tx_id: first_tx,
+                lienholder: cross_staking.contract_addr.to_string(),
+                amount: Uint128::new(100),
+            },
+            PendingClaim {
+                tx_id: second_tx,
+                lienholder: cross_staking.contract_addr.to_string(),
+                amount: Uint128::new(50),
+            },
+        ]
+    );
     // Can query claims, and value ranges are reported
     assert_eq!(
         vault
@@ -1326,10 +3406,7 @@ fn stake_cross_rollback_tx() {
         .stake_remote(
             cross_staking.contract_addr.to_string(),
             coin(100, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
         )
         .call(user)
         .unwrap();
@@ -1374,6 +3451,136 @@ fn stake_cross_rollback_tx() {
     );
 }
 
+#[test]
+fn stake_cross_failures() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _cross_staking) =
+        setup_without_local_staking(&app, owner, SLASHING_PERCENTAGE, 100);
+    let mock = cross_staking::sv::mt::CodeId::store_code(&app)
+        .instantiate(
+            vault.contract_addr.to_string(),
+            Decimal::percent(SLASHING_PERCENTAGE),
+        )
+        .call(owner)
+        .unwrap();
+    let stake_msg = ReceiveVirtualStake::new(validator).encode().unwrap();
+    let all_free = AccountResponse {
+        denom: OSMO.to_owned(),
+        bonded: Uint128::new(300),
+        free: ValueRange::new_val(Uint128::new(300)),
+    };
+
+    bond(&vault, user, 300);
+
+    // A failing `receive_virtual_stake` reverts the whole stake
+    mock.set_failure_mode(FailureMode::Error {})
+        .call(owner)
+        .unwrap();
+    let err = vault
+        .stake_remote(
+            mock.contract_addr.to_string(),
+            coin(100, OSMO),
+            stake_msg.clone(),
+        )
+        .call(user)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Std(StdError::generic_err("Stake failure"))
+    );
+    assert_eq!(vault.account(user.to_owned()).unwrap(), all_free);
+    assert_eq!(get_last_vault_pending_tx_id(&vault), None);
+
+    // Failed acks and timeouts roll the stake back once relayed
+    for mode in [FailureMode::AckFailure {}, FailureMode::Timeout {}] {
+        mock.set_failure_mode(mode).call(owner).unwrap();
+        vault
+            .stake_remote(
+                mock.contract_addr.to_string(),
+                coin(100, OSMO),
+                stake_msg.clone(),
+            )
+            .call(user)
+            .unwrap();
+
+        // The stake is pending until then
+        assert!(get_last_vault_pending_tx_id(&vault).is_some());
+        assert_eq!(
+            vault.account(user.to_owned()).unwrap().free,
+            ValueRange::new(Uint128::new(200), Uint128::new(300))
+        );
+
+        mock.relay().call(owner).unwrap();
+        assert_eq!(get_last_vault_pending_tx_id(&vault), None);
+        assert_eq!(vault.account(user.to_owned()).unwrap(), all_free);
+        let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+        assert_eq!(claims.claims, []);
+    }
+}
+
+#[test]
+fn delayed_cross_unstake() {
+    let owner = "owner";
+    let user = "user1";
+    let validator = "validator";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _cross_staking) =
+        setup_without_local_staking(&app, owner, SLASHING_PERCENTAGE, 100);
+    let mock = cross_staking::sv::mt::CodeId::store_code(&app)
+        .instantiate(
+            vault.contract_addr.to_string(),
+            Decimal::percent(SLASHING_PERCENTAGE),
+        )
+        .call(owner)
+        .unwrap();
+    mock.set_failure_mode(FailureMode::DelayedUnstake { delay: 100 })
+        .call(owner)
+        .unwrap();
+
+    bond(&vault, user, 300);
+    vault
+        .stake_remote(
+            mock.contract_addr.to_string(),
+            coin(100, OSMO),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
+        )
+        .call(user)
+        .unwrap();
+    // Committed right away
+    assert_eq!(get_last_vault_pending_tx_id(&vault), None);
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(200))
+    );
+
+    // The unstake is only released once due
+    mock.unstake(user.to_owned(), coin(100, OSMO))
+        .call(user)
+        .unwrap();
+    assert_eq!(mock.pending_unstakes().unwrap().len(), 1);
+    skip_time(&app, 50);
+    mock.relay().call(owner).unwrap();
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(200))
+    );
+
+    skip_time(&app, 50);
+    mock.relay().call(owner).unwrap();
+    assert_eq!(mock.pending_unstakes().unwrap(), []);
+    assert_eq!(
+        vault.account(user.to_owned()).unwrap().free,
+        ValueRange::new_val(Uint128::new(300))
+    );
+}
+
 #[test]
 fn multiple_stakes() {
     let owner = "owner";
@@ -1482,15 +3689,12 @@ fn multiple_stakes() {
         .stake_remote(
             cross_staking2.contract_addr.to_string(),
             coin(400, OSMO),
-            to_json_binary(&ReceiveVirtualStake {
-                validator: validator.to_string(),
-            })
-            .unwrap(),
+            ReceiveVirtualStake::new(validator).encode().unwrap(),
         )
         .call(user)
         .unwrap_err();
 
-    assert_eq!(err, ContractError::InsufficentBalance);
+    assert_eq!(err, ContractError::Mesh(MeshError::InsufficientBalance));
 }
 
 #[test]
@@ -2462,6 +4666,75 @@ fn cross_slash_no_native_staking() {
     assert_eq!(acc_details.free, ValueRange::new_val(Uint128::zero()));
 }
 
+/// Native slashing of the collateral, reported by the provider module
+#[test]
+fn slash_delegator() {
+    let owner = "owner";
+    let user = "user1";
+    let local_validator = "local";
+    let validator = "validator1";
+
+    let mut app = init_app(&[user], &[1000]);
+    add_local_validator(&mut app, local_validator);
+
+    let (vault, local_staking, cross_staking) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+    set_active_validators(&cross_staking, &[validator]);
+
+    bond(&vault, user, 1000);
+    stake_locally(&vault, user, 200, local_validator).unwrap();
+    stake_remotely(&vault, &cross_staking, user, &[validator], &[400]);
+
+    let err = vault
+        .slash_delegator(user.to_owned(), coin(100, STAR))
+        .unwrap_err();
+    assert_eq!(err, MeshError::InvalidDenom(OSMO.to_owned()).into());
+
+    // 10% of the collateral was burned
+    vault
+        .slash_delegator(user.to_owned(), coin(100, OSMO))
+        .unwrap();
+
+    // The liens are cut by 10% as well
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(
+        claims.claims,
+        [
+            LienResponse {
+                lienholder: local_staking.contract_addr.to_string(),
+                amount: ValueRange::new_val(Uint128::new(180))
+            },
+            LienResponse {
+                lienholder: cross_staking.contract_addr.to_string(),
+                amount: ValueRange::new_val(Uint128::new(360))
+            },
+        ]
+    );
+    let acc_details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(acc_details.bonded, Uint128::new(900));
+    assert_eq!(acc_details.max_lien, ValueRange::new_val(Uint128::new(360)));
+    assert_eq!(
+        acc_details.total_slashable,
+        ValueRange::new_val(Uint128::new(54))
+    );
+    assert_eq!(acc_details.free, ValueRange::new_val(Uint128::new(540)));
+
+    // And burned from the lienholders stakes
+    let cross_stake = cross_staking
+        .stake(user.to_owned(), validator.to_owned())
+        .unwrap();
+    assert_eq!(cross_stake.stake, ValueRange::new_val(Uint128::new(360)));
+
+    // Never more than the collateral
+    vault
+        .slash_delegator(user.to_owned(), coin(5000, OSMO))
+        .unwrap();
+    let acc_details = vault.account_details(user.to_owned()).unwrap();
+    assert_eq!(acc_details.bonded, Uint128::zero());
+    assert_eq!(acc_details.max_lien, ValueRange::new_val(Uint128::zero()));
+    let claims = vault.account_claims(user.to_owned(), None, None).unwrap();
+    assert_eq!(claims.claims, []);
+}
+
 /// Checks that the slashing applies to unbonding amounts as well.
 #[test]
 fn cross_slash_pending_unbonding() {
@@ -2542,7 +4815,7 @@ fn cross_slash_pending_unbonding() {
 
     // Unbond half the stake of validator1
     cross_staking
-        .unstake(validator1.to_owned(), coin(50, OSMO))
+        .unstake(validator1.to_owned(), coin(50, OSMO), None)
         .call(user)
         .unwrap();
     cross_staking
@@ -2853,3 +5126,185 @@ fn native_slashing_jailing() {
         .unwrap();
     assert_eq!(cross_stake2.stake, ValueRange::new_val(Uint128::new(50))); // no slashing
 }
+
+#[test]
+fn ownership_transfer() {
+    let owner = "owner";
+    let new_owner = "new_owner";
+    let user = "user1";
+
+    let app = init_app(&[user], &[300]);
+
+    let (vault, _, _) = setup(&app, owner, SLASHING_PERCENTAGE, 100);
+
+    let err = vault
+        .propose_owner(new_owner.to_owned(), None)
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+
+    vault
+        .propose_owner(new_owner.to_owned(), None)
+        .call(owner)
+        .unwrap();
+    let ownership = vault.ownership().unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked(owner)));
+    assert_eq!(ownership.pending_owner, Some(Addr::unchecked(new_owner)));
+
+    let err = vault.accept_owner().call(user).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Ownership(OwnershipError::NotPendingOwner)
+    );
+    vault.accept_owner().call(new_owner).unwrap();
+
+    // Only the new owner can manage the vault now
+    let err = vault
+        .update_bond_limits(Uint128::new(50), Uint128::new(20))
+        .call(owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Mesh(MeshError::Unauthorized));
+    vault
+        .update_bond_limits(Uint128::new(50), Uint128::new(20))
+        .call(new_owner)
+        .unwrap();
+
+    // And nobody once renounced
+    vault.renounce_owner().call(new_owner).unwrap();
+    assert_eq!(vault.ownership().unwrap().owner, None);
+    let err = vault
+        .update_bond_limits(Uint128::new(10), Uint128::new(10))
+        .call(new_owner)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Ownership(OwnershipError::NoOwner));
+}
+
+#[test]
+fn migrating_from_older_version() {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{ContractInfoResponse, ContractResult, SystemResult, WasmQuery};
+    use cw_storage_plus::Map;
+    use mesh_apis::ownership_api::OwnershipApi;
+    use sylvia::types::{MigrateCtx, QueryCtx};
+
+    use crate::state::Lien;
+
+    let mut deps = mock_dependencies();
+    let contract = VaultContract::new();
+    let user = Addr::unchecked("user");
+    let lienholder = Addr::unchecked("lienholder");
+
+    // Liens stored before they were indexed
+    let old_liens: Map<(&Addr, &Addr), Lien> = Map::new("liens");
+    let lien = Lien {
+        amount: ValueRange::new_val(Uint128::new(100)),
+        slashable: Decimal::percent(10),
+    };
+    old_liens
+        .save(deps.as_mut().storage, (&user, &lienholder), &lien)
+        .unwrap();
+    // Baseline config, the owner is the wasm admin
+    deps.as_mut().storage.set(b"config", br#"{"denom":"OSMO"}"#);
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::ContractInfo { .. } => {
+            let mut info = ContractInfoResponse::default();
+            info.code_id = 1;
+            info.creator = "creator".to_owned();
+            info.admin = Some("owner".to_owned());
+            SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+        }
+        _ => unimplemented!(),
+    });
+    cw2::set_contract_version(
+        deps.as_mut().storage,
+        contract::CONTRACT_NAME,
+        "0.10.0-alpha.1",
+    )
+    .unwrap();
+    assert_eq!(
+        contract
+            .liens
+            .idx
+            .lienholder
+            .prefix(lienholder.clone())
+            .range(deps.as_ref().storage, None, None, Order::Ascending)
+            .count(),
+        0
+    );
+
+    let ctx = MigrateCtx {
+        deps: deps.as_mut(),
+        env: mock_env(),
+    };
+    let resp = contract.migrate(ctx).unwrap();
+    let event = &resp.events[0];
+    assert_eq!(event.ty, "migrate");
+    assert_eq!(
+        event.attributes,
+        vec![
+            Attribute::new("from_version", "0.10.0-alpha.1"),
+            Attribute::new("to_version", contract::CONTRACT_VERSION),
+            Attribute::new("migrations", "reindex_liens,move_owner,init_stats"),
+        ]
+    );
+
+    // The owner is moved out of the config
+    let query_ctx = QueryCtx {
+        deps: deps.as_ref(),
+        env: mock_env(),
+    };
+    assert_eq!(
+        contract.ownership(query_ctx).unwrap().owner,
+        Some(Addr::unchecked("owner"))
+    );
+    assert_eq!(contract.config.load(&deps.storage).unwrap().denom, OSMO);
+
+    let indexed = contract
+        .liens
+        .idx
+        .lienholder
+        .prefix(lienholder)
+        .range(deps.as_ref().storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()
+        .unwrap();
+    assert_eq!(indexed, vec![((user, Addr::unchecked("lienholder")), lien)]);
+    // Lienholder totals are computed from the existing liens
+    assert_eq!(
+        contract
+            .lienholder_totals
+            .load(deps.as_ref().storage, &Addr::unchecked("lienholder"))
+            .unwrap(),
+        Uint128::new(100)
+    );
+    assert_eq!(
+        cw2::get_contract_version(deps.as_ref().storage)
+            .unwrap()
+            .version,
+        contract::CONTRACT_VERSION
+    );
+
+    // Nothing to run when migrating to the same version, but no downgrades
+    let ctx = MigrateCtx {
+        deps: deps.as_mut(),
+        env: mock_env(),
+    };
+    let resp = contract.migrate(ctx).unwrap();
+    assert_eq!(
+        resp.events[0].attributes[2],
+        Attribute::new("migrations", "")
+    );
+
+    cw2::set_contract_version(deps.as_mut().storage, contract::CONTRACT_NAME, "99.0.0").unwrap();
+    let ctx = MigrateCtx {
+        deps: deps.as_mut(),
+        env: mock_env(),
+    };
+    let err = contract.migrate(ctx).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::Mesh(MeshError::MigrationDowngrade(
+            "99.0.0".to_owned(),
+            contract::CONTRACT_VERSION.to_owned()
+        ))
+    );
+}
@@ -0,0 +1,218 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Binary, Coin, Decimal, Order, Response, StdError, StdResult, Timestamp};
+use cw_storage_plus::{Item, Map};
+use mesh_apis::cross_staking_api::{
+    self, CapabilitiesResponse, CrossStakingApi, SlashRatioResponse, TotalPendingRewardsResponse,
+};
+use mesh_apis::vault_api::VaultApiHelper;
+use sylvia::contract;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+
+use crate::error::ContractError;
+
+/// How the mock handles the vault stakes and the unstakes
+#[cw_serde]
+pub enum FailureMode {
+    /// Stakes are committed and unstakes released right away
+    None {},
+    /// `receive_virtual_stake` fails, reverting the vault stake
+    Error {},
+    /// Stakes stay pending until `relay`, and are then rolled back, as on an error ack
+    AckFailure {},
+    /// Stakes stay pending until `relay`, and are then rolled back, as on a packet timeout
+    Timeout {},
+    /// Stakes are committed right away, but unstakes are only released by `relay`, `delay`
+    /// seconds after them
+    DelayedUnstake { delay: u64 },
+}
+
+#[cw_serde]
+pub struct PendingUnstake {
+    pub owner: String,
+    pub amount: Coin,
+    pub release_at: Timestamp,
+}
+
+/// Cross-staking contract simulating the ack failures, timeouts and slow unbondings of a
+/// remote chain, for test purposes only
+pub struct CrossStakingMock<'a> {
+    vault: Item<'a, VaultApiHelper>,
+    max_slash: Item<'a, Decimal>,
+    mode: Item<'a, FailureMode>,
+    /// Stakes waiting for `relay`, by tx id, with the failure they are rolled back on
+    pending_txs: Map<'a, u64, String>,
+    pending_unstakes: Item<'a, Vec<PendingUnstake>>,
+}
+
+#[contract]
+#[sv::error(ContractError)]
+#[sv::messages(cross_staking_api as CrossStakingApi)]
+impl CrossStakingMock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            vault: Item::new("vault"),
+            max_slash: Item::new("max_slash"),
+            mode: Item::new("mode"),
+            pending_txs: Map::new("pending_txs"),
+            pending_unstakes: Item::new("pending_unstakes"),
+        }
+    }
+
+    #[sv::msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx,
+        vault: String,
+        max_slash: Decimal,
+    ) -> StdResult<Response> {
+        let vault = ctx.deps.api.addr_validate(&vault)?;
+        self.vault.save(ctx.deps.storage, &VaultApiHelper(vault))?;
+        self.max_slash.save(ctx.deps.storage, &max_slash)?;
+        self.mode.save(ctx.deps.storage, &FailureMode::None {})?;
+        self.pending_unstakes.save(ctx.deps.storage, &vec![])?;
+        Ok(Response::new())
+    }
+
+    #[sv::msg(exec)]
+    fn set_failure_mode(&self, ctx: ExecCtx, mode: FailureMode) -> StdResult<Response> {
+        self.mode.save(ctx.deps.storage, &mode)?;
+        Ok(Response::new())
+    }
+
+    /// Unstakes `amount` of `owner`, releasing it in the vault according to the failure mode
+    #[sv::msg(exec)]
+    fn unstake(&self, ctx: ExecCtx, owner: String, amount: Coin) -> StdResult<Response> {
+        let vault = self.vault.load(ctx.deps.storage)?;
+        match self.mode.load(ctx.deps.storage)? {
+            FailureMode::DelayedUnstake { delay } => {
+                let unstake = PendingUnstake {
+                    owner,
+                    amount,
+                    release_at: ctx.env.block.time.plus_seconds(delay),
+                };
+                self.pending_unstakes
+                    .update(ctx.deps.storage, |mut unstakes| -> StdResult<_> {
+                        unstakes.push(unstake);
+                        Ok(unstakes)
+                    })?;
+                Ok(Response::new())
+            }
+            _ => {
+                let msg = vault.release_cross_stake(owner, amount, vec![])?;
+                Ok(Response::new().add_message(msg))
+            }
+        }
+    }
+
+    /// Rolls back all the pending stakes, and releases the due unstakes
+    #[sv::msg(exec)]
+    fn relay(&self, ctx: ExecCtx) -> StdResult<Response> {
+        let vault = self.vault.load(ctx.deps.storage)?;
+        let mut resp = Response::new();
+
+        let txs = self
+            .pending_txs
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (tx_id, failure) in txs {
+            self.pending_txs.remove(ctx.deps.storage, tx_id);
+            resp = resp
+                .add_message(vault.rollback_tx(tx_id)?)
+                .add_attribute(failure, tx_id.to_string());
+        }
+
+        let (due, pending): (Vec<_>, Vec<_>) = self
+            .pending_unstakes
+            .load(ctx.deps.storage)?
+            .into_iter()
+            .partition(|unstake| unstake.release_at <= ctx.env.block.time);
+        self.pending_unstakes.save(ctx.deps.storage, &pending)?;
+        for unstake in due {
+            resp = resp.add_message(vault.release_cross_stake(
+                unstake.owner,
+                unstake.amount,
+                vec![],
+            )?);
+        }
+
+        Ok(resp)
+    }
+
+    #[sv::msg(query)]
+    fn failure_mode(&self, ctx: QueryCtx) -> StdResult<FailureMode> {
+        self.mode.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    fn pending_unstakes(&self, ctx: QueryCtx) -> StdResult<Vec<PendingUnstake>> {
+        self.pending_unstakes.load(ctx.deps.storage)
+    }
+}
+
+impl CrossStakingApi for CrossStakingMock<'_> {
+    // Same error as the vault, so the failures of `receive_virtual_stake` can be returned by the
+    // vault multitest proxies
+    type Error = ContractError;
+
+    fn receive_virtual_stake(
+        &self,
+        ctx: ExecCtx,
+        _owner: String,
+        _amount: Coin,
+        tx_id: u64,
+        _msg: Binary,
+    ) -> Result<Response, ContractError> {
+        let failure = match self.mode.load(ctx.deps.storage)? {
+            FailureMode::Error {} => return Err(StdError::generic_err("Stake failure").into()),
+            FailureMode::AckFailure {} => "ack_failure",
+            FailureMode::Timeout {} => "timeout",
+            FailureMode::None {} | FailureMode::DelayedUnstake { .. } => {
+                let vault = self.vault.load(ctx.deps.storage)?;
+                return Ok(Response::new().add_message(vault.commit_tx(tx_id)?));
+            }
+        };
+        self.pending_txs
+            .save(ctx.deps.storage, tx_id, &failure.to_owned())?;
+        Ok(Response::new())
+    }
+
+    fn burn_virtual_stake(
+        &self,
+        _ctx: ExecCtx,
+        _owner: String,
+        _amount: Coin,
+        _validator: Option<String>,
+    ) -> Result<Response, ContractError> {
+        Ok(Response::new())
+    }
+
+    fn withdraw_rewards_for(
+        &self,
+        _ctx: ExecCtx,
+        _owner: String,
+        _validator: String,
+        _remote_recipient: String,
+    ) -> Result<Response, ContractError> {
+        Ok(Response::new())
+    }
+
+    fn max_slash(&self, ctx: QueryCtx) -> Result<SlashRatioResponse, ContractError> {
+        let max_slash = self.max_slash.load(ctx.deps.storage)?;
+        Ok(SlashRatioResponse {
+            slash_ratio_dsign: max_slash,
+            slash_ratio_offline: max_slash,
+        })
+    }
+
+    fn capabilities(&self, _ctx: QueryCtx) -> Result<CapabilitiesResponse, ContractError> {
+        Ok(CapabilitiesResponse::default())
+    }
+
+    fn total_pending_rewards(
+        &self,
+        _ctx: QueryCtx,
+        _user: String,
+    ) -> Result<TotalPendingRewardsResponse, ContractError> {
+        Ok(TotalPendingRewardsResponse::default())
+    }
+}
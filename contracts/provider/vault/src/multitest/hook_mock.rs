@@ -0,0 +1,64 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Response, StdError, StdResult};
+use cw_storage_plus::Item;
+use mesh_apis::vault_hook_api::{self, VaultHookApi, VaultHookMsg};
+use sylvia::contract;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+
+#[cw_serde]
+pub struct CallsResponse {
+    pub calls: Vec<VaultHookMsg>,
+}
+
+/// Vault hook contract recording the calls it receives, for test purposes only.
+/// It can be set to fail on every call.
+pub struct HookMock<'a> {
+    calls: Item<'a, Vec<VaultHookMsg>>,
+    failing: Item<'a, bool>,
+}
+
+#[contract]
+#[sv::messages(vault_hook_api as VaultHookApi)]
+impl HookMock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            calls: Item::new("calls"),
+            failing: Item::new("failing"),
+        }
+    }
+
+    #[sv::msg(instantiate)]
+    pub fn instantiate(&self, ctx: InstantiateCtx) -> StdResult<Response> {
+        self.calls.save(ctx.deps.storage, &vec![])?;
+        self.failing.save(ctx.deps.storage, &false)?;
+        Ok(Response::new())
+    }
+
+    #[sv::msg(exec)]
+    fn set_failing(&self, ctx: ExecCtx, failing: bool) -> StdResult<Response> {
+        self.failing.save(ctx.deps.storage, &failing)?;
+        Ok(Response::new())
+    }
+
+    #[sv::msg(query)]
+    fn calls(&self, ctx: QueryCtx) -> StdResult<CallsResponse> {
+        let calls = self.calls.load(ctx.deps.storage)?;
+        Ok(CallsResponse { calls })
+    }
+}
+
+impl VaultHookApi for HookMock<'_> {
+    type Error = StdError;
+
+    fn vault_hook(&self, ctx: ExecCtx, msg: VaultHookMsg) -> StdResult<Response> {
+        if self.failing.load(ctx.deps.storage)? {
+            return Err(StdError::generic_err("Hook failure"));
+        }
+        self.calls
+            .update(ctx.deps.storage, |mut calls| -> StdResult<_> {
+                calls.push(msg);
+                Ok(calls)
+            })?;
+        Ok(Response::new())
+    }
+}
@@ -0,0 +1,80 @@
+use cosmwasm_std::{coins, BankMsg, Response, StdError, StdResult, Uint128};
+use cw_storage_plus::{Item, Map};
+use cw_utils::must_pay;
+use mesh_apis::vault_strategy_api::{self, DepositValueResponse, VaultStrategyApi};
+use sylvia::contract;
+use sylvia::types::{ExecCtx, InstantiateCtx, QueryCtx};
+
+/// Yield strategy keeping the deposits as they are, for test purposes only.
+/// Yield is accrued by sending tokens with `accrue`.
+pub struct StrategyMock<'a> {
+    denom: Item<'a, String>,
+    values: Map<'a, String, Uint128>,
+}
+
+#[contract]
+#[sv::messages(vault_strategy_api as VaultStrategyApi)]
+impl StrategyMock<'_> {
+    pub const fn new() -> Self {
+        Self {
+            denom: Item::new("denom"),
+            values: Map::new("values"),
+        }
+    }
+
+    #[sv::msg(instantiate)]
+    pub fn instantiate(&self, ctx: InstantiateCtx, denom: String) -> StdResult<Response> {
+        self.denom.save(ctx.deps.storage, &denom)?;
+        Ok(Response::new())
+    }
+
+    /// Adds the sent tokens to the deposits value of `owner`
+    #[sv::msg(exec)]
+    fn accrue(&self, ctx: ExecCtx, owner: String) -> StdResult<Response> {
+        let denom = self.denom.load(ctx.deps.storage)?;
+        let amount =
+            must_pay(&ctx.info, &denom).map_err(|err| StdError::generic_err(err.to_string()))?;
+        self.values
+            .update(ctx.deps.storage, owner, |value| -> StdResult<_> {
+                Ok(value.unwrap_or_default() + amount)
+            })?;
+        Ok(Response::new())
+    }
+}
+
+impl VaultStrategyApi for StrategyMock<'_> {
+    type Error = StdError;
+
+    fn deposit(&self, ctx: ExecCtx) -> StdResult<Response> {
+        let denom = self.denom.load(ctx.deps.storage)?;
+        let amount =
+            must_pay(&ctx.info, &denom).map_err(|err| StdError::generic_err(err.to_string()))?;
+        self.values.update(
+            ctx.deps.storage,
+            ctx.info.sender.into_string(),
+            |value| -> StdResult<_> { Ok(value.unwrap_or_default() + amount) },
+        )?;
+        Ok(Response::new())
+    }
+
+    fn withdraw(&self, ctx: ExecCtx, amount: Uint128) -> StdResult<Response> {
+        let denom = self.denom.load(ctx.deps.storage)?;
+        self.values.update(
+            ctx.deps.storage,
+            ctx.info.sender.to_string(),
+            |value| -> StdResult<_> { Ok(value.unwrap_or_default().checked_sub(amount)?) },
+        )?;
+        Ok(Response::new().add_message(BankMsg::Send {
+            to_address: ctx.info.sender.into_string(),
+            amount: coins(amount.u128(), denom),
+        }))
+    }
+
+    fn deposit_value(&self, ctx: QueryCtx, owner: String) -> StdResult<DepositValueResponse> {
+        let value = self
+            .values
+            .may_load(ctx.deps.storage, owner)?
+            .unwrap_or_default();
+        Ok(DepositValueResponse { value })
+    }
+}
@@ -0,0 +1,105 @@
+use bech32::{ToBase32, Variant};
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_vec, Addr, Api, Binary, Env, StdResult, Storage};
+use cw_storage_plus::Map;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::msg::StakeRemotePermit;
+
+/// What is actually signed for a permit: the permit itself, bound to the chain and the vault
+/// (like an EIP-712 domain), so it can't be replayed on another chain or vault
+#[cw_serde]
+pub struct PermitSignDoc {
+    pub chain_id: String,
+    pub vault: String,
+    pub permit: StakeRemotePermit,
+}
+
+/// Hash of the sign doc of `permit`, to be signed with the owner permit key
+pub fn permit_hash(chain_id: &str, vault: &str, permit: &StakeRemotePermit) -> StdResult<[u8; 32]> {
+    let doc = PermitSignDoc {
+        chain_id: chain_id.to_owned(),
+        vault: vault.to_owned(),
+        permit: permit.clone(),
+    };
+    Ok(Sha256::digest(to_json_vec(&doc)?).into())
+}
+
+/// Account address of the compressed secp256k1 `pubkey`, as derived by the Cosmos SDK:
+/// the bech32 encoding of `ripemd160(sha256(pubkey))`, with the given prefix
+pub fn pubkey_address(prefix: &str, pubkey: &[u8]) -> Result<String, ContractError> {
+    if pubkey.len() != 33 {
+        return Err(ContractError::InvalidPermitKey);
+    }
+    let hash = Ripemd160::digest(Sha256::digest(pubkey));
+    bech32::encode(prefix, hash.to_base32(), Variant::Bech32)
+        .map_err(|_| ContractError::InvalidPermitKey)
+}
+
+/// Off-chain signed approvals, allowing a relayer to submit vault messages on behalf of an
+/// account, which only signs them.
+///
+/// The public key is submitted along with the permit, and has to be the one of the owner
+/// account, so nothing has to be registered beforehand.
+pub struct Permits<'a> {
+    /// Nonces already used (or invalidated), by `(owner, nonce)`
+    pub nonces: Map<'a, (&'a Addr, u64), ()>,
+}
+
+impl<'a> Permits<'a> {
+    pub const fn new(nonces_key: &'a str) -> Self {
+        Self {
+            nonces: Map::new(nonces_key),
+        }
+    }
+
+    /// Marks `nonce` as used, failing if it already was
+    pub fn use_nonce(
+        &self,
+        storage: &mut dyn Storage,
+        owner: &Addr,
+        nonce: u64,
+    ) -> Result<(), ContractError> {
+        if self.nonces.has(storage, (owner, nonce)) {
+            return Err(ContractError::PermitNonceUsed(nonce));
+        }
+        self.nonces.save(storage, (owner, nonce), &())?;
+        Ok(())
+    }
+
+    /// Checks the permit is not expired, and signed with `pubkey`, the owner key, and consumes its
+    /// nonce. Returns the owner
+    pub fn verify(
+        &self,
+        api: &dyn Api,
+        storage: &mut dyn Storage,
+        env: &Env,
+        permit: &StakeRemotePermit,
+        pubkey: &Binary,
+        signature: &Binary,
+    ) -> Result<Addr, ContractError> {
+        let owner = api.addr_validate(&permit.owner)?;
+        if env.block.time.seconds() >= permit.expiry {
+            return Err(ContractError::PermitExpired);
+        }
+        // The key address has to be the owner, with the same prefix
+        let (prefix, _, _) =
+            bech32::decode(owner.as_str()).map_err(|_| ContractError::InvalidPermitKey)?;
+        if pubkey_address(&prefix, pubkey)? != owner.as_str() {
+            return Err(ContractError::PermitKeyMismatch(owner.into_string()));
+        }
+
+        let hash = permit_hash(&env.block.chain_id, env.contract.address.as_str(), permit)?;
+        let valid = api
+            .secp256k1_verify(&hash, signature, pubkey)
+            .unwrap_or(false);
+        if !valid {
+            return Err(ContractError::InvalidPermitSignature);
+        }
+
+        self.use_nonce(storage, &owner, permit.nonce)?;
+        Ok(owner)
+    }
+}
@@ -0,0 +1,58 @@
+//! Native bonding with the meshsecurityprovider module.
+//!
+//! On chains with the module, the vault is built with the `provider-bindings` feature. The bonded
+//! collateral is then kept by the module, on behalf of its owner, instead of by the vault: it is
+//! moved to the module with a `VaultMsg::Bond` once bonded, and moved back to the vault with a
+//! `VaultMsg::Unbond` right before leaving it (unbonds, local stakes, strategy deposits). The
+//! liens are accounted for the same way with or without the module.
+//!
+//! The module can also bond the tokens of vesting accounts, which it keeps locked (see the
+//! `BondVesting` sudo message). They never go through the vault: once unbonded, they are handed
+//! back to the vesting account with a `VaultMsg::Unbond` carrying vesting metadata.
+//!
+//! Without the feature, no message is sent, and the vault holds the collateral itself.
+use cosmwasm_std::{Addr, Coin, CosmosMsg, Uint128};
+
+use crate::contract::custom;
+
+/// Moves `amount` of `owner` collateral from the vault to the module
+#[cfg(feature = "provider-bindings")]
+pub fn bond_msg(owner: &Addr, amount: Coin) -> Option<CosmosMsg<custom::VaultMsg>> {
+    Some(mesh_bindings::VaultMsg::bond(owner.as_str(), amount).into())
+}
+
+/// Moves `amount` of `owner` collateral from the module back to the vault
+#[cfg(feature = "provider-bindings")]
+pub fn unbond_msg(owner: &Addr, amount: Coin) -> Option<CosmosMsg<custom::VaultMsg>> {
+    Some(mesh_bindings::VaultMsg::unbond(owner.as_str(), amount).into())
+}
+
+/// Returns `amount` of `owner` vesting collateral from the module to their vesting account,
+/// `remaining` being their vesting collateral still bonded
+#[cfg(feature = "provider-bindings")]
+pub fn return_vesting_msg(
+    owner: &Addr,
+    amount: Coin,
+    remaining: Uint128,
+) -> Option<CosmosMsg<custom::VaultMsg>> {
+    Some(mesh_bindings::VaultMsg::return_vesting(owner.as_str(), amount, remaining).into())
+}
+
+#[cfg(not(feature = "provider-bindings"))]
+pub fn bond_msg(_owner: &Addr, _amount: Coin) -> Option<CosmosMsg<custom::VaultMsg>> {
+    None
+}
+
+#[cfg(not(feature = "provider-bindings"))]
+pub fn unbond_msg(_owner: &Addr, _amount: Coin) -> Option<CosmosMsg<custom::VaultMsg>> {
+    None
+}
+
+#[cfg(not(feature = "provider-bindings"))]
+pub fn return_vesting_msg(
+    _owner: &Addr,
+    _amount: Coin,
+    _remaining: Uint128,
+) -> Option<CosmosMsg<custom::VaultMsg>> {
+    None
+}
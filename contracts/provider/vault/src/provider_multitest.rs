@@ -0,0 +1,267 @@
+//! Multitests of the vault built with the `provider-bindings` feature, against a mock of the
+//! meshsecurityprovider module recording the `VaultMsg` it receives.
+
+use anyhow::{bail, Result as AnyResult};
+use cosmwasm_std::testing::{MockApi, MockStorage};
+use cosmwasm_std::{
+    coin, coins, Addr, Api, Binary, BlockInfo, CustomQuery, Empty, Querier, Storage, Uint128,
+};
+use cw_multi_test::{AppBuilder, AppResponse, BankKeeper, CosmosRouter, Module, WasmKeeper};
+use cw_storage_plus::Item;
+use mesh_apis::error::MeshError;
+use mesh_bindings::{ProviderCustomMsg, VaultMsg};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use sylvia::multitest::App;
+
+use crate::contract;
+use crate::contract::sv::mt::VaultContractProxy;
+use crate::error::ContractError;
+
+const OSMO: &str = "OSMO";
+
+type MtApp = cw_multi_test::App<
+    BankKeeper,
+    MockApi,
+    MockStorage,
+    ProviderModule,
+    WasmKeeper<ProviderCustomMsg, Empty>,
+>;
+
+/// Provider module mock, recording the messages sent by the vault. The tokens are left with
+/// the vault
+struct ProviderModule {
+    msgs: Item<'static, Vec<VaultMsg>>,
+}
+
+impl ProviderModule {
+    fn new() -> Self {
+        Self {
+            msgs: Item::new("provider_module_msgs"),
+        }
+    }
+
+    /// Returns the messages received so far, clearing them
+    fn take_msgs(&self, storage: &mut dyn Storage) -> AnyResult<Vec<VaultMsg>> {
+        let msgs = self.msgs.may_load(storage)?.unwrap_or_default();
+        self.msgs.remove(storage);
+        Ok(msgs)
+    }
+}
+
+impl Module for ProviderModule {
+    type ExecT = ProviderCustomMsg;
+
+    type QueryT = Empty;
+
+    type SudoT = Empty;
+
+    fn execute<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        _sender: Addr,
+        msg: Self::ExecT,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        let ProviderCustomMsg::Vault(msg) = msg;
+        let mut msgs = self.msgs.may_load(storage)?.unwrap_or_default();
+        msgs.push(msg);
+        self.msgs.save(storage, &msgs)?;
+        Ok(AppResponse::default())
+    }
+
+    fn sudo<ExecC, QueryC>(
+        &self,
+        _api: &dyn Api,
+        _storage: &mut dyn Storage,
+        _router: &dyn CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
+        _block: &BlockInfo,
+        _msg: Self::SudoT,
+    ) -> AnyResult<AppResponse>
+    where
+        ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
+        QueryC: CustomQuery + DeserializeOwned + 'static,
+    {
+        bail!("provider module mock has no sudo messages")
+    }
+
+    fn query(
+        &self,
+        _api: &dyn Api,
+        _storage: &dyn Storage,
+        _querier: &dyn Querier,
+        _block: &BlockInfo,
+        _request: Self::QueryT,
+    ) -> AnyResult<Binary> {
+        bail!("provider module mock has no queries")
+    }
+}
+
+fn init_app(users: &[(&str, u128)]) -> App<MtApp> {
+    let app: MtApp = AppBuilder::new_custom()
+        .with_custom(ProviderModule::new())
+        .build(|router, _api, storage| {
+            for (user, amount) in users {
+                router
+                    .bank
+                    .init_balance(storage, &Addr::unchecked(*user), coins(*amount, OSMO))
+                    .unwrap();
+            }
+        });
+    App::new(app)
+}
+
+fn take_module_msgs(app: &App<MtApp>) -> Vec<VaultMsg> {
+    app.app_mut()
+        .init_modules(|router, _, storage| router.custom.take_msgs(storage))
+        .unwrap()
+}
+
+#[test]
+fn bonding_through_the_module() {
+    let owner = "owner";
+    let user = "user1";
+    let recipient = "user2";
+
+    let app = init_app(&[(user, 300)]);
+
+    let vault = contract::sv::mt::CodeId::store_code(&app)
+        .instantiate(OSMO.to_owned(), None, None, None, None)
+        .with_label("Vault")
+        .call(owner)
+        .unwrap();
+
+    // Bonded collateral is moved to the module, on behalf of its owner
+    vault
+        .bond()
+        .with_funds(&coins(300, OSMO))
+        .call(user)
+        .unwrap();
+    assert_eq!(
+        take_module_msgs(&app),
+        [VaultMsg::bond(user, coin(300, OSMO))]
+    );
+
+    // And moved back before being paid out
+    vault.unbond(coin(100, OSMO)).call(user).unwrap();
+    assert_eq!(
+        take_module_msgs(&app),
+        [VaultMsg::unbond(user, coin(100, OSMO))]
+    );
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(100, OSMO)
+    );
+
+    // A collateral transfer unbonds from the sender, and bonds to the recipient
+    vault
+        .transfer_collateral(recipient.to_owned(), coin(50, OSMO))
+        .call(user)
+        .unwrap();
+    assert_eq!(
+        take_module_msgs(&app),
+        [
+            VaultMsg::unbond(user, coin(50, OSMO)),
+            VaultMsg::bond(recipient, coin(50, OSMO)),
+        ]
+    );
+
+    // The accounting is the same as without the module
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.bonded.u128(), 150);
+    let account = vault.account(recipient.to_owned()).unwrap();
+    assert_eq!(account.bonded.u128(), 50);
+}
+
+#[test]
+fn vesting_handoff() {
+    let owner = "owner";
+    let user = "user1";
+
+    let app = init_app(&[(user, 100)]);
+
+    let vault = contract::sv::mt::CodeId::store_code(&app)
+        .instantiate(OSMO.to_owned(), None, None, None, None)
+        .with_label("Vault")
+        .call(owner)
+        .unwrap();
+
+    vault
+        .bond()
+        .with_funds(&coins(100, OSMO))
+        .call(user)
+        .unwrap();
+    take_module_msgs(&app);
+
+    // The module bonds vesting tokens it already holds
+    let err = vault
+        .bond_vesting(user.to_owned(), coin(200, "star"))
+        .unwrap_err();
+    assert_eq!(err, MeshError::InvalidDenom(OSMO.to_owned()).into());
+    vault
+        .bond_vesting(user.to_owned(), coin(200, OSMO))
+        .unwrap();
+    assert_eq!(take_module_msgs(&app), []);
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.bonded.u128(), 300);
+
+    // Vesting collateral can't leave the module
+    let err = vault
+        .transfer_collateral("user2".to_owned(), coin(150, OSMO))
+        .call(user)
+        .unwrap_err();
+    assert_eq!(err, ContractError::VestingLocked);
+
+    // The liquid collateral is unbonded first, then the vesting one is handed back to the
+    // vesting account
+    let resp = vault.unbond(coin(150, OSMO)).call(user).unwrap();
+    assert_eq!(
+        take_module_msgs(&app),
+        [
+            VaultMsg::return_vesting(user, coin(50, OSMO), Uint128::new(150)),
+            VaultMsg::unbond(user, coin(100, OSMO)),
+        ]
+    );
+    let handoff = resp
+        .events
+        .iter()
+        .find(|ev| ev.ty == "wasm-vesting_handoff")
+        .unwrap();
+    let attr = |key: &str| {
+        handoff
+            .attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.as_str())
+    };
+    assert_eq!(attr("delegator"), Some(user));
+    assert_eq!(attr("amount"), Some("50"));
+    assert_eq!(attr("remaining"), Some("150"));
+    // Only the liquid collateral is paid out by the vault
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(100, OSMO)
+    );
+
+    vault.unbond(coin(150, OSMO)).call(user).unwrap();
+    assert_eq!(
+        take_module_msgs(&app),
+        [VaultMsg::return_vesting(
+            user,
+            coin(150, OSMO),
+            Uint128::zero()
+        )]
+    );
+    assert_eq!(
+        app.app().wrap().query_balance(user, OSMO).unwrap(),
+        coin(100, OSMO)
+    );
+    let account = vault.account(user.to_owned()).unwrap();
+    assert_eq!(account.bonded.u128(), 0);
+}
@@ -0,0 +1,66 @@
+//! Liquid receipt token for bonded collateral.
+//!
+//! When enabled, the vault creates a tokenfactory denom at instantiation, and mints receipt
+//! tokens 1:1 for every bonded token. The receipt tokens can be freely used elsewhere, but they
+//! have to be sent back along with `unbond`, where they are burned.
+use cosmwasm_std::{Addr, CosmosMsg, MessageInfo, Uint128};
+use cw_utils::must_pay;
+use osmosis_std::types::cosmos::base::v1beta1::Coin as ProtoCoin;
+use osmosis_std::types::osmosis::tokenfactory::v1beta1::{MsgBurn, MsgCreateDenom, MsgMint};
+
+use crate::contract::custom;
+use crate::error::ContractError;
+
+/// Full tokenfactory denom of the receipt token
+pub fn receipt_denom(contract: &Addr, subdenom: &str) -> String {
+    format!("factory/{}/{}", contract, subdenom)
+}
+
+/// Creates the receipt token denom, owned by the vault
+pub fn create_denom_msg(contract: &Addr, subdenom: &str) -> CosmosMsg<custom::VaultMsg> {
+    MsgCreateDenom {
+        sender: contract.to_string(),
+        subdenom: subdenom.to_string(),
+    }
+    .into()
+}
+
+/// Mints `amount` receipt tokens to `recipient`
+pub fn mint_msg(
+    contract: &Addr,
+    denom: &str,
+    amount: Uint128,
+    recipient: &Addr,
+) -> CosmosMsg<custom::VaultMsg> {
+    MsgMint {
+        sender: contract.to_string(),
+        amount: Some(ProtoCoin {
+            denom: denom.to_string(),
+            amount: amount.to_string(),
+        }),
+        mint_to_address: recipient.to_string(),
+    }
+    .into()
+}
+
+/// Burns `amount` receipt tokens held by the vault
+pub fn burn_msg(contract: &Addr, denom: &str, amount: Uint128) -> CosmosMsg<custom::VaultMsg> {
+    MsgBurn {
+        sender: contract.to_string(),
+        amount: Some(ProtoCoin {
+            denom: denom.to_string(),
+            amount: amount.to_string(),
+        }),
+        burn_from_address: contract.to_string(),
+    }
+    .into()
+}
+
+/// Verifies exactly `amount` receipt tokens were sent along with the message
+pub fn must_return(info: &MessageInfo, denom: &str, amount: Uint128) -> Result<(), ContractError> {
+    let returned = must_pay(info, denom)?;
+    if returned != amount {
+        return Err(ContractError::InvalidReceiptAmount(amount));
+    }
+    Ok(())
+}
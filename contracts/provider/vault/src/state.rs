@@ -1,12 +1,48 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Decimal, Uint128};
+use cosmwasm_std::{Addr, Binary, Decimal, Timestamp, Uint128};
 use mesh_apis::local_staking_api::LocalStakingApiHelper;
 use mesh_sync::{max_range, ValueRange};
 
+use crate::contract::DEFAULT_MAX_LIENHOLDERS;
+use crate::msg::DenomMetadata;
+
 #[cw_serde]
 pub struct Config {
     /// The denom we accept for staking (only native tokens)
     pub denom: String,
+    /// Tokenfactory denom of the receipt token minted for bonded collateral, if enabled
+    pub receipt_denom: Option<String>,
+    /// Smallest amount accepted by `bond` and `transfer_collateral`
+    #[serde(default)]
+    pub min_bond: Uint128,
+    /// Smallest amount accepted by `unbond`, unless unbonding all the collateral
+    #[serde(default)]
+    pub min_unbond: Uint128,
+    /// Max ratio of the total slashable collateral to the total bonded collateral, over all the
+    /// accounts. New stakes beyond it are rejected
+    #[serde(default)]
+    pub max_utilization: Option<Decimal>,
+    /// Delay in seconds before unbonded collateral can be withdrawn, with `claim_matured`.
+    /// Unbonded collateral is sent right away if not set
+    #[serde(default)]
+    pub unbonding_period: Option<u64>,
+    /// Yield strategy free collateral can be deposited into, see `VaultStrategyApi`
+    #[serde(default)]
+    pub strategy: Option<Addr>,
+    /// Max number of distinct lienholders of an account, bounding the liens iterated over on
+    /// slashing. `DEFAULT_MAX_LIENHOLDERS` if not set
+    #[serde(default)]
+    pub max_lienholders: Option<u32>,
+    /// Display metadata of `denom` set at instantiation. Taken from the bank module if not set
+    #[serde(default)]
+    pub denom_metadata: Option<DenomMetadata>,
+}
+
+impl Config {
+    /// Effective max number of distinct lienholders of an account
+    pub fn max_lienholders(&self) -> u32 {
+        self.max_lienholders.unwrap_or(DEFAULT_MAX_LIENHOLDERS)
+    }
 }
 
 #[cw_serde]
@@ -57,3 +93,69 @@ impl UserInfo {
         self.collateral >= self.used_collateral().high()
     }
 }
+
+/// Free collateral of a user deposited into the yield strategy. It is not part of the user
+/// collateral until withdrawn
+#[cw_serde]
+#[derive(Default)]
+pub struct StrategyDeposit {
+    /// Shares of the vault deposits in the strategy
+    pub shares: Uint128,
+    /// Deposited amount
+    pub principal: Uint128,
+}
+
+/// Protocol-wide totals, maintained along with the users and the liens
+#[cw_serde]
+#[derive(Default)]
+pub struct VaultStats {
+    /// Total bonded collateral
+    pub total_collateral: Uint128,
+    /// Sum of the (high) total slashable amounts of all the accounts
+    pub total_slashable: Uint128,
+    /// Number of accounts
+    pub accounts: u64,
+}
+
+impl VaultStats {
+    /// Share of the collateral that is slashable, zero if nothing is bonded
+    pub fn utilization(&self) -> Decimal {
+        if self.total_collateral.is_zero() {
+            Decimal::zero()
+        } else {
+            Decimal::from_ratio(self.total_slashable, self.total_collateral)
+        }
+    }
+}
+
+/// Per-account auto-restaking preferences.
+///
+/// Claims released back to the vault are re-staked to `lienholder` (with `msg`),
+/// up to `cap` tokens per `period`, instead of becoming free collateral.
+#[cw_serde]
+pub struct AutoRestake {
+    /// Lienholder released claims are re-staked to
+    pub lienholder: Addr,
+    /// Message forwarded to the lienholder along with the stake (i.e. target validator)
+    pub msg: Binary,
+    /// Max amount re-staked over a single period
+    pub cap: Uint128,
+    /// Period length, in seconds
+    pub period: u64,
+    /// Start of the current period
+    pub period_start: Timestamp,
+    /// Amount already re-staked over the current period
+    pub restaked: Uint128,
+}
+
+impl AutoRestake {
+    /// Returns the amount that can still be re-staked at `now`, starting a new period
+    /// if the current one has expired
+    pub fn available(&mut self, now: Timestamp) -> Uint128 {
+        if now >= self.period_start.plus_seconds(self.period) {
+            self.period_start = now;
+            self.restaked = Uint128::zero();
+        }
+        self.cap.saturating_sub(self.restaked)
+    }
+}
@@ -0,0 +1,22 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use mesh_apis::ibc::test_vectors::test_vectors;
+
+/// Writes the IBC packet test vectors to the directory given as first argument
+/// (`./test_vectors` by default), one `<name>.json` file per vector.
+#[cfg(not(tarpaulin_include))]
+fn main() {
+    let out_dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("test_vectors"));
+    fs::create_dir_all(&out_dir).unwrap();
+
+    for vector in test_vectors().unwrap() {
+        let path = out_dir.join(format!("{}.json", vector.name));
+        fs::write(&path, vector.json.as_bytes()).unwrap();
+        println!("Created {}", path.display());
+    }
+}
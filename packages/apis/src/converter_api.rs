@@ -2,7 +2,7 @@
 #![allow(clippy::too_many_arguments)]
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Coin, CustomMsg, CustomQuery, Response, StdError, Uint128, Validator};
+use cosmwasm_std::{Coin, CustomMsg, CustomQuery, Decimal, Response, StdError, Uint128, Validator};
 use sylvia::types::ExecCtx;
 use sylvia::{interface, schemars};
 
@@ -50,6 +50,18 @@ pub trait ConverterApi {
         unjailed: Vec<String>,
         tombstoned: Vec<String>,
         slashed: Vec<ValidatorSlashInfo>,
+        uptimes: Vec<ValidatorUptime>,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    /// Sent by the virtual staking contract after the max cap was reduced, and the excess was
+    /// force-unbonded from the validators.
+    /// The converter reports these forced unbondings to the provider.
+    #[sv::msg(exec)]
+    fn max_cap_update(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        max_cap: Coin,
+        unbonds: Vec<ForcedUnbondInfo>,
     ) -> Result<Response<Self::ExecC>, Self::Error>;
 }
 
@@ -76,3 +88,32 @@ pub struct ValidatorSlashInfo {
     /// Useful in case we don't know if it's a double sign or downtime slash.
     pub slash_ratio: String,
 }
+
+/// Signing performance of a validator, over the consumer slashing window
+#[cw_serde]
+pub struct ValidatorUptime {
+    /// The address of the validator.
+    pub address: String,
+    /// The number of blocks missed over the signing window.
+    pub missed_blocks: u64,
+    /// The length of the signing window, in blocks.
+    pub window: u64,
+}
+
+impl ValidatorUptime {
+    /// Part of the window blocks signed. Fully up if there's no window yet
+    pub fn uptime(&self) -> Decimal {
+        if self.window == 0 {
+            return Decimal::one();
+        }
+        Decimal::from_ratio(self.window.saturating_sub(self.missed_blocks), self.window)
+    }
+}
+
+#[cw_serde]
+pub struct ForcedUnbondInfo {
+    /// The address of the validator.
+    pub validator: String,
+    /// The amount unbonded from the validator because of the max cap reduction.
+    pub amount: Coin,
+}
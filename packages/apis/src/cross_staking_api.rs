@@ -1,5 +1,7 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{to_json_binary, Addr, Binary, Coin, Deps, Response, StdError, WasmMsg};
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, Binary, Coin, Deps, Response, StdError, StdResult, WasmMsg,
+};
 use sylvia::types::{ExecCtx, QueryCtx};
 use sylvia::{interface, schemars};
 
@@ -43,9 +45,137 @@ pub trait CrossStakingApi {
         validator: Option<String>,
     ) -> Result<Response, Self::Error>;
 
+    /// Withdraws the rewards of `owner` from staking via `validator`, to `remote_recipient`.
+    /// This is called by the vault contract, when an operator granted by `owner` withdraws the
+    /// rewards on their behalf.
+    #[sv::msg(exec)]
+    fn withdraw_rewards_for(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        validator: String,
+        remote_recipient: String,
+    ) -> Result<Response, Self::Error>;
+
     /// Returns the maximum percentage that can be slashed
     #[sv::msg(query)]
     fn max_slash(&self, ctx: QueryCtx) -> Result<SlashRatioResponse, Self::Error>;
+
+    /// Returns the optional features supported by this implementation, so that the vault and
+    /// front-ends don't have to assume them
+    #[sv::msg(query)]
+    fn capabilities(&self, ctx: QueryCtx) -> Result<CapabilitiesResponse, Self::Error>;
+
+    /// Returns the rewards of `user` not withdrawn yet, summed over all the validators it stakes
+    /// with, in every rewards denom
+    #[sv::msg(query)]
+    fn total_pending_rewards(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+    ) -> Result<TotalPendingRewardsResponse, Self::Error>;
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct TotalPendingRewardsResponse {
+    pub rewards: Vec<Coin>,
+}
+
+/// Optional features of a cross staking contract
+#[cw_serde]
+#[derive(Default)]
+pub struct CapabilitiesResponse {
+    /// Whether stakes can be unstaked without waiting for the unbonding period
+    pub instant_unstake: bool,
+    /// Whether the rewards can be re-staked (compounded)
+    pub restake: bool,
+    /// Whether a single stake can be split over several validators
+    pub batch_stake: bool,
+    /// Max number of validators a user can stake to, if limited
+    pub max_validators_per_user: Option<u32>,
+}
+
+/// Payload of the `msg` field of `receive_virtual_stake` (and so of the vault `stake_remote`),
+/// telling the validator to stake on
+#[cw_serde]
+pub struct ReceiveVirtualStake {
+    pub validator: String,
+    /// Client-chosen key, so that a retried stake that already landed is rejected instead of
+    /// applied twice. Scoped to the owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
+
+impl ReceiveVirtualStake {
+    pub fn new(validator: impl Into<String>) -> Self {
+        Self {
+            validator: validator.into(),
+            idempotency_key: None,
+        }
+    }
+
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    pub fn validate(&self) -> StdResult<()> {
+        if self.validator.is_empty() {
+            return Err(StdError::generic_err(
+                "Empty validator in virtual stake msg",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Encodes the payload, to be sent as `msg` on `stake_remote`
+    pub fn encode(&self) -> StdResult<Binary> {
+        self.validate()?;
+        to_json_binary(self)
+    }
+
+    /// Decodes and validates the `msg` received on `receive_virtual_stake`
+    pub fn decode(msg: &Binary) -> StdResult<Self> {
+        let msg: Self = from_json(msg)?;
+        msg.validate()?;
+        Ok(msg)
+    }
+}
+
+/// How to split a stake across the validators, when not picking them manually
+#[cw_serde]
+pub enum ValidatorSelection {
+    /// Evenly across the `n` validators with the most stake from the provider
+    TopN { n: u32 },
+    /// Across all the active validators, in proportion of their stake from the provider
+    ProportionalToPower {},
+    /// Across the given validators, in proportion of their relative weights
+    Custom(Vec<(String, u64)>),
+}
+
+/// Payload of the `msg` field of `receive_virtual_stake` (and so of the vault
+/// `stake_remote_auto`), letting the contract select the validators to stake on
+#[cw_serde]
+pub struct AutoStake {
+    pub strategy: ValidatorSelection,
+    /// Rejects the stake if already used by the owner, see `ReceiveVirtualStake`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
+
+impl AutoStake {
+    pub fn new(strategy: ValidatorSelection) -> Self {
+        Self {
+            strategy,
+            idempotency_key: None,
+        }
+    }
+
+    /// Encodes the payload, to be sent as `msg` on `stake_remote`
+    pub fn encode(&self) -> StdResult<Binary> {
+        to_json_binary(self)
+    }
 }
 
 #[cw_serde]
@@ -97,8 +227,63 @@ impl CrossStakingApiHelper {
         Ok(wasm)
     }
 
+    pub fn withdraw_rewards_for(
+        &self,
+        owner: &Addr,
+        validator: String,
+        remote_recipient: String,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = sv::CrossStakingApiExecMsg::WithdrawRewardsFor {
+            owner: owner.to_string(),
+            validator,
+            remote_recipient,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn max_slash(&self, deps: Deps) -> Result<SlashRatioResponse, StdError> {
         let query = sv::CrossStakingApiQueryMsg::MaxSlash {};
         deps.querier.query_wasm_smart(&self.0, &query)
     }
+
+    pub fn capabilities(&self, deps: Deps) -> Result<CapabilitiesResponse, StdError> {
+        let query = sv::CrossStakingApiQueryMsg::Capabilities {};
+        deps.querier.query_wasm_smart(&self.0, &query)
+    }
+
+    pub fn total_pending_rewards(
+        &self,
+        deps: Deps,
+        user: String,
+    ) -> Result<TotalPendingRewardsResponse, StdError> {
+        let query = sv::CrossStakingApiQueryMsg::TotalPendingRewards { user };
+        deps.querier.query_wasm_smart(&self.0, &query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn receive_virtual_stake_roundtrip() {
+        let msg = ReceiveVirtualStake::new("val1").encode().unwrap();
+        assert_eq!(msg, Binary::from(br#"{"validator":"val1"}"#));
+        assert_eq!(
+            ReceiveVirtualStake::decode(&msg).unwrap(),
+            ReceiveVirtualStake::new("val1")
+        );
+
+        // Empty validators are rejected both ways
+        ReceiveVirtualStake::new("").encode().unwrap_err();
+        let msg = Binary::from(br#"{"validator":""}"#);
+        ReceiveVirtualStake::decode(&msg).unwrap_err();
+        // As well as the ad hoc empty payload
+        ReceiveVirtualStake::decode(&Binary::default()).unwrap_err();
+    }
 }
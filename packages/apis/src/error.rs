@@ -0,0 +1,45 @@
+use cosmwasm_std::{StdError, Uint128};
+use thiserror::Error;
+
+/// Errors shared by all the mesh contracts, so the same failure is reported with the same
+/// message whichever contract it comes from.
+///
+/// Every contract `ContractError` wraps it in a `Mesh` variant, displayed transparently.
+#[derive(Error, Debug, PartialEq)]
+pub enum MeshError {
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Invalid denom, {0} expected")]
+    InvalidDenom(String),
+
+    #[error("The address doesn't have sufficient balance for this operation")]
+    InsufficientBalance,
+
+    #[error("Not enough free collateral, only {0} available")]
+    InsufficientCollateral(Uint128),
+
+    #[error("Invalid reply id: {0}")]
+    InvalidReplyId(u64),
+
+    #[error("Contract has no open IBC channel")]
+    ChannelNotOpen,
+
+    #[error("Contract already has an open IBC channel")]
+    ChannelAlreadyOpen,
+
+    #[error("Cannot migrate from a different contract: {0}")]
+    InvalidContractName(String),
+
+    #[error("Invalid contract version: {0}")]
+    InvalidVersion(String),
+
+    #[error("Cannot migrate from version {0} down to {1}")]
+    MigrationDowngrade(String, String),
+}
+
+impl From<MeshError> for StdError {
+    fn from(err: MeshError) -> Self {
+        StdError::generic_err(err.to_string())
+    }
+}
@@ -0,0 +1,164 @@
+//! Typed events for the stake, unstake, slash and rewards actions.
+//!
+//! Every mesh contract reports these actions with the events below, so they carry the same
+//! attribute keys whatever the emitting contract. The emitting contract itself is available
+//! to indexers through the `_contract_address` attribute added by the chain.
+use cosmwasm_std::{Coin, Event};
+
+/// Account owning the stake
+pub const DELEGATOR_KEY: &str = "delegator";
+/// Validator the stake is delegated to
+pub const VALIDATOR_KEY: &str = "validator";
+/// Contract holding the lien over the collateral backing the stake
+pub const LIENHOLDER_KEY: &str = "lienholder";
+pub const AMOUNT_KEY: &str = "amount";
+pub const DENOM_KEY: &str = "denom";
+
+macro_rules! mesh_event {
+    ($(#[$meta:meta])* $name:ident, $ty:literal) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct $name {
+            pub delegator: Option<String>,
+            pub validator: Option<String>,
+            pub lienholder: Option<String>,
+            pub amount: Coin,
+        }
+
+        impl $name {
+            /// Event type, as seen by indexers
+            pub const TYPE: &'static str = $ty;
+
+            pub fn new(amount: Coin) -> Self {
+                Self {
+                    delegator: None,
+                    validator: None,
+                    lienholder: None,
+                    amount,
+                }
+            }
+
+            pub fn delegator(mut self, delegator: impl Into<String>) -> Self {
+                self.delegator = Some(delegator.into());
+                self
+            }
+
+            pub fn validator(mut self, validator: impl Into<String>) -> Self {
+                self.validator = Some(validator.into());
+                self
+            }
+
+            pub fn lienholder(mut self, lienholder: impl Into<String>) -> Self {
+                self.lienholder = Some(lienholder.into());
+                self
+            }
+        }
+
+        impl From<$name> for Event {
+            fn from(event: $name) -> Self {
+                build_event(
+                    $name::TYPE,
+                    event.delegator,
+                    event.validator,
+                    event.lienholder,
+                    event.amount,
+                )
+            }
+        }
+    };
+}
+
+mesh_event!(
+    /// Stake added by `delegator`, either as collateral liened to `lienholder` (vault),
+    /// or as a delegation to `validator` (staking contracts and converter)
+    StakeEvent,
+    "mesh_stake"
+);
+
+mesh_event!(
+    /// Stake removed by `delegator`, either released from the `lienholder` lien (vault),
+    /// or undelegated from `validator` (staking contracts and converter)
+    UnstakeEvent,
+    "mesh_unstake"
+);
+
+mesh_event!(
+    /// Collateral of `delegator` slashed for a misbehaviour of `validator`, as reported by
+    /// `lienholder`
+    SlashEvent,
+    "mesh_slash"
+);
+
+mesh_event!(
+    /// Rewards of `validator`, either distributed to all its delegators (no `delegator`),
+    /// or withdrawn by `delegator`
+    RewardsEvent,
+    "mesh_rewards"
+);
+
+/// Builds the event, skipping the unset attributes. The amount and denom are always set.
+fn build_event(
+    ty: &str,
+    delegator: Option<String>,
+    validator: Option<String>,
+    lienholder: Option<String>,
+    amount: Coin,
+) -> Event {
+    let mut event = Event::new(ty);
+    for (key, value) in [
+        (DELEGATOR_KEY, delegator),
+        (VALIDATOR_KEY, validator),
+        (LIENHOLDER_KEY, lienholder),
+    ] {
+        if let Some(value) = value {
+            event = event.add_attribute(key, value);
+        }
+    }
+    event
+        .add_attribute(AMOUNT_KEY, amount.amount.to_string())
+        .add_attribute(DENOM_KEY, amount.denom)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{attr, coin};
+
+    use super::*;
+
+    #[test]
+    fn events_have_consistent_keys() {
+        let event: Event = StakeEvent::new(coin(100, "uosmo"))
+            .delegator("alice")
+            .validator("val1")
+            .lienholder("cross_staking")
+            .into();
+        assert_eq!(event.ty, "mesh_stake");
+        assert_eq!(
+            event.attributes,
+            [
+                attr("delegator", "alice"),
+                attr("validator", "val1"),
+                attr("lienholder", "cross_staking"),
+                attr("amount", "100"),
+                attr("denom", "uosmo"),
+            ]
+        );
+
+        // Unset attributes are skipped
+        let event: Event = RewardsEvent::new(coin(7, "ustar")).validator("val1").into();
+        assert_eq!(event.ty, "mesh_rewards");
+        assert_eq!(
+            event.attributes,
+            [
+                attr("validator", "val1"),
+                attr("amount", "7"),
+                attr("denom", "ustar"),
+            ]
+        );
+
+        let event: Event = UnstakeEvent::new(coin(1, "uosmo")).delegator("bob").into();
+        assert_eq!(event.ty, "mesh_unstake");
+        let event: Event = SlashEvent::new(coin(1, "uosmo")).delegator("bob").into();
+        assert_eq!(event.ty, "mesh_slash");
+    }
+}
@@ -0,0 +1,113 @@
+use cosmwasm_std::{Uint128, Uint64};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a, good enough to detect accidental divergence. This is not meant to resist forgeries.
+fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Contribution of a single validator total stake to the checksum. Zero stakes don't
+/// contribute, so validators without stake don't need to be tracked on either side.
+fn stake_entry(validator: &str, stake: Uint128) -> u64 {
+    if stake.is_zero() {
+        return 0;
+    }
+    let bytes = validator
+        .bytes()
+        // Separator, so the validator address and stake can't be mixed up
+        .chain([0])
+        .chain(stake.u128().to_be_bytes());
+    fnv1a(bytes)
+}
+
+/// Checksum of the total stake per validator, as sent by the provider in
+/// `ProviderPacket::StakeChecksum`.
+///
+/// It is the (wrapping) sum of the hashes of all the `(validator, stake)` entries, so it doesn't
+/// depend on the validators order, and is updated incrementally on every stake change with
+/// [`StakeChecksum::update`], without iterating over all the validators.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StakeChecksum(u64);
+
+impl StakeChecksum {
+    /// Checksum of the given `(validator, stake)` entries
+    pub fn from_stakes<'a>(stakes: impl IntoIterator<Item = (&'a str, Uint128)>) -> Self {
+        stakes
+            .into_iter()
+            .fold(Self::default(), |checksum, (validator, stake)| {
+                checksum.update(validator, Uint128::zero(), stake)
+            })
+    }
+
+    /// Updates the checksum for the `validator` total stake changing from `old` to `new`
+    #[must_use]
+    pub fn update(self, validator: &str, old: Uint128, new: Uint128) -> Self {
+        Self(
+            self.0
+                .wrapping_sub(stake_entry(validator, old))
+                .wrapping_add(stake_entry(validator, new)),
+        )
+    }
+}
+
+impl From<StakeChecksum> for Uint64 {
+    fn from(checksum: StakeChecksum) -> Self {
+        Uint64::new(checksum.0)
+    }
+}
+
+impl From<Uint64> for StakeChecksum {
+    fn from(checksum: Uint64) -> Self {
+        Self(checksum.u64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stake_checksum_is_incremental() {
+        let stakes = [
+            ("val1", Uint128::new(100)),
+            ("val2", Uint128::new(250)),
+            ("val3", Uint128::new(7)),
+        ];
+        let checksum = StakeChecksum::from_stakes(stakes);
+
+        // Order independent
+        let reversed = StakeChecksum::from_stakes(stakes.into_iter().rev());
+        assert_eq!(checksum, reversed);
+
+        // Same as a full recomputation after an update
+        let updated = checksum.update("val2", Uint128::new(250), Uint128::new(200));
+        let expected = StakeChecksum::from_stakes([
+            ("val1", Uint128::new(100)),
+            ("val2", Uint128::new(200)),
+            ("val3", Uint128::new(7)),
+        ]);
+        assert_eq!(updated, expected);
+        assert_ne!(updated, checksum);
+
+        // Validators without stake don't count
+        let removed = updated.update("val3", Uint128::new(7), Uint128::zero());
+        let expected = StakeChecksum::from_stakes([
+            ("val1", Uint128::new(100)),
+            ("val2", Uint128::new(200)),
+            ("val4", Uint128::zero()),
+        ]);
+        assert_eq!(removed, expected);
+
+        // Stakes are bound to their validator
+        let swapped = StakeChecksum::from_stakes([
+            ("val1", Uint128::new(250)),
+            ("val2", Uint128::new(100)),
+            ("val3", Uint128::new(7)),
+        ]);
+        assert_ne!(swapped, checksum);
+    }
+}
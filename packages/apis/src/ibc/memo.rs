@@ -0,0 +1,27 @@
+use cosmwasm_schema::cw_serde;
+
+use crate::converter_api::RewardInfo;
+
+/// Memo of the ICS-20 rewards transfers from the converter to the provider contract.
+///
+/// ibc-hooks executes `wasm.msg` on the provider contract (the transfer receiver) with the
+/// transferred tokens, and reports the transfer outcome to the `ibc_callback` contract.
+#[cw_serde]
+pub struct RewardsTransferMemo {
+    pub wasm: RewardsTransferHook,
+    pub ibc_callback: String,
+}
+
+#[cw_serde]
+pub struct RewardsTransferHook {
+    /// Provider contract, also the receiver of the transfer
+    pub contract: String,
+    pub msg: RewardsTransferMsg,
+}
+
+/// Execute message of the provider contract, crediting the transferred tokens to the
+/// distributions of the validators
+#[cw_serde]
+pub enum RewardsTransferMsg {
+    ReceiveRewardsTransfer { rewards: Vec<RewardInfo> },
+}
@@ -1,5 +1,15 @@
+mod checksum;
+mod memo;
 mod packet;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod schema;
+#[cfg(any(test, feature = "test-vectors"))]
+pub mod test_vectors;
+mod timeout;
 mod version;
 
+pub use checksum::*;
+pub use memo::*;
 pub use packet::*;
+pub use timeout::*;
 pub use version::*;
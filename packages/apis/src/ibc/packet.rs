@@ -1,9 +1,13 @@
 use std::error::Error;
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{to_json_binary, Binary, Coin, Decimal, StdResult, Timestamp};
+use cosmwasm_std::{
+    from_json, to_json_binary, Binary, Coin, Decimal, StdResult, Timestamp, Uint128, Uint64,
+    VoteOption,
+};
 
-use crate::converter_api::{RewardInfo, ValidatorSlashInfo};
+use crate::converter_api::{ForcedUnbondInfo, RewardInfo, ValidatorSlashInfo, ValidatorUptime};
+use crate::ibc::Features;
 
 /// These are messages sent from provider -> consumer
 /// ibc_packet_receive in converter must handle them all.
@@ -20,6 +24,16 @@ pub enum ProviderPacket {
         /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
         tx_id: u64,
     },
+    /// This should be called when we lock more tokens to virtually stake, split across several
+    /// validators. It is a single transaction, acked with `StakeAck`.
+    StakeBatch {
+        /// Per-validator amounts, in the local (provider-side) denom
+        stakes: Vec<ValidatorStake>,
+        /// This is the local (provider-side) denom that is held in the vault.
+        denom: String,
+        /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
+        tx_id: u64,
+    },
     /// This should be called when we begin the unbonding period of some more tokens previously virtually staked
     Unstake {
         validator: String,
@@ -30,6 +44,14 @@ pub enum ProviderPacket {
         /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
         tx_id: u64,
     },
+    /// This should be called when we begin the unbonding period of tokens unstaked by several
+    /// users, queued by the provider. Its entries are separate transactions, but acked with a
+    /// single `UnstakeAck`, so they are committed or rolled back all together.
+    UnstakeBatch {
+        unstakes: Vec<BatchedUnstake>,
+        /// This is the local (provider-side) denom that is held in the vault.
+        denom: String,
+    },
     /// This should be called when we burn tokens from the given validators, because of slashing
     /// propagation / vault invariants keeping.
     /// If there is more than one validator, the burn amount will be split evenly between them.
@@ -50,13 +72,105 @@ pub enum ProviderPacket {
         /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
         tx_id: u64,
     },
+    /// This is sent to check the consumer stakes bookkeeping is consistent with the provider.
+    /// Only sent when no stake or unstake is in flight, so both sides should agree.
+    /// Informational only; on mismatch the consumer raises a reconciliation event.
+    StakeChecksum {
+        /// `StakeChecksum` of the total stake per validator, in the provider-side denom
+        checksum: Uint64,
+    },
+    /// This relays the votes of the cross-stakers on a consumer governance proposal, to be cast
+    /// by the virtual staking contract. It carries the whole tally of the proposal, replacing
+    /// the previous one. Non-transactional, failures are only logged.
+    Vote {
+        proposal_id: u64,
+        /// Cross-staked weight voting for each option, in the provider-side denom
+        tally: Vec<VoteWeight>,
+    },
+}
+
+impl ProviderPacket {
+    /// Type of the packet, as reported in events and used for the timeout overrides
+    pub fn packet_type(&self) -> &'static str {
+        match self {
+            ProviderPacket::Stake { .. } => "stake",
+            ProviderPacket::StakeBatch { .. } => "stake_batch",
+            ProviderPacket::Unstake { .. } => "unstake",
+            ProviderPacket::UnstakeBatch { .. } => "unstake_batch",
+            ProviderPacket::Burn { .. } => "burn",
+            ProviderPacket::TransferRewards { .. } => "transfer_rewards",
+            ProviderPacket::StakeChecksum { .. } => "stake_checksum",
+            ProviderPacket::Vote { .. } => "vote",
+        }
+    }
+
+    /// Encodes the packet, in a `V1` envelope if the channel `features` allow it
+    pub fn encode(&self, features: Features) -> StdResult<Binary> {
+        if features.contains(Features::VERSIONED_PACKETS) {
+            to_json_binary(&VersionedProviderPacket::V1(self.clone()))
+        } else {
+            to_json_binary(self)
+        }
+    }
+
+    /// Decodes a packet, either bare or in a versioned envelope
+    pub fn decode(data: &[u8]) -> StdResult<Self> {
+        match from_json(data) {
+            Ok(VersionedProviderPacket::V1(packet)) => Ok(packet),
+            Err(_) => from_json(data),
+        }
+    }
+}
+
+/// Versioned envelope of the provider -> consumer packets.
+/// Sent instead of the bare packets on channels with `Features::VERSIONED_PACKETS`, so later
+/// incompatible packet formats can be told apart. Both forms are accepted on receipt.
+#[cw_serde]
+pub enum VersionedProviderPacket {
+    V1(ProviderPacket),
 }
 
-/// Ack sent for ProviderPacket::Stake
+/// Stake on a single validator, part of ProviderPacket::StakeBatch
+#[cw_serde]
+pub struct ValidatorStake {
+    pub validator: String,
+    pub stake: Uint128,
+}
+
+/// Unstake of a single user, part of ProviderPacket::UnstakeBatch
+#[cw_serde]
+pub struct BatchedUnstake {
+    pub validator: String,
+    /// Amount in the local (provider-side) denom
+    pub unstake: Uint128,
+    /// This is local to the sending side to track the transaction, should be passed through opaquely on the consumer
+    pub tx_id: u64,
+}
+
+/// Weight voting for an option, part of ProviderPacket::Vote
+#[cw_serde]
+pub struct VoteWeight {
+    pub option: VoteOption,
+    pub weight: Uint128,
+}
+
+/// Position of a packet in a valset update split over several packets, part of
+/// ConsumerPacket::ValsetUpdate.
+/// The entries are split in the order the provider applies them (slashed, tombstoned, additions,
+/// jailed, removals, updated, unjailed), and all of the chunks have the same height and time.
+#[cw_serde]
+pub struct ValsetChunk {
+    /// Zero-based index of this chunk
+    pub index: u32,
+    /// Number of chunks of the update
+    pub total: u32,
+}
+
+/// Ack sent for ProviderPacket::Stake and ProviderPacket::StakeBatch
 #[cw_serde]
 pub struct StakeAck {}
 
-/// Ack sent for ProviderPacket::Unstake
+/// Ack sent for ProviderPacket::Unstake and ProviderPacket::UnstakeBatch
 #[cw_serde]
 pub struct UnstakeAck {}
 
@@ -64,6 +178,14 @@ pub struct UnstakeAck {}
 #[cw_serde]
 pub struct TransferRewardsAck {}
 
+/// Ack sent for ProviderPacket::StakeChecksum
+#[cw_serde]
+pub struct StakeChecksumAck {}
+
+/// Ack sent for ProviderPacket::Vote
+#[cw_serde]
+pub struct VoteAck {}
+
 /// These are messages sent from consumer -> provider
 /// ibc_packet_receive in external-staking must handle them all.
 #[cw_serde]
@@ -109,6 +231,14 @@ pub enum ConsumerPacket {
         /// for that validator.
         /// This has precedence over all other events in the same packet.
         slashed: Vec<ValidatorSlashInfo>,
+        /// Signing performance of the validators, reported periodically on channels with
+        /// `Features::VALIDATOR_HEALTH`. Informational only, the validators state is not modified.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        uptimes: Vec<ValidatorUptime>,
+        /// Set when a large update is split over several packets, on channels with
+        /// `Features::VALSET_CHUNKS`. Every chunk is applied on its own, in any order.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        chunk: Option<ValsetChunk>,
     },
     /// This is part of the rewards protocol
     Distribute {
@@ -124,6 +254,50 @@ pub enum ConsumerPacket {
         /// Rewards denom
         denom: String,
     },
+    /// This is sent when the max cap of the consumer was reduced below the virtually staked amount,
+    /// and the excess was force-unbonded from the validators.
+    /// Informational only; the provider side stakes are not modified.
+    MaxCapUpdate {
+        /// The new max cap, in the provider-side denom
+        max_cap: Coin,
+        /// Per-validator unbonded amounts, in the provider-side denom
+        unbonds: Vec<ForcedUnbondInfo>,
+    },
+}
+
+impl ConsumerPacket {
+    /// Type of the packet, as reported in events and used for the timeout overrides
+    pub fn packet_type(&self) -> &'static str {
+        match self {
+            ConsumerPacket::ValsetUpdate { .. } => "valset_update",
+            ConsumerPacket::Distribute { .. } => "distribute",
+            ConsumerPacket::DistributeBatch { .. } => "distribute_batch",
+            ConsumerPacket::MaxCapUpdate { .. } => "max_cap_update",
+        }
+    }
+
+    /// Encodes the packet, in a `V1` envelope if the channel `features` allow it
+    pub fn encode(&self, features: Features) -> StdResult<Binary> {
+        if features.contains(Features::VERSIONED_PACKETS) {
+            to_json_binary(&VersionedConsumerPacket::V1(self.clone()))
+        } else {
+            to_json_binary(self)
+        }
+    }
+
+    /// Decodes a packet, either bare or in a versioned envelope
+    pub fn decode(data: &[u8]) -> StdResult<Self> {
+        match from_json(data) {
+            Ok(VersionedConsumerPacket::V1(packet)) => Ok(packet),
+            Err(_) => from_json(data),
+        }
+    }
+}
+
+/// Versioned envelope of the consumer -> provider packets, see `VersionedProviderPacket`
+#[cw_serde]
+pub enum VersionedConsumerPacket {
+    V1(ConsumerPacket),
 }
 
 #[cw_serde]
@@ -135,6 +309,26 @@ pub struct AddValidator {
     /// This is the *Tendermint* public key, used for signing blocks.
     /// This is needed to detect slashing conditions
     pub pub_key: String,
+
+    /// Self-delegation of the validator operator, in the consumer-side staking denom.
+    /// `None` if not reported by the consumer.
+    #[serde(default)]
+    pub self_stake: Option<Uint128>,
+
+    /// Maximum cross-stake the consumer accepts on this validator from the provider, in the
+    /// provider-side denom. `None` if uncapped.
+    #[serde(default)]
+    pub max_external_stake: Option<Uint128>,
+
+    /// Commission rate of the validator on the consumer, for display on the provider.
+    /// `None` if not reported by the consumer.
+    #[serde(default)]
+    pub commission: Option<Decimal>,
+
+    /// Human readable name of the validator, for display on the provider.
+    /// `None` if not reported by the consumer.
+    #[serde(default)]
+    pub moniker: Option<String>,
 }
 
 impl AddValidator {
@@ -142,6 +336,10 @@ impl AddValidator {
         Self {
             valoper: valoper.to_string(),
             pub_key: "mock-pubkey".to_string(),
+            self_stake: None,
+            max_external_stake: None,
+            commission: None,
+            moniker: None,
         }
     }
 }
@@ -154,6 +352,10 @@ pub struct ValsetUpdateAck {}
 #[cw_serde]
 pub struct DistributeAck {}
 
+/// Ack sent for ConsumerPacket::MaxCapUpdate
+#[cw_serde]
+pub struct MaxCapUpdateAck {}
+
 /// This is a generic ICS acknowledgement format.
 /// Protobuf defined here: https://github.com/cosmos/cosmos-sdk/blob/v0.42.0/proto/ibc/core/channel/v1/channel.proto#L141-L147
 /// This is compatible with the JSON serialization.
@@ -0,0 +1,35 @@
+//! JSON schemas of the IBC packets and acks, written next to the contract schemas by the
+//! `schema` binaries, so the SDK side (Go module) can generate its types from them.
+
+use std::fs;
+use std::path::Path;
+
+use cosmwasm_schema::{export_schema, schema_for};
+
+use crate::ibc::{
+    AckWrapper, ConsumerPacket, DistributeAck, MaxCapUpdateAck, ProtocolVersion, ProviderPacket,
+    RewardsTransferMemo, StakeAck, StakeChecksumAck, TransferRewardsAck, UnstakeAck,
+    ValsetUpdateAck, VersionedConsumerPacket, VersionedProviderPacket, VoteAck,
+};
+
+/// Writes one `<type>.json` schema file per IBC packet, envelope and ack type, and for the
+/// rewards transfer memo, to `out_dir`
+pub fn export_schemas(out_dir: &Path) {
+    fs::create_dir_all(out_dir).unwrap();
+
+    export_schema(&schema_for!(ProtocolVersion), out_dir);
+    export_schema(&schema_for!(ProviderPacket), out_dir);
+    export_schema(&schema_for!(VersionedProviderPacket), out_dir);
+    export_schema(&schema_for!(ConsumerPacket), out_dir);
+    export_schema(&schema_for!(VersionedConsumerPacket), out_dir);
+    export_schema(&schema_for!(AckWrapper), out_dir);
+    export_schema(&schema_for!(StakeAck), out_dir);
+    export_schema(&schema_for!(UnstakeAck), out_dir);
+    export_schema(&schema_for!(TransferRewardsAck), out_dir);
+    export_schema(&schema_for!(StakeChecksumAck), out_dir);
+    export_schema(&schema_for!(VoteAck), out_dir);
+    export_schema(&schema_for!(ValsetUpdateAck), out_dir);
+    export_schema(&schema_for!(DistributeAck), out_dir);
+    export_schema(&schema_for!(MaxCapUpdateAck), out_dir);
+    export_schema(&schema_for!(RewardsTransferMemo), out_dir);
+}
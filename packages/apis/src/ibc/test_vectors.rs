@@ -0,0 +1,435 @@
+//! Canonical JSON encodings of every IBC packet and ack type, built from fixed sample data.
+//!
+//! These are meant for the SDK side (Go module) developers, to verify their serialization is
+//! byte-for-byte compatible with the one of the contracts. Run the `test_vectors` binary
+//! (`cargo run -p mesh-apis --features test-vectors --bin test_vectors`) to dump them.
+
+use cosmwasm_std::{
+    coin, to_json_string, Binary, Decimal, StdError, StdResult, Timestamp, Uint128, Uint64,
+    VoteOption,
+};
+
+use crate::converter_api::{ForcedUnbondInfo, RewardInfo, ValidatorSlashInfo};
+use crate::ibc::{
+    ack_fail, ack_success, AddValidator, BatchedUnstake, ConsumerPacket, DistributeAck,
+    MaxCapUpdateAck, PriceFeedProviderAck, ProtocolVersion, ProviderPacket, RemotePriceFeedPacket,
+    RewardsTransferHook, RewardsTransferMemo, RewardsTransferMsg, StakeAck, StakeChecksumAck,
+    TransferRewardsAck, UnstakeAck, ValidatorStake, ValsetChunk, ValsetUpdateAck,
+    VersionedConsumerPacket, VersionedProviderPacket, VoteAck, VoteWeight, PROTOCOL_NAME,
+};
+
+const VALIDATOR: &str = "cosmosvaloper1sample0validator0address";
+const VALIDATOR2: &str = "cosmosvaloper1sample0validator1address";
+const PROVIDER_DENOM: &str = "uosmo";
+const CONSUMER_DENOM: &str = "ujuno";
+const RECIPIENT: &str = "juno1sample0recipient0address";
+const PROVIDER_CONTRACT: &str = "osmo1sample0external0staking0address";
+const CONVERTER: &str = "juno1sample0converter0address";
+
+/// A single named test vector
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestVector {
+    /// Unique name, usable as a file name
+    pub name: &'static str,
+    /// Canonical JSON encoding
+    pub json: String,
+}
+
+impl TestVector {
+    fn new(name: &'static str, json: String) -> Self {
+        Self { name, json }
+    }
+}
+
+fn ack_vector(name: &'static str, ack: StdResult<Binary>) -> StdResult<TestVector> {
+    let json = String::from_utf8(ack?.to_vec())?;
+    Ok(TestVector::new(name, json))
+}
+
+/// Sample provider -> consumer packets, one per variant
+pub fn provider_packets() -> Vec<(&'static str, ProviderPacket)> {
+    vec![
+        (
+            "provider_packet_stake",
+            ProviderPacket::Stake {
+                validator: VALIDATOR.to_string(),
+                stake: coin(1_000_000, PROVIDER_DENOM),
+                tx_id: 1,
+            },
+        ),
+        (
+            "provider_packet_stake_batch",
+            ProviderPacket::StakeBatch {
+                stakes: vec![
+                    ValidatorStake {
+                        validator: VALIDATOR.to_string(),
+                        stake: Uint128::new(600_000),
+                    },
+                    ValidatorStake {
+                        validator: VALIDATOR2.to_string(),
+                        stake: Uint128::new(400_000),
+                    },
+                ],
+                denom: PROVIDER_DENOM.to_string(),
+                tx_id: 4,
+            },
+        ),
+        (
+            "provider_packet_unstake",
+            ProviderPacket::Unstake {
+                validator: VALIDATOR.to_string(),
+                unstake: coin(500_000, PROVIDER_DENOM),
+                tx_id: 2,
+            },
+        ),
+        (
+            "provider_packet_unstake_batch",
+            ProviderPacket::UnstakeBatch {
+                unstakes: vec![
+                    BatchedUnstake {
+                        validator: VALIDATOR.to_string(),
+                        unstake: Uint128::new(200_000),
+                        tx_id: 5,
+                    },
+                    BatchedUnstake {
+                        validator: VALIDATOR2.to_string(),
+                        unstake: Uint128::new(100_000),
+                        tx_id: 6,
+                    },
+                ],
+                denom: PROVIDER_DENOM.to_string(),
+            },
+        ),
+        (
+            "provider_packet_burn",
+            ProviderPacket::Burn {
+                validators: vec![VALIDATOR.to_string(), VALIDATOR2.to_string()],
+                burn: coin(10_000, PROVIDER_DENOM),
+            },
+        ),
+        (
+            "provider_packet_transfer_rewards",
+            ProviderPacket::TransferRewards {
+                rewards: coin(1_234, CONSUMER_DENOM),
+                recipient: RECIPIENT.to_string(),
+                tx_id: 3,
+            },
+        ),
+        (
+            "provider_packet_stake_checksum",
+            ProviderPacket::StakeChecksum {
+                checksum: Uint64::new(0x0123_4567_89ab_cdef),
+            },
+        ),
+        (
+            "provider_packet_vote",
+            ProviderPacket::Vote {
+                proposal_id: 42,
+                tally: vec![
+                    VoteWeight {
+                        option: VoteOption::Yes,
+                        weight: Uint128::new(7_000),
+                    },
+                    VoteWeight {
+                        option: VoteOption::NoWithVeto,
+                        weight: Uint128::new(3_000),
+                    },
+                ],
+            },
+        ),
+    ]
+}
+
+/// Sample consumer -> provider packets, one per variant
+pub fn consumer_packets() -> Vec<(&'static str, ConsumerPacket)> {
+    vec![
+        (
+            "consumer_packet_valset_update",
+            ConsumerPacket::ValsetUpdate {
+                height: 12_345,
+                time: 1_700_000_000,
+                additions: vec![AddValidator {
+                    valoper: VALIDATOR.to_string(),
+                    pub_key: "sample-pubkey".to_string(),
+                    self_stake: Some(Uint128::new(1_000_000)),
+                    max_external_stake: Some(Uint128::new(50_000_000)),
+                    commission: Some(Decimal::percent(5)),
+                    moniker: Some("sample-validator".to_string()),
+                }],
+                removals: vec![VALIDATOR2.to_string()],
+                updated: vec![],
+                jailed: vec![VALIDATOR2.to_string()],
+                unjailed: vec![],
+                tombstoned: vec![],
+                slashed: vec![ValidatorSlashInfo {
+                    address: VALIDATOR2.to_string(),
+                    infraction_height: 12_000,
+                    infraction_time: 1_699_990_000,
+                    power: 100,
+                    slash_amount: coin(5_000, PROVIDER_DENOM),
+                    slash_ratio: "0.010000000000000000".to_string(),
+                }],
+                uptimes: vec![],
+                chunk: None,
+            },
+        ),
+        (
+            "consumer_packet_valset_update_chunk",
+            ConsumerPacket::ValsetUpdate {
+                height: 12_345,
+                time: 1_700_000_000,
+                additions: vec![],
+                removals: vec![VALIDATOR.to_string()],
+                updated: vec![],
+                jailed: vec![],
+                unjailed: vec![],
+                tombstoned: vec![],
+                slashed: vec![],
+                uptimes: vec![],
+                chunk: Some(ValsetChunk { index: 1, total: 2 }),
+            },
+        ),
+        (
+            "consumer_packet_distribute",
+            ConsumerPacket::Distribute {
+                validator: VALIDATOR.to_string(),
+                rewards: coin(1_234, CONSUMER_DENOM),
+            },
+        ),
+        (
+            "consumer_packet_distribute_batch",
+            ConsumerPacket::DistributeBatch {
+                rewards: vec![
+                    RewardInfo {
+                        validator: VALIDATOR.to_string(),
+                        reward: Uint128::new(1_000),
+                    },
+                    RewardInfo {
+                        validator: VALIDATOR2.to_string(),
+                        reward: Uint128::new(234),
+                    },
+                ],
+                denom: CONSUMER_DENOM.to_string(),
+            },
+        ),
+        (
+            "consumer_packet_max_cap_update",
+            ConsumerPacket::MaxCapUpdate {
+                max_cap: coin(2_000_000, PROVIDER_DENOM),
+                unbonds: vec![ForcedUnbondInfo {
+                    validator: VALIDATOR.to_string(),
+                    amount: coin(300_000, PROVIDER_DENOM),
+                }],
+            },
+        ),
+    ]
+}
+
+/// Returns all the test vectors, in a stable order
+pub fn test_vectors() -> StdResult<Vec<TestVector>> {
+    let mut vectors = vec![];
+
+    for (name, packet) in provider_packets() {
+        vectors.push(TestVector::new(name, to_json_string(&packet)?));
+    }
+    for (name, packet) in consumer_packets() {
+        vectors.push(TestVector::new(name, to_json_string(&packet)?));
+    }
+    // The envelopes wrap the packets as they are, a sample of each is enough
+    let (_, stake) = &provider_packets()[0];
+    vectors.push(TestVector::new(
+        "provider_packet_stake_v1",
+        to_json_string(&VersionedProviderPacket::V1(stake.clone()))?,
+    ));
+    let (_, valset_update) = &consumer_packets()[0];
+    vectors.push(TestVector::new(
+        "consumer_packet_valset_update_v1",
+        to_json_string(&VersionedConsumerPacket::V1(valset_update.clone()))?,
+    ));
+
+    vectors.push(ack_vector("ack_stake", ack_success(&StakeAck {}))?);
+    vectors.push(ack_vector("ack_unstake", ack_success(&UnstakeAck {}))?);
+    vectors.push(ack_vector(
+        "ack_transfer_rewards",
+        ack_success(&TransferRewardsAck {}),
+    )?);
+    vectors.push(ack_vector(
+        "ack_stake_checksum",
+        ack_success(&StakeChecksumAck {}),
+    )?);
+    vectors.push(ack_vector("ack_vote", ack_success(&VoteAck {}))?);
+    vectors.push(ack_vector(
+        "ack_valset_update",
+        ack_success(&ValsetUpdateAck {}),
+    )?);
+    vectors.push(ack_vector(
+        "ack_distribute",
+        ack_success(&DistributeAck {}),
+    )?);
+    vectors.push(ack_vector(
+        "ack_max_cap_update",
+        ack_success(&MaxCapUpdateAck {}),
+    )?);
+    vectors.push(ack_vector(
+        "ack_error",
+        ack_fail(StdError::generic_err("sample error")),
+    )?);
+
+    vectors.push(TestVector::new(
+        "protocol_version",
+        to_json_string(&ProtocolVersion::new(PROTOCOL_NAME, "0.11.0"))?,
+    ));
+    vectors.push(TestVector::new(
+        "rewards_transfer_memo",
+        to_json_string(&RewardsTransferMemo {
+            wasm: RewardsTransferHook {
+                contract: PROVIDER_CONTRACT.to_string(),
+                msg: RewardsTransferMsg::ReceiveRewardsTransfer {
+                    rewards: vec![RewardInfo {
+                        validator: VALIDATOR.to_string(),
+                        reward: Uint128::new(1234),
+                    }],
+                },
+            },
+            ibc_callback: CONVERTER.to_string(),
+        })?,
+    ));
+    vectors.push(TestVector::new(
+        "remote_price_feed_packet_query_twap",
+        to_json_string(&RemotePriceFeedPacket::QueryTwap {
+            pool_id: 1,
+            base_asset: CONSUMER_DENOM.to_string(),
+            quote_asset: PROVIDER_DENOM.to_string(),
+        })?,
+    ));
+    vectors.push(TestVector::new(
+        "price_feed_provider_ack_update",
+        to_json_string(&PriceFeedProviderAck::Update {
+            time: Timestamp::from_seconds(1_700_000_000),
+            twap: Decimal::percent(150),
+        })?,
+    ));
+
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use cosmwasm_std::{from_json, to_json_binary};
+
+    use super::*;
+    use crate::ibc::{AckWrapper, Features};
+
+    #[test]
+    fn names_are_unique() {
+        let vectors = test_vectors().unwrap();
+        let names: HashSet<_> = vectors.iter().map(|v| v.name).collect();
+        assert_eq!(names.len(), vectors.len());
+    }
+
+    #[test]
+    fn packets_roundtrip() {
+        for (name, packet) in provider_packets() {
+            let json = to_json_string(&packet).unwrap();
+            let decoded: ProviderPacket = from_json(json.as_bytes()).unwrap();
+            assert_eq!(decoded, packet, "{}", name);
+        }
+        for (name, packet) in consumer_packets() {
+            let json = to_json_string(&packet).unwrap();
+            let decoded: ConsumerPacket = from_json(json.as_bytes()).unwrap();
+            assert_eq!(decoded, packet, "{}", name);
+        }
+    }
+
+    #[test]
+    fn canonical_encodings() {
+        let vectors = test_vectors().unwrap();
+        let get = |name: &str| {
+            vectors
+                .iter()
+                .find(|v| v.name == name)
+                .map(|v| v.json.as_str())
+                .unwrap()
+        };
+
+        assert_eq!(
+            get("provider_packet_stake"),
+            r#"{"stake":{"validator":"cosmosvaloper1sample0validator0address","stake":{"denom":"uosmo","amount":"1000000"},"tx_id":1}}"#
+        );
+        assert_eq!(
+            get("consumer_packet_distribute"),
+            r#"{"distribute":{"validator":"cosmosvaloper1sample0validator0address","rewards":{"denom":"ujuno","amount":"1234"}}}"#
+        );
+        // Unchunked valset updates are encoded as before the chunks
+        assert!(!get("consumer_packet_valset_update").contains("chunk"));
+        assert_eq!(
+            get("consumer_packet_valset_update_chunk"),
+            r#"{"valset_update":{"height":12345,"time":1700000000,"additions":[],"removals":["cosmosvaloper1sample0validator0address"],"updated":[],"jailed":[],"unjailed":[],"tombstoned":[],"slashed":[],"chunk":{"index":1,"total":2}}}"#
+        );
+        assert_eq!(
+            get("provider_packet_stake_v1"),
+            format!(r#"{{"v1":{}}}"#, get("provider_packet_stake"))
+        );
+        assert_eq!(
+            get("consumer_packet_valset_update_v1"),
+            format!(r#"{{"v1":{}}}"#, get("consumer_packet_valset_update"))
+        );
+        // The ack result is base64 encoded `{}`
+        assert_eq!(get("ack_stake"), r#"{"result":"e30="}"#);
+        assert_eq!(
+            get("ack_error"),
+            r#"{"error":"Generic error: sample error"}"#
+        );
+        assert_eq!(
+            get("protocol_version"),
+            r#"{"protocol":"mesh-security","version":"0.11.0"}"#
+        );
+    }
+
+    #[test]
+    fn packets_decode_with_and_without_envelope() {
+        let versioned = Features::VERSIONED_PACKETS;
+        for (name, packet) in provider_packets() {
+            let bare = packet.encode(Features::empty()).unwrap();
+            assert_eq!(bare, to_json_binary(&packet).unwrap(), "{}", name);
+            assert_eq!(ProviderPacket::decode(&bare).unwrap(), packet, "{}", name);
+            let enveloped = packet.encode(versioned).unwrap();
+            assert_eq!(
+                ProviderPacket::decode(&enveloped).unwrap(),
+                packet,
+                "{}",
+                name
+            );
+        }
+        for (name, packet) in consumer_packets() {
+            let bare = packet.encode(Features::empty()).unwrap();
+            assert_eq!(bare, to_json_binary(&packet).unwrap(), "{}", name);
+            assert_eq!(ConsumerPacket::decode(&bare).unwrap(), packet, "{}", name);
+            let enveloped = packet.encode(versioned).unwrap();
+            assert_eq!(
+                ConsumerPacket::decode(&enveloped).unwrap(),
+                packet,
+                "{}",
+                name
+            );
+        }
+
+        // Unknown envelope versions are rejected
+        let err = ProviderPacket::decode(br#"{"v2":{"stake":{}}}"#).unwrap_err();
+        assert!(matches!(err, StdError::ParseErr { .. }));
+    }
+
+    #[test]
+    fn acks_decode() {
+        let vectors = test_vectors().unwrap();
+        for vector in vectors.iter().filter(|v| v.name.starts_with("ack_")) {
+            let ack: AckWrapper = from_json(vector.json.as_bytes()).unwrap();
+            match ack {
+                AckWrapper::Result(data) => assert_eq!(data.as_slice(), b"{}", "{}", vector.name),
+                AckWrapper::Error(err) => assert_eq!(err, "Generic error: sample error"),
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use cosmwasm_std::{IbcTimeout, Timestamp};
+
+/// Timeouts of the packets sent by a contract, and backoff of the retries of the failed ones.
+///
+/// Built as a `const`, so each contract declares its policy in a single place:
+///
+/// ```
+/// # use mesh_apis::ibc::TimeoutPolicy;
+/// const POLICY: TimeoutPolicy = TimeoutPolicy::new(60 * 60)
+///     .with_overrides(&[("valset_update", 24 * 60 * 60)])
+///     .with_backoff(10 * 60, 24 * 60 * 60)
+///     .with_max_failures(5);
+/// ```
+///
+/// Packet types are the names reported in events, see `ProviderPacket::packet_type` and
+/// `ConsumerPacket::packet_type`. All durations are in seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeoutPolicy {
+    default_timeout: u64,
+    overrides: &'static [(&'static str, u64)],
+    retry_base_delay: u64,
+    max_retry_delay: u64,
+    max_failures: Option<u32>,
+}
+
+impl TimeoutPolicy {
+    /// Policy timing out all the packets after `default_timeout`, with retries allowed right
+    /// away and without limit
+    pub const fn new(default_timeout: u64) -> Self {
+        Self {
+            default_timeout,
+            overrides: &[],
+            retry_base_delay: 0,
+            max_retry_delay: 0,
+            max_failures: None,
+        }
+    }
+
+    /// Timeouts of specific packet types, instead of the default one
+    pub const fn with_overrides(mut self, overrides: &'static [(&'static str, u64)]) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Exponential backoff of the retries: `base_delay` after the first failure, doubling with
+    /// every further one, up to `max_delay`
+    pub const fn with_backoff(mut self, base_delay: u64, max_delay: u64) -> Self {
+        self.retry_base_delay = base_delay;
+        self.max_retry_delay = max_delay;
+        self
+    }
+
+    /// No more retries after `max_failures` failures
+    pub const fn with_max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = Some(max_failures);
+        self
+    }
+
+    /// Timeout of the `packet_type` packets, in seconds
+    pub fn timeout_secs(&self, packet_type: &str) -> u64 {
+        self.overrides
+            .iter()
+            .find(|(ty, _)| *ty == packet_type)
+            .map_or(self.default_timeout, |(_, timeout)| *timeout)
+    }
+
+    /// Time a `packet_type` packet sent `now` times out at
+    pub fn timeout_at(&self, now: Timestamp, packet_type: &str) -> Timestamp {
+        now.plus_seconds(self.timeout_secs(packet_type))
+    }
+
+    /// IBC timeout of a `packet_type` packet sent `now`
+    pub fn timeout(&self, now: Timestamp, packet_type: &str) -> IbcTimeout {
+        IbcTimeout::with_timestamp(self.timeout_at(now, packet_type))
+    }
+
+    /// Delay before retrying after `failures` failures, in seconds
+    pub fn retry_delay(&self, failures: u32) -> u64 {
+        let doublings = failures.saturating_sub(1);
+        let delay = match 1u64.checked_shl(doublings) {
+            Some(factor) => self.retry_base_delay.saturating_mul(factor),
+            None => u64::MAX,
+        };
+        delay.min(self.max_retry_delay.max(self.retry_base_delay))
+    }
+
+    /// Time of the next retry, after `failures` failures the last of which happened `now`
+    pub fn retry_at(&self, now: Timestamp, failures: u32) -> Timestamp {
+        now.plus_seconds(self.retry_delay(failures))
+    }
+
+    /// Whether no more retries are allowed after `failures` failures
+    pub fn retries_exhausted(&self, failures: u32) -> bool {
+        self.max_failures.is_some_and(|max| failures >= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: TimeoutPolicy = TimeoutPolicy::new(600)
+        .with_overrides(&[("valset_update", 3600)])
+        .with_backoff(60, 300)
+        .with_max_failures(4);
+
+    #[test]
+    fn timeouts_by_packet_type() {
+        let now = Timestamp::from_seconds(1000);
+        assert_eq!(POLICY.timeout_secs("stake"), 600);
+        assert_eq!(POLICY.timeout_secs("valset_update"), 3600);
+        assert_eq!(
+            POLICY.timeout(now, "valset_update"),
+            IbcTimeout::with_timestamp(Timestamp::from_seconds(4600))
+        );
+        assert_eq!(
+            TimeoutPolicy::new(600).timeout_at(now, "valset_update"),
+            Timestamp::from_seconds(1600)
+        );
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let delays: Vec<_> = (1..=5)
+            .map(|failures| POLICY.retry_delay(failures))
+            .collect();
+        assert_eq!(delays, [60, 120, 240, 300, 300]);
+        assert_eq!(POLICY.retry_delay(u32::MAX), 300);
+        assert_eq!(
+            POLICY.retry_at(Timestamp::from_seconds(1000), 2),
+            Timestamp::from_seconds(1120)
+        );
+
+        // The delay never goes below the base one
+        let policy = TimeoutPolicy::new(600).with_backoff(60, 0);
+        assert_eq!(policy.retry_delay(3), 60);
+        let policy = TimeoutPolicy::new(600).with_backoff(60, u64::MAX);
+        assert_eq!(policy.retry_delay(70), u64::MAX);
+    }
+
+    #[test]
+    fn max_failures() {
+        assert!(!POLICY.retries_exhausted(3));
+        assert!(POLICY.retries_exhausted(4));
+        assert!(!TimeoutPolicy::new(600).retries_exhausted(u32::MAX));
+    }
+}
@@ -24,6 +24,60 @@ fn parse_version(version: &str) -> Result<Version, VersionError> {
     Version::parse(version).map_err(|_| VersionError::InvalidVersion(version.to_string()))
 }
 
+/// Optional protocol features, carried in the channel version as a bitmap.
+/// Only the features supported on both sides are enabled on the channel, so packet variants
+/// behind a feature are never sent to a counterparty that doesn't know them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(transparent)]
+pub struct Features(u64);
+
+impl Features {
+    /// Consumer reports the rewards of many validators in a single `DistributeBatch` packet
+    pub const BATCH_REWARDS: Features = Features(1);
+    /// Provider stakes to many validators in a single `StakeBatch` packet
+    pub const BATCH_STAKE: Features = Features(1 << 1);
+    /// Consumer reports max cap changes with `MaxCapUpdate` packets
+    pub const MAX_CAP_UPDATE: Features = Features(1 << 2);
+    /// Provider reports the checksum of its stakes with `StakeChecksum` packets
+    pub const STAKE_CHECKSUM: Features = Features(1 << 3);
+    /// Packets are sent in versioned envelopes (`VersionedProviderPacket`,
+    /// `VersionedConsumerPacket`)
+    pub const VERSIONED_PACKETS: Features = Features(1 << 4);
+    /// Provider relays the cross-stakers governance votes with `Vote` packets
+    pub const REMOTE_GOVERNANCE: Features = Features(1 << 5);
+    /// Consumer splits large valset updates over several `ValsetUpdate` packets, with chunk
+    /// hints
+    pub const VALSET_CHUNKS: Features = Features(1 << 6);
+    /// Consumer reports the uptime of the validators in its `ValsetUpdate` packets
+    pub const VALIDATOR_HEALTH: Features = Features(1 << 7);
+    /// Provider queues the unstakes and sends them to the consumer in `UnstakeBatch` packets
+    pub const BATCH_UNSTAKE: Features = Features(1 << 8);
+
+    pub const fn empty() -> Self {
+        Features(0)
+    }
+
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Features) -> Self {
+        Features(self.0 | other.0)
+    }
+
+    pub const fn intersection(self, other: Features) -> Self {
+        Features(self.0 & other.0)
+    }
+}
+
 /// Implements logic as defined here:
 /// https://github.com/osmosis-labs/mesh-security/blob/main/docs/ibc/ControlChannel.md#establishing-a-channel
 /// (Note the comment not to use cw_serde)
@@ -31,6 +85,10 @@ fn parse_version(version: &str) -> Result<Version, VersionError> {
 pub struct ProtocolVersion {
     pub protocol: String,
     pub version: String,
+    /// Features supported by the sender. Left out when empty, as versions before 0.12.0
+    /// don't know about them
+    #[serde(default, skip_serializing_if = "Features::is_empty")]
+    pub features: Features,
 }
 
 impl ProtocolVersion {
@@ -38,9 +96,15 @@ impl ProtocolVersion {
         ProtocolVersion {
             protocol: protocol.to_string(),
             version: version.to_string(),
+            features: Features::empty(),
         }
     }
 
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
     pub fn validate(&self) -> Result<Version, VersionError> {
         if self.protocol != PROTOCOL_NAME {
             return Err(VersionError::InvalidProtocol(self.protocol.clone()));
@@ -52,6 +116,8 @@ impl ProtocolVersion {
     /// If it is below the min supported version, return an error.
     /// If it is has a higher major version than the supported version, return an error.
     /// Otherwise return min(self.version, supported_version)
+    ///
+    /// The response has no features, set them with `with_features`
     pub fn build_response(
         &self,
         supported_ver: &str,
@@ -72,10 +138,7 @@ impl ProtocolVersion {
             })
         } else {
             let ver = std::cmp::min(proposed, supported_ver);
-            Ok(ProtocolVersion {
-                protocol: PROTOCOL_NAME.to_string(),
-                version: ver.to_string(),
-            })
+            Ok(ProtocolVersion::new(PROTOCOL_NAME, &ver.to_string()))
         }
     }
 
@@ -156,6 +219,28 @@ mod tests {
             supported.to_string().unwrap(),
             r#"{"protocol":"mesh-security","version":"1.2.3"}"#.to_string()
         );
+
+        let with_features = supported.with_features(Features::BATCH_REWARDS);
+        assert_eq!(
+            with_features.to_string().unwrap(),
+            r#"{"protocol":"mesh-security","version":"1.2.3","features":1}"#.to_string()
+        );
+    }
+
+    #[test]
+    fn features_negotiation() {
+        // Older versions don't send features
+        let old: ProtocolVersion =
+            cosmwasm_std::from_json(r#"{"protocol":"mesh-security","version":"0.11.0"}"#).unwrap();
+        assert_eq!(old.features, Features::empty());
+
+        let supported = Features::BATCH_REWARDS.union(Features::BATCH_STAKE);
+        let proposed = Features::BATCH_STAKE.union(Features::MAX_CAP_UPDATE);
+        let negotiated = proposed.intersection(supported);
+        assert_eq!(negotiated, Features::BATCH_STAKE);
+        assert!(negotiated.contains(Features::BATCH_STAKE));
+        assert!(!negotiated.contains(Features::BATCH_REWARDS));
+        assert_eq!(supported.bits(), 3);
     }
 
     #[test]
@@ -1,7 +1,14 @@
 pub mod converter_api;
 pub mod cross_staking_api;
+pub mod error;
+pub mod events;
 pub mod ibc;
 pub mod local_staking_api;
+pub mod migration;
+pub mod ownership_api;
 pub mod price_feed_api;
+pub mod slash_evidence_api;
 pub mod vault_api;
+pub mod vault_hook_api;
+pub mod vault_strategy_api;
 pub mod virtual_staking_api;
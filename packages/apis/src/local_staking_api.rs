@@ -11,6 +11,23 @@ pub struct SlashRatioResponse {
     pub slash_ratio_offline: Decimal,
 }
 
+#[cw_serde]
+pub struct PendingRewardsResponse {
+    pub rewards: Coin,
+}
+
+#[cw_serde]
+pub struct GovernancePowerResponse {
+    /// Whether the locally staked tokens can be used to vote on governance proposals
+    pub enabled: bool,
+}
+
+#[cw_serde]
+pub struct UnbondingPeriodResponse {
+    /// Unbonding period of the local chain, in seconds. Zero if unknown
+    pub unbonding_period: u64,
+}
+
 /// This is the interface to any local staking contract needed by the vault contract.
 /// Users will need to use the custom methods to actually manage funds
 #[interface]
@@ -46,9 +63,37 @@ pub trait LocalStakingApi {
         validator: Option<String>,
     ) -> Result<Response, Self::Error>;
 
+    /// Withdraws `amount` of the sender's accumulated rewards, leaving the rest pending.
+    /// The sender is either the user, or the user's staking proxy (if any), in which case the
+    /// rewards are sent to the user.
+    #[sv::msg(exec)]
+    fn withdraw_rewards_partial(&self, ctx: ExecCtx, amount: Coin)
+        -> Result<Response, Self::Error>;
+
+    /// Withdraws all the rewards of `owner`, sending them to it. This is called by the vault
+    /// contract, so users can collect their local staking rewards through it.
+    #[sv::msg(exec)]
+    fn withdraw_rewards_for(&self, ctx: ExecCtx, owner: String) -> Result<Response, Self::Error>;
+
     /// Returns the maximum percentage that can be slashed
     #[sv::msg(query)]
     fn max_slash(&self, ctx: QueryCtx) -> Result<SlashRatioResponse, Self::Error>;
+
+    /// Returns the accumulated rewards of `user`, not withdrawn yet
+    #[sv::msg(query)]
+    fn pending_rewards(
+        &self,
+        ctx: QueryCtx,
+        user: String,
+    ) -> Result<PendingRewardsResponse, Self::Error>;
+
+    /// Returns whether the locally staked tokens carry governance power
+    #[sv::msg(query)]
+    fn governance_power(&self, ctx: QueryCtx) -> Result<GovernancePowerResponse, Self::Error>;
+
+    /// Returns the unbonding period of the local chain, after which redelegations complete
+    #[sv::msg(query)]
+    fn unbonding_period(&self, ctx: QueryCtx) -> Result<UnbondingPeriodResponse, Self::Error>;
 }
 
 #[cw_serde]
@@ -96,8 +141,49 @@ impl LocalStakingApiHelper {
         Ok(wasm)
     }
 
+    pub fn withdraw_rewards_partial(&self, amount: Coin) -> Result<WasmMsg, StdError> {
+        let msg = sv::LocalStakingApiExecMsg::WithdrawRewardsPartial { amount };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn withdraw_rewards_for(&self, owner: &Addr) -> Result<WasmMsg, StdError> {
+        let msg = sv::LocalStakingApiExecMsg::WithdrawRewardsFor {
+            owner: owner.to_string(),
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn max_slash(&self, deps: Deps) -> Result<SlashRatioResponse, StdError> {
         let query = sv::LocalStakingApiQueryMsg::MaxSlash {};
         deps.querier.query_wasm_smart(&self.0, &query)
     }
+
+    pub fn pending_rewards(
+        &self,
+        deps: Deps,
+        user: String,
+    ) -> Result<PendingRewardsResponse, StdError> {
+        let query = sv::LocalStakingApiQueryMsg::PendingRewards { user };
+        deps.querier.query_wasm_smart(&self.0, &query)
+    }
+
+    pub fn governance_power(&self, deps: Deps) -> Result<GovernancePowerResponse, StdError> {
+        let query = sv::LocalStakingApiQueryMsg::GovernancePower {};
+        deps.querier.query_wasm_smart(&self.0, &query)
+    }
+
+    pub fn unbonding_period(&self, deps: Deps) -> Result<UnbondingPeriodResponse, StdError> {
+        let query = sv::LocalStakingApiQueryMsg::UnbondingPeriod {};
+        deps.querier.query_wasm_smart(&self.0, &query)
+    }
 }
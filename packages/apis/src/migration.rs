@@ -0,0 +1,82 @@
+use semver::Version;
+
+use crate::error::MeshError;
+
+/// Contracts version the ownership storage was introduced in. Contracts migrated from before
+/// it have their owner seeded by their migration
+pub const OWNERSHIP_VERSION: &str = "0.10.0-alpha.2";
+
+/// Version change of a contract migration, checked to be an upgrade of the same contract
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Upgrade {
+    pub from: Version,
+    pub to: Version,
+}
+
+impl Upgrade {
+    /// Checks the contract name and version stored by cw2 against the new ones. Migrating to
+    /// the same version is allowed, downgrades are not
+    pub fn check(
+        stored_contract: &str,
+        stored_version: &str,
+        contract: &str,
+        version: &str,
+    ) -> Result<Self, MeshError> {
+        if stored_contract != contract {
+            return Err(MeshError::InvalidContractName(stored_contract.to_owned()));
+        }
+        let from = parse_version(stored_version)?;
+        let to = parse_version(version)?;
+        if from > to {
+            return Err(MeshError::MigrationDowngrade(
+                stored_version.to_owned(),
+                version.to_owned(),
+            ));
+        }
+        Ok(Self { from, to })
+    }
+
+    /// Whether the state changes of `version` have to be applied, that is whether it is after
+    /// `from`, up to `to` included
+    pub fn crosses(&self, version: &str) -> Result<bool, MeshError> {
+        let version = parse_version(version)?;
+        Ok(version > self.from && version <= self.to)
+    }
+}
+
+pub fn parse_version(version: &str) -> Result<Version, MeshError> {
+    Version::parse(version).map_err(|_| MeshError::InvalidVersion(version.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_only() {
+        let upgrade = Upgrade::check("vault", "0.10.0-alpha.1", "vault", "0.10.0").unwrap();
+        assert!(upgrade.crosses(OWNERSHIP_VERSION).unwrap());
+        assert!(upgrade.crosses("0.10.0").unwrap());
+        assert!(!upgrade.crosses("0.10.0-alpha.1").unwrap());
+        assert!(!upgrade.crosses("0.10.1").unwrap());
+
+        let upgrade = Upgrade::check("vault", "0.10.0", "vault", "0.10.0").unwrap();
+        assert!(!upgrade.crosses("0.10.0").unwrap());
+
+        assert_eq!(
+            Upgrade::check("converter", "0.10.0", "vault", "0.10.0"),
+            Err(MeshError::InvalidContractName("converter".to_owned()))
+        );
+        assert_eq!(
+            Upgrade::check("vault", "1.0.0", "vault", "0.10.0"),
+            Err(MeshError::MigrationDowngrade(
+                "1.0.0".to_owned(),
+                "0.10.0".to_owned()
+            ))
+        );
+        assert_eq!(
+            Upgrade::check("vault", "v1", "vault", "0.10.0"),
+            Err(MeshError::InvalidVersion("v1".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    Addr, Attribute, BlockInfo, CustomMsg, CustomQuery, QuerierWrapper, Response, StdError,
+    StdResult, Storage, Timestamp,
+};
+use cw_storage_plus::Item;
+use sylvia::types::{ExecCtx, QueryCtx};
+use sylvia::{interface, schemars};
+use thiserror::Error;
+
+use crate::error::MeshError;
+
+/// Ownership of a contract. The owner is allowed to manage the contract, through whatever
+/// privileged messages it has.
+///
+/// Ownership is transferred in two steps: the current owner proposes a new owner, which then
+/// has to accept it (before the optional expiry). Ownership can also be renounced, after which
+/// the privileged messages can't be called anymore.
+#[cw_serde]
+#[derive(Default)]
+pub struct Ownership {
+    /// Current owner, if any
+    pub owner: Option<Addr>,
+    /// Proposed owner, waiting to accept the ownership
+    pub pending_owner: Option<Addr>,
+    /// Block time the pending ownership transfer expires at, if any
+    pub pending_expiry: Option<Timestamp>,
+}
+
+impl Ownership {
+    pub fn into_attributes(self) -> Vec<Attribute> {
+        let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_owned());
+        vec![
+            Attribute::new("owner", or_none(self.owner.map(Addr::into_string))),
+            Attribute::new(
+                "pending_owner",
+                or_none(self.pending_owner.map(Addr::into_string)),
+            ),
+            Attribute::new(
+                "pending_expiry",
+                or_none(self.pending_expiry.map(|expiry| expiry.to_string())),
+            ),
+        ]
+    }
+}
+
+/// Ownership failures. A caller other than the owner gets `MeshError::Unauthorized`, which the
+/// contract errors unwrap from the `Mesh` variant, like any other unauthorized call.
+#[derive(Error, Debug, PartialEq)]
+pub enum OwnershipError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    Mesh(#[from] MeshError),
+
+    #[error("Contract has no owner")]
+    NoOwner,
+
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner,
+
+    #[error("Caller is not the pending owner")]
+    NotPendingOwner,
+
+    #[error("Ownership transfer expired")]
+    TransferExpired,
+
+    #[error("Expiry must be in the future")]
+    InvalidExpiry,
+}
+
+/// Ownership storage, shared by all the contracts
+pub const OWNERSHIP: Item<Ownership> = Item::new("ownership");
+
+/// Sets the initial owner, to be called on instantiation
+pub fn initialize_owner(storage: &mut dyn Storage, owner: Option<Addr>) -> StdResult<Ownership> {
+    let ownership = Ownership {
+        owner,
+        ..Default::default()
+    };
+    OWNERSHIP.save(storage, &ownership)?;
+    Ok(ownership)
+}
+
+/// Sets the owner of a contract migrated from before the ownership storage. Existing ownership
+/// is left untouched, so running it again is harmless
+pub fn migrate_owner(storage: &mut dyn Storage, owner: Option<Addr>) -> StdResult<Ownership> {
+    match OWNERSHIP.may_load(storage)? {
+        Some(ownership) => Ok(ownership),
+        None => initialize_owner(storage, owner),
+    }
+}
+
+/// Wasm admin of `contract`, the owner of the contracts instantiated before the ownership storage
+pub fn wasm_admin<Q: CustomQuery>(
+    querier: &QuerierWrapper<Q>,
+    contract: &Addr,
+) -> StdResult<Option<Addr>> {
+    let info = querier.query_wasm_contract_info(contract)?;
+    Ok(info.admin.map(Addr::unchecked))
+}
+
+pub fn get_ownership(storage: &dyn Storage) -> StdResult<Ownership> {
+    Ok(OWNERSHIP.may_load(storage)?.unwrap_or_default())
+}
+
+/// Fails if `sender` is not the current owner
+pub fn assert_owner(storage: &dyn Storage, sender: &Addr) -> Result<(), OwnershipError> {
+    match get_ownership(storage)?.owner {
+        Some(owner) if owner == *sender => Ok(()),
+        Some(_) => Err(MeshError::Unauthorized.into()),
+        None => Err(OwnershipError::NoOwner),
+    }
+}
+
+/// Proposes `new_owner` as the next owner, replacing any pending proposal.
+/// Only the current owner can call this.
+pub fn propose_owner(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    sender: &Addr,
+    new_owner: Addr,
+    expiry: Option<Timestamp>,
+) -> Result<Ownership, OwnershipError> {
+    assert_owner(storage, sender)?;
+    if matches!(expiry, Some(expiry) if expiry <= block.time) {
+        return Err(OwnershipError::InvalidExpiry);
+    }
+
+    let mut ownership = get_ownership(storage)?;
+    ownership.pending_owner = Some(new_owner);
+    ownership.pending_expiry = expiry;
+    OWNERSHIP.save(storage, &ownership)?;
+    Ok(ownership)
+}
+
+/// Completes a pending ownership transfer. Only the pending owner can call this.
+pub fn accept_owner(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    sender: &Addr,
+) -> Result<Ownership, OwnershipError> {
+    let mut ownership = get_ownership(storage)?;
+    match &ownership.pending_owner {
+        Some(pending_owner) if pending_owner == sender => {}
+        Some(_) => return Err(OwnershipError::NotPendingOwner),
+        None => return Err(OwnershipError::NoPendingOwner),
+    }
+    if matches!(ownership.pending_expiry, Some(expiry) if expiry <= block.time) {
+        return Err(OwnershipError::TransferExpired);
+    }
+
+    ownership.owner = ownership.pending_owner.take();
+    ownership.pending_expiry = None;
+    OWNERSHIP.save(storage, &ownership)?;
+    Ok(ownership)
+}
+
+/// Removes the owner, along with any pending transfer. Only the current owner can call this.
+pub fn renounce_owner(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+) -> Result<Ownership, OwnershipError> {
+    assert_owner(storage, sender)?;
+
+    let ownership = Ownership::default();
+    OWNERSHIP.save(storage, &ownership)?;
+    Ok(ownership)
+}
+
+/// This is the ownership management interface, implemented by all the mesh contracts on top
+/// of the helpers above.
+#[interface]
+pub trait OwnershipApi {
+    type Error: From<StdError>;
+    type ExecC: CustomMsg;
+    type QueryC: CustomQuery;
+
+    /// Proposes a new owner, which has to accept it with `accept_owner`, before `expiry` if
+    /// set. Only the current owner can call this.
+    #[sv::msg(exec)]
+    fn propose_owner(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        new_owner: String,
+        expiry: Option<Timestamp>,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    /// Accepts the ownership proposed to the caller
+    #[sv::msg(exec)]
+    fn accept_owner(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    /// Leaves the contract without owner. Only the current owner can call this.
+    #[sv::msg(exec)]
+    fn renounce_owner(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    #[sv::msg(query)]
+    fn ownership(&self, ctx: QueryCtx<Self::QueryC>) -> Result<Ownership, Self::Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_env, MockStorage};
+
+    use super::*;
+
+    #[test]
+    fn two_step_transfer() {
+        let mut storage = MockStorage::new();
+        let block = mock_env().block;
+        let owner = Addr::unchecked("owner");
+        let new_owner = Addr::unchecked("new_owner");
+
+        initialize_owner(&mut storage, Some(owner.clone())).unwrap();
+        assert_eq!(assert_owner(&storage, &owner), Ok(()));
+        assert_eq!(
+            assert_owner(&storage, &new_owner),
+            Err(MeshError::Unauthorized.into())
+        );
+
+        // Only the owner can propose, and only the proposed owner can accept
+        let err = propose_owner(&mut storage, &block, &new_owner, new_owner.clone(), None);
+        assert_eq!(err, Err(MeshError::Unauthorized.into()));
+        let err = accept_owner(&mut storage, &block, &new_owner);
+        assert_eq!(err, Err(OwnershipError::NoPendingOwner));
+
+        propose_owner(&mut storage, &block, &owner, new_owner.clone(), None).unwrap();
+        let err = accept_owner(&mut storage, &block, &owner);
+        assert_eq!(err, Err(OwnershipError::NotPendingOwner));
+        // Still the owner until accepted
+        assert_eq!(assert_owner(&storage, &owner), Ok(()));
+
+        let ownership = accept_owner(&mut storage, &block, &new_owner).unwrap();
+        assert_eq!(
+            ownership,
+            Ownership {
+                owner: Some(new_owner.clone()),
+                pending_owner: None,
+                pending_expiry: None,
+            }
+        );
+        assert_eq!(get_ownership(&storage).unwrap(), ownership);
+    }
+
+    #[test]
+    fn expired_transfer() {
+        let mut storage = MockStorage::new();
+        let mut block = mock_env().block;
+        let owner = Addr::unchecked("owner");
+        let new_owner = Addr::unchecked("new_owner");
+        initialize_owner(&mut storage, Some(owner.clone())).unwrap();
+
+        let err = propose_owner(
+            &mut storage,
+            &block,
+            &owner,
+            new_owner.clone(),
+            Some(block.time),
+        );
+        assert_eq!(err, Err(OwnershipError::InvalidExpiry));
+
+        let expiry = block.time.plus_seconds(10);
+        propose_owner(
+            &mut storage,
+            &block,
+            &owner,
+            new_owner.clone(),
+            Some(expiry),
+        )
+        .unwrap();
+        block.time = expiry;
+        let err = accept_owner(&mut storage, &block, &new_owner);
+        assert_eq!(err, Err(OwnershipError::TransferExpired));
+    }
+
+    #[test]
+    fn renouncing() {
+        let mut storage = MockStorage::new();
+        let block = mock_env().block;
+        let owner = Addr::unchecked("owner");
+        let new_owner = Addr::unchecked("new_owner");
+        initialize_owner(&mut storage, Some(owner.clone())).unwrap();
+        propose_owner(&mut storage, &block, &owner, new_owner.clone(), None).unwrap();
+
+        renounce_owner(&mut storage, &owner).unwrap();
+        assert_eq!(get_ownership(&storage).unwrap(), Ownership::default());
+        assert_eq!(assert_owner(&storage, &owner), Err(OwnershipError::NoOwner));
+        // Pending transfers are cancelled too
+        let err = accept_owner(&mut storage, &block, &new_owner);
+        assert_eq!(err, Err(OwnershipError::NoPendingOwner));
+    }
+
+    #[test]
+    fn migrating_owner() {
+        let mut storage = MockStorage::new();
+        let owner = Addr::unchecked("owner");
+
+        migrate_owner(&mut storage, Some(owner.clone())).unwrap();
+        assert_eq!(get_ownership(&storage).unwrap().owner, Some(owner.clone()));
+
+        // Already migrated, the current owner is kept
+        let ownership = migrate_owner(&mut storage, Some(Addr::unchecked("admin"))).unwrap();
+        assert_eq!(ownership.owner, Some(owner));
+    }
+}
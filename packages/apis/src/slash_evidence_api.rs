@@ -0,0 +1,33 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Binary, Response, StdError};
+use sylvia::types::ExecCtx;
+use sylvia::{interface, schemars};
+
+/// Kind of misbehaviour an evidence is about
+#[cw_serde]
+pub enum EvidenceType {
+    /// The validator signed two different blocks at the same height
+    DoubleSign,
+}
+
+/// This is the interface to submit misbehaviour evidence of remote validators, so the stake
+/// delegated to them is slashed.
+///
+/// Evidence can come from external parties, or from the consumer side. The implementing
+/// contract decides who it trusts to submit evidence, and how much of the proof it verifies.
+#[interface]
+pub trait SlashEvidenceApi {
+    type Error: From<StdError>;
+
+    /// Submits evidence of `validator` misbehaving at the (consumer) block `height`.
+    /// `proof` is opaque to this API, and interpreted by the implementing contract.
+    #[sv::msg(exec)]
+    fn submit_evidence(
+        &self,
+        ctx: ExecCtx,
+        validator: String,
+        height: u64,
+        evidence_type: EvidenceType,
+        proof: Binary,
+    ) -> Result<Response, Self::Error>;
+}
@@ -1,5 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{to_json_binary, Addr, Coin, Response, StdError, Uint128, WasmMsg};
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, Coin, CustomMsg, CustomQuery, Response, StdError, Timestamp,
+    Uint128, WasmMsg,
+};
 use sylvia::types::ExecCtx;
 use sylvia::{interface, schemars};
 
@@ -8,37 +11,77 @@ use sylvia::{interface, schemars};
 #[interface]
 pub trait VaultApi {
     type Error: From<StdError>;
+    type ExecC: CustomMsg;
+    type QueryC: CustomQuery;
 
     /// This must be called by the remote staking contract to release this claim
     #[sv::msg(exec)]
     fn release_cross_stake(
         &self,
-        ctx: ExecCtx,
+        ctx: ExecCtx<Self::QueryC>,
         // address of the user who originally called stake_remote
         owner: String,
         // amount to unstake on that contract
         amount: Coin,
-    ) -> Result<Response, Self::Error>;
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    /// This can be called by the remote staking contract to release this claim at `release_at`
+    /// instead of right away, e.g. once the remote unbonding period is over.
+    /// The amount is reported as incoming free collateral until then, and the release is
+    /// finalized by the vault
+    #[sv::msg(exec)]
+    fn release_cross_stake_delayed(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // amount to unstake on that contract
+        amount: Coin,
+        // time the claim is released at
+        release_at: Timestamp,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
 
     /// This must be called by the local staking contract to release this claim
     /// Amount of tokens unstaked are those included in ctx.info.funds
     #[sv::msg(exec)]
     fn release_local_stake(
         &self,
-        ctx: ExecCtx,
+        ctx: ExecCtx<Self::QueryC>,
         // address of the user who originally called stake_remote
         owner: String,
-    ) -> Result<Response, Self::Error>;
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
 
     /// This must be called by the remote staking contract to commit the remote staking call on success.
     /// Transaction ID is used to identify the original (vault contract originated) transaction.
     #[sv::msg(exec)]
-    fn commit_tx(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
+    fn commit_tx(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        tx_id: u64,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
 
     /// This must be called by the remote staking contract to rollback the remote staking call on failure.
     /// Transaction ID is used to identify the original (vault contract originated) transaction.
     #[sv::msg(exec)]
-    fn rollback_tx(&self, ctx: ExecCtx, tx_id: u64) -> Result<Response, Self::Error>;
+    fn rollback_tx(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        tx_id: u64,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    /// This must be called by the remote staking contract to re-stake rewards of `owner`.
+    /// Tokens sent along are bonded as `owner` collateral, and virtually staked on the calling
+    /// contract with `msg`, as in `stake_remote`.
+    /// `owner` must already have a lien on the calling contract.
+    #[sv::msg(exec)]
+    fn compound_stake(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        // address of the user whose rewards are re-staked
+        owner: String,
+        // action to take with that stake
+        msg: Binary,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
 
     /// This must be called by the native staking contract to process a slashing event
     /// because of a misbehaviour on the Provider chain.
@@ -47,10 +90,10 @@ pub trait VaultApi {
     #[sv::msg(exec)]
     fn local_slash(
         &self,
-        ctx: ExecCtx,
+        ctx: ExecCtx<Self::QueryC>,
         slashes: Vec<SlashInfo>,
         validator: String,
-    ) -> Result<Response, Self::Error>;
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
 
     /// This must be called by the external staking contract to process a slashing event
     /// because of a misbehaviour on the Consumer chain.
@@ -59,10 +102,10 @@ pub trait VaultApi {
     #[sv::msg(exec)]
     fn cross_slash(
         &self,
-        ctx: ExecCtx,
+        ctx: ExecCtx<Self::QueryC>,
         slashes: Vec<SlashInfo>,
         validator: String,
-    ) -> Result<Response, Self::Error>;
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
 }
 
 #[cw_serde]
@@ -96,6 +139,28 @@ impl VaultApiHelper {
         Ok(wasm)
     }
 
+    pub fn release_cross_stake_delayed(
+        &self,
+        // address of the user who originally called stake_remote
+        owner: String,
+        // amount to unstake on that contract
+        amount: Coin,
+        // time the claim is released at
+        release_at: Timestamp,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = sv::VaultApiExecMsg::ReleaseCrossStakeDelayed {
+            owner,
+            amount,
+            release_at,
+        };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
     pub fn release_local_stake(
         &self,
         // address of the user who originally called stake_remote
@@ -112,6 +177,24 @@ impl VaultApiHelper {
         Ok(wasm)
     }
 
+    pub fn compound_stake(
+        &self,
+        // address of the user whose rewards are re-staked
+        owner: String,
+        // action to take with that stake
+        msg: Binary,
+        // tokens to bond and stake
+        funds: Vec<Coin>,
+    ) -> Result<WasmMsg, StdError> {
+        let msg = sv::VaultApiExecMsg::CompoundStake { owner, msg };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds,
+        };
+        Ok(wasm)
+    }
+
     pub fn process_local_slashing(
         &self,
         slashes: Vec<SlashInfo>,
@@ -0,0 +1,52 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_binary, Addr, Coin, Response, StdError, Uint128, WasmMsg};
+use sylvia::types::ExecCtx;
+use sylvia::{interface, schemars};
+
+/// Vault activity reported to the hook contracts
+#[cw_serde]
+pub enum VaultHookMsg {
+    /// Collateral was bonded to the `owner` account
+    AfterBond { owner: String, amount: Coin },
+    /// Collateral was unbonded from the `owner` account
+    AfterUnbond { owner: String, amount: Coin },
+    /// The stake of `owner` on `lienholder` changed. `amount` is the committed stake after the
+    /// change, zero if fully unstaked
+    AfterStakeChange {
+        owner: String,
+        lienholder: String,
+        amount: Uint128,
+    },
+}
+
+/// This is the interface of the hook contracts registered in the vault.
+///
+/// Hooks are called after the vault state is updated, and their failures are ignored by the
+/// vault, so they can't block its users.
+#[interface]
+pub trait VaultHookApi {
+    type Error: From<StdError>;
+
+    /// Called by the vault on the activity described by `msg`
+    #[sv::msg(exec)]
+    fn vault_hook(&self, ctx: ExecCtx, msg: VaultHookMsg) -> Result<Response, Self::Error>;
+}
+
+#[cw_serde]
+pub struct VaultHookApiHelper(pub Addr);
+
+impl VaultHookApiHelper {
+    pub fn addr(&self) -> &Addr {
+        &self.0
+    }
+
+    pub fn vault_hook(&self, msg: VaultHookMsg) -> Result<WasmMsg, StdError> {
+        let msg = sv::VaultHookApiExecMsg::VaultHook { msg };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+}
@@ -0,0 +1,73 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_binary, Addr, Coin, Deps, Response, StdError, Uint128, WasmMsg};
+use sylvia::types::{ExecCtx, QueryCtx};
+use sylvia::{interface, schemars};
+
+#[cw_serde]
+pub struct DepositValueResponse {
+    /// Current value of the deposits, in the deposited denom, including the accrued yield
+    pub value: Uint128,
+}
+
+/// This is the interface of the yield strategies the vault can deposit free collateral into
+/// (i.e. a community pool deposit strategy).
+///
+/// The vault is the only depositor it cares about: it keeps track of the share of every
+/// user in its deposits itself.
+#[interface]
+pub trait VaultStrategyApi {
+    type Error: From<StdError>;
+
+    /// Deposits the sent tokens, credited to the sender
+    #[sv::msg(exec)]
+    fn deposit(&self, ctx: ExecCtx) -> Result<Response, Self::Error>;
+
+    /// Withdraws `amount` of the deposits value of the sender, sent back to it
+    #[sv::msg(exec)]
+    fn withdraw(&self, ctx: ExecCtx, amount: Uint128) -> Result<Response, Self::Error>;
+
+    /// Current value of the deposits of `owner`
+    #[sv::msg(query)]
+    fn deposit_value(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+    ) -> Result<DepositValueResponse, Self::Error>;
+}
+
+#[cw_serde]
+pub struct VaultStrategyApiHelper(pub Addr);
+
+impl VaultStrategyApiHelper {
+    pub fn addr(&self) -> &Addr {
+        &self.0
+    }
+
+    pub fn deposit(&self, funds: Vec<Coin>) -> Result<WasmMsg, StdError> {
+        let msg = sv::VaultStrategyApiExecMsg::Deposit {};
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds,
+        };
+        Ok(wasm)
+    }
+
+    pub fn withdraw(&self, amount: Uint128) -> Result<WasmMsg, StdError> {
+        let msg = sv::VaultStrategyApiExecMsg::Withdraw { amount };
+        let wasm = WasmMsg::Execute {
+            contract_addr: self.0.to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        };
+        Ok(wasm)
+    }
+
+    pub fn deposit_value(&self, deps: Deps, owner: &Addr) -> Result<Uint128, StdError> {
+        let query = sv::VaultStrategyApiQueryMsg::DepositValue {
+            owner: owner.to_string(),
+        };
+        let resp: DepositValueResponse = deps.querier.query_wasm_smart(&self.0, &query)?;
+        Ok(resp.value)
+    }
+}
@@ -2,11 +2,13 @@
 #![allow(clippy::too_many_arguments)]
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Coin, Response, StdError, Uint128, Validator};
+use cosmwasm_std::{Coin, Response, StdError, Uint128, Validator, WeightedVoteOption};
 use sylvia::cw_std::{CustomMsg, CustomQuery};
-use sylvia::types::{ExecCtx, SudoCtx};
+use sylvia::types::{ExecCtx, QueryCtx, SudoCtx};
 use sylvia::{interface, schemars};
 
+use crate::converter_api::ValidatorUptime;
+
 // TODO: make these parameters of the trait?
 
 /// The Virtual Staking API is called from the converter contract to bond and (instantly) unbond tokens.
@@ -51,6 +53,26 @@ pub trait VirtualStakingApi {
         amount: Coin,
     ) -> Result<Response<Self::ExecC>, Self::Error>;
 
+    /// Casts a weighted vote on a governance proposal, with the delegations of the virtual staking
+    /// contract. This is called with the votes of the cross-stakers relayed from the provider, and
+    /// replaces the previous vote on the proposal.
+    #[sv::msg(exec)]
+    fn vote(
+        &self,
+        ctx: ExecCtx<Self::QueryC>,
+        proposal_id: u64,
+        options: Vec<WeightedVoteOption>,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
+    /// Amount bonded to `validator` at the last epoch, and amount requested for the next one.
+    /// The converter reconciles its own bookkeeping with the requested amounts
+    #[sv::msg(query)]
+    fn bond_status(
+        &self,
+        ctx: QueryCtx<Self::QueryC>,
+        validator: String,
+    ) -> Result<ValidatorBondStatus, Self::Error>;
+
     /// SudoMsg::HandleEpoch{} should be called once per epoch by the sdk (in EndBlock).
     /// It allows the virtual staking contract to bond or unbond any pending requests, as well
     /// as to perform a rebalance if needed (over the max cap).
@@ -62,6 +84,16 @@ pub trait VirtualStakingApi {
         ctx: SudoCtx<Self::QueryC>,
     ) -> Result<Response<Self::ExecC>, Self::Error>;
 
+    /// SudoMsg::HandleMaxCapChanged{} should be called by the sdk right after the max cap of
+    /// this contract was changed (i.e. by governance).
+    /// If the new max cap is below the currently bonded amount, the excess is unbonded evenly
+    /// across all bonded validators, and reported to the converter contract.
+    #[sv::msg(sudo)]
+    fn handle_max_cap_changed(
+        &self,
+        ctx: SudoCtx<Self::QueryC>,
+    ) -> Result<Response<Self::ExecC>, Self::Error>;
+
     /// SudoMsg::ValsetUpdate{} should be called every time there's a validator set update:
     ///  - Addition of a new validator to the active validator set.
     ///  - Temporary removal of a validator from the active set. (i.e. `unbonded` state).
@@ -69,6 +101,9 @@ pub trait VirtualStakingApi {
     ///  - Temporary removal of a validator from the active set due to jailing. Implies slashing.
     ///  - Addition of an existing validator to the active validator set.
     ///  - Permanent removal (i.e. tombstoning) of a validator from the active set. Implies slashing
+    ///
+    /// It may also carry the signing performance (`uptimes`) of the validators, reported
+    /// periodically by the chain.
     #[sv::msg(sudo)]
     fn handle_valset_update(
         &self,
@@ -80,9 +115,20 @@ pub trait VirtualStakingApi {
         unjailed: Option<Vec<String>>,
         tombstoned: Option<Vec<String>>,
         slashed: Option<Vec<ValidatorSlash>>,
+        uptimes: Option<Vec<ValidatorUptime>>,
     ) -> Result<Response<Self::ExecC>, Self::Error>;
 }
 
+/// Bond status of a single validator
+#[cw_serde]
+pub struct ValidatorBondStatus {
+    pub validator: String,
+    /// Amount bonded to the validator at the last epoch
+    pub bonded: Uint128,
+    /// Amount requested to be bonded to the validator, applied at the next epoch
+    pub requested: Uint128,
+}
+
 #[cw_serde]
 pub struct ValidatorSlash {
     /// The address of the validator.
@@ -103,3 +149,11 @@ pub struct ValidatorSlash {
     /// Useful in case we don't know if it's a double sign or downtime slash.
     pub slash_ratio: String,
 }
+
+/// Instantiate message of the virtual staking contracts, sent by the converter that creates
+/// them
+#[cw_serde]
+pub struct VirtualStakingInitMsg {
+    /// Owner of the virtual staking contract, if any
+    pub owner: Option<String>,
+}
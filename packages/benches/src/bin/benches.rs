@@ -0,0 +1,75 @@
+#[cfg(not(feature = "provider-bindings"))]
+use std::{path::Path, process::exit};
+
+use anyhow::{bail, Result as AnyResult};
+#[cfg(not(feature = "provider-bindings"))]
+use cosmwasm_std::{from_json, to_json_vec};
+#[cfg(not(feature = "provider-bindings"))]
+use mesh_benches::{
+    report::Report,
+    scenarios::{run_all, Scale},
+};
+
+#[cfg(not(feature = "provider-bindings"))]
+const USAGE: &str = "usage: benches [--baseline <file>] [--update] [--tolerance <percent>]";
+
+#[cfg(feature = "provider-bindings")]
+fn main() -> AnyResult<()> {
+    bail!("the scenarios run the vault without the provider module custom messages, build without the provider-bindings feature")
+}
+
+#[cfg(not(feature = "provider-bindings"))]
+fn main() -> AnyResult<()> {
+    let mut baseline = None;
+    let mut update = false;
+    let mut tolerance = 5;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--baseline" => baseline = args.next(),
+            "--update" => update = true,
+            "--tolerance" => tolerance = args.next().unwrap_or_default().parse()?,
+            _ => bail!(USAGE),
+        }
+    }
+
+    let report = run_all(Scale::default())?;
+    print!("{}", report);
+
+    let Some(path) = baseline else {
+        return Ok(());
+    };
+    if update {
+        std::fs::write(&path, to_json_vec(&report)?)?;
+        println!("baseline written to {}", path);
+        return Ok(());
+    }
+
+    if !Path::new(&path).exists() {
+        bail!("no baseline at {}, run with --update to create it", path);
+    }
+    let baseline: Report = from_json(std::fs::read(&path)?)?;
+    let mut failed = false;
+    let unmeasured = report.unmeasured(&baseline);
+    if !unmeasured.is_empty() {
+        eprintln!("not in the baseline, run with --update to add them:");
+        for m in unmeasured {
+            eprintln!("  {} / {}", m.scenario, m.operation);
+        }
+        failed = true;
+    }
+    let regressions = report.regressions(&baseline, tolerance);
+    if !regressions.is_empty() {
+        eprintln!("gas regressions over {}%:", tolerance);
+        for regression in regressions {
+            eprintln!("  {}", regression);
+        }
+        failed = true;
+    }
+    if failed {
+        exit(1);
+    }
+
+    Ok(())
+}
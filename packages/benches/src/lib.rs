@@ -0,0 +1,28 @@
+//! Gas benchmarks of representative Mesh Security scenarios.
+//!
+//! The scenarios run on multitest, with storage metered using the SDK gas costs, so the reported
+//! numbers are reproducible and can be compared against a stored baseline to catch performance
+//! regressions in the accounting.
+pub mod report;
+// The scenarios run the vault without the provider module custom messages
+#[cfg(not(feature = "provider-bindings"))]
+pub mod scenarios;
+pub mod storage;
+
+#[cfg(all(test, not(feature = "provider-bindings")))]
+mod tests {
+    use crate::scenarios::{run_all, Scale};
+
+    #[test]
+    fn scenarios_run() {
+        let report = run_all(Scale {
+            liens: 2,
+            reward_users: 3,
+            slash_stakes: 3,
+        })
+        .unwrap();
+
+        assert_eq!(report.measurements.len(), 6);
+        assert!(report.measurements.iter().all(|m| m.gas > 0));
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+
+use cosmwasm_schema::cw_serde;
+
+/// Gas used by a single benchmarked operation
+#[cw_serde]
+pub struct Measurement {
+    pub scenario: String,
+    pub operation: String,
+    pub gas: u64,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Report {
+    pub measurements: Vec<Measurement>,
+}
+
+/// Operation using more gas than in the baseline
+#[derive(Debug, PartialEq)]
+pub struct Regression {
+    pub scenario: String,
+    pub operation: String,
+    pub baseline: u64,
+    pub gas: u64,
+}
+
+impl Report {
+    pub fn record(&mut self, scenario: &str, operation: &str, gas: u64) {
+        self.measurements.push(Measurement {
+            scenario: scenario.to_owned(),
+            operation: operation.to_owned(),
+            gas,
+        });
+    }
+
+    /// Compares against `baseline`, reporting operations exceeding it by more than
+    /// `tolerance` percent. Operations missing in the baseline are left to `unmeasured`.
+    pub fn regressions(&self, baseline: &Report, tolerance: u64) -> Vec<Regression> {
+        self.measurements
+            .iter()
+            .filter_map(|m| {
+                let base = baseline
+                    .measurements
+                    .iter()
+                    .find(|b| b.scenario == m.scenario && b.operation == m.operation)?;
+                let limit = base.gas + base.gas * tolerance / 100;
+                (m.gas > limit).then(|| Regression {
+                    scenario: m.scenario.clone(),
+                    operation: m.operation.clone(),
+                    baseline: base.gas,
+                    gas: m.gas,
+                })
+            })
+            .collect()
+    }
+
+    /// Operations missing in `baseline`, which can't be checked for regressions
+    pub fn unmeasured(&self, baseline: &Report) -> Vec<&Measurement> {
+        self.measurements
+            .iter()
+            .filter(|m| {
+                !baseline
+                    .measurements
+                    .iter()
+                    .any(|b| b.scenario == m.scenario && b.operation == m.operation)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<24} {:<20} {:>12}", "scenario", "operation", "gas")?;
+        for m in &self.measurements {
+            writeln!(f, "{:<24} {:<20} {:>12}", m.scenario, m.operation, m.gas)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} / {}: {} gas, baseline {} gas",
+            self.scenario, self.operation, self.gas, self.baseline
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regressions_over_tolerance() {
+        let mut baseline = Report::default();
+        baseline.record("bond", "bond", 1000);
+        baseline.record("rewards", "withdraw_rewards", 1000);
+
+        let mut report = Report::default();
+        report.record("bond", "bond", 1050);
+        report.record("rewards", "withdraw_rewards", 1051);
+        report.record("new", "op", 1_000_000);
+
+        assert_eq!(
+            report.regressions(&baseline, 5),
+            vec![Regression {
+                scenario: "rewards".to_owned(),
+                operation: "withdraw_rewards".to_owned(),
+                baseline: 1000,
+                gas: 1051,
+            }]
+        );
+        assert_eq!(
+            report.unmeasured(&baseline),
+            vec![&Measurement {
+                scenario: "new".to_owned(),
+                operation: "op".to_owned(),
+                gas: 1_000_000,
+            }]
+        );
+    }
+}
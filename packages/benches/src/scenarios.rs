@@ -0,0 +1,260 @@
+use anyhow::Result as AnyResult;
+use cosmwasm_std::testing::MockApi;
+use cosmwasm_std::{coin, coins, Addr, Decimal, Uint128};
+use cw_multi_test::{AppBuilder, BankKeeper};
+use mesh_apis::ibc::AddValidator;
+use mesh_external_staking::contract::sv::mt::{
+    CodeId as ExternalStakingCodeId, ExternalStakingContractProxy,
+};
+use mesh_external_staking::contract::ExternalStakingContract;
+use mesh_external_staking::msg::{AuthorizedEndpoint, ReceiveVirtualStake};
+use mesh_external_staking::state::SlashRatio;
+use mesh_external_staking::test_methods::sv::mt::TestMethodsProxy;
+use mesh_sync::Tx;
+use mesh_vault::contract::sv::mt::{CodeId as VaultCodeId, VaultContractProxy};
+use mesh_vault::contract::VaultContract;
+use sylvia::multitest::{App, Proxy};
+
+use crate::report::Report;
+use crate::storage::{GasMeter, MeteredStorage};
+
+pub const OSMO: &str = "osmo";
+pub const STAR: &str = "star";
+
+type BenchApp = cw_multi_test::App<BankKeeper, MockApi, MeteredStorage>;
+type Vault<'app> = Proxy<'app, BenchApp, VaultContract<'app>>;
+type ExternalStaking<'app> = Proxy<'app, BenchApp, ExternalStakingContract<'app>>;
+
+/// Size of the benchmarked scenarios
+#[derive(Clone, Copy, Debug)]
+pub struct Scale {
+    /// Number of liens (external staking contracts) a single account stakes on
+    pub liens: usize,
+    /// Number of stakers the rewards are distributed to
+    pub reward_users: usize,
+    /// Number of stakes affected by a single slashing
+    pub slash_stakes: usize,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self {
+            liens: 10,
+            reward_users: 1_000,
+            slash_stakes: 5_000,
+        }
+    }
+}
+
+/// Runs all the scenarios, returning gas used by every benchmarked operation
+pub fn run_all(scale: Scale) -> AnyResult<Report> {
+    let mut report = Report::default();
+    bond(&mut report)?;
+    liens(&mut report, scale.liens)?;
+    rewards(&mut report, scale.reward_users)?;
+    slashing(&mut report, scale.slash_stakes)?;
+    Ok(report)
+}
+
+struct Bench {
+    app: App<BenchApp>,
+    meter: GasMeter,
+}
+
+impl Bench {
+    fn new(users: &[String], amount: u128) -> Self {
+        let meter = GasMeter::default();
+        let app = AppBuilder::new()
+            .with_storage(MeteredStorage::new(meter.clone()))
+            .build(|router, _api, storage| {
+                for user in users {
+                    router
+                        .bank
+                        .init_balance(storage, &Addr::unchecked(user), coins(amount, OSMO))
+                        .unwrap();
+                }
+            });
+
+        Self {
+            app: App::new(app),
+            meter,
+        }
+    }
+
+    /// Gas used by storage access of `op`
+    fn measure<T, E>(&self, op: impl FnOnce() -> Result<T, E>) -> AnyResult<u64>
+    where
+        E: Into<anyhow::Error>,
+    {
+        self.meter.reset();
+        op().map_err(Into::into)?;
+        Ok(self.meter.consumed())
+    }
+
+    fn vault(&self) -> AnyResult<Vault<'_>> {
+        let vault = VaultCodeId::store_code(&self.app)
+            .instantiate(OSMO.to_owned(), None, None, None, None)
+            .with_label("Vault")
+            .call("owner")?;
+        Ok(vault)
+    }
+
+    fn external_staking<'app>(
+        &'app self,
+        vault: &Vault<'app>,
+        validators: &[String],
+    ) -> AnyResult<ExternalStaking<'app>> {
+        let contract = ExternalStakingCodeId::store_code(&self.app)
+            .instantiate(
+                OSMO.to_owned(),
+                STAR.to_owned(),
+                vault.contract_addr.to_string(),
+                100,
+                AuthorizedEndpoint::new("connection-2", "wasm-osmo1foobarbaz"),
+                SlashRatio {
+                    double_sign: Decimal::percent(10),
+                    offline: Decimal::percent(10),
+                },
+                None,
+                0,
+                None,
+                None,
+            )
+            .with_label("External staking")
+            .call("owner")?;
+
+        for validator in validators {
+            contract
+                .test_set_active_validator(AddValidator::mock(validator), 100, 1234)
+                .call("test")?;
+        }
+
+        Ok(contract)
+    }
+}
+
+fn users(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("user{}", i)).collect()
+}
+
+/// Remote stakes `amount` through the vault, and commits it as the IBC ack would
+fn stake(
+    vault: &Vault,
+    contract: &ExternalStaking,
+    user: &str,
+    validator: &str,
+    amount: u128,
+) -> AnyResult<()> {
+    vault
+        .stake_remote(
+            contract.contract_addr.to_string(),
+            coin(amount, OSMO),
+            ReceiveVirtualStake::new(validator).encode()?,
+        )
+        .call(user)?;
+    commit_last_stake(contract)
+}
+
+fn commit_last_stake(contract: &ExternalStaking) -> AnyResult<()> {
+    let txs = contract.all_pending_txs_desc(None, Some(1))?.txs;
+    let tx_id = txs.first().map(Tx::id).expect("no pending stake");
+    contract.test_commit_stake(tx_id).call("test")?;
+    Ok(())
+}
+
+/// Bonding collateral into the vault
+fn bond(report: &mut Report) -> AnyResult<()> {
+    let users = users(1);
+    let bench = Bench::new(&users, 1_000);
+    let vault = bench.vault()?;
+
+    let gas = bench.measure(|| vault.bond().with_funds(&coins(1_000, OSMO)).call(&users[0]))?;
+    report.record("bond", "bond", gas);
+
+    Ok(())
+}
+
+/// Remote staking with an account already holding `count - 1` liens
+fn liens(report: &mut Report, count: usize) -> AnyResult<()> {
+    let users = users(1);
+    let user = &users[0];
+    let validator = "validator".to_owned();
+    let bench = Bench::new(&users, 1_000);
+    let vault = bench.vault()?;
+    vault.bond().with_funds(&coins(1_000, OSMO)).call(user)?;
+
+    let contracts = (0..count)
+        .map(|_| bench.external_staking(&vault, &[validator.clone()]))
+        .collect::<AnyResult<Vec<_>>>()?;
+    let (last, rest) = contracts.split_last().expect("at least one lien");
+    for contract in rest {
+        stake(&vault, contract, user, &validator, 100)?;
+    }
+
+    let msg = ReceiveVirtualStake::new(&validator).encode()?;
+    let gas = bench.measure(|| {
+        vault
+            .stake_remote(last.contract_addr.to_string(), coin(100, OSMO), msg)
+            .call(user)
+    })?;
+    report.record(&format!("{} liens", count), "stake_remote", gas);
+
+    let gas = bench.measure(|| commit_last_stake(last))?;
+    report.record(&format!("{} liens", count), "commit_stake", gas);
+
+    Ok(())
+}
+
+/// Rewards distribution to a validator staked on by `count` users
+fn rewards(report: &mut Report, count: usize) -> AnyResult<()> {
+    let users = users(count);
+    let validator = "validator".to_owned();
+    let bench = Bench::new(&users, 100);
+    let vault = bench.vault()?;
+    let contract = bench.external_staking(&vault, &[validator.clone()])?;
+
+    for user in &users {
+        vault.bond().with_funds(&coins(100, OSMO)).call(user)?;
+        stake(&vault, &contract, user, &validator, 100)?;
+    }
+
+    let scenario = format!("rewards to {} users", count);
+    let gas = bench.measure(|| {
+        contract
+            .test_distribute_rewards(validator.clone(), coin(count as u128 * 1_000, STAR))
+            .call("test")
+    })?;
+    report.record(&scenario, "distribute_rewards", gas);
+
+    let gas = bench.measure(|| {
+        contract
+            .withdraw_rewards(validator.clone(), "star-user".to_owned())
+            .call(&users[0])
+    })?;
+    report.record(&scenario, "withdraw_rewards", gas);
+
+    Ok(())
+}
+
+/// Slashing a validator staked on by `count` users
+fn slashing(report: &mut Report, count: usize) -> AnyResult<()> {
+    let users = users(count);
+    let validator = "validator".to_owned();
+    let bench = Bench::new(&users, 100);
+    let vault = bench.vault()?;
+    let contract = bench.external_staking(&vault, &[validator.clone()])?;
+
+    for user in &users {
+        vault.bond().with_funds(&coins(100, OSMO)).call(user)?;
+        stake(&vault, &contract, user, &validator, 100)?;
+    }
+
+    let gas = bench.measure(|| {
+        contract
+            .test_handle_slashing(validator.clone(), Uint128::new(count as u128 * 10))
+            .call("test")
+    })?;
+    report.record(&format!("slash {} stakes", count), "handle_slashing", gas);
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use cosmwasm_std::{MemoryStorage, Order, Record, Storage};
+
+// Cosmos SDK `KVGasConfig` costs, charged for every contract storage access on chain
+pub const HAS_COST: u64 = 1000;
+pub const DELETE_COST: u64 = 1000;
+pub const READ_COST_FLAT: u64 = 1000;
+pub const READ_COST_PER_BYTE: u64 = 3;
+pub const WRITE_COST_FLAT: u64 = 2000;
+pub const WRITE_COST_PER_BYTE: u64 = 30;
+pub const ITER_NEXT_COST_FLAT: u64 = 30;
+
+/// Gas counter shared between the storage and the benchmark runner
+#[derive(Clone, Debug, Default)]
+pub struct GasMeter(Rc<Cell<u64>>);
+
+impl GasMeter {
+    pub fn consume(&self, gas: u64) {
+        self.0.set(self.0.get().saturating_add(gas));
+    }
+
+    pub fn consumed(&self) -> u64 {
+        self.0.get()
+    }
+
+    pub fn reset(&self) {
+        self.0.set(0);
+    }
+}
+
+/// In-memory storage charging storage gas the way the SDK `GasKVStore` does.
+///
+/// Only storage access is metered, so the numbers are a lower bound of the real gas usage,
+/// missing Wasm execution and tx overhead. They are stable, though, which is what matters for
+/// catching regressions.
+#[derive(Default)]
+pub struct MeteredStorage {
+    inner: MemoryStorage,
+    meter: GasMeter,
+}
+
+impl MeteredStorage {
+    pub fn new(meter: GasMeter) -> Self {
+        Self {
+            inner: MemoryStorage::new(),
+            meter,
+        }
+    }
+}
+
+impl Storage for MeteredStorage {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let value = self.inner.get(key);
+        let bytes = key.len() + value.as_ref().map(Vec::len).unwrap_or_default();
+        self.meter
+            .consume(READ_COST_FLAT + READ_COST_PER_BYTE * bytes as u64);
+        value
+    }
+
+    fn range<'a>(
+        &'a self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        let meter = self.meter.clone();
+        // Creating the iterator costs as much as checking for a key
+        meter.consume(HAS_COST);
+        Box::new(
+            self.inner
+                .range(start, end, order)
+                .map(move |(key, value)| {
+                    let bytes = key.len() + value.len();
+                    meter.consume(ITER_NEXT_COST_FLAT + READ_COST_PER_BYTE * bytes as u64);
+                    (key, value)
+                }),
+        )
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        let bytes = key.len() + value.len();
+        self.meter
+            .consume(WRITE_COST_FLAT + WRITE_COST_PER_BYTE * bytes as u64);
+        self.inner.set(key, value)
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.meter.consume(DELETE_COST);
+        self.inner.remove(key)
+    }
+}
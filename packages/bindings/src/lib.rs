@@ -1,10 +1,12 @@
 mod msg;
 mod query;
 
-pub use msg::{VirtualStakeCustomMsg, VirtualStakeMsg};
+pub use msg::{
+    ProviderCustomMsg, VaultMsg, VestingMetadata, VirtualStakeCustomMsg, VirtualStakeMsg,
+};
 pub use query::{
-    BondStatusResponse, SlashRatioResponse, TokenQuerier, VirtualStakeCustomQuery,
-    VirtualStakeQuery,
+    BondStatusResponse, MintedByContractResponse, SlashRatioResponse, TokenQuerier,
+    TotalMintedResponse, VirtualStakeCustomQuery, VirtualStakeQuery,
 };
 
 // This is a signal, such that any contract that imports these helpers
@@ -63,3 +63,71 @@ impl From<VirtualStakeMsg> for CosmosMsg<VirtualStakeCustomMsg> {
 }
 
 impl CustomMsg for VirtualStakeCustomMsg {}
+
+/// A top-level Custom message for the meshsecurityprovider module, used by the vault on the
+/// provider chains having it.
+#[cw_serde]
+pub enum ProviderCustomMsg {
+    Vault(VaultMsg),
+}
+
+/// Special messages to be supported by any chain that supports the meshsecurityprovider module
+#[cw_serde]
+pub enum VaultMsg {
+    /// Bond moves amount.amount tokens from the caller (i.e. the vault) account to the module,
+    /// where they are kept as collateral of the delegator.
+    /// It ensures amount.denom is the native staking denom.
+    Bond { delegator: String, amount: Coin },
+    /// Unbond moves amount.amount tokens of the delegator collateral from the module back to the
+    /// caller account.
+    /// It ensures the delegator has at least amount.amount tokens bonded by the caller.
+    ///
+    /// With vesting metadata, the tokens were bonded by the module from the delegator vesting
+    /// account, and are returned to it instead of the caller, still vesting.
+    Unbond {
+        delegator: String,
+        amount: Coin,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        vesting: Option<VestingMetadata>,
+    },
+}
+
+/// Vesting collateral handed back to its vesting account by an unbond
+#[cw_serde]
+pub struct VestingMetadata {
+    /// Vesting collateral of the delegator still bonded by the caller after the unbond
+    pub remaining: Uint128,
+}
+
+impl VaultMsg {
+    pub fn bond(delegator: &str, amount: Coin) -> VaultMsg {
+        VaultMsg::Bond {
+            delegator: delegator.to_string(),
+            amount,
+        }
+    }
+
+    pub fn unbond(delegator: &str, amount: Coin) -> VaultMsg {
+        VaultMsg::Unbond {
+            delegator: delegator.to_string(),
+            amount,
+            vesting: None,
+        }
+    }
+
+    pub fn return_vesting(delegator: &str, amount: Coin, remaining: Uint128) -> VaultMsg {
+        VaultMsg::Unbond {
+            delegator: delegator.to_string(),
+            amount,
+            vesting: Some(VestingMetadata { remaining }),
+        }
+    }
+}
+
+impl From<VaultMsg> for CosmosMsg<ProviderCustomMsg> {
+    fn from(msg: VaultMsg) -> CosmosMsg<ProviderCustomMsg> {
+        CosmosMsg::Custom(ProviderCustomMsg::Vault(msg))
+    }
+}
+
+impl CustomMsg for ProviderCustomMsg {}
@@ -18,6 +18,17 @@ pub enum VirtualStakeQuery {
     #[returns(BondStatusResponse)]
     BondStatus { contract: String },
 
+    /// Returns the virtual stake minted by all the contracts, and the chain-level cap on it,
+    /// if any.
+    #[returns(TotalMintedResponse)]
+    TotalMinted {},
+
+    /// Returns the virtual stake minted by the given contract.
+    /// If the contract has never been authorized for virtual staking,
+    /// it will return zero rather than an error.
+    #[returns(MintedByContractResponse)]
+    MintedByContract { contract: String },
+
     /// Returns the blockchain's slashing ratios.
     #[returns(SlashRatioResponse)]
     SlashRatio {},
@@ -28,12 +39,31 @@ pub enum VirtualStakeQuery {
 pub struct BondStatusResponse {
     /// Maximum number of tokens than can be minted by this address.
     /// denom is always the native staking token.
+    /// Capped by what the other contracts left of the chain-level cap, if any.
     pub cap: Coin,
     /// Number of tokens than already have been minted by this address.
     /// Trying to mint more than (cap - currently_minted) will fail.
     pub delegated: Coin,
 }
 
+/// Virtual stake minted over the chain
+#[cw_serde]
+pub struct TotalMintedResponse {
+    /// Number of tokens minted by all the contracts.
+    /// denom is always the native staking token.
+    pub minted: Coin,
+    /// Maximum number of tokens than can be minted by all the contracts together, if capped.
+    pub cap: Option<Coin>,
+}
+
+/// Virtual stake minted by a single contract
+#[cw_serde]
+pub struct MintedByContractResponse {
+    /// Number of tokens minted by the contract.
+    /// denom is always the native staking token.
+    pub minted: Coin,
+}
+
 #[cw_serde]
 pub struct SlashRatioResponse {
     /// Slash ratio due to downtime. Used for temporary jailing.
@@ -65,6 +95,16 @@ impl<'a> TokenQuerier<'a> {
         self.querier.query(&bond_status_query.into())
     }
 
+    pub fn total_minted(&self) -> StdResult<TotalMintedResponse> {
+        let total_minted_query = VirtualStakeQuery::TotalMinted {};
+        self.querier.query(&total_minted_query.into())
+    }
+
+    pub fn minted_by_contract(&self, contract: String) -> StdResult<MintedByContractResponse> {
+        let minted_by_contract_query = VirtualStakeQuery::MintedByContract { contract };
+        self.querier.query(&minted_by_contract_query.into())
+    }
+
     pub fn slash_ratio(&self) -> StdResult<SlashRatioResponse> {
         let slash_ratio_query = VirtualStakeQuery::SlashRatio {};
         self.querier.query(&slash_ratio_query.into())
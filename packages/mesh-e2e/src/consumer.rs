@@ -0,0 +1,108 @@
+use anyhow::Result as AnyResult;
+use cosmwasm_std::{coin, coins, Addr, Decimal};
+use cw_multi_test::{no_init, AppBuilder, BankSudo, SudoMsg};
+use mesh_apis::ibc::ConsumerPacket;
+use mesh_converter::contract::sv::mt::{CodeId as ConverterCodeId, ConverterContractProxy};
+use mesh_converter::contract::ConverterContract;
+use mesh_simple_price_feed::contract::sv::mt::CodeId as PriceFeedCodeId;
+use mesh_simple_price_feed::contract::SimplePriceFeedContract;
+use mesh_virtual_staking::contract::sv::mt::CodeId as VirtualStakingCodeId;
+use mesh_virtual_staking::contract::VirtualStakingContract;
+use mesh_virtual_staking_mock::VirtualStakingModule;
+use sylvia::multitest::{App, Proxy};
+
+/// Staking denom of the consumer chain, the bonded denom of the multitest staking module.
+/// Rewards are paid in it
+pub const CONSUMER_DENOM: &str = "TOKEN";
+
+pub type ConsumerApp = mesh_virtual_staking_mock::App;
+
+/// Contracts of the consumer chain
+pub struct Consumer<'app> {
+    pub app: &'app App<ConsumerApp>,
+    pub price_feed: Proxy<'app, ConsumerApp, SimplePriceFeedContract<'app>>,
+    pub converter: Proxy<'app, ConsumerApp, ConverterContract<'app>>,
+    pub virtual_staking: Proxy<'app, ConsumerApp, VirtualStakingContract<'app>>,
+}
+
+/// Pricing of the provider tokens on the consumer
+pub struct Pricing {
+    /// Consumer tokens per provider token
+    pub native_per_foreign: Decimal,
+    /// Discount applied to the value of the provider tokens
+    pub discount: Decimal,
+}
+
+/// New consumer chain, with virtual staking handled by the mock module
+pub fn new_app() -> App<ConsumerApp> {
+    let app = AppBuilder::new_custom()
+        .with_custom(VirtualStakingModule::new())
+        .build(no_init);
+    App::new(app)
+}
+
+/// Instantiates the price feed and the converter, along with its virtual staking, owned by
+/// `owner`. `remote_denom` is the provider staking denom
+pub fn setup<'app>(
+    app: &'app App<ConsumerApp>,
+    owner: &'app str,
+    remote_denom: &str,
+    pricing: Pricing,
+) -> AnyResult<Consumer<'app>> {
+    let price_feed_code = PriceFeedCodeId::store_code(app);
+    let virtual_staking_code = VirtualStakingCodeId::store_code(app);
+    let converter_code = ConverterCodeId::store_code(app);
+
+    let price_feed = price_feed_code
+        .instantiate(pricing.native_per_foreign, None)
+        .with_label("Price Feed")
+        .call(owner)?;
+
+    let converter = converter_code
+        .instantiate(
+            price_feed.contract_addr.to_string(),
+            pricing.discount,
+            remote_denom.to_owned(),
+            virtual_staking_code.code_id(),
+            Some(owner.to_owned()),
+            None,
+            None,
+        )
+        .with_label("Converter")
+        .with_admin(owner)
+        .call(owner)?;
+
+    // Virtual staking is instantiated by the converter
+    let config = converter.config()?;
+    let virtual_staking = Proxy::new(Addr::unchecked(config.virtual_staking), app);
+
+    Ok(Consumer {
+        app,
+        price_feed,
+        converter,
+        virtual_staking,
+    })
+}
+
+impl Consumer<'_> {
+    /// Sends `packet` to the provider. The packet is kept in the converter outbox until relayed
+    pub fn send_packet(&self, packet: ConsumerPacket) -> AnyResult<()> {
+        self.converter
+            .test_send_packet(packet)
+            .call(self.converter.contract_addr.as_str())?;
+        Ok(())
+    }
+
+    /// Distributes `amount` rewards to the provider stakers of `validator`. The rewards are held
+    /// by the converter until withdrawn by the stakers
+    pub fn distribute_rewards(&self, validator: &str, amount: u128) -> AnyResult<()> {
+        self.app.app_mut().sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: self.converter.contract_addr.to_string(),
+            amount: coins(amount, CONSUMER_DENOM),
+        }))?;
+        self.send_packet(ConsumerPacket::Distribute {
+            validator: validator.to_owned(),
+            rewards: coin(amount, CONSUMER_DENOM),
+        })
+    }
+}
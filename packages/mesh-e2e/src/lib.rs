@@ -0,0 +1,241 @@
+//! Provider/consumer end-to-end test harness.
+//!
+//! The provider contracts (vault, native staking and external staking) and the consumer ones
+//! (converter, with its virtual staking on the mock virtual staking module) run on two separate
+//! multitest apps. The in-process `Relayer` shuttles the `ProviderPacket`s and `ConsumerPacket`s
+//! between them, and acks them back, so full cross-chain flows can be tested in Rust.
+pub mod consumer;
+// The provider chain runs the vault without the provider module custom messages
+#[cfg(not(feature = "provider-bindings"))]
+pub mod provider;
+#[cfg(not(feature = "provider-bindings"))]
+pub mod relayer;
+
+#[cfg(all(test, not(feature = "provider-bindings")))]
+mod tests {
+    use cosmwasm_std::{coin, coins, Decimal, Uint128};
+    use mesh_apis::converter_api::ValidatorSlashInfo;
+    use mesh_apis::ibc::{AddValidator, ConsumerPacket, ProviderPacket};
+    use mesh_apis::virtual_staking_api::sv::mt::VirtualStakingApiProxy;
+    use mesh_external_staking::contract::sv::mt::ExternalStakingContractProxy;
+    use mesh_external_staking::msg::ReceiveVirtualStake;
+    use mesh_sync::ValueRange;
+    use mesh_vault::contract::sv::mt::VaultContractProxy;
+
+    use crate::consumer::{self, Pricing, CONSUMER_DENOM};
+    use crate::provider::{self, PROVIDER_DENOM};
+    use crate::relayer::Relayer;
+
+    fn valset_update(height: u64, time: u64) -> ConsumerPacket {
+        ConsumerPacket::ValsetUpdate {
+            height,
+            time,
+            additions: vec![],
+            removals: vec![],
+            updated: vec![],
+            jailed: vec![],
+            unjailed: vec![],
+            tombstoned: vec![],
+            slashed: vec![],
+            uptimes: vec![],
+            chunk: None,
+        }
+    }
+
+    #[test]
+    fn stake_rewards_and_slash() {
+        let owner = "owner";
+        let user = "user";
+        let recipient = "recipient";
+        let validator = "validator";
+
+        let provider_app = provider::new_app(&[(user, &coins(300, PROVIDER_DENOM))]);
+        let consumer_app = consumer::new_app();
+        let provider = provider::setup(&provider_app, owner, CONSUMER_DENOM).unwrap();
+        let consumer = consumer::setup(
+            &consumer_app,
+            owner,
+            PROVIDER_DENOM,
+            Pricing {
+                native_per_foreign: Decimal::percent(50),
+                discount: Decimal::percent(10),
+            },
+        )
+        .unwrap();
+        let mut relayer = Relayer::new();
+
+        // The consumer validator is synced to the provider
+        let mut update = valset_update(100, 1234);
+        if let ConsumerPacket::ValsetUpdate { additions, .. } = &mut update {
+            additions.push(AddValidator::mock(validator));
+        }
+        consumer.send_packet(update).unwrap();
+        let relayed = relayer.relay_to_provider(&consumer, &provider).unwrap();
+        assert_eq!(relayed.len(), 1);
+        assert!(relayed[0].success);
+
+        // The stake is pending until acked by the consumer
+        provider
+            .vault
+            .bond()
+            .with_funds(&coins(300, PROVIDER_DENOM))
+            .call(user)
+            .unwrap();
+        provider
+            .vault
+            .stake_remote(
+                provider.external_staking.contract_addr.to_string(),
+                coin(200, PROVIDER_DENOM),
+                ReceiveVirtualStake::new(validator).encode().unwrap(),
+            )
+            .call(user)
+            .unwrap();
+        let stake = provider
+            .external_staking
+            .stake(user.to_owned(), validator.to_owned())
+            .unwrap();
+        assert_eq!(stake.stake.high(), Uint128::new(200));
+        assert_eq!(stake.stake.low(), Uint128::zero());
+
+        let relayed = relayer.relay_to_consumer(&provider, &consumer).unwrap();
+        assert_eq!(relayed.len(), 1);
+        assert!(matches!(relayed[0].packet, ProviderPacket::Stake { .. }));
+        assert!(relayed[0].success);
+
+        // Committed on the provider, and virtually staked on the consumer (200 * 0.5 * 0.9)
+        let stake = provider
+            .external_staking
+            .stake(user.to_owned(), validator.to_owned())
+            .unwrap();
+        assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(200)));
+        let status = consumer
+            .virtual_staking
+            .bond_status(validator.to_owned())
+            .unwrap();
+        assert_eq!(status.requested, Uint128::new(90));
+
+        // Rewards are distributed to the provider stakers
+        consumer.distribute_rewards(validator, 100).unwrap();
+        let relayed = relayer.relay_to_provider(&consumer, &provider).unwrap();
+        assert_eq!(relayed.len(), 1);
+        assert!(relayed[0].success);
+        let rewards = provider
+            .external_staking
+            .pending_rewards(user.to_owned(), validator.to_owned())
+            .unwrap();
+        assert_eq!(rewards.rewards, coin(100, CONSUMER_DENOM));
+
+        // And withdrawn to a consumer account
+        provider
+            .external_staking
+            .withdraw_rewards(validator.to_owned(), recipient.to_owned())
+            .call(user)
+            .unwrap();
+        let relayed = relayer.relay_to_consumer(&provider, &consumer).unwrap();
+        assert_eq!(relayed.len(), 1);
+        assert!(matches!(
+            relayed[0].packet,
+            ProviderPacket::TransferRewards { .. }
+        ));
+        assert!(relayed[0].success);
+        let balance = consumer_app
+            .app()
+            .wrap()
+            .query_balance(recipient, CONSUMER_DENOM)
+            .unwrap();
+        assert_eq!(balance.amount, Uint128::new(100));
+        let rewards = provider
+            .external_staking
+            .pending_rewards(user.to_owned(), validator.to_owned())
+            .unwrap();
+        assert_eq!(rewards.rewards, coin(0, CONSUMER_DENOM));
+
+        // The validator is slashed by 10% on the consumer
+        let mut update = valset_update(200, 2234);
+        if let ConsumerPacket::ValsetUpdate { slashed, .. } = &mut update {
+            slashed.push(ValidatorSlashInfo {
+                address: validator.to_owned(),
+                infraction_height: 150,
+                infraction_time: 1800,
+                power: 100,
+                slash_amount: coin(20, PROVIDER_DENOM),
+                slash_ratio: "0.1".to_owned(),
+            });
+        }
+        consumer.send_packet(update).unwrap();
+        relayer.relay_all(&provider, &consumer).unwrap();
+
+        // Which is propagated to the stake, and to the vault collateral
+        let stake = provider
+            .external_staking
+            .stake(user.to_owned(), validator.to_owned())
+            .unwrap();
+        assert_eq!(stake.stake, ValueRange::new_val(Uint128::new(180)));
+        let claim = provider
+            .vault
+            .claim(
+                user.to_owned(),
+                provider.external_staking.contract_addr.to_string(),
+            )
+            .unwrap();
+        assert_eq!(claim.amount, ValueRange::new_val(Uint128::new(180)));
+    }
+
+    #[test]
+    fn failed_stake_is_rolled_back() {
+        let owner = "owner";
+        let user = "user";
+
+        let provider_app = provider::new_app(&[(user, &coins(300, PROVIDER_DENOM))]);
+        let consumer_app = consumer::new_app();
+        let provider = provider::setup(&provider_app, owner, CONSUMER_DENOM).unwrap();
+        // The consumer expects another provider denom, so it rejects the stakes
+        let consumer = consumer::setup(
+            &consumer_app,
+            owner,
+            "uother",
+            Pricing {
+                native_per_foreign: Decimal::one(),
+                discount: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        let mut relayer = Relayer::new();
+
+        let mut update = valset_update(100, 1234);
+        if let ConsumerPacket::ValsetUpdate { additions, .. } = &mut update {
+            additions.push(AddValidator::mock("validator"));
+        }
+        consumer.send_packet(update).unwrap();
+
+        provider
+            .vault
+            .bond()
+            .with_funds(&coins(300, PROVIDER_DENOM))
+            .call(user)
+            .unwrap();
+        relayer.relay_all(&provider, &consumer).unwrap();
+        provider
+            .vault
+            .stake_remote(
+                provider.external_staking.contract_addr.to_string(),
+                coin(200, PROVIDER_DENOM),
+                ReceiveVirtualStake::new("validator").encode().unwrap(),
+            )
+            .call(user)
+            .unwrap();
+
+        let relayed = relayer.relay_to_consumer(&provider, &consumer).unwrap();
+        assert_eq!(relayed.len(), 1);
+        assert!(!relayed[0].success);
+
+        // The error ack rolls the stake back, on the provider and in the vault
+        let stake = provider
+            .external_staking
+            .stake(user.to_owned(), "validator".to_owned())
+            .unwrap();
+        assert_eq!(stake.stake, ValueRange::new_val(Uint128::zero()));
+        let account = provider.vault.account(user.to_owned()).unwrap();
+        assert_eq!(account.free, ValueRange::new_val(Uint128::new(300)));
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::Result as AnyResult;
+use cosmwasm_std::{to_json_binary, Addr, Coin, Decimal};
+use mesh_external_staking::contract::sv::mt::CodeId as ExternalStakingCodeId;
+use mesh_external_staking::contract::ExternalStakingContract;
+use mesh_external_staking::msg::AuthorizedEndpoint;
+use mesh_external_staking::state::SlashRatio;
+use mesh_native_staking::contract::sv::mt::CodeId as NativeStakingCodeId;
+use mesh_native_staking::contract::sv::InstantiateMsg as NativeStakingInstantiateMsg;
+use mesh_native_staking_proxy::contract::sv::mt::CodeId as NativeStakingProxyCodeId;
+use mesh_vault::contract::sv::mt::CodeId as VaultCodeId;
+use mesh_vault::contract::VaultContract;
+use mesh_vault::msg::{LocalStakingInfo, StakingInitInfo};
+use sylvia::multitest::{App, Proxy};
+
+/// Vault and staking denom of the provider chain
+pub const PROVIDER_DENOM: &str = "uosmo";
+/// Unbonding period of the external staking contract, in seconds
+pub const UNBONDING_PERIOD: u64 = 100;
+/// Slash ratio of the local (native) staking
+pub const LOCAL_SLASH_RATIO: u64 = 5;
+/// Max slash ratio of the external staking
+pub const REMOTE_SLASH_RATIO: u64 = 10;
+
+pub type ProviderApp = cw_multi_test::BasicApp;
+
+/// Contracts of the provider chain
+pub struct Provider<'app> {
+    pub app: &'app App<ProviderApp>,
+    pub vault: Proxy<'app, ProviderApp, VaultContract<'app>>,
+    pub external_staking: Proxy<'app, ProviderApp, ExternalStakingContract<'app>>,
+}
+
+/// New provider chain, with the given initial balances
+pub fn new_app(balances: &[(&str, &[Coin])]) -> App<ProviderApp> {
+    let app = ProviderApp::new(|router, _api, storage| {
+        for (addr, coins) in balances {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(*addr), coins.to_vec())
+                .unwrap();
+        }
+    });
+    App::new(app)
+}
+
+/// Instantiates the vault, with native staking as its local staking, and the external staking
+/// of the consumer, owned by `owner`. `rewards_denom` is the consumer staking denom, the
+/// rewards are paid in
+pub fn setup<'app>(
+    app: &'app App<ProviderApp>,
+    owner: &'app str,
+    rewards_denom: &str,
+) -> AnyResult<Provider<'app>> {
+    let native_staking_proxy_code = NativeStakingProxyCodeId::store_code(app);
+    let native_staking_code = NativeStakingCodeId::store_code(app);
+    let vault_code = VaultCodeId::store_code(app);
+    let external_staking_code = ExternalStakingCodeId::store_code(app);
+
+    let native_staking_instantiate = NativeStakingInstantiateMsg {
+        denom: PROVIDER_DENOM.to_owned(),
+        proxy_code_id: native_staking_proxy_code.code_id(),
+        slash_ratio_dsign: Decimal::percent(LOCAL_SLASH_RATIO),
+        slash_ratio_offline: Decimal::percent(LOCAL_SLASH_RATIO),
+        owner: None,
+    };
+    let staking_init = StakingInitInfo {
+        admin: None,
+        code_id: native_staking_code.code_id(),
+        msg: to_json_binary(&native_staking_instantiate)?,
+        label: Some("Native staking".to_owned()),
+    };
+
+    let vault = vault_code
+        .instantiate(
+            PROVIDER_DENOM.to_owned(),
+            Some(LocalStakingInfo::New(staking_init)),
+            None,
+            None,
+            None,
+        )
+        .with_label("Vault")
+        .call(owner)?;
+
+    let external_staking = external_staking_code
+        .instantiate(
+            PROVIDER_DENOM.to_owned(),
+            rewards_denom.to_owned(),
+            vault.contract_addr.to_string(),
+            UNBONDING_PERIOD,
+            AuthorizedEndpoint::new("connection-2", "wasm-consumer1converter"),
+            SlashRatio {
+                double_sign: Decimal::percent(REMOTE_SLASH_RATIO),
+                offline: Decimal::percent(REMOTE_SLASH_RATIO),
+            },
+            None,
+            0,
+            None,
+            None,
+        )
+        .with_label("External staking")
+        .call(owner)?;
+
+    Ok(Provider {
+        app,
+        vault,
+        external_staking,
+    })
+}
@@ -0,0 +1,115 @@
+use anyhow::Result as AnyResult;
+use mesh_apis::ibc::{ConsumerPacket, ProviderPacket};
+use mesh_converter::contract::sv::mt::ConverterContractProxy;
+use mesh_converter::state::OutboxStatus;
+use mesh_external_staking::test_methods::sv::mt::TestMethodsProxy;
+
+use crate::consumer::Consumer;
+use crate::provider::Provider;
+
+/// Sender of the relayed packets and acks
+pub const RELAYER: &str = "relayer";
+
+/// Packet relayed to the other chain, and whether it was acked with success
+#[derive(Clone, Debug, PartialEq)]
+pub struct Relayed<P> {
+    pub packet: P,
+    pub success: bool,
+}
+
+/// In-process relayer between the provider and the consumer chains.
+///
+/// Multitest can't handle IBC, so the packets are picked up where the contracts keep them in
+/// test code: the packets sent by the external staking, and the converter outbox. They are
+/// applied on the other chain through its test methods, and their outcome is acked back the
+/// same way. A packet failing on the other chain is acked with an error, as the IBC entry
+/// points would.
+#[derive(Default)]
+pub struct Relayer {
+    /// Number of the external staking sent packets already relayed
+    provider_relayed: usize,
+    /// Id of the last converter outbox packet relayed
+    consumer_relayed: Option<u64>,
+}
+
+impl Relayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Relays the packets sent by the provider since the last call, in order
+    pub fn relay_to_consumer(
+        &mut self,
+        provider: &Provider,
+        consumer: &Consumer,
+    ) -> AnyResult<Vec<Relayed<ProviderPacket>>> {
+        let sent = provider.external_staking.test_sent_packets()?.packets;
+        let packets: Vec<_> = sent.into_iter().skip(self.provider_relayed).collect();
+        self.provider_relayed += packets.len();
+
+        let mut relayed = vec![];
+        for packet in packets {
+            let success = consumer
+                .converter
+                .test_receive_packet(packet.clone())
+                .call(RELAYER)
+                .is_ok();
+            provider
+                .external_staking
+                .test_packet_ack(packet.clone(), success)
+                .call(RELAYER)?;
+            relayed.push(Relayed { packet, success });
+        }
+        Ok(relayed)
+    }
+
+    /// Relays the packets sent by the consumer since the last call, in order
+    pub fn relay_to_provider(
+        &mut self,
+        consumer: &Consumer,
+        provider: &Provider,
+    ) -> AnyResult<Vec<Relayed<ConsumerPacket>>> {
+        let mut packets = vec![];
+        loop {
+            let page = consumer
+                .converter
+                .outbox(self.consumer_relayed, None)?
+                .packets;
+            let Some(last) = page.last() else {
+                break;
+            };
+            self.consumer_relayed = Some(last.id);
+            packets.extend(
+                page.into_iter()
+                    .filter(|info| matches!(info.status, OutboxStatus::InFlight {}))
+                    .map(|info| info.packet),
+            );
+        }
+
+        let mut relayed = vec![];
+        for packet in packets {
+            let success = provider
+                .external_staking
+                .test_receive_packet(packet.clone())
+                .call(RELAYER)
+                .is_ok();
+            consumer
+                .converter
+                .test_packet_ack(packet.clone(), success)
+                .call(RELAYER)?;
+            relayed.push(Relayed { packet, success });
+        }
+        Ok(relayed)
+    }
+
+    /// Relays the packets both ways, until there are none left
+    pub fn relay_all(&mut self, provider: &Provider, consumer: &Consumer) -> AnyResult<()> {
+        loop {
+            let to_consumer = self.relay_to_consumer(provider, consumer)?;
+            let to_provider = self.relay_to_provider(consumer, provider)?;
+            if to_consumer.is_empty() && to_provider.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 mod locks;
+mod points_alignment;
 mod range;
 mod txs;
 
 pub use locks::{LockError, LockState, Lockable};
+pub use points_alignment::PointsAlignment;
 pub use range::{
     max_range, min_range, reduce_max_range, reduce_min_range, spread, RangeError, ValueRange,
 };
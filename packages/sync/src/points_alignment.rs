@@ -48,6 +48,14 @@ impl PointsAlignment {
     pub fn stake_decreased(&mut self, amount: Uint128, pps: Uint256) {
         self.0 += Uint256::from(amount) * pps;
     }
+
+    /// Modify points alignment due to points taken out of the owner - e.g. moved elsewhere, so
+    /// they can't be withdrawn anymore
+    ///
+    /// * points - points taken out
+    pub fn points_removed(&mut self, points: Uint256) {
+        self.0 -= points;
+    }
 }
 
 impl Default for PointsAlignment {
@@ -1,5 +1,5 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 use std::fmt::Formatter;
 
 #[cw_serde]
@@ -26,6 +26,14 @@ pub enum Tx {
         /// Remote validator
         validator: String,
     },
+    InFlightRemoteStakingBatch {
+        /// Transaction id
+        id: u64,
+        /// Associated owner
+        user: Addr,
+        /// Remote validators, along with their associated amounts
+        stakes: Vec<(String, Uint128)>,
+    },
     InFlightRemoteUnstaking {
         /// Transaction id
         id: u64,
@@ -36,6 +44,19 @@ pub enum Tx {
         /// Remote validator
         validator: String,
     },
+    /// This is stored on the provider side when cancelling unbonding tokens, re-staking them
+    InFlightCancelUnbond {
+        /// Transaction id
+        id: u64,
+        /// Associated amount
+        amount: Uint128,
+        /// Associated owner
+        user: Addr,
+        /// Remote validator
+        validator: String,
+        /// Cancelled unbonding entries, as `(amount, release_at)`, restored on rollback
+        unbonds: Vec<(Uint128, Timestamp)>,
+    },
     /// This is stored on the provider side when releasing funds
     InFlightTransferFunds {
         id: u64,
@@ -53,7 +74,9 @@ impl Tx {
         match self {
             Tx::InFlightStaking { id, .. } => *id,
             Tx::InFlightRemoteStaking { id, .. } => *id,
+            Tx::InFlightRemoteStakingBatch { id, .. } => *id,
             Tx::InFlightRemoteUnstaking { id, .. } => *id,
+            Tx::InFlightCancelUnbond { id, .. } => *id,
             Tx::InFlightTransferFunds { id, .. } => *id,
         }
     }
@@ -2,16 +2,18 @@ use anyhow::Result as AnyResult;
 use cosmwasm_std::{
     coin,
     testing::{MockApi, MockStorage},
-    to_json_binary, Addr, Api, Binary, BlockInfo, CustomQuery, Empty, Querier, QuerierWrapper,
-    Storage, Uint128,
+    to_json_binary, Addr, Api, Binary, BlockInfo, CustomQuery, Querier, QuerierWrapper, Storage,
+    Uint128,
 };
 use cw_multi_test::{AppResponse, BankKeeper, Module, WasmKeeper};
 use cw_storage_plus::{Item, Map};
 use mesh_bindings::{
-    BondStatusResponse, SlashRatioResponse, VirtualStakeCustomMsg, VirtualStakeCustomQuery,
+    BondStatusResponse, MintedByContractResponse, SlashRatioResponse, TotalMintedResponse,
+    VirtualStakeCustomMsg, VirtualStakeCustomQuery,
 };
 use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
 pub type App = cw_multi_test::App<
     BankKeeper,
@@ -21,11 +23,23 @@ pub type App = cw_multi_test::App<
     WasmKeeper<VirtualStakeCustomMsg, VirtualStakeCustomQuery>,
 >;
 
+/// Governance messages of the virtual staking module
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VirtualStakingSudo {
+    /// Sets the max cap of a virtual staking contract
+    SetCap { contract: String, cap: Uint128 },
+    /// Sets the cap on the virtual stake minted by all the contracts together, or removes it
+    SetTotalCap { cap: Option<Uint128> },
+}
+
 pub struct VirtualStakingModule {
     /// virtual-staking contract -> max cap
     caps: Map<'static, Addr, Uint128>,
     /// (virtual-staking contract, validator) -> bonded amount
     bonds: Map<'static, (Addr, Addr), Uint128>,
+    /// Cap on the amount bonded by all the contracts, if any
+    total_cap: Item<'static, Uint128>,
     slash_ratio: Item<'static, SlashRatioResponse>,
 }
 
@@ -34,6 +48,7 @@ impl VirtualStakingModule {
         Self {
             caps: Map::new("virtual_staking_caps"),
             bonds: Map::new("virtual_staking_bonds"),
+            total_cap: Item::new("virtual_staking_total_cap"),
             slash_ratio: Item::new("virtual_staking_slash_ratios"),
         }
     }
@@ -55,6 +70,32 @@ impl VirtualStakingModule {
         Ok(())
     }
 
+    pub fn set_cap(
+        &self,
+        storage: &mut dyn Storage,
+        contract: Addr,
+        cap: Uint128,
+    ) -> AnyResult<()> {
+        self.caps.save(storage, contract, &cap)?;
+        Ok(())
+    }
+
+    pub fn set_total_cap(&self, storage: &mut dyn Storage, cap: Option<Uint128>) -> AnyResult<()> {
+        match cap {
+            Some(cap) => self.total_cap.save(storage, &cap)?,
+            None => self.total_cap.remove(storage),
+        }
+        Ok(())
+    }
+
+    fn total_bonded(&self, storage: &dyn Storage) -> AnyResult<Uint128> {
+        Ok(self
+            .bonds
+            .range(storage, None, None, cosmwasm_std::Order::Ascending)
+            .map(|item| item.map(|(_, amt)| amt))
+            .sum::<Result<Uint128, _>>()?)
+    }
+
     fn bonded_for_contract(&self, storage: &dyn Storage, contract: Addr) -> AnyResult<Uint128> {
         Ok(self
             .bonds
@@ -77,7 +118,7 @@ impl Module for VirtualStakingModule {
 
     type QueryT = VirtualStakeCustomQuery;
 
-    type SudoT = Empty;
+    type SudoT = VirtualStakingSudo;
 
     fn execute<ExecC, QueryC>(
         &self,
@@ -99,6 +140,11 @@ impl Module for VirtualStakingModule {
         match msg {
             mesh_bindings::VirtualStakeMsg::Bond { amount, validator } => {
                 let all_bonded = self.bonded_for_contract(storage, sender.clone())?;
+                if let Some(total_cap) = self.total_cap.may_load(storage)? {
+                    if self.total_bonded(storage)? + amount.amount > total_cap {
+                        return Err(anyhow::anyhow!("total cap exceeded"));
+                    }
+                }
 
                 if all_bonded + amount.amount <= cap {
                     let current_bonded = self
@@ -141,18 +187,22 @@ impl Module for VirtualStakingModule {
     fn sudo<ExecC, QueryC>(
         &self,
         _api: &dyn Api,
-        _storage: &mut dyn Storage,
+        storage: &mut dyn Storage,
         _router: &dyn cw_multi_test::CosmosRouter<ExecC = ExecC, QueryC = QueryC>,
         _block: &BlockInfo,
-        _msg: Self::SudoT,
+        msg: Self::SudoT,
     ) -> AnyResult<cw_multi_test::AppResponse>
     where
         ExecC: std::fmt::Debug + Clone + PartialEq + JsonSchema + DeserializeOwned + 'static,
         QueryC: CustomQuery + DeserializeOwned + 'static,
     {
-        Err(anyhow::anyhow!(
-            "sudo not implemented for the virtual staking module"
-        ))
+        match msg {
+            VirtualStakingSudo::SetCap { contract, cap } => {
+                self.set_cap(storage, Addr::unchecked(contract), cap)?
+            }
+            VirtualStakingSudo::SetTotalCap { cap } => self.set_total_cap(storage, cap)?,
+        }
+        Ok(AppResponse::default())
     }
 
     fn query(
@@ -170,19 +220,111 @@ impl Module for VirtualStakingModule {
                 let denom =
                     QuerierWrapper::<VirtualStakeCustomQuery>::new(querier).query_bonded_denom()?;
 
-                let cap = self.caps.load(storage, Addr::unchecked(&contract))?;
+                let mut cap = self.caps.load(storage, Addr::unchecked(&contract))?;
                 let bonded = self.bonded_for_contract(storage, Addr::unchecked(contract))?;
+                // Only what the other contracts left of the total cap can be minted
+                if let Some(total_cap) = self.total_cap.may_load(storage)? {
+                    let left = total_cap.saturating_sub(self.total_bonded(storage)?);
+                    cap = cap.min(bonded + left);
+                }
 
                 to_json_binary(&BondStatusResponse {
                     cap: coin(cap.u128(), &denom),
                     delegated: coin(bonded.u128(), denom),
                 })?
             }
+            mesh_bindings::VirtualStakeQuery::TotalMinted {} => {
+                let denom =
+                    QuerierWrapper::<VirtualStakeCustomQuery>::new(querier).query_bonded_denom()?;
+
+                let minted = self.total_bonded(storage)?;
+                let cap = self.total_cap.may_load(storage)?;
+
+                to_json_binary(&TotalMintedResponse {
+                    minted: coin(minted.u128(), &denom),
+                    cap: cap.map(|cap| coin(cap.u128(), denom)),
+                })?
+            }
+            mesh_bindings::VirtualStakeQuery::MintedByContract { contract } => {
+                let denom =
+                    QuerierWrapper::<VirtualStakeCustomQuery>::new(querier).query_bonded_denom()?;
+
+                let minted = self.bonded_for_contract(storage, Addr::unchecked(contract))?;
+
+                to_json_binary(&MintedByContractResponse {
+                    minted: coin(minted.u128(), denom),
+                })?
+            }
             mesh_bindings::VirtualStakeQuery::SlashRatio {} => {
                 to_json_binary(&self.slash_ratio.load(storage)?)?
             }
         };
 
-        Ok(to_json_binary(&result)?)
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, CosmosMsg};
+    use cw_multi_test::{no_init, AppBuilder, Executor};
+    use mesh_bindings::{TokenQuerier, VirtualStakeMsg};
+
+    use super::*;
+
+    fn bond(app: &mut App, contract: &str, amount: u128) -> AnyResult<AppResponse> {
+        let msg = VirtualStakeCustomMsg::VirtualStake(VirtualStakeMsg::Bond {
+            amount: coin(amount, "TOKEN"),
+            validator: "validator".to_owned(),
+        });
+        app.execute(Addr::unchecked(contract), CosmosMsg::Custom(msg))
+    }
+
+    #[test]
+    fn total_cap() {
+        let mut app: App = AppBuilder::new_custom()
+            .with_custom(VirtualStakingModule::new())
+            .build(no_init);
+        app.init_modules(|router, _, storage| {
+            router
+                .custom
+                .set_cap(storage, Addr::unchecked("contract1"), Uint128::new(100))?;
+            router
+                .custom
+                .set_cap(storage, Addr::unchecked("contract2"), Uint128::new(100))?;
+            router
+                .custom
+                .set_total_cap(storage, Some(Uint128::new(150)))
+        })
+        .unwrap();
+
+        bond(&mut app, "contract1", 100).unwrap();
+
+        // Only what contract1 left of the total cap can be minted by contract2
+        let wrapper = app.wrap();
+        let querier = TokenQuerier::new(&wrapper);
+        let status = querier.bond_status("contract2".to_owned()).unwrap();
+        assert_eq!(status.cap, coin(50, "TOKEN"));
+        bond(&mut app, "contract2", 60).unwrap_err();
+        bond(&mut app, "contract2", 50).unwrap();
+
+        let wrapper = app.wrap();
+        let querier = TokenQuerier::new(&wrapper);
+        let total = querier.total_minted().unwrap();
+        assert_eq!(total.minted, coin(150, "TOKEN"));
+        assert_eq!(total.cap, Some(coin(150, "TOKEN")));
+        let minted = querier.minted_by_contract("contract2".to_owned()).unwrap();
+        assert_eq!(minted.minted, coin(50, "TOKEN"));
+        let minted = querier.minted_by_contract("unknown".to_owned()).unwrap();
+        assert_eq!(minted.minted, coin(0, "TOKEN"));
+
+        // Lifting the total cap leaves the contract caps only
+        app.init_modules(|router, _, storage| router.custom.set_total_cap(storage, None))
+            .unwrap();
+        let wrapper = app.wrap();
+        let querier = TokenQuerier::new(&wrapper);
+        let status = querier.bond_status("contract2".to_owned()).unwrap();
+        assert_eq!(status.cap, coin(100, "TOKEN"));
+        assert_eq!(querier.total_minted().unwrap().cap, None);
     }
 }